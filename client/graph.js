@@ -173,6 +173,9 @@ function connectWebSocket() {
             const data = JSON.parse(event.data);
             if (data.type === 'graph_diff') {
                 applyDiff(data.diff);
+                if (data.explanation) {
+                    logChange(data.explanation);
+                }
             } else if (data.type === 'full_graph') {
                 renderGraph(data.graph);
             }
@@ -800,8 +803,7 @@ function drawGraph(data) {
         .attr('dy', 4)
         .text((d) => truncateLabel(labelForNode(d), 18));
 
-    nodeEnter.append('title')
-        .text((d) => `${labelForNode(d)}\n${d.file_path || ''}`);
+    nodeEnter.append('title');
 
     const nodes = nodeEnter.merge(nodeSelection)
         .attr('class', (d) => nodeClass(d))
@@ -812,6 +814,10 @@ function drawGraph(data) {
             highlightNeighbors(d, currentView || data);
         });
 
+    // Hovering a folder or file tells what it's for, once summarized
+    nodes.select('title')
+        .text((d) => nodeTooltip(d));
+
     svg.on('click', () => clearHighlights());
 
     positionGraph(edges, nodes);
@@ -822,6 +828,15 @@ function drawGraph(data) {
     }
 }
 
+function nodeTooltip(node) {
+    const summary = getAiSummary(node);
+    const lines = [labelForNode(node), node.file_path || ''];
+    if (summary) {
+        lines.push('', summary);
+    }
+    return lines.join('\n');
+}
+
 function nodeClass(node) {
     const kind = displayKindKey(node) || 'unknown';
     const state = typeof node.expanded === 'boolean' ? (node.expanded ? 'is-expanded' : 'is-collapsed') : '';
@@ -1067,12 +1082,30 @@ function getBounds(nodes) {
     return { minX, maxX, minY, maxY };
 }
 
+// Node whose details are shown, so a late answer for another is dropped
+let detailsNodeId = null;
+
 function showNodeDetails(node) {
     const details = document.getElementById('node-details');
     if (!details) {
         return;
     }
 
+    detailsNodeId = node.id;
+    renderNodeDetails(details, node);
+
+    // The server has the node in full, with its edges
+    fetch(`/api/nodes/${encodeURIComponent(node.id)}`)
+        .then((response) => (response.ok ? response.json() : null))
+        .then((detail) => {
+            if (detail && detailsNodeId === node.id) {
+                renderNodeDetails(details, { ...node, ...detail, metadata: detail.extra || node.metadata });
+            }
+        })
+        .catch(() => {});
+}
+
+function renderNodeDetails(details, node) {
     const summary = getAiSummary(node);
     const summaryBlock = summary
         ? `<div class="summary">${escapeHtml(summary)}</div>`
@@ -1087,13 +1120,46 @@ function showNodeDetails(node) {
         <div>Path: ${escapeHtml(node.file_path || 'N/A')}</div>
         ${node.language ? `<div>Language: ${escapeHtml(node.language)}</div>` : ''}
         ${node.line_start ? `<div>Lines: ${node.line_start}-${node.line_end || node.line_start}</div>` : ''}
+        ${node.signature ? `<pre>${escapeHtml(node.signature)}</pre>` : ''}
+        ${node.doc_summary ? `<div>${escapeHtml(node.doc_summary)}</div>` : ''}
         <div style="margin-top: 12px;">AI Summary:</div>
         ${summaryBlock}
+        ${buildEdgeList('Incoming', node.incoming)}
+        ${buildEdgeList('Outgoing', node.outgoing)}
         <div style="margin-top: 12px;">Metadata:</div>
         ${metadata}
     `;
 }
 
+function buildEdgeList(title, edges) {
+    if (!edges || edges.length === 0) {
+        return '';
+    }
+    const items = edges
+        .map((edge) => `<li>${escapeHtml(edge.kind)} ${escapeHtml(edge.node_name)}</li>`)
+        .join('');
+    return `<div style="margin-top: 12px;">${title} (${edges.length}):</div><ul>${items}</ul>`;
+}
+
+// Latest explanations kept in the change log
+const MAX_CHANGES = 20;
+
+function logChange(explanation) {
+    const log = document.getElementById('change-log');
+    if (!log) {
+        return;
+    }
+    log.querySelector('.empty')?.remove();
+
+    const list = log.querySelector('ol');
+    const entry = document.createElement('li');
+    entry.innerHTML = `<time>${new Date().toLocaleTimeString()}</time> ${escapeHtml(explanation)}`;
+    list.prepend(entry);
+    while (list.children.length > MAX_CHANGES) {
+        list.lastElementChild.remove();
+    }
+}
+
 function buildMetadataDisplay(metadata) {
     const cleaned = { ...metadata };
     delete cleaned.ai_summary;
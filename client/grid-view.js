@@ -174,6 +174,8 @@ function getModuleIcon(kind) {
         case 'Class': return '○';
         case 'Struct': return '□';
         case 'Interface': return '△';
+        case 'Trait': return '△';
+        case 'Union': return '□';
         default: return '📄';
     }
 }
@@ -428,7 +430,11 @@ function getRelationshipName(kind) {
         'Imports': 'imports',
         'Implements': 'implements',
         'Inherits': 'inherits from',
-        'SemanticReference': 'references'
+        'SemanticReference': 'references',
+        'References': 'references',
+        'Reexports': 're-exports',
+        'Overrides': 'overrides',
+        'TestedBy': 'tested by'
     };
     
     return names[kind] || kind;
@@ -620,6 +626,8 @@ function filterNodes(nodes) {
             case 'Function': return filters.functions;
             case 'Class':
             case 'Struct':
+            case 'Union':
+            case 'Trait':
             case 'Interface': return filters.classes;
             default: return true;
         }
@@ -48,6 +48,9 @@ function handleMessage(message) {
     switch (message.type) {
         case 'graph_diff':
             handleGraphDiff(message.diff);
+            if (message.explanation) {
+                logChange(message.explanation);
+            }
             break;
         case 'full_graph':
             handleFullGraph(message.graph);
@@ -64,11 +67,16 @@ function handleMessage(message) {
 function handleGraphDiff(diff) {
     console.log('Received graph diff:', diff);
     
-    // Apply diff to current graph
+    // Apply diff to current graph, unless it's one resent that the graph
+    // already has, and tell the server it arrived
     if (window.currentGraphData) {
+        if (diff.sequence <= (window.currentGraphData.sequence || 0)) {
+            return;
+        }
         applyDiffToGraph(window.currentGraphData, diff);
         renderGraph(window.currentGraphData);
     }
+    sendDiffAck(diff.sequence);
 }
 
 // Handle full graph data
@@ -1,10 +1,13 @@
 //! AI semantic analysis bridge for understanding code relationships
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use canopy_core::{GraphNode, GraphEdge, NodeId, EdgeKind};
+use futures_util::stream::{self, BoxStream, StreamExt};
+use crate::change::GraphChange;
+use crate::query::GraphQuery;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 /// Confidence score for AI-inferred relationships (0.0 - 1.0)
 pub type Confidence = f32;
@@ -34,6 +37,21 @@ pub enum SemanticRelationship {
     SemanticReference,
 }
 
+impl SemanticRelationship {
+    pub const ALL: [SemanticRelationship; 10] = [
+        SemanticRelationship::Calls,
+        SemanticRelationship::DependsOn,
+        SemanticRelationship::Implements,
+        SemanticRelationship::Extends,
+        SemanticRelationship::TestedBy,
+        SemanticRelationship::Uses,
+        SemanticRelationship::Configures,
+        SemanticRelationship::HandlesRoute,
+        SemanticRelationship::MigrationDepends,
+        SemanticRelationship::SemanticReference,
+    ];
+}
+
 impl From<SemanticRelationship> for EdgeKind {
     fn from(rel: SemanticRelationship) -> Self {
         match rel {
@@ -41,7 +59,7 @@ impl From<SemanticRelationship> for EdgeKind {
             SemanticRelationship::DependsOn => EdgeKind::TypeReference,
             SemanticRelationship::Implements => EdgeKind::Implements,
             SemanticRelationship::Extends => EdgeKind::Inherits,
-            SemanticRelationship::TestedBy => EdgeKind::SemanticReference,
+            SemanticRelationship::TestedBy => EdgeKind::TestedBy,
             SemanticRelationship::Uses => EdgeKind::Imports,
             SemanticRelationship::Configures => EdgeKind::ConfiguresArgument,
             SemanticRelationship::HandlesRoute => EdgeKind::RouteHandler,
@@ -64,6 +82,24 @@ pub struct AnalysisContext {
     pub imports: Vec<String>,
     /// Project-wide context (package.json, Cargo.toml, etc.)
     pub project_context: HashMap<String, String>,
+    /// Relationships reviewed before, as examples of what counts as one
+    #[serde(default)]
+    pub examples: Vec<ReviewExample>,
+    /// Source text by node: whole for the nodes analyzed, the first lines
+    /// for candidates
+    #[serde(default)]
+    pub sources: HashMap<NodeId, String>,
+}
+
+/// A reviewed relationship, given to the provider as an example
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReviewExample {
+    pub source: String,
+    pub target: String,
+    pub relationship: String,
+    pub explanation: String,
+    /// Whether the relationship was accepted, rather than rejected
+    pub accepted: bool,
 }
 
 /// Request for semantic analysis
@@ -79,6 +115,31 @@ pub struct SemanticAnalysisRequest {
     pub relationship_types: Vec<SemanticRelationship>,
 }
 
+/// Request for semantic analysis of several nodes of a file at once
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SemanticBatchRequest {
+    /// The source nodes to analyze
+    pub source_nodes: Vec<GraphNode>,
+    /// Related nodes that might have relationships
+    pub candidate_nodes: Vec<GraphNode>,
+    /// Source code context, shared by the source nodes
+    pub context: AnalysisContext,
+    /// Specific relationships to look for
+    pub relationship_types: Vec<SemanticRelationship>,
+}
+
+impl SemanticBatchRequest {
+    /// The batch as one request per source node.
+    pub fn requests(&self) -> impl Iterator<Item = SemanticAnalysisRequest> + '_ {
+        self.source_nodes.iter().map(|source| SemanticAnalysisRequest {
+            source_node: source.clone(),
+            candidate_nodes: self.candidate_nodes.clone(),
+            context: self.context.clone(),
+            relationship_types: self.relationship_types.clone(),
+        })
+    }
+}
+
 /// Result of semantic analysis
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SemanticAnalysisResult {
@@ -88,6 +149,20 @@ pub struct SemanticAnalysisResult {
     pub explanation: String,
     /// Tokens used for this analysis
     pub tokens_used: u32,
+    /// Name of the provider that made the analysis
+    #[serde(default)]
+    pub provider: String,
+    /// Tokens used by each model, for pricing
+    #[serde(default)]
+    pub usage: Vec<TokenUsage>,
+}
+
+/// Tokens a request to a model used
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub model: String,
+    pub input_tokens: u32,
+    pub output_tokens: u32,
 }
 
 /// A single inferred relationship
@@ -107,6 +182,22 @@ pub struct InferredRelationship {
     pub line_reference: Option<u32>,
 }
 
+/// What a provider's model can do, as found by its health check
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProviderCapabilities {
+    /// The model requests go to, if the provider has one
+    pub model: Option<String>,
+    /// Tokens the model takes at most, prompt and reply together, if known
+    pub max_context_tokens: Option<u32>,
+    /// Whether relationships can be reported through a tool call
+    pub supports_tools: bool,
+    /// Whether answers arrive a token at a time
+    pub supports_streaming: bool,
+}
+
+/// The tokens of an answer as the provider generates them.
+pub type TokenStream = BoxStream<'static, Result<String>>;
+
 /// AI provider trait for different LLM backends
 #[async_trait::async_trait]
 pub trait AIProvider: Send + Sync {
@@ -115,6 +206,29 @@ pub trait AIProvider: Send + Sync {
         &self,
         request: SemanticAnalysisRequest,
     ) -> Result<SemanticAnalysisResult>;
+
+    /// Analyze the relationships of several source nodes together.
+    /// Providers without a batched form analyze them one at a time.
+    async fn analyze_batch(&self, batch: SemanticBatchRequest) -> Result<SemanticAnalysisResult> {
+        let mut combined = SemanticAnalysisResult {
+            relationships: Vec::new(),
+            explanation: String::new(),
+            tokens_used: 0,
+            provider: self.name().to_string(),
+            usage: Vec::new(),
+        };
+        let mut explanations = Vec::new();
+        for request in batch.requests() {
+            let result = self.analyze_semantic_relationships(request).await?;
+            combined.relationships.extend(result.relationships);
+            combined.tokens_used += result.tokens_used;
+            combined.provider = result.provider;
+            combined.usage.extend(result.usage);
+            explanations.push(result.explanation);
+        }
+        combined.explanation = explanations.join("\n");
+        Ok(combined)
+    }
     
     /// Generate a summary of what a node does
     async fn generate_node_summary(
@@ -122,7 +236,28 @@ pub trait AIProvider: Send + Sync {
         node: &GraphNode,
         context: &AnalysisContext,
     ) -> Result<String>;
+
+    /// Describe what a file, module or directory is for, in a paragraph,
+    /// from its children and their summaries. Providers without a model
+    /// list the children.
+    async fn summarize_container(&self, node: &GraphNode, children: &[GraphNode]) -> Result<String> {
+        let mut names: Vec<&str> = children.iter().map(|c| c.name.as_str()).collect();
+        names.sort_unstable();
+        Ok(format!("{:?} {} containing {} items: {}.", node.kind, node.name, children.len(), names.join(", ")))
+    }
+
+    /// Explain a change to a file in a short paragraph for the change log.
+    /// Providers without a model list what was added, removed and edited.
+    async fn explain_change(&self, change: &GraphChange) -> Result<String> {
+        Ok(change.describe())
+    }
     
+    /// Translate a question into a query of the graph, failing if it
+    /// can't be asked of the graph
+    async fn translate_question(&self, _question: &str) -> Result<GraphQuery> {
+        anyhow::bail!("{} doesn't translate questions", self.name())
+    }
+
     /// Answer questions about the codebase
     async fn answer_code_question(
         &self,
@@ -130,7 +265,33 @@ pub trait AIProvider: Send + Sync {
         relevant_nodes: &[GraphNode],
         relevant_edges: &[GraphEdge],
     ) -> Result<String>;
+
+    /// Answer a question as [`answer_code_question`](Self::answer_code_question)
+    /// does, a token at a time as the answer is generated. Providers that
+    /// can't stream yield the whole answer at once.
+    async fn stream_code_question(
+        &self,
+        question: &str,
+        relevant_nodes: &[GraphNode],
+        relevant_edges: &[GraphEdge],
+    ) -> Result<TokenStream> {
+        let answer = self.answer_code_question(question, relevant_nodes, relevant_edges).await?;
+        Ok(stream::once(async move { Ok(answer) }).boxed())
+    }
     
+    /// Check that the provider accepts its credentials and serves its
+    /// model, and report what the model can do. Providers without an API
+    /// have nothing to check.
+    async fn health_check(&self) -> Result<ProviderCapabilities> {
+        Ok(ProviderCapabilities::default())
+    }
+
+    /// Whether the provider's requests cost money, and so count against
+    /// the budget. Providers without an API cost nothing.
+    fn is_billed(&self) -> bool {
+        true
+    }
+
     /// Get provider name
     fn name(&self) -> &str;
 }
@@ -168,8 +329,23 @@ impl AIBudget {
     }
 }
 
-/// Semantic analysis configuration
-#[derive(Debug, Clone)]
+impl Default for AIBudget {
+    fn default() -> Self {
+        Self::new(100_000)
+    }
+}
+
+/// Semantic analysis configuration, from the `[ai]` section of a
+/// project's `.canopy.toml`:
+///
+/// ```toml
+/// [ai]
+/// relationship_types = ["Calls", "DependsOn", "Uses", "TestedBy"]
+/// confidence_threshold = 0.7   # AI edges below this are dropped
+/// auto_accept_threshold = 0.95 # and from this are added without review
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
 pub struct SemanticConfig {
     /// Whether to enable AI analysis
     pub enabled: bool,
@@ -178,11 +354,19 @@ pub struct SemanticConfig {
     /// API key for the provider
     pub api_key: Option<String>,
     /// Budget configuration
+    #[serde(skip)]
     pub budget: AIBudget,
     /// Batch size for processing multiple nodes
     pub batch_size: usize,
     /// Delay between API calls to avoid rate limiting
     pub api_delay_ms: u64,
+    /// Relationships the provider is asked to look for
+    pub relationship_types: Vec<SemanticRelationship>,
+    /// Confidence below which an inferred relationship is dropped
+    pub confidence_threshold: Confidence,
+    /// Confidence from which an AI edge joins the graph without waiting
+    /// for review. Unless set, every AI edge is reviewed.
+    pub auto_accept_threshold: Option<Confidence>,
 }
 
 impl Default for SemanticConfig {
@@ -194,6 +378,54 @@ impl Default for SemanticConfig {
             budget: AIBudget::new(100_000),
             batch_size: 10,
             api_delay_ms: 1000,
+            relationship_types: vec![
+                SemanticRelationship::Calls,
+                SemanticRelationship::DependsOn,
+                SemanticRelationship::Uses,
+            ],
+            confidence_threshold: 0.7,
+            auto_accept_threshold: None,
         }
     }
+}
+
+/// The settings file holding the `[ai]` section, at the project root.
+pub const CONFIG_FILE: &str = ".canopy.toml";
+
+impl SemanticConfig {
+    /// The `[ai]` section of `text`, a `.canopy.toml`. Other sections are
+    /// left to the rest of Canopy.
+    pub fn parse(text: &str) -> Result<Self> {
+        #[derive(Deserialize)]
+        struct Sections {
+            #[serde(default)]
+            ai: SemanticConfig,
+        }
+        let config = toml::from_str::<Sections>(text)?.ai;
+        for (name, threshold) in [
+            ("confidence_threshold", Some(config.confidence_threshold)),
+            ("auto_accept_threshold", config.auto_accept_threshold),
+        ] {
+            if let Some(threshold) = threshold.filter(|t| !(0.0..=1.0).contains(t)) {
+                anyhow::bail!("{} must be between 0 and 1, not {}", name, threshold);
+            }
+        }
+        Ok(config)
+    }
+
+    /// The settings of the project at `root`, or the defaults if it has no
+    /// settings file.
+    pub fn load(root: &Path) -> Result<Self> {
+        let path = root.join(CONFIG_FILE);
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        let text = std::fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("parsing {}", path.display()))
+    }
+
+    /// Whether an AI edge of `confidence` skips review.
+    pub fn auto_accepts(&self, confidence: Confidence) -> bool {
+        self.auto_accept_threshold.is_some_and(|threshold| confidence >= threshold)
+    }
 }
\ No newline at end of file
@@ -1,6 +1,70 @@
 //! Budget tracking for AI API usage
+//!
+//! Besides tokens, a [`Budget`] tracks what they cost: each model's input
+//! and output tokens are priced per million, and crossing one of the
+//! [`spend_alerts`](Budget::spend_alerts) logs a warning.
+//!
+//! Prompts are measured in tokens by the tokenizer of OpenAI's current
+//! models. Other vendors split text a little differently, but far closer
+//! to it than to any count of characters.
 
-use super::bridge::Confidence;
+use super::bridge::{Confidence, SemanticAnalysisResult, SemanticBatchRequest, TokenUsage};
+use super::prompt::semantic_batch_prompt;
+use canopy_core::GraphNode;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Dollars per million tokens of a model.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+}
+
+impl ModelPrice {
+    pub fn cost(&self, usage: &TokenUsage) -> f64 {
+        (usage.input_tokens as f64 * self.input + usage.output_tokens as f64 * self.output) / 1_000_000.0
+    }
+}
+
+/// Prices of the providers' default models, as their APIs list them.
+pub fn default_prices() -> HashMap<String, ModelPrice> {
+    [
+        ("gpt-4o-mini", ModelPrice { input: 0.15, output: 0.60 }),
+        ("gpt-4o", ModelPrice { input: 2.50, output: 10.00 }),
+        ("anthropic/claude-3-haiku-20240307", ModelPrice { input: 0.25, output: 1.25 }),
+        ("anthropic/claude-3.5-sonnet", ModelPrice { input: 3.00, output: 15.00 }),
+        ("claude-3-5-haiku-latest", ModelPrice { input: 0.80, output: 4.00 }),
+        ("claude-3-5-sonnet-latest", ModelPrice { input: 3.00, output: 15.00 }),
+    ]
+    .into_iter()
+    .map(|(model, price)| (model.to_string(), price))
+    .collect()
+}
+
+/// Tokens allowed for the reply to a request.
+pub const REPLY_TOKENS: u32 = 500;
+
+/// The number of tokens in `text`.
+pub fn count_tokens(text: &str) -> u32 {
+    let tokens = tiktoken_rs::o200k_base_singleton().encode_ordinary(text).len();
+    u32::try_from(tokens).unwrap_or(u32::MAX)
+}
+
+/// What one model has used and cost.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ModelSpend {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cost_usd: f64,
+}
+
+/// Cumulative spend, in total and by model.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Spend {
+    pub cost_usd: f64,
+    pub models: BTreeMap<String, ModelSpend>,
+}
 
 /// Budget configuration and tracking
 #[derive(Debug, Clone)]
@@ -15,6 +79,14 @@ pub struct Budget {
     pub auto_accept_threshold: Confidence,
     /// Whether to use caching to reduce API calls
     pub enable_caching: bool,
+    /// Prices by model name. Models without one are counted at no cost.
+    pub prices: HashMap<String, ModelPrice>,
+    /// Spend, in dollars, past which a warning is logged
+    pub spend_alerts: Vec<f64>,
+    /// Spend so far
+    pub spend: Spend,
+    /// Requests not sent for lack of budget
+    pub requests_skipped: u32,
 }
 
 impl Budget {
@@ -26,17 +98,80 @@ impl Budget {
             max_tokens_per_request: 4000,
             auto_accept_threshold: 0.8,
             enable_caching: true,
+            prices: default_prices(),
+            spend_alerts: Vec::new(),
+            spend: Spend::default(),
+            requests_skipped: 0,
         }
     }
+
+    /// Price `model`'s tokens at `price`.
+    pub fn with_price(mut self, model: &str, price: ModelPrice) -> Self {
+        self.prices.insert(model.to_string(), price);
+        self
+    }
+
+    /// Warn when spend crosses each of `dollars`.
+    pub fn with_spend_alerts(mut self, dollars: Vec<f64>) -> Self {
+        self.spend_alerts = dollars;
+        self
+    }
+
+    /// Record the tokens of a request and what they cost, warning if the
+    /// total crosses an alert. Returns the request's cost.
+    pub fn record_usage(&mut self, usage: &TokenUsage) -> f64 {
+        let cost = match self.prices.get(&usage.model) {
+            Some(price) => price.cost(usage),
+            None => {
+                tracing::debug!("No price for AI model {}, counting it as free", usage.model);
+                0.0
+            }
+        };
+        self.use_tokens(usage.input_tokens.saturating_add(usage.output_tokens));
+
+        let before = self.spend.cost_usd;
+        let model = self.spend.models.entry(usage.model.clone()).or_default();
+        model.input_tokens += u64::from(usage.input_tokens);
+        model.output_tokens += u64::from(usage.output_tokens);
+        model.cost_usd += cost;
+        self.spend.cost_usd += cost;
+        for &alert in &self.spend_alerts {
+            if before < alert && self.spend.cost_usd >= alert {
+                tracing::warn!("AI spend has reached ${:.2}, past the ${:.2} alert", self.spend.cost_usd, alert);
+            }
+        }
+        cost
+    }
     
     /// Check if there's enough budget for an estimated token cost
     pub fn has_budget(&self, estimated_tokens: u32) -> bool {
-        self.tokens_used + estimated_tokens <= self.total_tokens
+        self.tokens_used.saturating_add(estimated_tokens) <= self.total_tokens
     }
     
+    /// Whether a request of about `estimated_tokens` may be sent, counting
+    /// it as skipped if not.
+    pub fn admit(&mut self, estimated_tokens: u32) -> bool {
+        let admitted = self.has_budget(estimated_tokens);
+        if !admitted {
+            self.requests_skipped += 1;
+        }
+        admitted
+    }
+
+    /// Record the tokens of a result: by model where the provider reported
+    /// them so, or else as a total.
+    pub fn record_result(&mut self, result: &SemanticAnalysisResult) {
+        if result.usage.is_empty() {
+            self.use_tokens(result.tokens_used);
+        }
+        for usage in &result.usage {
+            self.record_usage(usage);
+        }
+    }
+
     /// Record token usage
     pub fn use_tokens(&mut self, tokens: u32) {
-        self.tokens_used += tokens;
+        self.tokens_used = self.tokens_used.saturating_add(tokens);
     }
     
     /// Get remaining tokens
@@ -62,10 +197,59 @@ impl Budget {
         confidence >= self.auto_accept_threshold
     }
     
-    /// Estimate tokens for a request based on prompt length
-    /// Rough estimate: ~4 characters per token
-    pub fn estimate_tokens(prompt_length: usize) -> u32 {
-        ((prompt_length / 4) as u32).saturating_add(500) // Base overhead
+    /// Estimate tokens for a batch's request
+    pub fn estimate_batch(batch: &SemanticBatchRequest) -> u32 {
+        let prompt = semantic_batch_prompt(&batch.source_nodes, &batch.candidate_nodes, &batch.context, &batch.relationship_types);
+        Self::estimate_tokens(&prompt)
+    }
+
+    /// Estimate tokens for a request: those of its prompt, and an
+    /// allowance for the reply.
+    pub fn estimate_tokens(prompt: &str) -> u32 {
+        count_tokens(prompt).saturating_add(REPLY_TOKENS)
+    }
+}
+
+impl Budget {
+    /// Split a batch into batches whose prompts fit in
+    /// `max_tokens_per_request`, by estimate, keeping the source nodes in
+    /// order. A node too large to fit even on its own is left out.
+    pub fn plan_batches(&self, batch: SemanticBatchRequest) -> Vec<SemanticBatchRequest> {
+        let fits = |sources: &[GraphNode]| {
+            let prompt = semantic_batch_prompt(sources, &batch.candidate_nodes, &batch.context, &batch.relationship_types);
+            Self::estimate_tokens(&prompt) <= self.max_tokens_per_request
+        };
+
+        let mut planned: Vec<Vec<GraphNode>> = Vec::new();
+        let mut current: Vec<GraphNode> = Vec::new();
+        for node in &batch.source_nodes {
+            current.push(node.clone());
+            if fits(&current) {
+                continue;
+            }
+            current.pop();
+            if !current.is_empty() {
+                planned.push(std::mem::take(&mut current));
+            }
+            if fits(std::slice::from_ref(node)) {
+                current.push(node.clone());
+            } else {
+                tracing::warn!("Skipping AI analysis of {}: over {} tokens", node.qualified_name, self.max_tokens_per_request);
+            }
+        }
+        if !current.is_empty() {
+            planned.push(current);
+        }
+
+        planned
+            .into_iter()
+            .map(|source_nodes| SemanticBatchRequest {
+                source_nodes,
+                candidate_nodes: batch.candidate_nodes.clone(),
+                context: batch.context.clone(),
+                relationship_types: batch.relationship_types.clone(),
+            })
+            .collect()
     }
 }
 
@@ -1,92 +1,292 @@
 //! AI analysis cache for avoiding redundant API calls
+//!
+//! Entries are keyed by the analyzed node's stable identity (its file, kind
+//! and qualified name, as [`NodeId::new`] hashes them) and a hash of its
+//! file's content, so an edit elsewhere in the graph keeps them valid.
+//! Relationship targets are stored the same way and resolved against the
+//! current graph when read, since graph IDs change between runs.
+//!
+//! Node summaries are cached too, keyed by a hash of the node's own source
+//! rather than its file's, so editing one function keeps the summaries of
+//! the others.
+//!
+//! A cache made with [`AnalysisCache::persistent`] also keeps its entries
+//! under `.canopy/ai-cache/`, one file each, read back the first time they
+//! are asked for, so a restart doesn't pay for the same analysis again.
 
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
-use std::time::{Duration, Instant};
-use canopy_core::GraphNode;
-use super::bridge::InferredRelationship;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use canopy_core::{Graph, GraphNode, NodeId, NodeKind};
+use serde::{Deserialize, Serialize};
+use super::bridge::{Confidence, InferredRelationship, SemanticAnalysisResult, SemanticRelationship};
+
+/// Directory of a persistent cache, within `.canopy/`.
+pub const AI_CACHE_DIR: &str = "ai-cache";
+
+/// A node as it can be found again in a later run.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct NodeKey {
+    pub file_path: PathBuf,
+    pub kind: NodeKind,
+    pub qualified_name: String,
+}
+
+impl NodeKey {
+    pub fn of(node: &GraphNode) -> Self {
+        Self {
+            file_path: node.file_path.to_path_buf(),
+            kind: node.kind,
+            qualified_name: node.qualified_name.to_string(),
+        }
+    }
+
+    /// The node's stable identifier.
+    pub fn stable_id(&self) -> NodeId {
+        NodeId::new(&self.file_path, self.kind, &self.qualified_name)
+    }
+
+    /// The node in `graph` this key identifies.
+    pub fn resolve(&self, graph: &Graph) -> Option<NodeId> {
+        let id = graph.find_node_by_qualified(&self.qualified_name)?;
+        let node = graph.node(id)?;
+        (node.kind == self.kind && *node.file_path == *self.file_path).then_some(id)
+    }
+}
+
+/// A cached relationship from the entry's node.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedRelationship {
+    pub target: NodeKey,
+    pub relationship: SemanticRelationship,
+    pub confidence: Confidence,
+    pub explanation: String,
+    pub line_reference: Option<u32>,
+}
 
 /// Cache entry with expiration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CacheEntry {
-    pub relationships: Vec<InferredRelationship>,
-    pub timestamp: Instant,
+    pub relationships: Vec<CachedRelationship>,
+    /// Name of the provider that made the analysis
+    #[serde(default)]
+    pub provider: String,
+    pub timestamp: SystemTime,
     pub ttl: Duration,
 }
 
 impl CacheEntry {
     pub fn is_expired(&self) -> bool {
-        self.timestamp.elapsed() > self.ttl
+        is_expired(self.timestamp, self.ttl)
     }
 }
 
+/// A cached node summary
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SummaryEntry {
+    pub summary: String,
+    pub timestamp: SystemTime,
+    pub ttl: Duration,
+}
+
+/// When any persisted entry was made, and for how long it holds.
+#[derive(Deserialize)]
+struct Stamp {
+    timestamp: SystemTime,
+    ttl: Duration,
+}
+
+fn is_expired(timestamp: SystemTime, ttl: Duration) -> bool {
+    // A clock set back leaves entries as they were
+    timestamp.elapsed().is_ok_and(|age| age > ttl)
+}
+
 /// Cache for semantic analysis results
 pub struct AnalysisCache {
     entries: HashMap<CacheKey, CacheEntry>,
+    summaries: HashMap<CacheKey, SummaryEntry>,
     default_ttl: Duration,
+    /// Where entries are persisted, if they are.
+    dir: Option<PathBuf>,
 }
 
 /// Key for cache lookups
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 struct CacheKey {
     source_node_id: u64,
     file_hash: u64,
 }
 
+impl CacheKey {
+    fn new(source_node: &GraphNode, file_hash: u64) -> Self {
+        Self { source_node_id: NodeKey::of(source_node).stable_id().0, file_hash }
+    }
+
+    fn file_name(&self) -> String {
+        format!("{:016x}-{:016x}.json", self.source_node_id, self.file_hash)
+    }
+
+    fn summary_file_name(&self) -> String {
+        format!("{:016x}-{:016x}.summary.json", self.source_node_id, self.file_hash)
+    }
+}
+
 impl AnalysisCache {
     pub fn new(default_ttl: Duration) -> Self {
         Self {
             entries: HashMap::new(),
+            summaries: HashMap::new(),
             default_ttl,
+            dir: None,
         }
     }
-    
-    /// Get cached analysis result if available and not expired
-    pub fn get(&self, source_node: &GraphNode, file_content_hash: u64) -> Option<&CacheEntry> {
-        let key = CacheKey {
-            source_node_id: source_node.id.0,
-            file_hash: file_content_hash,
+
+    /// A cache that also keeps its entries under the `.canopy/ai-cache/`
+    /// directory of the project at `root`.
+    pub fn persistent(root: &Path, default_ttl: Duration) -> Self {
+        Self {
+            dir: Some(canopy_core::cache::cache_dir(root).join(AI_CACHE_DIR)),
+            ..Self::new(default_ttl)
+        }
+    }
+
+    /// The entry persisted as `name`, if there is a readable one.
+    fn load<T: serde::de::DeserializeOwned>(&self, name: &str) -> Option<T> {
+        let text = std::fs::read(self.dir.as_ref()?.join(name)).ok()?;
+        serde_json::from_slice(&text).ok()
+    }
+
+    fn save<T: Serialize>(&self, name: &str, entry: &T) -> anyhow::Result<()> {
+        let Some(dir) = &self.dir else {
+            return Ok(());
         };
-        
-        self.entries.get(&key).filter(|entry| !entry.is_expired())
+        std::fs::create_dir_all(dir)?;
+        std::fs::write(dir.join(name), serde_json::to_vec(entry)?)?;
+        Ok(())
     }
-    
-    /// Store analysis result in cache
+
+    /// Get the cached analysis of `source_node` if available and not
+    /// expired, with its targets as found in `graph`. Targets no longer in
+    /// the graph are left out.
+    pub fn get(&mut self, source_node: &GraphNode, file_content_hash: u64, graph: &Graph) -> Option<SemanticAnalysisResult> {
+        let key = CacheKey::new(source_node, file_content_hash);
+        if !self.entries.contains_key(&key) {
+            let entry = self.load(&key.file_name())?;
+            self.entries.insert(key, entry);
+        }
+        let entry = self.entries.get(&key).filter(|entry| !entry.is_expired())?;
+        let relationships = entry.relationships.iter()
+            .filter_map(|rel| Some(InferredRelationship {
+                source_id: source_node.id,
+                target_id: rel.target.resolve(graph)?,
+                relationship: rel.relationship,
+                confidence: rel.confidence,
+                explanation: rel.explanation.clone(),
+                line_reference: rel.line_reference,
+            }))
+            .collect();
+        Some(SemanticAnalysisResult {
+            relationships,
+            explanation: String::new(),
+            tokens_used: 0,
+            provider: entry.provider.clone(),
+            usage: Vec::new(),
+        })
+    }
+
+    /// Store the relationships `provider` inferred for `source_node`, whose
+    /// targets are in `graph`, in the cache
     pub fn insert(
         &mut self,
         source_node: &GraphNode,
         file_content_hash: u64,
-        relationships: Vec<InferredRelationship>,
+        relationships: &[&InferredRelationship],
+        provider: &str,
+        graph: &Graph,
     ) {
-        let key = CacheKey {
-            source_node_id: source_node.id.0,
-            file_hash: file_content_hash,
-        };
-        
+        let key = CacheKey::new(source_node, file_content_hash);
         let entry = CacheEntry {
-            relationships,
-            timestamp: Instant::now(),
+            relationships: relationships.iter()
+                .filter_map(|rel| Some(CachedRelationship {
+                    target: NodeKey::of(graph.node(rel.target_id)?),
+                    relationship: rel.relationship,
+                    confidence: rel.confidence,
+                    explanation: rel.explanation.clone(),
+                    line_reference: rel.line_reference,
+                }))
+                .collect(),
+            provider: provider.to_string(),
+            timestamp: SystemTime::now(),
             ttl: self.default_ttl,
         };
-        
+
+        if let Err(e) = self.save(&key.file_name(), &entry) {
+            tracing::warn!("Failed to persist AI analysis of {}: {}", source_node.qualified_name, e);
+        }
         self.entries.insert(key, entry);
     }
-    
-    /// Clear expired entries
+
+    /// Get the summary cached for `source_node` if its source still hashes
+    /// to `source_hash` and the summary hasn't expired
+    pub fn get_summary(&mut self, source_node: &GraphNode, source_hash: u64) -> Option<String> {
+        let key = CacheKey::new(source_node, source_hash);
+        if !self.summaries.contains_key(&key) {
+            let entry = self.load(&key.summary_file_name())?;
+            self.summaries.insert(key, entry);
+        }
+        let entry = self.summaries.get(&key)?;
+        (!is_expired(entry.timestamp, entry.ttl)).then(|| entry.summary.clone())
+    }
+
+    /// Store the summary of `source_node`, whose source hashes to
+    /// `source_hash`, in the cache
+    pub fn insert_summary(&mut self, source_node: &GraphNode, source_hash: u64, summary: &str) {
+        let key = CacheKey::new(source_node, source_hash);
+        let entry = SummaryEntry {
+            summary: summary.to_string(),
+            timestamp: SystemTime::now(),
+            ttl: self.default_ttl,
+        };
+        if let Err(e) = self.save(&key.summary_file_name(), &entry) {
+            tracing::warn!("Failed to persist AI summary of {}: {}", source_node.qualified_name, e);
+        }
+        self.summaries.insert(key, entry);
+    }
+
+    /// Clear expired entries, persisted ones included
     pub fn cleanup_expired(&mut self) {
         self.entries.retain(|_, entry| !entry.is_expired());
+        self.summaries.retain(|_, entry| !is_expired(entry.timestamp, entry.ttl));
+        let Some(files) = self.dir.as_ref().and_then(|dir| std::fs::read_dir(dir).ok()) else {
+            return;
+        };
+        for path in files.flatten().map(|file| file.path()) {
+            let expired = std::fs::read(&path)
+                .ok()
+                .and_then(|text| serde_json::from_slice::<Stamp>(&text).ok())
+                .is_none_or(|stamp| is_expired(stamp.timestamp, stamp.ttl));
+            if expired {
+                let _ = std::fs::remove_file(&path);
+            }
+        }
     }
-    
-    /// Clear all entries
+
+    /// Clear all entries, persisted ones included
     pub fn clear(&mut self) {
         self.entries.clear();
+        self.summaries.clear();
+        if let Some(dir) = &self.dir {
+            let _ = std::fs::remove_dir_all(dir);
+        }
     }
-    
-    /// Get cache statistics
+
+    /// Get statistics of the entries loaded so far
     pub fn stats(&self) -> CacheStats {
         CacheStats {
             total_entries: self.entries.len(),
             expired_entries: self.entries.values().filter(|e| e.is_expired()).count(),
+            summaries: self.summaries.len(),
         }
     }
 }
@@ -96,6 +296,7 @@ impl AnalysisCache {
 pub struct CacheStats {
     pub total_entries: usize,
     pub expired_entries: usize,
+    pub summaries: usize,
 }
 
 /// Compute a simple hash of file content for cache invalidation
@@ -104,4 +305,4 @@ pub fn compute_content_hash(content: &str) -> u64 {
     let mut hasher = DefaultHasher::new();
     content.hash(&mut hasher);
     hasher.finish()
-}
\ No newline at end of file
+}
@@ -0,0 +1,135 @@
+//! Confidence calibrated by review decisions
+//!
+//! A provider's 0.8 means 80% only if four in five of the edges it gives
+//! 0.8 are accepted. [`Calibration`] compares the confidence each provider
+//! gave the edges reviewed, by relationship, with how many of them were
+//! accepted, and scales the confidence of its later edges to match: a
+//! provider whose edges are accepted half as often as it claims has its
+//! confidence halved. A handful of decisions moves it little; the
+//! provider's own confidence counts for [`PRIOR_DECISIONS`] of them.
+
+use crate::bridge::Confidence;
+use crate::review::Decision;
+use crate::review::Verdict;
+use canopy_core::EdgeKind;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// How many decisions the provider's own confidence is worth.
+pub const PRIOR_DECISIONS: f32 = 5.0;
+
+/// Confidence bins of the stats, each a tenth wide.
+const BINS: usize = 10;
+
+/// Decisions on edges a provider gave confidences in one range.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CalibrationBin {
+    pub min_confidence: Confidence,
+    pub max_confidence: Confidence,
+    pub decisions: usize,
+    /// Share of them accepted.
+    pub accept_rate: f32,
+}
+
+/// How a provider's confidence in one kind of relationship held up in
+/// review.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CalibrationStats {
+    pub provider: String,
+    pub kind: EdgeKind,
+    pub decisions: usize,
+    pub accepted: usize,
+    /// Mean confidence the provider gave the edges decided.
+    pub mean_confidence: Confidence,
+    /// Share of them accepted.
+    pub accept_rate: f32,
+    /// What the provider's later confidences are multiplied by.
+    pub adjustment: f32,
+    /// The decisions by the confidence given, in tenths, leaving out
+    /// those with none.
+    pub bins: Vec<CalibrationBin>,
+}
+
+/// Adjustments of confidence by provider and relationship, from review
+/// decisions.
+#[derive(Debug, Clone, Default)]
+pub struct Calibration {
+    stats: HashMap<(String, EdgeKind), CalibrationStats>,
+}
+
+impl Calibration {
+    /// The calibration `decisions` make for. Decisions without the
+    /// provider's confidence, made before it was kept, are left out.
+    pub fn from_decisions(decisions: &[Decision]) -> Self {
+        let mut outcomes: HashMap<(String, EdgeKind), Vec<(Confidence, bool)>> = HashMap::new();
+        for decision in decisions {
+            if let Some(inference) = &decision.inference {
+                outcomes
+                    .entry((inference.provider.clone(), decision.kind))
+                    .or_default()
+                    .push((inference.confidence.clamp(0.0, 1.0), decision.verdict == Verdict::Accepted));
+            }
+        }
+        let stats = outcomes.into_iter().map(|(key, outcomes)| {
+            let stats = stats(&key.0, key.1, &outcomes);
+            (key, stats)
+        });
+        Self { stats: stats.collect() }
+    }
+
+    /// `confidence` given by `provider` to a `kind` edge, calibrated.
+    pub fn adjust(&self, provider: &str, kind: EdgeKind, confidence: Confidence) -> Confidence {
+        match self.stats.get(&(provider.to_string(), kind)) {
+            Some(stats) => (confidence * stats.adjustment).clamp(0.0, 1.0),
+            None => confidence,
+        }
+    }
+
+    /// The stats of each provider and relationship decided on, by provider
+    /// and then relationship.
+    pub fn stats(&self) -> Vec<CalibrationStats> {
+        let mut stats: Vec<CalibrationStats> = self.stats.values().cloned().collect();
+        stats.sort_by_cached_key(|s| (s.provider.clone(), format!("{:?}", s.kind)));
+        stats
+    }
+}
+
+fn stats(provider: &str, kind: EdgeKind, outcomes: &[(Confidence, bool)]) -> CalibrationStats {
+    let decisions = outcomes.len();
+    let accepted = outcomes.iter().filter(|(_, accepted)| *accepted).count();
+    let mean_confidence = outcomes.iter().map(|(confidence, _)| confidence).sum::<f32>() / decisions as f32;
+
+    // The accept rate, drawn toward the provider's own confidence while
+    // there are few decisions
+    let expected = (accepted as f32 + PRIOR_DECISIONS * mean_confidence) / (decisions as f32 + PRIOR_DECISIONS);
+    let adjustment = if mean_confidence > 0.0 { expected / mean_confidence } else { 1.0 };
+
+    let mut bins = vec![(0, 0); BINS];
+    for &(confidence, accepted) in outcomes {
+        let bin = ((confidence * BINS as f32) as usize).min(BINS - 1);
+        bins[bin].0 += 1;
+        bins[bin].1 += usize::from(accepted);
+    }
+    let bins = bins
+        .into_iter()
+        .enumerate()
+        .filter(|(_, (decisions, _))| *decisions > 0)
+        .map(|(bin, (decisions, accepted))| CalibrationBin {
+            min_confidence: bin as f32 / BINS as f32,
+            max_confidence: (bin + 1) as f32 / BINS as f32,
+            decisions,
+            accept_rate: accepted as f32 / decisions as f32,
+        })
+        .collect();
+
+    CalibrationStats {
+        provider: provider.to_string(),
+        kind,
+        decisions,
+        accepted,
+        mean_confidence,
+        accept_rate: accepted as f32 / decisions as f32,
+        adjustment,
+        bins,
+    }
+}
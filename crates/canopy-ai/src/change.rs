@@ -0,0 +1,168 @@
+//! Changes to the graph, for explaining
+//!
+//! Each save re-extracts a file, so its [`canopy_core::GraphDiff`] removes
+//! every node of the file and adds them back. A [`GraphChange`] compares
+//! the file's nodes and edges before and after by name instead, leaving
+//! what was added, removed or edited, along with the lines of source that
+//! changed, for [`AIProvider::explain_change`](crate::AIProvider::explain_change)
+//! to put in a sentence or two for the change log.
+
+use crate::redact::redact;
+use canopy_core::{EdgeKind, GraphNode, NodeKind};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+/// Most lines of a source diff kept for the prompt.
+pub const MAX_DIFF_LINES: usize = 200;
+
+/// An edge, by the names of the nodes it joins.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ChangedEdge {
+    pub source: String,
+    pub kind: EdgeKind,
+    pub target: String,
+}
+
+/// A file's part of the graph at one moment.
+#[derive(Debug, Clone, Default)]
+pub struct FileSnapshot {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<ChangedEdge>,
+    /// The file's source, if known.
+    pub source: Option<String>,
+}
+
+/// What a change to a file did to the graph.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GraphChange {
+    pub file_path: PathBuf,
+    pub added: Vec<GraphNode>,
+    pub removed: Vec<GraphNode>,
+    /// Nodes kept whose source changed.
+    pub modified: Vec<GraphNode>,
+    pub added_edges: Vec<ChangedEdge>,
+    pub removed_edges: Vec<ChangedEdge>,
+    /// The lines that changed, `-` before and `+` after, with secrets
+    /// redacted. Empty when the source before is unknown.
+    pub source_diff: String,
+}
+
+impl GraphChange {
+    /// The change to the file at `path` from `before` to `after`. Nodes are
+    /// matched by qualified name.
+    pub fn between(path: &Path, before: &FileSnapshot, after: &FileSnapshot) -> Self {
+        let symbols = |snapshot: &FileSnapshot| -> HashMap<String, GraphNode> {
+            snapshot
+                .nodes
+                .iter()
+                .filter(|n| !matches!(n.kind, NodeKind::File | NodeKind::Directory))
+                .map(|n| (n.qualified_name.to_string(), n.clone()))
+                .collect()
+        };
+        let old = symbols(before);
+        let new = symbols(after);
+
+        let mut change = GraphChange { file_path: path.to_path_buf(), ..Default::default() };
+        for (name, node) in &new {
+            match old.get(name) {
+                None => change.added.push(node.clone()),
+                Some(was) if source_changed(was, before, node, after) => change.modified.push(node.clone()),
+                Some(_) => {}
+            }
+        }
+        change.removed = old.into_iter().filter(|(name, _)| !new.contains_key(name)).map(|(_, n)| n).collect();
+        for nodes in [&mut change.added, &mut change.removed, &mut change.modified] {
+            nodes.sort_by_key(|n| (n.line_start, n.name.clone()));
+        }
+
+        let old_edges: HashSet<&ChangedEdge> = before.edges.iter().collect();
+        let new_edges: HashSet<&ChangedEdge> = after.edges.iter().collect();
+        change.added_edges = after.edges.iter().filter(|e| !old_edges.contains(e)).cloned().collect();
+        change.removed_edges = before.edges.iter().filter(|e| !new_edges.contains(e)).cloned().collect();
+        dedup(&mut change.added_edges);
+        dedup(&mut change.removed_edges);
+
+        if let (Some(old), Some(new)) = (&before.source, &after.source) {
+            change.source_diff = redact(path, &source_diff(old, new));
+        }
+        change
+    }
+
+    /// Whether the change added, removed or edited anything in the graph.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.modified.is_empty()
+            && self.added_edges.is_empty()
+            && self.removed_edges.is_empty()
+    }
+
+    /// The change in a sentence, for providers without a model.
+    pub fn describe(&self) -> String {
+        let names = |nodes: &[GraphNode]| nodes.iter().map(|n| n.name.as_str()).collect::<Vec<_>>().join(", ");
+        let edges = |n: usize| if n == 1 { "1 relationship".to_string() } else { format!("{} relationships", n) };
+        let mut parts = Vec::new();
+        if !self.added.is_empty() {
+            parts.push(format!("added {}", names(&self.added)));
+        }
+        if !self.removed.is_empty() {
+            parts.push(format!("removed {}", names(&self.removed)));
+        }
+        if !self.modified.is_empty() {
+            parts.push(format!("changed {}", names(&self.modified)));
+        }
+        if !self.added_edges.is_empty() {
+            parts.push(format!("{} added", edges(self.added_edges.len())));
+        }
+        if !self.removed_edges.is_empty() {
+            parts.push(format!("{} removed", edges(self.removed_edges.len())));
+        }
+        format!("{}: {}.", self.file_path.display(), parts.join("; "))
+    }
+}
+
+/// Whether the source of a node differs between the snapshots, by its
+/// text if both sources are known, or else its length.
+fn source_changed(was: &GraphNode, before: &FileSnapshot, is: &GraphNode, after: &FileSnapshot) -> bool {
+    match (node_lines(was, before), node_lines(is, after)) {
+        (Some(old), Some(new)) => old != new,
+        _ => was.line_end.zip(was.line_start).map(|(e, s)| e.saturating_sub(s)) != is.line_end.zip(is.line_start).map(|(e, s)| e.saturating_sub(s)),
+    }
+}
+
+fn node_lines<'a>(node: &GraphNode, snapshot: &'a FileSnapshot) -> Option<Vec<&'a str>> {
+    let (start, end) = (node.line_start? as usize, node.line_end? as usize);
+    let source = snapshot.source.as_deref()?;
+    Some(source.lines().skip(start.saturating_sub(1)).take(end.saturating_sub(start) + 1).collect())
+}
+
+fn dedup(edges: &mut Vec<ChangedEdge>) {
+    let mut seen = HashSet::new();
+    edges.retain(|e| seen.insert(e.clone()));
+}
+
+/// The lines between the unchanged start and end of `old` and `new`, those
+/// of `old` marked `-` and those of `new` marked `+`.
+pub fn source_diff(old: &str, new: &str) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+
+    let mut diff = String::new();
+    let removed = old[prefix..old.len() - suffix].iter().map(|line| ('-', line));
+    let added = new[prefix..new.len() - suffix].iter().map(|line| ('+', line));
+    let lines: Vec<_> = removed.chain(added).collect();
+    if !lines.is_empty() {
+        let _ = writeln!(diff, "@@ line {} @@", prefix + 1);
+    }
+    for (mark, line) in lines.iter().take(MAX_DIFF_LINES) {
+        let _ = writeln!(diff, "{}{}", mark, line);
+    }
+    if lines.len() > MAX_DIFF_LINES {
+        let _ = writeln!(diff, "... {} more lines", lines.len() - MAX_DIFF_LINES);
+    }
+    diff
+}
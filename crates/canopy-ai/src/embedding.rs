@@ -0,0 +1,388 @@
+//! Embedding index for choosing analysis candidates
+//!
+//! Every node of the graph is a possible target of a relationship, but a
+//! prompt can only hold so many. [`EmbeddingIndex`] embeds each node's name,
+//! qualified name and AI summary, and picks the candidates most similar to
+//! the nodes being analyzed.
+//!
+//! [`HashingEmbedder`] needs no model: it hashes the words of identifiers
+//! (`parse_config` and `ConfigParser` share `parse` and `config`) and their
+//! trigrams into a fixed-size vector. [`OpenAIEmbedder`] asks an
+//! OpenAI-compatible `/embeddings` endpoint instead, and, with the
+//! `local-embeddings` feature, `FastEmbedder` runs a small sentence
+//! embedding model on this machine.
+
+use crate::providers::ApiError;
+use anyhow::{Context, Result};
+use canopy_core::{GraphNode, NodeId};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+/// Dimensions of [`HashingEmbedder`] vectors.
+pub const HASHING_DIMENSIONS: usize = 256;
+
+/// Texts sent to an embedder at once. Embedding endpoints cap the inputs
+/// and tokens of a request, which a whole graph goes well over.
+pub const EMBED_BATCH_SIZE: usize = 128;
+
+/// Node metadata key holding a node's AI summary.
+const SUMMARY_KEY: &str = "ai_summary";
+
+/// Turns texts into vectors whose cosine similarity reflects how related
+/// the texts are.
+#[async_trait::async_trait]
+pub trait Embedder: Send + Sync {
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>>;
+
+    fn name(&self) -> &str;
+}
+
+/// Scale `vector` to unit length, so dot products are cosine similarities.
+fn normalize(mut vector: Vec<f32>) -> Vec<f32> {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+    vector
+}
+
+/// The lower-case words of a text, splitting identifiers at underscores,
+/// punctuation and camel-case humps.
+pub fn words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut previous: Option<char> = None;
+    for c in text.chars() {
+        let hump = c.is_uppercase() && previous.is_some_and(|p| p.is_lowercase() || p.is_ascii_digit());
+        if (!c.is_alphanumeric() || hump) && !current.is_empty() {
+            words.push(std::mem::take(&mut current));
+        }
+        if c.is_alphanumeric() {
+            current.extend(c.to_lowercase());
+        }
+        previous = Some(c);
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+/// Embeds texts locally by hashing their words and word trigrams.
+#[derive(Debug, Clone, Default)]
+pub struct HashingEmbedder;
+
+impl HashingEmbedder {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub fn embed_text(&self, text: &str) -> Vec<f32> {
+        let mut vector = vec![0.0; HASHING_DIMENSIONS];
+        let mut add = |feature: &str, weight: f32| {
+            let mut hasher = DefaultHasher::new();
+            feature.hash(&mut hasher);
+            let hash = hasher.finish();
+            // The sign bit spreads collisions out rather than piling them up
+            let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+            vector[(hash % HASHING_DIMENSIONS as u64) as usize] += sign * weight;
+        };
+        for word in words(text) {
+            add(&word, 1.0);
+            let padded: Vec<char> = format!(" {} ", word).chars().collect();
+            for trigram in padded.windows(3) {
+                add(&trigram.iter().collect::<String>(), 0.5);
+            }
+        }
+        normalize(vector)
+    }
+}
+
+#[async_trait::async_trait]
+impl Embedder for HashingEmbedder {
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        Ok(texts.iter().map(|text| self.embed_text(text)).collect())
+    }
+
+    fn name(&self) -> &str {
+        "Hashing"
+    }
+}
+
+/// Where embedding requests go unless a base URL is given.
+pub const DEFAULT_EMBEDDING_URL: &str = "https://api.openai.com/v1";
+
+/// Embeds texts with an OpenAI-compatible `/embeddings` endpoint.
+pub struct OpenAIEmbedder {
+    client: reqwest::Client,
+    api_key: String,
+    model: String,
+    base_url: String,
+    name: String,
+}
+
+#[derive(Debug, Serialize)]
+struct EmbeddingRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingData {
+    embedding: Vec<f32>,
+    index: usize,
+}
+
+impl OpenAIEmbedder {
+    pub fn new(api_key: Option<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            api_key: api_key.unwrap_or_default(),
+            model: "text-embedding-3-small".to_string(),
+            base_url: DEFAULT_EMBEDDING_URL.to_string(),
+            name: "OpenAI embeddings".to_string(),
+        }
+    }
+
+    pub fn with_model(mut self, model: String) -> Self {
+        self.model = model;
+        self
+    }
+
+    /// Send requests to the API at `base_url`, e.g. `http://localhost:1234/v1`.
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl Embedder for OpenAIEmbedder {
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut builder = self.client
+            .post(format!("{}/embeddings", self.base_url))
+            .json(&EmbeddingRequest { model: &self.model, input: texts });
+        if !self.api_key.is_empty() {
+            builder = builder.bearer_auth(&self.api_key);
+        }
+        let response = builder
+            .send()
+            .await
+            .with_context(|| format!("Failed to send request to {}", self.base_url))?;
+        if !response.status().is_success() {
+            return Err(ApiError::from_response(&self.name, response).await.into());
+        }
+        let mut response: EmbeddingResponse = response.json().await.context("Failed to parse embeddings response")?;
+        if response.data.len() != texts.len() {
+            anyhow::bail!("Asked for {} embeddings, got {}", texts.len(), response.data.len());
+        }
+        response.data.sort_by_key(|d| d.index);
+        Ok(response.data.into_iter().map(|d| normalize(d.embedding)).collect())
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Embeds texts with a small ONNX sentence embedding model,
+/// all-MiniLM-L6-v2, run locally. The model is downloaded into a cache
+/// directory the first time, and read from it after.
+#[cfg(feature = "local-embeddings")]
+pub struct FastEmbedder {
+    model: std::sync::Arc<fastembed::TextEmbedding>,
+}
+
+#[cfg(feature = "local-embeddings")]
+impl FastEmbedder {
+    /// Load the model, from `cache_dir` if it was downloaded there before.
+    pub fn new(cache_dir: &std::path::Path) -> Result<Self> {
+        let options = fastembed::InitOptions::new(fastembed::EmbeddingModel::AllMiniLML6V2)
+            .with_cache_dir(cache_dir.to_path_buf())
+            .with_show_download_progress(false);
+        let model = fastembed::TextEmbedding::try_new(options).context("Failed to load the local embedding model")?;
+        Ok(Self { model: std::sync::Arc::new(model) })
+    }
+}
+
+#[cfg(feature = "local-embeddings")]
+#[async_trait::async_trait]
+impl Embedder for FastEmbedder {
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+        // Inference takes the CPU for a while, so it stays off the runtime
+        let model = std::sync::Arc::clone(&self.model);
+        let texts = texts.to_vec();
+        let vectors = tokio::task::spawn_blocking(move || model.embed(texts, None)).await??;
+        Ok(vectors.into_iter().map(normalize).collect())
+    }
+
+    fn name(&self) -> &str {
+        "all-MiniLM-L6-v2"
+    }
+}
+
+/// What a node is embedded by: its names and, once it has one, its summary.
+pub fn node_text(node: &GraphNode) -> String {
+    let mut text = format!("{:?} {} {}", node.kind, node.name, node.qualified_name);
+    if let Some(summary) = node.metadata.extra.get(SUMMARY_KEY) {
+        text.push('\n');
+        text.push_str(summary);
+    }
+    text
+}
+
+/// The embeddings of a graph's nodes, kept up to date as the graph changes.
+pub struct EmbeddingIndex {
+    embedder: Box<dyn Embedder>,
+    /// Each node's embedding, with the text it was made from.
+    entries: HashMap<NodeId, (String, Vec<f32>)>,
+}
+
+impl Default for EmbeddingIndex {
+    fn default() -> Self {
+        Self::new(Box::new(HashingEmbedder::new()))
+    }
+}
+
+impl EmbeddingIndex {
+    pub fn new(embedder: Box<dyn Embedder>) -> Self {
+        Self { embedder, entries: HashMap::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Make the index hold exactly `nodes`, embedding those that are new or
+    /// whose text changed, [`EMBED_BATCH_SIZE`] at a time. On an error the
+    /// batches embedded before it are kept.
+    pub async fn sync(&mut self, nodes: &[GraphNode]) -> Result<()> {
+        let ids: HashSet<NodeId> = nodes.iter().map(|n| n.id).collect();
+        self.entries.retain(|id, _| ids.contains(id));
+
+        let stale: Vec<(NodeId, String)> = nodes
+            .iter()
+            .map(|node| (node.id, node_text(node)))
+            .filter(|(id, text)| self.entries.get(id).is_none_or(|(embedded, _)| embedded != text))
+            .collect();
+        for batch in stale.chunks(EMBED_BATCH_SIZE) {
+            let texts: Vec<String> = batch.iter().map(|(_, text)| text.clone()).collect();
+            let vectors = self.embedder.embed(&texts).await?;
+            for ((id, text), vector) in batch.iter().cloned().zip(vectors) {
+                self.entries.insert(id, (text, vector));
+            }
+        }
+        Ok(())
+    }
+
+    /// The `k` nodes most similar to each of `sources`, most similar
+    /// first, without duplicates or the sources themselves.
+    pub async fn candidates(&self, sources: &[GraphNode], k: usize) -> Result<Vec<NodeId>> {
+        let texts: Vec<String> = sources.iter().map(node_text).collect();
+        let queries = self.embedder.embed(&texts).await?;
+        let excluded: HashSet<NodeId> = sources.iter().map(|n| n.id).collect();
+
+        let mut chosen = Vec::new();
+        let mut seen = HashSet::new();
+        for query in &queries {
+            let mut scored: Vec<(f32, NodeId)> = self
+                .entries
+                .iter()
+                .filter(|(id, _)| !excluded.contains(id))
+                .map(|(&id, (_, vector))| (dot(query, vector), id))
+                .collect();
+            scored.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.0.cmp(&b.1.0)));
+            chosen.extend(scored.into_iter().take(k).map(|(_, id)| id).filter(|id| seen.insert(*id)));
+        }
+        Ok(chosen)
+    }
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use canopy_core::NodeKind;
+
+    #[test]
+    fn test_words() {
+        assert_eq!(words("parseHTTPConfig"), ["parse", "httpconfig"]);
+        assert_eq!(words("ConfigParser::load_file2"), ["config", "parser", "load", "file2"]);
+    }
+
+    #[tokio::test]
+    async fn test_candidates() {
+        let mut index = EmbeddingIndex::default();
+        let graph = [
+            GraphNode::for_test(NodeKind::Function, "load_config").with_id(1),
+            GraphNode::for_test(NodeKind::Struct, "ConfigLoader").with_id(2),
+            GraphNode::for_test(NodeKind::Function, "render_chart").with_id(3),
+            GraphNode::for_test(NodeKind::Function, "draw_axis").with_id(4),
+        ];
+        index.sync(&graph).await.unwrap();
+        assert_eq!(index.len(), 4);
+
+        let source = GraphNode::for_test(NodeKind::Function, "reload_config").with_id(5);
+        assert_eq!(index.candidates(std::slice::from_ref(&source), 2).await.unwrap(), [NodeId(1), NodeId(2)]);
+
+        // Removed nodes leave the index
+        index.sync(&graph[2..]).await.unwrap();
+        assert_eq!(index.len(), 2);
+        assert!(!index.candidates(std::slice::from_ref(&source), 2).await.unwrap().contains(&NodeId(1)));
+    }
+
+    /// Records the size of each request, failing once `fail_after` have
+    /// been answered.
+    struct Batches {
+        sizes: std::sync::Arc<std::sync::Mutex<Vec<usize>>>,
+        fail_after: usize,
+    }
+
+    #[async_trait::async_trait]
+    impl Embedder for Batches {
+        async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+            let mut sizes = self.sizes.lock().unwrap();
+            anyhow::ensure!(sizes.len() < self.fail_after, "too many requests");
+            sizes.push(texts.len());
+            Ok(texts.iter().map(|_| vec![1.0]).collect())
+        }
+
+        fn name(&self) -> &str {
+            "batches"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sync_batches() {
+        let graph: Vec<GraphNode> = (1..=300).map(|i| GraphNode::for_test(NodeKind::Function, &format!("f{}", i)).with_id(i)).collect();
+        let sizes = std::sync::Arc::default();
+        let mut index = EmbeddingIndex::new(Box::new(Batches { sizes: std::sync::Arc::clone(&sizes), fail_after: usize::MAX }));
+        index.sync(&graph).await.unwrap();
+        assert_eq!(*sizes.lock().unwrap(), [EMBED_BATCH_SIZE, EMBED_BATCH_SIZE, 300 - 2 * EMBED_BATCH_SIZE]);
+        assert_eq!(index.len(), 300);
+
+        // A failing request keeps the batches before it
+        let mut index = EmbeddingIndex::new(Box::new(Batches { sizes: std::sync::Arc::default(), fail_after: 1 }));
+        assert!(index.sync(&graph).await.is_err());
+        assert_eq!(index.len(), EMBED_BATCH_SIZE);
+    }
+}
@@ -0,0 +1,92 @@
+//! Bottom-up summaries of the project's structure
+//!
+//! Files, modules and directories are described from what they contain:
+//! functions are summarized first, then the files holding them, then the
+//! directories holding those. [`bottom_up`] orders containers so that each
+//! comes after everything inside it.
+
+use canopy_core::{EdgeKind, Graph, GraphNode, NodeId, NodeKind};
+use std::collections::HashSet;
+
+/// Whether nodes of `kind` are part of the project's structure, summarized
+/// from their children rather than from source.
+pub fn is_architectural(kind: NodeKind) -> bool {
+    matches!(
+        kind,
+        NodeKind::Directory | NodeKind::File | NodeKind::Module | NodeKind::Package | NodeKind::WorkspaceRoot
+    )
+}
+
+/// The nodes `id` directly contains.
+pub fn children(graph: &Graph, id: NodeId) -> Vec<GraphNode> {
+    graph
+        .edges_from(id)
+        .filter(|e| e.kind == EdgeKind::Contains)
+        .filter_map(|e| graph.node(e.target).cloned())
+        .collect()
+}
+
+/// The architectural nodes among `ids` and everything containing them,
+/// deepest first.
+pub fn bottom_up(graph: &Graph, ids: impl IntoIterator<Item = NodeId>) -> Vec<NodeId> {
+    let mut containers = HashSet::new();
+    for id in ids {
+        containers.insert(id);
+        containers.extend(graph.ancestors(id));
+    }
+    let mut ordered: Vec<(usize, NodeId)> = containers
+        .into_iter()
+        .filter(|&id| graph.node(id).is_some_and(|n| is_architectural(n.kind)))
+        .map(|id| (graph.ancestors(id).len(), id))
+        .collect();
+    ordered.sort_unstable_by(|a, b| b.0.cmp(&a.0).then(a.1.0.cmp(&b.1.0)));
+    ordered.into_iter().map(|(_, id)| id).collect()
+}
+
+/// Every architectural node of `graph`, deepest first.
+pub fn all_architecture(graph: &Graph) -> Vec<NodeId> {
+    bottom_up(graph, graph.all_nodes().filter(|n| is_architectural(n.kind)).map(|n| n.id).collect::<Vec<_>>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use canopy_core::{EdgeId, EdgeSource, GraphEdge};
+
+    fn contains(graph: &mut Graph, source: NodeId, target: NodeId) {
+        graph.add_edge(GraphEdge {
+            id: EdgeId(0),
+            source,
+            target,
+            kind: EdgeKind::Contains,
+            edge_source: EdgeSource::Structural,
+            confidence: 1.0,
+            label: None,
+            file_path: None,
+            line: None,
+        });
+    }
+
+    #[test]
+    fn test_bottom_up() {
+        let mut graph = Graph::new();
+        let root = graph.add_node(GraphNode::for_test(NodeKind::Directory, "."));
+        let src = graph.add_node(GraphNode::for_test(NodeKind::Directory, "src"));
+        let lib = graph.add_node(GraphNode::for_test(NodeKind::File, "src/lib.rs"));
+        let load = graph.add_node(GraphNode::for_test(NodeKind::Function, "load"));
+        let readme = graph.add_node(GraphNode::for_test(NodeKind::File, "README.md"));
+        contains(&mut graph, root, src);
+        contains(&mut graph, src, lib);
+        contains(&mut graph, lib, load);
+        contains(&mut graph, root, readme);
+
+        assert_eq!(bottom_up(&graph, [load]), [lib, src, root]);
+        let all = all_architecture(&graph);
+        assert_eq!(all.len(), 4);
+        assert_eq!(all.last(), Some(&root));
+        assert!(all.iter().position(|&id| id == lib) < all.iter().position(|&id| id == src));
+        let names: Vec<String> = children(&graph, root).into_iter().map(|n| n.name).collect();
+        assert_eq!(names.len(), 2);
+        assert!(names.contains(&"README.md".to_string()));
+    }
+}
@@ -8,11 +8,26 @@ pub mod bridge;
 pub mod prompt;
 pub mod providers;
 pub mod cache;
+pub mod calibration;
+pub mod change;
 pub mod budget;
+pub mod embedding;
+pub mod hierarchy;
+pub mod query;
+pub mod redact;
+pub mod review;
+pub mod session;
+pub mod source;
 
 #[cfg(test)]
 pub mod tests;
 
 pub use bridge::*;
 pub use budget::Budget;
-pub use cache::AnalysisCache;
\ No newline at end of file
+pub use cache::AnalysisCache;
+pub use calibration::Calibration;
+pub use change::GraphChange;
+pub use embedding::EmbeddingIndex;
+pub use query::GraphQuery;
+pub use review::ReviewQueue;
+pub use session::Sessions;
\ No newline at end of file
@@ -1,6 +1,7 @@
 //! Prompt templates for AI analysis
 
 use super::bridge::{SemanticRelationship, AnalysisContext};
+use super::change::{ChangedEdge, GraphChange};
 use canopy_core::{GraphNode, GraphEdge};
 
 /// Generate a prompt for semantic relationship analysis
@@ -17,12 +18,13 @@ pub fn semantic_analysis_prompt(
     
     let candidates_desc = candidate_nodes.iter()
         .map(|n| format!(
-            "- {} (ID: {}, kind: {}, lines: {}-{})",
+            "- {} (ID: {}, kind: {:?}, lines: {}-{}){}",
             n.name,
             n.id.0,
-            format!("{:?}", n.kind),
+            n.kind,
             n.line_start.unwrap_or(0),
-            n.line_end.unwrap_or(0)
+            n.line_end.unwrap_or(0),
+            snippet(context, n)
         ))
         .collect::<Vec<_>>()
         .join("\n");
@@ -31,7 +33,7 @@ pub fn semantic_analysis_prompt(
 
 File: {}
 Language: {}
-Source element: {} (ID: {}, kind: {}, lines: {}-{})
+Source element: {} (ID: {}, kind: {:?}, lines: {}-{})
 
 Source code context:
 ```
@@ -42,7 +44,7 @@ Surrounding context: {:?}
 
 Related elements to analyze:
 {}
-
+{}
 Look for these types of relationships: {}
 
 Instructions:
@@ -70,41 +72,242 @@ Return a JSON object with:
         context.language,
         source_node.name,
         source_node.id.0,
-        format!("{:?}", source_node.kind),
+        source_node.kind,
         source_node.line_start.unwrap_or(0),
         source_node.line_end.unwrap_or(0),
-        source_node.qualified_name,
+        source_code(context, source_node),
         context.enclosing_context,
         candidates_desc,
+        review_examples(context),
         relationship_types,
         source_node.id.0
     )
 }
 
+/// A node's source, or its qualified name if the context lacks it.
+pub fn source_code(context: &AnalysisContext, node: &GraphNode) -> String {
+    context.sources.get(&node.id).cloned().unwrap_or_else(|| node.qualified_name.to_string())
+}
+
+/// A node's source from the context, indented to follow its line in a
+/// list, or nothing.
+fn snippet(context: &AnalysisContext, node: &GraphNode) -> String {
+    context.sources.get(&node.id)
+        .map(|source| source.lines().map(|line| format!("\n    {}", line)).collect())
+        .unwrap_or_default()
+}
+
+/// The relationships reviewed in this project, as a prompt section, or
+/// nothing if there are none.
+fn review_examples(context: &AnalysisContext) -> String {
+    if context.examples.is_empty() {
+        return String::new();
+    }
+    let examples = context.examples.iter()
+        .map(|e| format!(
+            "- {}: {} {} {} ({})",
+            if e.accepted { "Accepted" } else { "Rejected" },
+            e.source,
+            e.relationship,
+            e.target,
+            e.explanation
+        ))
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!("\nRelationships reviewed by this project's developers; infer relationships the way they judged these:\n{}\n", examples)
+}
+
+/// Generate a prompt for analyzing several source elements in one request
+pub fn semantic_batch_prompt(
+    source_nodes: &[GraphNode],
+    candidate_nodes: &[GraphNode],
+    context: &AnalysisContext,
+    relationships: &[SemanticRelationship],
+) -> String {
+    let relationship_types = relationships.iter()
+        .map(|r| format!("{:?}", r))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let describe = |nodes: &[GraphNode]| nodes.iter()
+        .map(|n| format!(
+            "- {} (ID: {}, kind: {:?}, lines: {}-{}){}",
+            n.qualified_name,
+            n.id.0,
+            n.kind,
+            n.line_start.unwrap_or(0),
+            n.line_end.unwrap_or(0),
+            snippet(context, n)
+        ))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!(r#"You are analyzing code relationships in a software project.
+
+File: {}
+Language: {}
+
+Source elements, all from this file:
+{}
+
+Related elements to analyze:
+{}
+{}
+Look for these types of relationships: {}
+
+Instructions:
+1. For each source element, analyze if it has any semantic relationships with the related elements
+2. Consider imports, function calls, type usage, configuration, etc.
+3. Provide confidence scores (0.0-1.0) based on evidence in the code
+4. Include line numbers where relationships are evident
+5. Return only relationships with confidence > 0.5
+
+Return a JSON object with:
+{{
+  "relationships": [
+    {{
+      "source_id": <source_id>,
+      "target_id": <target_id>,
+      "relationship": "<relationship_type>",
+      "confidence": 0.85,
+      "explanation": "Brief explanation of the relationship",
+      "line_reference": 42
+    }}
+  ],
+  "explanation": "Overall analysis summary"
+}}"#,
+        context.file_path.display(),
+        context.language,
+        describe(source_nodes),
+        describe(candidate_nodes),
+        review_examples(context),
+        relationship_types
+    )
+}
+
 /// Generate a prompt for node summarization
 pub fn node_summary_prompt(node: &GraphNode, context: &AnalysisContext) -> String {
-    format!(r#"Summarize what this {} does in one concise sentence:
+    format!(r#"Summarize what this {:?} does in one concise sentence:
 
 File: {}
 Name: {}
 Type: {:?}
 Lines: {}-{}
 Qualified name: {}
-
+{}
 Context: {:?}
 
 Provide a clear, technical summary of its purpose and functionality."#,
-        format!("{:?}", node.kind),
+        node.kind,
         context.file_path.display(),
         node.name,
         node.kind,
         node.line_start.unwrap_or(0),
         node.line_end.unwrap_or(0),
         node.qualified_name,
+        context.sources.get(&node.id).map(|source| format!("\nSource:\n```\n{}\n```\n", source)).unwrap_or_default(),
         context.enclosing_context
     )
 }
 
+/// Generate a prompt describing a container from its children
+pub fn container_summary_prompt(node: &GraphNode, children: &[GraphNode]) -> String {
+    let children_desc = children.iter()
+        .map(|c| match c.metadata.extra.get("ai_summary") {
+            Some(summary) => format!("- {} ({:?}): {}", c.name, c.kind, summary),
+            None => format!("- {} ({:?})", c.name, c.kind),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!(r#"Describe what this {:?} is for, in one paragraph, for a developer new to the codebase:
+
+Name: {}
+Path: {}
+
+It contains:
+{}
+
+Explain the responsibility of this part of the system and how its contents work together, rather than listing them."#,
+        node.kind,
+        node.name,
+        node.file_path.display(),
+        children_desc
+    )
+}
+
+/// Generate a prompt explaining a change to a file for the change log
+pub fn change_explanation_prompt(change: &GraphChange) -> String {
+    let nodes = |nodes: &[GraphNode]| {
+        if nodes.is_empty() {
+            return "(none)".to_string();
+        }
+        nodes.iter().map(|n| format!("- {} ({:?})", n.qualified_name, n.kind)).collect::<Vec<_>>().join("\n")
+    };
+    let edges = |edges: &[ChangedEdge]| {
+        if edges.is_empty() {
+            return "(none)".to_string();
+        }
+        edges.iter().map(|e| format!("- {} {:?} {}", e.source, e.kind, e.target)).collect::<Vec<_>>().join("\n")
+    };
+    let source = if change.source_diff.is_empty() {
+        "The source before the change is unknown.".to_string()
+    } else {
+        format!("Changed lines:\n```diff\n{}```", change.source_diff)
+    };
+
+    format!(r#"Explain this change to a codebase in one short paragraph for a change log, such as "extracted payment validation into a new module, 3 callers updated":
+
+File: {}
+
+Symbols added:
+{}
+
+Symbols removed:
+{}
+
+Symbols edited:
+{}
+
+Relationships added:
+{}
+
+Relationships removed:
+{}
+
+{}
+
+Say what the change does and why it likely matters, rather than listing every symbol."#,
+        change.file_path.display(),
+        nodes(&change.added),
+        nodes(&change.removed),
+        nodes(&change.modified),
+        edges(&change.added_edges),
+        edges(&change.removed_edges),
+        source
+    )
+}
+
+/// Generate a prompt translating a question into a graph query
+pub fn graph_query_prompt(question: &str) -> String {
+    format!(r#"Translate this question about a codebase into a query of its code graph:
+
+Question: {}
+
+Nodes have a kind (Directory, File, Module, Class, Struct, Enum, Interface, Function, Method, Constant, TypeAlias, Trait, Macro, TestCase, Field, Variable, ConfigBlock, ConfigKey, EnvVariable, Route, Migration, CIJob, DockerService, Script, Document, Section, Package), a qualified name and a file path.
+Edges have a kind (Imports, Calls, Inherits, Implements, TypeReference, Instantiates, Exports, Overrides, References, TestedBy, DependsOn, ConfiguresArgument, EnvironmentBinding, RouteHandler, MigrationTarget, SemanticReference).
+
+Return only JSON in this format:
+{{
+  "nodes": {{"kinds": ["Function"], "names": ["handler"], "paths": []}},
+  "related": {{"edges": ["Calls"], "direction": "Outgoing", "to": {{"kinds": [], "names": ["db", "database", "sql"], "paths": []}}}}
+}}
+
+Names and paths match if the node's contains any of them. Leave a list empty to accept anything, and "related" null if the question is about the nodes alone. If the question can't be asked of the graph, return {{}}."#,
+        question
+    )
+}
+
 /// Generate a prompt for code question answering
 pub fn code_question_prompt(
     question: &str,
@@ -112,12 +315,12 @@ pub fn code_question_prompt(
     relevant_edges: &[GraphEdge],
 ) -> String {
     let nodes_desc = relevant_nodes.iter()
-        .map(|n| format!("- {} ({}): {}", n.name, format!("{:?}", n.kind), n.qualified_name))
+        .map(|n| format!("- {} ({:?}): {}", n.name, n.kind, n.qualified_name))
         .collect::<Vec<_>>()
         .join("\n");
         
     let edges_desc = relevant_edges.iter()
-        .map(|e| format!("- {} -> {} ({})", e.source.0, e.target.0, format!("{:?}", e.kind)))
+        .map(|e| format!("- {} -> {} ({:?})", e.source.0, e.target.0, e.kind))
         .collect::<Vec<_>>()
         .join("\n");
 
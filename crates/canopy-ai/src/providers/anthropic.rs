@@ -1,77 +1,270 @@
-//! Anthropic Claude provider implementation
-
-use super::super::bridge::{AIProvider, SemanticAnalysisRequest, SemanticAnalysisResult, InferredRelationship, SemanticRelationship, AnalysisContext};
+//! Anthropic Claude provider, using the Messages API
+//!
+//! Requests go to `{base_url}/v1/messages`, authenticated with an
+//! `x-api-key` header. Relationships are reported through a tool whose
+//! input follows [`analysis_schema`](super::structured::analysis_schema).
+//! Claude models served by OpenRouter are reached through the `openrouter`
+//! provider instead.
+
+use super::{sse, ApiError};
+use super::structured::{analysis_schema, repair_prompt, AnalysisReport, REPORT_DESCRIPTION, REPORT_TOOL};
+use super::super::prompt;
+use super::super::change::GraphChange;
+use super::super::query::GraphQuery;
+use super::super::bridge::{AIProvider, SemanticAnalysisRequest, SemanticBatchRequest, SemanticAnalysisResult, AnalysisContext, ProviderCapabilities, TokenStream, TokenUsage};
 use anyhow::{Result, Context};
 use canopy_core::{GraphNode, GraphEdge, NodeId};
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// Where requests go unless a base URL is given.
+pub const DEFAULT_BASE_URL: &str = "https://api.anthropic.com";
+
+/// Version of the Messages API requests are written for.
+pub const API_VERSION: &str = "2023-06-01";
+
+/// Model used unless another is given.
+pub const DEFAULT_MODEL: &str = "claude-3-5-haiku-latest";
+
+/// Context window of Claude models, which the models API doesn't give.
+pub const CONTEXT_TOKENS: u32 = 200_000;
 
 pub struct AnthropicProvider {
     client: reqwest::Client,
     api_key: String,
     model: String,
+    /// Root of the API, without the trailing `/v1/messages`.
+    base_url: String,
 }
 
 impl AnthropicProvider {
+    /// A provider using `api_key`, or else `ANTHROPIC_API_KEY`.
     pub fn new(api_key: Option<String>) -> Self {
-        // Use OpenRouter API key from .env file for consistency
-        let api_key = api_key.or_else(|| std::env::var("OPENROUTER_API_KEY").ok())
-            .or_else(|| std::env::var("openrouter_api_key").ok())
+        let api_key = api_key.or_else(|| std::env::var("ANTHROPIC_API_KEY").ok())
             .unwrap_or_default();
         
         Self {
             client: reqwest::Client::new(),
             api_key,
-            model: "anthropic/claude-3-haiku-20240307".to_string(), // OpenRouter format
+            model: DEFAULT_MODEL.to_string(),
+            base_url: DEFAULT_BASE_URL.to_string(),
+        }
+    }
+
+    pub fn with_model(mut self, model: String) -> Self {
+        self.model = model;
+        self
+    }
+
+    /// Send requests to the API at `base_url` rather than Anthropic's.
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Post a Messages API request, failing on an error status.
+    async fn send(&self, request: &MessagesRequest) -> Result<reqwest::Response> {
+        let response = self.client
+            .post(format!("{}/v1/messages", self.base_url))
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", API_VERSION)
+            .json(request)
+            .send()
+            .await
+            .context("Failed to send request to Anthropic")?;
+
+        if !response.status().is_success() {
+            return Err(ApiError::from_response(self.name(), response).await.into());
+        }
+        Ok(response)
+    }
+
+    /// Post a Messages API request and read the whole response.
+    async fn complete(&self, request: &MessagesRequest) -> Result<MessagesResponse> {
+        let response = self.send(request).await?;
+        response.json().await.context("Failed to parse Anthropic response")
+    }
+
+    /// A request of one user message, `prompt`.
+    fn request(&self, system: &str, prompt: String, temperature: f32, max_tokens: u32) -> MessagesRequest {
+        MessagesRequest {
+            model: self.model.clone(),
+            max_tokens,
+            system: system.to_string(),
+            messages: vec![Message { role: "user", content: prompt }],
+            temperature,
+            tools: Vec::new(),
+            tool_choice: None,
+            stream: false,
+        }
+    }
+
+    /// The text of the response to `request`.
+    async fn text(&self, request: &MessagesRequest) -> Result<String> {
+        Ok(self.complete(request).await?.text().trim().to_string())
+    }
+
+    /// The request answering `question` from the graph.
+    fn question_request(&self, question: &str, relevant_nodes: &[GraphNode], relevant_edges: &[GraphEdge]) -> MessagesRequest {
+        let nodes_info = relevant_nodes.iter()
+            .map(|n| format!("- {} ({}): {} at {:?}:{:?}", 
+                n.name, 
+                format!("{:?}", n.kind).to_lowercase(),
+                n.file_path.display(),
+                n.line_start,
+                n.line_end))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let edges_info = relevant_edges.iter()
+            .map(|e| format!("- {} -> {} ({:?})", 
+                e.source.0, 
+                e.target.0,
+                e.kind))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let prompt = format!(
+            r#"You are a code analysis expert. Answer the following question about the codebase.
+
+Question: {}
+
+Relevant code elements:
+{}
+
+Connections between elements:
+{}
+
+Provide a clear, concise answer based on the provided code context. If the information is insufficient to answer accurately, explain what additional context would be needed."#,
+            question,
+            nodes_info,
+            edges_info
+        );
+
+        self.request(
+            "You are a helpful code analysis assistant. Answer questions clearly and concisely.",
+            prompt,
+            0.2,
+            1000,
+        )
+    }
+
+    /// Ask for the relationships between `nodes` through the report tool,
+    /// sending a report off its schema back once to be repaired.
+    async fn report(&self, mut request: MessagesRequest, nodes: &[NodeId]) -> Result<SemanticAnalysisResult> {
+        request.tools = vec![ToolDefinition {
+            name: REPORT_TOOL,
+            description: REPORT_DESCRIPTION,
+            input_schema: analysis_schema(),
+        }];
+        request.tool_choice = Some(json!({"type": "tool", "name": REPORT_TOOL}));
+
+        let mut usages = Vec::new();
+        let mut repaired = false;
+        loop {
+            let response = self.complete(&request).await?;
+            usages.extend(response.usage.as_ref().map(|u| TokenUsage {
+                model: self.model.clone(),
+                input_tokens: u.input_tokens,
+                output_tokens: u.output_tokens,
+            }));
+            let text = response.report();
+            match AnalysisReport::parse(&text, nodes) {
+                Ok(report) => {
+                    return Ok(SemanticAnalysisResult {
+                        explanation: report.explanation.clone(),
+                        relationships: report.into_relationships(),
+                        tokens_used: usages.iter().map(|u| u.input_tokens + u.output_tokens).sum(),
+                        provider: self.name().to_string(),
+                        usage: usages,
+                    });
+                }
+                Err(e) if !repaired => {
+                    tracing::warn!("{} sent a malformed report, asking again: {:#}", self.name(), e);
+                    request.messages.push(Message { role: "assistant", content: text });
+                    request.messages.push(Message { role: "user", content: repair_prompt(&e) });
+                    repaired = true;
+                }
+                Err(e) => return Err(e.context("Failed to parse semantic analysis response from Anthropic")),
+            }
         }
     }
 }
 
 #[derive(Debug, Serialize)]
-struct OpenAIRequest {
+struct MessagesRequest {
     model: String,
-    messages: Vec<OpenAIMessage>,
-    temperature: f32,
     max_tokens: u32,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    system: String,
+    messages: Vec<Message>,
+    temperature: f32,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<ToolDefinition>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_choice: Option<Value>,
+    /// Send the response as server-sent events.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct OpenAIMessage {
-    role: String,
+#[derive(Debug, Serialize)]
+struct Message {
+    role: &'static str,
     content: String,
 }
 
-#[derive(Debug, Deserialize)]
-struct OpenAIResponse {
-    choices: Vec<OpenAIChoice>,
-    usage: Option<OpenAIUsage>,
+#[derive(Debug, Serialize)]
+struct ToolDefinition {
+    name: &'static str,
+    description: &'static str,
+    input_schema: Value,
 }
 
 #[derive(Debug, Deserialize)]
-struct OpenAIChoice {
-    message: OpenAIMessage,
+struct MessagesResponse {
+    content: Vec<ContentBlock>,
+    usage: Option<MessagesUsage>,
 }
 
-#[derive(Debug, Deserialize)]
-struct OpenAIUsage {
-    total_tokens: u32,
-}
+impl MessagesResponse {
+    /// The text blocks of the response, joined.
+    fn text(&self) -> String {
+        self.content
+            .iter()
+            .filter_map(|block| match block {
+                ContentBlock::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
 
-// Using OpenAI-compatible format for OpenRouter
+    /// The input of the response's use of the report tool, as JSON, or
+    /// else its text.
+    fn report(&self) -> String {
+        self.content
+            .iter()
+            .find_map(|block| match block {
+                ContentBlock::ToolUse { name, input } if name == REPORT_TOOL => Some(input.to_string()),
+                _ => None,
+            })
+            .unwrap_or_else(|| self.text())
+    }
+}
 
-#[derive(Debug, Serialize, Deserialize)]
-struct SemanticAnalysisResponse {
-    relationships: Vec<InferredRelationshipJson>,
-    explanation: String,
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentBlock {
+    Text { text: String },
+    ToolUse { name: String, input: Value },
+    #[serde(other)]
+    Other,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct InferredRelationshipJson {
-    source_id: u64,
-    target_id: u64,
-    relationship: String,
-    confidence: f32,
-    explanation: String,
-    line_reference: Option<u32>,
+#[derive(Debug, Deserialize)]
+struct MessagesUsage {
+    input_tokens: u32,
+    output_tokens: u32,
 }
 
 #[async_trait::async_trait]
@@ -88,7 +281,9 @@ Language: {:?}
 Source function: {} (lines {:?}-{:?})
 
 Source code:
-```{}```
+```
+{}
+```
 
 Candidate code elements to analyze relationships with:
 {}
@@ -118,86 +313,46 @@ Respond with a JSON object in this exact format:
             request.source_node.name,
             request.source_node.line_start,
             request.source_node.line_end,
-            "Source code not available in request",
+            prompt::source_code(&request.context, &request.source_node),
             request.candidate_nodes.iter()
-                .map(|n| format!("- {} ({}): {} lines {:?}-{:?}", 
-                    n.name, 
-                    format!("{:?}", n.kind).to_lowercase(),
-                    n.file_path.display(),
-                    n.line_start,
-                    n.line_end))
+                .map(|n| {
+                    let mut line = format!("- {} ({}): {} lines {:?}-{:?}",
+                        n.name,
+                        format!("{:?}", n.kind).to_lowercase(),
+                        n.file_path.display(),
+                        n.line_start,
+                        n.line_end);
+                    if let Some(source) = request.context.sources.get(&n.id) {
+                        line.extend(source.lines().map(|l| format!("\n    {}", l)));
+                    }
+                    line
+                })
                 .collect::<Vec<_>>()
                 .join("\n"),
             request.source_node.id.0
         );
 
-        // Convert to OpenAI-compatible format for OpenRouter
-        let openai_request = OpenAIRequest {
-            model: self.model.clone(),
-            messages: vec![
-                OpenAIMessage {
-                    role: "system".to_string(),
-                    content: "You are a code analysis expert. Respond only with valid JSON.".to_string(),
-                },
-                OpenAIMessage {
-                    role: "user".to_string(),
-                    content: prompt,
-                }
-            ],
-            temperature: 0.1,
-            max_tokens: 2000,
-        };
-
-        let response = self.client
-            .post("https://openrouter.ai/api/v1/chat/completions")
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .header("HTTP-Referer", "https://github.com/openclaw/openclaw")
-            .header("X-Title", "Canopy")
-            .json(&openai_request)
-            .send()
-            .await
-            .context("Failed to send request to OpenRouter")?;
-
-        if !response.status().is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            anyhow::bail!("OpenRouter API error: {}", error_text);
-        }
-
-        let openai_response: OpenAIResponse = response.json().await.context("Failed to parse OpenRouter response")?;
-        
-        let content = &openai_response.choices[0].message.content;
-
-        // Parse the JSON response
-        let analysis_response: SemanticAnalysisResponse = serde_json::from_str(content)
-            .context("Failed to parse semantic analysis response from Anthropic")?;
-
-        // Convert JSON relationships to proper InferredRelationship objects
-        let mut relationships = Vec::new();
-        for rel_json in analysis_response.relationships {
-            let relationship = match rel_json.relationship.as_str() {
-                "Calls" => SemanticRelationship::Calls,
-                "DependsOn" => SemanticRelationship::DependsOn,
-                "Uses" => SemanticRelationship::Uses,
-                "Configures" => SemanticRelationship::Configures,
-                _ => continue, // Skip unknown relationships
-            };
-
-            relationships.push(InferredRelationship {
-                source_id: NodeId(rel_json.source_id),
-                target_id: NodeId(rel_json.target_id),
-                relationship,
-                confidence: rel_json.confidence,
-                explanation: rel_json.explanation,
-                line_reference: rel_json.line_reference,
-            });
-        }
+        let messages_request = self.request(
+            "You are a code analysis expert. Report relationships only when the code shows them.",
+            prompt,
+            0.1,
+            2000,
+        );
+        let nodes: Vec<NodeId> = std::iter::once(&request.source_node)
+            .chain(&request.candidate_nodes)
+            .map(|n| n.id)
+            .collect();
+        self.report(messages_request, &nodes).await
+    }
 
-        Ok(SemanticAnalysisResult {
-            relationships,
-            explanation: analysis_response.explanation,
-            tokens_used: openai_response.usage.map(|u| u.total_tokens).unwrap_or(0),
-        })
+    async fn analyze_batch(&self, batch: SemanticBatchRequest) -> Result<SemanticAnalysisResult> {
+        let prompt = prompt::semantic_batch_prompt(&batch.source_nodes, &batch.candidate_nodes, &batch.context, &batch.relationship_types);
+        let messages_request = self.request(prompt::CODE_ANALYSIS_SYSTEM_PROMPT, prompt, 0.1, 4000);
+        let nodes: Vec<NodeId> = batch.source_nodes.iter().chain(&batch.candidate_nodes).map(|n| n.id).collect();
+        let mut result = self.report(messages_request, &nodes).await?;
+        // Only the batch's own nodes are sources
+        result.relationships.retain(|rel| batch.source_nodes.iter().any(|n| n.id == rel.source_id));
+        Ok(result)
     }
     
     async fn generate_node_summary(
@@ -223,43 +378,47 @@ Provide a brief summary (1-2 sentences) explaining what this code does and its p
             node.language
         );
 
-        let openai_request = OpenAIRequest {
-            model: self.model.clone(),
-            messages: vec![
-                OpenAIMessage {
-                    role: "system".to_string(),
-                    content: "You are a code documentation expert. Provide concise, clear summaries.".to_string(),
-                },
-                OpenAIMessage {
-                    role: "user".to_string(),
-                    content: prompt,
-                }
-            ],
-            temperature: 0.3,
-            max_tokens: 150,
-        };
+        let messages_request = self.request(
+            "You are a code documentation expert. Provide concise, clear summaries.",
+            prompt,
+            0.3,
+            150,
+        );
 
-        let response = self.client
-            .post("https://openrouter.ai/api/v1/chat/completions")
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .header("HTTP-Referer", "https://github.com/openclaw/openclaw")
-            .header("X-Title", "Canopy")
-            .json(&openai_request)
-            .send()
-            .await
-            .context("Failed to send request to OpenRouter")?;
+        self.text(&messages_request).await
+    }
 
-        if !response.status().is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            anyhow::bail!("OpenRouter API error: {}", error_text);
-        }
+    async fn translate_question(&self, question: &str) -> Result<GraphQuery> {
+        let messages_request = self.request(
+            "You translate questions about code into graph queries. Return only valid JSON.",
+            prompt::graph_query_prompt(question),
+            0.0,
+            300,
+        );
 
-        let openai_response: OpenAIResponse = response.json().await.context("Failed to parse OpenRouter response")?;
-        
-        let summary = openai_response.choices[0].message.content.trim().to_string();
+        GraphQuery::parse(&self.complete(&messages_request).await?.text())
+    }
+
+    async fn summarize_container(&self, node: &GraphNode, children: &[GraphNode]) -> Result<String> {
+        let messages_request = self.request(
+            "You are a software architect documenting a codebase. Be concise and concrete.",
+            prompt::container_summary_prompt(node, children),
+            0.3,
+            300,
+        );
+
+        self.text(&messages_request).await
+    }
+
+    async fn explain_change(&self, change: &GraphChange) -> Result<String> {
+        let messages_request = self.request(
+            "You are a senior engineer writing a project's change log. Be concise and concrete.",
+            prompt::change_explanation_prompt(change),
+            0.3,
+            200,
+        );
 
-        Ok(summary)
+        self.text(&messages_request).await
     }
     
     async fn answer_code_question(
@@ -268,81 +427,40 @@ Provide a brief summary (1-2 sentences) explaining what this code does and its p
         relevant_nodes: &[GraphNode],
         relevant_edges: &[GraphEdge],
     ) -> Result<String> {
-        let nodes_info = relevant_nodes.iter()
-            .map(|n| format!("- {} ({}): {} at {:?}:{:?}", 
-                n.name, 
-                format!("{:?}", n.kind).to_lowercase(),
-                n.file_path.display(),
-                n.line_start,
-                n.line_end))
-            .collect::<Vec<_>>()
-            .join("\n");
-
-        let edges_info = relevant_edges.iter()
-            .map(|e| format!("- {} -> {} ({:?})", 
-                e.source.0, 
-                e.target.0,
-                e.kind))
-            .collect::<Vec<_>>()
-            .join("\n");
-
-        let prompt = format!(
-            r#"You are a code analysis expert. Answer the following question about the codebase.
-
-Question: {}
-
-Relevant code elements:
-{}
-
-Connections between elements:
-{}
-
-Provide a clear, concise answer based on the provided code context. If the information is insufficient to answer accurately, explain what additional context would be needed."#,
-            question,
-            nodes_info,
-            edges_info
-        );
+        self.text(&self.question_request(question, relevant_nodes, relevant_edges)).await
+    }
 
-        let openai_request = OpenAIRequest {
-            model: self.model.clone(),
-            messages: vec![
-                OpenAIMessage {
-                    role: "system".to_string(),
-                    content: "You are a helpful code analysis assistant. Answer questions clearly and concisely.".to_string(),
-                },
-                OpenAIMessage {
-                    role: "user".to_string(),
-                    content: prompt,
-                }
-            ],
-            temperature: 0.2,
-            max_tokens: 1000,
-        };
+    async fn stream_code_question(
+        &self,
+        question: &str,
+        relevant_nodes: &[GraphNode],
+        relevant_edges: &[GraphEdge],
+    ) -> Result<TokenStream> {
+        let mut messages_request = self.question_request(question, relevant_nodes, relevant_edges);
+        messages_request.stream = true;
+        Ok(sse::message_deltas(self.send(&messages_request).await?))
+    }
 
+    async fn health_check(&self) -> Result<ProviderCapabilities> {
         let response = self.client
-            .post("https://openrouter.ai/api/v1/chat/completions")
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .header("HTTP-Referer", "https://github.com/openclaw/openclaw")
-            .header("X-Title", "Canopy")
-            .json(&openai_request)
+            .get(format!("{}/v1/models/{}", self.base_url, self.model))
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", API_VERSION)
             .send()
             .await
-            .context("Failed to send request to OpenRouter")?;
-
+            .context("Failed to send request to Anthropic")?;
         if !response.status().is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            anyhow::bail!("Anthropic API error: {}", error_text);
+            return Err(ApiError::from_response(self.name(), response).await.into());
         }
-
-        let openai_response: OpenAIResponse = response.json().await.context("Failed to parse OpenRouter response")?;
-        
-        let answer = openai_response.choices[0].message.content.trim().to_string();
-
-        Ok(answer)
+        Ok(ProviderCapabilities {
+            model: Some(self.model.clone()),
+            max_context_tokens: Some(CONTEXT_TOKENS),
+            supports_tools: true,
+            supports_streaming: true,
+        })
     }
     
     fn name(&self) -> &str {
-        "Anthropic (via OpenRouter)"
+        "Anthropic"
     }
 }
\ No newline at end of file
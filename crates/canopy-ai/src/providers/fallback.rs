@@ -0,0 +1,196 @@
+//! Failover between AI providers
+//!
+//! [`FallbackProvider`] tries its providers in order, moving on to the next
+//! when one is rate limited, times out, fails with a server error or can't
+//! be reached. Other errors, such as a rejected key, are returned as they
+//! are: the next provider would not fix them.
+
+use super::super::bridge::{AIProvider, AnalysisContext, ProviderCapabilities, SemanticAnalysisRequest, SemanticAnalysisResult, SemanticBatchRequest, TokenStream};
+use super::super::change::GraphChange;
+use super::super::query::GraphQuery;
+use super::is_transient;
+use anyhow::Result;
+use canopy_core::{GraphEdge, GraphNode};
+use std::future::Future;
+use std::time::Duration;
+
+pub struct FallbackProvider {
+    providers: Vec<Box<dyn AIProvider>>,
+    timeout: Option<Duration>,
+    name: String,
+}
+
+impl FallbackProvider {
+    /// Try `providers` in order, e.g. OpenAI, then Anthropic, then Local.
+    pub fn new(providers: Vec<Box<dyn AIProvider>>) -> Self {
+        let names: Vec<&str> = providers.iter().map(|p| p.name()).collect();
+        let name = format!("Fallback ({})", names.join(" → "));
+        Self { providers, timeout: None, name }
+    }
+
+    /// Give up on a provider, and try the next, after `timeout`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    async fn call<T>(&self, request: impl Future<Output = Result<T>>) -> Result<T> {
+        match self.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, request).await?,
+            None => request.await,
+        }
+    }
+
+    /// The error to carry on to the next provider with, or `Err` if the
+    /// failure isn't one to fall back on.
+    fn recover(provider: &dyn AIProvider, error: anyhow::Error) -> Result<anyhow::Error> {
+        if !is_transient(&error) {
+            return Err(error);
+        }
+        tracing::warn!("AI provider {} failed, falling back: {}", provider.name(), error);
+        Ok(error)
+    }
+}
+
+/// The last provider's error once all have failed.
+fn exhausted(last: Option<anyhow::Error>) -> anyhow::Error {
+    last.unwrap_or_else(|| anyhow::anyhow!("No AI provider to fall back on"))
+}
+
+#[async_trait::async_trait]
+impl AIProvider for FallbackProvider {
+    async fn analyze_semantic_relationships(
+        &self,
+        request: SemanticAnalysisRequest,
+    ) -> Result<SemanticAnalysisResult> {
+        let mut last = None;
+        for provider in &self.providers {
+            match self.call(provider.analyze_semantic_relationships(request.clone())).await {
+                Ok(result) => return Ok(result),
+                Err(e) => last = Some(Self::recover(provider.as_ref(), e)?),
+            }
+        }
+        Err(exhausted(last))
+    }
+
+    async fn analyze_batch(&self, batch: SemanticBatchRequest) -> Result<SemanticAnalysisResult> {
+        let mut last = None;
+        for provider in &self.providers {
+            match self.call(provider.analyze_batch(batch.clone())).await {
+                Ok(result) => return Ok(result),
+                Err(e) => last = Some(Self::recover(provider.as_ref(), e)?),
+            }
+        }
+        Err(exhausted(last))
+    }
+
+    async fn generate_node_summary(
+        &self,
+        node: &GraphNode,
+        context: &AnalysisContext,
+    ) -> Result<String> {
+        let mut last = None;
+        for provider in &self.providers {
+            match self.call(provider.generate_node_summary(node, context)).await {
+                Ok(summary) => return Ok(summary),
+                Err(e) => last = Some(Self::recover(provider.as_ref(), e)?),
+            }
+        }
+        Err(exhausted(last))
+    }
+
+    async fn translate_question(&self, question: &str) -> Result<GraphQuery> {
+        let mut last = None;
+        for provider in &self.providers {
+            match self.call(provider.translate_question(question)).await {
+                Ok(query) => return Ok(query),
+                Err(e) => last = Some(Self::recover(provider.as_ref(), e)?),
+            }
+        }
+        Err(exhausted(last))
+    }
+
+    async fn summarize_container(&self, node: &GraphNode, children: &[GraphNode]) -> Result<String> {
+        let mut last = None;
+        for provider in &self.providers {
+            match self.call(provider.summarize_container(node, children)).await {
+                Ok(summary) => return Ok(summary),
+                Err(e) => last = Some(Self::recover(provider.as_ref(), e)?),
+            }
+        }
+        Err(exhausted(last))
+    }
+
+    async fn explain_change(&self, change: &GraphChange) -> Result<String> {
+        let mut last = None;
+        for provider in &self.providers {
+            match self.call(provider.explain_change(change)).await {
+                Ok(explanation) => return Ok(explanation),
+                Err(e) => last = Some(Self::recover(provider.as_ref(), e)?),
+            }
+        }
+        Err(exhausted(last))
+    }
+
+    async fn answer_code_question(
+        &self,
+        question: &str,
+        relevant_nodes: &[GraphNode],
+        relevant_edges: &[GraphEdge],
+    ) -> Result<String> {
+        let mut last = None;
+        for provider in &self.providers {
+            match self.call(provider.answer_code_question(question, relevant_nodes, relevant_edges)).await {
+                Ok(answer) => return Ok(answer),
+                Err(e) => last = Some(Self::recover(provider.as_ref(), e)?),
+            }
+        }
+        Err(exhausted(last))
+    }
+
+    async fn stream_code_question(
+        &self,
+        question: &str,
+        relevant_nodes: &[GraphNode],
+        relevant_edges: &[GraphEdge],
+    ) -> Result<TokenStream> {
+        // Only failures before the first token fall back
+        let mut last = None;
+        for provider in &self.providers {
+            match self.call(provider.stream_code_question(question, relevant_nodes, relevant_edges)).await {
+                Ok(tokens) => return Ok(tokens),
+                Err(e) => last = Some(Self::recover(provider.as_ref(), e)?),
+            }
+        }
+        Err(exhausted(last))
+    }
+
+    /// Every provider is checked, and those failing are warned about; the
+    /// capabilities are those of the first healthy one, which requests go
+    /// to first.
+    async fn health_check(&self) -> Result<ProviderCapabilities> {
+        let mut healthy = None;
+        let mut last = None;
+        for provider in &self.providers {
+            match self.call(provider.health_check()).await {
+                Ok(capabilities) => {
+                    healthy.get_or_insert(capabilities);
+                }
+                Err(e) => {
+                    tracing::warn!("AI provider {} failed its health check: {:#}", provider.name(), e);
+                    last = Some(e);
+                }
+            }
+        }
+        healthy.ok_or_else(|| exhausted(last))
+    }
+
+    /// Any provider may be the one answering
+    fn is_billed(&self) -> bool {
+        self.providers.iter().any(|p| p.is_billed())
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
@@ -0,0 +1,231 @@
+//! Rate limiting and retries for AI providers
+//!
+//! [`LimitedProvider`] holds a provider's requests to a [`RateLimiter`]'s
+//! requests and tokens per minute, waiting for room rather than sending
+//! requests bound to be refused. Requests that fail anyway in a transient
+//! way (see [`is_transient`]) are retried with exponential backoff and
+//! jitter, so a burst of saves is spread out instead of failing at once.
+//!
+//! Retries happen before a [`FallbackProvider`](super::fallback::FallbackProvider)
+//! moves on: it only falls back once a provider's retries are used up.
+
+use super::super::bridge::{AIProvider, AnalysisContext, ProviderCapabilities, SemanticAnalysisRequest, SemanticAnalysisResult, SemanticBatchRequest, TokenStream};
+use super::super::budget::Budget;
+use super::super::change::GraphChange;
+use super::super::query::GraphQuery;
+use super::super::prompt::{change_explanation_prompt, code_question_prompt, container_summary_prompt, graph_query_prompt, node_summary_prompt, semantic_analysis_prompt, semantic_batch_prompt};
+use super::is_transient;
+use anyhow::Result;
+use canopy_core::{GraphEdge, GraphNode};
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::hash::{BuildHasher, RandomState};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+/// The span rates are counted over.
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// How much a provider may be asked, and how failures are retried.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RateLimits {
+    pub requests_per_minute: Option<u32>,
+    /// Estimated prompt tokens per minute.
+    pub tokens_per_minute: Option<u32>,
+    /// Retries of a transient failure before giving up.
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for each one after.
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        Self {
+            requests_per_minute: None,
+            tokens_per_minute: None,
+            max_retries: 3,
+            initial_backoff_ms: 500,
+            max_backoff_ms: 30_000,
+        }
+    }
+}
+
+impl RateLimits {
+    /// How long to wait before retry number `attempt`, counting from 0:
+    /// the doubled backoff, capped, less up to half of it at random so
+    /// retries of requests that failed together don't land together.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self
+            .initial_backoff_ms
+            .saturating_mul(1 << attempt.min(20))
+            .min(self.max_backoff_ms);
+        let jitter = RandomState::new().hash_one(attempt) % (delay / 2 + 1);
+        Duration::from_millis(delay - jitter)
+    }
+}
+
+/// Requests and tokens sent in the last minute, shared by every caller of
+/// a provider.
+pub struct RateLimiter {
+    requests_per_minute: Option<u32>,
+    tokens_per_minute: Option<u32>,
+    /// When each request of the window was let through, with its tokens.
+    sent: Mutex<VecDeque<(Instant, u32)>>,
+}
+
+impl RateLimiter {
+    pub fn new(limits: &RateLimits) -> Self {
+        Self {
+            requests_per_minute: limits.requests_per_minute,
+            tokens_per_minute: limits.tokens_per_minute,
+            sent: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Wait until a request of `tokens` fits in the limits, and count it.
+    /// A request larger than the whole token limit goes alone.
+    pub async fn acquire(&self, tokens: u32) {
+        loop {
+            let wait = {
+                let mut sent = self.sent.lock().await;
+                let now = Instant::now();
+                while sent.front().is_some_and(|&(at, _)| now.duration_since(at) >= WINDOW) {
+                    sent.pop_front();
+                }
+                let used: u32 = sent.iter().map(|&(_, tokens)| tokens).sum();
+                let full = self.requests_per_minute.is_some_and(|max| sent.len() as u32 >= max)
+                    || self.tokens_per_minute.is_some_and(|max| used.saturating_add(tokens) > max);
+                match sent.front() {
+                    Some(&(oldest, _)) if full => WINDOW - now.duration_since(oldest),
+                    _ => {
+                        sent.push_back((now, tokens));
+                        return;
+                    }
+                }
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// A provider held to [`RateLimits`].
+pub struct LimitedProvider {
+    inner: Box<dyn AIProvider>,
+    limits: RateLimits,
+    limiter: Arc<RateLimiter>,
+}
+
+impl LimitedProvider {
+    pub fn new(inner: Box<dyn AIProvider>, limits: RateLimits) -> Self {
+        let limiter = Arc::new(RateLimiter::new(&limits));
+        Self { inner, limits, limiter }
+    }
+
+    /// Count requests against `limiter`, shared with other providers using
+    /// the same account, rather than a limiter of its own.
+    pub fn with_limiter(mut self, limiter: Arc<RateLimiter>) -> Self {
+        self.limiter = limiter;
+        self
+    }
+
+    /// Send a request of about `prompt` once the limits allow, retrying
+    /// transient failures.
+    async fn call<T, F>(&self, prompt: &str, request: impl Fn() -> F) -> Result<T>
+    where
+        F: Future<Output = Result<T>>,
+    {
+        let tokens = Budget::estimate_tokens(prompt);
+        let mut attempt = 0;
+        loop {
+            self.limiter.acquire(tokens).await;
+            match request().await {
+                Err(e) if attempt < self.limits.max_retries && is_transient(&e) => {
+                    let delay = self.limits.backoff(attempt);
+                    tracing::warn!("AI provider {} failed, retrying in {:?}: {}", self.inner.name(), delay, e);
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl AIProvider for LimitedProvider {
+    async fn analyze_semantic_relationships(
+        &self,
+        request: SemanticAnalysisRequest,
+    ) -> Result<SemanticAnalysisResult> {
+        let prompt = semantic_analysis_prompt(&request.source_node, &request.candidate_nodes, &request.context, &request.relationship_types);
+        self.call(&prompt, || self.inner.analyze_semantic_relationships(request.clone())).await
+    }
+
+    async fn analyze_batch(&self, batch: SemanticBatchRequest) -> Result<SemanticAnalysisResult> {
+        let prompt = semantic_batch_prompt(&batch.source_nodes, &batch.candidate_nodes, &batch.context, &batch.relationship_types);
+        self.call(&prompt, || self.inner.analyze_batch(batch.clone())).await
+    }
+
+    async fn generate_node_summary(
+        &self,
+        node: &GraphNode,
+        context: &AnalysisContext,
+    ) -> Result<String> {
+        let prompt = node_summary_prompt(node, context);
+        self.call(&prompt, || self.inner.generate_node_summary(node, context)).await
+    }
+
+    async fn translate_question(&self, question: &str) -> Result<GraphQuery> {
+        let prompt = graph_query_prompt(question);
+        self.call(&prompt, || self.inner.translate_question(question)).await
+    }
+
+    async fn summarize_container(&self, node: &GraphNode, children: &[GraphNode]) -> Result<String> {
+        let prompt = container_summary_prompt(node, children);
+        self.call(&prompt, || self.inner.summarize_container(node, children)).await
+    }
+
+    async fn explain_change(&self, change: &GraphChange) -> Result<String> {
+        let prompt = change_explanation_prompt(change);
+        self.call(&prompt, || self.inner.explain_change(change)).await
+    }
+
+    async fn answer_code_question(
+        &self,
+        question: &str,
+        relevant_nodes: &[GraphNode],
+        relevant_edges: &[GraphEdge],
+    ) -> Result<String> {
+        let prompt = code_question_prompt(question, relevant_nodes, relevant_edges);
+        self.call(&prompt, || self.inner.answer_code_question(question, relevant_nodes, relevant_edges)).await
+    }
+
+    async fn stream_code_question(
+        &self,
+        question: &str,
+        relevant_nodes: &[GraphNode],
+        relevant_edges: &[GraphEdge],
+    ) -> Result<TokenStream> {
+        // Only failures before the first token are retried
+        let prompt = code_question_prompt(question, relevant_nodes, relevant_edges);
+        self.call(&prompt, || self.inner.stream_code_question(question, relevant_nodes, relevant_edges)).await
+    }
+
+    /// Checked at once, so a bad key shows without waiting on retries.
+    async fn health_check(&self) -> Result<ProviderCapabilities> {
+        self.inner.health_check().await
+    }
+
+    fn is_billed(&self) -> bool {
+        self.inner.is_billed()
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+}
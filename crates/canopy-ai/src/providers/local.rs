@@ -6,6 +6,12 @@ use canopy_core::{GraphNode, GraphEdge};
 
 pub struct LocalProvider;
 
+impl Default for LocalProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl LocalProvider {
     pub fn new() -> Self {
         Self
@@ -55,6 +61,8 @@ impl AIProvider for LocalProvider {
             relationships,
             explanation: "Heuristic-based analysis without AI".to_string(),
             tokens_used: 0,
+            provider: self.name().to_string(),
+            usage: Vec::new(),
         })
     }
     
@@ -72,7 +80,7 @@ impl AIProvider for LocalProvider {
                 format!("Class {} that encapsulates related functionality.", node.name)
             }
             _ => {
-                format!("{} {} in the codebase.", format!("{:?}", node.kind), node.name)
+                format!("{:?} {} in the codebase.", node.kind, node.name)
             }
         };
         Ok(summary)
@@ -87,8 +95,8 @@ impl AIProvider for LocalProvider {
         // Simple template-based answers
         let answer = if question.to_lowercase().contains("what does") {
             if let Some(node) = relevant_nodes.first() {
-                format!("{} {} appears to be a {} that {} based on its name and connections.",
-                    format!("{:?}", node.kind),
+                format!("{:?} {} appears to be a {} that {} based on its name and connections.",
+                    node.kind,
                     node.name,
                     format!("{:?}", node.kind).to_lowercase(),
                     if node.kind == canopy_core::NodeKind::Function { "performs specific operations" } else { "encapsulates functionality" }
@@ -107,6 +115,10 @@ impl AIProvider for LocalProvider {
         Ok(answer)
     }
     
+    fn is_billed(&self) -> bool {
+        false
+    }
+
     fn name(&self) -> &str {
         "Local (Heuristic)"
     }
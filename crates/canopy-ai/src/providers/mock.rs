@@ -0,0 +1,165 @@
+//! Scripted provider for tests
+//!
+//! [`MockProvider`] answers from a script instead of a model: each request
+//! takes the next [`MockReply`], so tests of what happens around a provider
+//! (budgets, retries, fallbacks, caching, review) can say exactly what it
+//! answers, including API errors, and check what it was asked. Requests
+//! past the end of the script get an empty analysis or an empty answer.
+//!
+//! Clones share the script and the requests, so a test can keep one to
+//! check after handing another to a wrapper.
+
+use super::ApiError;
+use crate::bridge::{
+    AIProvider, AnalysisContext, InferredRelationship, SemanticAnalysisRequest, SemanticAnalysisResult,
+    SemanticBatchRequest, TokenUsage,
+};
+use anyhow::{bail, Result};
+use canopy_core::{GraphEdge, GraphNode, NodeId};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// Model named in the usage of mock analyses.
+pub const MOCK_MODEL: &str = "mock-model";
+
+/// What a [`MockProvider`] answers a request with.
+#[derive(Debug, Clone)]
+pub enum MockReply {
+    /// An analysis finding these relationships, for analysis requests.
+    Relationships(Vec<InferredRelationship>),
+    /// Text, for summaries and answers.
+    Text(String),
+    /// An error with this HTTP status, as from the provider's API.
+    Error(u16),
+}
+
+/// A request a [`MockProvider`] was sent.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MockRequest {
+    /// Analysis of these source nodes, against these candidates.
+    Analysis { sources: Vec<NodeId>, candidates: Vec<NodeId> },
+    Summary(NodeId),
+    Question(String),
+}
+
+#[derive(Clone)]
+pub struct MockProvider {
+    name: String,
+    script: Arc<Mutex<VecDeque<MockReply>>>,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+    input_tokens: u32,
+    output_tokens: u32,
+}
+
+impl Default for MockProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockProvider {
+    pub fn new() -> Self {
+        Self {
+            name: "Mock".to_string(),
+            script: Arc::new(Mutex::new(VecDeque::new())),
+            requests: Arc::new(Mutex::new(Vec::new())),
+            input_tokens: 100,
+            output_tokens: 20,
+        }
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    /// Tokens each analysis reports using, in and out.
+    pub fn with_usage(mut self, input_tokens: u32, output_tokens: u32) -> Self {
+        self.input_tokens = input_tokens;
+        self.output_tokens = output_tokens;
+        self
+    }
+
+    /// Answer the next request not yet scripted with `reply`.
+    pub fn reply(self, reply: MockReply) -> Self {
+        self.script.lock().unwrap().push_back(reply);
+        self
+    }
+
+    /// The requests sent so far, oldest first. Retried requests appear
+    /// once for each attempt.
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Replies scripted and not yet given.
+    pub fn remaining(&self) -> usize {
+        self.script.lock().unwrap().len()
+    }
+
+    fn next(&self, request: MockRequest) -> Option<MockReply> {
+        self.requests.lock().unwrap().push(request);
+        self.script.lock().unwrap().pop_front()
+    }
+
+    fn error(&self, status: u16) -> anyhow::Error {
+        ApiError { provider: self.name.clone(), status, body: "Scripted failure".to_string() }.into()
+    }
+
+    fn analysis(&self, request: MockRequest) -> Result<SemanticAnalysisResult> {
+        let relationships = match self.next(request) {
+            Some(MockReply::Relationships(relationships)) => relationships,
+            Some(MockReply::Error(status)) => return Err(self.error(status)),
+            Some(MockReply::Text(text)) => bail!("Mock provider scripted text for an analysis: {}", text),
+            None => Vec::new(),
+        };
+        Ok(SemanticAnalysisResult {
+            explanation: format!("{} relationships scripted", relationships.len()),
+            relationships,
+            tokens_used: self.input_tokens + self.output_tokens,
+            provider: self.name.clone(),
+            usage: vec![TokenUsage {
+                model: MOCK_MODEL.to_string(),
+                input_tokens: self.input_tokens,
+                output_tokens: self.output_tokens,
+            }],
+        })
+    }
+
+    fn text(&self, request: MockRequest) -> Result<String> {
+        match self.next(request) {
+            Some(MockReply::Text(text)) => Ok(text),
+            Some(MockReply::Error(status)) => Err(self.error(status)),
+            Some(MockReply::Relationships(_)) => bail!("Mock provider scripted relationships for a text reply"),
+            None => Ok(String::new()),
+        }
+    }
+}
+
+fn ids(nodes: &[GraphNode]) -> Vec<NodeId> {
+    nodes.iter().map(|n| n.id).collect()
+}
+
+#[async_trait::async_trait]
+impl AIProvider for MockProvider {
+    async fn analyze_semantic_relationships(&self, request: SemanticAnalysisRequest) -> Result<SemanticAnalysisResult> {
+        let candidates = ids(&request.candidate_nodes);
+        self.analysis(MockRequest::Analysis { sources: vec![request.source_node.id], candidates })
+    }
+
+    async fn analyze_batch(&self, batch: SemanticBatchRequest) -> Result<SemanticAnalysisResult> {
+        self.analysis(MockRequest::Analysis { sources: ids(&batch.source_nodes), candidates: ids(&batch.candidate_nodes) })
+    }
+
+    async fn generate_node_summary(&self, node: &GraphNode, _context: &AnalysisContext) -> Result<String> {
+        self.text(MockRequest::Summary(node.id))
+    }
+
+    async fn answer_code_question(&self, question: &str, _nodes: &[GraphNode], _edges: &[GraphEdge]) -> Result<String> {
+        self.text(MockRequest::Question(question.to_string()))
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
@@ -3,16 +3,222 @@
 pub mod openai;
 pub mod anthropic;
 pub mod local;
+pub mod fallback;
+pub mod limit;
+#[cfg(any(test, feature = "testing"))]
+pub mod mock;
+pub mod openrouter;
+pub mod queue;
+pub mod similarity;
+mod sse;
+mod structured;
 
 use super::bridge::AIProvider;
-use anyhow::Result;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// An error status from a provider's API.
+#[derive(Debug, thiserror::Error)]
+#[error("{provider} API error ({status}): {body}")]
+pub struct ApiError {
+    pub provider: String,
+    pub status: u16,
+    pub body: String,
+}
+
+impl ApiError {
+    /// The error of a response with an error status: the message of its
+    /// JSON error body, if it has one, or else the whole body.
+    pub async fn from_response(provider: &str, response: reqwest::Response) -> Self {
+        let status = response.status().as_u16();
+        let text = response.text().await.unwrap_or_default();
+        let body = match serde_json::from_str::<ErrorResponse>(&text) {
+            Ok(response) => response.error.describe(),
+            Err(_) => text,
+        };
+        Self { provider: provider.to_string(), status, body }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorResponse {
+    error: ErrorBody,
+}
+
+/// The `error` object OpenAI-compatible APIs answer failures with. Routers
+/// also send it, with a success status, when an upstream provider fails
+/// part way.
+#[derive(Debug, Deserialize)]
+pub struct ErrorBody {
+    pub message: String,
+    /// An HTTP status from routers, a name such as `invalid_api_key` from
+    /// OpenAI.
+    #[serde(default)]
+    pub code: serde_json::Value,
+    #[serde(default)]
+    pub metadata: ErrorMetadata,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ErrorMetadata {
+    /// The upstream provider that failed, from routers.
+    pub provider_name: Option<String>,
+}
+
+impl ErrorBody {
+    fn describe(&self) -> String {
+        match &self.metadata.provider_name {
+            Some(upstream) => format!("{} (from {})", self.message, upstream),
+            None => self.message.clone(),
+        }
+    }
+
+    /// The error of `provider`'s response carrying this body. Without an
+    /// HTTP status the upstream is taken to have failed.
+    pub fn into_api_error(self, provider: &str) -> ApiError {
+        let status = self.code.as_u64().and_then(|code| u16::try_from(code).ok()).unwrap_or(502);
+        ApiError { provider: provider.to_string(), status, body: self.describe() }
+    }
+}
+
+/// Whether a provider's request failed in a way another provider, or a
+/// later attempt, might not: a rate limit, a server error, a timeout or an
+/// unreachable server. Bad keys and malformed answers are not transient.
+pub fn is_transient(error: &anyhow::Error) -> bool {
+    if let Some(api) = error.downcast_ref::<ApiError>() {
+        return api.status == 429 || api.status >= 500;
+    }
+    if let Some(request) = error.downcast_ref::<reqwest::Error>() {
+        return request.is_timeout() || request.is_connect();
+    }
+    error.downcast_ref::<tokio::time::error::Elapsed>().is_some()
+}
+
+/// Settings for [`create_provider_with`]. Those left unset take the
+/// provider's defaults.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ProviderOptions {
+    pub api_key: Option<String>,
+    /// Root of the provider's API, e.g. `http://localhost:1234/v1`.
+    pub base_url: Option<String>,
+    pub model: Option<String>,
+    /// Rate limits and retries by provider name. Providers not named get
+    /// [`limit::RateLimits::default`].
+    pub rate_limits: HashMap<String, limit::RateLimits>,
+    /// Routing of the `openrouter` provider.
+    pub openrouter: openrouter::OpenRouterOptions,
+    /// Where the `embeddings` provider keeps its local model.
+    pub model_dir: Option<PathBuf>,
+    /// Requests sent at once, by every caller together; the rest wait
+    /// their turn. [`queue::DEFAULT_MAX_CONCURRENT`] unless set.
+    pub max_concurrent: Option<usize>,
+}
+
+/// The provider used when none is named: `openai` with an API key, and
+/// otherwise the `embeddings` provider, which needs neither a key nor a
+/// network.
+pub fn default_provider_name(api_key: Option<&str>) -> &'static str {
+    if api_key.is_some_and(|key| !key.is_empty()) { "openai" } else { "embeddings" }
+}
 
 /// Factory function to create AI providers
 pub fn create_provider(provider_name: &str, api_key: Option<String>) -> Result<Box<dyn AIProvider>> {
+    create_provider_with(provider_name, &ProviderOptions { api_key, ..Default::default() })
+}
+
+/// Create an AI provider with `options`. The `custom` provider talks to any
+/// OpenAI-compatible server, so it needs a `base_url` and a `model`. The
+/// `openrouter` provider's `model` may be a comma-separated list of model
+/// slugs to fall back through.
+///
+/// The `embeddings` provider needs no API: it proposes edges between nodes
+/// named and described alike, by a local embedding model with the
+/// `local-embeddings` feature and by hashing their words without it.
+///
+/// A comma-separated list of names (`openai,anthropic,local`) makes a
+/// [`fallback::FallbackProvider`] trying each in turn, all with `options`.
+///
+/// Every provider but `local` and `embeddings` is held to its
+/// [`ProviderOptions::rate_limits`], and all of them to
+/// [`ProviderOptions::max_concurrent`] requests at once.
+pub fn create_provider_with(provider_name: &str, options: &ProviderOptions) -> Result<Box<dyn AIProvider>> {
+    let provider = create_limited_provider(provider_name, options)?;
+    let queue = queue::WorkQueue::new(options.max_concurrent.unwrap_or(queue::DEFAULT_MAX_CONCURRENT));
+    Ok(Box::new(queue::QueuedProvider::new(provider, std::sync::Arc::new(queue))))
+}
+
+fn create_limited_provider(provider_name: &str, options: &ProviderOptions) -> Result<Box<dyn AIProvider>> {
+    if provider_name.contains(',') {
+        let providers = provider_name
+            .split(',')
+            .map(|name| create_limited_provider(name.trim(), options))
+            .collect::<Result<Vec<_>>>()?;
+        return Ok(Box::new(fallback::FallbackProvider::new(providers)));
+    }
+    let provider = create_unlimited_provider(provider_name, options)?;
+    if matches!(provider_name, "local" | "embeddings") {
+        return Ok(provider);
+    }
+    let limits = options.rate_limits.get(provider_name).cloned().unwrap_or_default();
+    Ok(Box::new(limit::LimitedProvider::new(provider, limits)))
+}
+
+fn create_unlimited_provider(provider_name: &str, options: &ProviderOptions) -> Result<Box<dyn AIProvider>> {
+    let api_key = options.api_key.clone();
     match provider_name {
-        "openai" => Ok(Box::new(openai::OpenAIProvider::new(api_key))),
-        "anthropic" => Ok(Box::new(anthropic::AnthropicProvider::new(api_key))),
+        "openai" => {
+            let mut provider = openai::OpenAIProvider::new(api_key);
+            if let Some(base_url) = &options.base_url {
+                provider = provider.with_base_url(base_url);
+            }
+            if let Some(model) = &options.model {
+                provider = provider.with_model(model.clone());
+            }
+            Ok(Box::new(provider))
+        }
+        "custom" => {
+            let base_url = options.base_url.as_deref().context("The custom AI provider needs a base URL")?;
+            let model = options.model.as_deref().context("The custom AI provider needs a model")?;
+            Ok(Box::new(openai::OpenAIProvider::compatible(base_url, model, api_key)))
+        }
+        "anthropic" => {
+            let mut provider = anthropic::AnthropicProvider::new(api_key);
+            if let Some(base_url) = &options.base_url {
+                provider = provider.with_base_url(base_url);
+            }
+            if let Some(model) = &options.model {
+                provider = provider.with_model(model.clone());
+            }
+            Ok(Box::new(provider))
+        }
+        "openrouter" => {
+            let mut routing = options.openrouter.clone();
+            if let Some(models) = &options.model {
+                routing.models = models.split(',').map(|m| m.trim().to_string()).filter(|m| !m.is_empty()).collect();
+            }
+            let mut provider = openrouter::provider(api_key, &routing);
+            if let Some(base_url) = &options.base_url {
+                provider = provider.with_base_url(base_url);
+            }
+            Ok(Box::new(provider))
+        }
         "local" => Ok(Box::new(local::LocalProvider::new())),
+        "embeddings" => Ok(Box::new(similarity::SimilarityProvider::new(local_embedder(options)?))),
         _ => anyhow::bail!("Unknown AI provider: {}", provider_name),
     }
-}
\ No newline at end of file
+}
+/// The embedder of the `embeddings` provider.
+#[cfg(feature = "local-embeddings")]
+fn local_embedder(options: &ProviderOptions) -> Result<Box<dyn crate::embedding::Embedder>> {
+    let model_dir = options.model_dir.clone().unwrap_or_else(|| PathBuf::from(".canopy/models"));
+    Ok(Box::new(crate::embedding::FastEmbedder::new(&model_dir)?))
+}
+
+/// The embedder of the `embeddings` provider.
+#[cfg(not(feature = "local-embeddings"))]
+fn local_embedder(_options: &ProviderOptions) -> Result<Box<dyn crate::embedding::Embedder>> {
+    Ok(Box::new(crate::embedding::HashingEmbedder::new()))
+}
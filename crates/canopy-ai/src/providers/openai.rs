@@ -1,14 +1,33 @@
 //! OpenAI provider implementation
 
-use super::super::bridge::{AIProvider, SemanticAnalysisRequest, SemanticAnalysisResult, InferredRelationship, SemanticRelationship, AnalysisContext};
+use super::{sse, ApiError, ErrorBody};
+use super::structured::{null_as_empty, repair_prompt, report_text, AnalysisReport, ReportRequest, ToolCall};
+use super::super::prompt;
+use super::super::change::GraphChange;
+use super::super::query::GraphQuery;
+use super::super::bridge::{AIProvider, SemanticAnalysisRequest, SemanticBatchRequest, SemanticAnalysisResult, AnalysisContext, ProviderCapabilities, TokenStream, TokenUsage};
 use anyhow::{Result, Context};
 use canopy_core::{GraphNode, GraphEdge, NodeId};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Where requests go unless a base URL is given: OpenRouter, which serves
+/// OpenAI's models under their OpenAI names.
+pub const DEFAULT_BASE_URL: &str = "https://openrouter.ai/api/v1";
 
 pub struct OpenAIProvider {
     client: reqwest::Client,
     api_key: String,
     model: String,
+    /// Root of the API, without the trailing `/chat/completions`.
+    base_url: String,
+    name: String,
+    /// Fields added to every request, for servers reading more than
+    /// OpenAI's.
+    extra_body: Map<String, Value>,
+    /// Path under the API checking the key, for servers that list their
+    /// models to anyone.
+    key_check: Option<String>,
 }
 
 impl OpenAIProvider {
@@ -22,13 +41,203 @@ impl OpenAIProvider {
             client: reqwest::Client::new(),
             api_key,
             model: "gpt-4o-mini".to_string(),
+            base_url: DEFAULT_BASE_URL.to_string(),
+            name: "OpenAI (via OpenRouter)".to_string(),
+            extra_body: Map::new(),
+            key_check: None,
         }
     }
+
+    /// A provider for any server with an OpenAI-compatible chat completions
+    /// API at `base_url` (LM Studio, vLLM, llama.cpp's server, OpenRouter).
+    /// Servers that don't authenticate need no key.
+    pub fn compatible(base_url: &str, model: &str, api_key: Option<String>) -> Self {
+        let base_url = base_url.trim_end_matches('/');
+        Self {
+            client: reqwest::Client::new(),
+            api_key: api_key.unwrap_or_default(),
+            model: model.to_string(),
+            base_url: base_url.to_string(),
+            name: format!("OpenAI-compatible ({})", base_url),
+            extra_body: Map::new(),
+            key_check: None,
+        }
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    /// Add `fields` to the body of every request.
+    pub fn with_extra_body(mut self, fields: Map<String, Value>) -> Self {
+        self.extra_body.extend(fields);
+        self
+    }
+    
+    /// Check the key by a GET of `path` under the API in health checks,
+    /// besides listing the models.
+    pub fn with_key_check(mut self, path: &str) -> Self {
+        self.key_check = Some(path.trim_start_matches('/').to_string());
+        self
+    }
     
     pub fn with_model(mut self, model: String) -> Self {
         self.model = model;
         self
     }
+
+    /// Send requests to the API at `base_url`, e.g. `http://localhost:1234/v1`.
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Post a chat completion request, failing on an error status.
+    async fn send(&self, request: &(impl Serialize + Sync)) -> Result<reqwest::Response> {
+        let mut body = serde_json::to_value(request)?;
+        if let Value::Object(fields) = &mut body {
+            fields.extend(self.extra_body.clone());
+        }
+        let mut builder = self.client
+            .post(format!("{}/chat/completions", self.base_url))
+            .header("HTTP-Referer", "https://github.com/openclaw/openclaw")
+            .header("X-Title", "Canopy")
+            .json(&body);
+        if !self.api_key.is_empty() {
+            builder = builder.bearer_auth(&self.api_key);
+        }
+        let response = builder
+            .send()
+            .await
+            .with_context(|| format!("Failed to send request to {}", self.base_url))?;
+
+        if !response.status().is_success() {
+            return Err(ApiError::from_response(&self.name, response).await.into());
+        }
+        Ok(response)
+    }
+
+    /// Get `path` under the API, failing on an error status.
+    async fn get(&self, path: &str) -> Result<reqwest::Response> {
+        let mut builder = self.client.get(format!("{}/{}", self.base_url, path));
+        if !self.api_key.is_empty() {
+            builder = builder.bearer_auth(&self.api_key);
+        }
+        let response = builder
+            .send()
+            .await
+            .with_context(|| format!("Failed to send request to {}", self.base_url))?;
+        if !response.status().is_success() {
+            return Err(ApiError::from_response(&self.name, response).await.into());
+        }
+        Ok(response)
+    }
+
+    /// Post a chat completion request and read the whole completion.
+    /// Routers may report an upstream failure in the body of a success.
+    async fn complete(&self, request: &(impl Serialize + Sync)) -> Result<OpenAIResponse> {
+        let response = self.send(request).await?;
+        let response: OpenAIResponse = response.json().await.with_context(|| format!("Failed to parse {} response", self.name))?;
+        if let Some(error) = response.error {
+            return Err(error.into_api_error(&self.name).into());
+        }
+        anyhow::ensure!(!response.choices.is_empty(), "{} sent no completion", self.name);
+        Ok(response)
+    }
+
+    /// The model to price `usage` of a response by: the one asked for,
+    /// unless a router answered with another.
+    fn usage_of(&self, response: &OpenAIResponse) -> Option<TokenUsage> {
+        let model = match response.model.as_deref() {
+            Some(served) if !served.starts_with(&self.model) => served,
+            _ => &self.model,
+        };
+        response.usage.as_ref().map(|usage| usage.of_model(model))
+    }
+
+    /// Ask for the relationships between `nodes` through the report tool,
+    /// sending a report off its schema back once to be repaired.
+    async fn report(&self, mut request: OpenAIRequest, nodes: &[NodeId]) -> Result<SemanticAnalysisResult> {
+        let mut usages = Vec::new();
+        let mut repaired = false;
+        loop {
+            let openai_response = self.complete(&ReportRequest::new(&request)).await?;
+            usages.extend(self.usage_of(&openai_response));
+            let message = openai_response.reply()?;
+            let text = report_text(&message.content, &message.tool_calls);
+            match AnalysisReport::parse(text, nodes) {
+                Ok(report) => {
+                    return Ok(SemanticAnalysisResult {
+                        explanation: report.explanation.clone(),
+                        relationships: report.into_relationships(),
+                        tokens_used: usages.iter().map(|u| u.input_tokens + u.output_tokens).sum(),
+                        provider: self.name.clone(),
+                        usage: usages,
+                    });
+                }
+                Err(e) if !repaired => {
+                    tracing::warn!("{} sent a malformed report, asking again: {:#}", self.name, e);
+                    request.messages.push(OpenAIMessage {
+                        role: "assistant".to_string(),
+                        content: text.to_string(),
+                    });
+                    request.messages.push(OpenAIMessage {
+                        role: "user".to_string(),
+                        content: repair_prompt(&e),
+                    });
+                    repaired = true;
+                }
+                Err(e) => return Err(e.context(format!("Failed to parse {} report", self.name))),
+            }
+        }
+    }
+
+    /// The request answering `question` from the graph.
+    fn question_request(&self, question: &str, relevant_nodes: &[GraphNode], relevant_edges: &[GraphEdge]) -> OpenAIRequest {
+        let nodes_desc = relevant_nodes.iter()
+            .map(|n| format!("- {} ({:?}): {}", n.name, n.kind, n.qualified_name))
+            .collect::<Vec<_>>()
+            .join("\n");
+            
+        let edges_desc = relevant_edges.iter()
+            .map(|e| format!("- {} -> {} ({:?})", 
+                e.source.0, e.target.0, e.kind))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let prompt = format!(
+            r#"Based on this code graph information, answer the question:
+
+Question: {}
+
+Relevant code elements:
+{}
+
+Relationships:
+{}
+
+Provide a clear, accurate answer based on the graph data."#,
+            question, nodes_desc, edges_desc
+        );
+
+        OpenAIRequest {
+            model: self.model.clone(),
+            messages: vec![
+                OpenAIMessage {
+                    role: "system".to_string(),
+                    content: "You are a code analysis assistant. Answer questions accurately based on provided code graph data.".to_string(),
+                },
+                OpenAIMessage {
+                    role: "user".to_string(),
+                    content: prompt,
+                },
+            ],
+            temperature: 0.2,
+            max_tokens: 500,
+            stream: false,
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -37,6 +246,9 @@ struct OpenAIRequest {
     messages: Vec<OpenAIMessage>,
     temperature: f32,
     max_tokens: u32,
+    /// Send the completion as server-sent events, a token or so at a time.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -47,34 +259,73 @@ struct OpenAIMessage {
 
 #[derive(Debug, Deserialize)]
 struct OpenAIResponse {
+    #[serde(default)]
     choices: Vec<OpenAIChoice>,
     usage: Option<OpenAIUsage>,
+    /// The model that answered.
+    model: Option<String>,
+    error: Option<ErrorBody>,
+}
+
+impl OpenAIResponse {
+    /// The first choice's message. Servers may send no choices when a
+    /// content filter or an upstream error stops the completion.
+    fn reply(&self) -> Result<&OpenAIReply> {
+        self.choices.first().map(|choice| &choice.message).ok_or_else(|| anyhow::anyhow!("empty choices"))
+    }
 }
 
 #[derive(Debug, Deserialize)]
 struct OpenAIChoice {
-    message: OpenAIMessage,
+    message: OpenAIReply,
 }
 
+/// A completion's message: text, or calls of the tools offered.
 #[derive(Debug, Deserialize)]
-struct OpenAIUsage {
-    total_tokens: u32,
+struct OpenAIReply {
+    #[serde(default, deserialize_with = "null_as_empty")]
+    content: String,
+    #[serde(default)]
+    tool_calls: Vec<ToolCall>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct SemanticAnalysisResponse {
-    relationships: Vec<InferredRelationshipJson>,
-    explanation: String,
+/// The models a server offers, from `GET /models`.
+#[derive(Debug, Deserialize)]
+struct ModelList {
+    #[serde(default)]
+    data: Vec<ModelInfo>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct InferredRelationshipJson {
-    source_id: u64,
-    target_id: u64,
-    relationship: String,
-    confidence: f32,
-    explanation: String,
-    line_reference: Option<u32>,
+/// A model offered. OpenAI gives only its id; routers and local servers
+/// add what it can do.
+#[derive(Debug, Deserialize)]
+struct ModelInfo {
+    id: String,
+    /// From OpenRouter.
+    context_length: Option<u32>,
+    /// From vLLM.
+    max_model_len: Option<u32>,
+    /// Request parameters the model takes, such as `tools`, from
+    /// OpenRouter.
+    supported_parameters: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAIUsage {
+    #[serde(default)]
+    prompt_tokens: u32,
+    #[serde(default)]
+    completion_tokens: u32,
+}
+
+impl OpenAIUsage {
+    fn of_model(&self, model: &str) -> TokenUsage {
+        TokenUsage {
+            model: model.to_string(),
+            input_tokens: self.prompt_tokens,
+            output_tokens: self.completion_tokens,
+        }
+    }
 }
 
 #[async_trait::async_trait]
@@ -130,8 +381,8 @@ Return JSON in this format:
             request.source_node.line_end.unwrap_or(0),
             request.source_node.qualified_name,
             request.candidate_nodes.iter()
-                .map(|n| format!("- {} (ID: {}, kind: {}, lines: {}-{})", 
-                    n.name, n.id.0, format!("{:?}", n.kind), 
+                .map(|n| format!("- {} (ID: {}, kind: {:?}, lines: {}-{})", 
+                    n.name, n.id.0, n.kind, 
                     n.line_start.unwrap_or(0), n.line_end.unwrap_or(0)))
                 .collect::<Vec<_>>()
                 .join("\n"),
@@ -152,59 +403,40 @@ Return JSON in this format:
             ],
             temperature: 0.1,
             max_tokens: 2000,
+            stream: false,
         };
 
-        let response = self.client
-            .post("https://openrouter.ai/api/v1/chat/completions")
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .header("HTTP-Referer", "https://github.com/openclaw/openclaw")
-            .header("X-Title", "Canopy")
-            .json(&openai_request)
-            .send()
-            .await
-            .context("Failed to send request to OpenRouter")?;
-
-        if !response.status().is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            anyhow::bail!("OpenRouter API error: {}", error_text);
-        }
-
-        let openai_response: OpenAIResponse = response.json().await?;
-        let content = &openai_response.choices[0].message.content;
-        
-        // Extract JSON from the response
-        let json_start = content.find('{').unwrap_or(0);
-        let json_end = content.rfind('}').unwrap_or(content.len() - 1) + 1;
-        let json_str = &content[json_start..json_end];
-        
-        let analysis_response: SemanticAnalysisResponse = serde_json::from_str(json_str)
-            .context("Failed to parse OpenAI response JSON")?;
+        let nodes: Vec<NodeId> = std::iter::once(&request.source_node)
+            .chain(&request.candidate_nodes)
+            .map(|n| n.id)
+            .collect();
+        self.report(openai_request, &nodes).await
+    }
 
-        let tokens_used = openai_response.usage.map(|u| u.total_tokens).unwrap_or(0);
-        
-        let relationships = analysis_response.relationships.into_iter()
-            .map(|rel| InferredRelationship {
-                source_id: NodeId(rel.source_id),
-                target_id: NodeId(rel.target_id),
-                relationship: match rel.relationship.as_str() {
-                    "Calls" => SemanticRelationship::Calls,
-                    "DependsOn" => SemanticRelationship::DependsOn,
-                    "Uses" => SemanticRelationship::Uses,
-                    "Configures" => SemanticRelationship::Configures,
-                    _ => SemanticRelationship::SemanticReference,
+    async fn analyze_batch(&self, batch: SemanticBatchRequest) -> Result<SemanticAnalysisResult> {
+        let prompt = prompt::semantic_batch_prompt(&batch.source_nodes, &batch.candidate_nodes, &batch.context, &batch.relationship_types);
+        let openai_request = OpenAIRequest {
+            model: self.model.clone(),
+            messages: vec![
+                OpenAIMessage {
+                    role: "system".to_string(),
+                    content: prompt::CODE_ANALYSIS_SYSTEM_PROMPT.to_string(),
                 },
-                confidence: rel.confidence,
-                explanation: rel.explanation,
-                line_reference: rel.line_reference,
-            })
-            .collect();
+                OpenAIMessage {
+                    role: "user".to_string(),
+                    content: prompt,
+                },
+            ],
+            temperature: 0.1,
+            max_tokens: 4000,
+            stream: false,
+        };
 
-        Ok(SemanticAnalysisResult {
-            relationships,
-            explanation: analysis_response.explanation,
-            tokens_used,
-        })
+        let nodes: Vec<NodeId> = batch.source_nodes.iter().chain(&batch.candidate_nodes).map(|n| n.id).collect();
+        let mut result = self.report(openai_request, &nodes).await?;
+        // Only the batch's own nodes are sources
+        result.relationships.retain(|rel| batch.source_nodes.iter().any(|n| n.id == rel.source_id));
+        Ok(result)
     }
     
     async fn generate_node_summary(
@@ -213,7 +445,7 @@ Return JSON in this format:
         context: &AnalysisContext,
     ) -> Result<String> {
         let prompt = format!(
-            r#"Summarize what this {} does in one sentence:
+            r#"Summarize what this {:?} does in one sentence:
 
 File: {}
 Name: {}
@@ -221,12 +453,12 @@ Lines: {}-{}
 Code: {}
 
 Context: {:?}"#,
-            format!("{:?}", node.kind),
+            node.kind,
             context.file_path.display(),
             node.name,
             node.line_start.unwrap_or(0),
             node.line_end.unwrap_or(0),
-            node.qualified_name,
+            prompt::source_code(context, node),
             context.enclosing_context
         );
 
@@ -244,85 +476,129 @@ Context: {:?}"#,
             ],
             temperature: 0.3,
             max_tokens: 150,
+            stream: false,
         };
 
-        let response = self.client
-            .post("https://openrouter.ai/api/v1/chat/completions")
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .header("HTTP-Referer", "https://github.com/openclaw/openclaw")
-            .header("X-Title", "Canopy")
-            .json(&openai_request)
-            .send()
-            .await?;
-
-        let openai_response: OpenAIResponse = response.json().await?;
-        Ok(openai_response.choices[0].message.content.trim().to_string())
+        let openai_response = self.complete(&openai_request).await?;
+        Ok(openai_response.reply()?.content.trim().to_string())
     }
-    
-    async fn answer_code_question(
-        &self,
-        question: &str,
-        relevant_nodes: &[GraphNode],
-        relevant_edges: &[GraphEdge],
-    ) -> Result<String> {
-        let nodes_desc = relevant_nodes.iter()
-            .map(|n| format!("- {} ({}): {}", n.name, format!("{:?}", n.kind), n.qualified_name))
-            .collect::<Vec<_>>()
-            .join("\n");
-            
-        let edges_desc = relevant_edges.iter()
-            .map(|e| format!("- {} -> {} ({})", 
-                e.source.0, e.target.0, format!("{:?}", e.kind)))
-            .collect::<Vec<_>>()
-            .join("\n");
 
-        let prompt = format!(
-            r#"Based on this code graph information, answer the question:
-
-Question: {}
+    async fn translate_question(&self, question: &str) -> Result<GraphQuery> {
+        let openai_request = OpenAIRequest {
+            model: self.model.clone(),
+            messages: vec![
+                OpenAIMessage {
+                    role: "system".to_string(),
+                    content: "You translate questions about code into graph queries. Return only valid JSON.".to_string(),
+                },
+                OpenAIMessage {
+                    role: "user".to_string(),
+                    content: prompt::graph_query_prompt(question),
+                },
+            ],
+            temperature: 0.0,
+            max_tokens: 300,
+            stream: false,
+        };
 
-Relevant code elements:
-{}
+        let openai_response = self.complete(&openai_request).await?;
+        GraphQuery::parse(&openai_response.reply()?.content)
+    }
 
-Relationships:
-{}
+    async fn summarize_container(&self, node: &GraphNode, children: &[GraphNode]) -> Result<String> {
+        let openai_request = OpenAIRequest {
+            model: self.model.clone(),
+            messages: vec![
+                OpenAIMessage {
+                    role: "system".to_string(),
+                    content: "You are a software architect documenting a codebase. Be concise and concrete.".to_string(),
+                },
+                OpenAIMessage {
+                    role: "user".to_string(),
+                    content: prompt::container_summary_prompt(node, children),
+                },
+            ],
+            temperature: 0.3,
+            max_tokens: 300,
+            stream: false,
+        };
 
-Provide a clear, accurate answer based on the graph data."#,
-            question, nodes_desc, edges_desc
-        );
+        let openai_response = self.complete(&openai_request).await?;
+        Ok(openai_response.reply()?.content.trim().to_string())
+    }
 
+    async fn explain_change(&self, change: &GraphChange) -> Result<String> {
         let openai_request = OpenAIRequest {
             model: self.model.clone(),
             messages: vec![
                 OpenAIMessage {
                     role: "system".to_string(),
-                    content: "You are a code analysis assistant. Answer questions accurately based on provided code graph data.".to_string(),
+                    content: "You are a senior engineer writing a project's change log. Be concise and concrete.".to_string(),
                 },
                 OpenAIMessage {
                     role: "user".to_string(),
-                    content: prompt,
+                    content: prompt::change_explanation_prompt(change),
                 },
             ],
-            temperature: 0.2,
-            max_tokens: 500,
+            temperature: 0.3,
+            max_tokens: 200,
+            stream: false,
         };
 
-        let response = self.client
-            .post("https://openrouter.ai/api/v1/chat/completions")
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .header("HTTP-Referer", "https://github.com/openclaw/openclaw")
-            .header("X-Title", "Canopy")
-            .json(&openai_request)
-            .send()
-            .await?;
+        let openai_response = self.complete(&openai_request).await?;
+        Ok(openai_response.reply()?.content.trim().to_string())
+    }
+    
+    async fn answer_code_question(
+        &self,
+        question: &str,
+        relevant_nodes: &[GraphNode],
+        relevant_edges: &[GraphEdge],
+    ) -> Result<String> {
+        let openai_request = self.question_request(question, relevant_nodes, relevant_edges);
+
+        let openai_response = self.complete(&openai_request).await?;
+        Ok(openai_response.reply()?.content.trim().to_string())
+    }
 
-        let openai_response: OpenAIResponse = response.json().await?;
-        Ok(openai_response.choices[0].message.content.trim().to_string())
+    async fn stream_code_question(
+        &self,
+        question: &str,
+        relevant_nodes: &[GraphNode],
+        relevant_edges: &[GraphEdge],
+    ) -> Result<TokenStream> {
+        let openai_request = OpenAIRequest {
+            stream: true,
+            ..self.question_request(question, relevant_nodes, relevant_edges)
+        };
+        Ok(sse::chat_deltas(self.send(&openai_request).await?))
+    }
+    
+    /// Servers listing no models, as some local ones do, are taken to
+    /// serve whatever is asked for.
+    async fn health_check(&self) -> Result<ProviderCapabilities> {
+        let models: ModelList = self.get("models").await?
+            .json()
+            .await
+            .with_context(|| format!("Failed to parse {} model list", self.name))?;
+        let model = models.data.iter().find(|m| m.id == self.model);
+        if model.is_none() && !models.data.is_empty() {
+            anyhow::bail!("{} doesn't serve the model {}", self.name, self.model);
+        }
+        if let Some(path) = &self.key_check {
+            self.get(path).await?;
+        }
+        Ok(ProviderCapabilities {
+            model: Some(self.model.clone()),
+            max_context_tokens: model.and_then(|m| m.context_length.or(m.max_model_len)),
+            supports_tools: model
+                .and_then(|m| m.supported_parameters.as_ref())
+                .is_none_or(|parameters| parameters.iter().any(|p| p == "tools")),
+            supports_streaming: true,
+        })
     }
     
     fn name(&self) -> &str {
-        "OpenAI (via OpenRouter)"
+        &self.name
     }
 }
\ No newline at end of file
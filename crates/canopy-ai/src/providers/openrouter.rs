@@ -0,0 +1,105 @@
+//! OpenRouter provider
+//!
+//! OpenRouter serves many vendors' models behind one OpenAI-compatible API,
+//! so requests are made by an [`OpenAIProvider`] that adds OpenRouter's
+//! routing to each: fallback models to try when the first is down or rate
+//! limited, and preferences for which upstream providers serve them.
+//! Usage is priced by the model that actually answered.
+
+use super::openai::OpenAIProvider;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+pub const BASE_URL: &str = "https://openrouter.ai/api/v1";
+
+/// Model used unless others are given.
+pub const DEFAULT_MODEL: &str = "anthropic/claude-3.5-sonnet";
+
+/// Which upstream providers may serve requests, and in what order. See
+/// OpenRouter's provider routing documentation for the names.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProviderPreferences {
+    /// Providers to try first, in order, e.g. `["Anthropic", "Amazon Bedrock"]`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub order: Vec<String>,
+    /// Providers never to use.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub ignore: Vec<String>,
+    /// Whether providers outside `order` may serve requests it can't.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_fallbacks: Option<bool>,
+    /// Use only providers supporting every parameter of a request, such as
+    /// the tool relationships are reported with.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub require_parameters: Option<bool>,
+    /// `"deny"` to skip providers that may store prompts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_collection: Option<String>,
+    /// `"price"`, `"throughput"` or `"latency"`, to rank providers by.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<String>,
+}
+
+impl ProviderPreferences {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Settings of the OpenRouter provider.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct OpenRouterOptions {
+    /// Model slugs, e.g. `anthropic/claude-3.5-sonnet`: the first is asked,
+    /// the others tried in turn if it fails. [`DEFAULT_MODEL`] if empty.
+    pub models: Vec<String>,
+    pub preferences: ProviderPreferences,
+}
+
+/// The fields OpenRouter reads, beyond OpenAI's, from each request.
+pub fn routing(options: &OpenRouterOptions) -> Map<String, Value> {
+    let mut routing = Map::new();
+    if options.models.len() > 1 {
+        routing.insert("models".to_string(), options.models.iter().cloned().map(Value::String).collect());
+    }
+    if !options.preferences.is_empty() {
+        routing.insert("provider".to_string(), serde_json::to_value(&options.preferences).unwrap_or_default());
+    }
+    routing
+}
+
+/// A provider asking OpenRouter, with `api_key` or else
+/// `OPENROUTER_API_KEY`, routed by `options`.
+pub fn provider(api_key: Option<String>, options: &OpenRouterOptions) -> OpenAIProvider {
+    let api_key = api_key.or_else(|| std::env::var("OPENROUTER_API_KEY").ok());
+    let model = options.models.first().map_or(DEFAULT_MODEL, String::as_str);
+    // OpenRouter lists its models to anyone, but tells a key's owner
+    // about it
+    OpenAIProvider::compatible(BASE_URL, model, api_key)
+        .with_name("OpenRouter")
+        .with_key_check("key")
+        .with_extra_body(routing(options))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_routing() {
+        assert!(routing(&OpenRouterOptions::default()).is_empty());
+
+        let options = OpenRouterOptions {
+            models: vec!["anthropic/claude-3.5-sonnet".to_string(), "openai/gpt-4o-mini".to_string()],
+            preferences: ProviderPreferences {
+                order: vec!["Anthropic".to_string()],
+                allow_fallbacks: Some(false),
+                ..Default::default()
+            },
+        };
+        let routing = Value::Object(routing(&options));
+        assert_eq!(routing["models"][1], "openai/gpt-4o-mini");
+        assert_eq!(routing["provider"], serde_json::json!({"order": ["Anthropic"], "allow_fallbacks": false}));
+    }
+}
@@ -0,0 +1,165 @@
+//! A work queue bounding concurrent AI requests
+//!
+//! The watcher, summaries and questions from the server all share one
+//! provider. [`QueuedProvider`] lets at most [`WorkQueue`]'s limit of their
+//! requests run at once; the rest wait in the order they came, so saving a
+//! large file queues its requests instead of opening dozens of connections.
+//!
+//! The queue sits outside any fallback, so a request holds its place while
+//! it moves from one provider to the next.
+
+use super::super::bridge::{AIProvider, AnalysisContext, ProviderCapabilities, SemanticAnalysisRequest, SemanticAnalysisResult, SemanticBatchRequest, TokenStream};
+use super::super::change::GraphChange;
+use super::super::query::GraphQuery;
+use anyhow::Result;
+use canopy_core::{GraphEdge, GraphNode};
+use futures_util::StreamExt;
+use serde::Serialize;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Requests sent at once unless another limit is set.
+pub const DEFAULT_MAX_CONCURRENT: usize = 4;
+
+/// How busy a [`WorkQueue`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct QueueStatus {
+    pub max_concurrent: usize,
+    /// Requests being sent.
+    pub running: usize,
+    /// Requests waiting for one of those to finish.
+    pub waiting: usize,
+}
+
+/// A limit on the requests running at once, first come first served.
+pub struct WorkQueue {
+    permits: Arc<Semaphore>,
+    max_concurrent: usize,
+    waiting: AtomicUsize,
+}
+
+impl WorkQueue {
+    /// A queue running up to `max_concurrent` requests, at least one.
+    pub fn new(max_concurrent: usize) -> Self {
+        let max_concurrent = max_concurrent.max(1);
+        Self {
+            permits: Arc::new(Semaphore::new(max_concurrent)),
+            max_concurrent,
+            waiting: AtomicUsize::new(0),
+        }
+    }
+
+    /// Wait for a turn, which lasts as long as the permit is held.
+    pub async fn enter(&self) -> OwnedSemaphorePermit {
+        self.waiting.fetch_add(1, Ordering::Relaxed);
+        let permit = Arc::clone(&self.permits).acquire_owned().await;
+        self.waiting.fetch_sub(1, Ordering::Relaxed);
+        permit.expect("the work queue's semaphore is never closed")
+    }
+
+    pub fn status(&self) -> QueueStatus {
+        QueueStatus {
+            max_concurrent: self.max_concurrent,
+            running: self.max_concurrent - self.permits.available_permits(),
+            waiting: self.waiting.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Default for WorkQueue {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_CONCURRENT)
+    }
+}
+
+/// A provider whose requests take their turn in a [`WorkQueue`].
+pub struct QueuedProvider {
+    inner: Box<dyn AIProvider>,
+    queue: Arc<WorkQueue>,
+}
+
+impl QueuedProvider {
+    pub fn new(inner: Box<dyn AIProvider>, queue: Arc<WorkQueue>) -> Self {
+        Self { inner, queue }
+    }
+}
+
+#[async_trait::async_trait]
+impl AIProvider for QueuedProvider {
+    async fn analyze_semantic_relationships(
+        &self,
+        request: SemanticAnalysisRequest,
+    ) -> Result<SemanticAnalysisResult> {
+        let _turn = self.queue.enter().await;
+        self.inner.analyze_semantic_relationships(request).await
+    }
+
+    async fn analyze_batch(&self, batch: SemanticBatchRequest) -> Result<SemanticAnalysisResult> {
+        let _turn = self.queue.enter().await;
+        self.inner.analyze_batch(batch).await
+    }
+
+    async fn generate_node_summary(
+        &self,
+        node: &GraphNode,
+        context: &AnalysisContext,
+    ) -> Result<String> {
+        let _turn = self.queue.enter().await;
+        self.inner.generate_node_summary(node, context).await
+    }
+
+    async fn summarize_container(&self, node: &GraphNode, children: &[GraphNode]) -> Result<String> {
+        let _turn = self.queue.enter().await;
+        self.inner.summarize_container(node, children).await
+    }
+
+    async fn explain_change(&self, change: &GraphChange) -> Result<String> {
+        let _turn = self.queue.enter().await;
+        self.inner.explain_change(change).await
+    }
+
+    async fn translate_question(&self, question: &str) -> Result<GraphQuery> {
+        let _turn = self.queue.enter().await;
+        self.inner.translate_question(question).await
+    }
+
+    async fn answer_code_question(
+        &self,
+        question: &str,
+        relevant_nodes: &[GraphNode],
+        relevant_edges: &[GraphEdge],
+    ) -> Result<String> {
+        let _turn = self.queue.enter().await;
+        self.inner.answer_code_question(question, relevant_nodes, relevant_edges).await
+    }
+
+    async fn stream_code_question(
+        &self,
+        question: &str,
+        relevant_nodes: &[GraphNode],
+        relevant_edges: &[GraphEdge],
+    ) -> Result<TokenStream> {
+        // The turn lasts until the answer has been streamed
+        let turn = self.queue.enter().await;
+        let tokens = self.inner.stream_code_question(question, relevant_nodes, relevant_edges).await?;
+        Ok(tokens
+            .map(move |token| {
+                let _turn = &turn;
+                token
+            })
+            .boxed())
+    }
+
+    async fn health_check(&self) -> Result<ProviderCapabilities> {
+        self.inner.health_check().await
+    }
+
+    fn is_billed(&self) -> bool {
+        self.inner.is_billed()
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+}
@@ -0,0 +1,119 @@
+//! Offline provider proposing edges between similar nodes
+//!
+//! Without a language model, relationships can still be guessed from what
+//! nodes are called and what their summaries say: `load_config` and
+//! `ConfigLoader` are likely related. [`SimilarityProvider`] embeds the
+//! source and candidate nodes and proposes a `SemanticReference` to each
+//! candidate at least [`SIMILARITY_THRESHOLD`] alike, with the similarity
+//! as its confidence. Everything else is left to [`LocalProvider`].
+
+use super::local::LocalProvider;
+use crate::bridge::{
+    AIProvider, AnalysisContext, InferredRelationship, ProviderCapabilities, SemanticAnalysisRequest,
+    SemanticAnalysisResult, SemanticBatchRequest, SemanticRelationship,
+};
+use crate::embedding::{node_text, Embedder};
+use anyhow::Result;
+use canopy_core::{GraphEdge, GraphNode};
+
+/// Cosine similarity from which two nodes are taken to be related.
+pub const SIMILARITY_THRESHOLD: f32 = 0.8;
+
+pub struct SimilarityProvider {
+    embedder: Box<dyn Embedder>,
+    threshold: f32,
+    name: String,
+}
+
+impl SimilarityProvider {
+    pub fn new(embedder: Box<dyn Embedder>) -> Self {
+        let name = format!("Local ({})", embedder.name());
+        Self { embedder, threshold: SIMILARITY_THRESHOLD, name }
+    }
+
+    pub fn with_threshold(mut self, threshold: f32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// The candidates alike enough to each of `sources`, most similar
+    /// first.
+    async fn similar(&self, sources: &[GraphNode], candidates: &[GraphNode]) -> Result<Vec<InferredRelationship>> {
+        let texts: Vec<String> = sources.iter().chain(candidates).map(node_text).collect();
+        let vectors = self.embedder.embed(&texts).await?;
+        let (source_vectors, candidate_vectors) = vectors.split_at(sources.len());
+
+        let mut relationships = Vec::new();
+        for (source, source_vector) in sources.iter().zip(source_vectors) {
+            let mut similar: Vec<(f32, &GraphNode)> = candidates
+                .iter()
+                .zip(candidate_vectors)
+                .filter(|(candidate, _)| candidate.id != source.id)
+                .map(|(candidate, vector)| (dot(source_vector, vector), candidate))
+                .filter(|(similarity, _)| *similarity >= self.threshold)
+                .collect();
+            similar.sort_by(|a, b| b.0.total_cmp(&a.0));
+            relationships.extend(similar.into_iter().map(|(similarity, candidate)| InferredRelationship {
+                source_id: source.id,
+                target_id: candidate.id,
+                relationship: SemanticRelationship::SemanticReference,
+                confidence: similarity.min(1.0),
+                explanation: format!("{} and {} are named and described alike", source.name, candidate.name),
+                line_reference: None,
+            }));
+        }
+        Ok(relationships)
+    }
+
+    fn result(&self, relationships: Vec<InferredRelationship>) -> SemanticAnalysisResult {
+        SemanticAnalysisResult {
+            explanation: format!("{} similar nodes found by {} embeddings", relationships.len(), self.embedder.name()),
+            relationships,
+            tokens_used: 0,
+            provider: self.name.clone(),
+            usage: Vec::new(),
+        }
+    }
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+#[async_trait::async_trait]
+impl AIProvider for SimilarityProvider {
+    async fn analyze_semantic_relationships(&self, request: SemanticAnalysisRequest) -> Result<SemanticAnalysisResult> {
+        let relationships = self.similar(std::slice::from_ref(&request.source_node), &request.candidate_nodes).await?;
+        Ok(self.result(relationships))
+    }
+
+    async fn analyze_batch(&self, batch: SemanticBatchRequest) -> Result<SemanticAnalysisResult> {
+        let relationships = self.similar(&batch.source_nodes, &batch.candidate_nodes).await?;
+        Ok(self.result(relationships))
+    }
+
+    async fn generate_node_summary(&self, node: &GraphNode, context: &AnalysisContext) -> Result<String> {
+        LocalProvider::new().generate_node_summary(node, context).await
+    }
+
+    async fn answer_code_question(
+        &self,
+        question: &str,
+        relevant_nodes: &[GraphNode],
+        relevant_edges: &[GraphEdge],
+    ) -> Result<String> {
+        LocalProvider::new().answer_code_question(question, relevant_nodes, relevant_edges).await
+    }
+
+    async fn health_check(&self) -> Result<ProviderCapabilities> {
+        Ok(ProviderCapabilities { model: Some(self.embedder.name().to_string()), ..Default::default() })
+    }
+
+    fn is_billed(&self) -> bool {
+        false
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
@@ -0,0 +1,186 @@
+//! Streamed chat completions
+//!
+//! With `"stream": true`, OpenAI-compatible APIs send a completion as
+//! server-sent events: `data:` lines each holding a chunk whose
+//! `choices[0].delta.content` is the next few characters of the answer,
+//! ending with `data: [DONE]`. Other lines are comments or keep-alives.
+//!
+//! Anthropic's Messages API sends `content_block_delta` events whose
+//! `delta.text` is the next part of the answer, ending with
+//! `message_stop`, or an `error` event if generation fails part way.
+
+use super::super::bridge::TokenStream;
+use super::ErrorBody;
+use anyhow::{Context, Result};
+use futures_util::{stream, StreamExt};
+use serde::Deserialize;
+use std::collections::VecDeque;
+
+#[derive(Debug, Deserialize)]
+struct ChatChunk {
+    #[serde(default)]
+    choices: Vec<ChunkChoice>,
+    /// Set by routers when the upstream provider fails mid-stream.
+    error: Option<ErrorBody>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChunkChoice {
+    #[serde(default)]
+    delta: ChunkDelta,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ChunkDelta {
+    content: Option<String>,
+}
+
+/// An event of Anthropic's Messages API stream.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum MessageEvent {
+    ContentBlockDelta { delta: MessageDelta },
+    MessageStop,
+    Error { error: serde_json::Value },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct MessageDelta {
+    #[serde(default)]
+    text: String,
+}
+
+/// What the data of an event holds.
+enum Data {
+    Token(String),
+    Done,
+    Nothing,
+}
+
+/// The data of an OpenAI-compatible stream's event.
+fn chat_data(data: &str) -> Result<Data> {
+    if data == "[DONE]" {
+        return Ok(Data::Done);
+    }
+    let chunk: ChatChunk = serde_json::from_str(data).context("Malformed completion chunk")?;
+    if let Some(error) = chunk.error {
+        anyhow::bail!("Stream failed: {}", error.message);
+    }
+    let content = chunk.choices.into_iter().next().and_then(|c| c.delta.content);
+    Ok(content.map_or(Data::Nothing, Data::Token))
+}
+
+/// The data of a Messages API stream's event.
+fn message_data(data: &str) -> Result<Data> {
+    match serde_json::from_str(data).context("Malformed message event")? {
+        MessageEvent::ContentBlockDelta { delta } => Ok(Data::Token(delta.text)),
+        MessageEvent::MessageStop => Ok(Data::Done),
+        MessageEvent::Error { error } => anyhow::bail!("Stream failed: {}", error),
+        MessageEvent::Other => Ok(Data::Nothing),
+    }
+}
+
+/// Deltas read from an event stream so far.
+struct Deltas {
+    /// Bytes of a line not yet ended.
+    partial: Vec<u8>,
+    tokens: VecDeque<String>,
+    done: bool,
+    /// Reads the data of one event.
+    read: fn(&str) -> Result<Data>,
+}
+
+impl Deltas {
+    fn new(read: fn(&str) -> Result<Data>) -> Self {
+        Self { partial: Vec::new(), tokens: VecDeque::new(), done: false, read }
+    }
+
+    /// Take in the next bytes of the stream, queueing the deltas of the
+    /// lines they complete.
+    fn push(&mut self, bytes: &[u8]) -> Result<()> {
+        self.partial.extend_from_slice(bytes);
+        while let Some(end) = self.partial.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.partial.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let Some(data) = line.trim().strip_prefix("data:").map(str::trim) else {
+                continue;
+            };
+            match (self.read)(data)? {
+                Data::Token(token) if !token.is_empty() => self.tokens.push_back(token),
+                Data::Done => {
+                    self.done = true;
+                    break;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The tokens of a streamed chat completion, as they arrive.
+pub fn chat_deltas(response: reqwest::Response) -> TokenStream {
+    deltas(response, Deltas::new(chat_data))
+}
+
+/// The tokens of a streamed Messages API response, as they arrive.
+pub fn message_deltas(response: reqwest::Response) -> TokenStream {
+    deltas(response, Deltas::new(message_data))
+}
+
+fn deltas(response: reqwest::Response, deltas: Deltas) -> TokenStream {
+    stream::try_unfold((Some(response), deltas), |(mut response, mut deltas)| async move {
+        loop {
+            if let Some(token) = deltas.tokens.pop_front() {
+                return Ok(Some((token, (response, deltas))));
+            }
+            let Some(body) = response.as_mut().filter(|_| !deltas.done) else {
+                return Ok(None);
+            };
+            match body.chunk().await? {
+                Some(bytes) => deltas.push(&bytes)?,
+                None => {
+                    // A last line without its newline
+                    deltas.push(b"\n")?;
+                    response = None;
+                }
+            }
+        }
+    })
+    .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_events() {
+        let mut deltas = Deltas::new(chat_data);
+        deltas.push(b": OPENROUTER PROCESSING\n\ndata: {\"choices\":[{\"delta\":{\"role\":\"assistant\"}}]}\n\n").unwrap();
+        deltas.push(b"data: {\"choices\":[{\"delta\":{\"content\":\"The \"}}]}\n\ndata: {\"choi").unwrap();
+        assert_eq!(deltas.tokens, ["The "]);
+        deltas.push(b"ces\":[{\"delta\":{\"content\":\"parser\"}}]}\n\ndata: [DONE]\n\n").unwrap();
+        assert_eq!(deltas.tokens, ["The ", "parser"]);
+        assert!(deltas.done);
+
+        assert!(Deltas::new(chat_data).push(b"data: {not json}\n").is_err());
+        let error = b"data: {\"error\":{\"code\":502,\"message\":\"Upstream overloaded\"}}\n";
+        assert!(Deltas::new(chat_data).push(error).unwrap_err().to_string().contains("Upstream overloaded"));
+    }
+
+    #[test]
+    fn test_message_events() {
+        let mut deltas = Deltas::new(message_data);
+        deltas.push(b"event: message_start\ndata: {\"type\":\"message_start\",\"message\":{}}\n\n").unwrap();
+        deltas.push(b"event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"The \"}}\n\n").unwrap();
+        deltas.push(b"event: ping\ndata: {\"type\":\"ping\"}\n\nevent: message_stop\ndata: {\"type\":\"message_stop\"}\n\n").unwrap();
+        assert_eq!(deltas.tokens, ["The "]);
+        assert!(deltas.done);
+
+        let error = b"event: error\ndata: {\"type\":\"error\",\"error\":{\"type\":\"overloaded_error\"}}\n";
+        assert!(Deltas::new(message_data).push(error).unwrap_err().to_string().contains("overloaded_error"));
+    }
+}
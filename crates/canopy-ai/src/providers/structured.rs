@@ -0,0 +1,233 @@
+//! Structured analysis replies
+//!
+//! Providers are asked to report relationships by calling a
+//! [`REPORT_TOOL`] function, whose arguments follow [`analysis_schema`],
+//! instead of writing JSON somewhere in a reply. Servers without function
+//! calling answer in text, which is read as JSON. Either way the report is
+//! checked against the schema, and a reply that fails is sent back once
+//! with the error for the model to repair.
+
+use crate::bridge::{InferredRelationship, SemanticRelationship};
+use anyhow::{bail, Context, Result};
+use canopy_core::NodeId;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{json, Value};
+
+/// Name of the function relationships are reported with.
+pub const REPORT_TOOL: &str = "report_relationships";
+
+/// What the function is for, as told to the model.
+pub const REPORT_DESCRIPTION: &str = "Report the semantic relationships found in the code";
+
+/// JSON schema of a relationship report.
+pub fn analysis_schema() -> Value {
+    let relationships: Vec<String> = SemanticRelationship::ALL.iter().map(|r| format!("{:?}", r)).collect();
+    json!({
+        "type": "object",
+        "properties": {
+            "relationships": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "source_id": {"type": "integer", "description": "ID of the node the relationship is from"},
+                        "target_id": {"type": "integer", "description": "ID of the candidate it is to"},
+                        "relationship": {"type": "string", "enum": relationships},
+                        "confidence": {"type": "number", "minimum": 0.0, "maximum": 1.0},
+                        "explanation": {"type": "string"},
+                        "line_reference": {"type": ["integer", "null"], "description": "Line where the relationship is evident"}
+                    },
+                    "required": ["source_id", "target_id", "relationship", "confidence", "explanation"]
+                }
+            },
+            "explanation": {"type": "string", "description": "Overall analysis summary"}
+        },
+        "required": ["relationships", "explanation"]
+    })
+}
+
+/// A function the model may call.
+#[derive(Debug, Serialize)]
+pub struct Tool {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    function: FunctionDefinition,
+}
+
+#[derive(Debug, Serialize)]
+struct FunctionDefinition {
+    name: &'static str,
+    description: &'static str,
+    parameters: Value,
+}
+
+/// A chat completion request that must call [`REPORT_TOOL`].
+#[derive(Debug, Serialize)]
+pub struct ReportRequest<'a, R> {
+    #[serde(flatten)]
+    request: &'a R,
+    tools: [Tool; 1],
+    tool_choice: Value,
+}
+
+impl<'a, R: Serialize> ReportRequest<'a, R> {
+    pub fn new(request: &'a R) -> Self {
+        Self {
+            request,
+            tools: [Tool {
+                kind: "function",
+                function: FunctionDefinition {
+                    name: REPORT_TOOL,
+                    description: REPORT_DESCRIPTION,
+                    parameters: analysis_schema(),
+                },
+            }],
+            tool_choice: json!({"type": "function", "function": {"name": REPORT_TOOL}}),
+        }
+    }
+}
+
+/// A function call in a reply.
+#[derive(Debug, Deserialize)]
+pub struct ToolCall {
+    pub function: FunctionCall,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FunctionCall {
+    pub name: String,
+    /// The arguments, as JSON text.
+    pub arguments: String,
+}
+
+/// The report in a reply: the arguments of its call to [`REPORT_TOOL`], or
+/// else its text.
+pub fn report_text<'a>(content: &'a str, tool_calls: &'a [ToolCall]) -> &'a str {
+    tool_calls
+        .iter()
+        .find(|call| call.function.name == REPORT_TOOL)
+        .map_or(content, |call| call.function.arguments.as_str())
+}
+
+/// Read a JSON `null` as an empty string: replies calling a function have
+/// no text.
+pub fn null_as_empty<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(Option::<String>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+/// The message asking the model to repair a report that failed with `error`.
+pub fn repair_prompt(error: &anyhow::Error) -> String {
+    format!(
+        "That report doesn't match the {} schema: {:#}. Report the relationships again, correcting it.",
+        REPORT_TOOL, error
+    )
+}
+
+/// A relationship report, checked against [`analysis_schema`].
+#[derive(Debug, Deserialize)]
+pub struct AnalysisReport {
+    pub relationships: Vec<ReportedRelationship>,
+    pub explanation: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReportedRelationship {
+    pub source_id: u64,
+    pub target_id: u64,
+    pub relationship: SemanticRelationship,
+    pub confidence: f32,
+    pub explanation: String,
+    #[serde(default)]
+    pub line_reference: Option<u32>,
+}
+
+impl AnalysisReport {
+    /// The report in `text`, whose relationships must be between `nodes`.
+    /// Text around a JSON object is ignored.
+    pub fn parse(text: &str, nodes: &[NodeId]) -> Result<Self> {
+        // A reply like `} ... {` has braces but no object between them
+        let Some((start, end)) = text.find('{').zip(text.rfind('}')).filter(|(start, end)| start < end) else {
+            bail!("no JSON object");
+        };
+        let report: Self = serde_json::from_str(&text[start..=end]).context("invalid report")?;
+        for rel in &report.relationships {
+            if !(0.0..=1.0).contains(&rel.confidence) {
+                bail!("confidence {} is outside 0 to 1", rel.confidence);
+            }
+            for id in [rel.source_id, rel.target_id] {
+                if !nodes.contains(&NodeId(id)) {
+                    bail!("{} is not the ID of a node given", id);
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    pub fn into_relationships(self) -> Vec<InferredRelationship> {
+        self.relationships
+            .into_iter()
+            .map(|rel| InferredRelationship {
+                source_id: NodeId(rel.source_id),
+                target_id: NodeId(rel.target_id),
+                relationship: rel.relationship,
+                confidence: rel.confidence,
+                explanation: rel.explanation,
+                line_reference: rel.line_reference,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Request {
+        model: &'static str,
+    }
+
+    #[test]
+    fn test_report_request() {
+        let request = serde_json::to_value(ReportRequest::new(&Request { model: "gpt-4o-mini" })).unwrap();
+        assert_eq!(request["model"], "gpt-4o-mini");
+        assert_eq!(request["tool_choice"]["function"]["name"], REPORT_TOOL);
+        let relationships = &request["tools"][0]["function"]["parameters"]["properties"]["relationships"];
+        let kinds = relationships["items"]["properties"]["relationship"]["enum"].as_array().unwrap();
+        assert_eq!(kinds.len(), SemanticRelationship::ALL.len());
+    }
+
+    #[test]
+    fn test_parse_report() {
+        let nodes = [NodeId(1), NodeId(2)];
+        let calls = [ToolCall {
+            function: FunctionCall {
+                name: REPORT_TOOL.to_string(),
+                arguments: r#"{"relationships": [{"source_id": 1, "target_id": 2, "relationship": "Extends", "confidence": 0.9, "explanation": "extends"}], "explanation": "one"}"#.to_string(),
+            },
+        }];
+        let report = AnalysisReport::parse(report_text("", &calls), &nodes).unwrap();
+        let relationships = report.into_relationships();
+        assert_eq!(relationships[0].relationship, SemanticRelationship::Extends);
+        assert_eq!(relationships[0].line_reference, None);
+
+        // Replies in text are read too
+        let text = r#"Sure: {"relationships": [], "explanation": "none"} Hope that helps"#;
+        assert!(AnalysisReport::parse(report_text(text, &[]), &nodes).unwrap().relationships.is_empty());
+
+        // Reports off the schema fail, with what to repair
+        let report = |rel: &str| format!(r#"{{"relationships": [{}], "explanation": ""}}"#, rel);
+        let errors = [
+            report(r#"{"source_id": 1, "target_id": 2, "relationship": "Likes", "confidence": 0.9, "explanation": ""}"#),
+            report(r#"{"source_id": 1, "target_id": 2, "relationship": "Calls", "confidence": 1.5, "explanation": ""}"#),
+            report(r#"{"source_id": 1, "target_id": 7, "relationship": "Calls", "confidence": 0.5, "explanation": ""}"#),
+            "no report".to_string(),
+            "} no report {".to_string(),
+        ]
+        .map(|text| AnalysisReport::parse(&text, &nodes).unwrap_err());
+        assert!(repair_prompt(&errors[1]).contains("confidence 1.5 is outside 0 to 1"));
+        assert!(format!("{:#}", errors[0]).contains("Likes"));
+        assert!(errors[2].to_string().contains("7 is not the ID"));
+        assert!(errors[4].to_string().contains("no JSON object"));
+    }
+}
@@ -0,0 +1,168 @@
+//! Questions as graph queries
+//!
+//! A question like "which handlers touch the database?" is translated by
+//! the provider into a [`GraphQuery`]: the kinds, names and paths of the
+//! nodes asked about, and the edges they must have to other nodes. The
+//! query runs against the graph locally, so questions are answered from
+//! nodes and edges that exist. Only questions that can't be translated are
+//! left to the provider to answer free-form.
+
+use crate::bridge::AIProvider;
+use anyhow::{bail, Context, Result};
+use canopy_core::{EdgeKind, Graph, GraphNode, NodeKind, Subgraph};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use tokio::sync::RwLock;
+
+/// Most nodes a query matches.
+pub const MAX_QUERY_RESULTS: usize = 50;
+
+/// Which nodes a query is about. Empty lists accept any node.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NodePattern {
+    /// Kinds the node may be of.
+    pub kinds: Vec<NodeKind>,
+    /// Words, one of which the node's qualified name contains, ignoring case.
+    pub names: Vec<String>,
+    /// Fragments, one of which the node's file path contains.
+    pub paths: Vec<String>,
+}
+
+impl NodePattern {
+    pub fn is_empty(&self) -> bool {
+        self.kinds.is_empty() && self.names.is_empty() && self.paths.is_empty()
+    }
+
+    pub fn matches(&self, node: &GraphNode) -> bool {
+        let name = node.qualified_name.to_lowercase();
+        let path = node.file_path.to_string_lossy();
+        (self.kinds.is_empty() || self.kinds.contains(&node.kind))
+            && (self.names.is_empty() || self.names.iter().any(|word| name.contains(&word.to_lowercase())))
+            && (self.paths.is_empty() || self.paths.iter().any(|fragment| path.contains(fragment.as_str())))
+    }
+}
+
+/// Which way a [`Relation`]'s edges point.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    /// From the matched node to the related one.
+    #[default]
+    Outgoing,
+    /// From the related node to the matched one.
+    Incoming,
+}
+
+/// Edges a matched node must have.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Relation {
+    /// Kinds the edges may be of; any but Contains if empty.
+    pub edges: Vec<EdgeKind>,
+    pub direction: Direction,
+    /// The nodes at the other end.
+    pub to: NodePattern,
+}
+
+/// A question about the code, as a query of the graph.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GraphQuery {
+    /// The nodes asked about.
+    pub nodes: NodePattern,
+    /// Edges they must have, if any.
+    pub related: Option<Relation>,
+}
+
+impl GraphQuery {
+    /// The query in a provider's reply: the JSON object in it.
+    pub fn parse(reply: &str) -> Result<Self> {
+        // The last `}` can come before the first `{` in free text
+        let Some((start, end)) = reply.find('{').zip(reply.rfind('}')).filter(|(start, end)| start < end) else {
+            bail!("No graph query in reply");
+        };
+        let query: Self = serde_json::from_str(&reply[start..=end]).context("Failed to parse graph query")?;
+        // A query of nothing in particular would match the whole graph
+        if query.nodes.is_empty() && query.related.as_ref().is_none_or(|r| r.edges.is_empty() && r.to.is_empty()) {
+            bail!("Graph query matches every node");
+        }
+        Ok(query)
+    }
+
+    /// The nodes of `graph` matching the query, up to
+    /// [`MAX_QUERY_RESULTS`], with the nodes and edges relating them.
+    pub fn execute(&self, graph: &Graph) -> Subgraph {
+        let mut subgraph = Subgraph::default();
+        let mut seen = HashSet::new();
+        let mut matched = 0;
+        let mut candidates: Vec<&GraphNode> = graph.all_nodes().filter(|n| self.nodes.matches(n)).collect();
+        candidates.sort_by_key(|n| (n.is_container, n.id.0));
+
+        for node in candidates {
+            if matched == MAX_QUERY_RESULTS {
+                break;
+            }
+            let Some(relation) = &self.related else {
+                seen.insert(node.id);
+                subgraph.nodes.push(node.clone());
+                matched += 1;
+                continue;
+            };
+
+            let edges: Vec<_> = match relation.direction {
+                Direction::Outgoing => graph.edges_from(node.id).map(|e| (e, e.target)).collect(),
+                Direction::Incoming => graph.edges_to(node.id).map(|e| (e, e.source)).collect(),
+            };
+            let mut related = edges
+                .into_iter()
+                .filter(|(e, _)| {
+                    if relation.edges.is_empty() {
+                        e.kind != EdgeKind::Contains
+                    } else {
+                        relation.edges.contains(&e.kind)
+                    }
+                })
+                .filter_map(|(e, other)| Some((e, graph.node(other)?)))
+                .filter(|(_, other)| relation.to.matches(other))
+                .peekable();
+            if related.peek().is_none() {
+                continue;
+            }
+
+            if seen.insert(node.id) {
+                subgraph.nodes.push(node.clone());
+            }
+            for (edge, other) in related {
+                if seen.insert(other.id) {
+                    subgraph.nodes.push(other.clone());
+                }
+                subgraph.edges.push(edge.clone());
+            }
+            matched += 1;
+        }
+        subgraph
+    }
+}
+
+/// The part of `graph` answering `question`, found by the query `provider`
+/// translates it into. `None` if the question can't be translated, or
+/// the query matches nothing, leaving it to be answered free-form.
+pub async fn query_question(
+    provider: &dyn AIProvider,
+    question: &str,
+    graph: &RwLock<Graph>,
+) -> Option<(GraphQuery, Subgraph)> {
+    let query = match provider.translate_question(question).await {
+        Ok(query) => query,
+        Err(e) => {
+            tracing::debug!("Answering {:?} free-form: {}", question, e);
+            return None;
+        }
+    };
+    let result = query.execute(&*graph.read().await);
+    if result.nodes.is_empty() {
+        tracing::debug!("Graph query for {:?} matched nothing: {:?}", question, query);
+        return None;
+    }
+    Some((query, result))
+}
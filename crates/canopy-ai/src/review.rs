@@ -0,0 +1,206 @@
+//! Review of AI-inferred edges
+//!
+//! AI edges don't go straight into the graph: a [`ReviewQueue`] holds them
+//! as proposals until someone accepts or rejects them. Decisions are kept
+//! in `.canopy/reviews.json`, by the stable identity of the nodes involved,
+//! so an edge decided once is added, or dropped, without asking again when
+//! it is inferred in a later run.
+//!
+//! Recent decisions also go back to the provider as
+//! [`ReviewExample`]s, teaching it what this project counts as a
+//! relationship, and, with the confidence it gave each edge, into the
+//! [`Calibration`] of its later confidence.
+
+use crate::bridge::{Confidence, ReviewExample};
+use crate::cache::NodeKey;
+use crate::calibration::Calibration;
+use anyhow::{Context, Result};
+use canopy_core::{EdgeKind, Graph, GraphEdge};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+/// File of the decisions made, within `.canopy/`.
+pub const REVIEWS_FILE: &str = "reviews.json";
+
+/// Decisions given to the provider as examples with each analysis.
+pub const MAX_EXAMPLES: usize = 8;
+
+/// What was decided about an AI edge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Verdict {
+    Accepted,
+    Rejected,
+}
+
+/// Which provider inferred an AI edge, and how confident it was before
+/// calibration.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Inference {
+    pub provider: String,
+    pub confidence: Confidence,
+}
+
+/// An AI edge waiting for a decision.
+#[derive(Debug, Clone, Serialize)]
+pub struct Proposal {
+    /// Identifies the edge by its nodes and kind, across runs.
+    pub id: u64,
+    pub source: NodeKey,
+    pub target: NodeKey,
+    /// The edge as inferred; its node IDs may since have changed.
+    pub edge: GraphEdge,
+    pub inference: Inference,
+}
+
+/// A decision about an AI edge.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Decision {
+    pub source: NodeKey,
+    pub target: NodeKey,
+    pub kind: EdgeKind,
+    pub explanation: String,
+    pub verdict: Verdict,
+    /// Absent from decisions saved before it was kept.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inference: Option<Inference>,
+}
+
+impl Decision {
+    fn id(&self) -> u64 {
+        proposal_id(&self.source, &self.target, self.kind)
+    }
+}
+
+fn proposal_id(source: &NodeKey, target: &NodeKey, kind: EdgeKind) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    (source, target, kind).hash(&mut hasher);
+    hasher.finish()
+}
+
+/// AI edges waiting for review, and the decisions made so far.
+#[derive(Debug, Default)]
+pub struct ReviewQueue {
+    /// Where decisions are saved, if they are.
+    path: Option<PathBuf>,
+    proposed: BTreeMap<u64, Proposal>,
+    /// Decisions in the order they were made.
+    decisions: Vec<Decision>,
+}
+
+impl ReviewQueue {
+    /// A queue keeping no decisions beyond this run.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The queue of the project at `root`, with the decisions it saved
+    /// before. An unreadable file is set aside with a warning.
+    pub fn open(root: &Path) -> Self {
+        let path = canopy_core::cache::cache_dir(root).join(REVIEWS_FILE);
+        let decisions = match std::fs::read(&path) {
+            Ok(text) => serde_json::from_slice(&text).unwrap_or_else(|e| {
+                tracing::warn!("Ignoring unreadable {}: {}", path.display(), e);
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+        Self { path: Some(path), proposed: BTreeMap::new(), decisions }
+    }
+
+    fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_vec_pretty(&self.decisions)?)
+            .with_context(|| format!("writing {}", path.display()))
+    }
+
+    fn verdict(&self, id: u64) -> Option<Verdict> {
+        self.decisions.iter().rev().find(|d| d.id() == id).map(|d| d.verdict)
+    }
+
+    /// Queue an AI edge between nodes of `graph`, with the `inference` it
+    /// came from. An edge accepted before is returned to be added right
+    /// away; one rejected before is dropped.
+    pub fn propose(&mut self, edge: GraphEdge, inference: Inference, graph: &Graph) -> Option<GraphEdge> {
+        let source = NodeKey::of(graph.node(edge.source)?);
+        let target = NodeKey::of(graph.node(edge.target)?);
+        let id = proposal_id(&source, &target, edge.kind);
+        match self.verdict(id) {
+            Some(Verdict::Accepted) => Some(edge),
+            Some(Verdict::Rejected) => None,
+            None => {
+                self.proposed.insert(id, Proposal { id, source, target, edge, inference });
+                None
+            }
+        }
+    }
+
+    /// Take an AI edge confident enough to skip review, unless it was
+    /// rejected before.
+    pub fn auto_accept(&mut self, edge: GraphEdge, graph: &Graph) -> Option<GraphEdge> {
+        let source = NodeKey::of(graph.node(edge.source)?);
+        let target = NodeKey::of(graph.node(edge.target)?);
+        let id = proposal_id(&source, &target, edge.kind);
+        (self.verdict(id) != Some(Verdict::Rejected)).then_some(edge)
+    }
+
+    /// The edges waiting for a decision.
+    pub fn proposed(&self) -> impl Iterator<Item = &Proposal> {
+        self.proposed.values()
+    }
+
+    pub fn decisions(&self) -> &[Decision] {
+        &self.decisions
+    }
+
+    /// How confidence held up in the decisions made so far.
+    pub fn calibration(&self) -> Calibration {
+        Calibration::from_decisions(&self.decisions)
+    }
+
+    /// Decide on proposal `id`, saving the decision. An accepted edge is
+    /// returned, between its nodes as they are now in `graph`, to be added;
+    /// `None` if they have since left the graph.
+    pub fn decide(&mut self, id: u64, verdict: Verdict, graph: &Graph) -> Result<Option<GraphEdge>> {
+        let proposal = self.proposed.remove(&id).with_context(|| format!("No proposed edge {}", id))?;
+        self.decisions.push(Decision {
+            source: proposal.source.clone(),
+            target: proposal.target.clone(),
+            kind: proposal.edge.kind,
+            explanation: proposal.edge.label.clone().unwrap_or_default(),
+            verdict,
+            inference: Some(proposal.inference.clone()),
+        });
+        self.save()?;
+
+        if verdict == Verdict::Rejected {
+            return Ok(None);
+        }
+        let (Some(source), Some(target)) = (proposal.source.resolve(graph), proposal.target.resolve(graph)) else {
+            return Ok(None);
+        };
+        Ok(Some(GraphEdge { source, target, ..proposal.edge }))
+    }
+
+    /// The latest decisions, as examples for the provider.
+    pub fn examples(&self, limit: usize) -> Vec<ReviewExample> {
+        self.decisions
+            .iter()
+            .rev()
+            .take(limit)
+            .map(|d| ReviewExample {
+                source: d.source.qualified_name.clone(),
+                target: d.target.qualified_name.clone(),
+                relationship: format!("{:?}", d.kind),
+                explanation: d.explanation.clone(),
+                accepted: d.verdict == Verdict::Accepted,
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,170 @@
+//! Conversations about the code
+//!
+//! Providers answer each question on its own, so a follow-up such as "and
+//! who calls that?" would lose what "that" was. A [`Session`] keeps the
+//! last [`MAX_TURNS`] questions and answers, and the nodes they were
+//! answered from, and carries them into the next question: the earlier
+//! turns as text before it, the nodes alongside those it finds itself.
+
+use canopy_core::{GraphEdge, GraphNode, Subgraph};
+use serde::Serialize;
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Write;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Questions and answers a session remembers.
+pub const MAX_TURNS: usize = 5;
+
+/// Most nodes a question in a session is answered from.
+pub const MAX_SESSION_NODES: usize = 50;
+
+/// How long an unused session is kept.
+pub const SESSION_TTL: Duration = Duration::from_secs(30 * 60);
+
+/// Most sessions kept at once. The least recently used go first.
+pub const MAX_SESSIONS: usize = 100;
+
+/// A question and its answer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Turn {
+    pub question: String,
+    pub answer: String,
+}
+
+/// One conversation about the code.
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub id: String,
+    turns: VecDeque<Turn>,
+    nodes: Vec<GraphNode>,
+    edges: Vec<GraphEdge>,
+    last_used: Instant,
+}
+
+impl Session {
+    pub fn new(id: String) -> Self {
+        Self { id, turns: VecDeque::new(), nodes: Vec::new(), edges: Vec::new(), last_used: Instant::now() }
+    }
+
+    /// The questions and answers so far, oldest first.
+    pub fn turns(&self) -> impl Iterator<Item = &Turn> {
+        self.turns.iter()
+    }
+
+    /// `question`, after the conversation so far.
+    pub fn prompt(&self, question: &str) -> String {
+        if self.turns.is_empty() {
+            return question.to_string();
+        }
+        let mut prompt = "Earlier in this conversation:\n".to_string();
+        for turn in &self.turns {
+            let _ = write!(prompt, "\nQ: {}\nA: {}\n", turn.question, turn.answer);
+        }
+        let _ = write!(prompt, "\nAnswer this follow-up, which may refer to the above: {}", question);
+        prompt
+    }
+
+    /// What to answer the next question from: the nodes `found` for it,
+    /// then those earlier questions were answered from, with the edges
+    /// between them.
+    pub fn context(&self, found: Subgraph) -> Subgraph {
+        let mut seen = HashSet::new();
+        let nodes: Vec<GraphNode> = found
+            .nodes
+            .into_iter()
+            .chain(self.nodes.iter().cloned())
+            .filter(|n| seen.insert(n.id))
+            .take(MAX_SESSION_NODES)
+            .collect();
+        let kept: HashSet<_> = nodes.iter().map(|n| n.id).collect();
+        let mut edge_ids = HashSet::new();
+        let edges = found
+            .edges
+            .into_iter()
+            .chain(self.edges.iter().cloned())
+            .filter(|e| kept.contains(&e.source) && kept.contains(&e.target) && edge_ids.insert(e.id))
+            .collect();
+        Subgraph { nodes, edges }
+    }
+
+    /// Remember `question`, its `answer`, and the `context` it was
+    /// answered from.
+    pub fn record(&mut self, question: &str, answer: &str, context: Subgraph) {
+        self.turns.push_back(Turn { question: question.to_string(), answer: answer.trim().to_string() });
+        while self.turns.len() > MAX_TURNS {
+            self.turns.pop_front();
+        }
+        self.nodes = context.nodes;
+        self.edges = context.edges;
+        self.last_used = Instant::now();
+    }
+}
+
+/// The conversations going on, by id.
+#[derive(Debug)]
+pub struct Sessions {
+    sessions: HashMap<String, Session>,
+    ttl: Duration,
+}
+
+impl Sessions {
+    pub fn new() -> Self {
+        Self { sessions: HashMap::new(), ttl: SESSION_TTL }
+    }
+
+    /// Forget sessions unused for `ttl`.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// The session `id`, or a new one if there's no id, or it's unknown
+    /// or expired. The session is a copy; [`Sessions::save`] it once its
+    /// question is answered.
+    pub fn open(&mut self, id: Option<&str>) -> Session {
+        let ttl = self.ttl;
+        self.sessions.retain(|_, session| session.last_used.elapsed() < ttl);
+        match id.and_then(|id| self.sessions.get(id)) {
+            Some(session) => session.clone(),
+            None => Session::new(new_id()),
+        }
+    }
+
+    /// Keep `session`, in place of any earlier copy.
+    pub fn save(&mut self, session: Session) {
+        self.sessions.insert(session.id.clone(), session);
+        while self.sessions.len() > MAX_SESSIONS {
+            let Some(oldest) = self.sessions.values().min_by_key(|s| s.last_used).map(|s| s.id.clone()) else {
+                break;
+            };
+            self.sessions.remove(&oldest);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+}
+
+impl Default for Sessions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A random id for a new session.
+fn new_id() -> String {
+    let mut hasher = RandomState::new().build_hasher();
+    if let Ok(now) = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        hasher.write_u128(now.as_nanos());
+    }
+    let high = hasher.finish();
+    hasher.write_u64(high);
+    format!("{:016x}{:016x}", high, hasher.finish())
+}
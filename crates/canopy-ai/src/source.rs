@@ -0,0 +1,104 @@
+//! Source text of nodes, for prompts
+//!
+//! A relationship is evident in code, not in names: prompts show the
+//! analyzed nodes' source, up to [`MAX_SOURCE_LINES`], and the first
+//! [`SNIPPET_LINES`] of each candidate, enough for its signature. Secrets
+//! in it are [redacted](crate::redact) first.
+
+use crate::redact::redact;
+use canopy_core::{GraphNode, NodeId};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Most lines of an analyzed node's source given.
+pub const MAX_SOURCE_LINES: usize = 80;
+
+/// Lines of a candidate's source given.
+pub const SNIPPET_LINES: usize = 5;
+
+/// Lines `line_start` to `line_end` of `content`, redacted and at most
+/// `max_lines` of them, or `None` if the node has no lines in it.
+pub fn node_source(content: &str, node: &GraphNode, max_lines: usize) -> Option<String> {
+    let start = node.line_start? as usize;
+    let end = node.line_end.map_or(start, |end| end as usize).max(start);
+    let lines: Vec<&str> = content.lines().skip(start.saturating_sub(1)).take(end + 1 - start).collect();
+    if lines.is_empty() {
+        return None;
+    }
+    let redacted = redact(&node.file_path, &lines.join("\n"));
+    let lines: Vec<&str> = redacted.lines().collect();
+    let mut text = lines[..lines.len().min(max_lines)].join("\n");
+    if lines.len() > max_lines {
+        text.push_str(&format!("\n// ... {} more lines", lines.len() - max_lines));
+    }
+    Some(text)
+}
+
+/// The source of `sources`, all from the file with `content`, and
+/// snippets of `candidates`, read from their files. Nodes whose source
+/// can't be read are left out.
+pub fn collect_sources(sources: &[GraphNode], content: &str, candidates: &[GraphNode]) -> HashMap<NodeId, String> {
+    let mut collected: HashMap<NodeId, String> = sources
+        .iter()
+        .filter_map(|node| Some((node.id, node_source(content, node, MAX_SOURCE_LINES)?)))
+        .collect();
+
+    let mut files: HashMap<&Path, Option<String>> = HashMap::new();
+    for node in candidates {
+        let file = files
+            .entry(&node.file_path)
+            .or_insert_with(|| std::fs::read_to_string(&node.file_path).ok());
+        if let Some(snippet) = file.as_deref().and_then(|content| node_source(content, node, SNIPPET_LINES)) {
+            collected.insert(node.id, snippet);
+        }
+    }
+    collected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bridge::{AnalysisContext, SemanticRelationship};
+    use crate::prompt::semantic_batch_prompt;
+    use canopy_core::NodeKind;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_node_source() {
+        let content = "a\nb\nc\nd\ne";
+        let lines = |start, end| GraphNode::for_test(NodeKind::Function, "connect").with_lines(start, end);
+        assert_eq!(node_source(content, &lines(2, 3), 10).unwrap(), "b\nc");
+        assert_eq!(node_source(content, &lines(1, 5), 2).unwrap(), "a\nb\n// ... 3 more lines");
+        assert!(node_source(content, &lines(9, 9), 10).is_none());
+        let content = "fn connect() {\n    login(\"admin\", password = \"hunter22\")\n}";
+        assert_eq!(node_source(content, &lines(2, 2), 10).unwrap(), "    login(\"admin\", password = \"[REDACTED]\")");
+    }
+
+    #[test]
+    fn test_sources_in_prompt() {
+        let dir = tempfile::tempdir().unwrap();
+        let other = dir.path().join("parse.rs");
+        std::fs::write(&other, "pub fn parse(text: &str) -> Config {\n    todo!()\n}\n").unwrap();
+        let source = GraphNode::for_test(NodeKind::Function, "load").with_id(1).in_file("src/lib.rs").with_lines(1, 3);
+        let candidate = GraphNode::for_test(NodeKind::Function, "parse").with_id(2).in_file(&other).with_lines(1, 3);
+
+        let sources = collect_sources(
+            std::slice::from_ref(&source),
+            "fn load() -> Config {\n    parse(&read())\n}\n",
+            &[candidate.clone(), GraphNode::for_test(NodeKind::Function, "gone").with_id(3).in_file(dir.path().join("missing.rs")).with_lines(1, 1)],
+        );
+        assert_eq!(sources.len(), 2);
+        let context = AnalysisContext {
+            file_path: PathBuf::from("src/lib.rs"),
+            language: "Rust".to_string(),
+            enclosing_context: vec![],
+            imports: vec![],
+            project_context: HashMap::new(),
+            examples: vec![],
+            sources,
+        };
+        let prompt = semantic_batch_prompt(&[source], &[candidate], &context, &[SemanticRelationship::Calls]);
+        assert!(prompt.contains("\n        parse(&read())\n"));
+        assert!(prompt.contains("\n    pub fn parse(text: &str) -> Config {"));
+    }
+}
@@ -1,7 +1,7 @@
 //! Unit tests for canopy-ai module
 
-use canopy_ai::providers::create_provider;
-use canopy_ai::bridge::{AIProvider, SemanticAnalysisRequest, AnalysisContext, SemanticRelationship};
+use crate::providers::{create_provider, create_provider_with, ProviderOptions};
+use crate::bridge::{AIProvider, SemanticAnalysisRequest, AnalysisContext, SemanticRelationship};
 use canopy_core::{GraphNode, NodeKind, NodeId};
 use std::path::PathBuf;
 use std::collections::HashMap;
@@ -21,6 +21,29 @@ fn test_provider_creation() {
     // Test unknown provider
     let unknown = create_provider("unknown", None);
     assert!(unknown.is_err());
+
+    // The custom provider needs to know where to send requests
+    assert!(create_provider("custom", None).is_err());
+    let options = ProviderOptions {
+        base_url: Some("http://localhost:1234/v1/".to_string()),
+        model: Some("qwen2.5-coder".to_string()),
+        ..Default::default()
+    };
+    let custom = create_provider_with("custom", &options).unwrap();
+    assert_eq!(custom.name(), "OpenAI-compatible (http://localhost:1234/v1)");
+}
+
+#[test]
+fn test_default_provider() {
+    use crate::providers::default_provider_name;
+
+    // Without a key the embeddings provider stands in, offline
+    for key in [None, Some("")] {
+        let name = default_provider_name(key);
+        assert_eq!(name, "embeddings");
+        assert!(!create_provider(name, None).unwrap().is_billed());
+    }
+    assert_eq!(default_provider_name(Some("sk-test")), "openai");
 }
 
 #[test]
@@ -32,35 +55,17 @@ fn test_local_provider_analysis() {
         let provider = create_provider("local", None).unwrap();
         
         // Create test nodes
-        let node1 = GraphNode {
-            id: NodeId(1),
-            kind: NodeKind::Function,
-            name: "process_data".to_string(),
-            qualified_name: "process_data".to_string(),
-            file_path: PathBuf::from("src/lib.rs"),
-            line_start: Some(10),
-            line_end: Some(20),
-            language: Some(canopy_core::Language::Rust),
-            is_container: false,
-            child_count: 0,
-            loc: Some(10),
-            metadata: HashMap::new(),
-        };
+        let node1 = GraphNode::for_test(NodeKind::Function, "process_data")
+            .with_id(1)
+            .in_file("src/lib.rs")
+            .with_lines(10, 20)
+            .with_language(canopy_core::Language::Rust);
         
-        let node2 = GraphNode {
-            id: NodeId(2),
-            kind: NodeKind::Function,
-            name: "validate_input".to_string(),
-            qualified_name: "validate_input".to_string(),
-            file_path: PathBuf::from("src/lib.rs"),
-            line_start: Some(30),
-            line_end: Some(40),
-            language: Some(canopy_core::Language::Rust),
-            is_container: false,
-            child_count: 0,
-            loc: Some(10),
-            metadata: HashMap::new(),
-        };
+        let node2 = GraphNode::for_test(NodeKind::Function, "validate_input")
+            .with_id(2)
+            .in_file("src/lib.rs")
+            .with_lines(30, 40)
+            .with_language(canopy_core::Language::Rust);
         
         // Test semantic analysis
         let request = SemanticAnalysisRequest {
@@ -72,6 +77,8 @@ fn test_local_provider_analysis() {
                 enclosing_context: vec![],
                 imports: vec![],
                 project_context: HashMap::new(),
+                examples: Vec::new(),
+                sources: HashMap::new(),
             },
             relationship_types: vec![SemanticRelationship::Calls, SemanticRelationship::DependsOn],
         };
@@ -80,27 +87,17 @@ fn test_local_provider_analysis() {
         assert!(result.is_ok());
         
         let analysis = result.unwrap();
-        assert!(analysis.relationships.len() >= 0); // Local provider may return empty
         assert_eq!(analysis.tokens_used, 0); // Local provider uses no tokens
     });
 }
 
 #[test]
 fn test_semantic_analysis_request_creation() {
-    let node = GraphNode {
-        id: NodeId(1),
-        kind: NodeKind::Function,
-        name: "test_function".to_string(),
-        qualified_name: "test_function".to_string(),
-        file_path: PathBuf::from("test.rs"),
-        line_start: Some(10),
-        line_end: Some(20),
-        language: Some(canopy_core::Language::Rust),
-        is_container: false,
-        child_count: 0,
-        loc: Some(10),
-        metadata: HashMap::new(),
-    };
+    let node = GraphNode::for_test(NodeKind::Function, "test_function")
+        .with_id(1)
+        .in_file("test.rs")
+        .with_lines(10, 20)
+        .with_language(canopy_core::Language::Rust);
     
     let request = SemanticAnalysisRequest {
         source_node: node,
@@ -111,6 +108,8 @@ fn test_semantic_analysis_request_creation() {
             enclosing_context: vec!["fn main()".to_string()],
             imports: vec!["std::collections::HashMap".to_string()],
             project_context: HashMap::new(),
+            examples: Vec::new(),
+            sources: HashMap::new(),
         },
         relationship_types: vec![SemanticRelationship::Calls],
     };
@@ -121,7 +120,7 @@ fn test_semantic_analysis_request_creation() {
 
 #[test]
 fn test_ai_budget() {
-    use canopy_ai::bridge::AIBudget;
+    use crate::bridge::AIBudget;
     
     let mut budget = AIBudget::new(1000);
     
@@ -136,9 +135,48 @@ fn test_ai_budget() {
     assert!(budget.has_budget(600));
 }
 
+#[test]
+fn test_cost_tracking() {
+    use crate::bridge::TokenUsage;
+    use crate::budget::{Budget, ModelPrice};
+
+    let mut budget = Budget::new(10_000_000)
+        .with_price("house-model", ModelPrice { input: 1.0, output: 4.0 })
+        .with_spend_alerts(vec![1.0]);
+    let usage = |model: &str, input_tokens, output_tokens| TokenUsage { model: model.to_string(), input_tokens, output_tokens };
+
+    let cost = budget.record_usage(&usage("house-model", 500_000, 100_000));
+    assert!((cost - 0.9).abs() < 1e-9);
+    budget.record_usage(&usage("house-model", 100_000, 0));
+    budget.record_usage(&usage("gpt-4o-mini", 1_000_000, 0));
+    // Models without a price are counted, at no cost
+    budget.record_usage(&usage("mystery", 1000, 1000));
+
+    assert_eq!(budget.tokens_used, 1_702_000);
+    assert!((budget.spend.cost_usd - 1.15).abs() < 1e-9);
+    let house = &budget.spend.models["house-model"];
+    assert_eq!((house.input_tokens, house.output_tokens), (600_000, 100_000));
+    assert_eq!(budget.spend.models["mystery"].cost_usd, 0.0);
+}
+
+#[test]
+fn test_token_counting() {
+    use crate::budget::{count_tokens, Budget, REPLY_TOKENS};
+
+    assert_eq!(count_tokens(""), 0);
+    assert_eq!(count_tokens("hello world"), 2);
+    // Code splits into far more tokens than a quarter of its characters
+    let code = "fn f(a:&[u8])->Option<(u8,u8)>{a.get(0).zip(a.get(1)).map(|(x,y)|(*x,*y))}";
+    assert!(count_tokens(code) > code.len() as u32 / 4);
+    // Repeated prose into far fewer
+    let prose = "the ".repeat(1000);
+    assert!(count_tokens(&prose) < prose.len() as u32 / 3);
+    assert_eq!(Budget::estimate_tokens("hello world"), 2 + REPLY_TOKENS);
+}
+
 #[test]
 fn test_semantic_relationships() {
-    use canopy_ai::bridge::SemanticRelationship;
+    use crate::bridge::SemanticRelationship;
     
     let relationships = vec![
         SemanticRelationship::Calls,
@@ -169,6 +207,8 @@ fn test_analysis_context() {
             map.insert("version".to_string(), "1.0.0".to_string());
             map
         },
+        examples: Vec::new(),
+        sources: HashMap::new(),
     };
     
     assert_eq!(context.language, "Rust");
@@ -178,39 +218,979 @@ fn test_analysis_context() {
 
 #[tokio::test]
 async fn test_node_summary_generation() {
-    use tokio::runtime::Runtime;
+    let provider = create_provider("local", None).unwrap();
     
-    let rt = Runtime::new().unwrap();
-    rt.block_on(async {
-        let provider = create_provider("local", None).unwrap();
-        
-        let node = GraphNode {
-            id: NodeId(1),
-            kind: NodeKind::Function,
-            name: "calculate_total".to_string(),
-            qualified_name: "calculate_total".to_string(),
-            file_path: PathBuf::from("src/math.rs"),
-            line_start: Some(42),
-            line_end: Some(58),
-            language: Some(canopy_core::Language::Rust),
-            is_container: false,
-            child_count: 0,
-            loc: Some(16),
-            metadata: HashMap::new(),
-        };
-        
-        let context = AnalysisContext {
-            file_path: PathBuf::from("src/math.rs"),
+    let node = GraphNode::for_test(NodeKind::Function, "calculate_total")
+        .with_id(1)
+        .in_file("src/math.rs")
+        .with_lines(42, 58)
+        .with_language(canopy_core::Language::Rust);
+    
+    let context = AnalysisContext {
+        file_path: PathBuf::from("src/math.rs"),
+        language: "Rust".to_string(),
+        enclosing_context: vec![],
+        imports: vec![],
+        project_context: HashMap::new(),
+        examples: Vec::new(),
+        sources: HashMap::new(),
+    };
+    
+    let summary = provider.generate_node_summary(&node, &context).await;
+    assert!(summary.is_ok());
+    
+    let summary_text = summary.unwrap();
+    assert!(!summary_text.is_empty());
+}
+/// A provider whose API always answers with an error status.
+struct FailingProvider(u16);
+
+#[async_trait::async_trait]
+impl crate::bridge::AIProvider for FailingProvider {
+    async fn analyze_semantic_relationships(&self, _request: SemanticAnalysisRequest) -> anyhow::Result<crate::bridge::SemanticAnalysisResult> {
+        Err(crate::providers::ApiError { provider: self.name().to_string(), status: self.0, body: String::new() }.into())
+    }
+
+    async fn generate_node_summary(&self, _node: &GraphNode, _context: &AnalysisContext) -> anyhow::Result<String> {
+        Err(crate::providers::ApiError { provider: self.name().to_string(), status: self.0, body: String::new() }.into())
+    }
+
+    async fn answer_code_question(&self, _question: &str, _nodes: &[GraphNode], _edges: &[canopy_core::GraphEdge]) -> anyhow::Result<String> {
+        Err(anyhow::anyhow!("not answering"))
+    }
+
+    fn name(&self) -> &str {
+        "Failing"
+    }
+}
+
+#[tokio::test]
+async fn test_fallback_provider() {
+    use crate::bridge::AIProvider;
+    use crate::providers::fallback::FallbackProvider;
+    use crate::providers::local::LocalProvider;
+
+    let node = GraphNode::for_test(NodeKind::Function, "load")
+        .with_id(1)
+        .in_file("src/lib.rs")
+        .with_lines(1, 3)
+        .with_language(canopy_core::Language::Rust);
+    let request = SemanticAnalysisRequest {
+        source_node: node.clone(),
+        candidate_nodes: vec![],
+        context: AnalysisContext {
+            file_path: PathBuf::from("src/lib.rs"),
             language: "Rust".to_string(),
             enclosing_context: vec![],
             imports: vec![],
             project_context: HashMap::new(),
-        };
-        
-        let summary = provider.generate_node_summary(&node, &context).await;
-        assert!(summary.is_ok());
-        
-        let summary_text = summary.unwrap();
-        assert!(!summary_text.is_empty());
+            examples: Vec::new(),
+            sources: HashMap::new(),
+        },
+        relationship_types: vec![SemanticRelationship::Calls],
+    };
+
+    // Rate limits and server errors move on to the next provider
+    let chain = FallbackProvider::new(vec![Box::new(FailingProvider(429)), Box::new(FailingProvider(503)), Box::new(LocalProvider::new())]);
+    assert_eq!(chain.name(), "Fallback (Failing → Failing → Local (Heuristic))");
+    let result = chain.analyze_semantic_relationships(request.clone()).await.unwrap();
+    assert_eq!(result.provider, "Local (Heuristic)");
+    assert!(chain.generate_node_summary(&node, &request.context).await.is_ok());
+
+    // Other failures don't
+    let chain = FallbackProvider::new(vec![Box::new(FailingProvider(401)), Box::new(LocalProvider::new())]);
+    assert!(chain.analyze_semantic_relationships(request.clone()).await.is_err());
+    assert!(chain.answer_code_question("what?", &[], &[]).await.is_err());
+
+    // Nor does running out of providers
+    let chain = FallbackProvider::new(vec![Box::new(FailingProvider(500))]);
+    let error = chain.analyze_semantic_relationships(request).await.unwrap_err();
+    assert_eq!(error.to_string(), "Failing API error (500): ");
+
+    // A comma-separated provider name makes a chain
+    let chain = create_provider("local, local", None).unwrap();
+    assert_eq!(chain.name(), "Fallback (Local (Heuristic) → Local (Heuristic))");
+}
+
+#[tokio::test]
+async fn test_stream_code_question() {
+    use futures_util::StreamExt;
+
+    // Providers that can't stream answer in one piece
+    let provider = create_provider("local", None).unwrap();
+    let answer = provider.answer_code_question("how many nodes?", &[], &[]).await.unwrap();
+    let tokens: Vec<String> = provider
+        .stream_code_question("how many nodes?", &[], &[])
+        .await
+        .unwrap()
+        .map(|token| token.unwrap())
+        .collect()
+        .await;
+    assert_eq!(tokens, [answer]);
+}
+
+fn test_function(id: u64, name: &str) -> GraphNode {
+    GraphNode::for_test(NodeKind::Function, name)
+        .with_id(id)
+        .with_qualified_name(&format!("src/lib.rs::{}", name))
+        .in_file("src/lib.rs")
+        .with_lines(1, 5)
+        .with_language(canopy_core::Language::Rust)
+}
+
+#[tokio::test]
+async fn test_batched_analysis() {
+    use crate::bridge::SemanticBatchRequest;
+    use crate::budget::Budget;
+
+    let batch = SemanticBatchRequest {
+        source_nodes: (1..=6).map(|id| test_function(id, &format!("step_{}", id))).collect(),
+        candidate_nodes: vec![test_function(10, "step")],
+        context: AnalysisContext {
+            file_path: PathBuf::from("src/lib.rs"),
+            language: "Rust".to_string(),
+            enclosing_context: vec![],
+            imports: vec![],
+            project_context: HashMap::new(),
+            examples: Vec::new(),
+            sources: HashMap::new(),
+        },
+        relationship_types: vec![SemanticRelationship::Calls],
+    };
+
+    // Everything fits in one request by default
+    assert_eq!(Budget::default().plan_batches(batch.clone()).len(), 1);
+
+    // A tight limit splits the nodes up, in order
+    let one = crate::prompt::semantic_batch_prompt(&batch.source_nodes[..1], &batch.candidate_nodes, &batch.context, &batch.relationship_types);
+    let two = crate::prompt::semantic_batch_prompt(&batch.source_nodes[..2], &batch.candidate_nodes, &batch.context, &batch.relationship_types);
+    let budget = Budget { max_tokens_per_request: Budget::estimate_tokens(&two), ..Budget::default() };
+    let sizes: Vec<usize> = budget.plan_batches(batch.clone()).iter().map(|b| b.source_nodes.len()).collect();
+    assert_eq!(sizes, [2, 2, 2]);
+
+    // Nodes that can't fit at all are left out
+    let budget = Budget { max_tokens_per_request: Budget::estimate_tokens(&one) - 1, ..Budget::default() };
+    assert!(budget.plan_batches(batch.clone()).is_empty());
+
+    // Providers without a batched form analyze each node in turn
+    let provider = create_provider("local", None).unwrap();
+    let result = provider.analyze_batch(batch).await.unwrap();
+    let sources: Vec<u64> = result.relationships.iter().map(|r| r.source_id.0).collect();
+    assert_eq!(sources, [1, 2, 3, 4, 5, 6]);
+}
+
+#[test]
+fn test_persistent_analysis_cache() {
+    use crate::bridge::InferredRelationship;
+    use crate::cache::{compute_content_hash, AnalysisCache};
+    use canopy_core::Graph;
+    use std::time::Duration;
+
+    let dir = tempfile::tempdir().unwrap();
+    let ttl = Duration::from_secs(60);
+    let hash = compute_content_hash("fn load() { parse() }");
+
+    let mut graph = Graph::new();
+    let load = graph.add_node(test_function(0, "load"));
+    let parse = graph.add_node(test_function(0, "parse"));
+    let relationship = InferredRelationship {
+        source_id: load,
+        target_id: parse,
+        relationship: SemanticRelationship::Calls,
+        confidence: 0.9,
+        explanation: "load parses".to_string(),
+        line_reference: Some(1),
+    };
+    let mut cache = AnalysisCache::persistent(dir.path(), ttl);
+    cache.insert(graph.node(load).unwrap(), hash, &[&relationship], "Local", &graph);
+
+    // A later run finds the analysis on disk, with the graph's new IDs
+    let mut graph = Graph::new();
+    graph.add_node(test_function(0, "unrelated"));
+    let parse = graph.add_node(test_function(0, "parse"));
+    let load = graph.add_node(test_function(0, "load"));
+    let mut cache = AnalysisCache::persistent(dir.path(), ttl);
+    let result = cache.get(graph.node(load).unwrap(), hash, &graph).unwrap();
+    assert_eq!(result.provider, "Local");
+    assert_eq!(result.relationships.len(), 1);
+    assert_eq!((result.relationships[0].source_id, result.relationships[0].target_id), (load, parse));
+
+    // Other content is a miss, and cleared entries are gone from disk
+    assert!(cache.get(graph.node(load).unwrap(), hash + 1, &graph).is_none());
+    cache.insert_summary(graph.node(load).unwrap(), 7, "Loads the config");
+    let mut reopened = AnalysisCache::persistent(dir.path(), ttl);
+    assert_eq!(reopened.get_summary(graph.node(load).unwrap(), 7).as_deref(), Some("Loads the config"));
+    assert!(reopened.get_summary(graph.node(load).unwrap(), 8).is_none());
+    cache.cleanup_expired();
+    assert_eq!(cache.stats().summaries, 1);
+    cache.clear();
+    let mut cache = AnalysisCache::persistent(dir.path(), ttl);
+    assert!(cache.get(graph.node(load).unwrap(), hash, &graph).is_none());
+}
+
+/// Fails with a 503 until it has been asked `failures` times.
+struct FlakyProvider {
+    failures: u32,
+    calls: std::sync::atomic::AtomicU32,
+}
+
+#[async_trait::async_trait]
+impl crate::bridge::AIProvider for FlakyProvider {
+    async fn analyze_semantic_relationships(&self, _request: SemanticAnalysisRequest) -> anyhow::Result<crate::bridge::SemanticAnalysisResult> {
+        anyhow::bail!("not analyzing")
+    }
+
+    async fn generate_node_summary(&self, node: &GraphNode, _context: &AnalysisContext) -> anyhow::Result<String> {
+        if self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst) < self.failures {
+            return Err(crate::providers::ApiError { provider: self.name().to_string(), status: 503, body: String::new() }.into());
+        }
+        Ok(format!("Summary of {}", node.name))
+    }
+
+    async fn answer_code_question(&self, _question: &str, _nodes: &[GraphNode], _edges: &[canopy_core::GraphEdge]) -> anyhow::Result<String> {
+        Ok("answer".to_string())
+    }
+
+    fn name(&self) -> &str {
+        "Flaky"
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_rate_limits_and_retries() {
+    use crate::bridge::AIProvider;
+    use crate::providers::limit::{LimitedProvider, RateLimiter, RateLimits};
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    let limits = RateLimits { initial_backoff_ms: 100, max_backoff_ms: 250, ..RateLimits::default() };
+    for attempt in 0..5 {
+        let backoff = limits.backoff(attempt);
+        let full = Duration::from_millis((100 << attempt).min(250));
+        assert!(backoff <= full && backoff >= full / 2, "{:?} for attempt {}", backoff, attempt);
+    }
+
+    // Requests past the limit wait for the window to move on
+    let limiter = RateLimiter::new(&RateLimits { requests_per_minute: Some(2), tokens_per_minute: Some(1000), ..limits.clone() });
+    let start = Instant::now();
+    limiter.acquire(100).await;
+    limiter.acquire(100).await;
+    assert!(start.elapsed() < Duration::from_secs(1));
+    limiter.acquire(100).await;
+    assert!(start.elapsed() >= Duration::from_secs(60));
+    // As do requests past the token limit
+    limiter.acquire(950).await;
+    assert!(start.elapsed() >= Duration::from_secs(120));
+
+    // Transient failures are retried, up to the limit
+    let context = AnalysisContext {
+        file_path: PathBuf::from("src/lib.rs"),
+        language: "Rust".to_string(),
+        enclosing_context: vec![],
+        imports: vec![],
+        project_context: HashMap::new(),
+        examples: Vec::new(),
+        sources: HashMap::new(),
+    };
+    let node = test_function(1, "load");
+    let flaky = FlakyProvider { failures: 3, calls: Default::default() };
+    let provider = LimitedProvider::new(Box::new(flaky), limits.clone());
+    assert_eq!(provider.generate_node_summary(&node, &context).await.unwrap(), "Summary of load");
+    assert_eq!(provider.name(), "Flaky");
+
+    let flaky = FlakyProvider { failures: 4, calls: Default::default() };
+    let provider = LimitedProvider::new(Box::new(flaky), limits);
+    assert!(provider.generate_node_summary(&node, &context).await.is_err());
+
+    // Other failures are not
+    let provider = LimitedProvider::new(Box::new(FailingProvider(401)), RateLimits::default());
+    let start = Instant::now();
+    assert!(provider.generate_node_summary(&node, &context).await.is_err());
+    assert_eq!(start.elapsed(), Duration::ZERO);
+}
+
+#[test]
+fn test_review_queue() {
+    use crate::review::{Inference, ReviewQueue, Verdict};
+    use canopy_core::{EdgeId, EdgeKind, EdgeSource, Graph, GraphEdge};
+
+    let dir = tempfile::tempdir().unwrap();
+    let inference = || Inference { provider: "test".to_string(), confidence: 0.9 };
+    let mut graph = Graph::new();
+    let load = graph.add_node(test_function(0, "load"));
+    let parse = graph.add_node(test_function(0, "parse"));
+    let edge = |source, target| GraphEdge {
+        id: EdgeId(0),
+        source,
+        target,
+        kind: EdgeKind::Calls,
+        edge_source: EdgeSource::AI,
+        confidence: 0.9,
+        label: Some("load parses".to_string()),
+        file_path: None,
+        line: None,
+    };
+
+    // New edges wait for a decision
+    let mut reviews = ReviewQueue::open(dir.path());
+    assert!(reviews.propose(edge(load, parse), inference(), &graph).is_none());
+    assert!(reviews.propose(edge(parse, load), inference(), &graph).is_none());
+    let ids: Vec<u64> = reviews.proposed().map(|p| p.id).collect();
+    assert_eq!(ids.len(), 2);
+    let accepted = ids.iter().copied().find(|&id| reviews.proposed().any(|p| p.id == id && p.edge.source == load)).unwrap();
+    let rejected = ids.iter().copied().find(|&id| id != accepted).unwrap();
+    assert_eq!(reviews.decide(accepted, Verdict::Accepted, &graph).unwrap().map(|e| (e.source, e.target)), Some((load, parse)));
+    assert!(reviews.decide(rejected, Verdict::Rejected, &graph).unwrap().is_none());
+    assert!(reviews.decide(rejected, Verdict::Rejected, &graph).is_err());
+    assert_eq!(reviews.proposed().count(), 0);
+
+    // Decisions outlive the run, and hold for the same edges later
+    let mut graph = Graph::new();
+    let parse = graph.add_node(test_function(0, "parse"));
+    let load = graph.add_node(test_function(0, "load"));
+    let mut reviews = ReviewQueue::open(dir.path());
+    assert_eq!(reviews.decisions().len(), 2);
+    assert!(reviews.propose(edge(load, parse), inference(), &graph).is_some());
+    assert!(reviews.propose(edge(parse, load), inference(), &graph).is_none());
+    assert_eq!(reviews.proposed().count(), 0);
+
+    // Edges confident enough to skip review are taken, unless rejected
+    let analyze = graph.add_node(test_function(0, "analyze"));
+    assert!(reviews.auto_accept(edge(load, analyze), &graph).is_some());
+    assert!(reviews.auto_accept(edge(parse, load), &graph).is_none());
+    assert_eq!(reviews.proposed().count(), 0);
+
+    // And are given to the provider as examples, latest first
+    let examples = reviews.examples(1);
+    assert_eq!(examples.len(), 1);
+    assert!(!examples[0].accepted);
+    let context = AnalysisContext {
+        file_path: PathBuf::from("src/lib.rs"),
+        language: "Rust".to_string(),
+        enclosing_context: vec![],
+        imports: vec![],
+        project_context: HashMap::new(),
+        examples,
+        sources: HashMap::new(),
+    };
+    let prompt = crate::prompt::semantic_batch_prompt(&[test_function(1, "load")], &[], &context, &[SemanticRelationship::Calls]);
+    assert!(prompt.contains("- Rejected: src/lib.rs::parse Calls src/lib.rs::load (load parses)"));
+}
+
+#[test]
+fn test_calibration() {
+    use crate::cache::NodeKey;
+    use crate::calibration::{Calibration, PRIOR_DECISIONS};
+    use crate::review::{Decision, Inference, Verdict};
+    use canopy_core::EdgeKind;
+
+    let key = NodeKey::of(&test_function(0, "load"));
+    let decision = |provider: &str, kind, confidence, verdict| Decision {
+        source: key.clone(),
+        target: key.clone(),
+        kind,
+        explanation: String::new(),
+        verdict,
+        inference: Some(Inference { provider: provider.to_string(), confidence }),
+    };
+
+    // A provider claiming 0.8 but accepted half the time is brought down,
+    // drawn toward its own confidence by the prior
+    let mut decisions: Vec<Decision> = (0..10)
+        .map(|i| decision("gpt", EdgeKind::Calls, 0.8, if i % 2 == 0 { Verdict::Accepted } else { Verdict::Rejected }))
+        .collect();
+    decisions.push(decision("claude", EdgeKind::Calls, 0.8, Verdict::Accepted));
+    decisions.push(Decision { inference: None, ..decision("gpt", EdgeKind::DependsOn, 0.8, Verdict::Rejected) });
+    let calibration = Calibration::from_decisions(&decisions);
+
+    let expected = (5.0 + PRIOR_DECISIONS * 0.8) / (10.0 + PRIOR_DECISIONS);
+    assert!((calibration.adjust("gpt", EdgeKind::Calls, 0.8) - expected).abs() < 1e-5);
+    assert!(calibration.adjust("claude", EdgeKind::Calls, 0.8) > 0.8);
+    assert!(calibration.adjust("claude", EdgeKind::Calls, 1.0) <= 1.0);
+
+    // Relationships and providers without decisions are left alone
+    assert_eq!(calibration.adjust("gpt", EdgeKind::DependsOn, 0.8), 0.8);
+    assert_eq!(calibration.adjust("local", EdgeKind::Calls, 0.8), 0.8);
+
+    let stats = calibration.stats();
+    let providers: Vec<&str> = stats.iter().map(|s| s.provider.as_str()).collect();
+    assert_eq!(providers, ["claude", "gpt"]);
+    assert_eq!((stats[1].decisions, stats[1].accepted, stats[1].accept_rate), (10, 5, 0.5));
+    assert_eq!(stats[1].bins.len(), 1);
+    assert_eq!((stats[1].bins[0].min_confidence, stats[1].bins[0].decisions), (0.8, 10));
+}
+
+#[test]
+fn test_graph_query() {
+    use crate::query::{Direction, GraphQuery};
+    use canopy_core::{EdgeId, EdgeKind, EdgeSource, Graph, GraphEdge};
+
+    let mut graph = Graph::new();
+    let create = graph.add_node(test_function(0, "create_user_handler"));
+    let list = graph.add_node(test_function(0, "list_handler"));
+    let query = graph.add_node(test_function(0, "db_query"));
+    let format = graph.add_node(test_function(0, "format_date"));
+    for (source, target) in [(create, query), (list, format)] {
+        graph.add_edge(GraphEdge {
+            id: EdgeId(0),
+            source,
+            target,
+            kind: EdgeKind::Calls,
+            edge_source: EdgeSource::Structural,
+            confidence: 1.0,
+            label: None,
+            file_path: None,
+            line: None,
+        });
+    }
+
+    // "Which handlers touch the database?", as a provider might reply
+    let reply = r#"Here is the query:
+{"nodes": {"kinds": ["Function"], "names": ["handler"]},
+ "related": {"edges": ["Calls"], "to": {"names": ["db", "sql"]}}}"#;
+    let translated = GraphQuery::parse(reply).unwrap();
+    assert_eq!(translated.related.as_ref().unwrap().direction, Direction::Outgoing);
+    let result = translated.execute(&graph);
+    let mut names: Vec<&str> = result.nodes.iter().map(|n| n.name.as_str()).collect();
+    names.sort();
+    assert_eq!(names, ["create_user_handler", "db_query"]);
+    assert_eq!(result.edges.len(), 1);
+
+    // Without a relation, the matching nodes alone
+    let handlers = GraphQuery::parse(r#"{"nodes": {"names": ["HANDLER"]}}"#).unwrap().execute(&graph);
+    assert_eq!(handlers.nodes.len(), 2);
+    assert!(handlers.edges.is_empty());
+
+    // Questions that aren't about the graph don't translate
+    assert!(GraphQuery::parse("{}").is_err());
+    assert!(GraphQuery::parse("I can't answer that").is_err());
+    assert!(GraphQuery::parse("} is not a query {").is_err());
+}
+
+/// Requests received by [`serve_replies`]: their lowercased headers, and
+/// their bodies.
+type Received = std::sync::Arc<std::sync::Mutex<Vec<(String, serde_json::Value)>>>;
+
+/// Serve `replies`, as JSON bodies, to one request each, on a local port.
+/// Returns the server's URL and the requests it received.
+async fn serve_replies(replies: Vec<String>) -> (String, Received) {
+    serve_responses(replies.into_iter().map(|reply| (200, reply)).collect()).await
+}
+
+/// Serve `responses`, each a status and JSON body, like [`serve_replies`].
+async fn serve_responses(responses: Vec<(u16, String)>) -> (String, Received) {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let requests = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let received = std::sync::Arc::clone(&requests);
+    tokio::spawn(async move {
+        for (status, reply) in responses {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 4096];
+            // Read the headers, then as much body as they announce
+            let body_start = loop {
+                let n = socket.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+                if let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                    break end + 4;
+                }
+            };
+            let headers = String::from_utf8_lossy(&request[..body_start]).to_lowercase();
+            let length: usize = headers
+                .lines()
+                .find_map(|line| line.strip_prefix("content-length:"))
+                .map_or(0, |len| len.trim().parse().unwrap());
+            while request.len() < body_start + length {
+                let n = socket.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            let body = serde_json::from_slice(&request[body_start..]).unwrap_or_default();
+            received.lock().unwrap().push((headers, body));
+            let response = format!(
+                "HTTP/1.1 {} Status\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                status,
+                reply.len(),
+                reply
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        }
+    });
+    (url, requests)
+}
+
+/// A chat completion calling the report tool with `arguments`.
+fn tool_call_reply(arguments: &str) -> String {
+    serde_json::json!({
+        "choices": [{"message": {"role": "assistant", "content": null, "tool_calls": [{
+            "id": "call_1",
+            "type": "function",
+            "function": {"name": "report_relationships", "arguments": arguments}
+        }]}}],
+        "usage": {"prompt_tokens": 100, "completion_tokens": 20, "total_tokens": 120}
+    })
+    .to_string()
+}
+
+#[tokio::test]
+async fn test_structured_output_repaired() {
+    let (url, requests) = serve_replies(vec![
+        tool_call_reply(r#"{"relationships": [{"source_id": 1, "target_id": 2, "relationship": "Calls", "confidence": 7, "explanation": "calls"}], "explanation": ""}"#),
+        tool_call_reply(r#"{"relationships": [{"source_id": 1, "target_id": 2, "relationship": "Calls", "confidence": 0.7, "explanation": "calls"}], "explanation": "fixed"}"#),
+    ])
+    .await;
+    let provider = crate::providers::openai::OpenAIProvider::compatible(&url, "test-model", None);
+    let request = SemanticAnalysisRequest {
+        source_node: test_function(1, "load"),
+        candidate_nodes: vec![test_function(2, "parse")],
+        context: AnalysisContext {
+            file_path: PathBuf::from("src/lib.rs"),
+            language: "Rust".to_string(),
+            enclosing_context: vec![],
+            imports: vec![],
+            project_context: HashMap::new(),
+            examples: vec![],
+            sources: HashMap::new(),
+        },
+        relationship_types: vec![SemanticRelationship::Calls],
+    };
+
+    let result = provider.analyze_semantic_relationships(request).await.unwrap();
+    assert_eq!(result.explanation, "fixed");
+    assert_eq!(result.relationships.len(), 1);
+    assert_eq!(result.tokens_used, 240);
+
+    // The tool was required, and the bad report sent back with its error
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests[0].1["tool_choice"]["function"]["name"], "report_relationships");
+    let messages = requests[1].1["messages"].as_array().unwrap();
+    assert_eq!(messages.len(), 4);
+    assert!(messages[3]["content"].as_str().unwrap().contains("confidence 7 is outside 0 to 1"));
+}
+
+#[tokio::test]
+async fn test_native_anthropic() {
+    use futures_util::StreamExt;
+
+    let report = serde_json::json!({
+        "content": [{"type": "tool_use", "id": "toolu_1", "name": "report_relationships", "input": {
+            "relationships": [{"source_id": 1, "target_id": 2, "relationship": "Calls", "confidence": 0.8, "explanation": "load calls parse"}],
+            "explanation": "one call"
+        }}],
+        "usage": {"input_tokens": 300, "output_tokens": 40}
+    });
+    let summary = serde_json::json!({
+        "content": [{"type": "text", "text": " Loads the config. "}],
+        "usage": {"input_tokens": 50, "output_tokens": 5}
+    });
+    let stream = "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Nobody\"}}\n\nevent: message_stop\ndata: {\"type\":\"message_stop\"}\n\n";
+    let (url, requests) = serve_replies(vec![report.to_string(), summary.to_string(), stream.to_string()]).await;
+    let options = ProviderOptions {
+        api_key: Some("sk-ant-test".to_string()),
+        base_url: Some(url),
+        model: Some("claude-3-5-sonnet-latest".to_string()),
+        ..Default::default()
+    };
+    let provider = create_provider_with("anthropic", &options).unwrap();
+    assert_eq!(provider.name(), "Anthropic");
+
+    let context = AnalysisContext {
+        file_path: PathBuf::from("src/lib.rs"),
+        language: "Rust".to_string(),
+        enclosing_context: vec![],
+        imports: vec![],
+        project_context: HashMap::new(),
+        examples: vec![],
+        sources: HashMap::new(),
+    };
+    let request = SemanticAnalysisRequest {
+        source_node: test_function(1, "load"),
+        candidate_nodes: vec![test_function(2, "parse")],
+        context: context.clone(),
+        relationship_types: vec![SemanticRelationship::Calls],
+    };
+    let result = provider.analyze_semantic_relationships(request).await.unwrap();
+    assert_eq!(result.relationships[0].target_id, NodeId(2));
+    assert_eq!(result.usage[0].model, "claude-3-5-sonnet-latest");
+    assert_eq!((result.usage[0].input_tokens, result.usage[0].output_tokens), (300, 40));
+
+    let summary = provider.generate_node_summary(&test_function(1, "load"), &context).await.unwrap();
+    assert_eq!(summary, "Loads the config.");
+    let tokens: Vec<String> = provider
+        .stream_code_question("Who calls load?", &[], &[])
+        .await
+        .unwrap()
+        .map(|token| token.unwrap())
+        .collect()
+        .await;
+    assert_eq!(tokens, ["Nobody"]);
+
+    // Requests are Messages API requests, keyed and versioned
+    let requests = requests.lock().unwrap();
+    let (headers, body) = &requests[0];
+    assert!(headers.starts_with("post /v1/messages "));
+    assert!(headers.contains("x-api-key: sk-ant-test"));
+    assert!(headers.contains("anthropic-version: 2023-06-01"));
+    assert_eq!(body["tool_choice"], serde_json::json!({"type": "tool", "name": "report_relationships"}));
+    assert_eq!(body["tools"][0]["input_schema"]["type"], "object");
+    assert_eq!(body["messages"][0]["role"], "user");
+    assert!(body["system"].is_string());
+    assert_eq!(requests[2].1["stream"], true);
+}
+
+#[tokio::test]
+async fn test_openrouter_routing() {
+    use crate::providers::openrouter::{self, OpenRouterOptions, ProviderPreferences};
+    use crate::providers::ApiError;
+
+    let mut answered = serde_json::from_str::<serde_json::Value>(&tool_call_reply(r#"{"relationships": [], "explanation": "none"}"#)).unwrap();
+    answered["model"] = "openai/gpt-4o-mini".into();
+    let failed = serde_json::json!({
+        "error": {"code": 429, "message": "Rate limited upstream", "metadata": {"provider_name": "Anthropic"}}
     });
-}
\ No newline at end of file
+    let (url, requests) = serve_replies(vec![answered.to_string(), failed.to_string()]).await;
+    let options = OpenRouterOptions {
+        models: vec!["anthropic/claude-3.5-sonnet".to_string(), "openai/gpt-4o-mini".to_string()],
+        preferences: ProviderPreferences { order: vec!["Anthropic".to_string()], ..Default::default() },
+    };
+    let provider = openrouter::provider(Some("sk-or-test".to_string()), &options).with_base_url(&url);
+    assert_eq!(provider.name(), "OpenRouter");
+
+    let request = SemanticAnalysisRequest {
+        source_node: test_function(1, "load"),
+        candidate_nodes: vec![test_function(2, "parse")],
+        context: AnalysisContext {
+            file_path: PathBuf::from("src/lib.rs"),
+            language: "Rust".to_string(),
+            enclosing_context: vec![],
+            imports: vec![],
+            project_context: HashMap::new(),
+            examples: vec![],
+            sources: HashMap::new(),
+        },
+        relationship_types: vec![SemanticRelationship::Calls],
+    };
+    // Usage is priced by the fallback model that answered
+    let result = provider.analyze_semantic_relationships(request.clone()).await.unwrap();
+    assert_eq!(result.usage[0].model, "openai/gpt-4o-mini");
+
+    // An upstream failure in a success body is an API error, rate limited
+    let error = provider.analyze_semantic_relationships(request).await.unwrap_err();
+    let api = error.downcast_ref::<ApiError>().unwrap();
+    assert_eq!(api.status, 429);
+    assert_eq!(api.body, "Rate limited upstream (from Anthropic)");
+    assert!(crate::providers::is_transient(&error));
+
+    // Requests carry the first model, the others and the preferences
+    let requests = requests.lock().unwrap();
+    let body = &requests[0].1;
+    assert_eq!(body["model"], "anthropic/claude-3.5-sonnet");
+    assert_eq!(body["models"][1], "openai/gpt-4o-mini");
+    assert_eq!(body["provider"]["order"][0], "Anthropic");
+    assert!(requests[0].0.contains("authorization: bearer sk-or-test"));
+}
+
+#[tokio::test]
+async fn test_similarity_provider() {
+    use crate::bridge::SemanticBatchRequest;
+
+    let provider = create_provider("embeddings", None).unwrap();
+    assert_eq!(provider.name(), "Local (Hashing)");
+
+    // Local summaries say the same of every function, so names decide
+    let summarized = |id, name: &str| {
+        let mut node = test_function(id, name);
+        let summary = format!("Function {} that performs operations related to its name.", name);
+        node.metadata.extra.insert("ai_summary".to_string(), summary);
+        node
+    };
+    let batch = SemanticBatchRequest {
+        source_nodes: vec![summarized(1, "load_config")],
+        candidate_nodes: vec![summarized(2, "reload_config"), summarized(3, "render_chart"), summarized(1, "load_config")],
+        context: AnalysisContext {
+            file_path: PathBuf::from("src/lib.rs"),
+            language: "Rust".to_string(),
+            enclosing_context: vec![],
+            imports: vec![],
+            project_context: HashMap::new(),
+            examples: vec![],
+            sources: HashMap::new(),
+        },
+        relationship_types: vec![SemanticRelationship::Calls],
+    };
+    let result = provider.analyze_batch(batch).await.unwrap();
+    let proposed: Vec<(NodeId, SemanticRelationship)> = result.relationships.iter().map(|r| (r.target_id, r.relationship)).collect();
+    assert_eq!(proposed, [(NodeId(2), SemanticRelationship::SemanticReference)]);
+    assert!(result.relationships[0].confidence >= crate::providers::similarity::SIMILARITY_THRESHOLD);
+    assert_eq!(result.tokens_used, 0);
+}
+
+/// Takes a second over each summary, counting how many it works on at
+/// once; clones share the counts.
+#[derive(Default, Clone)]
+struct SlowProvider {
+    running: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+    most: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+}
+
+#[async_trait::async_trait]
+impl crate::bridge::AIProvider for SlowProvider {
+    async fn analyze_semantic_relationships(&self, _request: SemanticAnalysisRequest) -> anyhow::Result<crate::bridge::SemanticAnalysisResult> {
+        anyhow::bail!("not analyzing")
+    }
+
+    async fn generate_node_summary(&self, node: &GraphNode, _context: &AnalysisContext) -> anyhow::Result<String> {
+        use std::sync::atomic::Ordering;
+        let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+        self.most.fetch_max(running, Ordering::SeqCst);
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        self.running.fetch_sub(1, Ordering::SeqCst);
+        Ok(format!("Summary of {}", node.name))
+    }
+
+    async fn answer_code_question(&self, _question: &str, _nodes: &[GraphNode], _edges: &[canopy_core::GraphEdge]) -> anyhow::Result<String> {
+        Ok("answer".to_string())
+    }
+
+    fn name(&self) -> &str {
+        "Slow"
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_work_queue() {
+    use crate::bridge::AIProvider;
+    use crate::providers::queue::{QueueStatus, QueuedProvider, WorkQueue};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    let context = AnalysisContext {
+        file_path: PathBuf::from("src/lib.rs"),
+        language: "Rust".to_string(),
+        enclosing_context: vec![],
+        imports: vec![],
+        project_context: HashMap::new(),
+        examples: Vec::new(),
+        sources: HashMap::new(),
+    };
+    let nodes: Vec<GraphNode> = (0..5).map(|id| test_function(id, &format!("f{}", id))).collect();
+    let slow = SlowProvider::default();
+    let queue = Arc::new(WorkQueue::new(2));
+
+    // Five summaries asked for at once run two at a time
+    let provider = QueuedProvider::new(Box::new(slow.clone()), Arc::clone(&queue));
+    let start = Instant::now();
+    let summaries = futures_util::future::join_all(nodes.iter().map(|node| provider.generate_node_summary(node, &context))).await;
+    assert!(summaries.iter().all(|s| s.is_ok()));
+    assert_eq!(slow.most.load(std::sync::atomic::Ordering::SeqCst), 2);
+    assert_eq!(start.elapsed(), Duration::from_secs(3));
+    assert_eq!(queue.status(), QueueStatus { max_concurrent: 2, running: 0, waiting: 0 });
+
+    // A streamed answer holds its turn until the stream is dropped
+    let queue = Arc::new(WorkQueue::new(1));
+    let provider = QueuedProvider::new(Box::new(slow), Arc::clone(&queue));
+    let stream = provider.stream_code_question("why?", &[], &[]).await.unwrap();
+    assert_eq!(queue.status().running, 1);
+    drop(stream);
+    assert_eq!(queue.status().running, 0);
+}
+
+#[tokio::test]
+async fn test_health_check() {
+    use crate::bridge::{AIProvider, ProviderCapabilities};
+    use crate::providers::openai::OpenAIProvider;
+
+    let models = serde_json::json!({"data": [
+        {"id": "small-model", "context_length": 32768, "supported_parameters": ["temperature"]},
+        {"id": "test-model", "max_model_len": 8192}
+    ]})
+    .to_string();
+    let unauthorized = serde_json::json!({"error": {"message": "Incorrect API key provided"}}).to_string();
+    let (url, requests) = serve_responses(vec![
+        (200, models.clone()),
+        (200, models),
+        (401, unauthorized.clone()),
+        (200, r#"{"data": []}"#.to_string()),
+        (401, unauthorized),
+    ])
+    .await;
+
+    // What the model can do comes from the server's model list
+    let provider = OpenAIProvider::compatible(&url, "test-model", Some("sk-test".to_string()));
+    let capabilities = provider.health_check().await.unwrap();
+    assert_eq!(
+        capabilities,
+        ProviderCapabilities {
+            model: Some("test-model".to_string()),
+            max_context_tokens: Some(8192),
+            supports_tools: true,
+            supports_streaming: true,
+        }
+    );
+    assert!(requests.lock().unwrap()[0].0.starts_with("get /models "));
+    assert!(requests.lock().unwrap()[0].0.contains("authorization: bearer sk-test"));
+
+    // A model the server doesn't have
+    let provider = OpenAIProvider::compatible(&url, "missing-model", None);
+    let err = provider.health_check().await.unwrap_err();
+    assert!(err.to_string().contains("doesn't serve the model missing-model"), "{}", err);
+
+    // A bad key
+    let provider = OpenAIProvider::compatible(&url, "test-model", Some("sk-bad".to_string()));
+    let err = provider.health_check().await.unwrap_err();
+    assert!(err.to_string().contains("Incorrect API key"), "{}", err);
+
+    // A server listing its models to anyone has the key checked elsewhere
+    let provider = OpenAIProvider::compatible(&url, "test-model", Some("sk-bad".to_string())).with_key_check("key");
+    assert!(provider.health_check().await.is_err());
+    assert!(requests.lock().unwrap()[4].0.starts_with("get /key "));
+}
+
+#[test]
+fn test_session() {
+    use crate::session::{Session, Sessions, MAX_TURNS};
+    use canopy_core::{EdgeId, EdgeKind, EdgeSource, GraphEdge, Subgraph};
+    use std::time::Duration;
+
+    let save = test_function(1, "save_user");
+    let register = test_function(2, "register");
+    let import = test_function(3, "import_users");
+    let calls = |id, source: &GraphNode, target: &GraphNode| GraphEdge {
+        id: EdgeId(id),
+        source: source.id,
+        target: target.id,
+        kind: EdgeKind::Calls,
+        edge_source: EdgeSource::Structural,
+        confidence: 1.0,
+        label: None,
+        file_path: None,
+        line: None,
+    };
+
+    // The first question is asked as it is
+    let mut session = Session::new("s".to_string());
+    assert_eq!(session.prompt("What does save_user do?"), "What does save_user do?");
+    let first = session.context(Subgraph { nodes: vec![save.clone(), register.clone()], edges: vec![calls(1, &register, &save)] });
+    session.record("What does save_user do?", " It saves a user. ", first);
+
+    // A follow-up carries the conversation, and is answered from the nodes
+    // it finds besides those of the earlier question
+    let prompt = session.prompt("And who calls that?");
+    assert!(prompt.contains("Q: What does save_user do?\nA: It saves a user."));
+    assert!(prompt.ends_with("And who calls that?"));
+    let context = session.context(Subgraph { nodes: vec![import.clone(), save.clone()], edges: vec![calls(2, &import, &save)] });
+    let ids: Vec<_> = context.nodes.iter().map(|n| n.id.0).collect();
+    assert_eq!(ids, [3, 1, 2]);
+    assert_eq!(context.edges.len(), 2);
+
+    // Only the latest turns are kept
+    for n in 0..MAX_TURNS + 2 {
+        session.record(&format!("question {}", n), "answer", Subgraph::default());
+    }
+    assert_eq!(session.turns().count(), MAX_TURNS);
+    assert_eq!(session.turns().next().unwrap().question, "question 2");
+
+    // Sessions are found again by id, until they expire
+    let mut sessions = Sessions::new();
+    let opened = sessions.open(None);
+    let id = opened.id.clone();
+    sessions.save(opened);
+    assert_eq!(sessions.open(Some(&id)).id, id);
+    assert_ne!(sessions.open(Some("unknown")).id, "unknown");
+    assert_ne!(sessions.open(None).id, id);
+
+    let mut sessions = Sessions::new().with_ttl(Duration::ZERO);
+    let opened = sessions.open(None);
+    let id = opened.id.clone();
+    sessions.save(opened);
+    assert_ne!(sessions.open(Some(&id)).id, id);
+    assert!(sessions.is_empty());
+}
+
+#[test]
+fn test_graph_change() {
+    use crate::change::{ChangedEdge, FileSnapshot, GraphChange};
+    use crate::prompt::change_explanation_prompt;
+    use canopy_core::EdgeKind;
+    use std::path::Path;
+
+    let at = |id, name: &str, lines: (u32, u32)| test_function(id, name).with_lines(lines.0, lines.1);
+    let calls = |source: &str, target: &str| ChangedEdge {
+        source: format!("src/lib.rs::{}", source),
+        kind: EdgeKind::Calls,
+        target: format!("src/lib.rs::{}", target),
+    };
+    let before = FileSnapshot {
+        nodes: vec![at(1, "pay", (1, 3)), at(2, "refund", (4, 4))],
+        edges: vec![calls("refund", "pay")],
+        source: Some("fn pay() {\n    check();\n}\nfn refund() { pay() }\n".to_string()),
+    };
+    let after = FileSnapshot {
+        nodes: vec![at(3, "pay", (1, 3)), at(4, "refund", (4, 4)), at(5, "validate_payment", (5, 5))],
+        edges: vec![calls("refund", "pay"), calls("pay", "validate_payment")],
+        source: Some("fn pay() {\n    validate_payment(\"sk-proj-4f8Xa9Lq2ZbN7cVt1RmK\");\n}\nfn refund() { pay() }\nfn validate_payment() {}\n".to_string()),
+    };
+
+    // Nodes are matched by name, and edited ones told by their source
+    let change = GraphChange::between(Path::new("src/lib.rs"), &before, &after);
+    let names = |nodes: &[GraphNode]| nodes.iter().map(|n| n.name.clone()).collect::<Vec<_>>();
+    assert_eq!(names(&change.added), ["validate_payment"]);
+    assert!(change.removed.is_empty());
+    assert_eq!(names(&change.modified), ["pay"]);
+    assert_eq!(change.added_edges, [calls("pay", "validate_payment")]);
+    assert!(change.removed_edges.is_empty());
+    // The diff runs from the first changed line to the last
+    assert_eq!(
+        change.source_diff,
+        "@@ line 2 @@\n-    check();\n-}\n-fn refund() { pay() }\n+    validate_payment(\"[REDACTED]\");\n+}\n+fn refund() { pay() }\n+fn validate_payment() {}\n"
+    );
+    assert_eq!(change.describe(), "src/lib.rs: added validate_payment; changed pay; 1 relationship added.");
+
+    let prompt = change_explanation_prompt(&change);
+    assert!(prompt.contains("- src/lib.rs::validate_payment (Function)"));
+    assert!(prompt.contains("- src/lib.rs::pay Calls src/lib.rs::validate_payment"));
+    assert!(prompt.contains("+    validate_payment"));
+
+    // Without the source before, edits are told by length alone
+    let unknown = FileSnapshot { source: None, ..before };
+    let change = GraphChange::between(Path::new("src/lib.rs"), &unknown, &after);
+    assert!(change.modified.is_empty());
+    assert!(change.source_diff.is_empty());
+    assert!(GraphChange::between(Path::new("src/lib.rs"), &after, &after).is_empty());
+}
+
+#[test]
+fn test_semantic_config() {
+    use crate::bridge::SemanticConfig;
+
+    // Without an [ai] section, the defaults
+    let config = SemanticConfig::parse("[index]\nmax_file_size = 100\n").unwrap();
+    assert_eq!(config.relationship_types, [SemanticRelationship::Calls, SemanticRelationship::DependsOn, SemanticRelationship::Uses]);
+    assert_eq!(config.confidence_threshold, 0.7);
+    assert!(!config.auto_accepts(1.0));
+
+    let config = SemanticConfig::parse(
+        r#"
+[ai]
+relationship_types = ["Calls", "TestedBy"]
+confidence_threshold = 0.5
+auto_accept_threshold = 0.9
+"#,
+    )
+    .unwrap();
+    assert_eq!(config.relationship_types, [SemanticRelationship::Calls, SemanticRelationship::TestedBy]);
+    assert_eq!(config.confidence_threshold, 0.5);
+    assert!(config.auto_accepts(0.9));
+    assert!(!config.auto_accepts(0.89));
+
+    assert!(SemanticConfig::parse("[ai]\nconfidence_threshold = 70\n").is_err());
+    assert!(SemanticConfig::parse("[ai]\nrelationship_types = [\"Knows\"]\n").is_err());
+
+    let dir = tempfile::tempdir().unwrap();
+    assert_eq!(SemanticConfig::load(dir.path()).unwrap().confidence_threshold, 0.7);
+    std::fs::write(dir.path().join(".canopy.toml"), "[ai]\nconfidence_threshold = 0.9\n").unwrap();
+    assert_eq!(SemanticConfig::load(dir.path()).unwrap().confidence_threshold, 0.9);
+}
@@ -0,0 +1,107 @@
+//! Replay of recorded API responses
+//!
+//! Fixtures under `tests/fixtures/` are responses recorded from the
+//! providers' APIs, each a JSON file holding the `status` and the `body`
+//! (JSON, or the text of an event stream). A [`FixtureServer`] answers one
+//! request with each fixture in turn on a local port, and keeps the
+//! requests for checking. Requests past the last fixture are refused.
+
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// A recorded response.
+pub struct Fixture {
+    pub status: u16,
+    pub body: String,
+}
+
+/// The fixture at `name`, e.g. `openai/relationships`.
+pub fn fixture(name: &str) -> Fixture {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(format!("{}.json", name));
+    let text = std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("reading {}: {}", path.display(), e));
+    let recorded: serde_json::Value = serde_json::from_str(&text).unwrap();
+    let status = recorded["status"].as_u64().unwrap() as u16;
+    let body = match &recorded["body"] {
+        serde_json::Value::String(text) => text.clone(),
+        body => body.to_string(),
+    };
+    Fixture { status, body }
+}
+
+/// A request received by a [`FixtureServer`].
+#[derive(Debug, Clone)]
+pub struct Request {
+    /// The request line, e.g. `POST /v1/messages HTTP/1.1`.
+    pub line: String,
+    /// The headers, lowercased.
+    pub headers: String,
+    pub body: serde_json::Value,
+}
+
+pub struct FixtureServer {
+    pub url: String,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl FixtureServer {
+    /// Serve the fixtures `names`, one request each, in order.
+    pub async fn replay(names: &[&str]) -> Self {
+        let fixtures: Vec<Fixture> = names.iter().map(|name| fixture(name)).collect();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let received = Arc::clone(&requests);
+        tokio::spawn(async move {
+            for fixture in fixtures {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let request = read_request(&mut socket).await;
+                received.lock().unwrap().push(request);
+                let content_type = if fixture.body.starts_with("event:") { "text/event-stream" } else { "application/json" };
+                let response = format!(
+                    "HTTP/1.1 {} Recorded\r\ncontent-type: {}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    fixture.status,
+                    content_type,
+                    fixture.body.len(),
+                    fixture.body
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        Self { url, requests }
+    }
+
+    /// The requests received so far, oldest first.
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+/// Read a request's headers, then as much body as they announce.
+async fn read_request(socket: &mut tokio::net::TcpStream) -> Request {
+    let mut request = Vec::new();
+    let mut buf = [0; 4096];
+    let body_start = loop {
+        let n = socket.read(&mut buf).await.unwrap();
+        request.extend_from_slice(&buf[..n]);
+        if let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+            break end + 4;
+        }
+    };
+    let head = String::from_utf8_lossy(&request[..body_start]).to_string();
+    let (line, headers) = head.split_once("\r\n").unwrap_or((&head, ""));
+    let headers = headers.to_lowercase();
+    let length: usize = headers
+        .lines()
+        .find_map(|line| line.strip_prefix("content-length:"))
+        .map_or(0, |len| len.trim().parse().unwrap());
+    while request.len() < body_start + length {
+        let n = socket.read(&mut buf).await.unwrap();
+        request.extend_from_slice(&buf[..n]);
+    }
+    Request {
+        line: line.to_string(),
+        headers,
+        body: serde_json::from_slice(&request[body_start..]).unwrap_or_default(),
+    }
+}
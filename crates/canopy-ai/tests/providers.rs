@@ -0,0 +1,190 @@
+//! Providers over the network, against recorded responses
+//!
+//! These tests run the real providers against a [`FixtureServer`] replaying
+//! API responses, and what surrounds them against a [`MockProvider`], so
+//! parsing, budgets, retries and caching are tested without an API key.
+
+mod common;
+
+use canopy_ai::providers::fallback::FallbackProvider;
+use canopy_ai::providers::limit::RateLimits;
+use canopy_ai::providers::mock::{MockProvider, MockReply, MockRequest, MOCK_MODEL};
+use canopy_ai::providers::{create_provider_with, ApiError, ProviderOptions};
+use canopy_ai::{
+    AIProvider, AnalysisCache, AnalysisContext, Budget, InferredRelationship, SemanticAnalysisRequest,
+    SemanticRelationship,
+};
+use canopy_core::{Graph, GraphNode, NodeId, NodeKind};
+use common::FixtureServer;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// A graph of `main`, `load`, `parse` and `validate`, numbered from 0 as
+/// the recorded responses expect.
+fn graph() -> Graph {
+    let mut graph = Graph::new();
+    for name in ["main", "load", "parse", "validate"] {
+        let function = GraphNode::for_test(NodeKind::Function, name)
+            .with_qualified_name(&format!("src/config.rs::{}", name))
+            .in_file("src/config.rs")
+            .with_lines(1, 5)
+            .with_language(canopy_core::Language::Rust);
+        graph.add_node(function);
+    }
+    graph
+}
+
+/// A request to relate `load` to `parse` and `validate`.
+fn request(graph: &Graph) -> SemanticAnalysisRequest {
+    let node = |id| graph.node(NodeId(id)).unwrap().clone();
+    SemanticAnalysisRequest {
+        source_node: node(1),
+        candidate_nodes: vec![node(2), node(3)],
+        context: AnalysisContext {
+            file_path: PathBuf::from("src/config.rs"),
+            language: "Rust".to_string(),
+            enclosing_context: vec![],
+            imports: vec![],
+            project_context: HashMap::new(),
+            examples: vec![],
+            sources: HashMap::new(),
+        },
+        relationship_types: vec![SemanticRelationship::Calls, SemanticRelationship::DependsOn],
+    }
+}
+
+/// Options for `server`, retrying quickly.
+fn options(server: &FixtureServer, model: &str) -> ProviderOptions {
+    let limits = RateLimits { initial_backoff_ms: 1, max_backoff_ms: 2, ..RateLimits::default() };
+    ProviderOptions {
+        api_key: Some("sk-test".to_string()),
+        base_url: Some(server.url.clone()),
+        model: Some(model.to_string()),
+        rate_limits: ["openai", "anthropic"].into_iter().map(|name| (name.to_string(), limits.clone())).collect(),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn openai_relationships_parsed() {
+    let server = FixtureServer::replay(&["openai/relationships"]).await;
+    let provider = create_provider_with("openai", &options(&server, "gpt-4o-mini")).unwrap();
+
+    let result = provider.analyze_semantic_relationships(request(&graph())).await.unwrap();
+    let found: Vec<(NodeId, SemanticRelationship)> = result.relationships.iter().map(|r| (r.target_id, r.relationship)).collect();
+    assert_eq!(found, [(NodeId(2), SemanticRelationship::Calls), (NodeId(3), SemanticRelationship::DependsOn)]);
+    assert_eq!(result.relationships[0].line_reference, Some(3));
+    assert_eq!(result.relationships[1].confidence, 0.64);
+    assert_eq!(result.explanation, "load reads a file and parses it into a Config");
+    assert_eq!(result.tokens_used, 470);
+
+    let requests = server.requests();
+    assert_eq!(requests[0].line, "POST /chat/completions HTTP/1.1");
+    assert!(requests[0].headers.contains("authorization: bearer sk-test"));
+    assert_eq!(requests[0].body["model"], "gpt-4o-mini");
+}
+
+#[tokio::test]
+async fn anthropic_relationships_parsed() {
+    let server = FixtureServer::replay(&["anthropic/relationships"]).await;
+    let provider = create_provider_with("anthropic", &options(&server, "claude-3-5-haiku-latest")).unwrap();
+
+    let result = provider.analyze_semantic_relationships(request(&graph())).await.unwrap();
+    assert_eq!(result.relationships.len(), 1);
+    assert_eq!((result.relationships[0].target_id, result.relationships[0].confidence), (NodeId(2), 0.88));
+    assert_eq!((result.usage[0].input_tokens, result.usage[0].output_tokens), (530, 71));
+    assert_eq!(server.requests()[0].line, "POST /v1/messages HTTP/1.1");
+}
+
+#[tokio::test]
+async fn budget_counts_reported_usage() {
+    let server = FixtureServer::replay(&["openai/relationships"]).await;
+    let provider = create_provider_with("openai", &options(&server, "gpt-4o-mini")).unwrap();
+    let result = provider.analyze_semantic_relationships(request(&graph())).await.unwrap();
+
+    // Tokens are counted as reported, and priced by the model that
+    // answered
+    let mut budget = Budget::new(1000);
+    budget.record_result(&result);
+    assert_eq!(budget.tokens_used, 470);
+    let spend = &budget.spend.models["gpt-4o-mini"];
+    assert_eq!((spend.input_tokens, spend.output_tokens), (412, 58));
+    assert!((budget.spend.cost_usd - (412.0 * 0.15 + 58.0 * 0.60) / 1_000_000.0).abs() < 1e-12);
+
+    // Unpriced models count their tokens at no cost
+    let mock = MockProvider::new().with_usage(300, 100);
+    budget.record_result(&mock.analyze_semantic_relationships(request(&graph())).await.unwrap());
+    assert_eq!(budget.tokens_used, 870);
+    assert_eq!(budget.spend.models[MOCK_MODEL].cost_usd, 0.0);
+    assert!(!budget.has_budget(200));
+}
+
+#[tokio::test]
+async fn transient_failures_retried() {
+    // A rate limit is waited out and the request sent again
+    let server = FixtureServer::replay(&["openai/rate_limited", "openai/relationships"]).await;
+    let provider = create_provider_with("openai", &options(&server, "gpt-4o-mini")).unwrap();
+    let result = provider.analyze_semantic_relationships(request(&graph())).await.unwrap();
+    assert_eq!(result.relationships.len(), 2);
+    assert_eq!(server.requests().len(), 2);
+
+    // A bad key is not
+    let server = FixtureServer::replay(&["openai/invalid_key"]).await;
+    let provider = create_provider_with("openai", &options(&server, "gpt-4o-mini")).unwrap();
+    let error = provider.analyze_semantic_relationships(request(&graph())).await.unwrap_err();
+    let api = error.downcast_ref::<ApiError>().unwrap();
+    assert_eq!(api.status, 401);
+    assert!(api.body.starts_with("Incorrect API key provided"));
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn cached_analysis_sends_no_request() {
+    let graph = graph();
+    let server = FixtureServer::replay(&["openai/relationships"]).await;
+    let provider = create_provider_with("openai", &options(&server, "gpt-4o-mini")).unwrap();
+    let mut cache = AnalysisCache::new(Duration::from_secs(3600));
+    let source = graph.node(NodeId(1)).unwrap().clone();
+
+    assert!(cache.get(&source, 7, &graph).is_none());
+    let result = provider.analyze_semantic_relationships(request(&graph)).await.unwrap();
+    let relationships: Vec<&InferredRelationship> = result.relationships.iter().collect();
+    cache.insert(&source, 7, &relationships, &result.provider, &graph);
+
+    // The same source is answered from the cache, with nothing sent; a
+    // changed one is not
+    let cached = cache.get(&source, 7, &graph).unwrap();
+    assert_eq!(cached.relationships.len(), 2);
+    assert_eq!(cached.provider, result.provider);
+    assert!(cache.get(&source, 8, &graph).is_none());
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn mock_provider_scripts_replies() {
+    let load = NodeId(1);
+    let relationship = InferredRelationship {
+        source_id: load,
+        target_id: NodeId(2),
+        relationship: SemanticRelationship::Calls,
+        confidence: 0.9,
+        explanation: "load calls parse".to_string(),
+        line_reference: None,
+    };
+    let failing = MockProvider::new().with_name("Failing").reply(MockReply::Error(503));
+    let answering = MockProvider::new().with_name("Answering").reply(MockReply::Relationships(vec![relationship]));
+    let provider = FallbackProvider::new(vec![Box::new(failing.clone()), Box::new(answering.clone())]);
+
+    // The failure passes the request on to the next provider
+    let result = provider.analyze_semantic_relationships(request(&graph())).await.unwrap();
+    assert_eq!((result.provider.as_str(), result.relationships.len()), ("Answering", 1));
+    let asked = MockRequest::Analysis { sources: vec![load], candidates: vec![NodeId(2), NodeId(3)] };
+    assert_eq!(failing.requests(), answering.requests());
+    assert_eq!(answering.requests(), [asked]);
+
+    // Past the script, analyses find nothing
+    let result = answering.analyze_semantic_relationships(request(&graph())).await.unwrap();
+    assert!(result.relationships.is_empty());
+    assert_eq!(answering.remaining(), 0);
+}
@@ -1,7 +1,8 @@
 //! Edge aggregation for collapsed container nodes
 
+use crate::diff::GraphDiff;
 use crate::graph::Graph;
-use crate::model::{NodeId, EdgeKind, AggregatedEdge};
+use crate::model::{NodeId, EdgeId, EdgeKind, GraphEdge, AggregatedEdge};
 use std::collections::{HashMap, HashSet};
 
 /// Compute aggregated edges for currently visible/collapsed nodes.
@@ -73,3 +74,299 @@ fn nearest_visible_ancestor(
     }
     node
 }
+
+/// A change to the visible/collapsed node sets.
+#[derive(Debug, Clone, Default)]
+pub struct VisibilityDelta {
+    /// Nodes that became visible.
+    pub shown: Vec<NodeId>,
+    /// Nodes that are no longer visible.
+    pub hidden: Vec<NodeId>,
+    /// Container nodes that were collapsed.
+    pub collapsed: Vec<NodeId>,
+    /// Container nodes that were expanded.
+    pub expanded: Vec<NodeId>,
+}
+
+impl VisibilityDelta {
+    /// Check if this delta is empty (no changes).
+    pub fn is_empty(&self) -> bool {
+        self.shown.is_empty()
+            && self.hidden.is_empty()
+            && self.collapsed.is_empty()
+            && self.expanded.is_empty()
+    }
+
+    fn touched(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.shown
+            .iter()
+            .chain(&self.hidden)
+            .chain(&self.collapsed)
+            .chain(&self.expanded)
+            .copied()
+    }
+}
+
+/// Bookkeeping for an underlying edge tracked by the aggregator.
+#[derive(Debug, Clone)]
+struct TrackedEdge {
+    source: NodeId,
+    target: NodeId,
+    kind: EdgeKind,
+    /// Confidence, recorded only for AI edges.
+    ai_confidence: Option<f32>,
+    /// The aggregated edge this edge currently contributes to.
+    key: Option<(NodeId, NodeId)>,
+}
+
+/// Aggregated edges kept up to date as the graph and view change.
+///
+/// Produces the same result as [`aggregate_edges`], but after the initial
+/// build only the edges touched by a `GraphDiff` or `VisibilityDelta` are
+/// re-resolved instead of walking every edge in the graph.
+#[derive(Debug, Clone, Default)]
+pub struct IncrementalAggregator {
+    visible_nodes: HashSet<NodeId>,
+    collapsed_nodes: HashSet<NodeId>,
+    edges: HashMap<EdgeId, TrackedEdge>,
+    /// Edge IDs incident to each node (either endpoint), including Contains edges.
+    incident: HashMap<NodeId, HashSet<EdgeId>>,
+    aggregated: HashMap<(NodeId, NodeId), AggregatedEdge>,
+}
+
+impl IncrementalAggregator {
+    /// Build the aggregation from scratch for the given view.
+    pub fn new(
+        graph: &Graph,
+        visible_nodes: HashSet<NodeId>,
+        collapsed_nodes: HashSet<NodeId>,
+    ) -> Self {
+        let mut aggregator = IncrementalAggregator {
+            visible_nodes,
+            collapsed_nodes,
+            ..Default::default()
+        };
+        for edge in graph.all_edges() {
+            aggregator.track(graph, edge);
+        }
+        aggregator
+    }
+
+    /// Currently visible nodes.
+    pub fn visible_nodes(&self) -> &HashSet<NodeId> {
+        &self.visible_nodes
+    }
+
+    /// Currently collapsed nodes.
+    pub fn collapsed_nodes(&self) -> &HashSet<NodeId> {
+        &self.collapsed_nodes
+    }
+
+    /// Iterate over the current aggregated edges.
+    pub fn edges(&self) -> impl Iterator<Item = &AggregatedEdge> {
+        self.aggregated.values()
+    }
+
+    /// Get the aggregated edge between two visible nodes.
+    pub fn get(&self, source: NodeId, target: NodeId) -> Option<&AggregatedEdge> {
+        self.aggregated.get(&(source, target))
+    }
+
+    /// Snapshot the current aggregated edges.
+    pub fn aggregated_edges(&self) -> Vec<AggregatedEdge> {
+        self.aggregated.values().cloned().collect()
+    }
+
+    /// Apply a graph diff. `graph` must already reflect the diff.
+    pub fn apply_diff(&mut self, graph: &Graph, diff: &GraphDiff) {
+        let mut dirty = Vec::new();
+
+        for &edge_id in &diff.removed_edges {
+            if let Some(tracked) = self.untrack(edge_id)
+                && tracked.kind == EdgeKind::Contains {
+                dirty.push(tracked.target);
+            }
+        }
+
+        for &node_id in &diff.removed_nodes {
+            self.visible_nodes.remove(&node_id);
+            self.collapsed_nodes.remove(&node_id);
+            // Removing a node drops its edges even if the diff doesn't list them
+            for edge_id in self.incident.remove(&node_id).unwrap_or_default() {
+                if let Some(tracked) = self.untrack(edge_id)
+                    && tracked.kind == EdgeKind::Contains && tracked.source == node_id {
+                    dirty.push(tracked.target);
+                }
+            }
+        }
+
+        for edge in &diff.added_edges {
+            // Prefer the graph's copy, which carries the assigned endpoints
+            let edge = graph.edge(edge.id).unwrap_or(edge);
+            self.untrack(edge.id);
+            self.track(graph, edge);
+            if edge.kind == EdgeKind::Contains {
+                dirty.push(edge.target);
+            }
+        }
+
+        self.refresh(graph, dirty);
+    }
+
+    /// Apply a visibility change and re-resolve the affected edges.
+    pub fn apply_visibility(&mut self, graph: &Graph, delta: &VisibilityDelta) {
+        for node in &delta.hidden {
+            self.visible_nodes.remove(node);
+        }
+        for node in &delta.shown {
+            self.visible_nodes.insert(*node);
+        }
+        for node in &delta.expanded {
+            self.collapsed_nodes.remove(node);
+        }
+        for node in &delta.collapsed {
+            self.collapsed_nodes.insert(*node);
+        }
+
+        self.refresh(graph, delta.touched().collect());
+    }
+
+    /// Start tracking an edge and add it to its aggregated edge.
+    fn track(&mut self, graph: &Graph, edge: &GraphEdge) {
+        let mut tracked = TrackedEdge {
+            source: edge.source,
+            target: edge.target,
+            kind: edge.kind,
+            ai_confidence: (edge.edge_source == crate::model::EdgeSource::AI).then_some(edge.confidence),
+            key: None,
+        };
+        if tracked.kind != EdgeKind::Contains {
+            tracked.key = self.resolve(graph, &tracked);
+            if let Some(key) = tracked.key {
+                self.insert_into(key, edge.id, &tracked);
+            }
+        }
+
+        self.incident.entry(edge.source).or_default().insert(edge.id);
+        self.incident.entry(edge.target).or_default().insert(edge.id);
+        self.edges.insert(edge.id, tracked);
+    }
+
+    /// Stop tracking an edge and remove it from its aggregated edge.
+    fn untrack(&mut self, edge_id: EdgeId) -> Option<TrackedEdge> {
+        let tracked = self.edges.remove(&edge_id)?;
+        for endpoint in [tracked.source, tracked.target] {
+            if let Some(ids) = self.incident.get_mut(&endpoint) {
+                ids.remove(&edge_id);
+                if ids.is_empty() {
+                    self.incident.remove(&endpoint);
+                }
+            }
+        }
+        if let Some(key) = tracked.key {
+            self.remove_from(key, edge_id, &tracked);
+        }
+        Some(tracked)
+    }
+
+    /// Re-resolve every edge with an endpoint in the subtrees rooted at `roots`.
+    fn refresh(&mut self, graph: &Graph, roots: Vec<NodeId>) {
+        let mut seen = HashSet::new();
+        let mut to_visit = roots;
+        let mut affected_edges = HashSet::new();
+
+        while let Some(node) = to_visit.pop() {
+            if !seen.insert(node) {
+                continue;
+            }
+            if let Some(ids) = self.incident.get(&node) {
+                affected_edges.extend(ids.iter().copied());
+            }
+            to_visit.extend(
+                graph
+                    .edges_from(node)
+                    .filter(|e| e.kind == EdgeKind::Contains)
+                    .map(|e| e.target),
+            );
+        }
+
+        for edge_id in affected_edges {
+            let Some(tracked) = self.edges.get(&edge_id).cloned() else {
+                continue;
+            };
+            if tracked.kind == EdgeKind::Contains {
+                continue;
+            }
+
+            let new_key = self.resolve(graph, &tracked);
+            if new_key == tracked.key {
+                continue;
+            }
+            if let Some(old_key) = tracked.key {
+                self.remove_from(old_key, edge_id, &tracked);
+            }
+            if let Some(key) = new_key {
+                self.insert_into(key, edge_id, &tracked);
+            }
+            if let Some(entry) = self.edges.get_mut(&edge_id) {
+                entry.key = new_key;
+            }
+        }
+    }
+
+    /// Aggregation key for an edge, or None if it collapses into a self-loop.
+    fn resolve(&self, graph: &Graph, tracked: &TrackedEdge) -> Option<(NodeId, NodeId)> {
+        let source = nearest_visible_ancestor(graph, tracked.source, &self.visible_nodes, &self.collapsed_nodes);
+        let target = nearest_visible_ancestor(graph, tracked.target, &self.visible_nodes, &self.collapsed_nodes);
+        (source != target).then_some((source, target))
+    }
+
+    fn insert_into(&mut self, key: (NodeId, NodeId), edge_id: EdgeId, tracked: &TrackedEdge) {
+        let agg = self.aggregated.entry(key).or_insert_with(|| AggregatedEdge {
+            source: key.0,
+            target: key.1,
+            count: 0,
+            kind_counts: HashMap::new(),
+            underlying_edge_ids: Vec::new(),
+            min_confidence: None,
+        });
+
+        agg.count += 1;
+        *agg.kind_counts.entry(tracked.kind).or_insert(0) += 1;
+        agg.underlying_edge_ids.push(edge_id);
+
+        if let Some(conf) = tracked.ai_confidence
+            && (agg.min_confidence.is_none() || Some(conf) < agg.min_confidence) {
+            agg.min_confidence = Some(conf);
+        }
+    }
+
+    fn remove_from(&mut self, key: (NodeId, NodeId), edge_id: EdgeId, tracked: &TrackedEdge) {
+        let Some(agg) = self.aggregated.get_mut(&key) else {
+            return;
+        };
+
+        agg.underlying_edge_ids.retain(|id| *id != edge_id);
+        agg.count = agg.count.saturating_sub(1);
+        if let Some(count) = agg.kind_counts.get_mut(&tracked.kind) {
+            *count -= 1;
+            if *count == 0 {
+                agg.kind_counts.remove(&tracked.kind);
+            }
+        }
+
+        if agg.count == 0 {
+            self.aggregated.remove(&key);
+            return;
+        }
+
+        // Only AI edges affect the minimum, so recompute just when one leaves
+        if tracked.ai_confidence.is_some() {
+            agg.min_confidence = agg
+                .underlying_edge_ids
+                .iter()
+                .filter_map(|id| self.edges.get(id).and_then(|e| e.ai_confidence))
+                .reduce(f32::min);
+        }
+    }
+}
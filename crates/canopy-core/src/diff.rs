@@ -1,7 +1,14 @@
 //! Graph diff computation for incremental updates
 
+use crate::graph::Graph;
 use crate::model::*;
+use crate::snapshot::{diff_snapshots, Snapshot};
+use anyhow::{bail, Context};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
 
 /// Represents a change to the graph that should be broadcast to clients.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -64,10 +71,9 @@ impl DiffEngine {
 
         // Find added nodes
         for node_id in new_graph.nodes_of_kind(NodeKind::Unknown) {
-            if old_graph.node(node_id).is_none() {
-                if let Some(node) = new_graph.node(node_id) {
-                    diff.added_nodes.push(node.clone());
-                }
+            if old_graph.node(node_id).is_none()
+                && let Some(node) = new_graph.node(node_id) {
+                diff.added_nodes.push(node.clone());
             }
         }
 
@@ -110,3 +116,342 @@ impl Default for DiffEngine {
         Self::new()
     }
 }
+
+/// Diffs a [`DiffJournal`] keeps by default.
+pub const DEFAULT_JOURNAL_CAPACITY: usize = 1000;
+
+/// The latest diffs broadcast, for clients to catch up on those they
+/// missed from the sequence number they last saw.
+#[derive(Debug, Clone)]
+pub struct DiffJournal {
+    diffs: VecDeque<GraphDiff>,
+    capacity: usize,
+    /// Sequence number of the last diff recorded
+    sequence: u64,
+    /// Sequence number of the last diff dropped to make room
+    dropped: u64,
+}
+
+impl DiffJournal {
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_JOURNAL_CAPACITY)
+    }
+
+    /// A journal keeping the last `capacity` diffs.
+    pub fn with_capacity(capacity: usize) -> Self {
+        DiffJournal { diffs: VecDeque::new(), capacity: capacity.max(1), sequence: 0, dropped: 0 }
+    }
+
+    /// Keep `diff`, dropping the oldest if the journal is full.
+    pub fn record(&mut self, diff: GraphDiff) {
+        if self.diffs.len() == self.capacity
+            && let Some(oldest) = self.diffs.pop_front()
+        {
+            self.dropped = oldest.sequence;
+        }
+        self.sequence = self.sequence.max(diff.sequence);
+        self.diffs.push_back(diff);
+    }
+
+    /// Sequence number of the last diff recorded, 0 before any.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// The diffs after `sequence`, oldest first, or `None` if some have
+    /// been dropped or `sequence` is ahead of the journal, as it is for a
+    /// client of an earlier run. Either way the client must start over
+    /// from the full graph.
+    pub fn since(&self, sequence: u64) -> Option<Vec<GraphDiff>> {
+        if sequence < self.dropped || sequence > self.sequence {
+            return None;
+        }
+        Some(self.diffs.iter().filter(|d| d.sequence > sequence).cloned().collect())
+    }
+}
+
+impl Default for DiffJournal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A symbol as reported in a [`DiffSummary`], with root-relative paths.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SymbolChange {
+    pub kind: NodeKind,
+    pub name: String,
+    pub qualified_name: String,
+    pub file_path: PathBuf,
+}
+
+/// A symbol that disappeared from one file and appeared in another.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MovedSymbol {
+    pub kind: NodeKind,
+    pub name: String,
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+/// Human-oriented summary of the differences between two trees.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DiffSummary {
+    pub files_added: Vec<PathBuf>,
+    pub files_removed: Vec<PathBuf>,
+    pub added: Vec<SymbolChange>,
+    pub removed: Vec<SymbolChange>,
+    pub moved: Vec<MovedSymbol>,
+    /// Symbols whose signature or size changed.
+    pub modified: Vec<SymbolChange>,
+    pub edges_added: usize,
+    pub edges_removed: usize,
+}
+
+impl DiffSummary {
+    pub fn is_empty(&self) -> bool {
+        self.files_added.is_empty()
+            && self.files_removed.is_empty()
+            && self.added.is_empty()
+            && self.removed.is_empty()
+            && self.moved.is_empty()
+            && self.modified.is_empty()
+            && self.edges_added == 0
+            && self.edges_removed == 0
+    }
+}
+
+impl fmt::Display for DiffSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No structural changes.");
+        }
+        for path in &self.files_added {
+            writeln!(f, "+ file {}", path.display())?;
+        }
+        for path in &self.files_removed {
+            writeln!(f, "- file {}", path.display())?;
+        }
+        for symbol in &self.added {
+            writeln!(f, "+ {:?} {} ({})", symbol.kind, symbol.name, symbol.file_path.display())?;
+        }
+        for symbol in &self.removed {
+            writeln!(f, "- {:?} {} ({})", symbol.kind, symbol.name, symbol.file_path.display())?;
+        }
+        for symbol in &self.moved {
+            writeln!(f, "> {:?} {} ({} -> {})", symbol.kind, symbol.name, symbol.from.display(), symbol.to.display())?;
+        }
+        for symbol in &self.modified {
+            writeln!(f, "~ {:?} {} ({})", symbol.kind, symbol.name, symbol.file_path.display())?;
+        }
+        writeln!(f, "{} edges added, {} removed", self.edges_added, self.edges_removed)
+    }
+}
+
+/// Structural diff between two trees plus its summary.
+#[derive(Debug, Clone)]
+pub struct RootDiff {
+    /// Removed IDs refer to the first graph; added and modified to the second.
+    pub diff: GraphDiff,
+    pub summary: DiffSummary,
+}
+
+/// Index two checkouts with `index` and diff them.
+///
+/// Core does not depend on the indexer, so callers supply the function that
+/// builds a graph for a directory. See [`diff_graphs`] to diff graphs that are
+/// already built (e.g. from snapshots).
+pub fn diff_roots<F>(root_a: &Path, root_b: &Path, mut index: F) -> anyhow::Result<RootDiff>
+where
+    F: FnMut(&Path) -> anyhow::Result<Graph>,
+{
+    let graph_a = index(root_a).with_context(|| format!("indexing {}", root_a.display()))?;
+    let graph_b = index(root_b).with_context(|| format!("indexing {}", root_b.display()))?;
+    Ok(diff_graphs(&graph_a, root_a, &graph_b, root_b))
+}
+
+/// Check out two git refs of `repo` into temporary worktrees and diff them.
+pub fn diff_git_refs<F>(repo: &Path, ref_a: &str, ref_b: &str, index: F) -> anyhow::Result<RootDiff>
+where
+    F: FnMut(&Path) -> anyhow::Result<Graph>,
+{
+    let worktree_a = Worktree::checkout(repo, ref_a)?;
+    let worktree_b = Worktree::checkout(repo, ref_b)?;
+    diff_roots(&worktree_a.path, &worktree_b.path, index)
+}
+
+/// A detached git worktree, removed on drop.
+struct Worktree {
+    repo: PathBuf,
+    path: PathBuf,
+}
+
+impl Worktree {
+    fn checkout(repo: &Path, git_ref: &str) -> anyhow::Result<Self> {
+        let path = crate::cache::cache_dir(repo).join("worktrees").join(worktree_dir_name(git_ref));
+        if path.exists() {
+            git(repo, &["worktree", "remove", "--force", &path.to_string_lossy()])?;
+        }
+        git(repo, &["worktree", "add", "--detach", &path.to_string_lossy(), git_ref])
+            .with_context(|| format!("checking out '{}'", git_ref))?;
+        Ok(Worktree { repo: repo.to_path_buf(), path })
+    }
+}
+
+/// A directory name for a ref's worktree: the ref made safe for a path,
+/// then a hash of the ref as given, so refs that read alike once made safe
+/// (`feature/a`, `feature_a`) get worktrees of their own.
+fn worktree_dir_name(git_ref: &str) -> String {
+    let safe: String = git_ref
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+        .collect();
+    let mut hasher = DefaultHasher::new();
+    git_ref.hash(&mut hasher);
+    format!("{}-{:016x}", safe, hasher.finish())
+}
+
+impl Drop for Worktree {
+    fn drop(&mut self) {
+        if let Err(err) = git(&self.repo, &["worktree", "remove", "--force", &self.path.to_string_lossy()]) {
+            tracing::warn!("Failed to remove worktree {}: {}", self.path.display(), err);
+        }
+    }
+}
+
+fn git(repo: &Path, args: &[&str]) -> anyhow::Result<()> {
+    let output = std::process::Command::new("git").arg("-C").arg(repo).args(args).output()?;
+    if !output.status.success() {
+        bail!("git {} failed: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
+
+/// Diff two graphs built from different roots.
+///
+/// File paths, and qualified names that embed them, are made relative to
+/// their root so the same file matches across checkouts.
+pub fn diff_graphs(graph_a: &Graph, root_a: &Path, graph_b: &Graph, root_b: &Path) -> RootDiff {
+    let before = relativize(graph_a, root_a);
+    let after = relativize(graph_b, root_b);
+    let diff = diff_snapshots(&before, &after);
+
+    let is_symbol = |node: &GraphNode| !matches!(node.kind, NodeKind::Directory | NodeKind::File | NodeKind::WorkspaceRoot);
+    let change = |node: &GraphNode| SymbolChange {
+        kind: node.kind,
+        name: node.name.clone(),
+        qualified_name: node.qualified_name.to_string(),
+        file_path: node.file_path.to_path_buf(),
+    };
+
+    let before_nodes: HashMap<NodeId, &GraphNode> = before.nodes.iter().map(|n| (n.id, n)).collect();
+    let after_nodes: HashMap<NodeId, &GraphNode> = after.nodes.iter().map(|n| (n.id, n)).collect();
+    let removed: Vec<&GraphNode> = diff.removed_nodes.iter().filter_map(|id| before_nodes.get(id).copied()).collect();
+
+    let mut summary = DiffSummary {
+        edges_added: diff.added_edges.len(),
+        edges_removed: diff.removed_edges.len(),
+        ..Default::default()
+    };
+
+    // A removed and an added symbol with the same kind and name, each unique
+    // among the changes, is reported as a move.
+    let move_key = |node: &GraphNode| (node.kind, node.name.clone());
+    let mut removed_by_key: HashMap<_, Vec<&GraphNode>> = HashMap::new();
+    for node in removed.iter().filter(|n| is_symbol(n)) {
+        removed_by_key.entry(move_key(node)).or_default().push(node);
+    }
+    let mut added_by_key: HashMap<_, Vec<&GraphNode>> = HashMap::new();
+    for node in diff.added_nodes.iter().filter(|n| is_symbol(n)) {
+        added_by_key.entry(move_key(node)).or_default().push(node);
+    }
+    let mut moved_keys = HashSet::new();
+    for (key, added) in &added_by_key {
+        if let (Some(removed), [to]) = (removed_by_key.get(key), added.as_slice())
+            && let [from] = removed.as_slice()
+            && from.file_path != to.file_path {
+            summary.moved.push(MovedSymbol {
+                kind: to.kind,
+                name: to.name.clone(),
+                from: from.file_path.to_path_buf(),
+                to: to.file_path.to_path_buf(),
+            });
+            moved_keys.insert(key.clone());
+        }
+    }
+
+    for node in &diff.added_nodes {
+        if node.kind == NodeKind::File {
+            summary.files_added.push(node.file_path.to_path_buf());
+        } else if is_symbol(node) && !moved_keys.contains(&move_key(node)) {
+            summary.added.push(change(node));
+        }
+    }
+    for node in &removed {
+        if node.kind == NodeKind::File {
+            summary.files_removed.push(node.file_path.to_path_buf());
+        } else if is_symbol(node) && !moved_keys.contains(&move_key(node)) {
+            summary.removed.push(change(node));
+        }
+    }
+
+    // Line shifts alone are not interesting to a reviewer
+    let before_by_key: HashMap<(NodeKind, &str, &Path), &GraphNode> = before
+        .nodes
+        .iter()
+        .map(|n| ((n.kind, n.qualified_name.as_str(), n.file_path.as_path()), n))
+        .collect();
+    for id in &diff.modified_nodes {
+        let Some(node) = after_nodes.get(id).filter(|n| is_symbol(n)) else {
+            continue;
+        };
+        let Some(old) = before_by_key.get(&(node.kind, node.qualified_name.as_str(), node.file_path.as_path())) else {
+            continue;
+        };
+        if old.metadata.signature != node.metadata.signature || old.loc != node.loc {
+            summary.modified.push(change(node));
+        }
+    }
+
+    summary.files_added.sort();
+    summary.files_removed.sort();
+    for list in [&mut summary.added, &mut summary.removed, &mut summary.modified] {
+        list.sort_by(|a, b| (&a.file_path, &a.name).cmp(&(&b.file_path, &b.name)));
+    }
+    summary.moved.sort_by(|a, b| (&a.to, &a.name).cmp(&(&b.to, &b.name)));
+
+    RootDiff { diff, summary }
+}
+
+/// Snapshot of a graph with paths made relative to `root`.
+fn relativize(graph: &Graph, root: &Path) -> Snapshot {
+    let mut snapshot = Snapshot::from_graph("", graph);
+    let prefix = format!("{}/", root.display());
+    for node in &mut snapshot.nodes {
+        if let Ok(relative) = node.file_path.strip_prefix(root) {
+            node.file_path = relative.into();
+        }
+        if let Some(rest) = node.qualified_name.strip_prefix(&prefix) {
+            node.qualified_name = rest.into();
+        }
+    }
+    for edge in &mut snapshot.edges {
+        if let Some(path) = &edge.file_path
+            && let Ok(relative) = path.strip_prefix(root) {
+            edge.file_path = Some(relative.into());
+        }
+    }
+    snapshot
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_worktree_dir_name() {
+        assert!(worktree_dir_name("feature/a").starts_with("feature_a-"));
+        assert_ne!(worktree_dir_name("feature/a"), worktree_dir_name("feature_a"));
+        assert_eq!(worktree_dir_name("v1.0"), worktree_dir_name("v1.0"));
+    }
+}
@@ -0,0 +1,445 @@
+//! Graph export to text diagram and interchange formats
+
+use crate::aggregation::aggregate_edges;
+use crate::graph::{Graph, Subgraph};
+use crate::model::*;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write;
+use std::path::Path;
+
+/// Options for [`to_dot`].
+#[derive(Debug, Clone)]
+pub struct DotOptions {
+    /// Name of the emitted digraph.
+    pub graph_name: String,
+    /// Group nodes into clusters by their directory instead of drawing
+    /// directory nodes and their Contains edges.
+    pub cluster_by_directory: bool,
+    /// Draw Contains edges (ignored when clustering).
+    pub include_contains: bool,
+    /// Append the confidence to labels of AI edges.
+    pub show_confidence: bool,
+}
+
+impl Default for DotOptions {
+    fn default() -> Self {
+        DotOptions {
+            graph_name: "canopy".to_string(),
+            cluster_by_directory: false,
+            include_contains: true,
+            show_confidence: true,
+        }
+    }
+}
+
+/// Render the graph as Graphviz DOT.
+pub fn to_dot(graph: &Graph, options: &DotOptions) -> String {
+    let mut out = dot_header(options);
+
+    if options.cluster_by_directory {
+        let mut clusters: BTreeMap<String, Vec<&GraphNode>> = BTreeMap::new();
+        for node in graph.all_nodes().filter(|n| n.kind != NodeKind::Directory) {
+            let dir = cluster_dir(node).display().to_string();
+            clusters.entry(dir).or_default().push(node);
+        }
+        for (index, (dir, nodes)) in clusters.iter().enumerate() {
+            writeln!(out, "  subgraph cluster_{} {{", index).unwrap();
+            writeln!(out, "    label={};", quote(dir)).unwrap();
+            for node in nodes {
+                writeln!(out, "    {}", dot_node(node)).unwrap();
+            }
+            writeln!(out, "  }}").unwrap();
+        }
+    } else {
+        for node in graph.all_nodes() {
+            writeln!(out, "  {}", dot_node(node)).unwrap();
+        }
+    }
+
+    for edge in graph.all_edges() {
+        if edge.kind == EdgeKind::Contains && (options.cluster_by_directory || !options.include_contains) {
+            continue;
+        }
+        if options.cluster_by_directory && [edge.source, edge.target].iter().any(|&id| {
+            graph.node(id).is_some_and(|n| n.kind == NodeKind::Directory)
+        }) {
+            continue;
+        }
+
+        writeln!(out, "  {}", dot_edge(edge, options)).unwrap();
+    }
+
+    writeln!(out, "}}").unwrap();
+    out
+}
+
+/// Which part of the graph [`to_mermaid`] renders.
+#[derive(Debug, Clone)]
+pub struct MermaidScope {
+    /// Directory, module or other container to render the contents of.
+    pub root: NodeId,
+    /// Maximum number of nodes to draw. Above this, only the direct children
+    /// of `root` are drawn, with edges aggregated onto them.
+    pub max_nodes: usize,
+}
+
+impl MermaidScope {
+    pub fn new(root: NodeId) -> Self {
+        MermaidScope { root, max_nodes: 50 }
+    }
+}
+
+/// Render the contents of a container as a Mermaid flowchart.
+pub fn to_mermaid(graph: &Graph, scope: &MermaidScope) -> String {
+    let mut descendants = Vec::new();
+    let mut stack = vec![scope.root];
+    let mut seen = HashSet::new();
+    while let Some(id) = stack.pop() {
+        if !seen.insert(id) {
+            continue;
+        }
+        if id != scope.root {
+            descendants.push(id);
+        }
+        stack.extend(
+            graph
+                .edges_from(id)
+                .filter(|e| e.kind == EdgeKind::Contains)
+                .map(|e| e.target),
+        );
+    }
+
+    let (mut visible_sorted, collapsed): (Vec<NodeId>, HashSet<NodeId>) = if descendants.len() <= scope.max_nodes {
+        (descendants, HashSet::new())
+    } else {
+        // Too many nodes: collapse to the direct children of the scope
+        let mut children: Vec<NodeId> = graph
+            .edges_from(scope.root)
+            .filter(|e| e.kind == EdgeKind::Contains)
+            .map(|e| e.target)
+            .collect();
+        children.sort_by_key(|id| id.0);
+        children.truncate(scope.max_nodes);
+        let collapsed = children
+            .iter()
+            .copied()
+            .filter(|&id| graph.node(id).is_some_and(|n| n.is_container))
+            .collect();
+        (children, collapsed)
+    };
+
+    visible_sorted.sort_by_key(|id| id.0);
+    let visible: HashSet<NodeId> = visible_sorted.iter().copied().collect();
+
+    let mut out = String::from("flowchart LR\n");
+    for &id in &visible_sorted {
+        if let Some(node) = graph.node(id) {
+            let label = mermaid_escape(&node.name);
+            let shape = if collapsed.contains(&id) {
+                format!("[[\"{}\"]]", label)
+            } else if matches!(node.kind, NodeKind::Function | NodeKind::Method | NodeKind::TestCase) {
+                format!("(\"{}\")", label)
+            } else {
+                format!("[\"{}\"]", label)
+            };
+            writeln!(out, "    n{}{}", id.0, shape).unwrap();
+        }
+    }
+
+    let mut edges: Vec<AggregatedEdge> = aggregate_edges(graph, &visible, &collapsed)
+        .into_iter()
+        .filter(|e| visible.contains(&e.source) && visible.contains(&e.target))
+        .collect();
+    edges.sort_by_key(|e| (e.source.0, e.target.0));
+
+    for edge in edges {
+        let label = if edge.count == 1 {
+            edge.kind_counts
+                .keys()
+                .next()
+                .map(|&kind| edge_kind_name(kind).to_string())
+                .unwrap_or_default()
+        } else {
+            format!("{} edges", edge.count)
+        };
+        let arrow = if edge.min_confidence.is_some() { "-.->" } else { "-->" };
+        writeln!(out, "    n{} {}|\"{}\"| n{}", edge.source.0, arrow, mermaid_escape(&label), edge.target.0).unwrap();
+    }
+
+    out
+}
+
+/// Render the graph as GraphML, for yEd, Gephi and the like. Nodes and
+/// edges carry their kind, names and location as data, in order of id.
+pub fn to_graphml(graph: &Graph) -> String {
+    let mut out = graphml_header();
+    let mut nodes: Vec<&GraphNode> = graph.all_nodes().collect();
+    nodes.sort_by_key(|n| n.id.0);
+    for node in nodes {
+        out.push_str(&graphml_node(node));
+    }
+
+    let mut edges: Vec<&GraphEdge> = graph.all_edges().collect();
+    edges.sort_by_key(|e| e.id.0);
+    for edge in edges {
+        out.push_str(&graphml_edge(edge));
+    }
+
+    out.push_str(GRAPHML_FOOTER);
+    out
+}
+
+fn graphml_header() -> String {
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    writeln!(out, "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">").unwrap();
+    for (id, target, name, kind) in GRAPHML_KEYS {
+        writeln!(out, "  <key id=\"{}\" for=\"{}\" attr.name=\"{}\" attr.type=\"{}\"/>", id, target, name, kind).unwrap();
+    }
+    writeln!(out, "  <graph id=\"canopy\" edgedefault=\"directed\">").unwrap();
+    out
+}
+
+fn graphml_node(node: &GraphNode) -> String {
+    let mut out = String::new();
+    writeln!(out, "    <node id=\"n{}\">", node.id.0).unwrap();
+    let file = node.file_path.display().to_string();
+    let data = [
+        ("kind", Some(format!("{:?}", node.kind))),
+        ("name", Some(node.name.clone())),
+        ("qualified_name", Some(node.qualified_name.to_string())),
+        ("file", (!file.is_empty()).then_some(file)),
+        ("line", node.line_start.map(|line| line.to_string())),
+    ];
+    for (key, value) in data {
+        if let Some(value) = value {
+            writeln!(out, "      <data key=\"{}\">{}</data>", key, xml_escape(&value)).unwrap();
+        }
+    }
+    writeln!(out, "    </node>").unwrap();
+    out
+}
+
+fn graphml_edge(edge: &GraphEdge) -> String {
+    let mut out = String::new();
+    writeln!(out, "    <edge id=\"e{}\" source=\"n{}\" target=\"n{}\">", edge.id.0, edge.source.0, edge.target.0).unwrap();
+    writeln!(out, "      <data key=\"edge_kind\">{:?}</data>", edge.kind).unwrap();
+    writeln!(out, "      <data key=\"edge_source\">{:?}</data>", edge.edge_source).unwrap();
+    writeln!(out, "      <data key=\"confidence\">{}</data>", edge.confidence).unwrap();
+    writeln!(out, "    </edge>").unwrap();
+    out
+}
+
+const GRAPHML_FOOTER: &str = "  </graph>\n</graphml>\n";
+
+/// The GraphML attributes written: id, element, name and type.
+const GRAPHML_KEYS: [(&str, &str, &str, &str); 8] = [
+    ("kind", "node", "kind", "string"),
+    ("name", "node", "name", "string"),
+    ("qualified_name", "node", "qualified_name", "string"),
+    ("file", "node", "file_path", "string"),
+    ("line", "node", "line_start", "int"),
+    ("edge_kind", "edge", "kind", "string"),
+    ("edge_source", "edge", "edge_source", "string"),
+    ("confidence", "edge", "confidence", "double"),
+];
+
+/// Render the graph as JSON: its `nodes` and `edges` in order of id, as
+/// they are stored.
+pub fn to_json(graph: &Graph) -> serde_json::Result<String> {
+    let mut subgraph = Subgraph {
+        nodes: graph.all_nodes().cloned().collect(),
+        edges: graph.all_edges().cloned().collect(),
+    };
+    subgraph.nodes.sort_by_key(|n| n.id.0);
+    subgraph.edges.sort_by_key(|e| e.id.0);
+    serde_json::to_string_pretty(&subgraph)
+}
+
+/// The formats that can be written a node and an edge at a time, so that
+/// a large graph is never held as one string: its header, then each node,
+/// the text between nodes and edges, each edge, and its footer. Edges
+/// should only be written between nodes already written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamFormat {
+    /// DOT with the default [`DotOptions`].
+    Dot,
+    Graphml,
+    /// Compact JSON with the `nodes` and `edges` of [`to_json`].
+    Json,
+}
+
+impl StreamFormat {
+    pub fn header(self) -> String {
+        match self {
+            StreamFormat::Dot => dot_header(&DotOptions::default()),
+            StreamFormat::Graphml => graphml_header(),
+            StreamFormat::Json => "{\"nodes\":[".to_string(),
+        }
+    }
+
+    /// The `index`th node written, from 0. Only JSON can fail.
+    pub fn node(self, node: &GraphNode, index: usize) -> serde_json::Result<String> {
+        Ok(match self {
+            StreamFormat::Dot => format!("  {}\n", dot_node(node)),
+            StreamFormat::Graphml => graphml_node(node),
+            StreamFormat::Json => json_item(node, index)?,
+        })
+    }
+
+    pub fn between(self) -> &'static str {
+        match self {
+            StreamFormat::Dot | StreamFormat::Graphml => "",
+            StreamFormat::Json => "],\"edges\":[",
+        }
+    }
+
+    /// The `index`th edge written, from 0. Only JSON can fail.
+    pub fn edge(self, edge: &GraphEdge, index: usize) -> serde_json::Result<String> {
+        Ok(match self {
+            StreamFormat::Dot => format!("  {}\n", dot_edge(edge, &DotOptions::default())),
+            StreamFormat::Graphml => graphml_edge(edge),
+            StreamFormat::Json => json_item(edge, index)?,
+        })
+    }
+
+    pub fn footer(self) -> &'static str {
+        match self {
+            StreamFormat::Dot => "}\n",
+            StreamFormat::Graphml => GRAPHML_FOOTER,
+            StreamFormat::Json => "]}",
+        }
+    }
+}
+
+/// An element of a JSON array, after a comma unless it's the first.
+fn json_item(item: &impl serde::Serialize, index: usize) -> serde_json::Result<String> {
+    let json = serde_json::to_string(item)?;
+    Ok(if index == 0 { json } else { format!(",{}", json) })
+}
+
+/// Escape text for use in XML content or attributes.
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Escape text for use inside a quoted Mermaid label.
+fn mermaid_escape(s: &str) -> String {
+    s.replace('"', "#quot;")
+}
+
+/// Directory a node is clustered under when clustering by directory.
+fn cluster_dir(node: &GraphNode) -> &Path {
+    node.file_path.parent().unwrap_or(Path::new(""))
+}
+
+fn dot_header(options: &DotOptions) -> String {
+    let mut out = String::new();
+    writeln!(out, "digraph {} {{", quote(&options.graph_name)).unwrap();
+    writeln!(out, "  rankdir=LR;").unwrap();
+    writeln!(out, "  node [fontname=\"Helvetica\"];").unwrap();
+    out
+}
+
+fn dot_node(node: &GraphNode) -> String {
+    format!(
+        "n{} [label={}, shape={}];",
+        node.id.0,
+        quote(&node.name),
+        node_shape(node.kind)
+    )
+}
+
+/// Graphviz shape for a node kind.
+fn dot_edge(edge: &GraphEdge, options: &DotOptions) -> String {
+    let mut attrs = vec![format!("style={}", edge_style(edge.kind))];
+    if let Some(arrowhead) = edge_arrowhead(edge.kind) {
+        attrs.push(format!("arrowhead={}", arrowhead));
+    }
+    let mut label = edge_kind_name(edge.kind).to_string();
+    if edge.edge_source == EdgeSource::AI {
+        attrs.push("color=\"gray40\"".to_string());
+        if options.show_confidence {
+            label = format!("{} ({:.2})", label, edge.confidence);
+        }
+    }
+    attrs.push(format!("label={}", quote(&label)));
+    format!("n{} -> n{} [{}];", edge.source.0, edge.target.0, attrs.join(", "))
+}
+
+fn node_shape(kind: NodeKind) -> &'static str {
+    match kind {
+        NodeKind::Directory => "folder",
+        NodeKind::File => "note",
+        NodeKind::Module => "tab",
+        NodeKind::Class | NodeKind::Struct | NodeKind::Union => "box",
+        NodeKind::Enum => "octagon",
+        NodeKind::Interface | NodeKind::Trait => "component",
+        NodeKind::Function | NodeKind::Method => "ellipse",
+        NodeKind::Macro => "invtriangle",
+        NodeKind::TestCase => "diamond",
+        NodeKind::Constant | NodeKind::TypeAlias | NodeKind::Field | NodeKind::Variable => "plaintext",
+        NodeKind::ConfigBlock | NodeKind::ConfigKey | NodeKind::EnvVariable => "hexagon",
+        NodeKind::Route => "cds",
+        NodeKind::Migration => "cylinder",
+        NodeKind::CIJob | NodeKind::DockerService | NodeKind::Script => "parallelogram",
+        NodeKind::Document => "note",
+        NodeKind::Section => "tab",
+        NodeKind::WorkspaceRoot => "house",
+        NodeKind::Package => "box3d",
+        NodeKind::Unknown => "box",
+    }
+}
+
+/// Graphviz line style for an edge kind.
+fn edge_style(kind: EdgeKind) -> &'static str {
+    match kind {
+        EdgeKind::Contains | EdgeKind::TypeReference => "dotted",
+        EdgeKind::Imports | EdgeKind::Implements | EdgeKind::Exports | EdgeKind::Reexports => "dashed",
+        EdgeKind::Inherits | EdgeKind::Overrides => "bold",
+        EdgeKind::Calls | EdgeKind::Instantiates | EdgeKind::References => "solid",
+        _ => "dashed",
+    }
+}
+
+fn edge_arrowhead(kind: EdgeKind) -> Option<&'static str> {
+    match kind {
+        EdgeKind::Inherits | EdgeKind::Implements | EdgeKind::Overrides => Some("empty"),
+        EdgeKind::Contains => Some("odiamond"),
+        _ => None,
+    }
+}
+
+/// Short lowercase name for an edge kind, used in labels.
+fn edge_kind_name(kind: EdgeKind) -> &'static str {
+    match kind {
+        EdgeKind::Contains => "contains",
+        EdgeKind::Imports => "imports",
+        EdgeKind::Calls => "calls",
+        EdgeKind::Inherits => "inherits",
+        EdgeKind::Implements => "implements",
+        EdgeKind::TypeReference => "references type",
+        EdgeKind::Instantiates => "instantiates",
+        EdgeKind::Exports => "exports",
+        EdgeKind::Reexports => "re-exports",
+        EdgeKind::Overrides => "overrides",
+        EdgeKind::References => "references",
+        EdgeKind::TestedBy => "tested by",
+        EdgeKind::DependsOn => "depends on",
+        EdgeKind::ConfiguresArgument => "configures",
+        EdgeKind::EnvironmentBinding => "binds env",
+        EdgeKind::RouteHandler => "handles route",
+        EdgeKind::MigrationTarget => "migrates",
+        EdgeKind::CITrigger => "triggers",
+        EdgeKind::DockerMount => "mounts",
+        EdgeKind::SemanticReference => "references",
+    }
+}
+
+/// Quote a string as a DOT ID.
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
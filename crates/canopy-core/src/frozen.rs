@@ -0,0 +1,156 @@
+//! Compact read-only graph with CSR adjacency
+//!
+//! A [`FrozenGraph`] stores nodes and edges in flat vectors, with edges
+//! grouped by source and an index of incoming edges per target. This drops
+//! the per-edge linked-list pointers and vacant slots of the mutable
+//! `StableDiGraph`, which dominate memory for graphs with millions of edges.
+//! Node and edge IDs are preserved, so IDs handed to clients stay valid
+//! across `freeze` and `thaw`.
+
+use crate::graph::Graph;
+use crate::model::*;
+use std::collections::HashSet;
+
+/// Read-only graph with compressed sparse row adjacency.
+#[derive(Debug, Clone, Default)]
+pub struct FrozenGraph {
+    /// Nodes sorted by ID.
+    nodes: Vec<GraphNode>,
+    /// Edges sorted by (source position, ID).
+    edges: Vec<GraphEdge>,
+    /// `edges[out_offsets[i]..out_offsets[i + 1]]` leave `nodes[i]`.
+    out_offsets: Vec<u32>,
+    /// `in_edges[in_offsets[i]..in_offsets[i + 1]]` are positions in `edges`
+    /// of the edges entering `nodes[i]`.
+    in_offsets: Vec<u32>,
+    in_edges: Vec<u32>,
+    /// (edge ID, position in `edges`), sorted by ID.
+    edge_index: Vec<(u64, u32)>,
+}
+
+impl FrozenGraph {
+    pub(crate) fn from_parts(mut nodes: Vec<GraphNode>, mut edges: Vec<GraphEdge>) -> Self {
+        nodes.sort_by_key(|n| n.id.0);
+        nodes.shrink_to_fit();
+
+        let position = |id: NodeId| nodes.binary_search_by_key(&id.0, |n| n.id.0).ok();
+        edges.retain(|e| position(e.source).is_some() && position(e.target).is_some());
+        edges.sort_by_key(|e| (position(e.source), e.id.0));
+        edges.shrink_to_fit();
+
+        let mut out_offsets = vec![0u32; nodes.len() + 1];
+        let mut in_offsets = vec![0u32; nodes.len() + 1];
+        for edge in &edges {
+            out_offsets[position(edge.source).unwrap() + 1] += 1;
+            in_offsets[position(edge.target).unwrap() + 1] += 1;
+        }
+        for i in 0..nodes.len() {
+            out_offsets[i + 1] += out_offsets[i];
+            in_offsets[i + 1] += in_offsets[i];
+        }
+
+        let mut in_edges = vec![0u32; edges.len()];
+        let mut fill = in_offsets.clone();
+        for (pos, edge) in edges.iter().enumerate() {
+            let target = position(edge.target).unwrap();
+            in_edges[fill[target] as usize] = pos as u32;
+            fill[target] += 1;
+        }
+
+        let mut edge_index: Vec<(u64, u32)> = edges.iter().enumerate().map(|(pos, e)| (e.id.0, pos as u32)).collect();
+        edge_index.sort_unstable();
+
+        FrozenGraph { nodes, edges, out_offsets, in_offsets, in_edges, edge_index }
+    }
+
+    /// Convert back into a mutable graph with the same node and edge IDs.
+    pub fn thaw(self) -> Graph {
+        Graph::from_parts(self.nodes, self.edges)
+    }
+
+    fn position(&self, id: NodeId) -> Option<usize> {
+        self.nodes.binary_search_by_key(&id.0, |n| n.id.0).ok()
+    }
+
+    /// Get a node by ID.
+    pub fn node(&self, id: NodeId) -> Option<&GraphNode> {
+        self.position(id).map(|pos| &self.nodes[pos])
+    }
+
+    /// Get an edge by ID.
+    pub fn edge(&self, id: EdgeId) -> Option<&GraphEdge> {
+        let pos = self.edge_index.binary_search_by_key(&id.0, |&(id, _)| id).ok()?;
+        Some(&self.edges[self.edge_index[pos].1 as usize])
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.edges.len()
+    }
+
+    /// Iterate over all nodes in ID order.
+    pub fn all_nodes(&self) -> impl Iterator<Item = &GraphNode> {
+        self.nodes.iter()
+    }
+
+    /// Iterate over all edges, grouped by source.
+    pub fn all_edges(&self) -> impl Iterator<Item = &GraphEdge> {
+        self.edges.iter()
+    }
+
+    /// Outgoing edges of a node.
+    pub fn edges_from(&self, source: NodeId) -> impl Iterator<Item = &GraphEdge> {
+        let range = match self.position(source) {
+            Some(pos) => self.out_offsets[pos] as usize..self.out_offsets[pos + 1] as usize,
+            None => 0..0,
+        };
+        self.edges[range].iter()
+    }
+
+    /// Incoming edges of a node.
+    pub fn edges_to(&self, target: NodeId) -> impl Iterator<Item = &GraphEdge> {
+        let range = match self.position(target) {
+            Some(pos) => self.in_offsets[pos] as usize..self.in_offsets[pos + 1] as usize,
+            None => 0..0,
+        };
+        self.in_edges[range].iter().map(|&pos| &self.edges[pos as usize])
+    }
+
+    /// Find the edge with the given source, target and kind.
+    pub fn find_edge(&self, source: NodeId, target: NodeId, kind: EdgeKind) -> Option<EdgeId> {
+        self.edges_from(source)
+            .find(|e| e.target == target && e.kind == kind)
+            .map(|e| e.id)
+    }
+
+    pub fn has_edge_between(&self, source: NodeId, target: NodeId, kind: EdgeKind) -> bool {
+        self.find_edge(source, target, kind).is_some()
+    }
+
+    /// Find a node by fully qualified name.
+    pub fn find_node_by_qualified(&self, qualified_name: &str) -> Option<NodeId> {
+        self.nodes.iter().find(|n| n.qualified_name == qualified_name).map(|n| n.id)
+    }
+
+    /// Get all nodes of a specific kind.
+    pub fn nodes_of_kind(&self, kind: NodeKind) -> impl Iterator<Item = NodeId> + '_ {
+        self.nodes.iter().filter(move |n| n.kind == kind).map(|n| n.id)
+    }
+
+    /// Get all nodes that are ancestors of a given node (following Contains edges).
+    pub fn ancestors(&self, node: NodeId) -> HashSet<NodeId> {
+        let mut ancestors = HashSet::new();
+        let mut to_visit = vec![node];
+        while let Some(current) = to_visit.pop() {
+            for edge in self.edges_to(current) {
+                if edge.kind == EdgeKind::Contains && ancestors.insert(edge.source) {
+                    to_visit.push(edge.source);
+                }
+            }
+        }
+        ancestors
+    }
+}
@@ -1,10 +1,12 @@
 //! Graph wrapper using petgraph::StableDiGraph with custom NodeId/EdgeId
 
+use crate::frozen::FrozenGraph;
 use crate::model::*;
 use petgraph::stable_graph::{EdgeIndex, NodeIndex, StableDiGraph};
-use petgraph::visit::EdgeRef;
+use petgraph::visit::{EdgeIndexable, EdgeRef, NodeIndexable};
 use petgraph::Direction;
-use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
 
 /// The code graph — a directed multigraph with stable node/edge indices.
 pub struct Graph {
@@ -20,6 +22,21 @@ impl std::fmt::Debug for Graph {
     }
 }
 
+/// A path through the graph: its nodes in order, and the edge taken
+/// between each two.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GraphPath {
+    pub nodes: Vec<NodeId>,
+    pub edges: Vec<EdgeId>,
+}
+
+/// A slice of the graph, keeping the original node and edge IDs.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Subgraph {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
 impl Graph {
     pub fn new() -> Self {
         Graph {
@@ -39,7 +56,59 @@ impl Graph {
     }
 
     /// Add an edge to graph. Returns assigned EdgeId.
+    /// If an edge with the same source, target and kind already exists,
+    /// nothing is inserted and the existing EdgeId is returned.
     pub fn add_edge(&mut self, edge: GraphEdge) -> EdgeId {
+        if let Some(existing) = self.find_edge(edge.source, edge.target, edge.kind) {
+            return existing;
+        }
+        self.insert_edge(edge)
+    }
+
+    /// Add an edge, or update the confidence, label and location of an
+    /// existing edge with the same source, target and kind.
+    pub fn upsert_edge(&mut self, edge: GraphEdge) -> EdgeId {
+        let Some(existing) = self.find_edge(edge.source, edge.target, edge.kind) else {
+            return self.insert_edge(edge);
+        };
+        if let Some(edge_ref) = self.inner.edge_weight_mut(EdgeIndex::new(existing.0 as usize)) {
+            edge_ref.edge_source = edge.edge_source;
+            edge_ref.confidence = edge.confidence;
+            edge_ref.label = edge.label;
+            edge_ref.file_path = edge.file_path;
+            edge_ref.line = edge.line;
+        }
+        existing
+    }
+
+    /// Find the edge with the given source, target and kind.
+    pub fn find_edge(&self, source: NodeId, target: NodeId, kind: EdgeKind) -> Option<EdgeId> {
+        self.edges_from(source)
+            .find(|e| e.target == target && e.kind == kind)
+            .map(|e| e.id)
+    }
+
+    /// Remove duplicate edges (same source, target and kind), keeping the
+    /// lowest EdgeId of each group. Returns the number of edges removed.
+    pub fn dedupe_edges(&mut self) -> usize {
+        let mut seen = HashSet::new();
+        let duplicates: Vec<EdgeIndex> = self
+            .inner
+            .edge_indices()
+            .filter(|&idx| {
+                self.inner
+                    .edge_weight(idx)
+                    .is_some_and(|e| !seen.insert((e.source, e.target, e.kind)))
+            })
+            .collect();
+
+        for idx in &duplicates {
+            self.inner.remove_edge(*idx);
+        }
+        duplicates.len()
+    }
+
+    fn insert_edge(&mut self, edge: GraphEdge) -> EdgeId {
         let source = NodeIndex::new(edge.source.0 as usize);
         let target = NodeIndex::new(edge.target.0 as usize);
         let idx = self.inner.add_edge(source, target, edge);
@@ -51,6 +120,52 @@ impl Graph {
         edge_id
     }
 
+    /// Convert into a compact read-only graph, keeping node and edge IDs.
+    pub fn freeze(self) -> FrozenGraph {
+        let (nodes, edges) = petgraph::Graph::from(self.inner).into_nodes_edges();
+        FrozenGraph::from_parts(
+            nodes.into_iter().map(|n| n.weight).collect(),
+            edges.into_iter().map(|e| e.weight).collect(),
+        )
+    }
+
+    /// Rebuild a graph whose nodes and edges already carry their IDs.
+    /// Gaps in the ID ranges are filled with placeholders that are removed
+    /// again, so every ID maps to the same index as before.
+    pub(crate) fn from_parts(mut nodes: Vec<GraphNode>, mut edges: Vec<GraphEdge>) -> Self {
+        nodes.sort_by_key(|n| n.id.0);
+        edges.sort_by_key(|e| e.id.0);
+        let mut inner = StableDiGraph::with_capacity(nodes.len(), edges.len());
+
+        let mut vacant_nodes = Vec::new();
+        for node in nodes {
+            while NodeIndexable::node_bound(&inner) < node.id.0 as usize {
+                vacant_nodes.push(inner.add_node(placeholder_node()));
+            }
+            inner.add_node(node);
+        }
+
+        let mut vacant_edges = Vec::new();
+        for edge in edges {
+            let (source, target) = (NodeIndex::new(edge.source.0 as usize), NodeIndex::new(edge.target.0 as usize));
+            if inner.node_weight(source).is_none() || inner.node_weight(target).is_none() {
+                continue;
+            }
+            while EdgeIndexable::edge_bound(&inner) < edge.id.0 as usize {
+                vacant_edges.push(inner.add_edge(source, source, edge.clone()));
+            }
+            inner.add_edge(source, target, edge);
+        }
+
+        for idx in vacant_edges {
+            inner.remove_edge(idx);
+        }
+        for idx in vacant_nodes {
+            inner.remove_node(idx);
+        }
+        Graph { inner }
+    }
+
     /// Get a node by ID.
     pub fn node(&self, id: NodeId) -> Option<&GraphNode> {
         let idx = NodeIndex::new(id.0 as usize);
@@ -93,6 +208,19 @@ impl Graph {
             .filter_map(move |idx| self.inner.edge_weight(idx))
     }
 
+    /// Iterate over edges with at least `min_confidence` from the given sources.
+    /// An empty `allowed_sources` accepts every source.
+    pub fn filtered<'a>(
+        &'a self,
+        min_confidence: f32,
+        allowed_sources: &'a [EdgeSource],
+    ) -> impl Iterator<Item = &'a GraphEdge> + 'a {
+        self.all_edges().filter(move |e| {
+            e.confidence >= min_confidence
+                && (allowed_sources.is_empty() || allowed_sources.contains(&e.edge_source))
+        })
+    }
+
     /// Get all outgoing edges from a node.
     pub fn edges_from(&self, source: NodeId) -> impl Iterator<Item = &GraphEdge> {
         let idx = NodeIndex::new(source.0 as usize);
@@ -111,8 +239,7 @@ impl Graph {
 
     /// Check if an edge exists between two nodes of a specific kind.
     pub fn has_edge_between(&self, source: NodeId, target: NodeId, kind: EdgeKind) -> bool {
-        self.edges_from(source)
-            .any(|e| e.target == target && e.kind == kind)
+        self.find_edge(source, target, kind).is_some()
     }
 
     /// Find a node by name (first match).
@@ -122,7 +249,7 @@ impl Graph {
             .find(|&idx| {
                 self.inner
                     .node_weight(idx)
-                    .map_or(false, |n| n.name == name)
+                    .is_some_and(|n| n.name == name)
             })
             .map(|idx| NodeId(idx.index() as u64))
     }
@@ -134,7 +261,7 @@ impl Graph {
             .find(|&idx| {
                 self.inner
                     .node_weight(idx)
-                    .map_or(false, |n| n.qualified_name == qualified_name)
+                    .is_some_and(|n| n.qualified_name == qualified_name)
             })
             .map(|idx| NodeId(idx.index() as u64))
     }
@@ -146,7 +273,7 @@ impl Graph {
             .filter(move |&idx| {
                 self.inner
                     .node_weight(idx)
-                    .map_or(false, |n| n.kind == kind)
+                    .is_some_and(|n| n.kind == kind)
             })
             .map(|idx| NodeId(idx.index() as u64))
     }
@@ -171,17 +298,222 @@ impl Graph {
         while let Some(current) = to_visit.pop() {
             let current_idx = NodeIndex::new(current.0 as usize);
             for edge_ref in self.inner.edges_directed(current_idx, Direction::Incoming) {
-                if let Some(edge) = self.inner.edge_weight(edge_ref.id()) {
-                    if edge.kind == EdgeKind::Contains && !ancestors.contains(&edge.source) {
-                        ancestors.insert(edge.source);
-                        to_visit.push(edge.source);
-                    }
+                if let Some(edge) = self.inner.edge_weight(edge_ref.id())
+                    && edge.kind == EdgeKind::Contains && !ancestors.contains(&edge.source) {
+                    ancestors.insert(edge.source);
+                    to_visit.push(edge.source);
                 }
             }
         }
 
         ancestors
     }
+
+    /// Recompute `child_count` and rolled-up `loc` for every container.
+    ///
+    /// `child_count` is the number of direct Contains children. Directories,
+    /// workspace roots and packages take the sum of their children's LOC.
+    /// Files keep their own line count and code entities their own span.
+    pub fn recompute_rollups(&mut self) {
+        let roots: Vec<NodeId> = self
+            .all_nodes()
+            .map(|n| n.id)
+            .filter(|&id| !self.edges_to(id).any(|e| e.kind == EdgeKind::Contains))
+            .collect();
+
+        // Preorder over the containment tree, then process in reverse so
+        // children are always finished before their parents.
+        let mut order = Vec::new();
+        let mut seen = HashSet::new();
+        let mut stack = roots;
+        while let Some(id) = stack.pop() {
+            if !seen.insert(id) {
+                continue;
+            }
+            order.push(id);
+            stack.extend(self.contained_children(id));
+        }
+
+        for id in order.into_iter().rev() {
+            self.rollup_node(id);
+        }
+    }
+
+    /// Recompute roll-ups for the given nodes and all of their ancestors,
+    /// returning the nodes refreshed. After removing nodes, pass their
+    /// former parents.
+    pub fn refresh_rollups(&mut self, nodes: &[NodeId]) -> Vec<NodeId> {
+        let mut refreshed = Vec::new();
+        let mut seen = HashSet::new();
+        for &start in nodes {
+            let mut current = Some(start);
+            while let Some(id) = current {
+                if !seen.insert(id) {
+                    break;
+                }
+                self.rollup_node(id);
+                refreshed.push(id);
+                current = self
+                    .edges_to(id)
+                    .find(|e| e.kind == EdgeKind::Contains)
+                    .map(|e| e.source);
+            }
+        }
+        refreshed
+    }
+
+    fn contained_children(&self, id: NodeId) -> Vec<NodeId> {
+        self.edges_from(id)
+            .filter(|e| e.kind == EdgeKind::Contains)
+            .map(|e| e.target)
+            .collect()
+    }
+
+    fn rollup_node(&mut self, id: NodeId) {
+        let children = self.contained_children(id);
+        let child_loc = children
+            .iter()
+            .filter_map(|&child| self.node(child).and_then(|n| n.loc))
+            .reduce(|a, b| a + b);
+
+        if let Some(node) = self.node_mut(id) {
+            node.child_count = children.len() as u32;
+            if children.is_empty() {
+                return;
+            }
+            match node.kind {
+                NodeKind::Directory | NodeKind::WorkspaceRoot | NodeKind::Package => node.loc = child_loc,
+                _ => {}
+            }
+        }
+    }
+
+    /// A copy of the graph with only the nodes `keep` accepts and the edges
+    /// between them, each keeping its id.
+    pub fn filter_nodes(&self, keep: impl Fn(&GraphNode) -> bool) -> Graph {
+        Graph {
+            inner: self.inner.filter_map(
+                |_, node| keep(node).then(|| node.clone()),
+                |_, edge| Some(edge.clone()),
+            ),
+        }
+    }
+
+    /// Extract the neighborhood within `depth` hops of `center`.
+    /// Edges are followed in both directions; an empty `edge_kinds` follows every kind.
+    /// Only edges between included nodes whose kind is allowed are returned.
+    pub fn subgraph_around(&self, center: NodeId, depth: usize, edge_kinds: &[EdgeKind]) -> Subgraph {
+        let mut subgraph = Subgraph::default();
+        if self.node(center).is_none() {
+            return subgraph;
+        }
+
+        let allowed = |kind: EdgeKind| edge_kinds.is_empty() || edge_kinds.contains(&kind);
+        let mut included = HashSet::from([center]);
+        let mut queue = VecDeque::from([(center, 0)]);
+
+        while let Some((current, hops)) = queue.pop_front() {
+            if hops == depth {
+                continue;
+            }
+            let neighbors = self
+                .edges_from(current)
+                .map(|e| (e.kind, e.target))
+                .chain(self.edges_to(current).map(|e| (e.kind, e.source)));
+            for (kind, neighbor) in neighbors {
+                if allowed(kind) && included.insert(neighbor) {
+                    queue.push_back((neighbor, hops + 1));
+                }
+            }
+        }
+
+        subgraph.nodes = self
+            .all_nodes()
+            .filter(|n| included.contains(&n.id))
+            .cloned()
+            .collect();
+        subgraph.edges = included
+            .iter()
+            .flat_map(|&id| self.edges_from(id))
+            .filter(|e| allowed(e.kind) && included.contains(&e.target))
+            .cloned()
+            .collect();
+        subgraph.edges.sort_by_key(|e| e.id.0);
+        subgraph
+    }
+
+    /// Up to `limit` of the shortest paths from `from` to `to`. Only edges
+    /// of `edge_kinds` are followed, or of every kind if it's empty; unless
+    /// `directed`, edges are followed backwards too. No paths if `to` is
+    /// unreachable.
+    pub fn shortest_paths(
+        &self,
+        from: NodeId,
+        to: NodeId,
+        edge_kinds: &[EdgeKind],
+        directed: bool,
+        limit: usize,
+    ) -> Vec<GraphPath> {
+        if self.node(from).is_none() || self.node(to).is_none() || limit == 0 {
+            return Vec::new();
+        }
+
+        // Breadth first from `from`, keeping every edge that reaches a node
+        // at its shortest distance
+        let allowed = |kind: EdgeKind| edge_kinds.is_empty() || edge_kinds.contains(&kind);
+        let mut distance = HashMap::from([(from, 0)]);
+        let mut reached_by: HashMap<NodeId, Vec<(EdgeId, NodeId)>> = HashMap::new();
+        let mut queue = VecDeque::from([from]);
+        while let Some(current) = queue.pop_front() {
+            let hops = distance[&current];
+            if distance.get(&to).is_some_and(|&d| hops >= d) {
+                break;
+            }
+            let forward = self.edges_from(current).map(|e| (e, e.target));
+            let backward = self.edges_to(current).filter(|_| !directed).map(|e| (e, e.source));
+            for (edge, neighbor) in forward.chain(backward) {
+                if !allowed(edge.kind) {
+                    continue;
+                }
+                match distance.get(&neighbor) {
+                    None => {
+                        distance.insert(neighbor, hops + 1);
+                        queue.push_back(neighbor);
+                    }
+                    Some(&d) if d != hops + 1 => continue,
+                    Some(_) => {}
+                }
+                reached_by.entry(neighbor).or_default().push((edge.id, current));
+            }
+        }
+        if !distance.contains_key(&to) {
+            return Vec::new();
+        }
+
+        // Back from `to`, along the edges kept, to `from`
+        let mut paths = Vec::new();
+        let mut stack = vec![GraphPath { nodes: vec![to], edges: Vec::new() }];
+        while let Some(path) = stack.pop() {
+            let last = path.nodes[path.nodes.len() - 1];
+            if last == from {
+                let GraphPath { mut nodes, mut edges } = path;
+                nodes.reverse();
+                edges.reverse();
+                paths.push(GraphPath { nodes, edges });
+                if paths.len() == limit {
+                    break;
+                }
+                continue;
+            }
+            for &(edge, previous) in reached_by.get(&last).into_iter().flatten().rev() {
+                let mut longer = path.clone();
+                longer.nodes.push(previous);
+                longer.edges.push(edge);
+                stack.push(longer);
+            }
+        }
+        paths
+    }
 }
 
 impl Default for Graph {
@@ -189,3 +521,69 @@ impl Default for Graph {
         Self::new()
     }
 }
+
+/// Stand-in for a vacant node index while rebuilding a graph.
+fn placeholder_node() -> GraphNode {
+    GraphNode {
+        id: NodeId(0),
+        kind: NodeKind::Unknown,
+        name: String::new(),
+        qualified_name: Default::default(),
+        file_path: Default::default(),
+        line_start: None,
+        line_end: None,
+        language: None,
+        is_container: false,
+        child_count: 0,
+        loc: None,
+        metadata: NodeMetadata::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_dedupe_edges() {
+        let mut graph = Graph::new();
+        let node = |name: &str| GraphNode {
+            id: NodeId(0),
+            kind: NodeKind::File,
+            name: name.to_string(),
+            qualified_name: name.into(),
+            file_path: PathBuf::from(name).into(),
+            line_start: None,
+            line_end: None,
+            language: None,
+            is_container: false,
+            child_count: 0,
+            loc: None,
+            metadata: NodeMetadata::default(),
+        };
+        let a = graph.add_node(node("a.rs"));
+        let b = graph.add_node(node("b.rs"));
+        let edge = GraphEdge {
+            id: EdgeId(0),
+            source: a,
+            target: b,
+            kind: EdgeKind::Imports,
+            edge_source: EdgeSource::Structural,
+            confidence: 1.0,
+            label: None,
+            file_path: None,
+            line: None,
+        };
+
+        // Bypass insertion dedup to simulate a graph built before it existed
+        let kept = graph.insert_edge(edge.clone());
+        graph.insert_edge(edge.clone());
+        graph.insert_edge(edge);
+        assert_eq!(graph.edge_count(), 3);
+
+        assert_eq!(graph.dedupe_edges(), 2);
+        assert_eq!(graph.edge_count(), 1);
+        assert!(graph.edge(kept).is_some());
+    }
+}
@@ -0,0 +1,245 @@
+//! Interned strings and paths shared between graph nodes
+//!
+//! Every node of a file shares its path, and names such as `new` or a
+//! re-exported symbol's qualified name recur across files, so paths and
+//! names are stored once in a process-wide interner and handed out as cheap
+//! reference-counted handles. Deserialized values are interned as well.
+//! Values are kept until [`purge_unused`] finds no handle left to them.
+
+use dashmap::DashSet;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Borrow;
+use std::fmt;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock};
+
+static STRINGS: LazyLock<DashSet<Arc<str>>> = LazyLock::new(DashSet::new);
+static PATHS: LazyLock<DashSet<Arc<Path>>> = LazyLock::new(DashSet::new);
+
+/// Number of distinct interned strings and paths.
+pub fn interned_count() -> (usize, usize) {
+    (STRINGS.len(), PATHS.len())
+}
+
+/// Drop interned values that are no longer referenced by any handle.
+pub fn purge_unused() {
+    STRINGS.retain(|s| Arc::strong_count(s) > 1);
+    PATHS.retain(|p| Arc::strong_count(p) > 1);
+}
+
+/// An interned, immutable string.
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct IStr(Arc<str>);
+
+impl IStr {
+    pub fn new(s: &str) -> Self {
+        if let Some(existing) = STRINGS.get(s) {
+            return IStr(Arc::clone(&existing));
+        }
+        let arc: Arc<str> = Arc::from(s);
+        STRINGS.insert(Arc::clone(&arc));
+        IStr(arc)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Deref for IStr {
+    type Target = str;
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for IStr {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for IStr {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Default for IStr {
+    fn default() -> Self {
+        IStr::new("")
+    }
+}
+
+impl fmt::Debug for IStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+impl fmt::Display for IStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&*self.0, f)
+    }
+}
+
+impl From<&str> for IStr {
+    fn from(s: &str) -> Self {
+        IStr::new(s)
+    }
+}
+
+impl From<String> for IStr {
+    fn from(s: String) -> Self {
+        IStr::new(&s)
+    }
+}
+
+impl From<&String> for IStr {
+    fn from(s: &String) -> Self {
+        IStr::new(s)
+    }
+}
+
+impl PartialEq<str> for IStr {
+    fn eq(&self, other: &str) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl PartialEq<&str> for IStr {
+    fn eq(&self, other: &&str) -> bool {
+        &*self.0 == *other
+    }
+}
+
+impl PartialEq<String> for IStr {
+    fn eq(&self, other: &String) -> bool {
+        &*self.0 == other.as_str()
+    }
+}
+
+impl Serialize for IStr {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for IStr {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Ok(IStr::new(&s))
+    }
+}
+
+/// An interned, immutable path.
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct IPath(Arc<Path>);
+
+impl IPath {
+    pub fn new(path: &Path) -> Self {
+        if let Some(existing) = PATHS.get(path) {
+            return IPath(Arc::clone(&existing));
+        }
+        let arc: Arc<Path> = Arc::from(path);
+        PATHS.insert(Arc::clone(&arc));
+        IPath(arc)
+    }
+
+    pub fn as_path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Deref for IPath {
+    type Target = Path;
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for IPath {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Borrow<Path> for IPath {
+    fn borrow(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Default for IPath {
+    fn default() -> Self {
+        IPath::new(Path::new(""))
+    }
+}
+
+impl fmt::Debug for IPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+impl From<&Path> for IPath {
+    fn from(path: &Path) -> Self {
+        IPath::new(path)
+    }
+}
+
+impl From<PathBuf> for IPath {
+    fn from(path: PathBuf) -> Self {
+        IPath::new(&path)
+    }
+}
+
+impl From<&PathBuf> for IPath {
+    fn from(path: &PathBuf) -> Self {
+        IPath::new(path)
+    }
+}
+
+impl From<&str> for IPath {
+    fn from(path: &str) -> Self {
+        IPath::new(Path::new(path))
+    }
+}
+
+impl From<IPath> for PathBuf {
+    fn from(path: IPath) -> Self {
+        path.0.to_path_buf()
+    }
+}
+
+impl PartialEq<Path> for IPath {
+    fn eq(&self, other: &Path) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl PartialEq<&Path> for IPath {
+    fn eq(&self, other: &&Path) -> bool {
+        &*self.0 == *other
+    }
+}
+
+impl PartialEq<PathBuf> for IPath {
+    fn eq(&self, other: &PathBuf) -> bool {
+        &*self.0 == other.as_path()
+    }
+}
+
+impl Serialize for IPath {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for IPath {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let path = PathBuf::deserialize(deserializer)?;
+        Ok(IPath::new(&path))
+    }
+}
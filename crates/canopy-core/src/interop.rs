@@ -0,0 +1,371 @@
+//! Import of precise references from SCIP and LSIF indexes
+
+use crate::graph::Graph;
+use crate::model::*;
+use anyhow::Context;
+use prost::Message;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// A source location from an index. Lines are 1-based like graph nodes.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct IndexLocation {
+    pub path: PathBuf,
+    pub line: u32,
+}
+
+/// A reference at `from` to the symbol defined at `to`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PreciseReference {
+    pub from: IndexLocation,
+    pub to: IndexLocation,
+}
+
+/// Options for [`import_references`].
+#[derive(Debug, Clone)]
+pub struct ImportOptions {
+    /// Remove the Heuristic edges a resolved reference replaces: calls,
+    /// imports and type references from its node, to the same target or
+    /// guessed at the same line.
+    pub replace_heuristics: bool,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        ImportOptions { replace_heuristics: true }
+    }
+}
+
+/// Outcome of an import.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportStats {
+    pub added_edges: usize,
+    pub removed_heuristic_edges: usize,
+    /// References whose endpoints did not map onto graph nodes.
+    pub unresolved: usize,
+}
+
+/// Read a SCIP index (binary `index.scip`, or JSON as printed by
+/// `scip print --json`) or an LSIF dump (JSON lines) and import its
+/// references into the graph.
+pub fn import_index_file(graph: &mut Graph, root: &Path, index: &Path) -> anyhow::Result<ImportStats> {
+    let bytes = std::fs::read(index).with_context(|| format!("reading {}", index.display()))?;
+    let references = match String::from_utf8(bytes) {
+        Ok(content) if index.extension().is_none_or(|e| e != "scip") => {
+            if content.trim_start().starts_with("{\"documents\"") || index.extension().is_some_and(|e| e == "json") {
+                parse_scip_json(&content)?
+            } else {
+                parse_lsif(&content)?
+            }
+        }
+        Ok(content) => parse_scip(content.as_bytes())?,
+        Err(e) => parse_scip(e.as_bytes())?,
+    };
+    Ok(import_references(graph, root, &references, &ImportOptions::default()))
+}
+
+// The parts of SCIP's `Index`, `Document` and `Occurrence` messages that
+// references are read from, with their scip.proto field numbers.
+
+#[derive(Clone, PartialEq, Deserialize, Message)]
+struct ScipIndex {
+    #[serde(default)]
+    #[prost(message, repeated, tag = "2")]
+    documents: Vec<ScipDocument>,
+}
+
+#[derive(Clone, PartialEq, Deserialize, Message)]
+struct ScipDocument {
+    #[serde(alias = "relativePath")]
+    #[prost(string, tag = "1")]
+    relative_path: String,
+    #[serde(default)]
+    #[prost(message, repeated, tag = "2")]
+    occurrences: Vec<ScipOccurrence>,
+}
+
+#[derive(Clone, PartialEq, Deserialize, Message)]
+struct ScipOccurrence {
+    #[prost(int32, repeated, tag = "1")]
+    range: Vec<i32>,
+    #[serde(default)]
+    #[prost(string, tag = "2")]
+    symbol: String,
+    #[serde(default, alias = "symbolRoles")]
+    #[prost(int32, tag = "3")]
+    symbol_roles: i32,
+}
+
+/// SCIP `SymbolRole.Definition` bit.
+const SCIP_DEFINITION: i32 = 0x1;
+
+/// Parse a SCIP index in its binary protobuf form, as `scip-*` indexers
+/// write it.
+pub fn parse_scip(bytes: &[u8]) -> anyhow::Result<Vec<PreciseReference>> {
+    let index = ScipIndex::decode(bytes).context("parsing SCIP index")?;
+    Ok(scip_references(&index))
+}
+
+/// Parse a SCIP index in its JSON form.
+pub fn parse_scip_json(content: &str) -> anyhow::Result<Vec<PreciseReference>> {
+    let index: ScipIndex = serde_json::from_str(content).context("parsing SCIP JSON index")?;
+    Ok(scip_references(&index))
+}
+
+fn scip_references(index: &ScipIndex) -> Vec<PreciseReference> {
+    let mut definitions: HashMap<&str, IndexLocation> = HashMap::new();
+    let mut references = Vec::new();
+    for document in &index.documents {
+        for occurrence in &document.occurrences {
+            // Local symbols are scoped to a document and never cross files
+            if occurrence.symbol.is_empty() || occurrence.symbol.starts_with("local ") {
+                continue;
+            }
+            let Some(&line) = occurrence.range.first() else {
+                continue;
+            };
+            let location = IndexLocation {
+                path: PathBuf::from(&document.relative_path),
+                line: line as u32 + 1,
+            };
+            if occurrence.symbol_roles & SCIP_DEFINITION != 0 {
+                definitions.insert(occurrence.symbol.as_str(), location);
+            } else {
+                references.push((occurrence.symbol.as_str(), location));
+            }
+        }
+    }
+
+    references
+        .into_iter()
+        .filter_map(|(symbol, from)| {
+            let to = definitions.get(symbol)?.clone();
+            Some(PreciseReference { from, to })
+        })
+        .collect()
+}
+
+#[derive(Deserialize)]
+struct LsifElement {
+    id: serde_json::Value,
+    #[serde(rename = "type")]
+    element_type: String,
+    label: String,
+    uri: Option<String>,
+    start: Option<LsifPosition>,
+    #[serde(rename = "outV")]
+    out_v: Option<serde_json::Value>,
+    #[serde(rename = "inV")]
+    in_v: Option<serde_json::Value>,
+    #[serde(rename = "inVs", default)]
+    in_vs: Vec<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct LsifPosition {
+    line: u32,
+}
+
+/// Parse an LSIF dump (one JSON element per line).
+pub fn parse_lsif(content: &str) -> anyhow::Result<Vec<PreciseReference>> {
+    // LSIF ids may be numbers or strings; compare them by their JSON text
+    let key = |v: &serde_json::Value| v.to_string();
+
+    let mut documents: HashMap<String, PathBuf> = HashMap::new();
+    let mut range_lines: HashMap<String, u32> = HashMap::new();
+    let mut range_docs: HashMap<String, String> = HashMap::new();
+    let mut next: HashMap<String, String> = HashMap::new();
+    let mut definition_results: HashMap<String, String> = HashMap::new();
+    let mut items: HashMap<String, Vec<String>> = HashMap::new();
+
+    for (index, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let element: LsifElement = serde_json::from_str(line)
+            .with_context(|| format!("parsing LSIF line {}", index + 1))?;
+        let id = key(&element.id);
+
+        match (element.element_type.as_str(), element.label.as_str()) {
+            ("vertex", "document") => {
+                if let Some(uri) = element.uri {
+                    let path = uri.strip_prefix("file://").unwrap_or(&uri).to_string();
+                    documents.insert(id, PathBuf::from(path));
+                }
+            }
+            ("vertex", "range") => {
+                if let Some(start) = element.start {
+                    range_lines.insert(id, start.line + 1);
+                }
+            }
+            ("edge", "contains") => {
+                if let Some(out_v) = &element.out_v {
+                    for in_v in &element.in_vs {
+                        range_docs.insert(key(in_v), key(out_v));
+                    }
+                }
+            }
+            ("edge", "next") => {
+                if let (Some(out_v), Some(in_v)) = (&element.out_v, &element.in_v) {
+                    next.insert(key(out_v), key(in_v));
+                }
+            }
+            ("edge", "textDocument/definition") => {
+                if let (Some(out_v), Some(in_v)) = (&element.out_v, &element.in_v) {
+                    definition_results.insert(key(out_v), key(in_v));
+                }
+            }
+            ("edge", "item") => {
+                if let Some(out_v) = &element.out_v {
+                    items.entry(key(out_v)).or_default().extend(element.in_vs.iter().map(key));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let location = |range: &str| -> Option<IndexLocation> {
+        let doc = range_docs.get(range)?;
+        Some(IndexLocation {
+            path: documents.get(doc)?.clone(),
+            line: *range_lines.get(range)?,
+        })
+    };
+
+    let mut references = Vec::new();
+    for range in range_lines.keys() {
+        // Follow range -> resultSet -> ... until a definition result is found
+        let mut current = range.clone();
+        let mut seen = HashSet::new();
+        let definition_result = loop {
+            if let Some(result) = definition_results.get(&current) {
+                break Some(result);
+            }
+            if !seen.insert(current.clone()) {
+                break None;
+            }
+            match next.get(&current) {
+                Some(parent) => current = parent.clone(),
+                None => break None,
+            }
+        };
+        let Some(definition_ranges) = definition_result.and_then(|r| items.get(r)) else {
+            continue;
+        };
+        if definition_ranges.contains(range) {
+            continue;
+        }
+
+        let Some(from) = location(range) else {
+            continue;
+        };
+        for definition in definition_ranges {
+            if let Some(to) = location(definition) {
+                references.push(PreciseReference { from: from.clone(), to });
+            }
+        }
+    }
+
+    references.sort_by(|a, b| (&a.from.path, a.from.line, &a.to.path, a.to.line).cmp(&(&b.from.path, b.from.line, &b.to.path, b.to.line)));
+    Ok(references)
+}
+
+/// Kinds of Heuristic edge a precise reference can stand in for.
+const REPLACED_KINDS: [EdgeKind; 3] = [EdgeKind::Calls, EdgeKind::Imports, EdgeKind::TypeReference];
+
+/// A node's ID, line range and kind, for mapping index locations onto nodes.
+type NodeSpan = (NodeId, Option<u32>, Option<u32>, NodeKind);
+
+/// Add a Structural edge (confidence 1.0) for every reference whose endpoints
+/// map onto graph nodes. Index paths may be absolute or relative to `root`.
+pub fn import_references(
+    graph: &mut Graph,
+    root: &Path,
+    references: &[PreciseReference],
+    options: &ImportOptions,
+) -> ImportStats {
+    let mut stats = ImportStats::default();
+
+    let mut nodes_by_file: HashMap<PathBuf, Vec<NodeSpan>> = HashMap::new();
+    for node in graph.all_nodes() {
+        nodes_by_file
+            .entry(node.file_path.to_path_buf())
+            .or_default()
+            .push((node.id, node.line_start, node.line_end, node.kind));
+    }
+    let file_nodes = |path: &Path| {
+        nodes_by_file
+            .get(path)
+            .or_else(|| nodes_by_file.get(&root.join(path)))
+            .or_else(|| path.strip_prefix(root).ok().and_then(|rel| nodes_by_file.get(rel)))
+    };
+
+    // Innermost node whose line range contains `line`, preferring one that
+    // starts exactly there, falling back to the file node itself.
+    let resolve = |location: &IndexLocation| -> Option<NodeId> {
+        let candidates = file_nodes(&location.path)?;
+        candidates
+            .iter()
+            .filter_map(|&(id, start, end, _)| {
+                let (start, end) = (start?, end.or(start)?);
+                (start <= location.line && location.line <= end).then_some((id, start, end))
+            })
+            .min_by_key(|&(_, start, end)| (start != location.line, end - start))
+            .map(|(id, _, _)| id)
+            .or_else(|| candidates.iter().find(|c| c.3 == NodeKind::File).map(|c| c.0))
+    };
+
+    let resolved: Vec<(NodeId, NodeId, &PreciseReference)> = references
+        .iter()
+        .filter_map(|reference| match (resolve(&reference.from), resolve(&reference.to)) {
+            (Some(source), Some(target)) => Some((source, target, reference)),
+            _ => {
+                stats.unresolved += 1;
+                None
+            }
+        })
+        .collect();
+
+    if options.replace_heuristics {
+        // Guesses the index resolved precisely; others, and heuristic
+        // edges of kinds it has nothing to say about, are kept
+        let superseded = |edge: &GraphEdge| {
+            resolved.iter().any(|&(source, target, reference)| {
+                source == edge.source && (target == edge.target || edge.line == Some(reference.from.line))
+            })
+        };
+        let stale: Vec<EdgeId> = graph
+            .all_edges()
+            .filter(|e| e.edge_source == EdgeSource::Heuristic && REPLACED_KINDS.contains(&e.kind) && superseded(e))
+            .map(|e| e.id)
+            .collect();
+        for id in stale {
+            graph.remove_edge(id);
+            stats.removed_heuristic_edges += 1;
+        }
+    }
+
+    for (source, target, reference) in resolved {
+        if source == target {
+            continue;
+        }
+        let before = graph.edge_count();
+        graph.upsert_edge(GraphEdge {
+            id: EdgeId(0), // Will be set by graph
+            source,
+            target,
+            kind: EdgeKind::References,
+            edge_source: EdgeSource::Structural,
+            confidence: 1.0,
+            label: Some("references".to_string()),
+            file_path: Some(reference.from.path.as_path().into()),
+            line: Some(reference.from.line),
+        });
+        if graph.edge_count() > before {
+            stats.added_edges += 1;
+        }
+    }
+
+    stats
+}
@@ -1,23 +1,39 @@
 //! Canopy Core — Graph data model, symbol table, and diff engine
 
 pub mod graph;
+pub mod frozen;
+pub mod intern;
 pub mod model;
 pub mod symbols;
 pub mod aggregation;
 pub mod diff;
 pub mod workspace;
 pub mod cache;
+pub mod validation;
+pub mod snapshot;
+pub mod export;
+pub mod interop;
+pub mod stats;
+pub mod search;
+pub mod view;
 
 #[cfg(test)]
 pub mod tests;
 
-#[cfg(test)]
+#[cfg(any(test, feature = "test-util"))]
 pub mod test_utils;
 
-pub use model::{NodeId, EdgeId, NodeKind, Language, EdgeKind, EdgeSource, GraphNode, GraphEdge, AggregatedEdge};
-pub use graph::Graph;
+pub use intern::{IStr, IPath};
+pub use model::{NodeId, EdgeId, NodeKind, Language, EdgeKind, EdgeSource, GraphNode, GraphEdge, AggregatedEdge, NodeMetadata, Visibility};
+pub use graph::{Graph, GraphPath, Subgraph};
+pub use frozen::FrozenGraph;
 pub use symbols::SymbolTable;
-pub use diff::GraphDiff;
-pub use aggregation::aggregate_edges;
-pub use workspace::{WorkspaceType, detect_workspace};
+pub use diff::{GraphDiff, DiffJournal, DiffSummary, RootDiff, diff_roots, diff_graphs, diff_git_refs};
+pub use validation::ValidationReport;
+pub use stats::GraphStats;
+pub use search::{SearchFilter, SearchMatch};
+pub use snapshot::{Snapshot, save_snapshot, load_snapshot, list_snapshots, delete_snapshot, diff_snapshots, diff_named_snapshots};
+pub use aggregation::{aggregate_edges, IncrementalAggregator, VisibilityDelta};
+pub use view::{ViewState, AggregationCache};
+pub use workspace::{WorkspaceType, WorkspaceInfo, WorkspacePackage, detect_workspace, discover_workspace, add_workspace_nodes};
 pub use cache::{CACHE_DIR, GRAPH_CACHE, cache_dir, graph_cache_path, ensure_cache_dir, save_graph, load_graph, clear_cache, invalidate_file_cache};
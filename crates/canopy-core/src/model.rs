@@ -2,10 +2,12 @@
 
 use std::collections::HashMap;
 use std::hash::{DefaultHasher, Hash, Hasher};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use serde::{Deserialize, Serialize};
 
+pub use crate::intern::{IPath, IStr};
+
 /// Unique, stable identifier for a node.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
 pub struct NodeId(pub u64);
@@ -41,6 +43,18 @@ pub enum NodeKind {
     Method,
     Constant,
     TypeAlias,
+    #[serde(alias = "trait")]
+    Trait,
+    #[serde(alias = "macro")]
+    Macro,
+    #[serde(alias = "Test", alias = "test_case")]
+    TestCase,
+    #[serde(alias = "union")]
+    Union,
+    #[serde(alias = "Property", alias = "field")]
+    Field,
+    #[serde(alias = "Var", alias = "variable")]
+    Variable,
 
     // ── Config / data entities ──────────────────────────────
     ConfigBlock,
@@ -50,6 +64,14 @@ pub enum NodeKind {
     Migration,
     CIJob,
     DockerService,
+    /// A package script (`scripts` entry in package.json).
+    Script,
+
+    // ── Documentation ───────────────────────────────────────
+    /// A Markdown document (README, ADR, architecture notes).
+    Document,
+    /// A heading and the content under it.
+    Section,
 
     // ── Workspace / monorepo ────────────────────────────────
     WorkspaceRoot,
@@ -65,15 +87,61 @@ pub struct GraphNode {
     pub id: NodeId,
     pub kind: NodeKind,
     pub name: String,
-    pub qualified_name: String,
-    pub file_path: PathBuf,
+    pub qualified_name: IStr,
+    pub file_path: IPath,
     pub line_start: Option<u32>,
     pub line_end: Option<u32>,
     pub language: Option<Language>,
     pub is_container: bool,
     pub child_count: u32,
     pub loc: Option<u32>,
-    pub metadata: HashMap<String, String>,
+    pub metadata: NodeMetadata,
+}
+
+/// Declared visibility of a code entity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Visibility {
+    Public,
+    /// Visible within the crate, package or module (`pub(crate)`, Go lowercase, Java package-private).
+    Internal,
+    Protected,
+    Private,
+}
+
+/// Typed metadata attached to a node. Anything not covered by a dedicated
+/// field goes in `extra`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct NodeMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub visibility: Option<Visibility>,
+    /// First sentence of the doc comment or docstring.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub doc_summary: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_test: bool,
+    /// Deprecation note, or an empty string if deprecated without one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deprecated: Option<String>,
+    /// Declaration header, e.g. `pub fn run(&self) -> Result<()>`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    /// Cyclomatic complexity of a function: one plus its branch points.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub complexity: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parameter_count: Option<u32>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub extra: HashMap<String, String>,
+}
+
+impl NodeMetadata {
+    /// Metadata with only extension entries.
+    pub fn with_extra<const N: usize>(entries: [(&str, String); N]) -> Self {
+        NodeMetadata {
+            extra: entries.into_iter().map(|(k, v)| (k.to_string(), v)).collect(),
+            ..Default::default()
+        }
+    }
 }
 
 /// Supported languages for syntax-aware parsing.
@@ -87,6 +155,8 @@ pub enum Language {
     Java,
     C,
     Cpp,
+    Php,
+    Scala,
     Yaml,
     Toml,
     Json,
@@ -100,7 +170,7 @@ pub enum Language {
 
 impl Language {
     /// Detect language from file extension.
-    pub fn from_path(path: &PathBuf) -> Self {
+    pub fn from_path(path: &Path) -> Self {
         match path.extension().and_then(|e| e.to_str()) {
             Some("rs") => Language::Rust,
             Some("ts") | Some("tsx") => Language::TypeScript,
@@ -110,6 +180,8 @@ impl Language {
             Some("java") => Language::Java,
             Some("c") | Some("h") => Language::C,
             Some("cpp") | Some("cc") | Some("cxx") | Some("hpp") | Some("hh") => Language::Cpp,
+            Some("php") => Language::Php,
+            Some("scala") | Some("sc") => Language::Scala,
             Some("yml") | Some("yaml") => Language::Yaml,
             Some("toml") => Language::Toml,
             Some("json") | Some("jsonc") => Language::Json,
@@ -118,7 +190,7 @@ impl Language {
             Some("proto") => Language::Protobuf,
             Some("graphql") | Some("gql") => Language::GraphQL,
             _ => {
-                if path.file_name().map_or(false, |n| {
+                if path.file_name().is_some_and(|n| {
                     let s = n.to_string_lossy();
                     s == "Dockerfile" || s.starts_with("Dockerfile.")
                 }) {
@@ -145,6 +217,16 @@ pub enum EdgeKind {
     TypeReference,
     Instantiates,
     Exports,
+    /// `pub use` / `export { x } from` — a symbol re-exported under this module.
+    Reexports,
+    /// Method overriding (or implementing) a method of a supertype.
+    Overrides,
+    /// Generic symbol reference, e.g. from a precise code index.
+    References,
+    /// Symbol → test that exercises it.
+    TestedBy,
+    /// Class → dependency injected into it (constructor parameter types).
+    DependsOn,
 
     // ── Semantic (AI-inferred) ──────────────────────────────
     ConfiguresArgument,
@@ -156,8 +238,34 @@ pub enum EdgeKind {
     SemanticReference,
 }
 
+impl EdgeKind {
+    /// Every kind of edge, in declaration order.
+    pub const ALL: [EdgeKind; 20] = [
+        EdgeKind::Contains,
+        EdgeKind::Imports,
+        EdgeKind::Calls,
+        EdgeKind::Inherits,
+        EdgeKind::Implements,
+        EdgeKind::TypeReference,
+        EdgeKind::Instantiates,
+        EdgeKind::Exports,
+        EdgeKind::Reexports,
+        EdgeKind::Overrides,
+        EdgeKind::References,
+        EdgeKind::TestedBy,
+        EdgeKind::DependsOn,
+        EdgeKind::ConfiguresArgument,
+        EdgeKind::EnvironmentBinding,
+        EdgeKind::RouteHandler,
+        EdgeKind::MigrationTarget,
+        EdgeKind::CITrigger,
+        EdgeKind::DockerMount,
+        EdgeKind::SemanticReference,
+    ];
+}
+
 /// How this edge was determined.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum EdgeSource {
     /// Determined by AST/structural analysis. Always correct.
     Structural,
@@ -181,7 +289,7 @@ pub struct GraphEdge {
     /// Human-readable label.
     pub label: Option<String>,
     /// Where in source this relationship is expressed.
-    pub file_path: Option<PathBuf>,
+    pub file_path: Option<IPath>,
     pub line: Option<u32>,
 }
 
@@ -0,0 +1,115 @@
+//! Fuzzy search of nodes by name
+
+use crate::graph::Graph;
+use crate::model::*;
+use serde::{Deserialize, Serialize};
+
+/// Default number of matches [`Graph::search`] returns.
+pub const DEFAULT_SEARCH_LIMIT: usize = 20;
+
+/// Which nodes a search considers, and how many it returns.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchFilter {
+    pub kind: Option<NodeKind>,
+    pub language: Option<Language>,
+    pub limit: usize,
+}
+
+impl Default for SearchFilter {
+    fn default() -> Self {
+        Self { kind: None, language: None, limit: DEFAULT_SEARCH_LIMIT }
+    }
+}
+
+/// A node matching a search, and how well.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchMatch {
+    pub id: NodeId,
+    pub score: u32,
+}
+
+impl Graph {
+    /// Nodes whose name, or failing that qualified name, fuzzily matches
+    /// `query`, best first. Case is ignored. An exact name ranks above a
+    /// prefix, a prefix above a substring, and a substring above letters
+    /// found in order; among those, shorter names rank first.
+    pub fn search(&self, query: &str, filter: &SearchFilter) -> Vec<SearchMatch> {
+        let query = query.trim().to_lowercase();
+        if query.is_empty() {
+            return Vec::new();
+        }
+        let mut matches: Vec<(SearchMatch, &GraphNode)> = self
+            .all_nodes()
+            .filter(|n| filter.kind.is_none_or(|kind| n.kind == kind))
+            .filter(|n| filter.language.is_none_or(|language| n.language == Some(language)))
+            .filter_map(|n| {
+                let by_name = fuzzy_score(&query, &n.name);
+                // Paths match too, e.g. `graph::search`, but behind names
+                let by_path = fuzzy_score(&query, n.qualified_name.as_str()).map(|s| s / 2);
+                let score = by_name.max(by_path)?;
+                Some((SearchMatch { id: n.id, score }, n))
+            })
+            .collect();
+        matches.sort_by(|(a, x), (b, y)| {
+            b.score
+                .cmp(&a.score)
+                .then(x.name.len().cmp(&y.name.len()))
+                .then_with(|| x.qualified_name.as_str().cmp(y.qualified_name.as_str()))
+        });
+        matches.into_iter().take(filter.limit).map(|(m, _)| m).collect()
+    }
+}
+
+/// How well `query`, lowercased, matches `text`, or `None` if its letters
+/// don't all appear in `text` in order.
+pub fn fuzzy_score(query: &str, text: &str) -> Option<u32> {
+    let lower = text.to_lowercase();
+    if lower == query {
+        return Some(1000);
+    }
+    if lower.starts_with(query) {
+        return Some(900 - penalty(lower.len() - query.len()));
+    }
+    if let Some(at) = lower.find(query) {
+        // After a separator or at a capital, a substring starts a word
+        let word = is_word_start(text, at);
+        return Some(if word { 800 } else { 700 } - penalty(at));
+    }
+
+    // Letters in order: each one right after the last, or starting a word,
+    // counts for more
+    let mut score: u32 = 0;
+    let mut last: Option<usize> = None;
+    let mut chars = lower.char_indices();
+    for wanted in query.chars() {
+        let (at, _) = chars.by_ref().find(|&(_, c)| c == wanted)?;
+        score += match last {
+            Some(previous) if at == previous + 1 => 15,
+            _ if is_word_start(text, at) => 10,
+            _ => 1,
+        };
+        last = Some(at);
+    }
+    Some((100 + score).saturating_sub(penalty(lower.len() - query.len())).max(1))
+}
+
+/// A penalty for `n` characters more, or further in, than needed.
+fn penalty(n: usize) -> u32 {
+    n.min(99) as u32
+}
+
+/// Whether a word of `text` starts at byte `at`: at the start, after a
+/// separator, or at a capital after a lowercase letter.
+fn is_word_start(text: &str, at: usize) -> bool {
+    // Lowercasing may have moved non-ASCII letters
+    if !text.is_char_boundary(at) {
+        return false;
+    }
+    let Some(current) = text[at..].chars().next() else {
+        return false;
+    };
+    match text[..at].chars().next_back() {
+        None => true,
+        Some(previous) => !previous.is_alphanumeric() || (current.is_uppercase() && previous.is_lowercase()),
+    }
+}
@@ -0,0 +1,185 @@
+//! Named point-in-time graph snapshots stored in .canopy/snapshots/
+
+use crate::cache::cache_dir;
+use crate::diff::GraphDiff;
+use crate::graph::Graph;
+use crate::model::*;
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Snapshot directory, relative to the cache directory.
+pub const SNAPSHOT_DIR: &str = "snapshots";
+
+/// A serialized copy of the graph at a point in time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub name: String,
+    /// RFC 3339 timestamp of when the snapshot was taken.
+    pub created_at: String,
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+impl Snapshot {
+    /// Capture the current state of a graph.
+    pub fn from_graph(name: &str, graph: &Graph) -> Self {
+        Snapshot {
+            name: name.to_string(),
+            created_at: chrono::Utc::now().to_rfc3339(),
+            nodes: graph.all_nodes().cloned().collect(),
+            edges: graph.all_edges().cloned().collect(),
+        }
+    }
+
+    /// Rebuild a graph from this snapshot. Node and edge IDs are reassigned.
+    pub fn to_graph(&self) -> Graph {
+        let mut graph = Graph::new();
+        let mut id_map = HashMap::new();
+        for node in &self.nodes {
+            id_map.insert(node.id, graph.add_node(node.clone()));
+        }
+        for edge in &self.edges {
+            if let (Some(&source), Some(&target)) = (id_map.get(&edge.source), id_map.get(&edge.target)) {
+                graph.add_edge(GraphEdge {
+                    source,
+                    target,
+                    ..edge.clone()
+                });
+            }
+        }
+        graph
+    }
+}
+
+/// Get snapshot directory path
+pub fn snapshot_dir(root: &Path) -> PathBuf {
+    cache_dir(root).join(SNAPSHOT_DIR)
+}
+
+/// Get the file path for a named snapshot.
+pub fn snapshot_path(root: &Path, name: &str) -> anyhow::Result<PathBuf> {
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+        bail!("invalid snapshot name '{}'", name);
+    }
+    Ok(snapshot_dir(root).join(format!("{}.json", name)))
+}
+
+/// Save a named snapshot of the graph, replacing any snapshot with the same name.
+pub fn save_snapshot(graph: &Graph, root: &Path, name: &str) -> anyhow::Result<PathBuf> {
+    let path = snapshot_path(root, name)?;
+    std::fs::create_dir_all(snapshot_dir(root))?;
+
+    let snapshot = Snapshot::from_graph(name, graph);
+    std::fs::write(&path, serde_json::to_string(&snapshot)?)?;
+
+    tracing::debug!("Snapshot '{}' saved: {}", name, path.display());
+    Ok(path)
+}
+
+/// Load a named snapshot.
+pub fn load_snapshot(root: &Path, name: &str) -> anyhow::Result<Snapshot> {
+    let path = snapshot_path(root, name)?;
+    let json_str = std::fs::read_to_string(&path)
+        .with_context(|| format!("snapshot '{}' not found", name))?;
+    Ok(serde_json::from_str(&json_str)?)
+}
+
+/// List saved snapshot names, sorted.
+pub fn list_snapshots(root: &Path) -> anyhow::Result<Vec<String>> {
+    let dir = snapshot_dir(root);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut names = Vec::new();
+    for entry in std::fs::read_dir(&dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|e| e == "json")
+            && let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+            names.push(stem.to_string());
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// Delete a named snapshot.
+pub fn delete_snapshot(root: &Path, name: &str) -> anyhow::Result<()> {
+    std::fs::remove_file(snapshot_path(root, name)?)?;
+    Ok(())
+}
+
+/// Identity of a node across snapshots; IDs are not stable between indexing runs.
+type NodeKey<'a> = (NodeKind, &'a str, &'a Path);
+
+fn node_key(node: &GraphNode) -> NodeKey<'_> {
+    (node.kind, node.qualified_name.as_str(), node.file_path.as_path())
+}
+
+/// Compute the changes that turn snapshot `before` into snapshot `after`.
+///
+/// Nodes are matched by kind, qualified name and file path, and edges by
+/// their matched endpoints and kind. Removed IDs refer to `before`; added
+/// and modified entries refer to `after`.
+pub fn diff_snapshots(before: &Snapshot, after: &Snapshot) -> GraphDiff {
+    let mut diff = GraphDiff::new(0);
+
+    let before_nodes: HashMap<NodeKey, &GraphNode> = before.nodes.iter().map(|n| (node_key(n), n)).collect();
+    let after_nodes: HashMap<NodeKey, &GraphNode> = after.nodes.iter().map(|n| (node_key(n), n)).collect();
+
+    for node in &after.nodes {
+        match before_nodes.get(&node_key(node)) {
+            None => diff.added_nodes.push(node.clone()),
+            Some(old) => {
+                // Compare ignoring the (unstable) ID
+                let old = GraphNode { id: node.id, ..(*old).clone() };
+                if old != *node {
+                    diff.modified_nodes.push(node.id);
+                }
+            }
+        }
+    }
+    for node in &before.nodes {
+        if !after_nodes.contains_key(&node_key(node)) {
+            diff.removed_nodes.push(node.id);
+        }
+    }
+
+    let before_edges = edge_keys(before);
+    let after_edges = edge_keys(after);
+
+    for (key, edge) in &after_edges {
+        if !before_edges.contains_key(key) {
+            diff.added_edges.push((*edge).clone());
+        }
+    }
+    for (key, edge) in &before_edges {
+        if !after_edges.contains_key(key) {
+            diff.removed_edges.push(edge.id);
+        }
+    }
+
+    diff.added_edges.sort_by_key(|e| e.id.0);
+    diff.removed_edges.sort_by_key(|id| id.0);
+    diff
+}
+
+type EdgeKey<'a> = (NodeKey<'a>, NodeKey<'a>, EdgeKind);
+
+fn edge_keys(snapshot: &Snapshot) -> HashMap<EdgeKey<'_>, &GraphEdge> {
+    let keys: HashMap<NodeId, NodeKey> = snapshot.nodes.iter().map(|n| (n.id, node_key(n))).collect();
+    snapshot
+        .edges
+        .iter()
+        .filter_map(|e| Some(((*keys.get(&e.source)?, *keys.get(&e.target)?, e.kind), e)))
+        .collect()
+}
+
+/// Load two named snapshots and diff them.
+pub fn diff_named_snapshots(root: &Path, before: &str, after: &str) -> anyhow::Result<GraphDiff> {
+    let before = load_snapshot(root, before)?;
+    let after = load_snapshot(root, after)?;
+    Ok(diff_snapshots(&before, &after))
+}
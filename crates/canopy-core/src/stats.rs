@@ -0,0 +1,110 @@
+//! Summary statistics over the code graph
+
+use crate::graph::Graph;
+use crate::model::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Default number of entries in the ranked lists of [`GraphStats`].
+pub const DEFAULT_TOP_N: usize = 10;
+
+/// A file ranked by lines of code.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FileLoc {
+    pub id: NodeId,
+    pub path: PathBuf,
+    pub loc: u32,
+}
+
+/// A node ranked by a count of its edges, such as its fan-in.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RankedNode {
+    pub id: NodeId,
+    pub name: String,
+    pub count: usize,
+}
+
+/// Counts and rankings summarizing a graph.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct GraphStats {
+    pub node_count: usize,
+    pub edge_count: usize,
+    pub nodes_by_kind: HashMap<NodeKind, usize>,
+    pub edges_by_kind: HashMap<EdgeKind, usize>,
+    pub edges_by_source: HashMap<EdgeSource, usize>,
+    /// Number of file nodes per language.
+    pub languages: HashMap<Language, usize>,
+    /// Largest files by LOC, descending.
+    pub largest_files: Vec<FileLoc>,
+    /// Nodes with the most incoming non-containment edges, descending.
+    pub top_fan_in: Vec<RankedNode>,
+    /// Nodes with the most outgoing non-containment edges, descending.
+    pub top_fan_out: Vec<RankedNode>,
+}
+
+impl Graph {
+    /// Compute summary statistics, with the default ranking length.
+    pub fn stats(&self) -> GraphStats {
+        self.stats_with_limit(DEFAULT_TOP_N)
+    }
+
+    /// Compute summary statistics, keeping `top_n` entries in ranked lists.
+    pub fn stats_with_limit(&self, top_n: usize) -> GraphStats {
+        let mut stats = GraphStats {
+            node_count: self.node_count(),
+            edge_count: self.edge_count(),
+            ..Default::default()
+        };
+
+        for node in self.all_nodes() {
+            *stats.nodes_by_kind.entry(node.kind).or_insert(0) += 1;
+            if node.kind == NodeKind::File {
+                if let Some(language) = node.language {
+                    *stats.languages.entry(language).or_insert(0) += 1;
+                }
+                if let Some(loc) = node.loc {
+                    stats.largest_files.push(FileLoc {
+                        id: node.id,
+                        path: node.file_path.to_path_buf(),
+                        loc,
+                    });
+                }
+            }
+        }
+
+        let mut fan_in: HashMap<NodeId, usize> = HashMap::new();
+        let mut fan_out: HashMap<NodeId, usize> = HashMap::new();
+        for edge in self.all_edges() {
+            *stats.edges_by_kind.entry(edge.kind).or_insert(0) += 1;
+            *stats.edges_by_source.entry(edge.edge_source).or_insert(0) += 1;
+            if edge.kind != EdgeKind::Contains {
+                *fan_in.entry(edge.target).or_insert(0) += 1;
+                *fan_out.entry(edge.source).or_insert(0) += 1;
+            }
+        }
+
+        stats.largest_files.sort_by(|a, b| b.loc.cmp(&a.loc).then(a.id.0.cmp(&b.id.0)));
+        stats.largest_files.truncate(top_n);
+
+        stats.top_fan_in = self.rank(fan_in, top_n);
+        stats.top_fan_out = self.rank(fan_out, top_n);
+
+        stats
+    }
+
+    /// The `top_n` nodes with the highest counts, descending, ties in ID
+    /// order.
+    fn rank(&self, counts: HashMap<NodeId, usize>, top_n: usize) -> Vec<RankedNode> {
+        let mut ranked: Vec<RankedNode> = counts
+            .into_iter()
+            .filter_map(|(id, count)| {
+                let node = self.node(id)?;
+                Some(RankedNode { id, name: node.name.clone(), count })
+            })
+            .collect();
+        ranked.sort_by(|a, b| b.count.cmp(&a.count).then(a.id.0.cmp(&b.id.0)));
+        ranked.truncate(top_n);
+        ranked
+    }
+}
@@ -1,19 +1,26 @@
 //! Symbol table for cross-file resolution
 
-use crate::model::NodeId;
+use crate::model::{GraphNode, NodeId};
 use dashmap::DashMap;
 
 /// Symbol table mapping qualified names to NodeIds. Thread-safe for concurrent access.
+///
+/// Qualified names need not be unique across files (`crate::graph::Graph` in
+/// two crates); lookups by qualified name return the latest insert, while
+/// per-file and per-name lookups see every symbol.
 pub struct SymbolTable {
     symbols: DashMap<String, NodeId>,
-    /// For fast file lookup: file path -> list of symbol names in that file
-    file_symbols: DashMap<String, Vec<String>>,
+    /// Simple name -> symbols, for resolving unqualified references
+    names: DashMap<String, Vec<NodeId>>,
+    /// For fast file lookup: file path -> symbols in that file
+    file_symbols: DashMap<String, Vec<(String, NodeId)>>,
 }
 
 impl SymbolTable {
     pub fn new() -> Self {
         SymbolTable {
             symbols: DashMap::new(),
+            names: DashMap::new(),
             file_symbols: DashMap::new(),
         }
     }
@@ -23,8 +30,14 @@ impl SymbolTable {
         self.symbols.insert(qualified_name.clone(), node_id);
         self.file_symbols
             .entry(file_path)
-            .or_insert_with(Vec::new)
-            .push(qualified_name);
+            .or_default()
+            .push((qualified_name, node_id));
+    }
+
+    /// Insert a graph node, indexed by both its qualified and simple name.
+    pub fn insert_node(&self, node: &GraphNode) {
+        self.names.entry(node.name.clone()).or_default().push(node.id);
+        self.insert(node.qualified_name.to_string(), node.id, node.file_path.display().to_string());
     }
 
     /// Look up a symbol by qualified name.
@@ -32,25 +45,35 @@ impl SymbolTable {
         self.symbols.get(qualified_name).map(|r| *r.value())
     }
 
+    /// Look up a symbol by qualified name among those defined in a file.
+    pub fn lookup_in_file(&self, file_path: &str, qualified_name: &str) -> Option<NodeId> {
+        let symbols = self.file_symbols.get(file_path)?;
+        symbols.iter().find(|(name, _)| name == qualified_name).map(|&(_, id)| id)
+    }
+
+    /// Look up every symbol with the given simple name.
+    pub fn lookup_name(&self, name: &str) -> Vec<NodeId> {
+        self.names.get(name).map(|r| r.value().clone()).unwrap_or_default()
+    }
+
     /// Get all symbols defined in a file.
     pub fn symbols_in_file(&self, file_path: &str) -> Vec<NodeId> {
         self.file_symbols
             .get(file_path)
-            .map(|r| {
-                r.value()
-                    .iter()
-                    .filter_map(|name| self.symbols.get(name).map(|n| *n.value()))
-                    .collect()
-            })
+            .map(|r| r.value().iter().map(|&(_, id)| id).collect())
             .unwrap_or_default()
     }
 
     /// Remove all symbols for a file (useful for incremental re-indexing).
     pub fn remove_file(&self, file_path: &str) {
         if let Some((_, symbols)) = self.file_symbols.remove(file_path) {
-            for name in symbols {
-                self.symbols.remove(&name);
+            for (name, id) in &symbols {
+                self.symbols.remove_if(name, |_, existing| existing == id);
             }
+            self.names.retain(|_, ids| {
+                ids.retain(|id| !symbols.iter().any(|(_, removed)| removed == id));
+                !ids.is_empty()
+            });
         }
     }
 }
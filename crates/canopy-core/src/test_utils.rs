@@ -1,9 +1,68 @@
 //! Test utilities for Canopy
+//!
+//! Other crates' tests get these with the `test-util` feature.
 
+use crate::model::{GraphNode, Language, NodeId, NodeKind, NodeMetadata};
 use tempfile::TempDir;
 use std::fs;
 use std::path::Path;
 
+impl GraphNode {
+    /// A node for tests: a `kind` named `name`, at the path `name`, with
+    /// no lines, language or metadata. Directories, files, packages and
+    /// the workspace root are containers. The `with_*` methods fill in
+    /// what a test needs.
+    pub fn for_test(kind: NodeKind, name: &str) -> Self {
+        GraphNode {
+            id: NodeId(0),
+            kind,
+            name: name.to_string(),
+            qualified_name: name.into(),
+            file_path: Path::new(name).into(),
+            line_start: None,
+            line_end: None,
+            language: None,
+            is_container: matches!(kind, NodeKind::Directory | NodeKind::File | NodeKind::Package | NodeKind::WorkspaceRoot),
+            child_count: 0,
+            loc: None,
+            metadata: NodeMetadata::default(),
+        }
+    }
+
+    pub fn with_id(mut self, id: u64) -> Self {
+        self.id = NodeId(id);
+        self
+    }
+
+    pub fn with_qualified_name(mut self, qualified_name: &str) -> Self {
+        self.qualified_name = qualified_name.into();
+        self
+    }
+
+    pub fn in_file(mut self, path: impl AsRef<Path>) -> Self {
+        self.file_path = path.as_ref().into();
+        self
+    }
+
+    /// Lines `start` to `end`, which also make up its LOC.
+    pub fn with_lines(mut self, start: u32, end: u32) -> Self {
+        self.line_start = Some(start);
+        self.line_end = Some(end);
+        self.loc = Some(end - start + 1);
+        self
+    }
+
+    pub fn with_language(mut self, language: Language) -> Self {
+        self.language = Some(language);
+        self
+    }
+
+    pub fn with_container(mut self, is_container: bool) -> Self {
+        self.is_container = is_container;
+        self
+    }
+}
+
 /// Create a temporary test repository with sample files
 pub fn create_test_repo() -> TempDir {
     let temp_dir = TempDir::new().unwrap();
@@ -1,7 +1,7 @@
 //! Unit tests for canopy-core module
 
-use canopy_core::*;
-use std::path::PathBuf;
+use crate::*;
+use std::path::{Path, PathBuf};
 
 #[test]
 fn test_node_id_creation() {
@@ -23,15 +23,15 @@ fn test_graph_node_creation() {
         id: NodeId(1),
         kind: NodeKind::Function,
         name: "test_function".to_string(),
-        qualified_name: "module::test_function".to_string(),
-        file_path: PathBuf::from("src/lib.rs"),
+        qualified_name: "module::test_function".into(),
+        file_path: PathBuf::from("src/lib.rs").into(),
         line_start: Some(10),
         line_end: Some(20),
         language: Some(Language::Rust),
         is_container: false,
         child_count: 0,
         loc: Some(10),
-        metadata: std::collections::HashMap::new(),
+        metadata: NodeMetadata::default(),
     };
     
     assert_eq!(node.name, "test_function");
@@ -48,30 +48,30 @@ fn test_graph_operations() {
         id: NodeId(0),
         kind: NodeKind::Function,
         name: "func1".to_string(),
-        qualified_name: "func1".to_string(),
-        file_path: PathBuf::from("test.rs"),
+        qualified_name: "func1".into(),
+        file_path: PathBuf::from("test.rs").into(),
         line_start: None,
         line_end: None,
         language: None,
         is_container: false,
         child_count: 0,
         loc: None,
-        metadata: std::collections::HashMap::new(),
+        metadata: NodeMetadata::default(),
     };
     
     let node2 = GraphNode {
         id: NodeId(0),
         kind: NodeKind::Function,
         name: "func2".to_string(),
-        qualified_name: "func2".to_string(),
-        file_path: PathBuf::from("test.rs"),
+        qualified_name: "func2".into(),
+        file_path: PathBuf::from("test.rs").into(),
         line_start: None,
         line_end: None,
         language: None,
         is_container: false,
         child_count: 0,
         loc: None,
-        metadata: std::collections::HashMap::new(),
+        metadata: NodeMetadata::default(),
     };
     
     let id1 = graph.add_node(node1);
@@ -88,7 +88,7 @@ fn test_graph_operations() {
         edge_source: EdgeSource::Heuristic,
         confidence: 0.8,
         label: Some("calls".to_string()),
-        file_path: Some(PathBuf::from("test.rs")),
+        file_path: Some(PathBuf::from("test.rs").into()),
         line: None,
     };
     
@@ -105,7 +105,7 @@ fn test_edge_kinds() {
     let kinds = vec![
         EdgeKind::Calls,
         EdgeKind::DependsOn,
-        EdgeKind::Uses,
+        EdgeKind::References,
         EdgeKind::Imports,
     ];
     
@@ -127,10 +127,7 @@ fn test_node_kinds() {
     assert_ne!(function_kind, class_kind);
     
     // Test that node kinds can be used in match statements
-    match function_kind {
-        NodeKind::Function => assert!(true),
-        _ => panic!("Expected Function kind"),
-    }
+    assert!(matches!(function_kind, NodeKind::Function));
 }
 
 #[test]
@@ -142,30 +139,30 @@ fn test_graph_ancestors() {
         id: NodeId(0),
         kind: NodeKind::Directory,
         name: "src".to_string(),
-        qualified_name: "src".to_string(),
-        file_path: PathBuf::from("src"),
+        qualified_name: "src".into(),
+        file_path: PathBuf::from("src").into(),
         line_start: None,
         line_end: None,
         language: None,
         is_container: true,
         child_count: 0,
         loc: None,
-        metadata: std::collections::HashMap::new(),
+        metadata: NodeMetadata::default(),
     };
     
     let child = GraphNode {
         id: NodeId(0),
         kind: NodeKind::File,
         name: "lib.rs".to_string(),
-        qualified_name: "lib.rs".to_string(),
-        file_path: PathBuf::from("src/lib.rs"),
+        qualified_name: "lib.rs".into(),
+        file_path: PathBuf::from("src/lib.rs").into(),
         line_start: None,
         line_end: None,
         language: None,
         is_container: false,
         child_count: 0,
         loc: None,
-        metadata: std::collections::HashMap::new(),
+        metadata: NodeMetadata::default(),
     };
     
     let root_id = graph.add_node(root);
@@ -204,6 +201,8 @@ fn test_language_detection() {
         ("Main.java", Language::Java),
         ("main.c", Language::C),
         ("main.cpp", Language::Cpp),
+        ("index.php", Language::Php),
+        ("Main.scala", Language::Scala),
         ("config.yml", Language::Yaml),
         ("config.toml", Language::Toml),
         ("package.json", Language::Json),
@@ -236,18 +235,18 @@ fn test_graph_node_serialization() {
         id: NodeId(1),
         kind: NodeKind::Function,
         name: "test".to_string(),
-        qualified_name: "test".to_string(),
-        file_path: PathBuf::from("test.rs"),
+        qualified_name: "test".into(),
+        file_path: PathBuf::from("test.rs").into(),
         line_start: Some(10),
         line_end: Some(20),
         language: Some(Language::Rust),
         is_container: false,
         child_count: 0,
         loc: Some(10),
-        metadata: {
-            let mut map = std::collections::HashMap::new();
-            map.insert("test".to_string(), "value".to_string());
-            map
+        metadata: NodeMetadata {
+            visibility: Some(Visibility::Public),
+            signature: Some("fn test()".to_string()),
+            ..NodeMetadata::with_extra([("test", "value".to_string())])
         },
     };
     
@@ -256,4 +255,994 @@ fn test_graph_node_serialization() {
     
     assert_eq!(node.id, deserialized.id);
     assert_eq!(node.name, deserialized.name);
-}
\ No newline at end of file
+    assert_eq!(node.metadata, deserialized.metadata);
+
+    // Unset fields are omitted and default back when missing
+    let value = serde_json::to_value(&node).unwrap();
+    assert!(value["metadata"].get("doc_summary").is_none());
+    assert!(value["metadata"].get("is_test").is_none());
+}
+fn test_node(kind: NodeKind, name: &str, is_container: bool) -> GraphNode {
+    GraphNode {
+        id: NodeId(0),
+        kind,
+        name: name.to_string(),
+        qualified_name: name.into(),
+        file_path: PathBuf::from(name).into(),
+        line_start: None,
+        line_end: None,
+        language: None,
+        is_container,
+        child_count: 0,
+        loc: None,
+        metadata: NodeMetadata::default(),
+    }
+}
+
+fn test_edge(source: NodeId, target: NodeId, kind: EdgeKind) -> GraphEdge {
+    GraphEdge {
+        id: EdgeId(0),
+        source,
+        target,
+        kind,
+        edge_source: EdgeSource::Structural,
+        confidence: 1.0,
+        label: None,
+        file_path: None,
+        line: None,
+    }
+}
+
+/// Sort aggregated edges so incremental and full results can be compared.
+fn normalized(mut edges: Vec<AggregatedEdge>) -> Vec<AggregatedEdge> {
+    for edge in &mut edges {
+        edge.underlying_edge_ids.sort_by_key(|id| id.0);
+    }
+    edges.sort_by_key(|e| (e.source.0, e.target.0));
+    edges
+}
+
+#[test]
+fn test_incremental_aggregation() {
+    use std::collections::HashSet;
+
+    let mut graph = Graph::new();
+    let dir_a = graph.add_node(test_node(NodeKind::Directory, "a", true));
+    let dir_b = graph.add_node(test_node(NodeKind::Directory, "b", true));
+    let file_a = graph.add_node(test_node(NodeKind::File, "a/x.rs", false));
+    let file_b = graph.add_node(test_node(NodeKind::File, "b/y.rs", false));
+    graph.add_edge(test_edge(dir_a, file_a, EdgeKind::Contains));
+    graph.add_edge(test_edge(dir_b, file_b, EdgeKind::Contains));
+    graph.add_edge(test_edge(file_a, file_b, EdgeKind::Calls));
+    graph.add_edge(test_edge(file_a, file_b, EdgeKind::Imports));
+
+    let mut visible: HashSet<NodeId> = [dir_a, dir_b].into_iter().collect();
+    let mut collapsed = visible.clone();
+    let mut aggregator = IncrementalAggregator::new(&graph, visible.clone(), collapsed.clone());
+
+    let agg = aggregator.get(dir_a, dir_b).expect("collapsed edge");
+    assert_eq!(agg.count, 2);
+    assert_eq!(
+        normalized(aggregator.aggregated_edges()),
+        normalized(aggregate_edges(&graph, &visible, &collapsed))
+    );
+
+    // Expanding a container re-targets only the edges beneath it
+    let delta = VisibilityDelta {
+        shown: vec![file_a],
+        expanded: vec![dir_a],
+        ..Default::default()
+    };
+    aggregator.apply_visibility(&graph, &delta);
+    visible.insert(file_a);
+    collapsed.remove(&dir_a);
+
+    assert!(aggregator.get(dir_a, dir_b).is_none());
+    assert_eq!(aggregator.get(file_a, dir_b).map(|e| e.count), Some(2));
+    assert_eq!(
+        normalized(aggregator.aggregated_edges()),
+        normalized(aggregate_edges(&graph, &visible, &collapsed))
+    );
+
+    // Graph diffs add and remove underlying edges
+    let calls = graph
+        .edges_from(file_a)
+        .find(|e| e.kind == EdgeKind::Calls)
+        .map(|e| e.id)
+        .unwrap();
+    graph.remove_edge(calls);
+    let mut ai_edge = test_edge(file_b, file_a, EdgeKind::SemanticReference);
+    ai_edge.edge_source = EdgeSource::AI;
+    ai_edge.confidence = 0.6;
+    let ai_id = graph.add_edge(ai_edge);
+
+    let mut diff = GraphDiff::new(1);
+    diff.removed_edges.push(calls);
+    diff.added_edges.push(graph.edge(ai_id).unwrap().clone());
+    aggregator.apply_diff(&graph, &diff);
+
+    assert_eq!(aggregator.get(file_a, dir_b).map(|e| e.count), Some(1));
+    assert_eq!(aggregator.get(dir_b, file_a).and_then(|e| e.min_confidence), Some(0.6));
+    assert_eq!(
+        normalized(aggregator.aggregated_edges()),
+        normalized(aggregate_edges(&graph, &visible, &collapsed))
+    );
+
+    // Removing a node drops its edges even when the diff doesn't list them
+    graph.remove_node(file_b);
+    let mut diff = GraphDiff::new(2);
+    diff.removed_nodes.push(file_b);
+    aggregator.apply_diff(&graph, &diff);
+
+    assert_eq!(aggregator.edges().count(), 0);
+    assert_eq!(
+        normalized(aggregator.aggregated_edges()),
+        normalized(aggregate_edges(&graph, &visible, &collapsed))
+    );
+}
+
+#[test]
+fn test_subgraph_around() {
+    let mut graph = Graph::new();
+    let a = graph.add_node(test_node(NodeKind::Function, "a", false));
+    let b = graph.add_node(test_node(NodeKind::Function, "b", false));
+    let c = graph.add_node(test_node(NodeKind::Function, "c", false));
+    let d = graph.add_node(test_node(NodeKind::Function, "d", false));
+    graph.add_edge(test_edge(a, b, EdgeKind::Calls));
+    graph.add_edge(test_edge(c, b, EdgeKind::Calls));
+    graph.add_edge(test_edge(c, d, EdgeKind::Imports));
+
+    let ids = |sub: &Subgraph| {
+        let mut ids: Vec<_> = sub.nodes.iter().map(|n| n.id).collect();
+        ids.sort_by_key(|id| id.0);
+        ids
+    };
+
+    // Neighbors are reached through incoming as well as outgoing edges
+    let one_hop = graph.subgraph_around(b, 1, &[]);
+    assert_eq!(ids(&one_hop), vec![a, b, c]);
+    assert_eq!(one_hop.edges.len(), 2);
+
+    let two_hops = graph.subgraph_around(b, 2, &[]);
+    assert_eq!(ids(&two_hops), vec![a, b, c, d]);
+    assert_eq!(two_hops.edges.len(), 3);
+
+    // Edge kind filter restricts both traversal and returned edges
+    let calls_only = graph.subgraph_around(b, 2, &[EdgeKind::Calls]);
+    assert_eq!(ids(&calls_only), vec![a, b, c]);
+    assert!(calls_only.edges.iter().all(|e| e.kind == EdgeKind::Calls));
+
+    assert_eq!(ids(&graph.subgraph_around(b, 0, &[])), vec![b]);
+    assert!(graph.subgraph_around(NodeId(99), 2, &[]).nodes.is_empty());
+}
+
+#[test]
+fn test_filtered_edges() {
+    let mut graph = Graph::new();
+    let a = graph.add_node(test_node(NodeKind::Function, "a", false));
+    let b = graph.add_node(test_node(NodeKind::Function, "b", false));
+    graph.add_edge(test_edge(a, b, EdgeKind::Calls));
+    let mut heuristic = test_edge(a, b, EdgeKind::Imports);
+    heuristic.edge_source = EdgeSource::Heuristic;
+    heuristic.confidence = 0.9;
+    graph.add_edge(heuristic);
+    for (kind, confidence) in [(EdgeKind::SemanticReference, 0.5), (EdgeKind::ConfiguresArgument, 0.85)] {
+        let mut ai = test_edge(b, a, kind);
+        ai.edge_source = EdgeSource::AI;
+        ai.confidence = confidence;
+        graph.add_edge(ai);
+    }
+
+    let deterministic = graph.filtered(0.0, &[EdgeSource::Structural, EdgeSource::Heuristic]);
+    assert_eq!(deterministic.count(), 2);
+
+    let confident_ai: Vec<_> = graph.filtered(0.8, &[EdgeSource::AI]).collect();
+    assert_eq!(confident_ai.len(), 1);
+    assert_eq!(confident_ai[0].confidence, 0.85);
+
+    assert_eq!(graph.filtered(0.0, &[]).count(), 4);
+    assert_eq!(graph.filtered(0.95, &[]).count(), 1);
+}
+
+#[test]
+fn test_container_rollups() {
+    let mut graph = Graph::new();
+    let root = graph.add_node(test_node(NodeKind::Directory, "src", true));
+    let mut file = test_node(NodeKind::File, "src/lib.rs", true);
+    file.loc = Some(40);
+    let file = graph.add_node(file);
+    let mut func = test_node(NodeKind::Function, "run", false);
+    func.loc = Some(10);
+    let func = graph.add_node(func);
+    let mut other = test_node(NodeKind::Function, "stop", false);
+    other.loc = Some(5);
+    let other = graph.add_node(other);
+    graph.add_edge(test_edge(root, file, EdgeKind::Contains));
+    graph.add_edge(test_edge(file, func, EdgeKind::Contains));
+    graph.add_edge(test_edge(file, other, EdgeKind::Contains));
+
+    graph.recompute_rollups();
+    assert_eq!(graph.node(root).unwrap().child_count, 1);
+    assert_eq!(graph.node(file).unwrap().child_count, 2);
+    // A file counts its own lines, blank and comment ones included,
+    // not just those of the symbols in it
+    assert_eq!(graph.node(file).unwrap().loc, Some(40));
+    assert_eq!(graph.node(root).unwrap().loc, Some(40));
+    assert_eq!(graph.node(func).unwrap().loc, Some(10));
+
+    // Incremental refresh from the parent of a removed node
+    graph.remove_node(other);
+    graph.node_mut(file).unwrap().loc = Some(30);
+    assert_eq!(graph.refresh_rollups(&[file]), vec![file, root]);
+    assert_eq!(graph.node(file).unwrap().child_count, 1);
+    assert_eq!(graph.node(root).unwrap().loc, Some(30));
+}
+
+#[test]
+fn test_edge_dedup_on_insert() {
+    let mut graph = Graph::new();
+    let a = graph.add_node(test_node(NodeKind::File, "a.rs", false));
+    let b = graph.add_node(test_node(NodeKind::File, "b.rs", false));
+
+    let first = graph.add_edge(test_edge(a, b, EdgeKind::Imports));
+    let second = graph.add_edge(test_edge(a, b, EdgeKind::Imports));
+    assert_eq!(first, second);
+    assert_eq!(graph.edge_count(), 1);
+
+    // A different kind between the same nodes is still a separate edge
+    graph.add_edge(test_edge(a, b, EdgeKind::Calls));
+    assert_eq!(graph.edge_count(), 2);
+
+    let mut updated = test_edge(a, b, EdgeKind::Imports);
+    updated.confidence = 0.9;
+    updated.label = Some("imports b".to_string());
+    assert_eq!(graph.upsert_edge(updated), first);
+    let edge = graph.edge(first).unwrap();
+    assert_eq!(edge.confidence, 0.9);
+    assert_eq!(edge.label.as_deref(), Some("imports b"));
+    assert_eq!(graph.edge_count(), 2);
+
+    assert_eq!(graph.dedupe_edges(), 0);
+}
+
+#[test]
+fn test_graph_validation() {
+    let mut graph = Graph::new();
+    let root = graph.add_node(test_node(NodeKind::Directory, "src", true));
+    let file = graph.add_node(test_node(NodeKind::File, "src/lib.rs", true));
+    graph.add_edge(test_edge(root, file, EdgeKind::Contains));
+    assert!(graph.validate().is_valid());
+
+    let mut backwards = test_node(NodeKind::Function, "backwards", false);
+    backwards.line_start = Some(20);
+    backwards.line_end = Some(10);
+    let backwards = graph.add_node(backwards);
+    graph.add_edge(test_edge(file, backwards, EdgeKind::Contains));
+
+    // Removing a node drops its edges too, so nothing is left dangling
+    let orphan = graph.add_node(test_node(NodeKind::Function, "orphan", false));
+    let gone = graph.add_node(test_node(NodeKind::Function, "gone", false));
+    graph.add_edge(test_edge(orphan, gone, EdgeKind::Calls));
+    graph.remove_node(gone);
+
+    let report = graph.validate();
+    assert!(!report.is_valid());
+    assert_eq!(report.invalid_line_ranges, vec![backwards]);
+    assert_eq!(report.orphaned_nodes, vec![orphan]);
+    assert!(report.dangling_edges.is_empty());
+}
+
+#[test]
+fn test_cargo_workspace_packages() {
+    let temp_dir = crate::test_utils::create_repo_with_structure(&[
+        ("Cargo.toml", "[workspace]\nmembers = [\"crates/*\"]\n"),
+        ("crates/core/Cargo.toml", "[package]\nname = \"app-core\"\n\n[dependencies]\nserde = \"1\"\n"),
+        ("crates/cli/Cargo.toml", "[package]\nname = \"app-cli\"\n\n[dependencies]\ncore = { package = \"app-core\", path = \"../core\" }\n"),
+    ]);
+
+    let info = discover_workspace(temp_dir.path()).unwrap().unwrap();
+    assert_eq!(info.kind, WorkspaceType::Cargo);
+    let mut names: Vec<_> = info.packages.iter().map(|p| p.name.as_str()).collect();
+    names.sort();
+    assert_eq!(names, vec!["app-cli", "app-core"]);
+
+    let mut graph = Graph::new();
+    let root = add_workspace_nodes(&mut graph, &info, None);
+    assert_eq!(graph.node(root).unwrap().kind, NodeKind::WorkspaceRoot);
+    assert_eq!(graph.nodes_of_kind(NodeKind::Package).count(), 2);
+
+    let cli = graph.find_node_by_name("app-cli").unwrap();
+    let core = graph.find_node_by_name("app-core").unwrap();
+    assert!(graph.has_edge_between(root, cli, EdgeKind::Contains));
+    assert!(graph.has_edge_between(cli, core, EdgeKind::Imports));
+    // External dependencies don't produce edges
+    assert_eq!(graph.edges_from(core).count(), 0);
+}
+
+#[test]
+fn test_npm_and_go_workspaces() {
+    let npm = crate::test_utils::create_repo_with_structure(&[
+        ("package.json", r#"{"name": "root", "private": true, "workspaces": {"packages": ["packages/*", "!packages/skip"]}}"#),
+        ("packages/ui/package.json", r#"{"name": "@app/ui", "dependencies": {"react": "^18"}}"#),
+        ("packages/web/package.json", r#"{"name": "@app/web", "dependencies": {"@app/ui": "workspace:*"}}"#),
+        ("packages/skip/package.json", r#"{"name": "@app/skip"}"#),
+    ]);
+    let info = discover_workspace(npm.path()).unwrap().unwrap();
+    assert_eq!(info.kind, WorkspaceType::Npm);
+    let mut names: Vec<_> = info.packages.iter().map(|p| p.name.as_str()).collect();
+    names.sort();
+    assert_eq!(names, vec!["@app/ui", "@app/web", "root"]);
+
+    let pnpm = crate::test_utils::create_repo_with_structure(&[
+        ("package.json", r#"{"name": "root"}"#),
+        ("pnpm-workspace.yaml", "packages:\n  - 'apps/*'\n"),
+        ("apps/site/package.json", r#"{"name": "site"}"#),
+    ]);
+    let info = discover_workspace(pnpm.path()).unwrap().unwrap();
+    assert_eq!(info.kind, WorkspaceType::Pnpm);
+    assert_eq!(info.packages.len(), 2);
+
+    let go = crate::test_utils::create_repo_with_structure(&[
+        ("go.work", "go 1.22\n\nuse (\n    ./api\n    ./lib\n)\n"),
+        ("api/go.mod", "module example.com/api\n\nrequire (\n    example.com/lib v0.0.0\n)\n"),
+        ("lib/go.mod", "module example.com/lib\n"),
+    ]);
+    let info = discover_workspace(go.path()).unwrap().unwrap();
+    assert_eq!(info.kind, WorkspaceType::GoModules);
+    let api = info.packages.iter().find(|p| p.name == "example.com/api").unwrap();
+    assert_eq!(api.dependencies, vec!["example.com/lib".to_string()]);
+}
+
+#[test]
+fn test_snapshot_diff() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let root = temp_dir.path();
+
+    let mut graph = Graph::new();
+    let dir = graph.add_node(test_node(NodeKind::Directory, "src", true));
+    let old = graph.add_node(test_node(NodeKind::Function, "old", false));
+    let kept = graph.add_node(test_node(NodeKind::Function, "kept", false));
+    graph.add_edge(test_edge(dir, old, EdgeKind::Contains));
+    graph.add_edge(test_edge(dir, kept, EdgeKind::Contains));
+    save_snapshot(&graph, root, "before").unwrap();
+
+    // Rebuild the graph in a different order so IDs don't line up
+    let mut graph = Graph::new();
+    let mut changed = test_node(NodeKind::Function, "kept", false);
+    changed.loc = Some(12);
+    let kept = graph.add_node(changed);
+    let dir = graph.add_node(test_node(NodeKind::Directory, "src", true));
+    let new = graph.add_node(test_node(NodeKind::Function, "new", false));
+    graph.add_edge(test_edge(dir, kept, EdgeKind::Contains));
+    graph.add_edge(test_edge(dir, new, EdgeKind::Contains));
+    save_snapshot(&graph, root, "after").unwrap();
+
+    assert_eq!(list_snapshots(root).unwrap(), vec!["after".to_string(), "before".to_string()]);
+    assert!(save_snapshot(&graph, root, "../escape").is_err());
+
+    let diff = diff_named_snapshots(root, "before", "after").unwrap();
+    assert_eq!(diff.added_nodes.len(), 1);
+    assert_eq!(diff.added_nodes[0].name, "new");
+    assert_eq!(diff.removed_nodes, vec![old]);
+    assert_eq!(diff.modified_nodes, vec![kept]);
+    assert_eq!(diff.added_edges.len(), 1);
+    assert_eq!(diff.removed_edges.len(), 1);
+
+    let restored = load_snapshot(root, "after").unwrap().to_graph();
+    assert_eq!(restored.node_count(), 3);
+    assert_eq!(restored.edge_count(), 2);
+}
+
+#[test]
+fn test_dot_export() {
+    use crate::export::{to_dot, DotOptions};
+
+    let mut graph = Graph::new();
+    let dir = graph.add_node(test_node(NodeKind::Directory, "src", true));
+    let file = graph.add_node(test_node(NodeKind::File, "src/lib.rs", true));
+    let mut quoted = test_node(NodeKind::Function, "src/say\"hi\"", false);
+    quoted.name = "say\"hi\"".to_string();
+    let func = graph.add_node(quoted);
+    graph.add_edge(test_edge(dir, file, EdgeKind::Contains));
+    let mut ai = test_edge(func, file, EdgeKind::SemanticReference);
+    ai.edge_source = EdgeSource::AI;
+    ai.confidence = 0.75;
+    graph.add_edge(ai);
+
+    let dot = to_dot(&graph, &DotOptions::default());
+    assert!(dot.starts_with("digraph \"canopy\" {"));
+    assert!(dot.contains(&format!("n{} [label=\"src\", shape=folder];", dir.0)));
+    assert!(dot.contains("label=\"say\\\"hi\\\"\", shape=ellipse"));
+    assert!(dot.contains(&format!("n{} -> n{} [style=dotted", dir.0, file.0)));
+    assert!(dot.contains("label=\"references (0.75)\""));
+    assert!(dot.trim_end().ends_with('}'));
+
+    let clustered = to_dot(&graph, &DotOptions { cluster_by_directory: true, ..Default::default() });
+    assert!(clustered.contains("subgraph cluster_0 {"));
+    assert!(clustered.contains("label=\"src\";"));
+    assert!(!clustered.contains("shape=folder"));
+    assert!(!clustered.contains("contains"));
+}
+
+#[test]
+fn test_mermaid_export() {
+    use crate::export::{to_mermaid, MermaidScope};
+
+    let mut graph = Graph::new();
+    let root = graph.add_node(test_node(NodeKind::Directory, "src", true));
+    let file_a = graph.add_node(test_node(NodeKind::File, "src/a.rs", true));
+    let file_b = graph.add_node(test_node(NodeKind::File, "src/b.rs", true));
+    let run = graph.add_node(test_node(NodeKind::Function, "run", false));
+    let stop = graph.add_node(test_node(NodeKind::Function, "stop", false));
+    let helper = graph.add_node(test_node(NodeKind::Function, "helper", false));
+    graph.add_edge(test_edge(root, file_a, EdgeKind::Contains));
+    graph.add_edge(test_edge(root, file_b, EdgeKind::Contains));
+    graph.add_edge(test_edge(file_a, run, EdgeKind::Contains));
+    graph.add_edge(test_edge(file_a, stop, EdgeKind::Contains));
+    graph.add_edge(test_edge(file_b, helper, EdgeKind::Contains));
+    graph.add_edge(test_edge(run, helper, EdgeKind::Calls));
+    graph.add_edge(test_edge(stop, helper, EdgeKind::Calls));
+
+    let full = to_mermaid(&graph, &MermaidScope::new(root));
+    assert!(full.starts_with("flowchart LR\n"));
+    assert!(full.contains(&format!("n{}(\"run\")", run.0)));
+    assert!(full.contains(&format!("n{} -->|\"calls\"| n{}", run.0, helper.0)));
+
+    // Over the cap, functions fold into their files and edges aggregate
+    let capped = to_mermaid(&graph, &MermaidScope { root, max_nodes: 3 });
+    assert!(!capped.contains("\"run\""));
+    assert!(capped.contains(&format!("n{}[[\"src/a.rs\"]]", file_a.0)));
+    assert!(capped.contains(&format!("n{} -->|\"2 edges\"| n{}", file_a.0, file_b.0)));
+}
+
+#[test]
+fn test_graphml_and_json_export() {
+    use crate::export::{to_graphml, to_json};
+
+    let mut graph = Graph::new();
+    let dir = graph.add_node(test_node(NodeKind::Directory, "src", true));
+    let other = graph.add_node(test_node(NodeKind::Directory, "bin", true));
+    let mut generic = test_node(NodeKind::Struct, "src/lib.rs::Pool<T>", false);
+    generic.name = "Pool<T>".to_string();
+    let pool = graph.add_node(generic);
+    graph.add_edge(test_edge(dir, pool, EdgeKind::Contains));
+    graph.add_edge(test_edge(other, pool, EdgeKind::DependsOn));
+
+    let graphml = to_graphml(&graph);
+    assert!(graphml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<graphml"));
+    assert!(graphml.contains(&format!("<node id=\"n{}\">", pool.0)));
+    assert!(graphml.contains("<data key=\"name\">Pool&lt;T&gt;</data>"));
+    assert!(graphml.contains(&format!("source=\"n{}\" target=\"n{}\">", dir.0, pool.0)));
+    assert!(graphml.contains("<data key=\"edge_kind\">DependsOn</data>"));
+    assert!(graphml.trim_end().ends_with("</graphml>"));
+
+    // A filtered graph keeps the ids of what it keeps
+    let scoped = graph.filter_nodes(|n| n.id != other);
+    assert_eq!((scoped.node_count(), scoped.edge_count()), (2, 1));
+    assert_eq!(scoped.node(pool).map(|n| n.name.as_str()), Some("Pool<T>"));
+    let json: serde_json::Value = serde_json::from_str(&to_json(&scoped).unwrap()).unwrap();
+    assert_eq!(json["nodes"][1]["id"], pool.0);
+    assert_eq!(json["edges"].as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn test_streamed_export() {
+    use crate::export::{to_graphml, to_json, StreamFormat};
+
+    let mut graph = Graph::new();
+    let dir = graph.add_node(test_node(NodeKind::Directory, "src", true));
+    let lib = graph.add_node(test_node(NodeKind::File, "src/lib.rs", true));
+    let load = graph.add_node(test_node(NodeKind::Function, "src/lib.rs::load", false));
+    graph.add_edge(test_edge(dir, lib, EdgeKind::Contains));
+    graph.add_edge(test_edge(lib, load, EdgeKind::Contains));
+    let stream = |format: StreamFormat| {
+        let mut out = format.header();
+        for (index, id) in [dir, lib, load].into_iter().enumerate() {
+            out.push_str(&format.node(graph.node(id).unwrap(), index).unwrap());
+        }
+        out.push_str(format.between());
+        for (index, edge) in graph.all_edges().enumerate() {
+            out.push_str(&format.edge(edge, index).unwrap());
+        }
+        out + format.footer()
+    };
+
+    // Written a piece at a time, each says what the whole export does
+    assert_eq!(stream(StreamFormat::Graphml), to_graphml(&graph));
+    let json: serde_json::Value = serde_json::from_str(&stream(StreamFormat::Json)).unwrap();
+    assert_eq!(json, serde_json::from_str::<serde_json::Value>(&to_json(&graph).unwrap()).unwrap());
+    let dot = stream(StreamFormat::Dot);
+    assert!(dot.starts_with("digraph \"canopy\" {") && dot.ends_with("}\n"));
+    assert!(dot.contains(&format!("n{} -> n{}", lib.0, load.0)));
+}
+
+fn ranged_node(kind: NodeKind, name: &str, file: &str, lines: (u32, u32)) -> GraphNode {
+    GraphNode {
+        file_path: PathBuf::from(file).into(),
+        line_start: Some(lines.0),
+        line_end: Some(lines.1),
+        ..test_node(kind, name, false)
+    }
+}
+
+#[test]
+fn test_scip_import() {
+    use crate::interop::{import_references, parse_scip_json, ImportOptions};
+
+    let mut graph = Graph::new();
+    let caller = graph.add_node(ranged_node(NodeKind::Function, "main", "src/main.rs", (1, 5)));
+    let callee = graph.add_node(ranged_node(NodeKind::Function, "helper", "src/lib.rs", (10, 12)));
+    let other = graph.add_node(ranged_node(NodeKind::Function, "helper", "src/util.rs", (1, 3)));
+    let guess = |target, kind, line| {
+        let mut edge = test_edge(caller, target, kind);
+        edge.edge_source = EdgeSource::Heuristic;
+        edge.line = line;
+        edge
+    };
+    // Guesses at the call the index resolves, to its target or another
+    graph.add_edge(guess(callee, EdgeKind::Calls, None));
+    graph.add_edge(guess(other, EdgeKind::TypeReference, Some(3)));
+    // A guess the index doesn't cover, and a kind it doesn't supply
+    graph.add_edge(guess(other, EdgeKind::Calls, Some(4)));
+    graph.add_edge(guess(callee, EdgeKind::RouteHandler, None));
+
+    let index = r#"{"documents": [
+        {"relative_path": "src/lib.rs", "occurrences": [
+            {"range": [9, 7, 13], "symbol": "rust . lib/helper().", "symbol_roles": 1}
+        ]},
+        {"relativePath": "src/main.rs", "occurrences": [
+            {"range": [2, 4, 10], "symbol": "rust . lib/helper()."},
+            {"range": [3, 4, 5], "symbol": "local 0"}
+        ]}
+    ]}"#;
+    let references = parse_scip_json(index).unwrap();
+    assert_eq!(references.len(), 1);
+    assert_eq!(references[0].from.line, 3);
+    assert_eq!(references[0].to.line, 10);
+
+    let stats = import_references(&mut graph, Path::new("/repo"), &references, &ImportOptions::default());
+    assert_eq!(stats.added_edges, 1);
+    assert_eq!(stats.removed_heuristic_edges, 2);
+    assert!(!graph.has_edge_between(caller, callee, EdgeKind::Calls));
+    assert!(!graph.has_edge_between(caller, other, EdgeKind::TypeReference));
+    assert!(graph.has_edge_between(caller, other, EdgeKind::Calls));
+    assert!(graph.has_edge_between(caller, callee, EdgeKind::RouteHandler));
+    let edge = graph.edges_from(caller).find(|e| e.kind == EdgeKind::References).unwrap();
+    assert_eq!(edge.target, callee);
+    assert_eq!(edge.edge_source, EdgeSource::Structural);
+    assert_eq!(edge.confidence, 1.0);
+}
+
+#[test]
+fn test_scip_binary_import() {
+    use crate::interop::import_index_file;
+
+    // The protobuf `scip-*` indexers write, field by field
+    let field = |tag: u8, body: &[u8]| [&[tag << 3 | 2, body.len() as u8][..], body].concat();
+    let symbol = field(2, b"rust . lib/helper().");
+    let definition = [field(1, &[9, 7, 13]), symbol.clone(), vec![3 << 3, 1]].concat();
+    let reference = [field(1, &[2, 4, 10]), symbol].concat();
+    let index = [
+        field(2, &[field(1, b"src/lib.rs"), field(2, &definition)].concat()),
+        field(2, &[field(1, b"src/main.rs"), field(2, &reference)].concat()),
+    ]
+    .concat();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("index.scip");
+    std::fs::write(&path, index).unwrap();
+
+    let mut graph = Graph::new();
+    let caller = graph.add_node(ranged_node(NodeKind::Function, "main", "src/main.rs", (1, 5)));
+    let callee = graph.add_node(ranged_node(NodeKind::Function, "helper", "src/lib.rs", (10, 12)));
+    let stats = import_index_file(&mut graph, Path::new("/repo"), &path).unwrap();
+    assert_eq!(stats.added_edges, 1);
+    assert!(graph.has_edge_between(caller, callee, EdgeKind::References));
+
+    std::fs::write(&path, [0xff, 0xff]).unwrap();
+    assert!(import_index_file(&mut graph, Path::new("/repo"), &path).is_err());
+}
+
+#[test]
+fn test_lsif_parse() {
+    use crate::interop::parse_lsif;
+
+    let dump = r#"
+{"id": 1, "type": "vertex", "label": "document", "uri": "file:///repo/src/lib.rs"}
+{"id": 2, "type": "vertex", "label": "document", "uri": "file:///repo/src/main.rs"}
+{"id": 3, "type": "vertex", "label": "range", "start": {"line": 9, "character": 7}, "end": {"line": 9, "character": 13}}
+{"id": 4, "type": "vertex", "label": "range", "start": {"line": 2, "character": 4}, "end": {"line": 2, "character": 10}}
+{"id": 5, "type": "vertex", "label": "resultSet"}
+{"id": 6, "type": "vertex", "label": "definitionResult"}
+{"id": 7, "type": "edge", "label": "contains", "outV": 1, "inVs": [3]}
+{"id": 8, "type": "edge", "label": "contains", "outV": 2, "inVs": [4]}
+{"id": 9, "type": "edge", "label": "next", "outV": 3, "inV": 5}
+{"id": 10, "type": "edge", "label": "next", "outV": 4, "inV": 5}
+{"id": 11, "type": "edge", "label": "textDocument/definition", "outV": 5, "inV": 6}
+{"id": 12, "type": "edge", "label": "item", "outV": 6, "inVs": [3], "document": 1}
+"#;
+    let references = parse_lsif(dump).unwrap();
+    assert_eq!(references.len(), 1);
+    assert_eq!(references[0].from.path, PathBuf::from("/repo/src/main.rs"));
+    assert_eq!(references[0].from.line, 3);
+    assert_eq!(references[0].to.path, PathBuf::from("/repo/src/lib.rs"));
+    assert_eq!(references[0].to.line, 10);
+}
+
+#[test]
+fn test_graph_stats() {
+    let mut graph = Graph::new();
+    let dir = graph.add_node(test_node(NodeKind::Directory, "src", true));
+    let mut small = test_node(NodeKind::File, "src/small.rs", true);
+    small.language = Some(Language::Rust);
+    small.loc = Some(10);
+    let small = graph.add_node(small);
+    let mut big = test_node(NodeKind::File, "src/big.py", true);
+    big.language = Some(Language::Python);
+    big.loc = Some(200);
+    let big = graph.add_node(big);
+    graph.add_edge(test_edge(dir, small, EdgeKind::Contains));
+    graph.add_edge(test_edge(dir, big, EdgeKind::Contains));
+    let mut ai = test_edge(small, big, EdgeKind::SemanticReference);
+    ai.edge_source = EdgeSource::AI;
+    graph.add_edge(ai);
+    graph.add_edge(test_edge(small, big, EdgeKind::Imports));
+
+    let stats = graph.stats();
+    assert_eq!(stats.node_count, 3);
+    assert_eq!(stats.edge_count, 4);
+    assert_eq!(stats.nodes_by_kind[&NodeKind::File], 2);
+    assert_eq!(stats.edges_by_kind[&EdgeKind::Contains], 2);
+    assert_eq!(stats.edges_by_source[&EdgeSource::Structural], 3);
+    assert_eq!(stats.edges_by_source[&EdgeSource::AI], 1);
+    assert_eq!(stats.languages[&Language::Python], 1);
+    assert_eq!(stats.largest_files[0].id, big);
+    // Containment doesn't count towards fan-in
+    assert_eq!(stats.top_fan_in.len(), 1);
+    assert_eq!(stats.top_fan_in[0].id, big);
+    assert_eq!(stats.top_fan_in[0].count, 2);
+    assert_eq!(stats.top_fan_out.len(), 1);
+    assert_eq!((stats.top_fan_out[0].id, stats.top_fan_out[0].count), (small, 2));
+
+    assert_eq!(graph.stats_with_limit(1).largest_files.len(), 1);
+    assert!(serde_json::to_string(&stats).is_ok());
+}
+
+#[test]
+fn test_interned_paths_and_names() {
+    let a = test_node(NodeKind::Function, "src/interned.rs", false);
+    let b = test_node(NodeKind::Function, "src/interned.rs", false);
+    assert!(std::ptr::eq(a.file_path.as_path(), b.file_path.as_path()));
+    assert!(std::ptr::eq(a.qualified_name.as_str(), b.qualified_name.as_str()));
+    assert_eq!(a.qualified_name, "src/interned.rs");
+    assert_eq!(a.file_path, PathBuf::from("src/interned.rs"));
+
+    // Serialized as plain strings, and re-interned on the way back in
+    let json = serde_json::to_value(&a).unwrap();
+    assert_eq!(json["file_path"], "src/interned.rs");
+    assert_eq!(json["qualified_name"], "src/interned.rs");
+    let restored: GraphNode = serde_json::from_value(json).unwrap();
+    assert!(std::ptr::eq(restored.file_path.as_path(), a.file_path.as_path()));
+}
+
+#[test]
+fn test_node_kind_aliases() {
+    let kinds: Vec<NodeKind> = serde_json::from_str(r#"["Trait", "macro", "Test", "union", "Property", "Var"]"#).unwrap();
+    assert_eq!(
+        kinds,
+        vec![NodeKind::Trait, NodeKind::Macro, NodeKind::TestCase, NodeKind::Union, NodeKind::Field, NodeKind::Variable]
+    );
+    assert_eq!(serde_json::to_string(&NodeKind::TestCase).unwrap(), "\"TestCase\"");
+}
+
+#[test]
+fn test_view_state() {
+    let mut graph = Graph::new();
+    let root = graph.add_node(test_node(NodeKind::Directory, "root", true));
+    let dir_a = graph.add_node(test_node(NodeKind::Directory, "a", true));
+    let dir_b = graph.add_node(test_node(NodeKind::Directory, "b", true));
+    let file_a = graph.add_node(test_node(NodeKind::File, "a/x.rs", true));
+    let file_b = graph.add_node(test_node(NodeKind::File, "b/y.rs", true));
+    let func_a = graph.add_node(test_node(NodeKind::Function, "f", false));
+    let func_b = graph.add_node(test_node(NodeKind::Function, "g", false));
+    for (parent, child) in [(root, dir_a), (root, dir_b), (dir_a, file_a), (dir_b, file_b), (file_a, func_a), (file_b, func_b)] {
+        graph.add_edge(test_edge(parent, child, EdgeKind::Contains));
+    }
+    graph.add_edge(test_edge(func_a, func_b, EdgeKind::Calls));
+
+    let mut view = ViewState::new();
+    assert_eq!(view.visible_nodes(&graph), [root].into_iter().collect());
+    assert!(view.aggregated_edges(&graph).is_empty());
+
+    let delta = view.expand(&graph, root);
+    assert_eq!(delta.expanded, vec![root]);
+    assert_eq!(delta.shown.len(), 2);
+    let edges = view.aggregated_edges(&graph);
+    assert_eq!(edges.len(), 1);
+    assert_eq!((edges[0].source, edges[0].target), (dir_a, dir_b));
+
+    let mut aggregator = view.aggregator(&graph);
+    let delta = view.expand(&graph, dir_a);
+    aggregator.apply_visibility(&graph, &delta);
+    assert_eq!(normalized(aggregator.aggregated_edges()), normalized(view.aggregated_edges(&graph)));
+    assert_eq!(view.collapsed_nodes(&graph), [file_a, dir_b].into_iter().collect());
+
+    // Collapsing keeps descendants' expanded state
+    view.expand(&graph, file_a);
+    let delta = view.collapse(&graph, root);
+    assert_eq!(delta.hidden.len(), 4);
+    assert!(view.is_expanded(dir_a));
+    view.expand(&graph, root);
+    assert!(view.is_visible(&graph, func_a));
+    assert!(!view.is_visible(&graph, func_b));
+
+    let mut revealed = ViewState::new();
+    revealed.reveal(&graph, func_b);
+    assert!(revealed.is_visible(&graph, func_b));
+    assert_eq!(ViewState::fully_expanded(&graph).visible_nodes(&graph).len(), graph.node_count());
+
+    // Expanded to a depth, the nodes that far down show, and leaves are
+    // never counted as expanded
+    let shallow = ViewState::expanded_to_depth(&graph, 2);
+    assert_eq!(shallow.visible_nodes(&graph), [root, dir_a, dir_b, file_a, file_b].into_iter().collect());
+    assert_eq!(shallow.collapsed_nodes(&graph), [file_a, file_b].into_iter().collect());
+    assert_eq!(ViewState::expanded_to_depth(&graph, 0), ViewState::new());
+    assert_eq!(ViewState::expanded_to_depth(&graph, 9), ViewState::fully_expanded(&graph));
+}
+
+#[test]
+fn test_aggregation_cache() {
+    use crate::view::AggregationCache;
+
+    let mut graph = Graph::new();
+    let dir_a = graph.add_node(test_node(NodeKind::Directory, "a", true));
+    let dir_b = graph.add_node(test_node(NodeKind::Directory, "b", true));
+    let file_a = graph.add_node(test_node(NodeKind::File, "a/x.rs", false));
+    let file_b = graph.add_node(test_node(NodeKind::File, "b/y.rs", false));
+    graph.add_edge(test_edge(dir_a, file_a, EdgeKind::Contains));
+    graph.add_edge(test_edge(dir_b, file_b, EdgeKind::Contains));
+    graph.add_edge(test_edge(file_a, file_b, EdgeKind::Imports));
+
+    let mut cache = AggregationCache::new(2);
+    let collapsed = ViewState::new();
+    let mut expanded = ViewState::new();
+    expanded.expand(&graph, dir_a);
+
+    let first = cache.get_or_compute(&graph, 1, &collapsed);
+    let again = cache.get_or_compute(&graph, 1, &collapsed);
+    assert!(std::sync::Arc::ptr_eq(&first, &again));
+    assert_eq!(cache.hit_stats(), (1, 1));
+
+    let edges = cache.get_or_compute(&graph, 1, &expanded);
+    assert_eq!((edges[0].source, edges[0].target), (file_a, dir_b));
+
+    // A new graph sequence misses and evicts the least recently used view
+    cache.get(1, &collapsed);
+    cache.get_or_compute(&graph, 2, &collapsed);
+    assert_eq!(cache.len(), 2);
+    assert!(cache.get(1, &expanded).is_none());
+    assert!(cache.get(1, &collapsed).is_some());
+
+    cache.evict_before(2);
+    assert_eq!(cache.len(), 1);
+}
+
+/// Graph with one file node per (path, symbols) entry under `root`.
+fn tree_graph(root: &Path, files: &[(&str, &[&str])]) -> Graph {
+    let mut graph = Graph::new();
+    for (file, symbols) in files {
+        let path = root.join(file);
+        let mut file_node = test_node(NodeKind::File, file, true);
+        file_node.file_path = path.as_path().into();
+        let file_id = graph.add_node(file_node);
+        for symbol in *symbols {
+            let mut node = test_node(NodeKind::Function, symbol, false);
+            node.qualified_name = format!("{}::{}", path.display(), symbol).into();
+            node.file_path = path.as_path().into();
+            let id = graph.add_node(node);
+            graph.add_edge(test_edge(file_id, id, EdgeKind::Contains));
+        }
+    }
+    graph
+}
+
+#[test]
+fn test_diff_graphs_across_roots() {
+    use crate::diff::diff_graphs;
+
+    let (root_a, root_b) = (Path::new("/tmp/a"), Path::new("/tmp/b"));
+    let before = tree_graph(root_a, &[("lib.rs", &["parse", "render"]), ("old.rs", &["legacy"])]);
+    let after = tree_graph(root_b, &[("lib.rs", &["parse"]), ("view.rs", &["render", "layout"])]);
+
+    let result = diff_graphs(&before, root_a, &after, root_b);
+    let summary = &result.summary;
+    assert_eq!(summary.files_added, vec![PathBuf::from("view.rs")]);
+    assert_eq!(summary.files_removed, vec![PathBuf::from("old.rs")]);
+    assert_eq!(summary.added.iter().map(|s| s.name.as_str()).collect::<Vec<_>>(), vec!["layout"]);
+    assert_eq!(summary.added[0].qualified_name, "view.rs::layout");
+    assert_eq!(summary.removed.iter().map(|s| s.name.as_str()).collect::<Vec<_>>(), vec!["legacy"]);
+    assert_eq!(summary.moved.len(), 1);
+    assert_eq!((summary.moved[0].from.as_path(), summary.moved[0].to.as_path()), (Path::new("lib.rs"), Path::new("view.rs")));
+    assert!(summary.to_string().contains("> Function render (lib.rs -> view.rs)"));
+
+    // The unchanged file and symbol match across roots
+    assert!(!result.diff.added_nodes.iter().any(|n| n.name == "parse"));
+    assert!(diff_graphs(&before, root_a, &before, root_a).summary.is_empty());
+}
+
+#[test]
+fn test_diff_git_refs() {
+    use crate::diff::diff_git_refs;
+    use std::process::Command;
+
+    let repo = tempfile::tempdir().unwrap();
+    let git = |args: &[&str]| {
+        let status = Command::new("git")
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .arg("-C")
+            .arg(repo.path())
+            .args(args)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {:?} failed", args);
+    };
+    git(&["init", "-q"]);
+    std::fs::write(repo.path().join("a.txt"), "one").unwrap();
+    git(&["add", "."]);
+    git(&["commit", "-qm", "first"]);
+    git(&["tag", "v1"]);
+    std::fs::write(repo.path().join("b.txt"), "two").unwrap();
+    git(&["add", "."]);
+    git(&["commit", "-qm", "second"]);
+
+    let index = |root: &Path| -> anyhow::Result<Graph> {
+        let mut names: Vec<String> = std::fs::read_dir(root)?
+            .filter_map(|e| e.ok())
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .filter(|n| n.ends_with(".txt"))
+            .collect();
+        names.sort();
+        let files: Vec<(&str, &[&str])> = names.iter().map(|n| (n.as_str(), &[][..])).collect();
+        Ok(tree_graph(root, &files))
+    };
+
+    let result = diff_git_refs(repo.path(), "v1", "HEAD", index).unwrap();
+    assert_eq!(result.summary.files_added, vec![PathBuf::from("b.txt")]);
+    assert!(result.summary.files_removed.is_empty());
+    // Worktrees are cleaned up afterwards
+    let worktrees = crate::cache::cache_dir(repo.path()).join("worktrees");
+    assert!(!worktrees.exists() || std::fs::read_dir(&worktrees).unwrap().next().is_none());
+}
+
+#[test]
+fn test_freeze_and_thaw() {
+    let mut graph = Graph::new();
+    let dir = graph.add_node(test_node(NodeKind::Directory, "src", true));
+    let gone = graph.add_node(test_node(NodeKind::File, "gone.rs", true));
+    let file = graph.add_node(test_node(NodeKind::File, "lib.rs", true));
+    let func = graph.add_node(test_node(NodeKind::Function, "run", false));
+    let stale = graph.add_edge(test_edge(dir, gone, EdgeKind::Contains));
+    graph.add_edge(test_edge(dir, file, EdgeKind::Contains));
+    let contains = graph.add_edge(test_edge(file, func, EdgeKind::Contains));
+    let calls = graph.add_edge(test_edge(func, dir, EdgeKind::Calls));
+    graph.remove_edge(stale);
+    graph.remove_node(gone);
+
+    let frozen = graph.freeze();
+    assert_eq!(frozen.node_count(), 3);
+    assert_eq!(frozen.edge_count(), 3);
+    assert!(frozen.node(gone).is_none());
+    assert_eq!(frozen.node(func).unwrap().name, "run");
+    assert_eq!(frozen.edge(calls).unwrap().target, dir);
+    assert_eq!(frozen.edges_from(dir).count(), 1);
+    assert_eq!(frozen.edges_to(dir).map(|e| e.id).collect::<Vec<_>>(), vec![calls]);
+    assert_eq!(frozen.find_edge(file, func, EdgeKind::Contains), Some(contains));
+    assert_eq!(frozen.ancestors(func), [file, dir].into_iter().collect());
+
+    let mut graph = frozen.thaw();
+    assert_eq!(graph.node_count(), 3);
+    assert_eq!(graph.edge_count(), 3);
+    assert!(graph.node(gone).is_none());
+    assert_eq!(graph.node(func).unwrap().id, func);
+    assert_eq!(graph.edge(calls).unwrap().source, func);
+    assert!(graph.has_edge_between(file, func, EdgeKind::Contains));
+
+    // The thawed graph stays mutable
+    let added = graph.add_node(test_node(NodeKind::Function, "new", false));
+    assert_eq!(graph.node(added).unwrap().name, "new");
+    assert_eq!(graph.node_count(), 4);
+}
+
+#[test]
+fn test_fuzzy_search() {
+    let node = |name: &str, qualified: &str, kind, language| GraphNode {
+        id: NodeId(0),
+        kind,
+        name: name.to_string(),
+        qualified_name: qualified.into(),
+        file_path: PathBuf::from("src/lib.rs").into(),
+        line_start: Some(1),
+        line_end: Some(2),
+        language: Some(language),
+        is_container: false,
+        child_count: 0,
+        loc: Some(2),
+        metadata: NodeMetadata::default(),
+    };
+    let mut graph = Graph::new();
+    let user = graph.add_node(node("User", "models::User", NodeKind::Struct, Language::Rust));
+    let get_user = graph.add_node(node("get_user", "api::get_user", NodeKind::Function, Language::Rust));
+    let users = graph.add_node(node("users", "db::users", NodeKind::Function, Language::Rust));
+    let find = graph.add_node(node("findUserByEmail", "auth.findUserByEmail", NodeKind::Function, Language::TypeScript));
+    let unrelated = graph.add_node(node("parse", "config::parse", NodeKind::Function, Language::Rust));
+
+    // Exact before prefix before words before anywhere
+    let ids: Vec<NodeId> = graph.search("user", &SearchFilter::default()).iter().map(|m| m.id).collect();
+    assert_eq!(ids, [user, users, get_user, find]);
+
+    // Letters in order match, word starts first
+    let matches = graph.search("fube", &SearchFilter::default());
+    assert_eq!(matches[0].id, find);
+    assert!(!matches.iter().any(|m| m.id == unrelated));
+
+    // Filters and the limit apply
+    let filter = SearchFilter { kind: Some(NodeKind::Function), language: Some(Language::Rust), limit: 1 };
+    let ids: Vec<NodeId> = graph.search("USER", &filter).iter().map(|m| m.id).collect();
+    assert_eq!(ids, [users]);
+    assert!(graph.search("  ", &SearchFilter::default()).is_empty());
+
+    // Qualified names match behind names
+    let matches = graph.search("config::", &SearchFilter::default());
+    assert_eq!(matches.iter().map(|m| m.id).collect::<Vec<_>>(), [unrelated]);
+}
+
+#[test]
+fn test_shortest_paths() {
+    let mut graph = Graph::new();
+    let [http, auth, cache, store, db, log] =
+        ["http", "auth", "cache", "store", "db", "log"].map(|name| graph.add_node(test_node(NodeKind::Function, name, false)));
+    let via_auth = [graph.add_edge(test_edge(http, auth, EdgeKind::Calls)), graph.add_edge(test_edge(auth, store, EdgeKind::Calls))];
+    let via_cache = [graph.add_edge(test_edge(http, cache, EdgeKind::Calls)), graph.add_edge(test_edge(cache, store, EdgeKind::Imports))];
+    let last = graph.add_edge(test_edge(store, db, EdgeKind::Calls));
+    graph.add_edge(test_edge(log, db, EdgeKind::Calls));
+
+    // Every path of the shortest length, up to the limit
+    let paths = graph.shortest_paths(http, db, &[], true, 10);
+    assert_eq!(paths.len(), 2);
+    assert!(paths.iter().all(|p| p.nodes.len() == 4 && p.nodes[0] == http && p.nodes[3] == db));
+    let edges: Vec<Vec<EdgeId>> = paths.iter().map(|p| p.edges.clone()).collect();
+    assert!(edges.contains(&vec![via_auth[0], via_auth[1], last]));
+    assert!(edges.contains(&vec![via_cache[0], via_cache[1], last]));
+    assert_eq!(graph.shortest_paths(http, db, &[], true, 1).len(), 1);
+
+    // Only through the kinds asked for
+    let calls = graph.shortest_paths(http, db, &[EdgeKind::Calls], true, 10);
+    assert_eq!(calls.iter().map(|p| p.nodes.clone()).collect::<Vec<_>>(), [vec![http, auth, store, db]]);
+
+    // Against the direction of edges only when undirected
+    assert!(graph.shortest_paths(http, log, &[], true, 10).is_empty());
+    let undirected = graph.shortest_paths(http, log, &[], false, 10);
+    assert_eq!(undirected.len(), 2);
+    assert!(undirected.iter().all(|p| p.nodes.len() == 5 && p.nodes[3..] == [db, log]));
+    assert_eq!(graph.shortest_paths(db, db, &[], true, 10)[0].nodes, [db]);
+}
+
+#[test]
+fn test_diff_journal() {
+    use crate::diff::DiffJournal;
+
+    let mut journal = DiffJournal::with_capacity(3);
+    assert_eq!(journal.since(0).unwrap().len(), 0);
+    for sequence in 1..=5 {
+        journal.record(GraphDiff::new(sequence));
+    }
+    assert_eq!(journal.sequence(), 5);
+    let sequences = |since| journal.since(since).map(|diffs| diffs.iter().map(|d| d.sequence).collect::<Vec<_>>());
+    assert_eq!(sequences(2), Some(vec![3, 4, 5]));
+    assert_eq!(sequences(4), Some(vec![5]));
+    assert_eq!(sequences(5), Some(vec![]));
+
+    // Diffs 1 and 2 are gone, and 9 is from another run
+    assert_eq!(sequences(1), None);
+    assert_eq!(sequences(9), None);
+}
@@ -0,0 +1,57 @@
+//! Graph integrity checks for catching extractor bugs early
+
+use crate::graph::Graph;
+use crate::model::{NodeId, EdgeId, NodeKind, EdgeKind};
+use serde::{Deserialize, Serialize};
+
+/// Problems found by [`Graph::validate`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ValidationReport {
+    /// Edges whose source or target does not resolve to a node in the graph.
+    pub dangling_edges: Vec<EdgeId>,
+    /// Nodes with a zero line number or a start line after the end line.
+    pub invalid_line_ranges: Vec<NodeId>,
+    /// Non-root nodes that have no Contains parent.
+    pub orphaned_nodes: Vec<NodeId>,
+}
+
+impl ValidationReport {
+    /// Check if no problems were found.
+    pub fn is_valid(&self) -> bool {
+        self.dangling_edges.is_empty()
+            && self.invalid_line_ranges.is_empty()
+            && self.orphaned_nodes.is_empty()
+    }
+}
+
+impl Graph {
+    /// Check the graph for dangling edges, impossible line ranges and orphans.
+    /// Directories and workspace roots are allowed to have no parent.
+    pub fn validate(&self) -> ValidationReport {
+        let mut report = ValidationReport::default();
+
+        for edge in self.all_edges() {
+            if self.node(edge.source).is_none() || self.node(edge.target).is_none() {
+                report.dangling_edges.push(edge.id);
+            }
+        }
+
+        for node in self.all_nodes() {
+            let bad_range = match (node.line_start, node.line_end) {
+                (Some(start), Some(end)) => start == 0 || start > end,
+                (Some(line), None) | (None, Some(line)) => line == 0,
+                (None, None) => false,
+            };
+            if bad_range {
+                report.invalid_line_ranges.push(node.id);
+            }
+
+            let is_root_kind = matches!(node.kind, NodeKind::Directory | NodeKind::WorkspaceRoot);
+            if !is_root_kind && !self.edges_to(node.id).any(|e| e.kind == EdgeKind::Contains) {
+                report.orphaned_nodes.push(node.id);
+            }
+        }
+
+        report
+    }
+}
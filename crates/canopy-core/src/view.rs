@@ -0,0 +1,314 @@
+//! Expand/collapse state for a hierarchical view of the graph
+
+use crate::aggregation::{aggregate_edges, IncrementalAggregator, VisibilityDelta};
+use crate::graph::Graph;
+use crate::model::{AggregatedEdge, EdgeKind, NodeId};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+/// Which containers are expanded in a view.
+///
+/// Nodes without a Contains parent are always visible. Any other node is
+/// visible when its parent is visible and expanded. Visible nodes that have
+/// Contains children but are not expanded are collapsed, and edges from
+/// hidden nodes are aggregated onto them.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ViewState {
+    expanded: HashSet<NodeId>,
+}
+
+impl ViewState {
+    /// A view with every container collapsed.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A view with every container expanded.
+    pub fn fully_expanded(graph: &Graph) -> Self {
+        ViewState {
+            expanded: graph
+                .all_edges()
+                .filter(|e| e.kind == EdgeKind::Contains)
+                .map(|e| e.source)
+                .collect(),
+        }
+    }
+
+    /// A view with the containers less than `depth` levels below a root
+    /// expanded, so that nodes down to `depth` levels are visible.
+    pub fn expanded_to_depth(graph: &Graph, depth: usize) -> Self {
+        let mut expanded = HashSet::new();
+        let mut level: Vec<NodeId> = graph
+            .all_nodes()
+            .map(|n| n.id)
+            .filter(|&id| parent_of(graph, id).is_none())
+            .collect();
+        for _ in 0..depth {
+            level = level
+                .into_iter()
+                .filter(|&id| expanded.insert(id))
+                .flat_map(|id| children(graph, id))
+                .collect();
+        }
+        expanded.retain(|&id| children(graph, id).next().is_some());
+        ViewState { expanded }
+    }
+
+    pub fn is_expanded(&self, node: NodeId) -> bool {
+        self.expanded.contains(&node)
+    }
+
+    /// Expanded containers, including ones currently hidden by a collapsed
+    /// ancestor.
+    pub fn expanded(&self) -> &HashSet<NodeId> {
+        &self.expanded
+    }
+
+    /// Expand a container, returning the resulting visibility change.
+    pub fn expand(&mut self, graph: &Graph, node: NodeId) -> VisibilityDelta {
+        if !self.expanded.insert(node) {
+            return VisibilityDelta::default();
+        }
+        let mut delta = VisibilityDelta { expanded: vec![node], ..Default::default() };
+        if self.is_visible(graph, node) {
+            delta.shown = self.visible_descendants(graph, node);
+            delta.collapsed = delta.shown.iter().copied().filter(|&id| self.is_collapsed(graph, id)).collect();
+        }
+        delta
+    }
+
+    /// Collapse a container, returning the resulting visibility change.
+    /// Expanded descendants stay expanded and reappear on the next expand.
+    pub fn collapse(&mut self, graph: &Graph, node: NodeId) -> VisibilityDelta {
+        if !self.expanded.contains(&node) {
+            return VisibilityDelta::default();
+        }
+        let hidden = if self.is_visible(graph, node) {
+            self.visible_descendants(graph, node)
+        } else {
+            Vec::new()
+        };
+        self.expanded.remove(&node);
+        VisibilityDelta { hidden, collapsed: vec![node], ..Default::default() }
+    }
+
+    /// Expand or collapse a container depending on its current state.
+    pub fn toggle(&mut self, graph: &Graph, node: NodeId) -> VisibilityDelta {
+        if self.is_expanded(node) {
+            self.collapse(graph, node)
+        } else {
+            self.expand(graph, node)
+        }
+    }
+
+    /// Expand every ancestor of `node` so that it becomes visible.
+    pub fn reveal(&mut self, graph: &Graph, node: NodeId) {
+        self.expanded.extend(graph.ancestors(node));
+    }
+
+    /// Collapse everything.
+    pub fn collapse_all(&mut self) {
+        self.expanded.clear();
+    }
+
+    /// Forget expanded nodes that no longer exist in the graph.
+    pub fn retain_existing(&mut self, graph: &Graph) {
+        self.expanded.retain(|&id| graph.node(id).is_some());
+    }
+
+    /// Order-independent hash of the expanded set, for cache keys.
+    pub fn fingerprint(&self) -> u64 {
+        let mut ids: Vec<u64> = self.expanded.iter().map(|id| id.0).collect();
+        ids.sort_unstable();
+        let mut hasher = DefaultHasher::new();
+        ids.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Whether a node is shown in this view.
+    pub fn is_visible(&self, graph: &Graph, node: NodeId) -> bool {
+        let mut current = node;
+        let mut seen = HashSet::new();
+        while let Some(parent) = parent_of(graph, current) {
+            if !self.expanded.contains(&parent) || !seen.insert(parent) {
+                return false;
+            }
+            current = parent;
+        }
+        graph.node(node).is_some()
+    }
+
+    /// Visible nodes derived from the Contains hierarchy.
+    pub fn visible_nodes(&self, graph: &Graph) -> HashSet<NodeId> {
+        let mut visible = HashSet::new();
+        let mut stack: Vec<NodeId> = graph
+            .all_nodes()
+            .map(|n| n.id)
+            .filter(|&id| parent_of(graph, id).is_none())
+            .collect();
+        while let Some(id) = stack.pop() {
+            if !visible.insert(id) {
+                continue;
+            }
+            if self.expanded.contains(&id) {
+                stack.extend(children(graph, id));
+            }
+        }
+        visible
+    }
+
+    /// Visible containers that are not expanded.
+    pub fn collapsed_nodes(&self, graph: &Graph) -> HashSet<NodeId> {
+        self.visible_nodes(graph)
+            .into_iter()
+            .filter(|&id| self.is_collapsed(graph, id))
+            .collect()
+    }
+
+    /// Edges between visible nodes, with hidden endpoints lifted to their
+    /// nearest visible ancestor.
+    pub fn aggregated_edges(&self, graph: &Graph) -> Vec<AggregatedEdge> {
+        let visible = self.visible_nodes(graph);
+        let collapsed = visible.iter().copied().filter(|&id| self.is_collapsed(graph, id)).collect();
+        aggregate_edges(graph, &visible, &collapsed)
+    }
+
+    /// An incremental aggregator seeded with this view. Feed it the deltas
+    /// returned by [`expand`](Self::expand) and [`collapse`](Self::collapse).
+    pub fn aggregator(&self, graph: &Graph) -> IncrementalAggregator {
+        let visible = self.visible_nodes(graph);
+        let collapsed = visible.iter().copied().filter(|&id| self.is_collapsed(graph, id)).collect();
+        IncrementalAggregator::new(graph, visible, collapsed)
+    }
+
+    fn is_collapsed(&self, graph: &Graph, node: NodeId) -> bool {
+        !self.expanded.contains(&node) && children(graph, node).next().is_some()
+    }
+
+    /// Descendants of a visible, expanded node that are visible.
+    fn visible_descendants(&self, graph: &Graph, node: NodeId) -> Vec<NodeId> {
+        let mut found = Vec::new();
+        let mut seen = HashSet::new();
+        let mut stack: Vec<NodeId> = children(graph, node).collect();
+        while let Some(id) = stack.pop() {
+            if !seen.insert(id) {
+                continue;
+            }
+            found.push(id);
+            if self.expanded.contains(&id) {
+                stack.extend(children(graph, id));
+            }
+        }
+        found
+    }
+}
+
+/// Default number of views kept by [`AggregationCache`].
+pub const DEFAULT_AGGREGATION_CACHE_SIZE: usize = 32;
+
+/// A cached aggregation, the tick it was last used at and the view it is
+/// for.
+type CacheEntry = (u64, ViewState, Arc<Vec<AggregatedEdge>>);
+
+/// Small LRU of aggregated edges keyed by (graph sequence, view fingerprint).
+///
+/// The sequence must change whenever the graph does (e.g. the diff sequence),
+/// so entries for older graphs are never returned and age out naturally.
+/// Each entry keeps its view too, so views whose fingerprints collide never
+/// get each other's edges.
+#[derive(Debug)]
+pub struct AggregationCache {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<(u64, u64), CacheEntry>,
+    hits: u64,
+    misses: u64,
+}
+
+impl Default for AggregationCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_AGGREGATION_CACHE_SIZE)
+    }
+}
+
+impl AggregationCache {
+    pub fn new(capacity: usize) -> Self {
+        AggregationCache {
+            capacity: capacity.max(1),
+            tick: 0,
+            entries: HashMap::new(),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Cached edges for a view of the graph at `sequence`, if present.
+    pub fn get(&mut self, sequence: u64, view: &ViewState) -> Option<Arc<Vec<AggregatedEdge>>> {
+        self.tick += 1;
+        match self.entries.get_mut(&(sequence, view.fingerprint())) {
+            Some((last_used, cached, edges)) if cached == view => {
+                *last_used = self.tick;
+                self.hits += 1;
+                Some(Arc::clone(edges))
+            }
+            _ => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Store edges for a view, evicting the least recently used entry if full.
+    pub fn insert(&mut self, sequence: u64, view: &ViewState, edges: Vec<AggregatedEdge>) -> Arc<Vec<AggregatedEdge>> {
+        self.tick += 1;
+        let key = (sequence, view.fingerprint());
+        if !self.entries.contains_key(&key) && self.entries.len() >= self.capacity
+            && let Some(oldest) = self.entries.iter().min_by_key(|(_, (used, _, _))| *used).map(|(k, _)| *k) {
+            self.entries.remove(&oldest);
+        }
+        let edges = Arc::new(edges);
+        self.entries.insert(key, (self.tick, view.clone(), Arc::clone(&edges)));
+        edges
+    }
+
+    /// Cached edges for a view, aggregating and caching them on a miss.
+    pub fn get_or_compute(&mut self, graph: &Graph, sequence: u64, view: &ViewState) -> Arc<Vec<AggregatedEdge>> {
+        match self.get(sequence, view) {
+            Some(edges) => edges,
+            None => self.insert(sequence, view, view.aggregated_edges(graph)),
+        }
+    }
+
+    /// Drop entries computed for graphs older than `sequence`.
+    pub fn evict_before(&mut self, sequence: u64) {
+        self.entries.retain(|(seq, _), _| *seq >= sequence);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// (hits, misses) since creation.
+    pub fn hit_stats(&self) -> (u64, u64) {
+        (self.hits, self.misses)
+    }
+}
+
+fn parent_of(graph: &Graph, node: NodeId) -> Option<NodeId> {
+    graph.edges_to(node).find(|e| e.kind == EdgeKind::Contains).map(|e| e.source)
+}
+
+fn children(graph: &Graph, node: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+    graph.edges_from(node).filter(|e| e.kind == EdgeKind::Contains).map(|e| e.target)
+}
@@ -1,14 +1,26 @@
 //! Workspace/monorepo detection
 
-use std::path::Path;
+use crate::graph::Graph;
+use crate::model::*;
+use anyhow::{Context, Result};
+use globset::Glob;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
 
 /// Detect if this is a workspace (Cargo, npm, etc.)
 pub fn detect_workspace(root: &Path) -> Option<WorkspaceType> {
     if root.join("Cargo.toml").exists() {
         Some(WorkspaceType::Cargo)
+    } else if root.join("pnpm-workspace.yaml").exists() {
+        Some(WorkspaceType::Pnpm)
     } else if root.join("package.json").exists() {
-        Some(WorkspaceType::Npm)
-    } else if root.join("go.mod").exists() {
+        if root.join("yarn.lock").exists() {
+            Some(WorkspaceType::Yarn)
+        } else {
+            Some(WorkspaceType::Npm)
+        }
+    } else if root.join("go.work").exists() || root.join("go.mod").exists() {
         Some(WorkspaceType::GoModules)
     } else {
         None
@@ -19,7 +31,399 @@ pub fn detect_workspace(root: &Path) -> Option<WorkspaceType> {
 pub enum WorkspaceType {
     Cargo,
     Npm,
+    Pnpm,
+    Yarn,
     GoModules,
     Maven,  // pom.xml
     Gradle, // build.gradle
 }
+
+/// A package (crate, npm package, Go module) declared in a workspace.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkspacePackage {
+    pub name: String,
+    /// Directory containing the manifest.
+    pub path: PathBuf,
+    pub manifest: PathBuf,
+    /// Names of all declared dependencies, internal or external.
+    pub dependencies: Vec<String>,
+}
+
+/// The packages that make up a workspace.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkspaceInfo {
+    pub kind: WorkspaceType,
+    pub root: PathBuf,
+    pub packages: Vec<WorkspacePackage>,
+}
+
+/// Detect the workspace at `root` and read its package manifests.
+/// Returns `None` if no supported workspace manifest is found.
+pub fn discover_workspace(root: &Path) -> Result<Option<WorkspaceInfo>> {
+    let Some(kind) = detect_workspace(root) else {
+        return Ok(None);
+    };
+
+    let packages = match kind {
+        WorkspaceType::Cargo => cargo_packages(root)?,
+        WorkspaceType::Npm | WorkspaceType::Pnpm | WorkspaceType::Yarn => npm_packages(root, kind)?,
+        WorkspaceType::GoModules => go_packages(root)?,
+        WorkspaceType::Maven | WorkspaceType::Gradle => Vec::new(),
+    };
+
+    Ok(Some(WorkspaceInfo {
+        kind,
+        root: root.to_path_buf(),
+        packages,
+    }))
+}
+
+/// Add WorkspaceRoot and Package nodes for a workspace.
+///
+/// The WorkspaceRoot is contained by `parent` (typically the root directory
+/// node) and contains one Package node per package. Dependencies between
+/// packages of the same workspace become Imports edges.
+pub fn add_workspace_nodes(graph: &mut Graph, info: &WorkspaceInfo, parent: Option<NodeId>) -> NodeId {
+    let root_name = info
+        .root
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("workspace")
+        .to_string();
+    let root_id = graph.add_node(GraphNode {
+        id: NodeId(0), // Will be set by graph
+        kind: NodeKind::WorkspaceRoot,
+        name: root_name.clone(),
+        qualified_name: format!("workspace::{}", root_name).into(),
+        file_path: info.root.as_path().into(),
+        line_start: None,
+        line_end: None,
+        language: None,
+        is_container: true,
+        child_count: info.packages.len() as u32,
+        loc: None,
+        metadata: NodeMetadata::with_extra([("workspace_type", format!("{:?}", info.kind))]),
+    });
+    if let Some(parent) = parent {
+        graph.add_edge(contains_edge(parent, root_id));
+    }
+
+    let mut package_ids = HashMap::new();
+    for package in &info.packages {
+        let package_id = graph.add_node(GraphNode {
+            id: NodeId(0), // Will be set by graph
+            kind: NodeKind::Package,
+            name: package.name.clone(),
+            qualified_name: format!("package::{}", package.name).into(),
+            file_path: package.path.as_path().into(),
+            line_start: None,
+            line_end: None,
+            language: None,
+            is_container: false,
+            child_count: 0,
+            loc: None,
+            metadata: NodeMetadata::with_extra([("manifest", package.manifest.display().to_string())]),
+        });
+        graph.add_edge(contains_edge(root_id, package_id));
+        package_ids.insert(package.name.as_str(), package_id);
+    }
+
+    for package in &info.packages {
+        let source = package_ids[package.name.as_str()];
+        for dependency in &package.dependencies {
+            if let Some(&target) = package_ids.get(dependency.as_str())
+                && target != source {
+                graph.add_edge(GraphEdge {
+                    id: EdgeId(0), // Will be set by graph
+                    source,
+                    target,
+                    kind: EdgeKind::Imports,
+                    edge_source: EdgeSource::Structural,
+                    confidence: 1.0,
+                    label: Some(format!("depends on {}", dependency)),
+                    file_path: Some(package.manifest.as_path().into()),
+                    line: None,
+                });
+            }
+        }
+    }
+
+    root_id
+}
+
+fn contains_edge(source: NodeId, target: NodeId) -> GraphEdge {
+    GraphEdge {
+        id: EdgeId(0), // Will be set by graph
+        source,
+        target,
+        kind: EdgeKind::Contains,
+        edge_source: EdgeSource::Structural,
+        confidence: 1.0,
+        label: None,
+        file_path: None,
+        line: None,
+    }
+}
+
+fn cargo_packages(root: &Path) -> Result<Vec<WorkspacePackage>> {
+    let manifest = read_toml(&root.join("Cargo.toml"))?;
+    let mut packages = Vec::new();
+
+    // A root manifest can be both a package and a workspace
+    if manifest.get("package").is_some() {
+        packages.extend(cargo_package(root)?);
+    }
+
+    let members: Vec<String> = manifest
+        .get("workspace")
+        .and_then(|w| w.get("members"))
+        .and_then(|m| m.as_array())
+        .map(|m| m.iter().filter_map(|v| v.as_str().map(String::from)).collect())
+        .unwrap_or_default();
+    for dir in expand_members(root, &members)? {
+        packages.extend(cargo_package(&dir)?);
+    }
+
+    Ok(packages)
+}
+
+fn cargo_package(dir: &Path) -> Result<Option<WorkspacePackage>> {
+    let manifest_path = dir.join("Cargo.toml");
+    if !manifest_path.exists() {
+        return Ok(None);
+    }
+    let manifest = read_toml(&manifest_path)?;
+    let Some(name) = manifest
+        .get("package")
+        .and_then(|p| p.get("name"))
+        .and_then(|n| n.as_str())
+    else {
+        return Ok(None);
+    };
+
+    let mut dependencies = Vec::new();
+    for table in ["dependencies", "dev-dependencies", "build-dependencies"] {
+        if let Some(deps) = manifest.get(table).and_then(|d| d.as_table()) {
+            for (key, value) in deps {
+                // `foo = { package = "real-name" }` renames a dependency
+                let real_name = value.get("package").and_then(|p| p.as_str()).unwrap_or(key);
+                dependencies.push(real_name.to_string());
+            }
+        }
+    }
+
+    Ok(Some(WorkspacePackage {
+        name: name.to_string(),
+        path: dir.to_path_buf(),
+        manifest: manifest_path,
+        dependencies,
+    }))
+}
+
+fn npm_packages(root: &Path, kind: WorkspaceType) -> Result<Vec<WorkspacePackage>> {
+    let patterns: Vec<String> = if kind == WorkspaceType::Pnpm {
+        let path = root.join("pnpm-workspace.yaml");
+        let content = fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))?;
+        let yaml: serde_yaml::Value = serde_yaml::from_str(&content)
+            .with_context(|| format!("parsing {}", path.display()))?;
+        yaml.get("packages")
+            .and_then(|p| p.as_sequence())
+            .map(|p| p.iter().filter_map(|v| v.as_str().map(String::from)).collect())
+            .unwrap_or_default()
+    } else {
+        let manifest = read_json(&root.join("package.json"))?;
+        // `workspaces` is either an array or `{ "packages": [...] }`
+        let workspaces = manifest.get("workspaces");
+        workspaces
+            .and_then(|w| w.as_array())
+            .or_else(|| workspaces.and_then(|w| w.get("packages")).and_then(|p| p.as_array()))
+            .map(|p| p.iter().filter_map(|v| v.as_str().map(String::from)).collect())
+            .unwrap_or_default()
+    };
+
+    let mut packages = Vec::new();
+    packages.extend(npm_package(root)?);
+    for dir in expand_members(root, &patterns)? {
+        packages.extend(npm_package(&dir)?);
+    }
+    Ok(packages)
+}
+
+fn npm_package(dir: &Path) -> Result<Option<WorkspacePackage>> {
+    let manifest_path = dir.join("package.json");
+    if !manifest_path.exists() {
+        return Ok(None);
+    }
+    let manifest = read_json(&manifest_path)?;
+    let Some(name) = manifest.get("name").and_then(|n| n.as_str()) else {
+        return Ok(None);
+    };
+
+    let mut dependencies = Vec::new();
+    for field in ["dependencies", "devDependencies", "peerDependencies", "optionalDependencies"] {
+        if let Some(deps) = manifest.get(field).and_then(|d| d.as_object()) {
+            dependencies.extend(deps.keys().cloned());
+        }
+    }
+
+    Ok(Some(WorkspacePackage {
+        name: name.to_string(),
+        path: dir.to_path_buf(),
+        manifest: manifest_path,
+        dependencies,
+    }))
+}
+
+fn go_packages(root: &Path) -> Result<Vec<WorkspacePackage>> {
+    let go_work = root.join("go.work");
+    if !go_work.exists() {
+        return Ok(go_module(root)?.into_iter().collect());
+    }
+
+    let content = fs::read_to_string(&go_work).with_context(|| format!("reading {}", go_work.display()))?;
+    let mut packages = Vec::new();
+    for dir in go_directive_entries(&content, "use") {
+        packages.extend(go_module(&root.join(dir))?);
+    }
+    Ok(packages)
+}
+
+fn go_module(dir: &Path) -> Result<Option<WorkspacePackage>> {
+    let manifest_path = dir.join("go.mod");
+    if !manifest_path.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(&manifest_path)
+        .with_context(|| format!("reading {}", manifest_path.display()))?;
+    let Some(name) = go_directive_entries(&content, "module").into_iter().next() else {
+        return Ok(None);
+    };
+
+    Ok(Some(WorkspacePackage {
+        name,
+        path: dir.to_path_buf(),
+        manifest: manifest_path,
+        dependencies: go_directive_entries(&content, "require"),
+    }))
+}
+
+/// First field of each entry of a go.mod/go.work directive, in either the
+/// single-line (`require foo v1`) or block (`require ( ... )`) form.
+fn go_directive_entries(content: &str, directive: &str) -> Vec<String> {
+    let mut entries = Vec::new();
+    let mut in_block = false;
+
+    for line in content.lines() {
+        let line = line.split("//").next().unwrap_or("").trim();
+        if in_block {
+            if line == ")" {
+                in_block = false;
+            } else if let Some(entry) = line.split_whitespace().next() {
+                entries.push(entry.to_string());
+            }
+            continue;
+        }
+
+        let Some(rest) = line.strip_prefix(directive) else {
+            continue;
+        };
+        if !rest.starts_with(char::is_whitespace) && !rest.starts_with('(') {
+            continue;
+        }
+        let rest = rest.trim();
+        if rest == "(" {
+            in_block = true;
+        } else if let Some(entry) = rest.split_whitespace().next() {
+            entries.push(entry.to_string());
+        }
+    }
+
+    entries
+}
+
+/// Expand workspace member patterns (`crates/*`, `packages/**`) into
+/// directories. Patterns starting with `!` exclude matches.
+fn expand_members(root: &Path, patterns: &[String]) -> Result<Vec<PathBuf>> {
+    let mut included = Vec::new();
+    let mut excluded = Vec::new();
+
+    for pattern in patterns {
+        let (negated, pattern) = match pattern.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, pattern.as_str()),
+        };
+        let pattern = pattern.trim_start_matches("./").trim_end_matches('/');
+
+        let mut dirs = vec![root.to_path_buf()];
+        for segment in pattern.split('/') {
+            let mut next = Vec::new();
+            for dir in &dirs {
+                if segment == "**" {
+                    next.push(dir.clone());
+                    next.extend(descendant_dirs(dir));
+                } else if segment.contains(['*', '?', '[']) {
+                    let matcher = Glob::new(segment)
+                        .with_context(|| format!("invalid workspace pattern '{}'", pattern))?
+                        .compile_matcher();
+                    next.extend(child_dirs(dir).into_iter().filter(|d| {
+                        d.file_name().is_some_and(|n| matcher.is_match(n))
+                    }));
+                } else {
+                    let candidate = dir.join(segment);
+                    if candidate.is_dir() {
+                        next.push(candidate);
+                    }
+                }
+            }
+            dirs = next;
+        }
+
+        if negated {
+            excluded.extend(dirs);
+        } else {
+            included.extend(dirs);
+        }
+    }
+
+    included.retain(|d| !excluded.contains(d));
+    included.sort();
+    included.dedup();
+    Ok(included)
+}
+
+fn child_dirs(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| {
+            p.is_dir()
+                && p.file_name().is_some_and(|n| {
+                    let n = n.to_string_lossy();
+                    !n.starts_with('.') && n != "node_modules" && n != "target"
+                })
+        })
+        .collect()
+}
+
+fn descendant_dirs(dir: &Path) -> Vec<PathBuf> {
+    let mut result = Vec::new();
+    let mut stack = child_dirs(dir);
+    while let Some(current) = stack.pop() {
+        stack.extend(child_dirs(&current));
+        result.push(current);
+    }
+    result
+}
+
+fn read_toml(path: &Path) -> Result<toml::Value> {
+    let content = fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    toml::from_str(&content).with_context(|| format!("parsing {}", path.display()))
+}
+
+fn read_json(path: &Path) -> Result<serde_json::Value> {
+    let content = fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    serde_json::from_str(&content).with_context(|| format!("parsing {}", path.display()))
+}
@@ -0,0 +1,57 @@
+//! Parsing every source file of this repository, as a full index does,
+//! through the parser pool and through a parser that switches language on
+//! every file as the pool's workers used to.
+
+use canopy_indexer::{FileType, ParseRequest, ParserPool};
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::path::{Path, PathBuf};
+
+/// The repository's parseable files, in walk order so languages interleave.
+fn repo_files() -> Vec<(FileType, PathBuf, String)> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("../..");
+    ignore::WalkBuilder::new(root)
+        .build()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+        .filter_map(|entry| {
+            let file_type = FileType::from_path(entry.path()).filter(|t| *t != FileType::Generic)?;
+            let content = std::fs::read_to_string(entry.path()).ok()?;
+            Some((file_type, entry.into_path(), content))
+        })
+        .collect()
+}
+
+fn parse_repo(c: &mut Criterion) {
+    let files = repo_files();
+    let mut group = c.benchmark_group("parse_repo");
+    group.sample_size(10);
+
+    group.bench_function("set_language_per_file", |b| {
+        let mut parser = tree_sitter::Parser::new();
+        b.iter(|| {
+            for (file_type, _, content) in &files {
+                parser.set_language(&file_type.get_language()).unwrap();
+                parser.parse(content, None).unwrap();
+            }
+        })
+    });
+
+    // One worker, so the comparison is parser reuse and not parallelism
+    let pool = ParserPool::new(1);
+    group.bench_function("parser_pool", |b| {
+        b.iter(|| {
+            for (file_type, path, content) in &files {
+                pool.parse_blocking(ParseRequest {
+                    file_type: file_type.clone(),
+                    content: content.clone(),
+                    path: path.clone(),
+                })
+                .unwrap();
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, parse_repo);
+criterion_main!(benches);
@@ -0,0 +1,133 @@
+//! Intra-file call resolution
+//!
+//! Call expressions are collected from the syntax tree and resolved by name
+//! against the functions and methods extracted from the same file. Calls to
+//! names defined more than once in the file are left unresolved rather than
+//! guessed; calls to names not defined in the file become [`Reference`]s for
+//! cross-file resolution.
+
+use crate::resolve::Reference;
+use canopy_core::{GraphNode, NodeKind};
+use std::collections::HashMap;
+use tree_sitter::Node;
+
+/// A call from `nodes[caller]` to `nodes[callee]` on `line`.
+pub type LocalCall = (usize, usize, u32);
+
+fn is_callable(node: &GraphNode) -> bool {
+    matches!(node.kind, NodeKind::Function | NodeKind::Method | NodeKind::TestCase | NodeKind::Macro)
+}
+
+/// The called name in a callee expression: `helper`, `self.helper`,
+/// `Foo::helper::<T>`, `$this->helper` and `obj?.helper` all give `helper`.
+pub fn callee_name(text: &str) -> Option<&str> {
+    let text = text.split(['<', '(']).next().unwrap_or(text).trim().trim_end_matches(':');
+    let name = text.rsplit(['.', ':', '>', '\\', '?']).next().unwrap_or(text).trim();
+    let valid = !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '$');
+    valid.then_some(name)
+}
+
+/// Index of the innermost function or method in `nodes` spanning `line`.
+pub fn enclosing_callable(nodes: &[GraphNode], line: u32) -> Option<usize> {
+    nodes
+        .iter()
+        .enumerate()
+        .filter(|(_, n)| is_callable(n))
+        .filter(|(_, n)| n.line_start.is_some_and(|s| s <= line) && n.line_end.is_some_and(|e| e >= line))
+        .min_by_key(|(_, n)| n.line_end.unwrap_or(0) - n.line_start.unwrap_or(0))
+        .map(|(i, _)| i)
+}
+
+/// Resolve `calls`, as (callee name, line) pairs, against the functions in
+/// `nodes`. Each caller/callee pair is reported once, at its first line.
+/// Calls to names not defined in `nodes` are returned as references.
+pub fn resolve_calls<'a>(
+    nodes: &[GraphNode],
+    calls: impl IntoIterator<Item = (&'a str, u32)>,
+) -> (Vec<LocalCall>, Vec<Reference>) {
+    // Names defined more than once map to None; repeated nodes for the same
+    // definition (same start line) still count as one
+    let mut definitions: HashMap<&str, Option<usize>> = HashMap::new();
+    for (i, node) in nodes.iter().enumerate().filter(|(_, n)| is_callable(n)) {
+        definitions
+            .entry(node.name.as_str())
+            .and_modify(|d| {
+                if d.is_some_and(|first| nodes[first].line_start != node.line_start) {
+                    *d = None;
+                }
+            })
+            .or_insert(Some(i));
+    }
+
+    let mut resolved: Vec<LocalCall> = Vec::new();
+    let mut unresolved: Vec<Reference> = Vec::new();
+    for (name, line) in calls {
+        let Some(caller) = enclosing_callable(nodes, line) else {
+            continue;
+        };
+        match definitions.get(name) {
+            Some(Some(callee)) => {
+                if !resolved.iter().any(|&(c, d, _)| c == caller && d == *callee) {
+                    resolved.push((caller, *callee, line));
+                }
+            }
+            Some(None) => {}
+            None => {
+                if !unresolved.iter().any(|r| r.source == Some(caller) && r.target == name) {
+                    unresolved.push(Reference::call(caller, name, line));
+                }
+            }
+        }
+    }
+    (resolved, unresolved)
+}
+
+/// Calls within `root` to functions defined in the same file. `call_kinds`
+/// pairs each call node kind of the grammar with the field holding its
+/// callee (`("call_expression", "function")`). Calls to names defined
+/// elsewhere are returned as references.
+pub fn local_calls(
+    root: Node,
+    source: &[u8],
+    nodes: &[GraphNode],
+    call_kinds: &[(&str, &str)],
+) -> (Vec<LocalCall>, Vec<Reference>) {
+    let mut calls = Vec::new();
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        if let Some(&(_, field)) = call_kinds.iter().find(|(kind, _)| *kind == node.kind())
+            && let Some(name) = node
+                .child_by_field_name(field)
+                .and_then(|callee| callee.utf8_text(source).ok())
+                .and_then(callee_name) {
+            calls.push((name, node.start_position().row as u32 + 1));
+        }
+        let mut cursor = node.walk();
+        stack.extend(node.children(&mut cursor));
+    }
+    // The stack visits later siblings first
+    calls.sort_by_key(|&(_, line)| line);
+    resolve_calls(nodes, calls)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_callee_name() {
+        for (text, expected) in [
+            ("helper", Some("helper")),
+            ("self.helper", Some("helper")),
+            ("Foo::helper::<T>", Some("helper")),
+            ("$this->helper", Some("helper")),
+            ("obj?.helper", Some("helper")),
+            ("App\\Util\\helper", Some("helper")),
+            ("(get_fn())", None),
+        ] {
+            assert_eq!(callee_name(text), expected, "{}", text);
+        }
+    }
+}
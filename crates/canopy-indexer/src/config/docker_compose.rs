@@ -0,0 +1,181 @@
+//! docker-compose parser
+//!
+//! Each entry under `services` becomes a DockerService node. Host paths it
+//! mounts and the services it `depends_on` are recorded in metadata for
+//! [`crate::heuristics::docker`] to link; variables it passes through or
+//! interpolates go in `env_reads`, so [`crate::heuristics::env_vars`] binds
+//! them to `.env` entries like any other reader.
+
+use crate::extractor::{ExtractionResult, LanguageExtractor};
+use crate::heuristics::env_vars::ENV_READS_KEY;
+use canopy_core::{GraphNode, NodeKind, Language, NodeId, NodeMetadata};
+use regex::Regex;
+use serde_yaml::Value;
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::sync::LazyLock;
+use anyhow::{Context, Result};
+
+/// Metadata key holding newline-separated host paths a service mounts.
+pub const MOUNTS_KEY: &str = "mounts";
+/// Metadata key holding newline-separated services a service depends on.
+pub const DEPENDS_ON_KEY: &str = "depends_on";
+
+/// `${VAR}`, `${VAR:-default}` and `$VAR` interpolations.
+static INTERPOLATION: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\$\{?([A-Za-z_][A-Za-z0-9_]*)").unwrap());
+
+#[derive(Default)]
+pub struct DockerComposeParser;
+
+impl DockerComposeParser {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Whether `path` names a compose file (`docker-compose.yml`,
+/// `docker-compose.prod.yaml`, `compose.yml`).
+pub fn is_compose_file(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return false;
+    };
+    (name.starts_with("docker-compose") || name.starts_with("compose."))
+        && (name.ends_with(".yml") || name.ends_with(".yaml"))
+}
+
+/// Host side of bind mounts; named volumes are skipped.
+fn host_mounts(volumes: &Value) -> Vec<String> {
+    let Some(volumes) = volumes.as_sequence() else {
+        return Vec::new();
+    };
+    volumes
+        .iter()
+        .filter_map(|v| match v {
+            Value::String(s) => s.split(':').next(),
+            Value::Mapping(_) => v.get("source").and_then(|s| s.as_str()),
+            _ => None,
+        })
+        .filter(|source| source.starts_with(['.', '/', '~']))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Variables a service reads: pass-through `environment` entries and any
+/// interpolation in its definition.
+fn env_reads(service: &Value) -> BTreeSet<String> {
+    let mut reads = BTreeSet::new();
+    if let Some(environment) = service.get("environment") {
+        for entry in super::yaml_strings(environment) {
+            reads.insert(entry.split('=').next().unwrap_or(entry).to_string());
+        }
+    }
+    fn walk(value: &Value, reads: &mut BTreeSet<String>) {
+        match value {
+            Value::String(s) => reads.extend(INTERPOLATION.captures_iter(s).map(|c| c[1].to_string())),
+            Value::Sequence(items) => items.iter().for_each(|i| walk(i, reads)),
+            Value::Mapping(map) => map.values().for_each(|v| walk(v, reads)),
+            _ => {}
+        }
+    }
+    walk(service, &mut reads);
+    reads
+}
+
+impl LanguageExtractor for DockerComposeParser {
+    fn extract(&self, path: &Path, content: &[u8]) -> Result<ExtractionResult> {
+        let source_code = std::str::from_utf8(content)?;
+        let compose: Value = serde_yaml::from_str(source_code)
+            .with_context(|| format!("parsing {}", path.display()))?;
+
+        let mut nodes = Vec::new();
+        let services_line = source_code.lines().position(|l| l.starts_with("services:")).unwrap_or(0);
+        for (name, service) in compose.get("services").and_then(|s| s.as_mapping()).into_iter().flatten() {
+            let Some(name) = name.as_str() else {
+                continue;
+            };
+            let (line_start, line_end) = super::yaml_block_lines(source_code, name, services_line).unwrap_or((1, 1));
+
+            let mut metadata = NodeMetadata::default();
+            let mut extra = |key: &str, value: String| {
+                if !value.is_empty() {
+                    metadata.extra.insert(key.to_string(), value);
+                }
+            };
+            extra("image", service.get("image").and_then(|i| i.as_str()).unwrap_or("").to_string());
+            let build = service.get("build").and_then(|b| b.as_str().or_else(|| b.get("context").and_then(|c| c.as_str())));
+            extra("build", build.unwrap_or("").to_string());
+            extra(MOUNTS_KEY, service.get("volumes").map(host_mounts).unwrap_or_default().join("\n"));
+            extra(DEPENDS_ON_KEY, service.get("depends_on").map(super::yaml_strings).unwrap_or_default().join("\n"));
+            extra(ENV_READS_KEY, env_reads(service).into_iter().collect::<Vec<_>>().join("\n"));
+            extra("ports", service.get("ports").map(super::yaml_strings).unwrap_or_default().join(", "));
+
+            nodes.push(GraphNode {
+                id: NodeId(0), // Will be set by graph
+                kind: NodeKind::DockerService,
+                name: name.to_string(),
+                qualified_name: format!("{}::services.{}", path.display(), name).into(),
+                file_path: path.into(),
+                line_start: Some(line_start),
+                line_end: Some(line_end),
+                language: Some(Language::Yaml),
+                is_container: false,
+                child_count: 0,
+                loc: Some(line_end - line_start),
+                metadata,
+            });
+        }
+
+        Ok(ExtractionResult { nodes, edges: Vec::new(), contains: Vec::new(), calls: Vec::new(), references: Vec::new(), errors: Vec::new() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_compose() {
+        let code = r#"services:
+  api:
+    build: ./services/api
+    ports:
+      - "8080:8080"
+    volumes:
+      - ./services/api/src:/app/src:ro
+      - cache:/var/cache
+    environment:
+      - DATABASE_URL
+      - LOG_LEVEL=${LOG_LEVEL:-info}
+    depends_on:
+      - db
+
+  db:
+    image: postgres:16
+    environment:
+      POSTGRES_PASSWORD: secret
+volumes:
+  cache:
+"#;
+
+        let path = Path::new("docker-compose.yml");
+        let result = DockerComposeParser::new().extract(path, code.as_bytes()).unwrap();
+
+        let api = &result.nodes[0];
+        assert_eq!((api.kind, api.name.as_str()), (NodeKind::DockerService, "api"));
+        assert_eq!((api.line_start, api.line_end), (Some(2), Some(13)));
+        assert_eq!(api.metadata.extra["build"], "./services/api");
+        assert_eq!(api.metadata.extra[MOUNTS_KEY], "./services/api/src");
+        assert_eq!(api.metadata.extra[DEPENDS_ON_KEY], "db");
+        assert_eq!(api.metadata.extra[ENV_READS_KEY], "DATABASE_URL\nLOG_LEVEL");
+        assert_eq!(api.metadata.extra["ports"], "8080:8080");
+
+        let db = &result.nodes[1];
+        assert_eq!((db.line_start, db.line_end), (Some(15), Some(18)));
+        assert_eq!(db.metadata.extra["image"], "postgres:16");
+        assert_eq!(db.metadata.extra[ENV_READS_KEY], "POSTGRES_PASSWORD");
+
+        assert!(is_compose_file(Path::new("deploy/docker-compose.prod.yaml")));
+        assert!(is_compose_file(Path::new("compose.yml")));
+        assert!(!is_compose_file(Path::new("config.yml")));
+    }
+}
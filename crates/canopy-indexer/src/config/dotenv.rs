@@ -1 +1,141 @@
+//! Dotenv parser
+//!
+//! Each `KEY=value` line of a `.env*` file becomes an EnvVariable node, with
+//! the comment line above it as its summary. Values are only kept for example
+//! and template files; real `.env` files hold secrets that don't belong in
+//! the graph. [`crate::heuristics::env_vars`] links the variables to the code
+//! that reads them.
+
+use crate::extractor::{ExtractionResult, LanguageExtractor};
+use canopy_core::{GraphNode, NodeKind, NodeId, NodeMetadata};
+use std::path::Path;
+use anyhow::Result;
+
+/// Suffixes of dotenv files that document variables rather than set them.
+const TEMPLATE_SUFFIXES: &[&str] = &["example", "sample", "template", "dist", "defaults"];
+
+#[derive(Default)]
 pub struct DotenvParser;
+
+impl DotenvParser {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Whether `path` names a dotenv file (`.env`, `.env.local`, `prod.env`).
+pub fn is_dotenv(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| n == ".env" || n.starts_with(".env.") || n.ends_with(".env"))
+}
+
+/// Parse a `KEY=value` line, handling `export` and quoted values.
+fn parse_line(line: &str) -> Option<(&str, String)> {
+    let line = line.trim();
+    let line = line.strip_prefix("export ").unwrap_or(line);
+    let (key, value) = line.split_once('=')?;
+    let key = key.trim();
+    if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.') {
+        return None;
+    }
+    let value = value.trim();
+    let value = match value.chars().next() {
+        Some(q @ ('"' | '\'')) => value[1..].split(q).next().unwrap_or(""),
+        // Unquoted values end at an inline comment
+        _ => value.split(" #").next().unwrap_or("").trim_end(),
+    };
+    Some((key, value.to_string()))
+}
+
+impl LanguageExtractor for DotenvParser {
+    fn extract(&self, path: &Path, content: &[u8]) -> Result<ExtractionResult> {
+        let source_code = std::str::from_utf8(content)?;
+        let keep_values = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| TEMPLATE_SUFFIXES.iter().any(|s| n.ends_with(s)));
+
+        let mut nodes: Vec<GraphNode> = Vec::new();
+        let mut comment: Option<&str> = None;
+        for (row, line) in source_code.lines().enumerate() {
+            let line_no = row as u32 + 1;
+            if let Some(text) = line.trim().strip_prefix('#') {
+                comment = Some(text.trim()).filter(|t| !t.is_empty());
+                continue;
+            }
+            let Some((key, value)) = parse_line(line) else {
+                comment = None;
+                continue;
+            };
+
+            let mut metadata = NodeMetadata {
+                doc_summary: comment.take().map(str::to_string),
+                ..Default::default()
+            };
+            if keep_values {
+                metadata.extra.insert("value".to_string(), value);
+            }
+            // A later assignment overrides an earlier one
+            nodes.retain(|n| n.name != key);
+            nodes.push(GraphNode {
+                id: NodeId(0), // Will be set by graph
+                kind: NodeKind::EnvVariable,
+                name: key.to_string(),
+                qualified_name: format!("{}::{}", path.display(), key).into(),
+                file_path: path.into(),
+                line_start: Some(line_no),
+                line_end: Some(line_no),
+                language: None,
+                is_container: false,
+                child_count: 0,
+                loc: Some(0),
+                metadata,
+            });
+        }
+
+        Ok(ExtractionResult { nodes, edges: Vec::new(), contains: Vec::new(), calls: Vec::new(), references: Vec::new(), errors: Vec::new() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_dotenv() {
+        let code = r#"# Postgres connection string
+DATABASE_URL="postgres://localhost/canopy"
+export API_KEY='abc#123'
+
+PORT=8080 # overridden below
+PORT=9090
+not a variable
+"#;
+
+        let result = DotenvParser::new().extract(Path::new(".env.example"), code.as_bytes()).unwrap();
+        let vars: Vec<_> = result
+            .nodes
+            .iter()
+            .map(|n| (n.name.as_str(), n.line_start, n.metadata.extra["value"].as_str()))
+            .collect();
+        assert_eq!(
+            vars,
+            vec![
+                ("DATABASE_URL", Some(2), "postgres://localhost/canopy"),
+                ("API_KEY", Some(3), "abc#123"),
+                ("PORT", Some(6), "9090"),
+            ]
+        );
+        assert_eq!(result.nodes[0].metadata.doc_summary.as_deref(), Some("Postgres connection string"));
+        assert_eq!(result.nodes[0].qualified_name, ".env.example::DATABASE_URL");
+
+        // Values in real env files stay out of the graph
+        let result = DotenvParser::new().extract(Path::new(".env"), code.as_bytes()).unwrap();
+        assert!(result.nodes.iter().all(|n| n.metadata.extra.is_empty()));
+
+        assert!(is_dotenv(Path::new("app/.env.local")));
+        assert!(is_dotenv(Path::new("prod.env")));
+        assert!(!is_dotenv(Path::new("environment.rs")));
+    }
+}
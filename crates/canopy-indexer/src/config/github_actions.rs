@@ -1 +1,186 @@
+//! GitHub Actions workflow parser
+//!
+//! Each job in a `.github/workflows/*.yml` file becomes a CIJob node. The
+//! workflow's trigger events, branches and path filters are copied onto every
+//! job, and the commands in its `run` steps are recorded in the `invokes`
+//! metadata entry. [`crate::heuristics::ci`] links jobs to the paths that
+//! trigger them and [`crate::heuristics::packages`] to what they run.
+
+use super::json::{parse_invocations, INVOKES_KEY};
+use crate::extractor::{ExtractionResult, LanguageExtractor};
+use canopy_core::{GraphNode, NodeKind, Language, NodeId, NodeMetadata};
+use serde_yaml::Value;
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result};
+
+/// Metadata key holding newline-separated path filters that trigger a job.
+pub const TRIGGER_PATHS_KEY: &str = "trigger_paths";
+/// Metadata key holding the directory a job's commands run in.
+pub const WORKING_DIR_KEY: &str = "working_directory";
+
+#[derive(Default)]
 pub struct GithubActionsParser;
+
+impl GithubActionsParser {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Whether `path` is a workflow file under `.github/workflows`.
+pub fn is_workflow(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == "yml" || e == "yaml")
+        && path.parent().is_some_and(|p| p.ends_with(".github/workflows"))
+}
+
+/// Trigger events, branch filters and path filters of a workflow's `on`.
+fn triggers(on: &Value) -> (Vec<String>, Vec<String>, Vec<String>) {
+    let events = super::yaml_strings(on).into_iter().map(str::to_string).collect();
+    let (mut branches, mut paths) = (Vec::new(), Vec::new());
+    for (_, filters) in on.as_mapping().into_iter().flatten() {
+        for branch in filters.get("branches").map(super::yaml_strings).unwrap_or_default() {
+            if !branches.iter().any(|b| b == branch) {
+                branches.push(branch.to_string());
+            }
+        }
+        for path in filters.get("paths").map(super::yaml_strings).unwrap_or_default() {
+            if !paths.iter().any(|p| p == path) {
+                paths.push(path.to_string());
+            }
+        }
+    }
+    (events, branches, paths)
+}
+
+fn working_directory(value: &Value) -> Option<&str> {
+    value.get("defaults")?.get("run")?.get("working-directory")?.as_str()
+}
+
+impl LanguageExtractor for GithubActionsParser {
+    fn extract(&self, path: &Path, content: &[u8]) -> Result<ExtractionResult> {
+        let source_code = std::str::from_utf8(content)?;
+        let workflow: Value = serde_yaml::from_str(source_code)
+            .with_context(|| format!("parsing {}", path.display()))?;
+
+        // `.github/workflows/ci.yml` → repository root
+        let root = path.ancestors().nth(3).map(Path::to_path_buf).unwrap_or_default();
+        let (events, branches, trigger_paths) = workflow.get("on").map(triggers).unwrap_or_default();
+
+        let mut nodes = Vec::new();
+        let jobs_line = source_code.lines().position(|l| l.starts_with("jobs:")).unwrap_or(0);
+        for (id, job) in workflow.get("jobs").and_then(|j| j.as_mapping()).into_iter().flatten() {
+            let Some(id) = id.as_str() else {
+                continue;
+            };
+            let (line_start, line_end) = super::yaml_block_lines(source_code, id, jobs_line).unwrap_or((1, 1));
+
+            let steps = job.get("steps").and_then(|s| s.as_sequence()).map(Vec::as_slice).unwrap_or_default();
+            let uses: Vec<&str> = steps.iter().filter_map(|s| s.get("uses")?.as_str()).collect();
+            let mut invokes: Vec<String> = Vec::new();
+            for run in steps.iter().filter_map(|s| s.get("run")?.as_str()) {
+                for invoke in run.lines().flat_map(parse_invocations) {
+                    if !invokes.contains(&invoke) {
+                        invokes.push(invoke);
+                    }
+                }
+            }
+            let dir: PathBuf = match working_directory(job).or_else(|| working_directory(&workflow)) {
+                Some(dir) => root.join(dir),
+                None => root.clone(),
+            };
+
+            let mut metadata = NodeMetadata {
+                doc_summary: job.get("name").and_then(|n| n.as_str()).map(str::to_string),
+                ..Default::default()
+            };
+            let mut extra = |key: &str, value: String| {
+                if !value.is_empty() {
+                    metadata.extra.insert(key.to_string(), value);
+                }
+            };
+            extra("triggers", events.join(", "));
+            extra("branches", branches.join(", "));
+            extra(TRIGGER_PATHS_KEY, trigger_paths.join("\n"));
+            extra("runs_on", job.get("runs-on").map(super::yaml_strings).unwrap_or_default().join(", "));
+            extra("needs", job.get("needs").map(super::yaml_strings).unwrap_or_default().join(", "));
+            extra("uses", uses.join(", "));
+            extra(INVOKES_KEY, invokes.join("\n"));
+            extra(WORKING_DIR_KEY, dir.display().to_string());
+
+            nodes.push(GraphNode {
+                id: NodeId(0), // Will be set by graph
+                kind: NodeKind::CIJob,
+                name: id.to_string(),
+                qualified_name: format!("{}::jobs.{}", path.display(), id).into(),
+                file_path: path.into(),
+                line_start: Some(line_start),
+                line_end: Some(line_end),
+                language: Some(Language::Yaml),
+                is_container: false,
+                child_count: 0,
+                loc: Some(line_end - line_start),
+                metadata,
+            });
+        }
+
+        Ok(ExtractionResult { nodes, edges: Vec::new(), contains: Vec::new(), calls: Vec::new(), references: Vec::new(), errors: Vec::new() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_workflow() {
+        let code = r#"name: CI
+on:
+  push:
+    branches: [main]
+    paths: ["crates/**", "Cargo.lock"]
+  pull_request:
+    paths: ["crates/**"]
+
+jobs:
+  test:
+    name: Test suite
+    runs-on: ubuntu-latest
+    steps:
+      - uses: actions/checkout@v4
+      - run: |
+          cargo build --workspace
+          cargo test -p canopy-core
+  ui:
+    needs: test
+    runs-on: ubuntu-latest
+    defaults:
+      run:
+        working-directory: ui
+    steps:
+      - run: npm ci && npm run build
+      - run: ./scripts/deploy.sh
+"#;
+
+        let path = Path::new("/repo/.github/workflows/ci.yml");
+        let result = GithubActionsParser::new().extract(path, code.as_bytes()).unwrap();
+
+        let test = &result.nodes[0];
+        assert_eq!((test.kind, test.name.as_str()), (NodeKind::CIJob, "test"));
+        assert_eq!((test.line_start, test.line_end), (Some(10), Some(17)));
+        assert_eq!(test.metadata.doc_summary.as_deref(), Some("Test suite"));
+        assert_eq!(test.metadata.extra["triggers"], "push, pull_request");
+        assert_eq!(test.metadata.extra["branches"], "main");
+        assert_eq!(test.metadata.extra[TRIGGER_PATHS_KEY], "crates/**\nCargo.lock");
+        assert_eq!(test.metadata.extra["uses"], "actions/checkout@v4");
+        assert_eq!(test.metadata.extra[INVOKES_KEY], "package:canopy-core");
+        assert_eq!(test.metadata.extra[WORKING_DIR_KEY], "/repo");
+
+        let ui = &result.nodes[1];
+        assert_eq!(ui.metadata.extra["needs"], "test");
+        assert_eq!(ui.metadata.extra[INVOKES_KEY], "script:build\nfile:./scripts/deploy.sh");
+        assert_eq!(ui.metadata.extra[WORKING_DIR_KEY], "/repo/ui");
+
+        assert!(is_workflow(Path::new(".github/workflows/release.yaml")));
+        assert!(!is_workflow(Path::new("workflows/ci.yml")));
+    }
+}
@@ -1 +1,238 @@
+//! JSON config parser
+//!
+//! package.json manifests become a Package node carrying its dependency names
+//! and one Script node per `scripts` entry. Each script records what its
+//! command runs in the `invokes` metadata entry; [`crate::heuristics::packages`]
+//! links those to the scripts, files and workspace packages they name.
+//! OpenAPI specs are handed to [`super::openapi`]; other JSON files produce
+//! no nodes yet.
+
+use super::DEPENDENCIES_KEY;
+use crate::extractor::{ExtractionResult, LanguageExtractor};
+use canopy_core::{GraphNode, NodeKind, Language, NodeId, NodeMetadata};
+use std::path::Path;
+use anyhow::{Context, Result};
+
+/// Metadata key holding a command's newline-separated `script:`, `file:`,
+/// `bin:` and `package:` targets.
+pub const INVOKES_KEY: &str = "invokes";
+
+/// Commands that run their first argument rather than being the program.
+const WRAPPERS: &[&str] = &["npx", "node", "ts-node", "tsx", "deno", "cross-env", "env", "dotenv", "sh", "bash", "exec", "time"];
+/// Package managers whose `run <script>` invokes another script.
+const PACKAGE_MANAGERS: &[&str] = &["npm", "yarn", "pnpm", "bun"];
+/// Package manager subcommands that are not script names (`yarn install`).
+const BUILTIN_COMMANDS: &[&str] = &["install", "ci", "add", "remove", "exec", "dlx", "publish", "test", "link", "audit"];
+/// Extensions of arguments treated as files even without a `/`.
+const SCRIPT_EXTENSIONS: &[&str] = &[".js", ".mjs", ".cjs", ".ts", ".mts", ".sh", ".py"];
+
+#[derive(Default)]
 pub struct JsonParser;
+
+impl JsonParser {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// What a shell command runs: package scripts, files, binaries and cargo
+/// packages, in order.
+pub fn parse_invocations(command: &str) -> Vec<String> {
+    let mut invokes = Vec::new();
+    for segment in command.split(['&', '|', ';']).map(str::trim).filter(|s| !s.is_empty()) {
+        let mut words = segment
+            .split_whitespace()
+            .map(|w| w.trim_matches(['"', '\'']))
+            // `NODE_ENV=production webpack`
+            .skip_while(|w| w.contains('=') && !w.starts_with('-'))
+            .peekable();
+        while words.peek().is_some_and(|w| WRAPPERS.contains(w) || w.starts_with('-')) {
+            words.next();
+        }
+        let Some(program) = words.next() else {
+            continue;
+        };
+
+        let args: Vec<&str> = words.collect();
+        if program == "cargo" {
+            // `cargo test -p canopy-core`
+            let package = args.windows(2).find(|w| w[0] == "-p" || w[0] == "--package").map(|w| w[1]);
+            invokes.extend(package.map(|p| format!("package:{}", p)));
+            continue;
+        }
+        if PACKAGE_MANAGERS.contains(&program) {
+            // `npm run build`, `yarn build`, `pnpm -r run lint`
+            let mut rest = args.iter().filter(|a| !a.starts_with('-'));
+            let script = match rest.next() {
+                Some(&"run") | Some(&"run-script") => rest.next().copied(),
+                Some(&"test") => Some("test"),
+                Some(name) if !BUILTIN_COMMANDS.contains(name) => Some(*name),
+                _ => None,
+            };
+            invokes.extend(script.map(|s| format!("script:{}", s)));
+            continue;
+        }
+
+        for word in std::iter::once(program).chain(args.iter().copied()) {
+            let is_file = !word.starts_with('-')
+                && !word.contains("://")
+                && !word.contains('*')
+                && (word.starts_with("./") || SCRIPT_EXTENSIONS.iter().any(|e| word.ends_with(e)));
+            if is_file {
+                invokes.push(format!("file:{}", word));
+            } else if word == program {
+                invokes.push(format!("bin:{}", program));
+            }
+        }
+    }
+    invokes.dedup();
+    invokes
+}
+
+/// 1-based number of the first line after line `after` containing `needle`.
+fn find_line(content: &str, after: u32, needle: &str) -> Option<u32> {
+    content
+        .lines()
+        .enumerate()
+        .skip(after as usize)
+        .find(|(_, l)| l.contains(needle))
+        .map(|(i, _)| i as u32 + 1)
+}
+
+fn make_node(path: &Path, kind: NodeKind, name: &str, qualified: String, lines: (u32, u32), metadata: NodeMetadata) -> GraphNode {
+    GraphNode {
+        id: NodeId(0), // Will be set by graph
+        kind,
+        name: name.to_string(),
+        qualified_name: qualified.into(),
+        file_path: path.into(),
+        line_start: Some(lines.0),
+        line_end: Some(lines.1),
+        language: Some(Language::Json),
+        is_container: kind == NodeKind::Package,
+        child_count: 0,
+        loc: Some(lines.1 - lines.0),
+        metadata,
+    }
+}
+
+fn package_manifest(path: &Path, content: &str, manifest: &serde_json::Value) -> ExtractionResult {
+    let mut nodes = Vec::new();
+    let mut contains = Vec::new();
+    let last_line = content.lines().count().max(1) as u32;
+
+    let Some(name) = manifest.get("name").and_then(|n| n.as_str()) else {
+        return ExtractionResult { nodes, edges: Vec::new(), contains, calls: Vec::new(), references: Vec::new(), errors: Vec::new() };
+    };
+
+    let mut dependencies: Vec<&str> = Vec::new();
+    for field in ["dependencies", "devDependencies", "peerDependencies", "optionalDependencies"] {
+        for dependency in manifest.get(field).and_then(|d| d.as_object()).into_iter().flat_map(|d| d.keys()) {
+            if !dependencies.contains(&dependency.as_str()) {
+                dependencies.push(dependency);
+            }
+        }
+    }
+
+    let mut metadata = NodeMetadata::with_extra([
+        ("manifest", path.display().to_string()),
+        (DEPENDENCIES_KEY, dependencies.join("\n")),
+    ]);
+    if let Some(version) = manifest.get("version").and_then(|v| v.as_str()) {
+        metadata.extra.insert("version".to_string(), version.to_string());
+    }
+    metadata.doc_summary = manifest.get("description").and_then(|d| d.as_str()).map(str::to_string);
+    // Same qualified name as workspace discovery, so both describe one package
+    nodes.push(make_node(path, NodeKind::Package, name, format!("package::{}", name), (1, last_line), metadata));
+
+    if let Some(scripts) = manifest.get("scripts").and_then(|s| s.as_object()) {
+        let scripts_line = find_line(content, 0, "\"scripts\"").unwrap_or(1);
+        for (script, command) in scripts {
+            let Some(command) = command.as_str() else {
+                continue;
+            };
+            let line = find_line(content, scripts_line, &format!("\"{}\"", script)).unwrap_or(scripts_line);
+            let mut metadata = NodeMetadata::with_extra([
+                ("command", command.to_string()),
+                (INVOKES_KEY, parse_invocations(command).join("\n")),
+            ]);
+            metadata.signature = Some(command.chars().take(200).collect());
+            nodes.push(make_node(
+                path,
+                NodeKind::Script,
+                script,
+                format!("{}::scripts.{}", path.display(), script),
+                (line, line),
+                metadata,
+            ));
+            contains.push((0, nodes.len() - 1));
+        }
+    }
+
+    ExtractionResult { nodes, edges: Vec::new(), contains, calls: Vec::new(), references: Vec::new(), errors: Vec::new() }
+}
+
+impl LanguageExtractor for JsonParser {
+    fn extract(&self, path: &Path, content: &[u8]) -> Result<ExtractionResult> {
+        let source_code = std::str::from_utf8(content)?;
+        if super::openapi::looks_like_openapi(source_code) {
+            return super::openapi::OpenApiParser::new().extract(path, content);
+        }
+        // An empty manifest is a file being created, not a parse error
+        if path.file_name().is_none_or(|n| n != "package.json") || source_code.trim().is_empty() {
+            return Ok(ExtractionResult { nodes: Vec::new(), edges: Vec::new(), contains: Vec::new(), calls: Vec::new(), references: Vec::new(), errors: Vec::new() });
+        }
+        let manifest: serde_json::Value = serde_json::from_str(source_code)
+            .with_context(|| format!("parsing {}", path.display()))?;
+        Ok(package_manifest(path, source_code, &manifest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_package_json() {
+        let code = r#"{
+  "name": "@canopy/ui",
+  "version": "0.2.0",
+  "scripts": {
+    "build": "tsc -p . && node scripts/bundle.mjs --minify",
+    "dev": "NODE_ENV=development vite",
+    "prepublishOnly": "npm run build",
+    "test": "jest"
+  },
+  "dependencies": { "react": "^18.0.0" },
+  "devDependencies": { "@canopy/shared": "workspace:*", "react": "^18.0.0" }
+}
+"#;
+
+        let path = Path::new("ui/package.json");
+        let result = JsonParser::new().extract(path, code.as_bytes()).unwrap();
+
+        let package = &result.nodes[0];
+        assert_eq!((package.kind, package.name.as_str()), (NodeKind::Package, "@canopy/ui"));
+        assert_eq!(package.qualified_name, "package::@canopy/ui");
+        assert_eq!(package.metadata.extra[DEPENDENCIES_KEY], "react\n@canopy/shared");
+
+        let scripts: Vec<_> = result.nodes[1..]
+            .iter()
+            .map(|n| (n.kind, n.name.as_str(), n.line_start, n.metadata.extra[INVOKES_KEY].as_str()))
+            .collect();
+        assert_eq!(
+            scripts,
+            vec![
+                (NodeKind::Script, "build", Some(5), "bin:tsc\nfile:scripts/bundle.mjs"),
+                (NodeKind::Script, "dev", Some(6), "bin:vite"),
+                (NodeKind::Script, "prepublishOnly", Some(7), "script:build"),
+                (NodeKind::Script, "test", Some(8), "bin:jest"),
+            ]
+        );
+        assert_eq!(result.contains, vec![(0, 1), (0, 2), (0, 3), (0, 4)]);
+
+        // Other JSON files are ignored
+        let result = JsonParser::new().extract(Path::new("tsconfig.json"), b"{}").unwrap();
+        assert!(result.nodes.is_empty());
+    }
+}
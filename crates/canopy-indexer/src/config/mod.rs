@@ -4,6 +4,44 @@ pub mod yaml;
 pub mod toml_parser;
 pub mod json;
 pub mod dotenv;
+pub mod docker_compose;
 pub mod dockerfile;
 pub mod github_actions;
+pub mod openapi;
 pub mod sql_migration;
+
+use serde_yaml::Value;
+
+/// Metadata key holding a manifest's newline-separated dependency names.
+pub const DEPENDENCIES_KEY: &str = "dependencies";
+
+/// Lines of the nested YAML block under `name:`, searched from line index
+/// `after`: its key line through the last line before the next key at the
+/// same or lower indentation.
+pub(crate) fn yaml_block_lines(content: &str, name: &str, after: usize) -> Option<(u32, u32)> {
+    let lines: Vec<&str> = content.lines().collect();
+    let start = (after..lines.len()).find(|&i| {
+        let trimmed = lines[i].trim_start();
+        trimmed.strip_prefix(name).is_some_and(|rest| rest.starts_with(':')) && lines[i].len() > trimmed.len()
+    })?;
+    let indent = lines[start].len() - lines[start].trim_start().len();
+    let end = (start + 1..lines.len())
+        .take_while(|&i| {
+            let line = lines[i];
+            line.trim().is_empty() || line.trim_start().starts_with('#') || line.len() - line.trim_start().len() > indent
+        })
+        .filter(|&i| !lines[i].trim().is_empty())
+        .last()
+        .unwrap_or(start);
+    Some((start as u32 + 1, end as u32 + 1))
+}
+
+/// A scalar, the scalars of a sequence, or the keys of a mapping.
+pub(crate) fn yaml_strings(value: &Value) -> Vec<&str> {
+    match value {
+        Value::String(s) => vec![s.as_str()],
+        Value::Sequence(items) => items.iter().filter_map(|i| i.as_str()).collect(),
+        Value::Mapping(map) => map.keys().filter_map(|k| k.as_str()).collect(),
+        _ => Vec::new(),
+    }
+}
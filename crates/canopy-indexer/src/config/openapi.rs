@@ -0,0 +1,178 @@
+//! OpenAPI / Swagger spec parser
+//!
+//! Every operation under `paths` becomes a Route node named `METHOD /path`,
+//! with its operationId, tags and summary in metadata. Specs are parsed with
+//! `serde_yaml`, which also reads JSON. [`crate::heuristics::routes`] links
+//! the routes to their handler functions.
+
+use crate::extractor::{ExtractionResult, LanguageExtractor};
+use crate::heuristics::routes::{METHOD_KEY, OPERATION_ID_KEY, PATH_KEY};
+use canopy_core::{GraphNode, NodeKind, Language, NodeId, NodeMetadata};
+use serde_yaml::Value;
+use std::path::Path;
+use anyhow::{Context, Result};
+
+/// Operation keys of an OpenAPI path item.
+const METHODS: &[&str] = &["get", "put", "post", "delete", "options", "head", "patch", "trace"];
+
+#[derive(Default)]
+pub struct OpenApiParser;
+
+impl OpenApiParser {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Whether `path` is named like a spec (`openapi.yaml`, `swagger.json`,
+/// `billing.openapi.yml`).
+pub fn is_openapi_path(path: &Path) -> bool {
+    let is_data = path.extension().is_some_and(|e| e == "yaml" || e == "yml" || e == "json");
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("").to_lowercase();
+    is_data && (stem.starts_with("openapi") || stem.starts_with("swagger") || stem.ends_with(".openapi") || stem.ends_with(".swagger"))
+}
+
+/// Whether a YAML or JSON document declares an `openapi` or `swagger`
+/// version at its top level.
+pub fn looks_like_openapi(source: &str) -> bool {
+    source.lines().any(|l| l.starts_with("openapi:") || l.starts_with("swagger:"))
+        || source.get(..source.len().min(512)).is_some_and(|head| {
+            let head = head.trim_start();
+            head.starts_with('{') && (head.contains("\"openapi\"") || head.contains("\"swagger\""))
+        })
+}
+
+/// Index of the first line at or after index `after` whose trimmed text
+/// starts with `key` as a YAML or JSON key.
+fn key_line(lines: &[&str], key: &str, after: usize) -> Option<usize> {
+    let quoted = [format!("\"{}\"", key), format!("'{}'", key), key.to_string()];
+    (after..lines.len()).find(|&i| {
+        let line = lines[i].trim_start();
+        quoted.iter().any(|k| line.strip_prefix(k.as_str()).is_some_and(|rest| rest.trim_start().starts_with(':')))
+    })
+}
+
+impl LanguageExtractor for OpenApiParser {
+    fn extract(&self, path: &Path, content: &[u8]) -> Result<ExtractionResult> {
+        let source_code = std::str::from_utf8(content)?;
+        if source_code.trim().is_empty() {
+            return Ok(ExtractionResult { nodes: Vec::new(), edges: Vec::new(), contains: Vec::new(), calls: Vec::new(), references: Vec::new(), errors: Vec::new() });
+        }
+        let spec: Value = serde_yaml::from_str(source_code)
+            .with_context(|| format!("parsing {}", path.display()))?;
+        let language = if path.extension().is_some_and(|e| e == "json") { Language::Json } else { Language::Yaml };
+
+        let lines: Vec<&str> = source_code.lines().collect();
+        let paths_line = key_line(&lines, "paths", 0).unwrap_or(0);
+        let mut nodes = Vec::new();
+        for (route, item) in spec.get("paths").and_then(|p| p.as_mapping()).into_iter().flatten() {
+            let Some(route) = route.as_str() else {
+                continue;
+            };
+            let route_line = key_line(&lines, route, paths_line).unwrap_or(paths_line);
+            for (method, operation) in item.as_mapping().into_iter().flatten() {
+                let Some(method) = method.as_str().filter(|m| METHODS.contains(m)) else {
+                    continue;
+                };
+                let method = method.to_uppercase();
+                let name = format!("{} {}", method, route);
+                let method_line = key_line(&lines, &method.to_lowercase(), route_line).unwrap_or(route_line);
+                let line_start = method_line as u32 + 1;
+                // JSON operations are marked by their key line only
+                let line_end = match language {
+                    Language::Yaml => super::yaml_block_lines(source_code, &method.to_lowercase(), method_line).map_or(line_start, |(_, end)| end),
+                    _ => line_start,
+                };
+
+                let mut metadata = NodeMetadata::with_extra([(METHOD_KEY, method), (PATH_KEY, route.to_string())]);
+                if let Some(id) = operation.get("operationId").and_then(|i| i.as_str()) {
+                    metadata.extra.insert(OPERATION_ID_KEY.to_string(), id.to_string());
+                }
+                let tags = operation.get("tags").map(super::yaml_strings).unwrap_or_default();
+                if !tags.is_empty() {
+                    metadata.extra.insert("tags".to_string(), tags.join(", "));
+                }
+                metadata.doc_summary = operation
+                    .get("summary")
+                    .or_else(|| operation.get("description"))
+                    .and_then(|s| s.as_str())
+                    .map(|s| s.trim().to_string());
+                if operation.get("deprecated").and_then(|d| d.as_bool()) == Some(true) {
+                    metadata.deprecated = Some(String::new());
+                }
+
+                nodes.push(GraphNode {
+                    id: NodeId(0), // Will be set by graph
+                    kind: NodeKind::Route,
+                    name: name.clone(),
+                    qualified_name: format!("{}::{}", path.display(), name).into(),
+                    file_path: path.into(),
+                    line_start: Some(line_start),
+                    line_end: Some(line_end),
+                    language: Some(language),
+                    is_container: false,
+                    child_count: 0,
+                    loc: Some(line_end - line_start),
+                    metadata,
+                });
+            }
+        }
+
+        Ok(ExtractionResult { nodes, edges: Vec::new(), contains: Vec::new(), calls: Vec::new(), references: Vec::new(), errors: Vec::new() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_openapi() {
+        let code = r#"openapi: 3.0.0
+info:
+  title: Users
+paths:
+  /users/{id}:
+    get:
+      operationId: getUserById
+      summary: Fetch one user
+      tags: [users]
+    delete:
+      operationId: deleteUser
+      deprecated: true
+  /users:
+    post:
+      summary: Create a user
+"#;
+
+        let path = Path::new("api/users.yaml");
+        assert!(looks_like_openapi(code));
+        let result = OpenApiParser::new().extract(path, code.as_bytes()).unwrap();
+
+        let routes: Vec<_> = result.nodes.iter().map(|n| (n.name.as_str(), n.line_start, n.line_end)).collect();
+        assert_eq!(
+            routes,
+            vec![
+                ("GET /users/{id}", Some(6), Some(9)),
+                ("DELETE /users/{id}", Some(10), Some(12)),
+                ("POST /users", Some(14), Some(15)),
+            ]
+        );
+        let get = &result.nodes[0];
+        assert_eq!(get.kind, NodeKind::Route);
+        assert_eq!(get.metadata.extra[OPERATION_ID_KEY], "getUserById");
+        assert_eq!(get.metadata.extra["tags"], "users");
+        assert_eq!(get.metadata.doc_summary.as_deref(), Some("Fetch one user"));
+        assert_eq!(result.nodes[1].metadata.deprecated.as_deref(), Some(""));
+
+        let json = r#"{"swagger": "2.0", "paths": {"/health": {"get": {"operationId": "health"}}}}"#;
+        assert!(looks_like_openapi(json));
+        let result = OpenApiParser::new().extract(Path::new("swagger.json"), json.as_bytes()).unwrap();
+        assert_eq!(result.nodes[0].name, "GET /health");
+        assert_eq!(result.nodes[0].language, Some(Language::Json));
+
+        assert!(is_openapi_path(Path::new("docs/openapi.yml")));
+        assert!(is_openapi_path(Path::new("billing.openapi.json")));
+        assert!(!is_openapi_path(Path::new("config.yaml")));
+    }
+}
@@ -1 +1,197 @@
+//! TOML config parser
+//!
+//! Cargo manifests become a Package node carrying its dependency names, plus a
+//! `features` ConfigBlock with one ConfigKey per feature flag. Dependency
+//! edges between packages are added by [`crate::heuristics::packages`] once
+//! the other manifests are indexed. Other TOML files produce no nodes yet.
+
+use super::DEPENDENCIES_KEY;
+use crate::extractor::{ExtractionResult, LanguageExtractor};
+use canopy_core::{GraphNode, NodeKind, Language, NodeId, NodeMetadata};
+use std::path::Path;
+use anyhow::{Context, Result};
+
+#[derive(Default)]
 pub struct TomlParser;
+
+impl TomlParser {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// 1-based number of the first line after line `after` for which `matches`
+/// holds on the trimmed line.
+fn find_line(content: &str, after: u32, matches: impl Fn(&str) -> bool) -> Option<u32> {
+    content
+        .lines()
+        .enumerate()
+        .skip(after as usize)
+        .find(|(_, l)| matches(l.trim()))
+        .map(|(i, _)| i as u32 + 1)
+}
+
+/// Line of a `[table]` header.
+fn table_line(content: &str, table: &str) -> Option<u32> {
+    find_line(content, 0, |l| l.strip_prefix('[').and_then(|l| l.strip_suffix(']')).is_some_and(|t| t.trim() == table))
+}
+
+/// Line of `key = ...` after line `after`.
+fn key_line(content: &str, key: &str, after: u32) -> Option<u32> {
+    let quoted = format!("\"{}\"", key);
+    find_line(content, after, |l| {
+        l.strip_prefix(quoted.as_str()).or_else(|| l.strip_prefix(key)).is_some_and(|rest| rest.trim_start().starts_with('='))
+    })
+}
+
+fn make_node(path: &Path, kind: NodeKind, name: &str, qualified: String, lines: (u32, u32), metadata: NodeMetadata) -> GraphNode {
+    GraphNode {
+        id: NodeId(0), // Will be set by graph
+        kind,
+        name: name.to_string(),
+        qualified_name: qualified.into(),
+        file_path: path.into(),
+        line_start: Some(lines.0),
+        line_end: Some(lines.1),
+        language: Some(Language::Toml),
+        is_container: kind != NodeKind::ConfigKey,
+        child_count: 0,
+        loc: Some(lines.1 - lines.0),
+        metadata,
+    }
+}
+
+fn cargo_manifest(path: &Path, content: &str, manifest: &toml::Value) -> ExtractionResult {
+    let mut nodes = Vec::new();
+    let mut contains = Vec::new();
+    let last_line = content.lines().count().max(1) as u32;
+
+    let Some(name) = manifest.get("package").and_then(|p| p.get("name")).and_then(|n| n.as_str()) else {
+        return ExtractionResult { nodes, edges: Vec::new(), contains, calls: Vec::new(), references: Vec::new(), errors: Vec::new() };
+    };
+
+    let mut dependencies = Vec::new();
+    let mut tables: Vec<&toml::Value> = ["dependencies", "dev-dependencies", "build-dependencies"]
+        .iter()
+        .filter_map(|t| manifest.get(*t))
+        .collect();
+    // `[target.'cfg(unix)'.dependencies]`
+    if let Some(targets) = manifest.get("target").and_then(|t| t.as_table()) {
+        for target in targets.values() {
+            tables.extend(["dependencies", "dev-dependencies", "build-dependencies"].iter().filter_map(|t| target.get(*t)));
+        }
+    }
+    for deps in tables.into_iter().filter_map(|d| d.as_table()) {
+        for (key, value) in deps {
+            // `foo = { package = "real-name" }` renames a dependency
+            let real_name = value.get("package").and_then(|p| p.as_str()).unwrap_or(key);
+            if !dependencies.iter().any(|d| d == real_name) {
+                dependencies.push(real_name.to_string());
+            }
+        }
+    }
+
+    let mut metadata = NodeMetadata::with_extra([
+        ("manifest", path.display().to_string()),
+        (DEPENDENCIES_KEY, dependencies.join("\n")),
+    ]);
+    if let Some(version) = manifest.get("package").and_then(|p| p.get("version")).and_then(|v| v.as_str()) {
+        metadata.extra.insert("version".to_string(), version.to_string());
+    }
+    metadata.doc_summary = manifest.get("package").and_then(|p| p.get("description")).and_then(|d| d.as_str()).map(str::to_string);
+    let package_line = table_line(content, "package").unwrap_or(1);
+    // Same qualified name as workspace discovery, so both describe one package
+    nodes.push(make_node(path, NodeKind::Package, name, format!("package::{}", name), (package_line, last_line), metadata));
+
+    if let Some(features) = manifest.get("features").and_then(|f| f.as_table()) {
+        let features_line = table_line(content, "features").unwrap_or(package_line);
+        let features_end = find_line(content, features_line, |l| l.starts_with('[')).map(|l| l - 1).unwrap_or(last_line);
+        nodes.push(make_node(
+            path,
+            NodeKind::ConfigBlock,
+            "features",
+            format!("{}::features", path.display()),
+            (features_line, features_end),
+            NodeMetadata::default(),
+        ));
+        contains.push((0, 1));
+
+        for (feature, enables) in features {
+            let enables: Vec<&str> = enables.as_array().map(|a| a.iter().filter_map(|v| v.as_str()).collect()).unwrap_or_default();
+            let line = key_line(content, feature, features_line).unwrap_or(features_line);
+            let mut metadata = NodeMetadata::with_extra([("value", enables.join(", "))]);
+            metadata.extra.insert("feature_flag".to_string(), "true".to_string());
+            nodes.push(make_node(
+                path,
+                NodeKind::ConfigKey,
+                feature,
+                format!("{}::features.{}", path.display(), feature),
+                (line, line),
+                metadata,
+            ));
+            contains.push((1, nodes.len() - 1));
+        }
+    }
+
+    ExtractionResult { nodes, edges: Vec::new(), contains, calls: Vec::new(), references: Vec::new(), errors: Vec::new() }
+}
+
+impl LanguageExtractor for TomlParser {
+    fn extract(&self, path: &Path, content: &[u8]) -> Result<ExtractionResult> {
+        let source_code = std::str::from_utf8(content)?;
+        if path.file_name().is_none_or(|n| n != "Cargo.toml") {
+            return Ok(ExtractionResult { nodes: Vec::new(), edges: Vec::new(), contains: Vec::new(), calls: Vec::new(), references: Vec::new(), errors: Vec::new() });
+        }
+        let manifest: toml::Value = toml::from_str(source_code)
+            .with_context(|| format!("parsing {}", path.display()))?;
+        Ok(cargo_manifest(path, source_code, &manifest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cargo_manifest() {
+        let code = r#"[package]
+name = "canopy-server"
+version = "0.1.0"
+description = "HTTP and WebSocket server"
+
+[dependencies]
+canopy-core = { path = "../canopy-core" }
+axum = "0.7"
+json = { package = "serde_json", version = "1" }
+
+[target.'cfg(unix)'.dependencies]
+libc = "0.2"
+
+[features]
+default = ["tls"]
+tls = ["axum/tls"]
+
+[dev-dependencies]
+tempfile = "3"
+"#;
+
+        let path = Path::new("crates/canopy-server/Cargo.toml");
+        let result = TomlParser::new().extract(path, code.as_bytes()).unwrap();
+
+        let package = &result.nodes[0];
+        assert_eq!((package.kind, package.name.as_str()), (NodeKind::Package, "canopy-server"));
+        assert_eq!(package.qualified_name, "package::canopy-server");
+        assert_eq!(package.metadata.extra["version"], "0.1.0");
+        assert_eq!(package.metadata.doc_summary.as_deref(), Some("HTTP and WebSocket server"));
+        assert_eq!(package.metadata.extra[DEPENDENCIES_KEY], "axum\ncanopy-core\nserde_json\ntempfile\nlibc");
+
+        let features: Vec<_> = result.nodes[2..].iter().map(|n| (n.name.as_str(), n.line_start, n.metadata.extra["value"].as_str())).collect();
+        assert_eq!(features, vec![("default", Some(15), "tls"), ("tls", Some(16), "axum/tls")]);
+        assert_eq!((result.nodes[1].line_start, result.nodes[1].line_end), (Some(14), Some(17)));
+        assert_eq!(result.contains, vec![(0, 1), (1, 2), (1, 3)]);
+
+        // Other TOML files are ignored
+        let result = TomlParser::new().extract(Path::new("rustfmt.toml"), b"edition = \"2021\"").unwrap();
+        assert!(result.nodes.is_empty());
+    }
+}
@@ -1 +1,241 @@
-pub struct YamlParser;
+//! YAML config parser
+//!
+//! Mappings become ConfigBlock nodes and scalar entries ConfigKey nodes, with
+//! the nesting recorded as Contains pairs. Sequences of mappings (Kubernetes
+//! containers, CI steps) become one block per item, named by the item's
+//! `name` or `id` key when it has one. OpenAPI specs are handed to
+//! [`super::openapi`].
+
+use crate::extractor::{syntax_errors, ExtractionResult, LanguageExtractor};
+use crate::parser_pool::{ParserPool, ParseRequest, FileType};
+use canopy_core::{GraphNode, NodeKind, Language, NodeId, NodeMetadata};
+use std::path::Path;
+use tree_sitter::Node;
+use anyhow::Result;
+
+/// Files with more entries than this (lock files, fixtures) are truncated.
+const MAX_NODES: usize = 2000;
+/// Longest value kept in a ConfigKey's `value` entry.
+const MAX_VALUE_LEN: usize = 200;
+
+pub struct YamlParser {
+    parser_pool: ParserPool,
+}
+
+/// Where new nodes go while walking a document.
+struct Walk<'a> {
+    source: &'a [u8],
+    path: &'a Path,
+    nodes: Vec<GraphNode>,
+    contains: Vec<(usize, usize)>,
+}
+
+impl YamlParser {
+    pub fn new(parser_pool: ParserPool) -> Self {
+        Self { parser_pool }
+    }
+}
+
+/// The content of a `block_node`/`flow_node`, skipping anchors and tags.
+fn content(node: Node) -> Option<Node> {
+    if !matches!(node.kind(), "block_node" | "flow_node") {
+        return Some(node);
+    }
+    let mut cursor = node.walk();
+    let inner = node.named_children(&mut cursor).find(|c| !matches!(c.kind(), "anchor" | "tag"));
+    inner.and_then(content)
+}
+
+fn text<'a>(node: Node, source: &'a [u8]) -> &'a str {
+    node.utf8_text(source).unwrap_or("")
+}
+
+/// Scalar text without quotes.
+fn scalar(node: Node, source: &[u8]) -> String {
+    let raw = text(node, source).trim();
+    match node.kind() {
+        "double_quote_scalar" | "single_quote_scalar" => raw.get(1..raw.len().saturating_sub(1)).unwrap_or("").to_string(),
+        _ => raw.to_string(),
+    }
+}
+
+fn is_mapping(node: Node) -> bool {
+    matches!(node.kind(), "block_mapping" | "flow_mapping")
+}
+
+fn is_sequence(node: Node) -> bool {
+    matches!(node.kind(), "block_sequence" | "flow_sequence")
+}
+
+impl Walk<'_> {
+    fn push(&mut self, node: Node, kind: NodeKind, name: &str, qualified: &str, parent: Option<usize>, metadata: NodeMetadata) -> Option<usize> {
+        if self.nodes.len() >= MAX_NODES {
+            return None;
+        }
+        let start_pos = node.start_position().row as u32 + 1;
+        let end_pos = node.end_position().row as u32 + 1;
+        self.nodes.push(GraphNode {
+            id: NodeId(0), // Will be set by graph
+            kind,
+            name: name.to_string(),
+            qualified_name: format!("{}::{}", self.path.display(), qualified).into(),
+            file_path: self.path.into(),
+            line_start: Some(start_pos),
+            line_end: Some(end_pos),
+            language: Some(Language::Yaml),
+            is_container: kind == NodeKind::ConfigBlock,
+            child_count: 0,
+            loc: Some(end_pos - start_pos),
+            metadata,
+        });
+        let index = self.nodes.len() - 1;
+        if let Some(parent) = parent {
+            self.contains.push((parent, index));
+        }
+        Some(index)
+    }
+
+    /// Key/value pairs of a mapping.
+    fn pairs<'t>(&self, mapping: Node<'t>) -> Vec<(String, Node<'t>, Option<Node<'t>>)> {
+        let mut cursor = mapping.walk();
+        mapping
+            .named_children(&mut cursor)
+            .filter(|c| matches!(c.kind(), "block_mapping_pair" | "flow_pair"))
+            .filter_map(|pair| {
+                let key = content(pair.child_by_field_name("key")?)?;
+                let value = pair.child_by_field_name("value").and_then(content);
+                Some((scalar(key, self.source), pair, value))
+            })
+            .collect()
+    }
+
+    fn mapping(&mut self, mapping: Node, prefix: &str, parent: Option<usize>) {
+        for (key, pair, value) in self.pairs(mapping) {
+            let qualified = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+            self.entry(pair, value, &key, &qualified, parent);
+        }
+    }
+
+    fn entry(&mut self, node: Node, value: Option<Node>, name: &str, qualified: &str, parent: Option<usize>) {
+        match value {
+            Some(value) if is_mapping(value) => {
+                if let Some(index) = self.push(node, NodeKind::ConfigBlock, name, qualified, parent, NodeMetadata::default()) {
+                    self.mapping(value, qualified, Some(index));
+                }
+            }
+            Some(value) if is_sequence(value) && self.has_mapping_items(value) => {
+                let Some(index) = self.push(node, NodeKind::ConfigBlock, name, qualified, parent, NodeMetadata::default()) else {
+                    return;
+                };
+                let mut cursor = value.walk();
+                let items: Vec<_> = value.named_children(&mut cursor).collect();
+                for (i, item) in items.into_iter().enumerate() {
+                    let inner = if item.kind() == "block_sequence_item" { item.named_child(0).and_then(content) } else { content(item) };
+                    let label = inner
+                        .filter(|n| is_mapping(*n))
+                        .and_then(|m| self.pairs(m).into_iter().find(|(k, _, _)| k == "name" || k == "id"))
+                        .and_then(|(_, _, v)| v)
+                        .map(|v| scalar(v, self.source))
+                        .unwrap_or_else(|| format!("{}[{}]", name, i));
+                    self.entry(item, inner, &label, &format!("{}[{}]", qualified, i), Some(index));
+                }
+            }
+            _ => {
+                let raw = value.map(|v| if is_sequence(v) { text(v, self.source).to_string() } else { scalar(v, self.source) }).unwrap_or_default();
+                let value: String = raw.split_whitespace().collect::<Vec<_>>().join(" ").chars().take(MAX_VALUE_LEN).collect();
+                self.push(node, NodeKind::ConfigKey, name, qualified, parent, NodeMetadata::with_extra([("value", value)]));
+            }
+        }
+    }
+
+    fn has_mapping_items(&self, sequence: Node) -> bool {
+        let mut cursor = sequence.walk();
+        sequence.named_children(&mut cursor).any(|item| {
+            let inner = if item.kind() == "block_sequence_item" { item.named_child(0).and_then(content) } else { content(item) };
+            inner.is_some_and(is_mapping)
+        })
+    }
+}
+
+impl LanguageExtractor for YamlParser {
+    fn extract(&self, path: &Path, content_bytes: &[u8]) -> Result<ExtractionResult> {
+        let source_code = std::str::from_utf8(content_bytes)?;
+        if super::openapi::looks_like_openapi(source_code) {
+            return super::openapi::OpenApiParser::new().extract(path, content_bytes);
+        }
+
+        let request = ParseRequest {
+            file_type: FileType::Yaml,
+            content: source_code.to_string(),
+            path: path.to_path_buf(),
+        };
+        let parse_result = self.parser_pool.parse_blocking(request)?;
+        let root_node = parse_result.tree.root_node();
+
+        let mut walk = Walk { source: source_code.as_bytes(), path, nodes: Vec::new(), contains: Vec::new() };
+        let mut cursor = root_node.walk();
+        let documents: Vec<_> = root_node.named_children(&mut cursor).filter(|c| c.kind() == "document").collect();
+        let multi = documents.len() > 1;
+        for (i, document) in documents.into_iter().enumerate() {
+            let mut doc_cursor = document.walk();
+            let body = document.named_children(&mut doc_cursor).find_map(content);
+            if let Some(body) = body.filter(|b| is_mapping(*b)) {
+                // Keys in multi-document files get a `[n]` prefix to stay unique
+                let prefix = if multi { format!("[{}]", i) } else { String::new() };
+                walk.mapping(body, &prefix, None);
+            }
+        }
+
+        Ok(ExtractionResult { nodes: walk.nodes, edges: Vec::new(), contains: walk.contains, calls: Vec::new(), references: Vec::new(), errors: syntax_errors(root_node) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_yaml() {
+        let parser = YamlParser::new(crate::parser_pool::create_parser_pool());
+        let code = r#"server:
+  host: "0.0.0.0"
+  port: 8080
+database:
+  max_connections: 10
+  replicas: [a, b]
+containers:
+  - name: web
+    image: nginx
+  - image: redis
+"#;
+
+        let result = parser.extract(Path::new("config.yaml"), code.as_bytes()).unwrap();
+        let names: Vec<_> = result.nodes.iter().map(|n| (n.kind, n.qualified_name.to_string())).collect();
+        assert_eq!(
+            names,
+            vec![
+                (NodeKind::ConfigBlock, "config.yaml::server".to_string()),
+                (NodeKind::ConfigKey, "config.yaml::server.host".to_string()),
+                (NodeKind::ConfigKey, "config.yaml::server.port".to_string()),
+                (NodeKind::ConfigBlock, "config.yaml::database".to_string()),
+                (NodeKind::ConfigKey, "config.yaml::database.max_connections".to_string()),
+                (NodeKind::ConfigKey, "config.yaml::database.replicas".to_string()),
+                (NodeKind::ConfigBlock, "config.yaml::containers".to_string()),
+                (NodeKind::ConfigBlock, "config.yaml::containers[0]".to_string()),
+                (NodeKind::ConfigKey, "config.yaml::containers[0].name".to_string()),
+                (NodeKind::ConfigKey, "config.yaml::containers[0].image".to_string()),
+                (NodeKind::ConfigBlock, "config.yaml::containers[1]".to_string()),
+                (NodeKind::ConfigKey, "config.yaml::containers[1].image".to_string()),
+            ]
+        );
+        assert_eq!(result.nodes[1].metadata.extra["value"], "0.0.0.0");
+        assert_eq!(result.nodes[5].metadata.extra["value"], "[a, b]");
+        assert_eq!(result.nodes[7].name, "web");
+        assert_eq!(result.nodes[10].name, "containers[1]");
+        assert_eq!((result.nodes[0].line_start, result.nodes[0].line_end), (Some(1), Some(3)));
+        assert_eq!(
+            result.contains,
+            vec![(0, 1), (0, 2), (3, 4), (3, 5), (6, 7), (7, 8), (7, 9), (6, 10), (10, 11)]
+        );
+    }
+}
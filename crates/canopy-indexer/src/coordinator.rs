@@ -1,15 +1,412 @@
 //! Orchestrates parallel indexing
+//!
+//! Indexing runs in two phases. Each file is extracted on its own and its
+//! nodes added to the graph and the [`SymbolTable`]; once the files are in,
+//! [`Coordinator::resolve_references`] turns the references they make to
+//! one another into edges.
+//!
+//! [`Coordinator::run_full_index`] runs the first phase for every file of
+//! the graph at once: worker threads read and extract files while the
+//! calling thread merges their results into the graph.
 
+use crate::calls::callee_name;
+use crate::extractor::{ExtractionResult, SyntaxError};
+use crate::resolve::{resolve_call, resolve_import, resolve_type, ModuleIndex, Reference};
+use crate::project::ExtractionConfig;
+use crate::tree_cache::ParseTreeCache;
+use crate::walk::{SkipRules, SKIPPED_KEY};
 use anyhow::Result;
+use canopy_core::{EdgeId, EdgeKind, EdgeSource, Graph, GraphEdge, GraphNode, NodeId, NodeKind, SymbolTable};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-pub struct Coordinator;
+/// Confidence of a call resolved by name through the caller's imports.
+const CALL_CONFIDENCE: f32 = 0.8;
+
+/// Extracted files that may wait to be merged before workers block.
+const MERGE_QUEUE: usize = 64;
+
+/// What a full index added, for incremental updates to build on.
+#[derive(Debug, Default)]
+pub struct IndexReport {
+    /// The nodes extracted from each file.
+    pub file_nodes: HashMap<PathBuf, Vec<NodeId>>,
+    /// Files that could not be read or extracted, with the error.
+    pub failed: Vec<(PathBuf, String)>,
+    /// Files left unparsed by the [`SkipRules`], with why.
+    pub skipped: Vec<(PathBuf, String)>,
+    /// Files only partly indexed, with their syntax errors.
+    pub partial: Vec<(PathBuf, Vec<SyntaxError>)>,
+}
+
+/// Metadata key on File nodes that parsed with errors, holding where, e.g.
+/// 3-5, 12 (missing `;`).
+pub const PARSE_ERRORS_KEY: &str = "parse_errors";
+
+/// Record a file's syntax errors on its File node, clearing those of an
+/// earlier parse.
+pub fn mark_parse_errors(file: &mut GraphNode, errors: &[SyntaxError]) {
+    if errors.is_empty() {
+        file.metadata.extra.remove(PARSE_ERRORS_KEY);
+    } else {
+        let lines: Vec<String> = errors.iter().map(SyntaxError::to_string).collect();
+        file.metadata.extra.insert(PARSE_ERRORS_KEY.to_string(), lines.join(", "));
+    }
+}
+
+/// Lines in a file's content, the LOC of its File node.
+pub fn count_lines(content: &[u8]) -> u32 {
+    let newlines = content.iter().filter(|&&b| b == b'\n').count();
+    let unterminated = content.last().is_some_and(|&b| b != b'\n');
+    (newlines + usize::from(unterminated)) as u32
+}
+
+/// What became of a file read for a full index.
+enum Indexed {
+    /// Its extraction result, and how many lines it has.
+    Extracted(ExtractionResult, u32),
+    Skipped(String),
+}
+
+/// Extract a file's content, or take its result from the cache if the
+/// content is unchanged, and leave out what the config skips.
+fn extract(path: &Path, content: &[u8], extraction: &ExtractionConfig, cache: Option<&ParseTreeCache>) -> Result<ExtractionResult> {
+    let mut result = match cache.and_then(|cache| cache.get(path, content)) {
+        Some(result) => result,
+        None => {
+            let result = crate::languages::extract_file(path, content)?;
+            if let Some(cache) = cache
+                && let Err(e) = cache.put(path, content, &result)
+            {
+                tracing::warn!("Failed to cache {}: {}", path.display(), e);
+            }
+            result
+        }
+    };
+    extraction.apply(path, &mut result);
+    Ok(result)
+}
+
+/// Read and extract a file, unless the rules or extraction config skip it.
+/// Its size and name are checked before it is read.
+fn index_file(path: &Path, rules: &SkipRules, extraction: &ExtractionConfig, cache: Option<&ParseTreeCache>) -> Result<Indexed> {
+    if extraction.skips_file(path) {
+        return Ok(Indexed::Skipped("test file".to_string()));
+    }
+    let size = std::fs::metadata(path)?.len();
+    if let Some(reason) = rules.skip_by_name(path, size) {
+        return Ok(Indexed::Skipped(reason));
+    }
+    let content = std::fs::read(path)?;
+    if let Some(reason) = rules.skip_reason(path, &content) {
+        return Ok(Indexed::Skipped(reason));
+    }
+    Ok(Indexed::Extracted(extract(path, &content, extraction, cache)?, count_lines(&content)))
+}
+
+/// A file's extraction result once added to the graph.
+pub struct AddedFile {
+    /// The added nodes, with their graph IDs, in extraction order.
+    pub nodes: Vec<GraphNode>,
+    /// Containment, call and extractor-made edges among them.
+    pub edges: Vec<EdgeId>,
+    pub references: Vec<Reference>,
+    pub errors: Vec<SyntaxError>,
+}
+
+/// Add a file's extraction result to the graph: its nodes, and the
+/// containment and call edges between them. Its references are returned
+/// for [`Coordinator::register_file`].
+pub fn add_extraction(graph: &mut Graph, path: &Path, result: ExtractionResult) -> AddedFile {
+    let nodes: Vec<GraphNode> = result
+        .nodes
+        .into_iter()
+        .map(|mut node| {
+            node.id = graph.add_node(node.clone());
+            node
+        })
+        .collect();
+
+    let mut edges = Vec::new();
+    let local = result
+        .contains
+        .into_iter()
+        .map(|(parent, child)| (parent, child, EdgeKind::Contains, None))
+        .chain(result.calls.into_iter().map(|(caller, callee, line)| (caller, callee, EdgeKind::Calls, Some(line))));
+    for (source, target, kind, line) in local {
+        let (Some(source), Some(target)) = (nodes.get(source), nodes.get(target)) else {
+            continue;
+        };
+        edges.push(graph.add_edge(GraphEdge {
+            id: EdgeId(0), // Will be set by graph
+            source: source.id,
+            target: target.id,
+            kind,
+            edge_source: EdgeSource::Structural,
+            confidence: 1.0,
+            label: None,
+            file_path: Some(path.into()),
+            line,
+        }));
+    }
+    edges.extend(result.edges.into_iter().map(|edge| graph.add_edge(edge)));
+
+    AddedFile { nodes, edges, references: result.references, errors: result.errors }
+}
+
+/// A reference made by an indexed file, with the edge it resolved to.
+struct TrackedReference {
+    file: PathBuf,
+    /// The referring node, or `None` for the file's File node.
+    source: Option<NodeId>,
+    reference: Reference,
+    /// Source and target of the resolved edge.
+    resolved: Option<(NodeId, NodeId)>,
+}
+
+pub struct Coordinator {
+    symbols: SymbolTable,
+    references: Vec<TrackedReference>,
+    skip_rules: SkipRules,
+    extraction: ExtractionConfig,
+    tree_cache: Option<ParseTreeCache>,
+}
+
+impl Default for Coordinator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl Coordinator {
     pub fn new() -> Self {
-        Coordinator
+        Coordinator {
+            symbols: SymbolTable::new(),
+            references: Vec::new(),
+            skip_rules: SkipRules::default(),
+            extraction: ExtractionConfig::default(),
+            tree_cache: None,
+        }
+    }
+
+    /// Use `rules` to decide which files are too large, binary or
+    /// generated to parse.
+    pub fn with_skip_rules(mut self, rules: SkipRules) -> Self {
+        self.skip_rules = rules;
+        self
+    }
+
+    pub fn skip_rules(&self) -> &SkipRules {
+        &self.skip_rules
+    }
+
+    /// Leave the kinds of symbols and files `config` skips out of the graph.
+    pub fn with_extraction(mut self, config: ExtractionConfig) -> Self {
+        self.extraction = config;
+        self
+    }
+
+    pub fn extraction(&self) -> &ExtractionConfig {
+        &self.extraction
+    }
+
+    /// Reuse the extraction results `cache` holds for unchanged files, and
+    /// store those of the files extracted.
+    pub fn with_tree_cache(mut self, cache: ParseTreeCache) -> Self {
+        self.tree_cache = Some(cache);
+        self
+    }
+
+    /// Extract a file's content as a full index would, through the tree
+    /// cache and with the extraction config applied.
+    pub fn extract(&self, path: &Path, content: &[u8]) -> Result<ExtractionResult> {
+        extract(path, content, &self.extraction, self.tree_cache.as_ref())
+    }
+
+    /// Extract every File node of the graph that Canopy recognises, on
+    /// `workers` threads, add their symbols to the graph and link them:
+    /// references are resolved and the heuristics run once all are in.
+    /// Files the skip rules single out are marked with [`SKIPPED_KEY`], and
+    /// those with syntax errors with [`PARSE_ERRORS_KEY`].
+    pub fn run_full_index(&mut self, graph: &mut Graph, workers: usize) -> IndexReport {
+        let files: Vec<(NodeId, PathBuf)> = graph
+            .all_nodes()
+            .filter(|n| n.kind == NodeKind::File && crate::languages::is_indexable(&n.file_path))
+            .map(|n| (n.id, n.file_path.to_path_buf()))
+            .collect();
+
+        let mut report = IndexReport::default();
+        let next = AtomicUsize::new(0);
+        let (rules, extraction, cache) = (self.skip_rules.clone(), self.extraction.clone(), self.tree_cache.clone());
+        let (sender, receiver) = std::sync::mpsc::sync_channel::<(&(NodeId, PathBuf), Result<Indexed>)>(MERGE_QUEUE);
+        std::thread::scope(|scope| {
+            for _ in 0..workers.max(1) {
+                let (sender, files, next, rules, extraction, cache) =
+                    (sender.clone(), &files, &next, &rules, &extraction, cache.as_ref());
+                scope.spawn(move || {
+                    while let Some(file) = files.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        if sender.send((file, index_file(&file.1, rules, extraction, cache))).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            drop(sender);
+
+            for ((file_id, path), result) in receiver {
+                match result {
+                    Ok(Indexed::Extracted(result, lines)) => {
+                        let added = add_extraction(graph, path, result);
+                        self.register_file(path, &added.nodes, added.references);
+                        report.file_nodes.insert(path.clone(), added.nodes.iter().map(|n| n.id).collect());
+                        if let Some(node) = graph.node_mut(*file_id) {
+                            node.loc = Some(lines);
+                            mark_parse_errors(node, &added.errors);
+                        }
+                        if !added.errors.is_empty() {
+                            report.partial.push((path.clone(), added.errors));
+                        }
+                    }
+                    Ok(Indexed::Skipped(reason)) => {
+                        if let Some(node) = graph.node_mut(*file_id) {
+                            node.metadata.extra.insert(SKIPPED_KEY.to_string(), reason.clone());
+                        }
+                        report.skipped.push((path.clone(), reason));
+                    }
+                    Err(e) => report.failed.push((path.clone(), e.to_string())),
+                }
+            }
+        });
+
+        self.resolve_references(graph);
+        crate::heuristics::link_graph(graph);
+        report
+    }
+
+    pub fn symbols(&self) -> &SymbolTable {
+        &self.symbols
+    }
+
+    /// Record the symbols and references of a file whose nodes were just
+    /// added to the graph. `nodes` are the added nodes, with their graph IDs,
+    /// in the order they were extracted.
+    pub fn register_file(&mut self, path: &Path, nodes: &[GraphNode], references: Vec<Reference>) {
+        self.remove_file(path);
+        for node in nodes {
+            self.symbols.insert_node(node);
+        }
+        for reference in references {
+            let source = match reference.source {
+                Some(index) => match nodes.get(index) {
+                    Some(node) => Some(node.id),
+                    None => continue,
+                },
+                None => None,
+            };
+            self.references.push(TrackedReference {
+                file: path.to_path_buf(),
+                source,
+                reference,
+                resolved: None,
+            });
+        }
+    }
+
+    /// Forget a file's symbols and references before it is re-indexed or
+    /// after it is removed.
+    pub fn remove_file(&mut self, path: &Path) {
+        self.symbols.remove_file(&path.display().to_string());
+        self.references.retain(|r| r.file != path);
+    }
+
+    /// Add edges for the references that resolve, returning their IDs.
+    /// References whose edge has since been removed are resolved again and
+    /// unresolved ones retried, so this is safe to call after every update.
+    pub fn resolve_references(&mut self, graph: &mut Graph) -> Vec<EdgeId> {
+        let modules = ModuleIndex::new(graph);
+        let mut added = Vec::new();
+
+        // Node IDs are reused, so find every removed edge before adding any
+        for tracked in &mut self.references {
+            if let Some((source, target)) = tracked.resolved
+                && !graph.has_edge_between(source, target, tracked.reference.kind) {
+                tracked.resolved = None;
+            }
+        }
+
+        // Imports first: calls and type references resolve through the files
+        // their file imports
+        for scoped in [false, true] {
+            let imported = if scoped { self.imported_files(graph) } else { HashMap::new() };
+            for tracked in &mut self.references {
+                let kind = tracked.reference.kind;
+                let needs_imports = matches!(
+                    kind,
+                    EdgeKind::Calls | EdgeKind::TypeReference | EdgeKind::DependsOn | EdgeKind::RouteHandler
+                );
+                if needs_imports != scoped || tracked.resolved.is_some() {
+                    continue;
+                }
+                let Some(source) = tracked.source.or_else(|| modules.file(&tracked.file)) else {
+                    continue;
+                };
+                let name = &tracked.reference.target;
+                let files = imported.get(&tracked.file).map(Vec::as_slice).unwrap_or_default();
+                let scope = || files.iter().cloned().chain([tracked.file.clone()]).collect::<Vec<_>>();
+                // Qualified names may repeat across files, so a file's own
+                // symbols are found among its own
+                let own = |name: &str| self.symbols.lookup_in_file(&tracked.file.display().to_string(), name);
+                let target = match kind {
+                    EdgeKind::Calls => resolve_call(graph, &self.symbols, name, files),
+                    EdgeKind::TypeReference | EdgeKind::DependsOn => resolve_type(graph, &self.symbols, name, &scope()),
+                    EdgeKind::Exports => own(name),
+                    // A handler's qualified name, or an expression naming an
+                    // imported function or view class
+                    EdgeKind::RouteHandler => own(name).or_else(|| self.symbols.lookup(name)).or_else(|| {
+                        let handler = callee_name(name)?;
+                        resolve_call(graph, &self.symbols, handler, &scope())
+                            .or_else(|| resolve_type(graph, &self.symbols, handler, &scope()))
+                    }),
+                    _ if tracked.reference.local_first => modules
+                        .beside(&tracked.file, name)
+                        .or_else(|| resolve_import(graph, &self.symbols, &modules, &tracked.file, name)),
+                    _ => resolve_import(graph, &self.symbols, &modules, &tracked.file, name),
+                };
+                let Some(target) = target.filter(|&t| t != source) else {
+                    continue;
+                };
+
+                let calls = kind == EdgeKind::Calls;
+                if !graph.has_edge_between(source, target, kind) {
+                    added.push(graph.add_edge(GraphEdge {
+                        id: EdgeId(0), // Will be set by graph
+                        source,
+                        target,
+                        kind,
+                        edge_source: if calls { EdgeSource::Heuristic } else { EdgeSource::Structural },
+                        confidence: if calls { CALL_CONFIDENCE } else { 1.0 },
+                        label: Some(tracked.reference.label.clone()),
+                        file_path: Some(tracked.file.as_path().into()),
+                        line: tracked.reference.line,
+                    }));
+                }
+                tracked.resolved = Some((source, target));
+            }
+        }
+        added
     }
 
-    pub fn run_full_index(&self) -> Result<()> {
-        todo!("Implement full indexing")
+    /// The files and directories each file's resolved imports point into.
+    fn imported_files(&self, graph: &Graph) -> HashMap<PathBuf, Vec<PathBuf>> {
+        let mut imported: HashMap<PathBuf, Vec<PathBuf>> = HashMap::new();
+        let is_import = |kind| matches!(kind, EdgeKind::Imports | EdgeKind::Reexports);
+        for tracked in self.references.iter().filter(|r| is_import(r.reference.kind)) {
+            if let Some((_, target)) = tracked.resolved
+                && let Some(node) = graph.node(target) {
+                imported.entry(tracked.file.clone()).or_default().push(node.file_path.to_path_buf());
+            }
+        }
+        imported
     }
 }
@@ -1,14 +1,140 @@
 //! Language extractor trait definition
 
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
 use canopy_core::{GraphNode, GraphEdge};
+use serde::{Deserialize, Serialize};
+use tree_sitter::Node;
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct ExtractionResult {
     pub nodes: Vec<GraphNode>,
     pub edges: Vec<GraphEdge>,
+    /// Contains edges between nodes of this result, as (parent, child)
+    /// indexes into `nodes`.
+    pub contains: Vec<(usize, usize)>,
+    /// Calls between functions of this result, as (caller, callee, line)
+    /// indexes into `nodes`.
+    pub calls: Vec<crate::calls::LocalCall>,
+    /// References to symbols that may be defined in other files, resolved
+    /// once every file is extracted.
+    pub references: Vec<crate::resolve::Reference>,
+    /// Regions the parser could not make sense of. Symbols in or around
+    /// them may be missing, so a file with errors is only partly indexed.
+    pub errors: Vec<SyntaxError>,
+}
+
+impl ExtractionResult {
+    /// Keep only the nodes `keep` accepts. Children of a dropped node move
+    /// up to its nearest kept ancestor; calls and references from or to
+    /// dropped nodes go with them.
+    pub fn retain_nodes(&mut self, keep: impl Fn(&GraphNode) -> bool) {
+        let into: Vec<Option<usize>> = self.nodes.iter().enumerate().map(|(i, node)| keep(node).then_some(i)).collect();
+        self.merge_nodes(&into);
+    }
+
+    /// Merge each node `i` into node `into[i]`, which must map to itself,
+    /// or drop it if `None`. A merged node's calls and references move to
+    /// the node it merged into, which keeps its own parent; children of a
+    /// dropped node move up to its nearest kept ancestor.
+    pub fn merge_nodes(&mut self, into: &[Option<usize>]) {
+        let kept: Vec<bool> = into.iter().enumerate().map(|(i, &target)| target == Some(i)).collect();
+        if kept.iter().all(|&k| k) {
+            return;
+        }
+        let mut next = 0;
+        let index: Vec<Option<usize>> = kept
+            .iter()
+            .map(|&k| {
+                k.then(|| {
+                    next += 1;
+                    next - 1
+                })
+            })
+            .collect();
+        let resolve = |node: usize| into[node].and_then(|target| index[target]);
+        let parents: HashMap<usize, usize> = self.contains.iter().map(|&(parent, child)| (child, parent)).collect();
+        let kept_ancestor = |mut node: usize| {
+            while into[node].is_none() {
+                node = *parents.get(&node)?;
+            }
+            resolve(node)
+        };
+
+        let mut seen = HashSet::new();
+        self.contains = self
+            .contains
+            .iter()
+            .filter_map(|&(parent, child)| Some((kept_ancestor(parent)?, index[child]?)))
+            .filter(|&edge| seen.insert(edge))
+            .collect();
+        let mut seen = HashSet::new();
+        self.calls = self
+            .calls
+            .iter()
+            .filter_map(|&(caller, callee, line)| Some((resolve(caller)?, resolve(callee)?, line)))
+            .filter(|&call| seen.insert(call))
+            .collect();
+        self.references.retain_mut(|reference| match reference.source {
+            Some(source) => {
+                reference.source = resolve(source);
+                reference.source.is_some()
+            }
+            None => true,
+        });
+        let mut kept = kept.into_iter();
+        self.nodes.retain(|_| kept.next().unwrap_or(false));
+    }
+}
+
+/// A region of a file that failed to parse, by 1-based lines.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyntaxError {
+    pub line_start: u32,
+    pub line_end: u32,
+    /// The token the parser expected and inserted, if one was missing
+    /// rather than unexpected.
+    pub missing: Option<String>,
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line_start == self.line_end {
+            write!(f, "{}", self.line_start)?;
+        } else {
+            write!(f, "{}-{}", self.line_start, self.line_end)?;
+        }
+        match &self.missing {
+            Some(missing) => write!(f, " (missing `{}`)", missing),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The ERROR and MISSING nodes of a syntax tree, outermost first.
+pub fn syntax_errors(root: Node) -> Vec<SyntaxError> {
+    fn visit(node: Node, errors: &mut Vec<SyntaxError>) {
+        if node.is_error() || node.is_missing() {
+            errors.push(SyntaxError {
+                line_start: node.start_position().row as u32 + 1,
+                line_end: node.end_position().row as u32 + 1,
+                missing: node.is_missing().then(|| node.kind().to_string()),
+            });
+            return;
+        }
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor).filter(|c| c.has_error()) {
+            visit(child, errors);
+        }
+    }
+    let mut errors = Vec::new();
+    if root.has_error() {
+        visit(root, &mut errors);
+    }
+    errors
 }
 
 pub trait LanguageExtractor: Send + Sync {
-    fn extract(&self, path: &PathBuf, content: &[u8]) -> anyhow::Result<ExtractionResult>;
+    fn extract(&self, path: &Path, content: &[u8]) -> anyhow::Result<ExtractionResult>;
 }
@@ -0,0 +1,66 @@
+//! Link CI jobs to the paths whose changes trigger them
+
+use super::docs::normalize;
+use crate::config::github_actions::TRIGGER_PATHS_KEY;
+use canopy_core::{EdgeId, EdgeKind, EdgeSource, Graph, GraphEdge, NodeId, NodeKind};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// The literal directory or file a path filter starts with: `crates/**` →
+/// `crates`, `src/*.rs` → `src`. Negated and root-level globs have none.
+fn literal_prefix(pattern: &str) -> Option<PathBuf> {
+    if pattern.starts_with('!') {
+        return None;
+    }
+    let prefix: PathBuf = pattern
+        .split('/')
+        .take_while(|part| !part.contains(['*', '?', '[', '{']))
+        .collect();
+    (!prefix.as_os_str().is_empty()).then_some(prefix)
+}
+
+/// Add CITrigger edges from CIJob nodes to the directories and files named
+/// by their workflow's path filters, resolved against the repository root.
+/// Returns the IDs of the added edges.
+pub fn link_ci_triggers(graph: &mut Graph) -> Vec<EdgeId> {
+    let mut paths: HashMap<PathBuf, NodeId> = HashMap::new();
+    for node in graph.all_nodes().filter(|n| matches!(n.kind, NodeKind::Directory | NodeKind::File)) {
+        paths.insert(normalize(&node.file_path), node.id);
+    }
+
+    let mut links = Vec::new();
+    for job in graph.all_nodes().filter(|n| n.kind == NodeKind::CIJob) {
+        let Some(patterns) = job.metadata.extra.get(TRIGGER_PATHS_KEY) else {
+            continue;
+        };
+        // `.github/workflows/ci.yml` → repository root
+        let root = job.file_path.ancestors().nth(3).unwrap_or(Path::new(""));
+        for pattern in patterns.lines() {
+            let target = literal_prefix(pattern).and_then(|prefix| paths.get(&normalize(&root.join(prefix))));
+            if let Some(&target) = target {
+                links.push((job.id, target, pattern.to_string()));
+            }
+        }
+    }
+
+    let mut added = Vec::new();
+    for (job, target, pattern) in links {
+        if graph.has_edge_between(job, target, EdgeKind::CITrigger) {
+            continue;
+        }
+        let file_path = graph.node(job).map(|n| n.file_path.clone());
+        let line = graph.node(job).and_then(|n| n.line_start);
+        added.push(graph.add_edge(GraphEdge {
+            id: EdgeId(0),
+            source: job,
+            target,
+            kind: EdgeKind::CITrigger,
+            edge_source: EdgeSource::Structural,
+            confidence: 1.0,
+            label: Some(format!("triggered by {}", pattern)),
+            file_path,
+            line,
+        }));
+    }
+    added
+}
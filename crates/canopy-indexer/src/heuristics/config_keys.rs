@@ -1 +1,135 @@
-pub struct ConfigKeys;
+//! Link config keys to the code identifiers they configure
+//!
+//! Keys are also linked to the code reading the environment variable that
+//! overrides them, by the common convention of spelling the key's full path
+//! as the variable name (`database.maxConnections` → `DATABASE_MAX_CONNECTIONS`).
+
+use super::env_vars::ENV_READS_KEY;
+use canopy_core::{EdgeId, EdgeKind, EdgeSource, Graph, GraphEdge, NodeId, NodeKind};
+use std::collections::HashMap;
+
+/// Confidence of a link when the name is the only match.
+const UNIQUE_MATCH_CONFIDENCE: f32 = 0.6;
+/// Confidence of a key linked to a reader of the variable spelling its path.
+const ENV_OVERRIDE_CONFIDENCE: f32 = 0.7;
+/// Names shared by more identifiers than this are too ambiguous to link.
+const MAX_CANDIDATES: usize = 3;
+/// Shorter keys (`id`, `url`, `port`) are too generic to link.
+const MIN_KEY_LEN: usize = 5;
+/// Suffixes of structs that model a config block (`database` → `DatabaseConfig`).
+const BLOCK_SUFFIXES: &[&str] = &["", "config", "configuration", "settings", "options"];
+
+/// Case- and separator-insensitive form, so `max_connections`,
+/// `maxConnections` and `MAX-CONNECTIONS` compare equal.
+fn normalize(name: &str) -> String {
+    name.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+}
+
+/// Add ConfiguresArgument edges from ConfigKey nodes to fields, variables
+/// and constants with the same name, and from ConfigBlock nodes to structs
+/// or classes named after them. Returns the IDs of the added edges.
+pub fn link_config_keys(graph: &mut Graph) -> Vec<EdgeId> {
+    let mut identifiers: HashMap<String, Vec<NodeId>> = HashMap::new();
+    let mut types: HashMap<String, Vec<NodeId>> = HashMap::new();
+    for node in graph.all_nodes() {
+        match node.kind {
+            NodeKind::Field | NodeKind::Variable | NodeKind::Constant => {
+                identifiers.entry(normalize(&node.name)).or_default().push(node.id)
+            }
+            NodeKind::Struct | NodeKind::Class => types.entry(normalize(&node.name)).or_default().push(node.id),
+            _ => {}
+        }
+    }
+
+    let mut links = Vec::new();
+    for node in graph.all_nodes() {
+        let key = normalize(&node.name);
+        if key.len() < MIN_KEY_LEN {
+            continue;
+        }
+        let candidates: Vec<NodeId> = match node.kind {
+            NodeKind::ConfigKey | NodeKind::EnvVariable => identifiers.get(&key).cloned().unwrap_or_default(),
+            NodeKind::ConfigBlock => BLOCK_SUFFIXES
+                .iter()
+                .filter_map(|suffix| types.get(&format!("{}{}", key, suffix)))
+                .flatten()
+                .copied()
+                .collect(),
+            _ => continue,
+        };
+        if candidates.is_empty() || candidates.len() > MAX_CANDIDATES {
+            continue;
+        }
+        let confidence = UNIQUE_MATCH_CONFIDENCE / candidates.len() as f32;
+        for target in candidates {
+            links.push((node.id, target, confidence));
+        }
+    }
+
+    let mut added = Vec::new();
+    for (config, target, confidence) in links {
+        if graph.has_edge_between(config, target, EdgeKind::ConfiguresArgument) {
+            continue;
+        }
+        let label = graph.node(target).map(|n| format!("configures {}", n.name));
+        let file_path = graph.node(config).map(|n| n.file_path.clone());
+        let line = graph.node(config).and_then(|n| n.line_start);
+        added.push(graph.add_edge(GraphEdge {
+            id: EdgeId(0),
+            source: config,
+            target,
+            kind: EdgeKind::ConfiguresArgument,
+            edge_source: EdgeSource::Heuristic,
+            confidence,
+            label,
+            file_path,
+            line,
+        }));
+    }
+    added
+}
+
+/// Add EnvironmentBinding edges from ConfigKey nodes to the symbols whose
+/// `env_reads` name a variable spelling the key's full path. Returns the IDs
+/// of the added edges.
+pub fn link_env_overrides(graph: &mut Graph) -> Vec<EdgeId> {
+    let mut keys: HashMap<String, Vec<NodeId>> = HashMap::new();
+    for node in graph.all_nodes().filter(|n| n.kind == NodeKind::ConfigKey) {
+        let path = node.qualified_name.split_once("::").map_or(node.qualified_name.as_ref(), |(_, path)| path);
+        let path = normalize(path);
+        if path.len() >= MIN_KEY_LEN {
+            keys.entry(path).or_default().push(node.id);
+        }
+    }
+
+    let mut links = Vec::new();
+    for node in graph.all_nodes() {
+        for variable in node.metadata.extra.get(ENV_READS_KEY).into_iter().flat_map(|r| r.lines()) {
+            for &key in keys.get(&normalize(variable)).into_iter().flatten() {
+                links.push((key, node.id, variable.to_string()));
+            }
+        }
+    }
+
+    let mut added = Vec::new();
+    for (key, reader, variable) in links {
+        if graph.has_edge_between(key, reader, EdgeKind::EnvironmentBinding) {
+            continue;
+        }
+        let label = graph.node(reader).map(|n| format!("read as {} by {}", variable, n.name));
+        let file_path = graph.node(reader).map(|n| n.file_path.clone());
+        let line = graph.node(reader).and_then(|n| n.line_start);
+        added.push(graph.add_edge(GraphEdge {
+            id: EdgeId(0),
+            source: key,
+            target: reader,
+            kind: EdgeKind::EnvironmentBinding,
+            edge_source: EdgeSource::Heuristic,
+            confidence: ENV_OVERRIDE_CONFIDENCE,
+            label,
+            file_path,
+            line,
+        }));
+    }
+    added
+}
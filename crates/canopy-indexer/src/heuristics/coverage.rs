@@ -0,0 +1,42 @@
+//! Link symbols to the tests that exercise them
+//!
+//! A test covers what its body calls, constructs or names as a type; each
+//! such link from a TestCase node is mirrored as a TestedBy edge from the
+//! symbol back to the test, so coverage rolls up the containment tree.
+
+use canopy_core::{EdgeId, EdgeKind, Graph, GraphEdge, NodeKind};
+
+/// Edge kinds from a test body that count as exercising their target.
+const EXERCISED_BY: &[EdgeKind] = &[EdgeKind::Calls, EdgeKind::Instantiates, EdgeKind::TypeReference];
+
+/// Add TestedBy edges from the symbols tests call, instantiate or reference
+/// to those tests. Each carries the source and confidence of the link it
+/// mirrors. Returns the IDs of the added edges.
+pub fn link_tested_symbols(graph: &mut Graph) -> Vec<EdgeId> {
+    let is_kind = |graph: &Graph, id, kind| graph.node(id).is_some_and(|n| n.kind == kind);
+    let links: Vec<GraphEdge> = graph
+        .all_edges()
+        .filter(|e| EXERCISED_BY.contains(&e.kind))
+        .filter(|e| is_kind(graph, e.source, NodeKind::TestCase) && !is_kind(graph, e.target, NodeKind::TestCase))
+        .filter(|e| !graph.has_edge_between(e.target, e.source, EdgeKind::TestedBy))
+        .map(|e| GraphEdge {
+            id: EdgeId(0),
+            source: e.target,
+            target: e.source,
+            kind: EdgeKind::TestedBy,
+            edge_source: e.edge_source,
+            confidence: e.confidence,
+            label: graph.node(e.source).map(|test| format!("tested by {}", test.name)),
+            file_path: e.file_path.clone(),
+            line: e.line,
+        })
+        .collect();
+
+    let mut added = Vec::new();
+    for link in links {
+        if !graph.has_edge_between(link.source, link.target, EdgeKind::TestedBy) {
+            added.push(graph.add_edge(link));
+        }
+    }
+    added
+}
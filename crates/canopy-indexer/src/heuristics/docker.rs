@@ -1 +1,63 @@
-pub struct Docker;
+//! Link docker-compose services to mounted paths and to each other
+
+use super::docs::normalize;
+use crate::config::docker_compose::{DEPENDS_ON_KEY, MOUNTS_KEY};
+use canopy_core::{EdgeId, EdgeKind, EdgeSource, Graph, GraphEdge, NodeId, NodeKind};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Add DockerMount edges from DockerService nodes to the directories and
+/// files they bind-mount, resolved against the compose file's directory, and
+/// Imports edges to the services they `depends_on` in the same file. Returns
+/// the IDs of the added edges.
+pub fn link_compose_services(graph: &mut Graph) -> Vec<EdgeId> {
+    let mut paths: HashMap<PathBuf, NodeId> = HashMap::new();
+    let mut services: HashMap<(&Path, &str), NodeId> = HashMap::new();
+    for node in graph.all_nodes() {
+        match node.kind {
+            NodeKind::Directory | NodeKind::File => {
+                paths.insert(normalize(&node.file_path), node.id);
+            }
+            NodeKind::DockerService => {
+                services.insert((&*node.file_path, node.name.as_str()), node.id);
+            }
+            _ => {}
+        }
+    }
+
+    let mut links = Vec::new();
+    for service in graph.all_nodes().filter(|n| n.kind == NodeKind::DockerService) {
+        let dir = service.file_path.parent().unwrap_or(Path::new(""));
+        for mount in service.metadata.extra.get(MOUNTS_KEY).into_iter().flat_map(|m| m.lines()) {
+            if let Some(&target) = paths.get(&normalize(&dir.join(mount))) {
+                links.push((service.id, target, EdgeKind::DockerMount, format!("mounts {}", mount)));
+            }
+        }
+        for dependency in service.metadata.extra.get(DEPENDS_ON_KEY).into_iter().flat_map(|d| d.lines()) {
+            if let Some(&target) = services.get(&(&*service.file_path, dependency)) {
+                links.push((service.id, target, EdgeKind::Imports, format!("depends on {}", dependency)));
+            }
+        }
+    }
+
+    let mut added = Vec::new();
+    for (service, target, kind, label) in links {
+        if service == target || graph.has_edge_between(service, target, kind) {
+            continue;
+        }
+        let file_path = graph.node(service).map(|n| n.file_path.clone());
+        let line = graph.node(service).and_then(|n| n.line_start);
+        added.push(graph.add_edge(GraphEdge {
+            id: EdgeId(0),
+            source: service,
+            target,
+            kind,
+            edge_source: EdgeSource::Structural,
+            confidence: 1.0,
+            label: Some(label),
+            file_path,
+            line,
+        }));
+    }
+    added
+}
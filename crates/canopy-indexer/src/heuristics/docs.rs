@@ -0,0 +1,110 @@
+//! Link Markdown documents to the files and symbols they mention
+
+use crate::languages::markdown::MENTIONS_KEY;
+use canopy_core::{EdgeId, EdgeKind, EdgeSource, Graph, GraphEdge, Language, NodeId, NodeKind};
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+/// Confidence of a link to a mentioned file path.
+const PATH_CONFIDENCE: f32 = 0.9;
+/// Confidence of a link to a uniquely named symbol.
+const SYMBOL_CONFIDENCE: f32 = 0.7;
+/// Names shared by more symbols than this are too ambiguous to link.
+const MAX_CANDIDATES: usize = 3;
+/// Shorter names (`id`, `io`) match too much to be meaningful.
+const MIN_SYMBOL_LEN: usize = 3;
+
+fn is_symbol(kind: NodeKind) -> bool {
+    matches!(
+        kind,
+        NodeKind::Module | NodeKind::Class | NodeKind::Struct | NodeKind::Enum | NodeKind::Interface
+            | NodeKind::Function | NodeKind::Method | NodeKind::Constant | NodeKind::TypeAlias
+            | NodeKind::Trait | NodeKind::Macro | NodeKind::Union
+    )
+}
+
+/// Resolve `.` and `..` without touching the filesystem.
+pub(super) fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+/// Add SemanticReference edges from Document and Section nodes to the files
+/// and symbols named in their `mentions`. Relative paths are resolved against
+/// the document's directory first, then matched as a path suffix. Returns the
+/// IDs of the added edges.
+pub fn link_doc_references(graph: &mut Graph) -> Vec<EdgeId> {
+    let mut files: HashMap<PathBuf, NodeId> = HashMap::new();
+    let mut symbols: HashMap<&str, Vec<NodeId>> = HashMap::new();
+    for node in graph.all_nodes() {
+        if node.kind == NodeKind::File {
+            files.insert(normalize(&node.file_path), node.id);
+        } else if is_symbol(node.kind) && node.language != Some(Language::Markdown) && node.name.len() >= MIN_SYMBOL_LEN {
+            symbols.entry(node.name.as_str()).or_default().push(node.id);
+        }
+    }
+
+    let mut links = Vec::new();
+    for doc in graph.all_nodes().filter(|n| matches!(n.kind, NodeKind::Document | NodeKind::Section)) {
+        let Some(mentions) = doc.metadata.extra.get(MENTIONS_KEY) else {
+            continue;
+        };
+        let doc_dir = doc.file_path.parent().unwrap_or(Path::new(""));
+        for mention in mentions.lines() {
+            if mention.contains('/') || (mention.contains('.') && !mention.contains("::")) {
+                let relative = normalize(&doc_dir.join(mention));
+                let suffix = normalize(Path::new(mention.trim_start_matches("./").trim_start_matches("../")));
+                let target = files.get(&relative).copied().or_else(|| {
+                    let mut matches = files.iter().filter(|(p, _)| p.ends_with(&suffix));
+                    match (matches.next(), matches.next()) {
+                        (Some((_, &id)), None) => Some(id),
+                        _ => None,
+                    }
+                });
+                if let Some(target) = target {
+                    links.push((doc.id, target, PATH_CONFIDENCE, mention.to_string()));
+                    continue;
+                }
+            }
+
+            // `Graph::add_node`, `graph.add_node` → `add_node`
+            let name = mention.rsplit([':', '.']).next().unwrap_or(mention);
+            let Some(candidates) = symbols.get(name).filter(|c| c.len() <= MAX_CANDIDATES) else {
+                continue;
+            };
+            let confidence = SYMBOL_CONFIDENCE / candidates.len() as f32;
+            for &target in candidates {
+                links.push((doc.id, target, confidence, mention.to_string()));
+            }
+        }
+    }
+
+    let mut added = Vec::new();
+    for (doc, target, confidence, mention) in links {
+        if doc == target || graph.has_edge_between(doc, target, EdgeKind::SemanticReference) {
+            continue;
+        }
+        let file_path = graph.node(doc).map(|n| n.file_path.clone());
+        added.push(graph.add_edge(GraphEdge {
+            id: EdgeId(0),
+            source: doc,
+            target,
+            kind: EdgeKind::SemanticReference,
+            edge_source: EdgeSource::Heuristic,
+            confidence,
+            label: Some(format!("mentions {}", mention)),
+            file_path,
+            line: None,
+        }));
+    }
+    added
+}
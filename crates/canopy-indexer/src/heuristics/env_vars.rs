@@ -1 +1,110 @@
-pub struct EnvVars;
+//! Link environment variables to the code that reads them
+
+use crate::extractor::ExtractionResult;
+use canopy_core::{EdgeId, EdgeKind, EdgeSource, Graph, GraphEdge, NodeId, NodeKind};
+use regex::Regex;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::LazyLock;
+
+/// Metadata key holding the newline-separated variables a symbol reads.
+pub const ENV_READS_KEY: &str = "env_reads";
+
+/// `process.env.X`, `process.env["X"]`, `std::env::var("X")`,
+/// `os.environ["X"]`, `os.environ.get("X")`, `os.getenv("X")`,
+/// `os.Getenv("X")`, `System.getenv("X")`, `getenv("X")`.
+static ENV_READ: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(concat!(
+        r"process\.env\.([A-Za-z_][A-Za-z0-9_]*)",
+        r#"|process\.env\[\s*["'`]([A-Za-z_][A-Za-z0-9_]*)["'`]\s*\]"#,
+        r#"|\benv::var(?:_os)?\(\s*"([A-Za-z_][A-Za-z0-9_]*)""#,
+        r#"|\benv!\(\s*"([A-Za-z_][A-Za-z0-9_]*)""#,
+        r#"|os\.environ(?:\.get\(\s*|\[\s*)["']([A-Za-z_][A-Za-z0-9_]*)["']"#,
+        r#"|\b(?:os\.getenv|os\.Getenv|os\.LookupEnv|System\.getenv|getenv)\(\s*["']([A-Za-z_][A-Za-z0-9_]*)["']"#,
+    ))
+    .unwrap()
+});
+
+/// Variables read on each line of `source`, as (1-based line, name).
+pub fn find_env_reads(source: &str) -> Vec<(u32, String)> {
+    let mut reads = Vec::new();
+    for (row, line) in source.lines().enumerate() {
+        for caps in ENV_READ.captures_iter(line) {
+            if let Some(name) = caps.iter().skip(1).flatten().next() {
+                reads.push((row as u32 + 1, name.as_str().to_string()));
+            }
+        }
+    }
+    reads
+}
+
+/// Record in each function's `env_reads` metadata the variables read within
+/// its lines. Reads are attributed to the innermost enclosing function, so a
+/// method doesn't also count for its class.
+pub fn annotate_env_reads(result: &mut ExtractionResult, source: &str) {
+    let reads = find_env_reads(source);
+    if reads.is_empty() {
+        return;
+    }
+
+    let mut by_node: HashMap<usize, BTreeSet<String>> = HashMap::new();
+    for (line, name) in reads {
+        let innermost = result
+            .nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| matches!(n.kind, NodeKind::Function | NodeKind::Method | NodeKind::TestCase))
+            .filter(|(_, n)| n.line_start.is_some_and(|s| s <= line) && n.line_end.is_some_and(|e| e >= line))
+            .min_by_key(|(_, n)| n.line_end.unwrap_or(0) - n.line_start.unwrap_or(0))
+            .map(|(i, _)| i);
+        if let Some(index) = innermost {
+            by_node.entry(index).or_default().insert(name);
+        }
+    }
+    for (index, names) in by_node {
+        let names: Vec<_> = names.into_iter().collect();
+        result.nodes[index].metadata.extra.insert(ENV_READS_KEY.to_string(), names.join("\n"));
+    }
+}
+
+/// Add EnvironmentBinding edges from EnvVariable nodes to the symbols whose
+/// `env_reads` name them. Returns the IDs of the added edges.
+pub fn link_env_bindings(graph: &mut Graph) -> Vec<EdgeId> {
+    let mut variables: HashMap<&str, Vec<NodeId>> = HashMap::new();
+    for node in graph.all_nodes().filter(|n| n.kind == NodeKind::EnvVariable) {
+        variables.entry(node.name.as_str()).or_default().push(node.id);
+    }
+
+    let mut links = Vec::new();
+    for node in graph.all_nodes() {
+        let Some(reads) = node.metadata.extra.get(ENV_READS_KEY) else {
+            continue;
+        };
+        for name in reads.lines() {
+            for &variable in variables.get(name).into_iter().flatten() {
+                links.push((variable, node.id));
+            }
+        }
+    }
+
+    let mut added = Vec::new();
+    for (variable, reader) in links {
+        if graph.has_edge_between(variable, reader, EdgeKind::EnvironmentBinding) {
+            continue;
+        }
+        let label = graph.node(reader).map(|n| format!("read by {}", n.name));
+        let file_path = graph.node(reader).map(|n| n.file_path.clone());
+        let line = graph.node(reader).and_then(|n| n.line_start);
+        added.push(graph.add_edge(GraphEdge {
+            id: EdgeId(0),
+            source: variable,
+            target: reader,
+            kind: EdgeKind::EnvironmentBinding,
+            edge_source: EdgeSource::Heuristic,
+            confidence: 1.0,
+            label,
+            file_path,
+            line,
+        }));
+    }
+    added
+}
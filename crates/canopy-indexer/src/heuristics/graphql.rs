@@ -0,0 +1,76 @@
+//! Link GraphQL schema definitions to resolver code by name
+
+use canopy_core::{EdgeId, EdgeKind, EdgeSource, Graph, GraphEdge, Language, NodeId, NodeKind};
+use std::collections::HashMap;
+
+/// Confidence of a resolver link when the name is the only match.
+const UNIQUE_MATCH_CONFIDENCE: f32 = 0.8;
+/// Candidates beyond this many are treated as too ambiguous to link.
+const MAX_CANDIDATES: usize = 3;
+
+/// Lowercased name with resolver affixes removed, so `resolveUser`,
+/// `user_resolver`, `getUser` and `user` all match the field `user`.
+fn resolver_key(name: &str) -> String {
+    let lower = name.to_lowercase().replace('_', "");
+    let lower = lower.strip_prefix("resolve").unwrap_or(&lower);
+    let lower = lower.strip_suffix("resolver").unwrap_or(lower);
+    let lower = lower.strip_prefix("get").filter(|rest| !rest.is_empty()).unwrap_or(lower);
+    lower.to_string()
+}
+
+/// Add RouteHandler edges from GraphQL root fields (`Query.user`) to
+/// functions and methods that look like their resolvers, and from schema
+/// types to `<Type>Resolver` classes. Returns the IDs of the added edges.
+pub fn link_resolvers(graph: &mut Graph) -> Vec<EdgeId> {
+    let mut functions: HashMap<String, Vec<NodeId>> = HashMap::new();
+    let mut classes: HashMap<String, Vec<NodeId>> = HashMap::new();
+    for node in graph.all_nodes().filter(|n| n.language != Some(Language::GraphQL)) {
+        match node.kind {
+            NodeKind::Function | NodeKind::Method => functions.entry(resolver_key(&node.name)).or_default().push(node.id),
+            NodeKind::Class | NodeKind::Struct if node.name.to_lowercase().ends_with("resolver") => {
+                classes.entry(resolver_key(&node.name)).or_default().push(node.id)
+            }
+            _ => {}
+        }
+    }
+
+    let mut links = Vec::new();
+    for node in graph.all_nodes().filter(|n| n.language == Some(Language::GraphQL)) {
+        let candidates = match node.kind {
+            NodeKind::Function if node.metadata.extra.contains_key("graphql_root") => {
+                let field = node.name.rsplit('.').next().unwrap_or(&node.name);
+                functions.get(&resolver_key(field))
+            }
+            NodeKind::Class | NodeKind::Interface => classes.get(&node.name.to_lowercase()),
+            _ => None,
+        };
+        let Some(candidates) = candidates.filter(|c| c.len() <= MAX_CANDIDATES) else {
+            continue;
+        };
+        let confidence = UNIQUE_MATCH_CONFIDENCE / candidates.len() as f32;
+        for &resolver in candidates {
+            links.push((node.id, resolver, confidence));
+        }
+    }
+
+    let mut added = Vec::new();
+    for (field, resolver, confidence) in links {
+        if graph.has_edge_between(field, resolver, EdgeKind::RouteHandler) {
+            continue;
+        }
+        let label = graph.node(resolver).map(|n| format!("resolved by {}", n.name));
+        let file_path = graph.node(resolver).map(|n| n.file_path.clone());
+        added.push(graph.add_edge(GraphEdge {
+            id: EdgeId(0),
+            source: field,
+            target: resolver,
+            kind: EdgeKind::RouteHandler,
+            edge_source: EdgeSource::Heuristic,
+            confidence,
+            label,
+            file_path,
+            line: None,
+        }));
+    }
+    added
+}
@@ -0,0 +1,132 @@
+//! Link files by where they sit and what they are called
+//!
+//! A test file is named after the file it tests (`cart_test.go` →
+//! `cart.go`, `test_pricing.py` → `pricing.py`), next to it or under a test
+//! directory. A route module is named after the service it calls into
+//! (`routes/users.ts` → `services/users.ts`).
+
+use crate::test_cases::is_test_file;
+use canopy_core::{EdgeId, EdgeKind, EdgeSource, Graph, GraphEdge, NodeId, NodeKind};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Confidence of a test file linked to a file beside it.
+const NEARBY_TEST_CONFIDENCE: f32 = 0.9;
+/// Confidence of a test file linked to a file elsewhere when the name is the
+/// only match.
+const DISTANT_TEST_CONFIDENCE: f32 = 0.7;
+/// Confidence of a route module linked to a service when the name is the
+/// only match.
+const SERVICE_CONFIDENCE: f32 = 0.6;
+/// Names shared by more files than this are too ambiguous to link.
+const MAX_CANDIDATES: usize = 3;
+
+/// Directories holding route handlers and services.
+const ROUTE_DIRS: &[&str] = &["routes", "handlers", "controllers"];
+const SERVICE_DIRS: &[&str] = &["services"];
+/// Name suffixes of route and service modules (`users.routes.ts`,
+/// `user_service.py`, `UserController.java`).
+const MODULE_SUFFIXES: &[&str] = &["routes", "route", "router", "handlers", "handler", "controller", "services", "service"];
+
+/// The name of the file `name` tests, if it is named as a test file.
+fn tested_file_name(name: &str) -> Option<String> {
+    let (stem, extension) = name.split_once('.')?;
+    if let Some(base) = extension.strip_prefix("test.").or_else(|| extension.strip_prefix("spec.")) {
+        return Some(format!("{}.{}", stem, base));
+    }
+    let base = match extension {
+        "go" | "py" => stem.strip_suffix("_test").or_else(|| stem.strip_prefix("test_"))?,
+        "java" | "kt" => stem.strip_suffix("Tests").or_else(|| stem.strip_suffix("Test"))?,
+        _ => return None,
+    };
+    Some(format!("{}.{}", base, extension))
+}
+
+/// Whether `file` is in `test`'s directory, or in the directory above a
+/// `tests` or `__tests__` directory holding it.
+fn is_near(test: &Path, file: &Path) -> bool {
+    let (Some(dir), Some(test_dir)) = (file.parent(), test.parent()) else {
+        return false;
+    };
+    test_dir == dir || (test_dir.file_name().is_some_and(|n| n == "tests" || n == "__tests__") && test_dir.parent() == Some(dir))
+}
+
+fn in_dir(path: &Path, dirs: &[&str]) -> bool {
+    path.parent()
+        .is_some_and(|dir| dir.components().any(|c| c.as_os_str().to_str().is_some_and(|c| dirs.contains(&c))))
+}
+
+/// A route or service module's entity, case-, plural- and
+/// separator-insensitive: `users.routes.ts` and `UserService.java` are both
+/// `user`.
+fn module_entity(path: &Path) -> Option<String> {
+    let name = path.file_name()?.to_str()?;
+    let stem = name.split('.').next().unwrap_or(name);
+    let mut entity: String = stem.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect();
+    if let Some(suffix) = MODULE_SUFFIXES.iter().find(|s| entity.len() > s.len() && entity.ends_with(*s)) {
+        entity.truncate(entity.len() - suffix.len());
+    }
+    let entity = entity.strip_suffix('s').unwrap_or(&entity);
+    (!entity.is_empty() && entity != "index").then(|| entity.to_string())
+}
+
+/// Add TestedBy edges from files to the test files named after them, and
+/// DependsOn edges from route modules to the services named after them.
+/// Returns the IDs of the added edges.
+pub fn link_file_layout(graph: &mut Graph) -> Vec<EdgeId> {
+    let mut by_name: HashMap<&str, Vec<(NodeId, &Path)>> = HashMap::new();
+    let mut services: HashMap<String, Vec<NodeId>> = HashMap::new();
+    let files: Vec<_> = graph.all_nodes().filter(|n| n.kind == NodeKind::File).collect();
+    for file in files.iter().filter(|f| !is_test_file(&f.file_path)) {
+        by_name.entry(file.name.as_str()).or_default().push((file.id, &file.file_path));
+        if in_dir(&file.file_path, SERVICE_DIRS)
+            && let Some(entity) = module_entity(&file.file_path)
+        {
+            services.entry(entity).or_default().push(file.id);
+        }
+    }
+
+    let mut links = Vec::new();
+    for file in &files {
+        if is_test_file(&file.file_path) {
+            let name = tested_file_name(&file.name).unwrap_or_else(|| file.name.clone());
+            let candidates = by_name.get(name.as_str()).map(Vec::as_slice).unwrap_or_default();
+            let near: Vec<NodeId> =
+                candidates.iter().filter(|(_, path)| is_near(&file.file_path, path)).map(|&(id, _)| id).collect();
+            if let [source] = near[..] {
+                links.push((source, file.id, EdgeKind::TestedBy, NEARBY_TEST_CONFIDENCE));
+            } else if near.is_empty() && !candidates.is_empty() && candidates.len() <= MAX_CANDIDATES {
+                let confidence = DISTANT_TEST_CONFIDENCE / candidates.len() as f32;
+                links.extend(candidates.iter().map(|&(source, _)| (source, file.id, EdgeKind::TestedBy, confidence)));
+            }
+        } else if in_dir(&file.file_path, ROUTE_DIRS)
+            && let Some(candidates) = module_entity(&file.file_path).and_then(|entity| services.get(&entity))
+            && candidates.len() <= MAX_CANDIDATES
+        {
+            let confidence = SERVICE_CONFIDENCE / candidates.len() as f32;
+            links.extend(candidates.iter().map(|&service| (file.id, service, EdgeKind::DependsOn, confidence)));
+        }
+    }
+
+    let mut added = Vec::new();
+    for (source, target, kind, confidence) in links {
+        if source == target || graph.has_edge_between(source, target, kind) {
+            continue;
+        }
+        let (verb, anchor) = if kind == EdgeKind::TestedBy { ("tested by", target) } else { ("depends on", source) };
+        let label = graph.node(target).map(|n| format!("{} {}", verb, n.name));
+        let file_path = graph.node(anchor).map(|n| n.file_path.clone());
+        added.push(graph.add_edge(GraphEdge {
+            id: EdgeId(0),
+            source,
+            target,
+            kind,
+            edge_source: EdgeSource::Heuristic,
+            confidence,
+            label,
+            file_path,
+            line: None,
+        }));
+    }
+    added
+}
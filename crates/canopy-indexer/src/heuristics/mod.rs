@@ -1,6 +1,38 @@
-//! Heuristics for config-to-code linking
+//! Heuristics for config-to-code linking, and for relationships implied by
+//! naming conventions and project layout
 
 pub mod env_vars;
 pub mod config_keys;
 pub mod routes;
 pub mod docker;
+pub mod ci;
+pub mod coverage;
+pub mod docs;
+pub mod graphql;
+pub mod layout;
+pub mod naming;
+pub mod packages;
+pub mod receivers;
+
+use canopy_core::{EdgeId, Graph};
+
+/// Run the graph-wide linking passes, returning the IDs of added edges.
+/// Each pass skips links that already exist, so this is safe to call after
+/// every update.
+pub fn link_graph(graph: &mut Graph) -> Vec<EdgeId> {
+    let mut added = graphql::link_resolvers(graph);
+    added.extend(docs::link_doc_references(graph));
+    added.extend(config_keys::link_config_keys(graph));
+    added.extend(config_keys::link_env_overrides(graph));
+    added.extend(packages::link_package_dependencies(graph));
+    added.extend(packages::link_scripts(graph));
+    added.extend(env_vars::link_env_bindings(graph));
+    added.extend(docker::link_compose_services(graph));
+    added.extend(ci::link_ci_triggers(graph));
+    added.extend(routes::link_route_handlers(graph));
+    added.extend(receivers::link_receivers(graph));
+    added.extend(coverage::link_tested_symbols(graph));
+    added.extend(naming::link_layers(graph));
+    added.extend(layout::link_file_layout(graph));
+    added
+}
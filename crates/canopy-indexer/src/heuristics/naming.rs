@@ -0,0 +1,83 @@
+//! Link types to the next layer's type for the same entity
+//!
+//! Layered code names each layer after the entity it serves: a
+//! `UserController` hands off to `UserService`, which loads through
+//! `UserRepository`. Types are matched by name alone, within a language.
+
+use canopy_core::{EdgeId, EdgeKind, EdgeSource, Graph, GraphEdge, Language, NodeId, NodeKind};
+use std::collections::HashMap;
+
+/// Type name suffixes of each layer, outermost first.
+const LAYERS: &[&[&str]] = &[
+    &["Controller", "Handler", "Resource", "Endpoint"],
+    &["Service", "Manager", "UseCase"],
+    &["Repository", "Repo", "Dao", "Store"],
+];
+
+/// Confidence of a link to the adjacent layer when the name is the only match.
+const NEXT_LAYER_CONFIDENCE: f32 = 0.7;
+/// Confidence of a link skipping a layer the entity has no type for.
+const SKIPPED_LAYER_CONFIDENCE: f32 = 0.5;
+/// Entities with more types in a layer than this are too ambiguous to link.
+const MAX_CANDIDATES: usize = 3;
+
+/// The layer and entity of a type name: `UserServiceImpl` is layer 1 of
+/// `user`.
+fn layer_of(name: &str) -> Option<(usize, String)> {
+    let name = name.strip_suffix("Impl").unwrap_or(name);
+    LAYERS.iter().enumerate().find_map(|(layer, suffixes)| {
+        let entity = suffixes.iter().find_map(|suffix| name.strip_suffix(suffix))?;
+        let entity: String = entity.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect();
+        (!entity.is_empty()).then_some((layer, entity))
+    })
+}
+
+/// Add DependsOn edges from each layer's classes, structs and interfaces to
+/// those of the nearest inner layer named for the same entity. Returns the
+/// IDs of the added edges.
+pub fn link_layers(graph: &mut Graph) -> Vec<EdgeId> {
+    let mut layers: HashMap<(Option<Language>, String, usize), Vec<NodeId>> = HashMap::new();
+    let mut types = Vec::new();
+    for node in graph.all_nodes().filter(|n| matches!(n.kind, NodeKind::Class | NodeKind::Struct | NodeKind::Interface)) {
+        if let Some((layer, entity)) = layer_of(&node.name) {
+            layers.entry((node.language, entity.clone(), layer)).or_default().push(node.id);
+            types.push((node.id, node.language, entity, layer));
+        }
+    }
+
+    let mut links = Vec::new();
+    for (source, language, entity, layer) in types {
+        let Some((inner, candidates)) =
+            (layer + 1..LAYERS.len()).find_map(|inner| Some((inner, layers.get(&(language, entity.clone(), inner))?)))
+        else {
+            continue;
+        };
+        if candidates.len() > MAX_CANDIDATES {
+            continue;
+        }
+        let confidence = if inner == layer + 1 { NEXT_LAYER_CONFIDENCE } else { SKIPPED_LAYER_CONFIDENCE };
+        links.extend(candidates.iter().map(|&target| (source, target, confidence / candidates.len() as f32)));
+    }
+
+    let mut added = Vec::new();
+    for (source, target, confidence) in links {
+        if graph.has_edge_between(source, target, EdgeKind::DependsOn) {
+            continue;
+        }
+        let label = graph.node(target).map(|n| format!("depends on {}", n.name));
+        let file_path = graph.node(source).map(|n| n.file_path.clone());
+        let line = graph.node(source).and_then(|n| n.line_start);
+        added.push(graph.add_edge(GraphEdge {
+            id: EdgeId(0),
+            source,
+            target,
+            kind: EdgeKind::DependsOn,
+            edge_source: EdgeSource::Heuristic,
+            confidence,
+            label,
+            file_path,
+            line,
+        }));
+    }
+    added
+}
@@ -0,0 +1,131 @@
+//! Link package manifests to the workspace packages they depend on, and
+//! package scripts and CI jobs to what they run
+
+use super::docs::normalize;
+use crate::config::DEPENDENCIES_KEY;
+use crate::config::github_actions::WORKING_DIR_KEY;
+use crate::config::json::INVOKES_KEY;
+use canopy_core::{EdgeId, EdgeKind, EdgeSource, Graph, GraphEdge, NodeId, NodeKind};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Confidence of a link to a script file named in a command.
+const FILE_CONFIDENCE: f32 = 0.9;
+/// Confidence of a link from a binary name to the workspace package of that name.
+const BIN_CONFIDENCE: f32 = 0.7;
+
+/// Add Imports edges from Package nodes to the Package nodes named in their
+/// `dependencies` metadata. Only packages present in the graph are linked, so
+/// external crates and npm modules are left out. Returns the IDs of the
+/// added edges.
+pub fn link_package_dependencies(graph: &mut Graph) -> Vec<EdgeId> {
+    let mut packages: HashMap<&str, Vec<NodeId>> = HashMap::new();
+    for node in graph.all_nodes().filter(|n| n.kind == NodeKind::Package) {
+        packages.entry(node.name.as_str()).or_default().push(node.id);
+    }
+
+    let mut links = Vec::new();
+    for node in graph.all_nodes().filter(|n| n.kind == NodeKind::Package) {
+        let Some(dependencies) = node.metadata.extra.get(DEPENDENCIES_KEY) else {
+            continue;
+        };
+        for dependency in dependencies.lines() {
+            for &target in packages.get(dependency).into_iter().flatten() {
+                if target != node.id {
+                    links.push((node.id, target, dependency.to_string()));
+                }
+            }
+        }
+    }
+
+    let mut added = Vec::new();
+    for (package, target, dependency) in links {
+        if graph.has_edge_between(package, target, EdgeKind::Imports) {
+            continue;
+        }
+        let file_path = graph.node(package).map(|n| n.file_path.clone());
+        added.push(graph.add_edge(GraphEdge {
+            id: EdgeId(0),
+            source: package,
+            target,
+            kind: EdgeKind::Imports,
+            edge_source: EdgeSource::Structural,
+            confidence: 1.0,
+            label: Some(format!("depends on {}", dependency)),
+            file_path,
+            line: None,
+        }));
+    }
+    added
+}
+
+/// Add Calls edges from Script and CIJob nodes to the scripts, files and
+/// workspace packages named in their `invokes` metadata. Scripts and files
+/// resolve against the script's manifest directory, or the job's working
+/// directory. Returns the IDs of the added edges.
+pub fn link_scripts(graph: &mut Graph) -> Vec<EdgeId> {
+    let mut files: HashMap<PathBuf, NodeId> = HashMap::new();
+    // Keyed by the directory of the package.json defining them
+    let mut scripts: HashMap<(&Path, &str), NodeId> = HashMap::new();
+    let mut packages: HashMap<&str, NodeId> = HashMap::new();
+    for node in graph.all_nodes() {
+        match node.kind {
+            NodeKind::File => {
+                files.insert(normalize(&node.file_path), node.id);
+            }
+            NodeKind::Script => {
+                let dir = node.file_path.parent().unwrap_or(Path::new(""));
+                scripts.insert((dir, node.name.as_str()), node.id);
+            }
+            NodeKind::Package => {
+                packages.insert(node.name.as_str(), node.id);
+            }
+            _ => {}
+        }
+    }
+
+    let mut links = Vec::new();
+    for script in graph.all_nodes().filter(|n| matches!(n.kind, NodeKind::Script | NodeKind::CIJob)) {
+        let Some(invokes) = script.metadata.extra.get(INVOKES_KEY) else {
+            continue;
+        };
+        let dir = match script.metadata.extra.get(WORKING_DIR_KEY) {
+            Some(dir) => Path::new(dir),
+            None => script.file_path.parent().unwrap_or(Path::new("")),
+        };
+        for invoke in invokes.lines() {
+            let target = match invoke.split_once(':') {
+                Some(("script", name)) => scripts.get(&(dir, name)).map(|&id| (id, 1.0)),
+                Some(("file", file)) => files.get(&normalize(&dir.join(file))).map(|&id| (id, FILE_CONFIDENCE)),
+                Some(("bin", bin)) => packages.get(bin).map(|&id| (id, BIN_CONFIDENCE)),
+                Some(("package", package)) => packages.get(package).map(|&id| (id, 1.0)),
+                _ => None,
+            };
+            if let Some((target, confidence)) = target.filter(|&(id, _)| id != script.id) {
+                let name = invoke.split_once(':').map_or(invoke, |(_, n)| n);
+                links.push((script.id, target, confidence, name.to_string()));
+            }
+        }
+    }
+
+    let mut added = Vec::new();
+    for (script, target, confidence, name) in links {
+        if graph.has_edge_between(script, target, EdgeKind::Calls) {
+            continue;
+        }
+        let file_path = graph.node(script).map(|n| n.file_path.clone());
+        let line = graph.node(script).and_then(|n| n.line_start);
+        added.push(graph.add_edge(GraphEdge {
+            id: EdgeId(0),
+            source: script,
+            target,
+            kind: EdgeKind::Calls,
+            edge_source: EdgeSource::Heuristic,
+            confidence,
+            label: Some(format!("runs {}", name)),
+            file_path,
+            line,
+        }));
+    }
+    added
+}
@@ -0,0 +1,80 @@
+//! Link Go methods to their receiver types, and types to the interfaces
+//! their method sets satisfy
+//!
+//! Go methods may be declared in any file of their type's package, so both
+//! links are made graph-wide, within a package directory for receivers.
+
+use canopy_core::{EdgeId, EdgeKind, EdgeSource, Graph, GraphEdge, Language, NodeId, NodeKind};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+/// Metadata key on Go methods holding their receiver's type name.
+pub const RECEIVER_KEY: &str = "receiver";
+/// Metadata key on Go interfaces holding their method names, newline-separated.
+pub const METHODS_KEY: &str = "methods";
+
+/// Confidence of an interface satisfied by method names alone, without
+/// comparing signatures.
+const IMPLEMENTS_CONFIDENCE: f32 = 0.8;
+
+fn package(path: &Path) -> &Path {
+    path.parent().unwrap_or(Path::new(""))
+}
+
+/// Add Contains edges from Go structs to the methods declared on them and
+/// Implements edges from structs to the interfaces whose methods they all
+/// have. Returns the IDs of the added edges.
+pub fn link_receivers(graph: &mut Graph) -> Vec<EdgeId> {
+    let is_go = |language: Option<Language>| language == Some(Language::Go);
+    let mut structs: HashMap<(&Path, &str), NodeId> = HashMap::new();
+    for node in graph.all_nodes().filter(|n| n.kind == NodeKind::Struct && is_go(n.language)) {
+        structs.insert((package(&node.file_path), node.name.as_str()), node.id);
+    }
+
+    let mut links = Vec::new();
+    let mut method_sets: HashMap<NodeId, HashSet<&str>> = HashMap::new();
+    for node in graph.all_nodes().filter(|n| n.kind == NodeKind::Method && is_go(n.language)) {
+        let Some(receiver) = node.metadata.extra.get(RECEIVER_KEY) else {
+            continue;
+        };
+        if let Some(&owner) = structs.get(&(package(&node.file_path), receiver.as_str())) {
+            links.push((owner, node.id, EdgeKind::Contains, EdgeSource::Structural, 1.0, None));
+            method_sets.entry(owner).or_default().insert(node.name.as_str());
+        }
+    }
+
+    for interface in graph.all_nodes().filter(|n| n.kind == NodeKind::Interface && is_go(n.language)) {
+        let Some(methods) = interface.metadata.extra.get(METHODS_KEY) else {
+            continue;
+        };
+        for (&owner, set) in &method_sets {
+            if methods.lines().all(|m| set.contains(m)) {
+                let label = Some(format!("implements {}", interface.name));
+                links.push((owner, interface.id, EdgeKind::Implements, EdgeSource::Heuristic, IMPLEMENTS_CONFIDENCE, label));
+            }
+        }
+    }
+
+    let mut added = Vec::new();
+    for (source, target, kind, edge_source, confidence, label) in links {
+        if graph.has_edge_between(source, target, kind) {
+            continue;
+        }
+        // A method's link belongs to its declaration, an implementation's to the type
+        let anchor = if kind == EdgeKind::Contains { target } else { source };
+        let file_path = graph.node(anchor).map(|n| n.file_path.clone());
+        let line = graph.node(anchor).and_then(|n| n.line_start);
+        added.push(graph.add_edge(GraphEdge {
+            id: EdgeId(0),
+            source,
+            target,
+            kind,
+            edge_source,
+            confidence,
+            label,
+            file_path,
+            line,
+        }));
+    }
+    added
+}
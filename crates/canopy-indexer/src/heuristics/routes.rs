@@ -1 +1,148 @@
-pub struct Routes;
+//! Link Route nodes to the functions that handle them
+
+use crate::resolve::Reference;
+use canopy_core::{EdgeId, EdgeKind, EdgeSource, Graph, GraphEdge, GraphNode, Language, NodeId, NodeKind, NodeMetadata};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Metadata key holding a route's HTTP method (`GET`).
+pub const METHOD_KEY: &str = "method";
+/// Metadata key holding a route's path template (`/users/{id}`).
+pub const PATH_KEY: &str = "path";
+/// Metadata key holding a route's OpenAPI operationId.
+pub const OPERATION_ID_KEY: &str = "operation_id";
+/// Metadata key on handler functions holding the routes their decorators or
+/// attributes register, newline-separated as `METHOD /path`.
+pub const ROUTE_KEY: &str = "route";
+
+/// Confidence of a link through a handler's own route registration.
+const ROUTE_CONFIDENCE: f32 = 0.95;
+/// Confidence of a link by operationId when the name is the only match.
+const OPERATION_ID_CONFIDENCE: f32 = 0.8;
+/// Names shared by more functions than this are too ambiguous to link.
+const MAX_CANDIDATES: usize = 3;
+
+/// Case- and separator-insensitive form, so `getUserById` matches
+/// `get_user_by_id`.
+fn normalize_name(name: &str) -> String {
+    name.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+}
+
+/// `METHOD /path` with parameters in any framework's syntax (`{id}`, `:id`,
+/// `<int:id>`) reduced to `{}` and trailing slashes dropped.
+pub fn normalize_route(route: &str) -> String {
+    let (method, path) = route.trim().split_once(' ').unwrap_or(("", route.trim()));
+    let segments: Vec<String> = path
+        .trim()
+        .trim_end_matches('/')
+        .split('/')
+        .map(|segment| {
+            let is_param = (segment.starts_with('{') && segment.ends_with('}'))
+                || (segment.starts_with('<') && segment.ends_with('>'))
+                || segment.starts_with(':');
+            if is_param { "{}".to_string() } else { segment.to_string() }
+        })
+        .collect();
+    format!("{} {}", method.to_uppercase(), segments.join("/"))
+}
+
+/// A Route node for `route` (`METHOD /path`) declared at `line` of `file`.
+pub fn route_node(route: &str, file: &Path, line: Option<u32>, language: Option<Language>) -> GraphNode {
+    let (method, path) = route.split_once(' ').unwrap_or(("ANY", route));
+    GraphNode {
+        id: NodeId(0), // Will be set by graph
+        kind: NodeKind::Route,
+        name: route.to_string(),
+        qualified_name: format!("{}::{}", file.display(), route).into(),
+        file_path: file.into(),
+        line_start: line,
+        line_end: line,
+        language,
+        is_container: false,
+        child_count: 0,
+        loc: Some(1),
+        metadata: NodeMetadata::with_extra([(METHOD_KEY, method.to_string()), (PATH_KEY, path.to_string())]),
+    }
+}
+
+/// Append a Route node for each route registered in `nodes`' `route`
+/// metadata, returning references linking each route to its handler.
+pub fn route_nodes(nodes: &mut Vec<GraphNode>) -> Vec<Reference> {
+    let mut routes = Vec::new();
+    let mut references = Vec::new();
+    for handler in nodes.iter() {
+        for route in handler.metadata.extra.get(ROUTE_KEY).into_iter().flat_map(|r| r.lines()) {
+            references.push(Reference {
+                source: Some(nodes.len() + routes.len()),
+                kind: EdgeKind::RouteHandler,
+                target: handler.qualified_name.to_string(),
+                label: format!("handled by {}", handler.name),
+                line: handler.line_start,
+                local_first: false,
+            });
+            routes.push(route_node(route, &handler.file_path, handler.line_start, handler.language));
+        }
+    }
+    nodes.extend(routes);
+    references
+}
+
+/// Add RouteHandler edges from Route nodes to the functions and methods that
+/// handle them: those whose `route` metadata registers the same method and
+/// path, else those named after the route's operationId. Returns the IDs of
+/// the added edges.
+pub fn link_route_handlers(graph: &mut Graph) -> Vec<EdgeId> {
+    let mut registered: HashMap<String, Vec<(NodeId, PathBuf)>> = HashMap::new();
+    let mut functions: HashMap<String, Vec<NodeId>> = HashMap::new();
+    for node in graph.all_nodes().filter(|n| matches!(n.kind, NodeKind::Function | NodeKind::Method)) {
+        for route in node.metadata.extra.get(ROUTE_KEY).into_iter().flat_map(|r| r.lines()) {
+            registered.entry(normalize_route(route)).or_default().push((node.id, node.file_path.to_path_buf()));
+        }
+        functions.entry(normalize_name(&node.name)).or_default().push(node.id);
+    }
+
+    let mut links = Vec::new();
+    for route in graph.all_nodes().filter(|n| n.kind == NodeKind::Route) {
+        let (Some(method), Some(path)) = (route.metadata.extra.get(METHOD_KEY), route.metadata.extra.get(PATH_KEY)) else {
+            continue;
+        };
+        if let Some(handlers) = registered.get(&normalize_route(&format!("{} {}", method, path))) {
+            // Routes declared by a handler in their own file are linked
+            // structurally when the file is resolved
+            if !handlers.iter().any(|(_, file)| *route.file_path == *file) {
+                links.extend(handlers.iter().map(|&(h, _)| (route.id, h, ROUTE_CONFIDENCE)));
+            }
+            continue;
+        }
+        let Some(operation_id) = route.metadata.extra.get(OPERATION_ID_KEY) else {
+            continue;
+        };
+        let Some(candidates) = functions.get(&normalize_name(operation_id)).filter(|c| c.len() <= MAX_CANDIDATES) else {
+            continue;
+        };
+        let confidence = OPERATION_ID_CONFIDENCE / candidates.len() as f32;
+        links.extend(candidates.iter().map(|&h| (route.id, h, confidence)));
+    }
+
+    let mut added = Vec::new();
+    for (route, handler, confidence) in links {
+        if graph.has_edge_between(route, handler, EdgeKind::RouteHandler) {
+            continue;
+        }
+        let label = graph.node(handler).map(|n| format!("handled by {}", n.name));
+        let file_path = graph.node(route).map(|n| n.file_path.clone());
+        let line = graph.node(route).and_then(|n| n.line_start);
+        added.push(graph.add_edge(GraphEdge {
+            id: EdgeId(0),
+            source: route,
+            target: handler,
+            kind: EdgeKind::RouteHandler,
+            edge_source: EdgeSource::Heuristic,
+            confidence,
+            label,
+            file_path,
+            line,
+        }));
+    }
+    added
+}
@@ -1,11 +1,54 @@
 //! C language extractor using tree-sitter
 
-use super::{ExtractionResult, LanguageExtractor};
-use canopy_core::{GraphNode, GraphEdge, NodeKind, EdgeKind, EdgeSource, Language, NodeId, EdgeId};
-use std::path::PathBuf;
+use super::{preprocessor::apply_conditionals, ExtractionResult, LanguageExtractor};
+use canopy_core::{GraphNode, NodeKind, EdgeKind, Language, NodeId};
+use std::path::Path;
 use tree_sitter::{Node, Point};
 use anyhow::Result;
+use crate::extractor::syntax_errors;
+use crate::metadata::{code_lines, node_metadata};
 use crate::parser_pool::{ParserPool, ParseRequest, FileType};
+use crate::calls::local_calls;
+use crate::resolve::Reference;
+
+/// Call expressions and the field holding their callee.
+const CALL_KINDS: &[(&str, &str)] = &[("call_expression", "function")];
+
+/// Source file extensions whose same-named header they implement.
+const SOURCE_EXTENSIONS: &[&str] = &["c", "cc", "cpp", "cxx"];
+
+/// References for a file's `#include`s, plus an `Implements` reference
+/// pairing a source file with the included header of the same name.
+///
+/// Quoted includes are looked up next to the including file first, as the
+/// compiler does, once every file is in the graph; others are matched
+/// against the repository's files by path suffix.
+pub fn include_references(path: &Path, includes: &[(String, bool)]) -> Vec<Reference> {
+    let stem = path.file_stem().and_then(|s| s.to_str());
+    let is_source = path.extension().and_then(|e| e.to_str()).is_some_and(|e| SOURCE_EXTENSIONS.contains(&e));
+    let mut references = Vec::new();
+    for (header, quoted) in includes {
+        references.push(Reference {
+            source: None,
+            kind: EdgeKind::Imports,
+            target: header.clone(),
+            label: format!("includes {}", header),
+            line: None,
+            local_first: *quoted,
+        });
+        if is_source && Path::new(header).file_stem().and_then(|s| s.to_str()) == stem {
+            references.push(Reference {
+                source: None,
+                kind: EdgeKind::Implements,
+                target: header.clone(),
+                label: format!("implements {}", header),
+                line: None,
+                local_first: *quoted,
+            });
+        }
+    }
+    references
+}
 
 pub struct CExtractor {
     parser_pool: ParserPool,
@@ -20,61 +63,31 @@ impl CExtractor {
         (point.row as u32) + 1
     }
     
-    fn extract_function(&self, node: Node, source: &[u8], path: &PathBuf) -> Option<GraphNode> {
-        if node.kind() == "function_definition" {
-            if let Some(declarator) = node.child_by_field_name("declarator") {
-                // Find the function name in the declarator
-                let mut cursor = declarator.walk();
-                for child in declarator.children(&mut cursor) {
-                    if child.kind() == "function_declarator" {
-                        if let Some(name_node) = child.child_by_field_name("declarator") {
-                            if let Ok(name) = name_node.utf8_text(source) {
-                                let start_pos = Self::point_to_u32(node.start_position());
-                                let end_pos = Self::point_to_u32(node.end_position());
-                                
-                                return Some(GraphNode {
-                                    id: NodeId(0), // Will be set by graph
-                                    kind: NodeKind::Function,
-                                    name: name.to_string(),
-                                    qualified_name: format!("{}::{}", path.display(), name),
-                                    file_path: path.clone(),
-                                    line_start: Some(start_pos),
-                                    line_end: Some(end_pos),
-                                    language: Some(Language::C),
-                                    is_container: false,
-                                    child_count: 0,
-                                    loc: Some(((end_pos - start_pos) as usize) as u32),
-                                    metadata: std::collections::HashMap::new(),
-                                });
-                            }
-                        }
-                    }
-                }
-            }
-        }
-        None
-    }
-    
-    fn extract_struct(&self, node: Node, source: &[u8], path: &PathBuf) -> Option<GraphNode> {
-        if node.kind() == "struct_specifier" {
-            if let Some(name_node) = node.child_by_field_name("name") {
-                if let Ok(name) = name_node.utf8_text(source) {
+    fn extract_function(&self, node: Node, source: &[u8], path: &Path) -> Option<GraphNode> {
+        if node.kind() == "function_definition"
+            && let Some(declarator) = node.child_by_field_name("declarator") {
+            // Find the function name in the declarator: `int f()` is a
+            // function declarator itself, `int *f()` nests one
+            for child in std::iter::successors(Some(declarator), |d| d.child_by_field_name("declarator")) {
+                if child.kind() == "function_declarator"
+                    && let Some(name_node) = child.child_by_field_name("declarator")
+                    && let Ok(name) = name_node.utf8_text(source) {
                     let start_pos = Self::point_to_u32(node.start_position());
                     let end_pos = Self::point_to_u32(node.end_position());
-                    
+                                
                     return Some(GraphNode {
                         id: NodeId(0), // Will be set by graph
-                        kind: NodeKind::Struct,
+                        kind: NodeKind::Function,
                         name: name.to_string(),
-                        qualified_name: format!("{}::{}", path.display(), name),
-                        file_path: path.clone(),
+                        qualified_name: format!("{}::{}", path.display(), name).into(),
+                        file_path: path.into(),
                         line_start: Some(start_pos),
                         line_end: Some(end_pos),
                         language: Some(Language::C),
-                        is_container: true,
+                        is_container: false,
                         child_count: 0,
-                        loc: Some(((end_pos - start_pos) as usize) as u32),
-                        metadata: std::collections::HashMap::new(),
+                        loc: Some(code_lines(node, source)),
+                        metadata: node_metadata(node, source, Language::C, name),
                     });
                 }
             }
@@ -82,56 +95,57 @@ impl CExtractor {
         None
     }
     
-    fn extract_typedef(&self, node: Node, source: &[u8], path: &PathBuf) -> Option<GraphNode> {
-        if node.kind() == "type_definition" {
-            let mut cursor = node.walk();
-            for child in node.children(&mut cursor) {
-                if child.kind() == "type_identifier" {
-                    if let Ok(name) = child.utf8_text(source) {
-                        let start_pos = Self::point_to_u32(node.start_position());
-                        let end_pos = Self::point_to_u32(node.end_position());
-                        
-                        return Some(GraphNode {
-                            id: NodeId(0), // Will be set by graph
-                            kind: NodeKind::TypeAlias,
-                            name: name.to_string(),
-                            qualified_name: format!("{}::{}", path.display(), name),
-                            file_path: path.clone(),
-                            line_start: Some(start_pos),
-                            line_end: Some(end_pos),
-                            language: Some(Language::C),
-                            is_container: false,
-                            child_count: 0,
-                            loc: Some(((end_pos - start_pos) as usize) as u32),
-                            metadata: std::collections::HashMap::new(),
-                        });
-                    }
-                }
-            }
+    fn extract_struct(&self, node: Node, source: &[u8], path: &Path) -> Option<GraphNode> {
+        let kind = match node.kind() {
+            "struct_specifier" => NodeKind::Struct,
+            "union_specifier" => NodeKind::Union,
+            _ => return None,
+        };
+        if let Some(name_node) = node.child_by_field_name("name")
+            && let Ok(name) = name_node.utf8_text(source) {
+            let start_pos = Self::point_to_u32(node.start_position());
+            let end_pos = Self::point_to_u32(node.end_position());
+                    
+            return Some(GraphNode {
+                id: NodeId(0), // Will be set by graph
+                kind,
+                name: name.to_string(),
+                qualified_name: format!("{}::{}", path.display(), name).into(),
+                file_path: path.into(),
+                line_start: Some(start_pos),
+                line_end: Some(end_pos),
+                language: Some(Language::C),
+                is_container: true,
+                child_count: 0,
+                loc: Some(code_lines(node, source)),
+                metadata: node_metadata(node, source, Language::C, name),
+            });
         }
         None
     }
     
-    fn extract_enum(&self, node: Node, source: &[u8], path: &PathBuf) -> Option<GraphNode> {
-        if node.kind() == "enum_specifier" {
-            if let Some(name_node) = node.child_by_field_name("name") {
-                if let Ok(name) = name_node.utf8_text(source) {
+    fn extract_typedef(&self, node: Node, source: &[u8], path: &Path) -> Option<GraphNode> {
+        if node.kind() == "type_definition" {
+            let mut cursor = node.walk();
+            for child in node.children(&mut cursor) {
+                if child.kind() == "type_identifier"
+                    && let Ok(name) = child.utf8_text(source) {
                     let start_pos = Self::point_to_u32(node.start_position());
                     let end_pos = Self::point_to_u32(node.end_position());
-                    
+                        
                     return Some(GraphNode {
                         id: NodeId(0), // Will be set by graph
-                        kind: NodeKind::Enum,
+                        kind: NodeKind::TypeAlias,
                         name: name.to_string(),
-                        qualified_name: format!("{}::{}", path.display(), name),
-                        file_path: path.clone(),
+                        qualified_name: format!("{}::{}", path.display(), name).into(),
+                        file_path: path.into(),
                         line_start: Some(start_pos),
                         line_end: Some(end_pos),
                         language: Some(Language::C),
-                        is_container: true,
+                        is_container: false,
                         child_count: 0,
-                        loc: Some(((end_pos - start_pos) as usize) as u32),
-                        metadata: std::collections::HashMap::new(),
+                        loc: Some(code_lines(node, source)),
+                        metadata: node_metadata(node, source, Language::C, name),
                     });
                 }
             }
@@ -139,17 +153,81 @@ impl CExtractor {
         None
     }
     
-    fn extract_include(&self, node: Node, source: &[u8]) -> Vec<String> {
+    fn extract_enum(&self, node: Node, source: &[u8], path: &Path) -> Option<GraphNode> {
+        if node.kind() == "enum_specifier"
+            && let Some(name_node) = node.child_by_field_name("name")
+            && let Ok(name) = name_node.utf8_text(source) {
+            let start_pos = Self::point_to_u32(node.start_position());
+            let end_pos = Self::point_to_u32(node.end_position());
+                    
+            return Some(GraphNode {
+                id: NodeId(0), // Will be set by graph
+                kind: NodeKind::Enum,
+                name: name.to_string(),
+                qualified_name: format!("{}::{}", path.display(), name).into(),
+                file_path: path.into(),
+                line_start: Some(start_pos),
+                line_end: Some(end_pos),
+                language: Some(Language::C),
+                is_container: true,
+                child_count: 0,
+                loc: Some(code_lines(node, source)),
+                metadata: node_metadata(node, source, Language::C, name),
+            });
+        }
+        None
+    }
+    
+    fn extract_field(&self, node: Node, source: &[u8], path: &Path) -> Option<GraphNode> {
+        if node.kind() == "field_declaration"
+            && let Some(name_node) = Self::declarator_name(node, "field_identifier")
+            && let Ok(name) = name_node.utf8_text(source) {
+            let start_pos = Self::point_to_u32(node.start_position());
+            let end_pos = Self::point_to_u32(node.end_position());
+
+            return Some(GraphNode {
+                id: NodeId(0), // Will be set by graph
+                kind: NodeKind::Field,
+                name: name.to_string(),
+                qualified_name: format!("{}::{}", path.display(), name).into(),
+                file_path: path.into(),
+                line_start: Some(start_pos),
+                line_end: Some(end_pos),
+                language: Some(Language::C),
+                is_container: false,
+                child_count: 0,
+                loc: Some(code_lines(node, source)),
+                metadata: node_metadata(node, source, Language::C, name),
+            });
+        }
+        None
+    }
+
+    /// Follow nested declarators (pointers, arrays) down to the identifier.
+    fn declarator_name<'t>(node: Node<'t>, identifier_kind: &str) -> Option<Node<'t>> {
+        let mut current = node.child_by_field_name("declarator")?;
+        while current.kind() != identifier_kind {
+            // Member function declarations are not fields
+            if current.kind() == "function_declarator" {
+                return None;
+            }
+            current = current.child_by_field_name("declarator")?;
+        }
+        Some(current)
+    }
+    
+    /// Included headers, with whether each is a quoted (local) include.
+    fn extract_include(&self, node: Node, source: &[u8]) -> Vec<(String, bool)> {
         let mut includes = Vec::new();
         
         if node.kind() == "preproc_include" {
             let mut cursor = node.walk();
             for child in node.children(&mut cursor) {
-                if child.kind() == "string_literal" || child.kind() == "system_lib_string" {
-                    if let Ok(header) = child.utf8_text(source) {
-                        // Remove quotes or angle brackets
-                        includes.push(header.trim_matches('"').trim_matches('<').trim_matches('>').to_string());
-                    }
+                if (child.kind() == "string_literal" || child.kind() == "system_lib_string")
+                    && let Ok(header) = child.utf8_text(source) {
+                    // Remove quotes or angle brackets
+                    let quoted = child.kind() == "string_literal";
+                    includes.push((header.trim_matches('"').trim_matches('<').trim_matches('>').to_string(), quoted));
                 }
             }
         }
@@ -159,21 +237,20 @@ impl CExtractor {
 }
 
 impl LanguageExtractor for CExtractor {
-    fn extract(&self, path: &PathBuf, content: &[u8]) -> Result<ExtractionResult> {
+    fn extract(&self, path: &Path, content: &[u8]) -> Result<ExtractionResult> {
         let source_code = std::str::from_utf8(content)?;
         
         // Use the parser pool to parse the content
         let request = ParseRequest {
             file_type: FileType::C,
             content: source_code.to_string(),
-            path: path.clone(),
+            path: path.to_path_buf(),
         };
         
         let parse_result = self.parser_pool.parse_blocking(request)?;
         let tree = parse_result.tree;
         
         let mut nodes = Vec::new();
-        let mut edges = Vec::new();
         let mut include_files = Vec::new();
         
         // Walk the AST
@@ -182,10 +259,9 @@ impl LanguageExtractor for CExtractor {
         fn visit_node(
             node: Node,
             source: &str,
-            path: &PathBuf,
+            path: &Path,
             nodes: &mut Vec<GraphNode>,
-            edges: &mut Vec<GraphEdge>,
-            includes: &mut Vec<String>,
+            includes: &mut Vec<(String, bool)>,
             extractor: &CExtractor,
         ) {
             // Extract functions
@@ -193,11 +269,16 @@ impl LanguageExtractor for CExtractor {
                 nodes.push(function);
             }
             
-            // Extract structs
+            // Extract structs and unions
             if let Some(struct_type) = extractor.extract_struct(node, source.as_bytes(), path) {
                 nodes.push(struct_type);
             }
             
+            // Extract struct fields
+            if let Some(field) = extractor.extract_field(node, source.as_bytes(), path) {
+                nodes.push(field);
+            }
+            
             // Extract typedefs
             if let Some(typedef) = extractor.extract_typedef(node, source.as_bytes(), path) {
                 nodes.push(typedef);
@@ -214,31 +295,21 @@ impl LanguageExtractor for CExtractor {
             // Visit children
             let mut cursor = node.walk();
             for child in node.children(&mut cursor) {
-                visit_node(child, source, path, nodes, edges, includes, extractor);
+                visit_node(child, source, path, nodes, includes, extractor);
             }
         }
         
         // Start visiting from root
-        visit_node(root_node, source_code, path, &mut nodes, &mut edges, &mut include_files, self);
-        
-        // Create edges from includes to nodes
-        for include in &include_files {
-            for node in &nodes {
-                // Simple heuristic: create a relationship
-                edges.push(GraphEdge {
-                    id: EdgeId(0), // Will be set by graph
-                    source: NodeId(0), // Placeholder
-                    target: node.id,
-                    kind: EdgeKind::Imports,
-                    edge_source: EdgeSource::Heuristic,
-                    confidence: 0.5,
-                    label: Some(format!("includes {}", include)),
-                    file_path: Some(path.clone()),
-                    line: node.line_start,
-                });
-            }
-        }
+        visit_node(root_node, source_code, path, &mut nodes, &mut include_files, self);
         
-        Ok(ExtractionResult { nodes, edges })
+        // Symbols are merged across preprocessor branches before calls
+        // between them are found
+        let mut result = ExtractionResult { nodes, edges: Vec::new(), contains: Vec::new(), calls: Vec::new(), references: Vec::new(), errors: syntax_errors(root_node) };
+        apply_conditionals(root_node, source_code.as_bytes(), &mut result);
+        let (calls, mut references) = local_calls(root_node, source_code.as_bytes(), &result.nodes, CALL_KINDS);
+        references.extend(include_references(path, &include_files));
+        result.calls = calls;
+        result.references = references;
+        Ok(result)
     }
 }
\ No newline at end of file
@@ -1,16 +1,45 @@
 //! C++ language extractor using tree-sitter
+//!
+//! Namespaces, classes and their members are walked with their enclosing
+//! scope, giving qualified names such as `path::ns::Shape::area`. Member
+//! functions defined outside their class (`double Shape::area() {}`) are
+//! merged into the declaration from the class body when both are in the
+//! same file.
 
-use super::{ExtractionResult, LanguageExtractor};
-use canopy_core::{GraphNode, GraphEdge, NodeKind, EdgeKind, EdgeSource, Language, NodeId, EdgeId};
-use std::path::PathBuf;
+use super::{c::include_references, preprocessor::apply_conditionals, ExtractionResult, LanguageExtractor};
+use canopy_core::{GraphNode, NodeKind, Language, NodeId, Visibility};
+use std::path::Path;
 use tree_sitter::{Node, Point};
 use anyhow::Result;
+use crate::extractor::syntax_errors;
+use crate::metadata::{code_lines, node_metadata};
 use crate::parser_pool::{ParserPool, ParseRequest, FileType};
+use crate::calls::local_calls;
+
+/// Call expressions and the field holding their callee.
+const CALL_KINDS: &[(&str, &str)] = &[("call_expression", "function")];
+
+/// Type definitions and the node kind they produce.
+const TYPE_KINDS: &[(&str, NodeKind)] = &[
+    ("class_specifier", NodeKind::Class),
+    ("struct_specifier", NodeKind::Struct),
+    ("union_specifier", NodeKind::Union),
+    ("enum_specifier", NodeKind::Enum),
+];
 
 pub struct CppExtractor {
     parser_pool: ParserPool,
 }
 
+/// What a walk of one file has collected so far.
+struct Walk<'a> {
+    source: &'a [u8],
+    path: &'a Path,
+    nodes: Vec<GraphNode>,
+    includes: Vec<(String, bool)>,
+    contains: Vec<(usize, usize)>,
+}
+
 impl CppExtractor {
     pub fn new(parser_pool: ParserPool) -> Self {
         Self { parser_pool }
@@ -19,161 +48,105 @@ impl CppExtractor {
     fn point_to_u32(point: Point) -> u32 {
         (point.row as u32) + 1
     }
-    
-    fn extract_function(&self, node: Node, source: &[u8], path: &PathBuf) -> Option<GraphNode> {
-        if node.kind() == "function_definition" {
-            if let Some(declarator) = node.child_by_field_name("declarator") {
-                // Find the function name in the declarator
-                let mut cursor = declarator.walk();
-                for child in declarator.children(&mut cursor) {
-                    if child.kind() == "function_declarator" || child.kind() == "parenthesized_declarator" {
-                        if let Some(name_node) = child.child_by_field_name("declarator") {
-                            if let Ok(name) = name_node.utf8_text(source) {
-                                let start_pos = Self::point_to_u32(node.start_position());
-                                let end_pos = Self::point_to_u32(node.end_position());
-                                
-                                return Some(GraphNode {
-                                    id: NodeId(0), // Will be set by graph
-                                    kind: NodeKind::Function,
-                                    name: name.to_string(),
-                                    qualified_name: format!("{}::{}", path.display(), name),
-                                    file_path: path.clone(),
-                                    line_start: Some(start_pos),
-                                    line_end: Some(end_pos),
-                                    language: Some(Language::Cpp),
-                                    is_container: false,
-                                    child_count: 0,
-                                    loc: Some(((end_pos - start_pos) as usize) as u32),
-                                    metadata: std::collections::HashMap::new(),
-                                });
-                            }
-                        }
-                    }
-                }
-            }
+
+    fn graph_node(node: Node, source: &[u8], path: &Path, kind: NodeKind, name: &str, qualified_name: String) -> GraphNode {
+        let start_pos = Self::point_to_u32(node.start_position());
+        let end_pos = Self::point_to_u32(node.end_position());
+        let mut metadata = node_metadata(node, source, Language::Cpp, name);
+        // Templates keep their parameter list in the signature
+        if let Some(template) = node.parent().filter(|p| p.kind() == "template_declaration")
+            && let Some(parameters) = template.child_by_field_name("parameters")
+            && let Ok(parameters) = parameters.utf8_text(source) {
+            metadata.signature = metadata.signature.map(|s| format!("template{} {}", parameters, s));
+        }
+        if let Some(visibility) = member_access(node, source) {
+            metadata.visibility = Some(visibility);
+        }
+        GraphNode {
+            id: NodeId(0), // Will be set by graph
+            kind,
+            name: name.to_string(),
+            qualified_name: qualified_name.into(),
+            file_path: path.into(),
+            line_start: Some(start_pos),
+            line_end: Some(end_pos),
+            language: Some(Language::Cpp),
+            is_container: matches!(kind, NodeKind::Module | NodeKind::Class | NodeKind::Struct | NodeKind::Union | NodeKind::Enum),
+            child_count: 0,
+            loc: Some(code_lines(node, source)),
+            metadata,
         }
-        None
     }
-    
-    fn extract_class(&self, node: Node, source: &[u8], path: &PathBuf) -> Option<GraphNode> {
-        if node.kind() == "class_specifier" {
-            if let Some(name_node) = node.child_by_field_name("name") {
-                if let Ok(name) = name_node.utf8_text(source) {
-                    let start_pos = Self::point_to_u32(node.start_position());
-                    let end_pos = Self::point_to_u32(node.end_position());
-                    
-                    return Some(GraphNode {
-                        id: NodeId(0), // Will be set by graph
-                        kind: NodeKind::Class,
-                        name: name.to_string(),
-                        qualified_name: format!("{}::{}", path.display(), name),
-                        file_path: path.clone(),
-                        line_start: Some(start_pos),
-                        line_end: Some(end_pos),
-                        language: Some(Language::Cpp),
-                        is_container: true,
-                        child_count: 0,
-                        loc: Some(((end_pos - start_pos) as usize) as u32),
-                        metadata: std::collections::HashMap::new(),
-                    });
-                }
-            }
+
+    /// Functions and methods: definitions anywhere, and member function
+    /// declarations in class bodies. `in_class` is whether `node` is a
+    /// member of a class, struct or union.
+    fn extract_function(&self, node: Node, source: &[u8], path: &Path, scope: &str, in_class: bool) -> Option<GraphNode> {
+        let declaration = matches!(node.kind(), "field_declaration" | "declaration");
+        if node.kind() != "function_definition" && !(in_class && declaration) {
+            return None;
         }
-        None
+        let name_node = function_name(node)?;
+        let segments = qualified_segments(name_node, source);
+        let name = *segments.last()?;
+        // `Shape::area` defined outside its class is a method of Shape
+        let kind = if in_class || segments.len() > 1 { NodeKind::Method } else { NodeKind::Function };
+        let qualified_name = std::iter::once(scope).chain(segments.iter().copied()).collect::<Vec<_>>().join("::");
+        Some(Self::graph_node(node, source, path, kind, name, qualified_name))
     }
-    
-    fn extract_struct(&self, node: Node, source: &[u8], path: &PathBuf) -> Option<GraphNode> {
-        if node.kind() == "struct_specifier" {
-            if let Some(name_node) = node.child_by_field_name("name") {
-                if let Ok(name) = name_node.utf8_text(source) {
-                    let start_pos = Self::point_to_u32(node.start_position());
-                    let end_pos = Self::point_to_u32(node.end_position());
-                    
-                    return Some(GraphNode {
-                        id: NodeId(0), // Will be set by graph
-                        kind: NodeKind::Struct,
-                        name: name.to_string(),
-                        qualified_name: format!("{}::{}", path.display(), name),
-                        file_path: path.clone(),
-                        line_start: Some(start_pos),
-                        line_end: Some(end_pos),
-                        language: Some(Language::Cpp),
-                        is_container: true,
-                        child_count: 0,
-                        loc: Some(((end_pos - start_pos) as usize) as u32),
-                        metadata: std::collections::HashMap::new(),
-                    });
-                }
-            }
+
+    fn extract_type(&self, node: Node, source: &[u8], path: &Path, scope: &str) -> Option<GraphNode> {
+        let &(_, kind) = TYPE_KINDS.iter().find(|(k, _)| *k == node.kind())?;
+        // Forward declarations and elaborated type uses have no body
+        node.child_by_field_name("body")?;
+        let name_node = node.child_by_field_name("name")?;
+        let name = *qualified_segments(name_node, source).last()?;
+        Some(Self::graph_node(node, source, path, kind, name, format!("{}::{}", scope, name)))
+    }
+
+    fn extract_namespace(&self, node: Node, source: &[u8], path: &Path, scope: &str) -> Option<GraphNode> {
+        if node.kind() == "namespace_definition"
+            && let Some(name_node) = node.child_by_field_name("name")
+            && let Ok(name) = name_node.utf8_text(source) {
+            return Some(Self::graph_node(node, source, path, NodeKind::Module, name, format!("{}::{}", scope, name)));
         }
         None
     }
-    
-    fn extract_namespace(&self, node: Node, source: &[u8], path: &PathBuf) -> Option<GraphNode> {
-        if node.kind() == "namespace_definition" {
-            if let Some(name_node) = node.child_by_field_name("name") {
-                if let Ok(name) = name_node.utf8_text(source) {
-                    let start_pos = Self::point_to_u32(node.start_position());
-                    let end_pos = Self::point_to_u32(node.end_position());
-                    
-                    return Some(GraphNode {
-                        id: NodeId(0), // Will be set by graph
-                        kind: NodeKind::Module,
-                        name: name.to_string(),
-                        qualified_name: format!("{}::{}", path.display(), name),
-                        file_path: path.clone(),
-                        line_start: Some(start_pos),
-                        line_end: Some(end_pos),
-                        language: Some(Language::Cpp),
-                        is_container: true,
-                        child_count: 0,
-                        loc: Some(((end_pos - start_pos) as usize) as u32),
-                        metadata: std::collections::HashMap::new(),
-                    });
-                }
-            }
+
+    fn extract_field(&self, node: Node, source: &[u8], path: &Path, scope: &str) -> Option<GraphNode> {
+        if node.kind() == "field_declaration"
+            && let Some(name_node) = Self::declarator_name(node, "field_identifier")
+            && let Ok(name) = name_node.utf8_text(source) {
+            return Some(Self::graph_node(node, source, path, NodeKind::Field, name, format!("{}::{}", scope, name)));
         }
         None
     }
     
-    fn extract_enum(&self, node: Node, source: &[u8], path: &PathBuf) -> Option<GraphNode> {
-        if node.kind() == "enum_specifier" {
-            if let Some(name_node) = node.child_by_field_name("name") {
-                if let Ok(name) = name_node.utf8_text(source) {
-                    let start_pos = Self::point_to_u32(node.start_position());
-                    let end_pos = Self::point_to_u32(node.end_position());
-                    
-                    return Some(GraphNode {
-                        id: NodeId(0), // Will be set by graph
-                        kind: NodeKind::Enum,
-                        name: name.to_string(),
-                        qualified_name: format!("{}::{}", path.display(), name),
-                        file_path: path.clone(),
-                        line_start: Some(start_pos),
-                        line_end: Some(end_pos),
-                        language: Some(Language::Cpp),
-                        is_container: true,
-                        child_count: 0,
-                        loc: Some(((end_pos - start_pos) as usize) as u32),
-                        metadata: std::collections::HashMap::new(),
-                    });
-                }
+    /// Follow nested declarators (pointers, arrays) down to the identifier.
+    fn declarator_name<'t>(node: Node<'t>, identifier_kind: &str) -> Option<Node<'t>> {
+        let mut current = node.child_by_field_name("declarator")?;
+        while current.kind() != identifier_kind {
+            // Member function declarations are not fields
+            if current.kind() == "function_declarator" {
+                return None;
             }
+            current = current.child_by_field_name("declarator")?;
         }
-        None
+        Some(current)
     }
     
-    fn extract_include(&self, node: Node, source: &[u8]) -> Vec<String> {
+    /// Included headers, with whether each is a quoted (local) include.
+    fn extract_include(&self, node: Node, source: &[u8]) -> Vec<(String, bool)> {
         let mut includes = Vec::new();
         
         if node.kind() == "preproc_include" {
             let mut cursor = node.walk();
             for child in node.children(&mut cursor) {
-                if child.kind() == "string_literal" || child.kind() == "system_lib_string" {
-                    if let Ok(header) = child.utf8_text(source) {
-                        // Remove quotes or angle brackets
-                        includes.push(header.trim_matches('"').trim_matches('<').trim_matches('>').to_string());
-                    }
+                if (child.kind() == "string_literal" || child.kind() == "system_lib_string")
+                    && let Ok(header) = child.utf8_text(source) {
+                    // Remove quotes or angle brackets
+                    let quoted = child.kind() == "string_literal";
+                    includes.push((header.trim_matches('"').trim_matches('<').trim_matches('>').to_string(), quoted));
                 }
             }
         }
@@ -182,92 +155,182 @@ impl CppExtractor {
     }
 }
 
+
 impl LanguageExtractor for CppExtractor {
-    fn extract(&self, path: &PathBuf, content: &[u8]) -> Result<ExtractionResult> {
+    fn extract(&self, path: &Path, content: &[u8]) -> Result<ExtractionResult> {
         let source_code = std::str::from_utf8(content)?;
         
         // Use the parser pool to parse the content
         let request = ParseRequest {
             file_type: FileType::Cpp,
             content: source_code.to_string(),
-            path: path.clone(),
+            path: path.to_path_buf(),
         };
         
         let parse_result = self.parser_pool.parse_blocking(request)?;
         let tree = parse_result.tree;
         
-        let mut nodes = Vec::new();
-        let mut edges = Vec::new();
-        let mut include_files = Vec::new();
+        let mut walk = Walk {
+            source: source_code.as_bytes(),
+            path,
+            nodes: Vec::new(),
+            includes: Vec::new(),
+            contains: Vec::new(),
+        };
         
         // Walk the AST
         let root_node = tree.root_node();
         
-        fn visit_node(
-            node: Node,
-            source: &str,
-            path: &PathBuf,
-            nodes: &mut Vec<GraphNode>,
-            edges: &mut Vec<GraphEdge>,
-            includes: &mut Vec<String>,
-            extractor: &CppExtractor,
-        ) {
-            // Extract functions
-            if let Some(function) = extractor.extract_function(node, source.as_bytes(), path) {
-                nodes.push(function);
-            }
-            
-            // Extract classes
-            if let Some(class) = extractor.extract_class(node, source.as_bytes(), path) {
-                nodes.push(class);
-            }
-            
-            // Extract structs
-            if let Some(struct_type) = extractor.extract_struct(node, source.as_bytes(), path) {
-                nodes.push(struct_type);
-            }
+        fn visit_node(node: Node, scope: &str, parent: Option<usize>, walk: &mut Walk, extractor: &CppExtractor) {
+            let source = walk.source;
+            let path = walk.path;
             
-            // Extract namespaces
-            if let Some(namespace) = extractor.extract_namespace(node, source.as_bytes(), path) {
-                nodes.push(namespace);
+            // Namespaces and types are walked with their own scope; members
+            // of anonymous namespaces stay in the enclosing one
+            let is_namespace = node.kind() == "namespace_definition";
+            let scoped = if is_namespace {
+                extractor.extract_namespace(node, source, path, scope)
+            } else {
+                extractor.extract_type(node, source, path, scope)
+            };
+            if is_namespace || scoped.is_some() {
+                let (inner, parent) = match scoped {
+                    Some(scoped) => {
+                        let index = walk.nodes.len();
+                        let inner = scoped.qualified_name.to_string();
+                        walk.nodes.push(scoped);
+                        if let Some(parent) = parent {
+                            walk.contains.push((parent, index));
+                        }
+                        (inner, Some(index))
+                    }
+                    None => (scope.to_string(), parent),
+                };
+                if let Some(body) = node.child_by_field_name("body") {
+                    let mut cursor = body.walk();
+                    for child in body.children(&mut cursor) {
+                        visit_node(child, &inner, parent, walk, extractor);
+                    }
+                }
+                return;
             }
             
-            // Extract enums
-            if let Some(enum_type) = extractor.extract_enum(node, source.as_bytes(), path) {
-                nodes.push(enum_type);
+            let member = is_member(node);
+            let item = extractor
+                .extract_function(node, source, path, scope, member)
+                .or_else(|| extractor.extract_field(node, source, path, scope));
+            if let Some(item) = item {
+                // An out-of-line definition replaces its in-class declaration
+                let declared = walk.nodes.iter().position(|n| {
+                    n.kind == NodeKind::Method && item.kind == NodeKind::Method && n.qualified_name == item.qualified_name
+                });
+                match declared {
+                    Some(index) => walk.nodes[index] = item,
+                    None => {
+                        let index = walk.nodes.len();
+                        walk.nodes.push(item);
+                        if let Some(parent) = parent {
+                            walk.contains.push((parent, index));
+                        }
+                    }
+                }
             }
             
             // Extract includes
-            includes.extend(extractor.extract_include(node, source.as_bytes()));
+            walk.includes.extend(extractor.extract_include(node, source));
             
             // Visit children
             let mut cursor = node.walk();
             for child in node.children(&mut cursor) {
-                visit_node(child, source, path, nodes, edges, includes, extractor);
+                visit_node(child, scope, parent, walk, extractor);
             }
         }
         
         // Start visiting from root
-        visit_node(root_node, source_code, path, &mut nodes, &mut edges, &mut include_files, self);
+        visit_node(root_node, &path.display().to_string(), None, &mut walk, self);
         
-        // Create edges from includes to nodes
-        for include in &include_files {
-            for node in &nodes {
-                // Simple heuristic: create a relationship
-                edges.push(GraphEdge {
-                    id: EdgeId(0), // Will be set by graph
-                    source: NodeId(0), // Placeholder
-                    target: node.id,
-                    kind: EdgeKind::Imports,
-                    edge_source: EdgeSource::Heuristic,
-                    confidence: 0.5,
-                    label: Some(format!("includes {}", include)),
-                    file_path: Some(path.clone()),
-                    line: node.line_start,
-                });
+        let Walk { nodes, includes, contains, .. } = walk;
+        // Symbols are merged across preprocessor branches before calls
+        // between them are found
+        let mut result = ExtractionResult { nodes, edges: Vec::new(), contains, calls: Vec::new(), references: Vec::new(), errors: syntax_errors(root_node) };
+        apply_conditionals(root_node, source_code.as_bytes(), &mut result);
+        let (calls, mut references) = local_calls(root_node, source_code.as_bytes(), &result.nodes, CALL_KINDS);
+        references.extend(include_references(path, &includes));
+        result.calls = calls;
+        result.references = references;
+        Ok(result)
+    }
+}
+
+/// Whether `node` is declared directly in a class, struct or union body,
+/// perhaps within preprocessor conditionals.
+fn is_member(node: Node) -> bool {
+    let member = match node.parent() {
+        Some(parent) if parent.kind() == "template_declaration" => parent,
+        _ => node,
+    };
+    std::iter::successors(member.parent(), |p| p.parent())
+        .find(|p| !p.kind().starts_with("preproc_"))
+        .is_some_and(|p| p.kind() == "field_declaration_list")
+}
+
+/// The name of a function definition or declaration, found by following its
+/// declarators (pointers, references) down to the function declarator.
+fn function_name(node: Node) -> Option<Node> {
+    std::iter::successors(node.child_by_field_name("declarator"), |d| inner_declarator(*d))
+        .find(|d| d.kind() == "function_declarator")?
+        .child_by_field_name("declarator")
+}
+
+fn inner_declarator(node: Node) -> Option<Node> {
+    node.child_by_field_name("declarator").or_else(|| {
+        // Reference declarators hold theirs without a field name
+        let last = node.named_child_count().checked_sub(1)?;
+        (node.kind() == "reference_declarator").then(|| node.named_child(last)).flatten()
+    })
+}
+
+/// The segments of a possibly qualified name, without template arguments:
+/// `ns::Box<T>::get` gives `["ns", "Box", "get"]`.
+fn qualified_segments<'s>(node: Node, source: &'s [u8]) -> Vec<&'s str> {
+    match node.kind() {
+        "qualified_identifier" => {
+            let mut segments: Vec<&str> = node
+                .child_by_field_name("scope")
+                .map(|scope| qualified_segments(scope, source))
+                .unwrap_or_default();
+            if let Some(name) = node.child_by_field_name("name") {
+                segments.extend(qualified_segments(name, source));
             }
+            segments
         }
-        
-        Ok(ExtractionResult { nodes, edges })
+        "template_type" | "template_function" => node
+            .child_by_field_name("name")
+            .map(|name| qualified_segments(name, source))
+            .unwrap_or_default(),
+        _ => node.utf8_text(source).ok().into_iter().collect(),
     }
-}
\ No newline at end of file
+}
+
+/// Visibility of a class member from the nearest preceding access
+/// specifier, defaulting to private in classes and public otherwise.
+fn member_access(node: Node, source: &[u8]) -> Option<Visibility> {
+    let member = match node.parent() {
+        Some(parent) if matches!(parent.kind(), "template_declaration" | "field_declaration") => parent,
+        _ => node,
+    };
+    let body = member.parent().filter(|p| p.kind() == "field_declaration_list")?;
+    let specifier = std::iter::successors(member.prev_sibling(), |s| s.prev_sibling())
+        .find(|s| s.kind() == "access_specifier")
+        .and_then(|s| s.utf8_text(source).ok());
+    let access = match specifier {
+        Some(specifier) => specifier.trim_end_matches(':').trim(),
+        None if body.parent().is_some_and(|c| c.kind() == "class_specifier") => "private",
+        None => "public",
+    };
+    Some(match access {
+        "private" => Visibility::Private,
+        "protected" => Visibility::Protected,
+        _ => Visibility::Public,
+    })
+}
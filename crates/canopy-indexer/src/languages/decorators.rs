@@ -0,0 +1,205 @@
+//! Decorator-driven frameworks (NestJS, Angular) for the TypeScript
+//! extractor
+//!
+//! HTTP method decorators on controller methods register routes, prefixed
+//! by the class's `@Controller` path. Constructor parameters of decorated
+//! classes are injected dependencies.
+
+use crate::calls::callee_name;
+use crate::heuristics::routes::ROUTE_KEY;
+use crate::resolve::Reference;
+use canopy_core::{EdgeKind, GraphNode, NodeKind};
+use tree_sitter::Node;
+
+/// Metadata key holding a class's or method's decorator names,
+/// newline-separated.
+pub const DECORATORS_KEY: &str = "decorators";
+
+/// Route decorators and the HTTP method they register.
+const HTTP_DECORATORS: &[(&str, &str)] = &[
+    ("Get", "GET"),
+    ("Post", "POST"),
+    ("Put", "PUT"),
+    ("Patch", "PATCH"),
+    ("Delete", "DELETE"),
+    ("Options", "OPTIONS"),
+    ("Head", "HEAD"),
+    ("All", "ALL"),
+];
+
+/// The decorators on a class or method, as (name, first string argument).
+/// Class decorators may sit on an enclosing `export`; method decorators
+/// precede the method in the class body.
+fn decorators<'s>(node: Node, source: &'s [u8]) -> Vec<(&'s str, Option<&'s str>)> {
+    let mut found = Vec::new();
+    let mut cursor = node.walk();
+    found.extend(node.children(&mut cursor).filter(|c| c.kind() == "decorator"));
+    if let Some(export) = node.parent().filter(|p| p.kind() == "export_statement") {
+        let mut cursor = export.walk();
+        found.extend(export.children(&mut cursor).filter(|c| c.kind() == "decorator"));
+    }
+    let mut sibling = node.prev_named_sibling().filter(|_| enclosing_class(node).is_some());
+    while let Some(decorator) = sibling.filter(|s| s.kind() == "decorator") {
+        found.push(decorator);
+        sibling = decorator.prev_named_sibling();
+    }
+
+    found
+        .into_iter()
+        .filter_map(|decorator| {
+            let expression = decorator.named_child(0)?;
+            let (callee, arguments) = match expression.kind() {
+                "call_expression" => (expression.child_by_field_name("function")?, expression.child_by_field_name("arguments")),
+                _ => (expression, None),
+            };
+            let name = callee_name(callee.utf8_text(source).ok()?)?;
+            let argument = arguments
+                .and_then(|a| a.named_child(0))
+                .filter(|a| a.kind() == "string")
+                .and_then(|a| a.utf8_text(source).ok())
+                .map(|a| a.trim_matches(['"', '\'', '`']));
+            Some((name, argument))
+        })
+        .collect()
+}
+
+/// The class declaration a method belongs to.
+fn enclosing_class(node: Node) -> Option<Node> {
+    node.parent().filter(|p| p.kind() == "class_body")?.parent()
+}
+
+/// `prefix` and `path` joined into one absolute route path.
+fn join_route(prefix: &str, path: &str) -> String {
+    let segments: Vec<&str> = [prefix, path].iter().flat_map(|p| p.split('/')).filter(|s| !s.is_empty()).collect();
+    format!("/{}", segments.join("/"))
+}
+
+/// Record `extracted`'s decorators and, for controller methods, the routes
+/// they register.
+pub fn tag(node: Node, source: &[u8], extracted: &mut GraphNode) {
+    let found = decorators(node, source);
+    if found.is_empty() {
+        return;
+    }
+    let names: Vec<&str> = found.iter().map(|&(name, _)| name).collect();
+    extracted.metadata.extra.insert(DECORATORS_KEY.to_string(), names.join("\n"));
+
+    let prefix = enclosing_class(node)
+        .map(|class| decorators(class, source))
+        .and_then(|class| class.into_iter().find(|&(name, _)| name == "Controller"))
+        .map(|(_, path)| path.unwrap_or_default());
+    let Some(prefix) = prefix else {
+        return;
+    };
+    let routes: Vec<String> = found
+        .iter()
+        .filter_map(|&(name, path)| {
+            let &(_, method) = HTTP_DECORATORS.iter().find(|(decorator, _)| *decorator == name)?;
+            Some(format!("{} {}", method, join_route(prefix, path.unwrap_or_default())))
+        })
+        .collect();
+    if !routes.is_empty() {
+        extracted.metadata.extra.insert(ROUTE_KEY.to_string(), routes.join("\n"));
+    }
+}
+
+/// References from decorated classes in `root` to the types their
+/// constructors take, which the framework injects.
+pub fn injections(root: Node, source: &[u8], nodes: &[GraphNode]) -> Vec<Reference> {
+    let mut references = Vec::new();
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        let mut cursor = node.walk();
+        stack.extend(node.children(&mut cursor));
+        if node.kind() != "class_declaration" || decorators(node, source).is_empty() {
+            continue;
+        }
+        let line = node.start_position().row as u32 + 1;
+        let Some(class) = nodes.iter().position(|n| n.kind == NodeKind::Class && n.line_start == Some(line)) else {
+            continue;
+        };
+        let Some(body) = node.child_by_field_name("body") else {
+            continue;
+        };
+        let mut body_cursor = body.walk();
+        let constructor = body.named_children(&mut body_cursor).find(|m| {
+            m.kind() == "method_definition"
+                && m.child_by_field_name("name").and_then(|n| n.utf8_text(source).ok()) == Some("constructor")
+        });
+        let Some(parameters) = constructor.and_then(|c| c.child_by_field_name("parameters")) else {
+            continue;
+        };
+        let mut parameter_cursor = parameters.walk();
+        for parameter in parameters.named_children(&mut parameter_cursor) {
+            let Some(ty) = parameter
+                .child_by_field_name("type")
+                .and_then(|t| t.named_child(0))
+                .filter(|t| matches!(t.kind(), "type_identifier" | "generic_type" | "nested_type_identifier"))
+                .and_then(|t| t.utf8_text(source).ok())
+                .map(|t| t.split('<').next().unwrap_or(t))
+                .and_then(callee_name)
+            else {
+                continue;
+            };
+            references.push(Reference {
+                source: Some(class),
+                kind: EdgeKind::DependsOn,
+                target: ty.to_string(),
+                label: format!("depends on {}", ty),
+                line: Some(parameter.start_position().row as u32 + 1),
+                local_first: false,
+            });
+        }
+    }
+    references
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::heuristics::routes::{METHOD_KEY, PATH_KEY};
+    use crate::languages::get_extractor;
+    use std::path::Path;
+
+    #[tokio::test]
+    async fn test_nest_controller() {
+        let code = r#"
+@Injectable()
+export class UsersService {}
+
+@Controller('users')
+export class UsersController {
+    constructor(private readonly users: UsersService, private config: ConfigService<Env>) {}
+
+    @Get(':id')
+    find(id: string) {}
+
+    @Post()
+    @HttpCode(201)
+    create() {}
+}
+"#;
+        let path = Path::new("users.controller.ts");
+        let result = get_extractor(path).unwrap().extract(path, code.as_bytes()).unwrap();
+        let node = |name: &str| result.nodes.iter().position(|n| n.name == name).unwrap();
+        let extra = |name: &str, key: &str| result.nodes[node(name)].metadata.extra.get(key).cloned();
+        assert_eq!(extra("UsersService", DECORATORS_KEY).as_deref(), Some("Injectable"));
+        assert_eq!(extra("find", ROUTE_KEY).as_deref(), Some("GET /users/:id"));
+        assert_eq!(extra("create", ROUTE_KEY).as_deref(), Some("POST /users"));
+
+        let route = &result.nodes[node("GET /users/:id")];
+        assert_eq!(route.kind, NodeKind::Route);
+        assert_eq!(route.metadata.extra.get(METHOD_KEY).map(String::as_str), Some("GET"));
+        assert_eq!(route.metadata.extra.get(PATH_KEY).map(String::as_str), Some("/users/:id"));
+        let handler = result.references.iter().find(|r| r.source == Some(node("GET /users/:id"))).unwrap();
+        assert_eq!((handler.kind, handler.target.as_str()), (EdgeKind::RouteHandler, "users.controller::find"));
+
+        let mut injected: Vec<_> = result.references.iter()
+            .filter(|r| r.kind == EdgeKind::DependsOn)
+            .map(|r| (r.source, r.target.as_str()))
+            .collect();
+        injected.sort();
+        let controller = Some(node("UsersController"));
+        assert_eq!(injected, vec![(controller, "ConfigService"), (controller, "UsersService")]);
+    }
+}
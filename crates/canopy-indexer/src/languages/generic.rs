@@ -1,37 +1,143 @@
 //! Generic fallback extractor
+//!
+//! Files no language extractor handles yield no symbols, unless the project
+//! defines [`RegexRule`]s for them: each match of a rule's pattern becomes a
+//! node of the rule's kind, named by the pattern's `name` group (or its
+//! first group) and placed on the line the match starts on.
 
+use super::registry::PathGlob;
 use super::{ExtractionResult, LanguageExtractor};
-use std::path::PathBuf;
+use canopy_core::{GraphNode, NodeId, NodeKind, NodeMetadata};
+use std::path::Path;
 use anyhow::Result;
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
 use crate::parser_pool::{ParserPool, ParseRequest, FileType};
 
+/// A rule as written in config.
+#[derive(Deserialize)]
+struct RegexRuleSpec {
+    files: String,
+    pattern: String,
+    kind: NodeKind,
+}
+
+/// Turns matches of a regex in the files matching a glob into nodes of a
+/// kind. `^` and `$` match at line boundaries.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "RegexRuleSpec")]
+pub struct RegexRule {
+    files: PathGlob,
+    pattern: Regex,
+    kind: NodeKind,
+}
+
+impl RegexRule {
+    /// A rule for files matching `files`, a glob matched against the file
+    /// name unless it holds a `/`.
+    pub fn new(files: &str, pattern: &str, kind: NodeKind) -> Result<Self> {
+        Ok(Self {
+            files: PathGlob::new(files)?,
+            pattern: RegexBuilder::new(pattern).multi_line(true).build()?,
+            kind,
+        })
+    }
+
+    pub fn applies_to(&self, path: &Path) -> bool {
+        self.files.is_match(path)
+    }
+}
+
+impl TryFrom<RegexRuleSpec> for RegexRule {
+    type Error = anyhow::Error;
+
+    fn try_from(spec: RegexRuleSpec) -> Result<Self> {
+        Self::new(&spec.files, &spec.pattern, spec.kind)
+    }
+}
+
 pub struct GenericExtractor {
     parser_pool: ParserPool,
+    rules: Vec<RegexRule>,
 }
 
 impl GenericExtractor {
     pub fn new(parser_pool: ParserPool) -> Self {
-        Self { parser_pool }
+        Self { parser_pool, rules: Vec::new() }
+    }
+
+    /// Apply `rules` to the files this extractor is given.
+    pub fn with_rules(mut self, rules: Vec<RegexRule>) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    fn apply_rules(&self, path: &Path, source: &str) -> Vec<GraphNode> {
+        let mut nodes = Vec::new();
+        for rule in self.rules.iter().filter(|rule| rule.applies_to(path)) {
+            for captures in rule.pattern.captures_iter(source) {
+                let matched = captures.get(0).expect("group 0 is the whole match");
+                let name = captures.name("name").or_else(|| captures.get(1)).unwrap_or(matched).as_str().trim();
+                if name.is_empty() {
+                    continue;
+                }
+                let line = source[..matched.start()].matches('\n').count() as u32 + 1;
+                let line_text = source[matched.start()..].lines().next().unwrap_or_default().trim();
+                nodes.push(GraphNode {
+                    id: NodeId(0), // Will be set by graph
+                    kind: rule.kind,
+                    name: name.to_string(),
+                    qualified_name: format!("{}::{}", path.display(), name).into(),
+                    file_path: path.into(),
+                    line_start: Some(line),
+                    line_end: Some(line),
+                    language: None,
+                    is_container: false,
+                    child_count: 0,
+                    loc: Some(1),
+                    metadata: NodeMetadata {
+                        signature: (!line_text.is_empty()).then(|| line_text.to_string()),
+                        ..Default::default()
+                    },
+                });
+            }
+        }
+        nodes
     }
 }
 
 impl LanguageExtractor for GenericExtractor {
-    fn extract(&self, path: &PathBuf, content: &[u8]) -> Result<ExtractionResult> {
+    fn extract(&self, path: &Path, content: &[u8]) -> Result<ExtractionResult> {
         let source_code = std::str::from_utf8(content)?;
-        
+
+        if !self.rules.is_empty() {
+            return Ok(ExtractionResult {
+                nodes: self.apply_rules(path, source_code),
+                edges: vec![],
+                contains: vec![],
+                calls: vec![],
+                references: vec![],
+                errors: vec![],
+            });
+        }
+
         // Use the parser pool to parse the content with a fallback language
         let request = ParseRequest {
             file_type: FileType::Generic,
             content: source_code.to_string(),
-            path: path.clone(),
+            path: path.to_path_buf(),
         };
-        
+
         let _parse_result = self.parser_pool.parse_blocking(request)?;
-        
+
         // Generic extractor doesn't extract specific symbols
         Ok(ExtractionResult {
             nodes: vec![],
             edges: vec![],
+            contains: vec![],
+            calls: vec![],
+            references: vec![],
+            errors: vec![],
         })
     }
-}
\ No newline at end of file
+}
@@ -1,11 +1,20 @@
 //! Go language extractor using tree-sitter
 
 use super::{ExtractionResult, LanguageExtractor};
-use canopy_core::{GraphNode, GraphEdge, NodeKind, EdgeKind, EdgeSource, Language, NodeId, EdgeId};
-use std::path::PathBuf;
+use canopy_core::{GraphNode, NodeKind, EdgeKind, Language, NodeId};
+use std::path::Path;
 use tree_sitter::{Node, Point};
 use anyhow::Result;
+use crate::extractor::syntax_errors;
+use crate::metadata::{code_lines, node_metadata};
 use crate::parser_pool::{ParserPool, ParseRequest, FileType};
+use crate::calls::local_calls;
+use crate::test_cases::tag_tests;
+use crate::resolve::Reference;
+use crate::heuristics::receivers::{METHODS_KEY, RECEIVER_KEY};
+
+/// Call expressions and the field holding their callee.
+const CALL_KINDS: &[(&str, &str)] = &[("call_expression", "function")];
 
 pub struct GoExtractor {
     parser_pool: ParserPool,
@@ -20,104 +29,152 @@ impl GoExtractor {
         (point.row as u32) + 1
     }
     
-    fn extract_function(&self, node: Node, source: &[u8], path: &PathBuf) -> Option<GraphNode> {
-        if node.kind() == "function_declaration" || node.kind() == "method_declaration" {
-            if let Some(name_node) = node.child_by_field_name("name") {
-                if let Ok(name) = name_node.utf8_text(source) {
-                    let start_pos = Self::point_to_u32(node.start_position());
-                    let end_pos = Self::point_to_u32(node.end_position());
-                    
-                    let kind = if node.kind() == "method_declaration" {
-                        NodeKind::Method
-                    } else {
-                        NodeKind::Function
-                    };
+    fn extract_function(&self, node: Node, source: &[u8], path: &Path) -> Option<GraphNode> {
+        if (node.kind() == "function_declaration" || node.kind() == "method_declaration")
+            && let Some(name_node) = node.child_by_field_name("name")
+            && let Ok(name) = name_node.utf8_text(source) {
+            let start_pos = Self::point_to_u32(node.start_position());
+            let end_pos = Self::point_to_u32(node.end_position());
                     
-                    return Some(GraphNode {
-                        id: NodeId(0), // Will be set by graph
-                        kind,
-                        name: name.to_string(),
-                        qualified_name: format!("{}::{}", path.display(), name),
-                        file_path: path.clone(),
-                        line_start: Some(start_pos),
-                        line_end: Some(end_pos),
-                        language: Some(Language::Go),
-                        is_container: false,
-                        child_count: 0,
-                        loc: Some(((end_pos - start_pos) as usize) as u32),
-                        metadata: std::collections::HashMap::new(),
-                    });
+            let kind = if node.kind() == "method_declaration" {
+                NodeKind::Method
+            } else {
+                NodeKind::Function
+            };
+            let mut metadata = node_metadata(node, source, Language::Go, name);
+            let receiver = Self::receiver_type(node, source);
+            let qualified_name = match receiver {
+                Some(receiver) => {
+                    metadata.extra.insert(RECEIVER_KEY.to_string(), receiver.to_string());
+                    format!("{}::{}::{}", path.display(), receiver, name)
                 }
-            }
+                None => format!("{}::{}", path.display(), name),
+            };
+                    
+            return Some(GraphNode {
+                id: NodeId(0), // Will be set by graph
+                kind,
+                name: name.to_string(),
+                qualified_name: qualified_name.into(),
+                file_path: path.into(),
+                line_start: Some(start_pos),
+                line_end: Some(end_pos),
+                language: Some(Language::Go),
+                is_container: false,
+                child_count: 0,
+                loc: Some(code_lines(node, source)),
+                metadata,
+            });
         }
         None
     }
     
-    fn extract_struct(&self, node: Node, source: &[u8], path: &PathBuf) -> Option<GraphNode> {
-        if node.kind() == "type_declaration" {
-            // Find the struct_spec within type_declaration
-            let mut cursor = node.walk();
-            for child in node.children(&mut cursor) {
-                if child.kind() == "struct_type" {
-                    // Get the parent type_declaration's name
-                    if let Some(parent) = node.child_by_field_name("name") {
-                        if let Ok(name) = parent.utf8_text(source) {
-                            let start_pos = Self::point_to_u32(node.start_position());
-                            let end_pos = Self::point_to_u32(node.end_position());
-                            
-                            return Some(GraphNode {
-                                id: NodeId(0), // Will be set by graph
-                                kind: NodeKind::Struct,
-                                name: name.to_string(),
-                                qualified_name: format!("{}::{}", path.display(), name),
-                                file_path: path.clone(),
-                                line_start: Some(start_pos),
-                                line_end: Some(end_pos),
-                                language: Some(Language::Go),
-                                is_container: true,
-                                child_count: 0,
-                                loc: Some(((end_pos - start_pos) as usize) as u32),
-                                metadata: std::collections::HashMap::new(),
-                            });
-                        }
-                    }
-                }
-            }
+    /// The receiver's type name of a method: `Server` for `func (s *Server)`
+    /// and `func (l List[T])`.
+    fn receiver_type<'s>(node: Node, source: &'s [u8]) -> Option<&'s str> {
+        let receiver = node.child_by_field_name("receiver")?;
+        let mut cursor = receiver.walk();
+        let parameter = receiver.named_children(&mut cursor).find(|p| p.kind() == "parameter_declaration")?;
+        let ty = parameter.child_by_field_name("type")?.utf8_text(source).ok()?;
+        let ty = ty.trim_start_matches('*');
+        Some(ty.split('[').next().unwrap_or(ty).trim())
+    }
+    
+    /// A named type declaration `type Name <kind>`: the spec's name, and the
+    /// node spanning its declaration (the whole `type` statement unless it
+    /// groups several specs).
+    fn type_spec<'t>(node: Node<'t>, source: &'t [u8], kind: &str) -> Option<(&'t str, Node<'t>)> {
+        if node.kind() != "type_spec" || node.child_by_field_name("type")?.kind() != kind {
+            return None;
         }
-        None
+        let name = node.child_by_field_name("name")?.utf8_text(source).ok()?;
+        let declaration = node
+            .parent()
+            .filter(|p| p.kind() == "type_declaration" && p.named_child_count() == 1)
+            .unwrap_or(node);
+        Some((name, declaration))
     }
     
-    fn extract_interface(&self, node: Node, source: &[u8], path: &PathBuf) -> Option<GraphNode> {
-        if node.kind() == "type_declaration" {
-            // Find the interface_spec within type_declaration
-            let mut cursor = node.walk();
-            for child in node.children(&mut cursor) {
-                if child.kind() == "interface_type" {
-                    // Get the parent type_declaration's name
-                    if let Some(parent) = node.child_by_field_name("name") {
-                        if let Ok(name) = parent.utf8_text(source) {
-                            let start_pos = Self::point_to_u32(node.start_position());
-                            let end_pos = Self::point_to_u32(node.end_position());
-                            
-                            return Some(GraphNode {
-                                id: NodeId(0), // Will be set by graph
-                                kind: NodeKind::Interface,
-                                name: name.to_string(),
-                                qualified_name: format!("{}::{}", path.display(), name),
-                                file_path: path.clone(),
-                                line_start: Some(start_pos),
-                                line_end: Some(end_pos),
-                                language: Some(Language::Go),
-                                is_container: true,
-                                child_count: 0,
-                                loc: Some(((end_pos - start_pos) as usize) as u32),
-                                metadata: std::collections::HashMap::new(),
-                            });
-                        }
-                    }
-                }
+    fn extract_struct(&self, node: Node, source: &[u8], path: &Path) -> Option<GraphNode> {
+        let (name, declaration) = Self::type_spec(node, source, "struct_type")?;
+        let start_pos = Self::point_to_u32(declaration.start_position());
+        let end_pos = Self::point_to_u32(declaration.end_position());
+        
+        Some(GraphNode {
+            id: NodeId(0), // Will be set by graph
+            kind: NodeKind::Struct,
+            name: name.to_string(),
+            qualified_name: format!("{}::{}", path.display(), name).into(),
+            file_path: path.into(),
+            line_start: Some(start_pos),
+            line_end: Some(end_pos),
+            language: Some(Language::Go),
+            is_container: true,
+            child_count: 0,
+            loc: Some(code_lines(node, source)),
+            metadata: node_metadata(declaration, source, Language::Go, name),
+        })
+    }
+    
+    fn extract_interface(&self, node: Node, source: &[u8], path: &Path) -> Option<GraphNode> {
+        let (name, declaration) = Self::type_spec(node, source, "interface_type")?;
+        let start_pos = Self::point_to_u32(declaration.start_position());
+        let end_pos = Self::point_to_u32(declaration.end_position());
+        let mut metadata = node_metadata(declaration, source, Language::Go, name);
+        
+        // Method names, for matching against method sets
+        if let Some(interface) = node.child_by_field_name("type") {
+            let mut cursor = interface.walk();
+            let methods: Vec<&str> = interface
+                .named_children(&mut cursor)
+                .filter(|c| c.kind() == "method_elem")
+                .filter_map(|m| m.child_by_field_name("name")?.utf8_text(source).ok())
+                .collect();
+            if !methods.is_empty() {
+                metadata.extra.insert(METHODS_KEY.to_string(), methods.join("\n"));
             }
         }
+        
+        Some(GraphNode {
+            id: NodeId(0), // Will be set by graph
+            kind: NodeKind::Interface,
+            name: name.to_string(),
+            qualified_name: format!("{}::{}", path.display(), name).into(),
+            file_path: path.into(),
+            line_start: Some(start_pos),
+            line_end: Some(end_pos),
+            language: Some(Language::Go),
+            is_container: true,
+            child_count: 0,
+            loc: Some(code_lines(node, source)),
+            metadata,
+        })
+    }
+    
+    /// Package-level `var` declarations
+    fn extract_variable(&self, node: Node, source: &[u8], path: &Path) -> Option<GraphNode> {
+        if node.kind() == "var_spec"
+            && node.parent().and_then(|p| p.parent()).is_some_and(|p| p.kind() == "source_file")
+            && let Some(name_node) = node.child_by_field_name("name")
+            && let Ok(name) = name_node.utf8_text(source) {
+            let start_pos = Self::point_to_u32(node.start_position());
+            let end_pos = Self::point_to_u32(node.end_position());
+                    
+            return Some(GraphNode {
+                id: NodeId(0), // Will be set by graph
+                kind: NodeKind::Variable,
+                name: name.to_string(),
+                qualified_name: format!("{}::{}", path.display(), name).into(),
+                file_path: path.into(),
+                line_start: Some(start_pos),
+                line_end: Some(end_pos),
+                language: Some(Language::Go),
+                is_container: false,
+                child_count: 0,
+                loc: Some(code_lines(node, source)),
+                metadata: node_metadata(node, source, Language::Go, name),
+            });
+        }
         None
     }
     
@@ -126,11 +183,10 @@ impl GoExtractor {
         
         if node.kind() == "import_declaration" {
             // Extract the import path
-            if let Some(path_node) = node.child_by_field_name("path") {
-                if let Ok(path) = path_node.utf8_text(source) {
-                    // Remove quotes
-                    imports.push(path.trim_matches('"').to_string());
-                }
+            if let Some(path_node) = node.child_by_field_name("path")
+                && let Ok(path) = path_node.utf8_text(source) {
+                // Remove quotes
+                imports.push(path.trim_matches('"').to_string());
             }
         }
         
@@ -139,21 +195,20 @@ impl GoExtractor {
 }
 
 impl LanguageExtractor for GoExtractor {
-    fn extract(&self, path: &PathBuf, content: &[u8]) -> Result<ExtractionResult> {
+    fn extract(&self, path: &Path, content: &[u8]) -> Result<ExtractionResult> {
         let source_code = std::str::from_utf8(content)?;
         
         // Use the parser pool to parse the content
         let request = ParseRequest {
             file_type: FileType::Go,
             content: source_code.to_string(),
-            path: path.clone(),
+            path: path.to_path_buf(),
         };
         
         let parse_result = self.parser_pool.parse_blocking(request)?;
         let tree = parse_result.tree;
         
         let mut nodes = Vec::new();
-        let mut edges = Vec::new();
         let mut import_modules = Vec::new();
         
         // Walk the AST
@@ -162,9 +217,8 @@ impl LanguageExtractor for GoExtractor {
         fn visit_node(
             node: Node,
             source: &str,
-            path: &PathBuf,
+            path: &Path,
             nodes: &mut Vec<GraphNode>,
-            edges: &mut Vec<GraphEdge>,
             imports: &mut Vec<String>,
             extractor: &GoExtractor,
         ) {
@@ -183,39 +237,28 @@ impl LanguageExtractor for GoExtractor {
                 nodes.push(interface);
             }
             
+            // Extract package-level variables
+            if let Some(variable) = extractor.extract_variable(node, source.as_bytes(), path) {
+                nodes.push(variable);
+            }
+            
             // Extract imports
             imports.extend(extractor.extract_imports(node, source.as_bytes()));
             
             // Visit children
             let mut cursor = node.walk();
             for child in node.children(&mut cursor) {
-                visit_node(child, source, path, nodes, edges, imports, extractor);
+                visit_node(child, source, path, nodes, imports, extractor);
             }
         }
         
         // Start visiting from root
-        visit_node(root_node, source_code, path, &mut nodes, &mut edges, &mut import_modules, self);
+        visit_node(root_node, source_code, path, &mut nodes, &mut import_modules, self);
         
-        // Create edges from imports to nodes
-        for import in &import_modules {
-            for node in &nodes {
-                // Simple heuristic: if node name appears in import or vice versa
-                if import.contains(&node.name) || node.name.contains(import) {
-                    edges.push(GraphEdge {
-                        id: EdgeId(0), // Will be set by graph
-                        source: NodeId(0), // Placeholder - would need proper resolution
-                        target: node.id,
-                        kind: EdgeKind::Imports,
-                        edge_source: EdgeSource::Heuristic,
-                        confidence: 0.7,
-                        label: Some(format!("imports {}", import)),
-                        file_path: Some(path.clone()),
-                        line: node.line_start,
-                    });
-                }
-            }
-        }
+        tag_tests(path, &mut nodes);
         
-        Ok(ExtractionResult { nodes, edges })
+        let (calls, mut references) = local_calls(root_node, source_code.as_bytes(), &nodes, CALL_KINDS);
+        references.extend(import_modules.iter().map(|import| Reference::file_level(EdgeKind::Imports, "imports", import)));
+        Ok(ExtractionResult { nodes, edges: Vec::new(), contains: Vec::new(), calls, references, errors: syntax_errors(root_node) })
     }
 }
\ No newline at end of file
@@ -0,0 +1,350 @@
+//! GraphQL schema and operation extractor
+//!
+//! Schema types become Class/Interface/Enum/Union/Struct nodes, fields of the
+//! root types (`Query`, `Mutation`, `Subscription`) become Function nodes, and
+//! named operations become Function nodes with TypeReference references to
+//! every type they select. Types are resolved through the schema when it
+//! lives in the same file; otherwise only root types and fragment type
+//! conditions are known.
+
+use super::{ExtractionResult, LanguageExtractor};
+use canopy_core::{GraphNode, NodeKind, EdgeKind, Language, NodeId, NodeMetadata};
+use crate::resolve::Reference;
+use graphql_parser::query::{self, OperationDefinition, Selection, SelectionSet, TypeCondition};
+use graphql_parser::schema::{self, Type, TypeDefinition};
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+use anyhow::Result;
+
+/// Scalars every schema has; selecting them is not worth an edge.
+const BUILTIN_SCALARS: &[&str] = &["String", "Int", "Float", "Boolean", "ID"];
+
+/// type name → field name → named field type.
+type SchemaTypes = HashMap<String, HashMap<String, String>>;
+
+#[derive(Default)]
+pub struct GraphQlExtractor;
+
+impl GraphQlExtractor {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Top-level definitions as (first line, last line, text). Each definition is
+/// parsed on its own so schema and operations can share a file.
+fn split_definitions(source: &str) -> Vec<(usize, usize, String)> {
+    let mut chunks: Vec<(usize, usize, String)> = Vec::new();
+    let mut depth = 0i32;
+    let mut in_block_string = false;
+    for (row, line) in source.lines().enumerate() {
+        let code = line.split('#').next().unwrap_or("");
+        let starts_definition = depth == 0
+            && !in_block_string
+            && code.starts_with(|c: char| c.is_alphabetic() || c == '{' || c == '"');
+        in_block_string ^= line.matches("\"\"\"").count() % 2 == 1;
+        match chunks.last_mut() {
+            // Continuation lines, and definitions preceded by a description
+            Some((_, end, text))
+                if !starts_definition
+                    || text.trim_end().ends_with(['=', '|', '&'])
+                    || (text.trim_start().starts_with('"') && text.trim_end().ends_with('"')) =>
+            {
+                text.push('\n');
+                text.push_str(line);
+                if !code.trim().is_empty() {
+                    *end = row + 1;
+                }
+            }
+            _ => chunks.push((row + 1, row + 1, line.to_string())),
+        }
+        depth += code.matches(['{', '(']).count() as i32 - code.matches(['}', ')']).count() as i32;
+    }
+    chunks.retain(|(_, _, text)| !text.trim().is_empty());
+    chunks
+}
+
+/// Text after a leading `"description"` or `"""description"""`, if any.
+fn skip_description(text: &str) -> &str {
+    let text = text.trim_start();
+    let rest = if let Some(rest) = text.strip_prefix("\"\"\"") {
+        rest.find("\"\"\"").map(|end| &rest[end + 3..])
+    } else if let Some(rest) = text.strip_prefix('"') {
+        rest.find('"').map(|end| &rest[end + 1..])
+    } else {
+        Some(text)
+    };
+    rest.unwrap_or("").trim_start()
+}
+
+fn named_type<'a>(ty: &'a Type<'_, String>) -> &'a str {
+    match ty {
+        Type::NamedType(name) => name,
+        Type::ListType(inner) | Type::NonNullType(inner) => named_type(inner),
+    }
+}
+
+fn make_node(path: &Path, kind: NodeKind, name: &str, lines: (usize, usize), metadata: NodeMetadata) -> GraphNode {
+    let (start, end) = (lines.0 as u32, lines.1 as u32);
+    GraphNode {
+        id: NodeId(0), // Will be set by graph
+        kind,
+        name: name.to_string(),
+        qualified_name: format!("{}::{}", path.display(), name).into(),
+        file_path: path.into(),
+        line_start: Some(start),
+        line_end: Some(end),
+        language: Some(Language::GraphQL),
+        is_container: !matches!(kind, NodeKind::Function),
+        child_count: 0,
+        loc: Some(end - start),
+        metadata,
+    }
+}
+
+fn description_metadata(description: &Option<String>) -> NodeMetadata {
+    NodeMetadata {
+        doc_summary: description.as_ref().and_then(|d| d.lines().map(str::trim).find(|l| !l.is_empty())).map(str::to_string),
+        ..Default::default()
+    }
+}
+
+/// Collects the types an operation selects, following the schema where known.
+struct SelectionWalker<'a> {
+    types: &'a SchemaTypes,
+    fragments: &'a HashMap<String, query::FragmentDefinition<'static, String>>,
+}
+
+impl SelectionWalker<'_> {
+    fn walk(&self, parent: Option<&str>, set: &SelectionSet<'_, String>, selected: &mut BTreeSet<String>, depth: usize) {
+        if depth > 32 {
+            return;
+        }
+        for item in &set.items {
+            match item {
+                Selection::Field(field) => {
+                    let child = parent
+                        .and_then(|p| self.types.get(p))
+                        .and_then(|fields| fields.get(&field.name));
+                    if let Some(child) = child.filter(|c| !BUILTIN_SCALARS.contains(&c.as_str())) {
+                        selected.insert(child.clone());
+                    }
+                    self.walk(child.map(String::as_str), &field.selection_set, selected, depth + 1);
+                }
+                Selection::InlineFragment(fragment) => {
+                    let parent = match &fragment.type_condition {
+                        Some(TypeCondition::On(on)) => {
+                            selected.insert(on.clone());
+                            Some(on.as_str())
+                        }
+                        None => parent,
+                    };
+                    self.walk(parent, &fragment.selection_set, selected, depth + 1);
+                }
+                Selection::FragmentSpread(spread) => {
+                    if let Some(fragment) = self.fragments.get(&spread.fragment_name) {
+                        let TypeCondition::On(on) = &fragment.type_condition;
+                        selected.insert(on.clone());
+                        self.walk(Some(on), &fragment.selection_set, selected, depth + 1);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl LanguageExtractor for GraphQlExtractor {
+    fn extract(&self, path: &Path, content: &[u8]) -> Result<ExtractionResult> {
+        let source_code = std::str::from_utf8(content)?;
+
+        let mut nodes = Vec::new();
+        let mut references = Vec::new();
+
+        let mut schema_defs = Vec::new();
+        let mut query_defs = Vec::new();
+        for (start, end, text) in split_definitions(source_code) {
+            // Pad so parser positions match file lines
+            let padded = format!("{}{}", "\n".repeat(start - 1), text);
+            let keyword = skip_description(&text).split(|c: char| !c.is_alphanumeric()).next().unwrap_or("");
+            if matches!(keyword, "query" | "mutation" | "subscription" | "fragment" | "") {
+                if let Ok(doc) = query::parse_query::<String>(&padded) {
+                    query_defs.extend(doc.into_static().definitions.into_iter().map(|d| (end, d)));
+                }
+            } else if let Ok(doc) = schema::parse_schema::<String>(&padded) {
+                schema_defs.extend(doc.into_static().definitions.into_iter().map(|d| (end, d)));
+            }
+        }
+
+        // Root operation types, overridable by `schema { query: RootQuery }`
+        let mut roots: HashMap<&str, String> = [("query", "Query"), ("mutation", "Mutation"), ("subscription", "Subscription")]
+            .into_iter()
+            .map(|(op, ty)| (op, ty.to_string()))
+            .collect();
+        for (_, definition) in &schema_defs {
+            if let schema::Definition::SchemaDefinition(def) = definition {
+                for (op, ty) in [("query", &def.query), ("mutation", &def.mutation), ("subscription", &def.subscription)] {
+                    if let Some(ty) = ty {
+                        roots.insert(op, ty.clone());
+                    }
+                }
+            }
+        }
+        let root_of = |ty: &str| roots.iter().find(|(_, root)| root.as_str() == ty).map(|(op, _)| *op);
+
+        let mut types = SchemaTypes::new();
+        for (end, definition) in &schema_defs {
+            let (kind, position, name, description, fields) = match definition {
+                schema::Definition::TypeDefinition(TypeDefinition::Object(t)) => (NodeKind::Class, t.position, &t.name, &t.description, &t.fields[..]),
+                schema::Definition::TypeDefinition(TypeDefinition::Interface(t)) => (NodeKind::Interface, t.position, &t.name, &t.description, &t.fields[..]),
+                schema::Definition::TypeDefinition(TypeDefinition::Union(t)) => (NodeKind::Union, t.position, &t.name, &t.description, &[][..]),
+                schema::Definition::TypeDefinition(TypeDefinition::Enum(t)) => (NodeKind::Enum, t.position, &t.name, &t.description, &[][..]),
+                schema::Definition::TypeDefinition(TypeDefinition::InputObject(t)) => (NodeKind::Struct, t.position, &t.name, &t.description, &[][..]),
+                schema::Definition::TypeDefinition(TypeDefinition::Scalar(t)) => (NodeKind::TypeAlias, t.position, &t.name, &t.description, &[][..]),
+                schema::Definition::TypeExtension(schema::TypeExtension::Object(t)) => {
+                    // `extend type Query` adds fields without a new type node
+                    types.entry(t.name.clone()).or_default().extend(t.fields.iter().map(|f| (f.name.clone(), named_type(&f.field_type).to_string())));
+                    if let Some(op) = root_of(&t.name) {
+                        for field in &t.fields {
+                            nodes.push(root_field_node(path, op, &t.name, field));
+                        }
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+            nodes.push(make_node(path, kind, name, (position.line, *end), description_metadata(description)));
+            types.entry(name.clone()).or_default().extend(fields.iter().map(|f| (f.name.clone(), named_type(&f.field_type).to_string())));
+            if let Some(op) = root_of(name) {
+                for field in fields {
+                    nodes.push(root_field_node(path, op, name, field));
+                }
+            }
+        }
+
+        let fragments: HashMap<String, query::FragmentDefinition<'static, String>> = query_defs
+            .iter()
+            .filter_map(|(_, d)| match d {
+                query::Definition::Fragment(f) => Some((f.name.clone(), f.clone())),
+                _ => None,
+            })
+            .collect();
+        let walker = SelectionWalker { types: &types, fragments: &fragments };
+
+        for (end, definition) in &query_defs {
+            let (op, position, name, selection_set) = match definition {
+                query::Definition::Operation(OperationDefinition::Query(q)) => ("query", q.position, &q.name, &q.selection_set),
+                query::Definition::Operation(OperationDefinition::Mutation(m)) => ("mutation", m.position, &m.name, &m.selection_set),
+                query::Definition::Operation(OperationDefinition::Subscription(s)) => ("subscription", s.position, &s.name, &s.selection_set),
+                query::Definition::Operation(OperationDefinition::SelectionSet(s)) => ("query", s.span.0, &None, s),
+                query::Definition::Fragment(_) => continue,
+            };
+            let name = name.clone().unwrap_or_else(|| format!("anonymous {} at line {}", op, position.line));
+            nodes.push(make_node(
+                path,
+                NodeKind::Function,
+                &name,
+                (position.line, *end),
+                NodeMetadata::with_extra([("graphql_operation", op.to_string())]),
+            ));
+
+            let root = &roots[op];
+            let mut selected = BTreeSet::from([root.clone()]);
+            walker.walk(Some(root), selection_set, &mut selected, 0);
+            for ty in selected {
+                references.push(Reference {
+                    source: Some(nodes.len() - 1),
+                    kind: EdgeKind::TypeReference,
+                    label: format!("{} selects {}", name, ty),
+                    target: ty,
+                    line: Some(position.line as u32),
+                    local_first: false,
+                });
+            }
+        }
+
+        Ok(ExtractionResult { nodes, edges: Vec::new(), contains: Vec::new(), calls: Vec::new(), references, errors: Vec::new() })
+    }
+}
+
+/// A field of a root operation type, e.g. `Query.user`.
+fn root_field_node(path: &Path, op: &str, type_name: &str, field: &schema::Field<'_, String>) -> GraphNode {
+    let mut metadata = description_metadata(&field.description);
+    metadata.extra.insert("graphql_root".to_string(), op.to_string());
+    metadata.extra.insert("graphql_type".to_string(), named_type(&field.field_type).to_string());
+    let line = field.position.line;
+    make_node(path, NodeKind::Function, &format!("{}.{}", type_name, field.name), (line, line), metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_graphql() {
+        let code = r#"
+"""A registered user."""
+type User {
+  id: ID!
+  posts: [Post!]!
+}
+
+type Post {
+  title: String
+}
+
+type Query {
+  "Look up a user."
+  user(id: ID!): User
+}
+
+type Mutation {
+  createPost(title: String!): Post
+}
+
+query GetUser($id: ID!) {
+  user(id: $id) {
+    ...UserPosts
+  }
+}
+
+fragment UserPosts on User {
+  posts { title }
+}
+
+mutation {
+  createPost(title: "hi") { title }
+}
+"#;
+
+        let path = Path::new("schema.graphql");
+        let result = GraphQlExtractor::new().extract(path, code.as_bytes()).unwrap();
+
+        let find = |name: &str| result.nodes.iter().find(|n| n.name == name).unwrap();
+        let user = find("User");
+        assert_eq!(user.kind, NodeKind::Class);
+        assert_eq!((user.line_start, user.line_end), (Some(3), Some(6)));
+        assert_eq!(user.metadata.doc_summary.as_deref(), Some("A registered user."));
+
+        let query_user = find("Query.user");
+        assert_eq!(query_user.kind, NodeKind::Function);
+        assert_eq!(query_user.metadata.extra.get("graphql_root").map(String::as_str), Some("query"));
+        assert_eq!(find("Mutation.createPost").metadata.extra.get("graphql_type").map(String::as_str), Some("Post"));
+
+        let get_user = find("GetUser");
+        assert_eq!(get_user.metadata.extra.get("graphql_operation").map(String::as_str), Some("query"));
+        assert_eq!((get_user.line_start, get_user.line_end), (Some(21), Some(25)));
+        assert!(result.nodes.iter().any(|n| n.name == "anonymous mutation at line 31"));
+
+        let labels: Vec<_> = result.references.iter().map(|r| r.label.as_str()).collect();
+        assert_eq!(
+            labels,
+            vec![
+                "GetUser selects Post",
+                "GetUser selects Query",
+                "GetUser selects User",
+                "anonymous mutation at line 31 selects Mutation",
+                "anonymous mutation at line 31 selects Post",
+            ]
+        );
+    }
+}
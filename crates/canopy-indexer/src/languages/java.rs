@@ -1,11 +1,24 @@
 //! Java language extractor using tree-sitter
 
 use super::{ExtractionResult, LanguageExtractor};
-use canopy_core::{GraphNode, GraphEdge, NodeKind, EdgeKind, EdgeSource, Language, NodeId, EdgeId};
-use std::path::PathBuf;
+use canopy_core::{GraphNode, NodeKind, EdgeKind, Language, NodeId};
+use std::path::Path;
 use tree_sitter::{Node, Point};
 use anyhow::Result;
+use crate::extractor::syntax_errors;
+use crate::metadata::{code_lines, node_metadata};
 use crate::parser_pool::{ParserPool, ParseRequest, FileType};
+use crate::calls::local_calls;
+use crate::test_cases::tag_tests;
+use crate::resolve::Reference;
+
+/// Call expressions and the field holding their callee.
+const CALL_KINDS: &[(&str, &str)] = &[("method_invocation", "name")];
+
+/// `name` within the package or type `scope`.
+fn qualify(scope: &str, name: &str) -> String {
+    if scope.is_empty() { name.to_string() } else { format!("{}.{}", scope, name) }
+}
 
 pub struct JavaExtractor {
     parser_pool: ParserPool,
@@ -20,83 +33,103 @@ impl JavaExtractor {
         (point.row as u32) + 1
     }
     
-    fn extract_method(&self, node: Node, source: &[u8], path: &PathBuf) -> Option<GraphNode> {
-        if node.kind() == "method_declaration" {
-            if let Some(name_node) = node.child_by_field_name("name") {
-                if let Ok(name) = name_node.utf8_text(source) {
-                    let start_pos = Self::point_to_u32(node.start_position());
-                    let end_pos = Self::point_to_u32(node.end_position());
+    fn extract_method(&self, node: Node, source: &[u8], path: &Path, scope: &str) -> Option<GraphNode> {
+        if node.kind() == "method_declaration"
+            && let Some(name_node) = node.child_by_field_name("name")
+            && let Ok(name) = name_node.utf8_text(source) {
+            let start_pos = Self::point_to_u32(node.start_position());
+            let end_pos = Self::point_to_u32(node.end_position());
                     
-                    return Some(GraphNode {
-                        id: NodeId(0), // Will be set by graph
-                        kind: NodeKind::Method,
-                        name: name.to_string(),
-                        qualified_name: format!("{}::{}", path.display(), name),
-                        file_path: path.clone(),
-                        line_start: Some(start_pos),
-                        line_end: Some(end_pos),
-                        language: Some(Language::Java),
-                        is_container: false,
-                        child_count: 0,
-                        loc: Some(((end_pos - start_pos) as usize) as u32),
-                        metadata: std::collections::HashMap::new(),
-                    });
-                }
-            }
+            return Some(GraphNode {
+                id: NodeId(0), // Will be set by graph
+                kind: NodeKind::Method,
+                name: name.to_string(),
+                qualified_name: qualify(scope, name).into(),
+                file_path: path.into(),
+                line_start: Some(start_pos),
+                line_end: Some(end_pos),
+                language: Some(Language::Java),
+                is_container: false,
+                child_count: 0,
+                loc: Some(code_lines(node, source)),
+                metadata: node_metadata(node, source, Language::Java, name),
+            });
         }
         None
     }
     
-    fn extract_class(&self, node: Node, source: &[u8], path: &PathBuf) -> Option<GraphNode> {
-        if node.kind() == "class_declaration" {
-            if let Some(name_node) = node.child_by_field_name("name") {
-                if let Ok(name) = name_node.utf8_text(source) {
-                    let start_pos = Self::point_to_u32(node.start_position());
-                    let end_pos = Self::point_to_u32(node.end_position());
+    fn extract_class(&self, node: Node, source: &[u8], path: &Path, scope: &str) -> Option<GraphNode> {
+        if node.kind() == "class_declaration"
+            && let Some(name_node) = node.child_by_field_name("name")
+            && let Ok(name) = name_node.utf8_text(source) {
+            let start_pos = Self::point_to_u32(node.start_position());
+            let end_pos = Self::point_to_u32(node.end_position());
                     
-                    return Some(GraphNode {
-                        id: NodeId(0), // Will be set by graph
-                        kind: NodeKind::Class,
-                        name: name.to_string(),
-                        qualified_name: format!("{}::{}", path.display(), name),
-                        file_path: path.clone(),
-                        line_start: Some(start_pos),
-                        line_end: Some(end_pos),
-                        language: Some(Language::Java),
-                        is_container: true,
-                        child_count: 0,
-                        loc: Some(((end_pos - start_pos) as usize) as u32),
-                        metadata: std::collections::HashMap::new(),
-                    });
-                }
-            }
+            return Some(GraphNode {
+                id: NodeId(0), // Will be set by graph
+                kind: NodeKind::Class,
+                name: name.to_string(),
+                qualified_name: qualify(scope, name).into(),
+                file_path: path.into(),
+                line_start: Some(start_pos),
+                line_end: Some(end_pos),
+                language: Some(Language::Java),
+                is_container: true,
+                child_count: 0,
+                loc: Some(code_lines(node, source)),
+                metadata: node_metadata(node, source, Language::Java, name),
+            });
         }
         None
     }
     
-    fn extract_interface(&self, node: Node, source: &[u8], path: &PathBuf) -> Option<GraphNode> {
-        if node.kind() == "interface_declaration" {
-            if let Some(name_node) = node.child_by_field_name("name") {
-                if let Ok(name) = name_node.utf8_text(source) {
-                    let start_pos = Self::point_to_u32(node.start_position());
-                    let end_pos = Self::point_to_u32(node.end_position());
+    fn extract_interface(&self, node: Node, source: &[u8], path: &Path, scope: &str) -> Option<GraphNode> {
+        if node.kind() == "interface_declaration"
+            && let Some(name_node) = node.child_by_field_name("name")
+            && let Ok(name) = name_node.utf8_text(source) {
+            let start_pos = Self::point_to_u32(node.start_position());
+            let end_pos = Self::point_to_u32(node.end_position());
                     
-                    return Some(GraphNode {
-                        id: NodeId(0), // Will be set by graph
-                        kind: NodeKind::Interface,
-                        name: name.to_string(),
-                        qualified_name: format!("{}::{}", path.display(), name),
-                        file_path: path.clone(),
-                        line_start: Some(start_pos),
-                        line_end: Some(end_pos),
-                        language: Some(Language::Java),
-                        is_container: true,
-                        child_count: 0,
-                        loc: Some(((end_pos - start_pos) as usize) as u32),
-                        metadata: std::collections::HashMap::new(),
-                    });
-                }
-            }
+            return Some(GraphNode {
+                id: NodeId(0), // Will be set by graph
+                kind: NodeKind::Interface,
+                name: name.to_string(),
+                qualified_name: qualify(scope, name).into(),
+                file_path: path.into(),
+                line_start: Some(start_pos),
+                line_end: Some(end_pos),
+                language: Some(Language::Java),
+                is_container: true,
+                child_count: 0,
+                loc: Some(code_lines(node, source)),
+                metadata: node_metadata(node, source, Language::Java, name),
+            });
+        }
+        None
+    }
+    
+    fn extract_field(&self, node: Node, source: &[u8], path: &Path, scope: &str) -> Option<GraphNode> {
+        if node.kind() == "field_declaration"
+            && let Some(declarator) = node.child_by_field_name("declarator")
+            && let Some(name_node) = declarator.child_by_field_name("name")
+            && let Ok(name) = name_node.utf8_text(source) {
+            let start_pos = Self::point_to_u32(node.start_position());
+            let end_pos = Self::point_to_u32(node.end_position());
+                    
+            return Some(GraphNode {
+                id: NodeId(0), // Will be set by graph
+                kind: NodeKind::Field,
+                name: name.to_string(),
+                qualified_name: qualify(scope, name).into(),
+                file_path: path.into(),
+                line_start: Some(start_pos),
+                line_end: Some(end_pos),
+                language: Some(Language::Java),
+                is_container: false,
+                child_count: 0,
+                loc: Some(code_lines(node, source)),
+                metadata: node_metadata(node, source, Language::Java, name),
+            });
         }
         None
     }
@@ -105,10 +138,9 @@ impl JavaExtractor {
         if node.kind() == "package_declaration" {
             let mut cursor = node.walk();
             for child in node.children(&mut cursor) {
-                if child.kind() == "scoped_identifier" || child.kind() == "identifier" {
-                    if let Ok(name) = child.utf8_text(source) {
-                        return Some(name.to_string());
-                    }
+                if (child.kind() == "scoped_identifier" || child.kind() == "identifier")
+                    && let Ok(name) = child.utf8_text(source) {
+                    return Some(name.to_string());
                 }
             }
         }
@@ -122,10 +154,9 @@ impl JavaExtractor {
             // Extract the import path
             let mut cursor = node.walk();
             for child in node.children(&mut cursor) {
-                if child.kind() == "scoped_identifier" || child.kind() == "asterisk" {
-                    if let Ok(path) = child.utf8_text(source) {
-                        imports.push(path.to_string());
-                    }
+                if (child.kind() == "scoped_identifier" || child.kind() == "asterisk")
+                    && let Ok(path) = child.utf8_text(source) {
+                    imports.push(path.to_string());
                 }
             }
         }
@@ -135,92 +166,84 @@ impl JavaExtractor {
 }
 
 impl LanguageExtractor for JavaExtractor {
-    fn extract(&self, path: &PathBuf, content: &[u8]) -> Result<ExtractionResult> {
+    fn extract(&self, path: &Path, content: &[u8]) -> Result<ExtractionResult> {
         let source_code = std::str::from_utf8(content)?;
         
         // Use the parser pool to parse the content
         let request = ParseRequest {
             file_type: FileType::Java,
             content: source_code.to_string(),
-            path: path.clone(),
+            path: path.to_path_buf(),
         };
         
         let parse_result = self.parser_pool.parse_blocking(request)?;
         let tree = parse_result.tree;
         
         let mut nodes = Vec::new();
-        let mut edges = Vec::new();
         let mut import_modules = Vec::new();
-        let mut package_name = None;
         
         // Walk the AST
         let root_node = tree.root_node();
         
+        // Symbols are qualified by the file's package, and members by their
+        // enclosing types
+        let mut cursor = root_node.walk();
+        let package_name = root_node
+            .children(&mut cursor)
+            .find_map(|child| self.extract_package(child, source_code.as_bytes()))
+            .unwrap_or_default();
+        
         fn visit_node(
             node: Node,
             source: &str,
-            path: &PathBuf,
+            path: &Path,
+            scope: &str,
             nodes: &mut Vec<GraphNode>,
-            edges: &mut Vec<GraphEdge>,
             imports: &mut Vec<String>,
-            package_name: &mut Option<String>,
             extractor: &JavaExtractor,
         ) {
-            // Extract package declaration
-            if package_name.is_none() {
-                if let Some(pkg) = extractor.extract_package(node, source.as_bytes()) {
-                    *package_name = Some(pkg);
-                }
-            }
+            let mut inner_scope = None;
             
             // Extract classes
-            if let Some(class) = extractor.extract_class(node, source.as_bytes(), path) {
+            if let Some(class) = extractor.extract_class(node, source.as_bytes(), path, scope) {
+                inner_scope = Some(class.qualified_name.to_string());
                 nodes.push(class);
             }
             
             // Extract interfaces
-            if let Some(interface) = extractor.extract_interface(node, source.as_bytes(), path) {
+            if let Some(interface) = extractor.extract_interface(node, source.as_bytes(), path, scope) {
+                inner_scope = Some(interface.qualified_name.to_string());
                 nodes.push(interface);
             }
             
             // Extract methods
-            if let Some(method) = extractor.extract_method(node, source.as_bytes(), path) {
+            if let Some(method) = extractor.extract_method(node, source.as_bytes(), path, scope) {
                 nodes.push(method);
             }
             
+            // Extract fields
+            if let Some(field) = extractor.extract_field(node, source.as_bytes(), path, scope) {
+                nodes.push(field);
+            }
+            
             // Extract imports
             imports.extend(extractor.extract_imports(node, source.as_bytes()));
             
             // Visit children
+            let scope = inner_scope.as_deref().unwrap_or(scope);
             let mut cursor = node.walk();
             for child in node.children(&mut cursor) {
-                visit_node(child, source, path, nodes, edges, imports, package_name, extractor);
+                visit_node(child, source, path, scope, nodes, imports, extractor);
             }
         }
         
         // Start visiting from root
-        visit_node(root_node, source_code, path, &mut nodes, &mut edges, &mut import_modules, &mut package_name, self);
+        visit_node(root_node, source_code, path, &package_name, &mut nodes, &mut import_modules, self);
         
-        // Create edges from imports to nodes
-        for import in &import_modules {
-            for node in &nodes {
-                // Simple heuristic: if node name appears in import or vice versa
-                if import.contains(&node.name) || node.name.contains(import) {
-                    edges.push(GraphEdge {
-                        id: EdgeId(0), // Will be set by graph
-                        source: NodeId(0), // Placeholder - would need proper resolution
-                        target: node.id,
-                        kind: EdgeKind::Imports,
-                        edge_source: EdgeSource::Heuristic,
-                        confidence: 0.7,
-                        label: Some(format!("imports {}", import)),
-                        file_path: Some(path.clone()),
-                        line: node.line_start,
-                    });
-                }
-            }
-        }
+        tag_tests(path, &mut nodes);
         
-        Ok(ExtractionResult { nodes, edges })
+        let (calls, mut references) = local_calls(root_node, source_code.as_bytes(), &nodes, CALL_KINDS);
+        references.extend(import_modules.iter().map(|import| Reference::file_level(EdgeKind::Imports, "imports", import)));
+        Ok(ExtractionResult { nodes, edges: Vec::new(), contains: Vec::new(), calls, references, errors: syntax_errors(root_node) })
     }
 }
\ No newline at end of file
@@ -1,11 +1,44 @@
 //! JavaScript language extractor using tree-sitter
 
-use super::{ExtractionResult, LanguageExtractor};
-use canopy_core::{GraphNode, GraphEdge, NodeKind, EdgeSource, Language, NodeId, EdgeId};
-use std::path::PathBuf;
+use super::{react, ExtractionResult, LanguageExtractor};
+use canopy_core::{GraphNode, NodeKind, EdgeKind, Language, NodeId};
+use std::path::Path;
 use tree_sitter::{Node, Point};
 use anyhow::Result;
+use crate::extractor::syntax_errors;
+use crate::metadata::{code_lines, node_metadata};
 use crate::parser_pool::{ParserPool, ParseRequest, FileType};
+use crate::calls::local_calls;
+use crate::test_cases::test_blocks;
+use crate::resolve::Reference;
+
+/// Call expressions and the field holding their callee.
+const CALL_KINDS: &[(&str, &str)] = &[("call_expression", "function")];
+
+/// The module a JavaScript or TypeScript file defines, as another package
+/// imports it: the name of the nearest `package.json`'s package, then the
+/// path from there without the extension, an `index` file standing for its
+/// directory. Files outside any package keep their full path.
+pub fn module_specifier(path: &Path) -> String {
+    let mut module = path.with_extension("");
+    if module.file_name().is_some_and(|name| name == "index") {
+        module.pop();
+    }
+    let Some(package) = path.ancestors().skip(1).find(|dir| dir.join("package.json").is_file()) else {
+        return module.display().to_string();
+    };
+    let name = std::fs::read_to_string(package.join("package.json"))
+        .ok()
+        .and_then(|manifest| serde_json::from_str::<serde_json::Value>(&manifest).ok())
+        .and_then(|manifest| manifest.get("name")?.as_str().map(str::to_string));
+    let relative: Vec<_> = module
+        .strip_prefix(package)
+        .unwrap_or(&module)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect();
+    name.into_iter().map(Into::into).chain(relative).collect::<Vec<_>>().join("/")
+}
 
 pub struct JavaScriptExtractor {
     parser_pool: ParserPool,
@@ -20,64 +53,65 @@ impl JavaScriptExtractor {
         (point.row as u32) + 1
     }
     
-    fn extract_function(&self, node: Node, source: &[u8], path: &PathBuf) -> Option<GraphNode> {
+    fn extract_function(&self, node: Node, source: &[u8], path: &Path, module: &str) -> Option<GraphNode> {
         if node.kind() == "function_declaration" || 
            node.kind() == "function_expression" ||
            node.kind() == "arrow_function" ||
            node.kind() == "method_definition" {
-            // Find the identifier node
-            let mut cursor = node.walk();
-            for child in node.children(&mut cursor) {
-                if child.kind() == "identifier" {
-                    if let Ok(name) = child.utf8_text(source) {
-                        let start_pos = Self::point_to_u32(node.start_position());
-                        let end_pos = Self::point_to_u32(node.end_position());
+            // Methods and declarations carry their own name; anonymous functions
+            // take the name of the variable they are assigned to
+            let name_node = node.child_by_field_name("name").or_else(|| {
+                node.parent()
+                    .filter(|parent| parent.kind() == "variable_declarator")
+                    .and_then(|parent| parent.child_by_field_name("name"))
+            });
+            if let Some(name_node) = name_node
+                && let Ok(name) = name_node.utf8_text(source) {
+                let start_pos = Self::point_to_u32(node.start_position());
+                let end_pos = Self::point_to_u32(node.end_position());
                         
-                        return Some(GraphNode {
-                            id: NodeId(0), // Will be set by graph
-                            kind: NodeKind::Function,
-                            name: name.to_string(),
-                            qualified_name: format!("{}::{}", path.display(), name),
-                            file_path: path.clone(),
-                            line_start: Some(start_pos),
-                            line_end: Some(end_pos),
-                            language: Some(Language::JavaScript),
-                            is_container: false,
-                            child_count: 0,
-                            loc: Some(((end_pos - start_pos) as usize) as u32),
-                            metadata: std::collections::HashMap::new(),
-                        });
-                    }
-                }
+                return Some(GraphNode {
+                    id: NodeId(0), // Will be set by graph
+                    kind: NodeKind::Function,
+                    name: name.to_string(),
+                    qualified_name: format!("{}::{}", module, name).into(),
+                    file_path: path.into(),
+                    line_start: Some(start_pos),
+                    line_end: Some(end_pos),
+                    language: Some(Language::JavaScript),
+                    is_container: false,
+                    child_count: 0,
+                    loc: Some(code_lines(node, source)),
+                    metadata: node_metadata(node, source, Language::JavaScript, name),
+                });
             }
         }
         None
     }
     
-    fn extract_class(&self, node: Node, source: &[u8], path: &PathBuf) -> Option<GraphNode> {
+    fn extract_class(&self, node: Node, source: &[u8], path: &Path, module: &str) -> Option<GraphNode> {
         if node.kind() == "class_declaration" {
             let mut cursor = node.walk();
             for child in node.children(&mut cursor) {
-                if child.kind() == "identifier" {
-                    if let Ok(name) = child.utf8_text(source) {
-                        let start_pos = Self::point_to_u32(node.start_position());
-                        let end_pos = Self::point_to_u32(node.end_position());
+                if child.kind() == "identifier"
+                    && let Ok(name) = child.utf8_text(source) {
+                    let start_pos = Self::point_to_u32(node.start_position());
+                    let end_pos = Self::point_to_u32(node.end_position());
                         
-                        return Some(GraphNode {
-                            id: NodeId(0), // Will be set by graph
-                            kind: NodeKind::Class,
-                            name: name.to_string(),
-                            qualified_name: format!("{}::{}", path.display(), name),
-                            file_path: path.clone(),
-                            line_start: Some(start_pos),
-                            line_end: Some(end_pos),
-                            language: Some(Language::JavaScript),
-                            is_container: true,
-                            child_count: 0,
-                            loc: Some(((end_pos - start_pos) as usize) as u32),
-                            metadata: std::collections::HashMap::new(),
-                        });
-                    }
+                    return Some(GraphNode {
+                        id: NodeId(0), // Will be set by graph
+                        kind: NodeKind::Class,
+                        name: name.to_string(),
+                        qualified_name: format!("{}::{}", module, name).into(),
+                        file_path: path.into(),
+                        line_start: Some(start_pos),
+                        line_end: Some(end_pos),
+                        language: Some(Language::JavaScript),
+                        is_container: true,
+                        child_count: 0,
+                        loc: Some(code_lines(node, source)),
+                        metadata: node_metadata(node, source, Language::JavaScript, name),
+                    });
                 }
             }
         }
@@ -112,21 +146,21 @@ impl JavaScriptExtractor {
 }
 
 impl LanguageExtractor for JavaScriptExtractor {
-    fn extract(&self, path: &PathBuf, content: &[u8]) -> Result<ExtractionResult> {
+    fn extract(&self, path: &Path, content: &[u8]) -> Result<ExtractionResult> {
         let source_code = std::str::from_utf8(content)?;
         
         // Use the parser pool to parse the content
         let request = ParseRequest {
             file_type: FileType::JavaScript,
             content: source_code.to_string(),
-            path: path.clone(),
+            path: path.to_path_buf(),
         };
         
         let parse_result = self.parser_pool.parse_blocking(request)?;
         let tree = parse_result.tree;
         
         let mut nodes = Vec::new();
-        let mut edges = Vec::new();
+        let mut imports = Vec::new();
         
         // Walk the AST
         let root_node = tree.root_node();
@@ -134,47 +168,44 @@ impl LanguageExtractor for JavaScriptExtractor {
         fn visit_node(
             node: Node,
             source: &str,
-            path: &PathBuf,
+            path: &Path,
+            module: &str,
             nodes: &mut Vec<GraphNode>,
-            edges: &mut Vec<GraphEdge>,
+            imports: &mut Vec<String>,
             extractor: &JavaScriptExtractor,
         ) {
-            // Extract functions
-            if let Some(function) = extractor.extract_function(node, source.as_bytes(), path) {
-                nodes.push(function);
-            }
-            
-            // Extract classes
-            if let Some(class_node) = extractor.extract_class(node, source.as_bytes(), path) {
-                nodes.push(class_node);
+            // Extract functions and classes, tagging React components and hooks
+            let symbol = extractor
+                .extract_function(node, source.as_bytes(), path, module)
+                .or_else(|| extractor.extract_class(node, source.as_bytes(), path, module));
+            if let Some(mut symbol) = symbol {
+                react::tag(node, source.as_bytes(), &mut symbol);
+                nodes.push(symbol);
             }
             
             // Extract imports
-            let imports = extractor.extract_import(node, source.as_bytes());
-            for import in imports {
-                edges.push(GraphEdge {
-                    id: EdgeId(0), // Will be set by graph
-                    source: NodeId(0), // Will be set when added to graph
-                    target: NodeId(0), // Will be set when added to graph
-                    kind: canopy_core::EdgeKind::Imports,
-                    edge_source: EdgeSource::Heuristic,
-                    confidence: 1.0,
-                    label: Some(format!("imports {}", import)),
-                    file_path: Some(path.clone()),
-                    line: None,
-                });
-            }
+            imports.extend(extractor.extract_import(node, source.as_bytes()));
             
             // Visit children
             let mut cursor = node.walk();
             for child in node.children(&mut cursor) {
-                visit_node(child, source, path, nodes, edges, extractor);
+                visit_node(child, source, path, module, nodes, imports, extractor);
             }
         }
         
-        visit_node(root_node, source_code, path, &mut nodes, &mut edges, self);
+        let module = module_specifier(path);
+        visit_node(root_node, source_code, path, &module, &mut nodes, &mut imports, self);
+        
+        // `describe`/`it` blocks of test files
+        let mut contains = Vec::new();
+        test_blocks(root_node, source_code.as_bytes(), path, &module, Language::JavaScript, &mut nodes, &mut contains);
         
-        Ok(ExtractionResult { nodes, edges })
+        let (mut calls, mut references) = local_calls(root_node, source_code.as_bytes(), &nodes, CALL_KINDS);
+        let (renders, rendered) = react::renders(root_node, source_code.as_bytes(), &nodes);
+        calls.extend(renders);
+        references.extend(rendered);
+        references.extend(imports.iter().map(|import| Reference::file_level(EdgeKind::Imports, "imports", import)));
+        Ok(ExtractionResult { nodes, edges: Vec::new(), contains, calls, references, errors: syntax_errors(root_node) })
     }
 }
 
@@ -209,11 +240,11 @@ const arrowFunc = (x, y) => x + y;
 export default createUser;
 "#;
         
-        let path = PathBuf::from("test.js");
-        let result = extractor.extract(&path, code.as_bytes()).unwrap();
+        let path = Path::new("test.js");
+        let result = extractor.extract(path, code.as_bytes()).unwrap();
         
-        // Should extract 1 class, 3 functions, 2 imports
-        assert_eq!(result.nodes.len(), 4); // 1 class + 3 functions
-        assert_eq!(result.edges.len(), 2); // 2 imports
+        // Should extract 1 class, 4 functions (constructor, getName, createUser, arrowFunc), 2 imports
+        assert_eq!(result.nodes.len(), 5); // 1 class + 4 functions
+        assert_eq!(result.references.iter().filter(|r| r.kind == EdgeKind::Imports).count(), 2); // 2 imports
     }
 }
\ No newline at end of file
@@ -0,0 +1,263 @@
+//! Markdown extractor using tree-sitter-md
+//!
+//! Each file becomes a Document node and each heading a Section node spanning
+//! the content up to the next heading of the same or higher level. Code spans,
+//! link targets and path-like words are recorded in the `mentions` metadata
+//! entry; [`crate::heuristics::docs`] turns them into SemanticReference edges
+//! once the code they name is in the graph.
+
+use super::{ExtractionResult, LanguageExtractor};
+use canopy_core::{GraphNode, NodeKind, Language, NodeId, NodeMetadata};
+use regex::Regex;
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::sync::LazyLock;
+use tree_sitter::{Node, Point};
+use anyhow::Result;
+use crate::extractor::syntax_errors;
+use crate::parser_pool::{ParserPool, ParseRequest, FileType};
+
+/// Metadata key holding newline-separated mentions.
+pub const MENTIONS_KEY: &str = "mentions";
+
+static CODE_SPAN: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"`([^`\n]+)`").unwrap());
+static LINK_TARGET: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\]\(([^)\s#]+)").unwrap());
+static BARE_PATH: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?:^|[\s(\[])((?:\.{0,2}/)?(?:[\w.-]+/)+[\w-]+\.\w+|[\w-]+\.(?:rs|ts|tsx|js|jsx|py|go|java|c|h|cpp|hpp|php|scala|graphql|toml|ya?ml|json|sql))\b").unwrap()
+});
+
+pub struct MarkdownExtractor {
+    parser_pool: ParserPool,
+}
+
+impl MarkdownExtractor {
+    pub fn new(parser_pool: ParserPool) -> Self {
+        Self { parser_pool }
+    }
+
+    fn point_to_u32(point: Point) -> u32 {
+        (point.row as u32) + 1
+    }
+
+    /// Heading text of a section, without markers or underline.
+    fn heading_text(heading: Node, source: &[u8]) -> Option<String> {
+        let content = match heading.kind() {
+            "atx_heading" => heading.child_by_field_name("heading_content")?,
+            "setext_heading" => heading.named_child(0)?,
+            _ => return None,
+        };
+        let text = content.utf8_text(source).ok()?.trim().trim_end_matches('#').trim();
+        (!text.is_empty()).then(|| text.to_string())
+    }
+
+    fn heading_level(heading: Node) -> Option<u8> {
+        let mut cursor = heading.walk();
+        let marker = heading.children(&mut cursor).find(|c| c.kind().starts_with("atx_h") || c.kind().starts_with("setext_h"))?;
+        marker.kind().chars().find(|c| c.is_ascii_digit()).and_then(|c| c.to_digit(10)).map(|d| d as u8)
+    }
+
+    /// Text directly under a node, skipping nested sections and code blocks.
+    fn own_text(node: Node, source: &[u8], out: &mut String) {
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            match child.kind() {
+                "section" | "fenced_code_block" | "indented_code_block" => {}
+                "inline" | "link_destination" => {
+                    if let Ok(text) = child.utf8_text(source) {
+                        out.push_str(text);
+                        out.push('\n');
+                    }
+                }
+                _ => Self::own_text(child, source, out),
+            }
+        }
+    }
+
+    fn make_node(node: Node, path: &Path, kind: NodeKind, name: &str, qualified: &str, metadata: NodeMetadata) -> GraphNode {
+        let start_pos = Self::point_to_u32(node.start_position());
+        // Sections end where the next one starts; don't count that line
+        let end_pos = if node.end_position().column == 0 && node.end_position().row > node.start_position().row {
+            node.end_position().row as u32
+        } else {
+            Self::point_to_u32(node.end_position())
+        };
+
+        GraphNode {
+            id: NodeId(0), // Will be set by graph
+            kind,
+            name: name.to_string(),
+            qualified_name: format!("{}::{}", path.display(), qualified).into(),
+            file_path: path.into(),
+            line_start: Some(start_pos),
+            line_end: Some(end_pos),
+            language: Some(Language::Markdown),
+            is_container: true,
+            child_count: 0,
+            loc: Some(end_pos - start_pos),
+            metadata,
+        }
+    }
+}
+
+/// Code spans, link targets and path-like words in `text`.
+pub fn find_mentions(text: &str) -> BTreeSet<String> {
+    let mut mentions = BTreeSet::new();
+    for caps in CODE_SPAN.captures_iter(text) {
+        let span = caps[1].trim().trim_end_matches("()").trim_end_matches(';');
+        if !span.is_empty() && !span.contains(char::is_whitespace) {
+            mentions.insert(span.to_string());
+        }
+    }
+    for caps in LINK_TARGET.captures_iter(text) {
+        if !caps[1].contains("://") && !caps[1].starts_with("mailto:") {
+            mentions.insert(caps[1].to_string());
+        }
+    }
+    for caps in BARE_PATH.captures_iter(text) {
+        mentions.insert(caps[1].to_string());
+    }
+    mentions
+}
+
+fn mention_metadata(text: &str) -> NodeMetadata {
+    let mut metadata = NodeMetadata::default();
+    let mentions = find_mentions(text);
+    if !mentions.is_empty() {
+        metadata.extra.insert(MENTIONS_KEY.to_string(), mentions.into_iter().collect::<Vec<_>>().join("\n"));
+    }
+    metadata
+}
+
+impl LanguageExtractor for MarkdownExtractor {
+    fn extract(&self, path: &Path, content: &[u8]) -> Result<ExtractionResult> {
+        let source_code = std::str::from_utf8(content)?;
+
+        let request = ParseRequest {
+            file_type: FileType::Markdown,
+            content: source_code.to_string(),
+            path: path.to_path_buf(),
+        };
+
+        let parse_result = self.parser_pool.parse_blocking(request)?;
+        let tree = parse_result.tree;
+        let source = source_code.as_bytes();
+
+        let root_node = tree.root_node();
+
+        // Document node first, so sections can point at index 0
+        let mut preamble = String::new();
+        let mut cursor = root_node.walk();
+        for child in root_node.children(&mut cursor) {
+            if child.kind() == "section" && !child.named_child(0).is_some_and(|h| h.kind().ends_with("_heading")) {
+                MarkdownExtractor::own_text(child, source, &mut preamble);
+            }
+        }
+        let document = Self::make_node(root_node, path, NodeKind::Document, "", "", mention_metadata(&preamble));
+        let mut nodes = vec![GraphNode { qualified_name: path.display().to_string().into(), ..document }];
+        let mut contains = Vec::new();
+
+        fn visit_node(
+            node: Node,
+            source: &[u8],
+            path: &Path,
+            parents: &mut Vec<String>,
+            parent: usize,
+            nodes: &mut Vec<GraphNode>,
+            contains: &mut Vec<(usize, usize)>,
+        ) {
+            let mut cursor = node.walk();
+            for child in node.children(&mut cursor) {
+                if child.kind() != "section" {
+                    continue;
+                }
+                let heading = child.named_child(0).filter(|h| h.kind().ends_with("_heading"));
+                let Some(title) = heading.and_then(|h| MarkdownExtractor::heading_text(h, source)) else {
+                    // Content before the first heading belongs to the parent
+                    visit_node(child, source, path, parents, parent, nodes, contains);
+                    continue;
+                };
+
+                let mut text = String::new();
+                MarkdownExtractor::own_text(child, source, &mut text);
+                let mut metadata = mention_metadata(&text);
+                if let Some(level) = heading.and_then(MarkdownExtractor::heading_level) {
+                    metadata.extra.insert("heading_level".to_string(), level.to_string());
+                }
+                parents.push(title.clone());
+                nodes.push(MarkdownExtractor::make_node(child, path, NodeKind::Section, &title, &parents.join(" > "), metadata));
+                let index = nodes.len() - 1;
+                contains.push((parent, index));
+                visit_node(child, source, path, parents, index, nodes, contains);
+                parents.pop();
+            }
+        }
+
+        visit_node(root_node, source, path, &mut Vec::new(), 0, &mut nodes, &mut contains);
+
+        // Title the document by its first top-level heading
+        nodes[0].name = nodes
+            .iter()
+            .find(|n| n.metadata.extra.get("heading_level").is_some_and(|l| l == "1"))
+            .map(|n| n.name.clone())
+            .or_else(|| path.file_stem().map(|s| s.to_string_lossy().into_owned()))
+            .unwrap_or_default();
+
+        Ok(ExtractionResult { nodes, edges: Vec::new(), contains, calls: Vec::new(), references: Vec::new(), errors: syntax_errors(root_node) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_markdown() {
+        let parser_pool = crate::parser_pool::create_parser_pool();
+        let extractor = MarkdownExtractor::new(parser_pool);
+        let code = r#"Intro mentions src/lib.rs.
+
+# Architecture
+
+The graph lives in `Graph` ([source](crates/core/src/graph.rs)).
+
+## Indexing
+
+Extraction is done by `get_extractor()` in languages/mod.rs.
+
+```rust
+let ignored = `NotAMention`;
+```
+
+## Serving
+
+See [the docs](https://example.com/guide.md).
+"#;
+
+        let path = Path::new("docs/ARCHITECTURE.md");
+        let result = extractor.extract(path, code.as_bytes()).unwrap();
+
+        let names: Vec<_> = result.nodes.iter().map(|n| (n.kind, n.name.as_str())).collect();
+        assert_eq!(
+            names,
+            vec![
+                (NodeKind::Document, "Architecture"),
+                (NodeKind::Section, "Architecture"),
+                (NodeKind::Section, "Indexing"),
+                (NodeKind::Section, "Serving"),
+            ]
+        );
+
+        let mentions = |i: usize| result.nodes[i].metadata.extra.get(MENTIONS_KEY).cloned().unwrap_or_default();
+        assert_eq!(mentions(0), "src/lib.rs");
+        assert_eq!(mentions(1), "Graph\ncrates/core/src/graph.rs");
+        assert_eq!(mentions(2), "get_extractor\nlanguages/mod.rs");
+        assert_eq!(mentions(3), "");
+
+        let architecture = &result.nodes[1];
+        assert_eq!((architecture.line_start, architecture.line_end), (Some(3), Some(17)));
+        let indexing = &result.nodes[2];
+        assert_eq!((indexing.line_start, indexing.line_end), (Some(7), Some(14)));
+        assert_eq!(indexing.qualified_name, "docs/ARCHITECTURE.md::Architecture > Indexing");
+        assert_eq!(result.contains, vec![(0, 1), (1, 2), (1, 3)]);
+    }
+}
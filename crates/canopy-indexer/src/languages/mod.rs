@@ -6,29 +6,39 @@ pub mod go;
 pub mod java;
 pub mod c;
 pub mod cpp;
+pub mod decorators;
 pub mod generic;
+pub mod graphql;
+pub mod markdown;
+pub mod php;
+pub mod preprocessor;
+pub mod react;
+pub mod registry;
 pub mod rust;
+pub mod scala;
 pub mod typescript;
 
-use std::path::PathBuf;
+use std::path::Path;
+use canopy_core::Language;
 use crate::extractor::{ExtractionResult, LanguageExtractor};
 
-/// Get the appropriate extractor for a file based on its extension
-pub fn get_extractor(path: &PathBuf) -> Option<Box<dyn LanguageExtractor>> {
-    let ext = path.extension()?.to_str()?;
-    
-    // Create a parser pool for the extractors that need it
-    let parser_pool = crate::parser_pool::create_parser_pool();
-    
-    match ext {
-        "rs" => Some(Box::new(rust::RustExtractor::new(parser_pool))),
-        "ts" | "tsx" => Some(Box::new(typescript::TypeScriptExtractor::new(parser_pool))),
-        "js" | "jsx" => Some(Box::new(javascript::JavaScriptExtractor::new(parser_pool.clone()))),
-        "py" => Some(Box::new(python::PythonExtractor::new(parser_pool.clone()))),
-        "go" => Some(Box::new(go::GoExtractor::new(parser_pool.clone()))),
-        "java" => Some(Box::new(java::JavaExtractor::new(parser_pool.clone()))),
-        "c" => Some(Box::new(c::CExtractor::new(parser_pool.clone()))),
-        "cpp" | "cc" | "cxx" | "c++" => Some(Box::new(cpp::CppExtractor::new(parser_pool.clone()))),
-        _ => Some(Box::new(generic::GenericExtractor::new(parser_pool.clone()))),
-    }
-}
\ No newline at end of file
+/// Extract a file with the extractor for its path, noting the environment
+/// variables its code reads.
+pub fn extract_file(path: &Path, content: &[u8]) -> anyhow::Result<ExtractionResult> {
+    let extractor = get_extractor(path).ok_or_else(|| anyhow::anyhow!("No extractor for {}", path.display()))?;
+    let mut result = extractor.extract(path, content)?;
+    crate::heuristics::env_vars::annotate_env_reads(&mut result, std::str::from_utf8(content)?);
+    Ok(result)
+}
+
+/// Whether a file is in a language or config format Canopy recognises, or
+/// one an extractor is registered for. Other files only get the generic
+/// fallback, which finds no symbols.
+pub fn is_indexable(path: &Path) -> bool {
+    Language::from_path(path) != Language::Other || registry::registry().handles(path)
+}
+
+/// Get the appropriate extractor for a file from the extractor registry
+pub fn get_extractor(path: &Path) -> Option<Box<dyn LanguageExtractor>> {
+    Some(registry::registry().extractor_for(path))
+}
@@ -0,0 +1,215 @@
+//! PHP language extractor using tree-sitter
+
+use super::{ExtractionResult, LanguageExtractor};
+use canopy_core::{GraphNode, NodeKind, EdgeKind, Language, NodeId};
+use std::path::Path;
+use tree_sitter::{Node, Point};
+use anyhow::Result;
+use crate::extractor::syntax_errors;
+use crate::metadata::{code_lines, node_metadata};
+use crate::parser_pool::{ParserPool, ParseRequest, FileType};
+use crate::calls::local_calls;
+use crate::resolve::Reference;
+
+/// Call expressions and the field holding their callee.
+const CALL_KINDS: &[(&str, &str)] = &[
+    ("function_call_expression", "function"),
+    ("member_call_expression", "name"),
+    ("scoped_call_expression", "name"),
+    ("nullsafe_member_call_expression", "name"),
+];
+
+pub struct PhpExtractor {
+    parser_pool: ParserPool,
+}
+
+impl PhpExtractor {
+    pub fn new(parser_pool: ParserPool) -> Self {
+        Self { parser_pool }
+    }
+
+    fn point_to_u32(point: Point) -> u32 {
+        (point.row as u32) + 1
+    }
+
+    fn make_node(&self, node: Node, source: &[u8], path: &Path, kind: NodeKind, name: &str) -> GraphNode {
+        let start_pos = Self::point_to_u32(node.start_position());
+        let end_pos = Self::point_to_u32(node.end_position());
+
+        GraphNode {
+            id: NodeId(0), // Will be set by graph
+            kind,
+            name: name.to_string(),
+            qualified_name: format!("{}::{}", path.display(), name).into(),
+            file_path: path.into(),
+            line_start: Some(start_pos),
+            line_end: Some(end_pos),
+            language: Some(Language::Php),
+            is_container: !matches!(kind, NodeKind::Function | NodeKind::Method),
+            child_count: 0,
+            loc: Some(code_lines(node, source)),
+            metadata: node_metadata(node, source, Language::Php, name),
+        }
+    }
+
+    /// Classes, interfaces, traits, enums, functions, methods and namespaces.
+    fn extract_declaration(&self, node: Node, source: &[u8], path: &Path) -> Option<GraphNode> {
+        let kind = match node.kind() {
+            "class_declaration" => NodeKind::Class,
+            "interface_declaration" => NodeKind::Interface,
+            "trait_declaration" => NodeKind::Trait,
+            "enum_declaration" => NodeKind::Enum,
+            "function_definition" => NodeKind::Function,
+            "method_declaration" => NodeKind::Method,
+            "namespace_definition" => NodeKind::Module,
+            _ => return None,
+        };
+        let name_node = node.child_by_field_name("name")?;
+        let name = name_node.utf8_text(source).ok()?;
+        Some(self.make_node(node, source, path, kind, name))
+    }
+
+    fn extract_imports(&self, node: Node, source: &[u8]) -> Vec<String> {
+        let mut imports = Vec::new();
+
+        if node.kind() == "namespace_use_declaration" {
+            // Group imports (`use App\{Foo, Bar}`) share a prefix
+            let mut cursor = node.walk();
+            let prefix = node
+                .children(&mut cursor)
+                .find(|c| c.kind() == "namespace_name")
+                .and_then(|c| c.utf8_text(source).ok());
+
+            let clause_parent = node.child_by_field_name("body").unwrap_or(node);
+            let mut cursor = clause_parent.walk();
+            for clause in clause_parent.children(&mut cursor) {
+                if clause.kind() != "namespace_use_clause" {
+                    continue;
+                }
+                let mut clause_cursor = clause.walk();
+                let imported = clause
+                    .children(&mut clause_cursor)
+                    .find(|c| c.kind() == "qualified_name" || c.kind() == "name")
+                    .and_then(|c| c.utf8_text(source).ok());
+                if let Some(imported) = imported {
+                    let imported = imported.trim_start_matches('\\');
+                    imports.push(match prefix {
+                        Some(prefix) => format!("{}\\{}", prefix.trim_start_matches('\\'), imported),
+                        None => imported.to_string(),
+                    });
+                }
+            }
+        }
+
+        imports
+    }
+}
+
+impl LanguageExtractor for PhpExtractor {
+    fn extract(&self, path: &Path, content: &[u8]) -> Result<ExtractionResult> {
+        let source_code = std::str::from_utf8(content)?;
+
+        // Use the parser pool to parse the content
+        let request = ParseRequest {
+            file_type: FileType::Php,
+            content: source_code.to_string(),
+            path: path.to_path_buf(),
+        };
+
+        let parse_result = self.parser_pool.parse_blocking(request)?;
+        let tree = parse_result.tree;
+
+        let mut nodes = Vec::new();
+        let mut import_modules = Vec::new();
+
+        // Walk the AST
+        let root_node = tree.root_node();
+
+        fn visit_node(
+            node: Node,
+            source: &str,
+            path: &Path,
+            nodes: &mut Vec<GraphNode>,
+            imports: &mut Vec<String>,
+            extractor: &PhpExtractor,
+        ) {
+            // Extract declarations
+            if let Some(declaration) = extractor.extract_declaration(node, source.as_bytes(), path) {
+                nodes.push(declaration);
+            }
+
+            // Extract imports
+            imports.extend(extractor.extract_imports(node, source.as_bytes()));
+
+            // Visit children
+            let mut cursor = node.walk();
+            for child in node.children(&mut cursor) {
+                visit_node(child, source, path, nodes, imports, extractor);
+            }
+        }
+
+        // Start visiting from root
+        visit_node(root_node, source_code, path, &mut nodes, &mut import_modules, self);
+
+        let (calls, mut references) = local_calls(root_node, source_code.as_bytes(), &nodes, CALL_KINDS);
+        references.extend(import_modules.iter().map(|import| Reference::file_level(EdgeKind::Imports, "uses", import)));
+        Ok(ExtractionResult { nodes, edges: Vec::new(), contains: Vec::new(), calls, references, errors: syntax_errors(root_node) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_php() {
+        let parser_pool = crate::parser_pool::create_parser_pool();
+        let extractor = PhpExtractor::new(parser_pool);
+        let code = r#"<?php
+namespace App\Models;
+
+use Illuminate\Database\Eloquent\Model;
+use App\Contracts\{HasName, HasEmail};
+
+interface Named {
+    public function name(): string;
+}
+
+trait Greets {
+    public function greet() { return "hi"; }
+}
+
+class User extends Model implements Named {
+    use Greets;
+
+    private function secret() {}
+
+    public function name(): string { return $this->name; }
+}
+
+function helper() {}
+"#;
+
+        let path = Path::new("User.php");
+        let result = extractor.extract(path, code.as_bytes()).unwrap();
+
+        let find = |name: &str| result.nodes.iter().find(|n| n.name == name).unwrap();
+        assert_eq!(find("App\\Models").kind, NodeKind::Module);
+        assert_eq!(find("Named").kind, NodeKind::Interface);
+        assert_eq!(find("Greets").kind, NodeKind::Trait);
+        assert_eq!(find("User").kind, NodeKind::Class);
+        assert_eq!(find("helper").kind, NodeKind::Function);
+        assert_eq!(find("secret").kind, NodeKind::Method);
+        assert_eq!(find("secret").metadata.visibility, Some(canopy_core::Visibility::Private));
+
+        let labels: Vec<_> = result.references.iter().filter(|r| r.kind == EdgeKind::Imports).map(|r| r.label.as_str()).collect();
+        assert_eq!(
+            labels,
+            vec![
+                "uses Illuminate\\Database\\Eloquent\\Model",
+                "uses App\\Contracts\\HasName",
+                "uses App\\Contracts\\HasEmail",
+            ]
+        );
+    }
+}
@@ -0,0 +1,326 @@
+//! C and C++ preprocessor conditionals
+//!
+//! The extractors see every branch of an `#if`/`#ifdef` chain, so a symbol
+//! defined once per platform shows up once per branch. Symbols inside a
+//! conditional are tagged with its condition under [`CONDITION_KEY`], and
+//! those a file defines in several branches are merged into one node
+//! tagged with every condition. When a define set is configured with
+//! [`set_defines`], branches it rules out are dropped; conditions it cannot
+//! evaluate, such as function-like macros, keep their branch.
+
+use crate::extractor::ExtractionResult;
+use std::collections::HashMap;
+use std::sync::{LazyLock, RwLock};
+use tree_sitter::Node;
+
+/// Metadata key on symbols declared inside preprocessor conditionals,
+/// holding the condition, e.g. `defined(DEBUG) && !(VERSION >= 2)`.
+pub const CONDITION_KEY: &str = "preprocessor_condition";
+
+/// Macro definitions, as given to the compiler with `-D`.
+#[derive(Debug, Clone, Default)]
+pub struct Defines(HashMap<String, String>);
+
+impl Defines {
+    /// Defines from `NAME` or `NAME=VALUE` entries; a bare name is `1`.
+    pub fn new<'a>(entries: impl IntoIterator<Item = &'a str>) -> Self {
+        Defines(
+            entries
+                .into_iter()
+                .map(|entry| match entry.split_once('=') {
+                    Some((name, value)) => (name.trim().to_string(), value.trim().to_string()),
+                    None => (entry.trim().to_string(), "1".to_string()),
+                })
+                .collect(),
+        )
+    }
+
+    fn is_defined(&self, name: &str) -> bool {
+        self.0.contains_key(name)
+    }
+
+    /// A macro's value in `#if`: undefined macros are 0, and values that
+    /// are not integers cannot be evaluated.
+    fn value(&self, name: &str) -> Option<i64> {
+        self.0.get(name).map_or(Some(0), |value| parse_int(value))
+    }
+}
+
+static DEFINES: LazyLock<RwLock<Option<Defines>>> = LazyLock::new(|| RwLock::new(None));
+
+/// Evaluate conditionals against `defines` from now on, or keep every
+/// branch if `None`.
+pub fn set_defines(defines: Option<Defines>) {
+    *DEFINES.write().unwrap() = defines;
+}
+
+/// One branch of a conditional, by 0-based rows of its body.
+struct Branch {
+    first_row: usize,
+    last_row: usize,
+    condition: String,
+    /// Whether the configured defines take the branch, if known.
+    taken: Option<bool>,
+}
+
+fn text<'a>(node: Node, source: &'a [u8]) -> &'a str {
+    node.utf8_text(source).unwrap_or("")
+}
+
+fn parse_int(literal: &str) -> Option<i64> {
+    let literal = literal.trim().trim_end_matches(['u', 'U', 'l', 'L']);
+    match literal.strip_prefix("0x").or_else(|| literal.strip_prefix("0X")) {
+        Some(hex) => i64::from_str_radix(hex, 16).ok(),
+        None => literal.parse().ok(),
+    }
+}
+
+fn and(a: Option<bool>, b: Option<bool>) -> Option<bool> {
+    match (a, b) {
+        (Some(false), _) | (_, Some(false)) => Some(false),
+        (Some(true), Some(true)) => Some(true),
+        _ => None,
+    }
+}
+
+fn or(a: Option<bool>, b: Option<bool>) -> Option<bool> {
+    match (a, b) {
+        (Some(true), _) | (_, Some(true)) => Some(true),
+        (Some(false), Some(false)) => Some(false),
+        _ => None,
+    }
+}
+
+/// The value of an `#if` expression, if the defines settle it.
+fn evaluate(node: Node, source: &[u8], defines: &Defines) -> Option<i64> {
+    let operand = |field: &str| node.child_by_field_name(field).and_then(|n| evaluate(n, source, defines));
+    match node.kind() {
+        "number_literal" => parse_int(text(node, source)),
+        "identifier" => defines.value(text(node, source)),
+        "preproc_defined" => Some(node.named_child(0).is_some_and(|name| defines.is_defined(text(name, source))) as i64),
+        "parenthesized_expression" => evaluate(node.named_child(0)?, source, defines),
+        "unary_expression" => {
+            let value = operand("argument")?;
+            match text(node.child_by_field_name("operator")?, source) {
+                "!" => Some((value == 0) as i64),
+                "-" => Some(-value),
+                "~" => Some(!value),
+                _ => None,
+            }
+        }
+        "binary_expression" => {
+            let (left, right) = (operand("left"), operand("right"));
+            let truth = |v: Option<i64>| v.map(|v| v != 0);
+            let operator = text(node.child_by_field_name("operator")?, source);
+            match operator {
+                "&&" => and(truth(left), truth(right)).map(i64::from),
+                "||" => or(truth(left), truth(right)).map(i64::from),
+                _ => {
+                    let (left, right) = (left?, right?);
+                    Some(match operator {
+                        "==" => (left == right) as i64,
+                        "!=" => (left != right) as i64,
+                        "<" => (left < right) as i64,
+                        "<=" => (left <= right) as i64,
+                        ">" => (left > right) as i64,
+                        ">=" => (left >= right) as i64,
+                        "+" => left.checked_add(right)?,
+                        "-" => left.checked_sub(right)?,
+                        "*" => left.checked_mul(right)?,
+                        "/" => left.checked_div(right)?,
+                        _ => return None,
+                    })
+                }
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Whether an `#ifndef X` opens an include guard: its body starts with
+/// `#define X` and it has no other branch.
+fn is_include_guard(node: Node, source: &[u8]) -> bool {
+    let Some(name) = node.child_by_field_name("name") else {
+        return false;
+    };
+    let first = name.next_named_sibling();
+    node.child(0).is_some_and(|c| c.kind() == "#ifndef")
+        && node.child_by_field_name("alternative").is_none()
+        && first.is_some_and(|d| {
+            d.kind() == "preproc_def" && d.child_by_field_name("name").is_some_and(|n| text(n, source) == text(name, source))
+        })
+}
+
+/// The branches of the conditional chain starting at `node`.
+fn chain(node: Node, source: &[u8], defines: Option<&Defines>, branches: &mut Vec<Branch>) {
+    let mut previous: Vec<String> = Vec::new();
+    // Whether an earlier branch of the chain is taken
+    let mut earlier_taken = Some(false);
+    let mut current = Some(node);
+    while let Some(branch) = current {
+        let (own, value) = match branch.kind() {
+            "preproc_ifdef" | "preproc_elifdef" => {
+                let name = branch.child_by_field_name("name").map(|n| text(n, source)).unwrap_or_default();
+                let negated = branch.child(0).is_some_and(|c| c.kind().ends_with("ndef"));
+                let defined = defines.map(|d| d.is_defined(name) != negated);
+                let condition = format!("{}defined({})", if negated { "!" } else { "" }, name);
+                (Some(condition), defined)
+            }
+            "preproc_if" | "preproc_elif" => {
+                let condition = branch.child_by_field_name("condition");
+                let value = defines.zip(condition).and_then(|(d, c)| evaluate(c, source, d)).map(|v| v != 0);
+                let condition = condition.map(|c| text(c, source).split_whitespace().collect::<Vec<_>>().join(" "));
+                (condition, value)
+            }
+            _ => (None, Some(true)),
+        };
+        let negated = previous.iter().map(|c| format!("!({})", c));
+        let condition: Vec<String> = negated.chain(own.clone()).collect();
+        let taken = and(earlier_taken.map(|t| !t), if defines.is_some() { value } else { None });
+
+        let alternative = branch.child_by_field_name("alternative");
+        let mut cursor = branch.walk();
+        let endif = branch.children(&mut cursor).find(|c| c.kind() == "#endif");
+        let last_row = match alternative.or(endif) {
+            Some(end) => end.start_position().row.saturating_sub(1),
+            None => branch.end_position().row,
+        };
+        branches.push(Branch { first_row: branch.start_position().row + 1, last_row, condition: condition.join(" && "), taken });
+
+        previous.extend(own);
+        earlier_taken = or(earlier_taken, value);
+        current = alternative;
+    }
+}
+
+/// Every conditional branch in the tree, include guards aside.
+fn branches(root: Node, source: &[u8], defines: Option<&Defines>) -> Vec<Branch> {
+    fn visit(node: Node, source: &[u8], defines: Option<&Defines>, branches: &mut Vec<Branch>) {
+        if matches!(node.kind(), "preproc_if" | "preproc_ifdef") && !is_include_guard(node, source) {
+            chain(node, source, defines, branches);
+        }
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            visit(child, source, defines, branches);
+        }
+    }
+    let mut branches = Vec::new();
+    visit(root, source, defines, &mut branches);
+    branches
+}
+
+/// Tag the symbols of a C or C++ file with the conditionals guarding them,
+/// drop those in branches the configured defines rule out, and merge those
+/// defined in several branches.
+pub fn apply_conditionals(root: Node, source: &[u8], result: &mut ExtractionResult) {
+    let defines = DEFINES.read().unwrap().clone();
+    let branches = branches(root, source, defines.as_ref());
+    if branches.is_empty() {
+        return;
+    }
+
+    let mut taken = Vec::with_capacity(result.nodes.len());
+    for node in &mut result.nodes {
+        let (Some(start), Some(end)) = (node.line_start, node.line_end) else {
+            taken.push(Some(true));
+            continue;
+        };
+        let (start, end) = (start as usize - 1, end as usize - 1);
+        // Branches are listed outermost first
+        let guarding: Vec<&Branch> = branches.iter().filter(|b| b.first_row <= start && end <= b.last_row).collect();
+        if !guarding.is_empty() {
+            let condition: Vec<&str> = guarding.iter().map(|b| b.condition.as_str()).collect();
+            node.metadata.extra.insert(CONDITION_KEY.to_string(), condition.join(" && "));
+        }
+        taken.push(guarding.iter().map(|b| b.taken).reduce(and).unwrap_or(Some(true)));
+    }
+
+    // A symbol of several branches becomes one, under any of their
+    // conditions; one outside every conditional is unconditional
+    let mut first: HashMap<(canopy_core::NodeKind, String), usize> = HashMap::new();
+    let mut into: Vec<Option<usize>> = Vec::with_capacity(result.nodes.len());
+    for (i, node) in result.nodes.iter().enumerate() {
+        if taken[i] == Some(false) {
+            into.push(None);
+            continue;
+        }
+        let target = *first.entry((node.kind, node.qualified_name.to_string())).or_insert(i);
+        into.push(Some(target));
+    }
+    for (i, target) in into.iter().enumerate() {
+        let Some(target) = *target else { continue };
+        if target == i {
+            continue;
+        }
+        let condition = result.nodes[i].metadata.extra.get(CONDITION_KEY).cloned();
+        let merged = &mut result.nodes[target].metadata.extra;
+        match (merged.get(CONDITION_KEY), condition) {
+            (Some(existing), Some(condition)) if existing != &condition => {
+                let combined = format!("({}) || ({})", existing, condition);
+                merged.insert(CONDITION_KEY.to_string(), combined);
+            }
+            (Some(_), None) => {
+                merged.remove(CONDITION_KEY);
+            }
+            _ => {}
+        }
+    }
+    result.merge_nodes(&into);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::languages::get_extractor;
+    use std::path::Path;
+
+    const SOURCE: &str = r#"#ifndef LOG_H
+#define LOG_H
+#ifdef DEBUG
+int log_level(void) { return 2; }
+#elif defined(TRACE) && VERSION >= 2
+int log_level(void) { return 1; }
+#else
+int log_level(void) { return 0; }
+#endif
+#if !defined(_WIN32)
+int log_fd(void) { return 2; }
+#endif
+int log_init(void) { return log_level(); }
+#endif
+"#;
+
+    #[test]
+    fn test_conditional_symbols() {
+        let path = Path::new("log.c");
+        let result = get_extractor(path).unwrap().extract(path, SOURCE.as_bytes()).unwrap();
+        let condition = |name: &str| {
+            let matching: Vec<_> = result.nodes.iter().filter(|n| n.name == name).collect();
+            assert_eq!(matching.len(), 1, "{}", name);
+            matching[0].metadata.extra.get(CONDITION_KEY).cloned()
+        };
+        assert_eq!(
+            condition("log_level").as_deref(),
+            Some("((defined(DEBUG)) || (!(defined(DEBUG)) && defined(TRACE) && VERSION >= 2)) || (!(defined(DEBUG)) && !(defined(TRACE) && VERSION >= 2))")
+        );
+        assert_eq!(condition("log_fd").as_deref(), Some("!defined(_WIN32)"));
+        // The include guard is no condition
+        assert_eq!(condition("log_init"), None);
+        assert_eq!(result.calls.len(), 1);
+    }
+
+    #[test]
+    fn test_evaluate_branches() {
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(&tree_sitter_c::LANGUAGE.into()).unwrap();
+        let tree = parser.parse(SOURCE, None).unwrap();
+        let taken = |defines: Option<Defines>| -> Vec<Option<bool>> {
+            branches(tree.root_node(), SOURCE.as_bytes(), defines.as_ref()).iter().map(|b| b.taken).collect()
+        };
+        assert_eq!(taken(None), vec![None, None, None, None]);
+        assert_eq!(taken(Some(Defines::new(["DEBUG"]))), vec![Some(true), Some(false), Some(false), Some(true)]);
+        assert_eq!(taken(Some(Defines::new(["TRACE", "VERSION=3", "_WIN32"]))), vec![Some(false), Some(true), Some(false), Some(false)]);
+        assert_eq!(taken(Some(Defines::new(["TRACE", "VERSION=1"]))), vec![Some(false), Some(false), Some(true), Some(true)]);
+        assert_eq!(taken(Some(Defines::new(["VERSION=beta"]))), vec![Some(false), Some(false), Some(true), Some(true)]);
+    }
+}
@@ -1,11 +1,72 @@
 //! Python language extractor using tree-sitter
 
 use super::{ExtractionResult, LanguageExtractor};
-use canopy_core::{GraphNode, GraphEdge, NodeKind, EdgeKind, EdgeSource, Language, NodeId, EdgeId};
-use std::path::PathBuf;
+use canopy_core::{GraphNode, NodeKind, EdgeKind, Language, NodeId};
+use std::path::Path;
 use tree_sitter::{Node, Point};
 use anyhow::Result;
+use crate::extractor::syntax_errors;
+use crate::metadata::{code_lines, node_metadata};
 use crate::parser_pool::{ParserPool, ParseRequest, FileType};
+use crate::calls::local_calls;
+use crate::test_cases::tag_tests;
+use crate::resolve::Reference;
+use crate::heuristics::routes::{self, ROUTE_KEY};
+
+/// Call expressions and the field holding their callee.
+const CALL_KINDS: &[(&str, &str)] = &[("call", "function")];
+
+/// Route decorator methods (`@app.get`) and the HTTP method they register;
+/// `route` and `api_route` take theirs from `methods=`, defaulting to GET.
+const ROUTE_DECORATORS: &[(&str, &str)] = &[
+    ("get", "GET"),
+    ("post", "POST"),
+    ("put", "PUT"),
+    ("patch", "PATCH"),
+    ("delete", "DELETE"),
+    ("head", "HEAD"),
+    ("options", "OPTIONS"),
+    ("websocket", "WS"),
+    ("route", ""),
+    ("api_route", ""),
+];
+
+/// Django URL configuration functions.
+const DJANGO_URL_FUNCTIONS: &[&str] = &["path", "re_path", "url"];
+
+/// Builtin and `typing` names that never refer to project classes.
+const BUILTIN_TYPES: &[&str] = &[
+    "None", "int", "float", "complex", "str", "bytes", "bool", "object", "list", "dict", "set", "frozenset",
+    "tuple", "type", "Any", "Optional", "Union", "List", "Dict", "Set", "FrozenSet", "Tuple", "Type",
+    "Callable", "Iterable", "Iterator", "Generator", "AsyncIterator", "AsyncGenerator", "Awaitable",
+    "Coroutine", "Sequence", "Mapping", "MutableMapping", "Literal", "Self", "TypeVar", "Annotated",
+];
+
+/// The dotted module a Python file defines: its stem after the packages
+/// holding it, found by their `__init__.py` files. A package's
+/// `__init__.py` defines the package itself.
+pub fn module_name(path: &Path) -> String {
+    let mut parts = Vec::new();
+    let mut dir = path.parent();
+    match path.file_stem().and_then(|s| s.to_str()) {
+        Some("__init__") => {
+            parts.extend(dir.and_then(|d| d.file_name()).and_then(|n| n.to_str()));
+            dir = dir.and_then(Path::parent);
+        }
+        stem => parts.extend(stem),
+    }
+    while let Some(package) = dir.filter(|d| d.join("__init__.py").is_file()) {
+        parts.extend(package.file_name().and_then(|n| n.to_str()));
+        dir = package.parent();
+    }
+    parts.reverse();
+    parts.join(".")
+}
+
+/// `name` within the module or class `scope`.
+fn qualify(scope: &str, name: &str) -> String {
+    if scope.is_empty() { name.to_string() } else { format!("{}.{}", scope, name) }
+}
 
 pub struct PythonExtractor {
     parser_pool: ParserPool,
@@ -20,110 +81,303 @@ impl PythonExtractor {
         (point.row as u32) + 1
     }
     
-    fn extract_function(&self, node: Node, source: &[u8], path: &PathBuf) -> Option<GraphNode> {
-        if node.kind() == "function_definition" {
-            if let Some(name_node) = node.child_by_field_name("name") {
-                if let Ok(name) = name_node.utf8_text(source) {
-                    let start_pos = Self::point_to_u32(node.start_position());
-                    let end_pos = Self::point_to_u32(node.end_position());
+    fn extract_function(node: Node, source: &[u8], path: &Path, module: &str) -> Option<GraphNode> {
+        if node.kind() == "function_definition"
+            && let Some(name_node) = node.child_by_field_name("name")
+            && let Ok(name) = name_node.utf8_text(source) {
+            let start_pos = Self::point_to_u32(node.start_position());
+            let end_pos = Self::point_to_u32(node.end_position());
                     
-                    return Some(GraphNode {
-                        id: NodeId(0), // Will be set by graph
-                        kind: NodeKind::Function,
-                        name: name.to_string(),
-                        qualified_name: format!("{}::{}", path.display(), name),
-                        file_path: path.clone(),
-                        line_start: Some(start_pos),
-                        line_end: Some(end_pos),
-                        language: Some(Language::Python),
-                        is_container: false,
-                        child_count: 0,
-                        loc: Some(((end_pos - start_pos) as usize) as u32),
-                        metadata: std::collections::HashMap::new(),
-                    });
-                }
-            }
+            return Some(GraphNode {
+                id: NodeId(0), // Will be set by graph
+                kind: NodeKind::Function,
+                name: name.to_string(),
+                qualified_name: qualify(module, name).into(),
+                file_path: path.into(),
+                line_start: Some(start_pos),
+                line_end: Some(end_pos),
+                language: Some(Language::Python),
+                is_container: false,
+                child_count: 0,
+                loc: Some(code_lines(node, source)),
+                metadata: node_metadata(node, source, Language::Python, name),
+            });
         }
         None
     }
     
-    fn extract_class(&self, node: Node, source: &[u8], path: &PathBuf) -> Option<GraphNode> {
-        if node.kind() == "class_definition" {
-            if let Some(name_node) = node.child_by_field_name("name") {
-                if let Ok(name) = name_node.utf8_text(source) {
-                    let start_pos = Self::point_to_u32(node.start_position());
-                    let end_pos = Self::point_to_u32(node.end_position());
+    fn extract_class(node: Node, source: &[u8], path: &Path, module: &str) -> Option<GraphNode> {
+        if node.kind() == "class_definition"
+            && let Some(name_node) = node.child_by_field_name("name")
+            && let Ok(name) = name_node.utf8_text(source) {
+            let start_pos = Self::point_to_u32(node.start_position());
+            let end_pos = Self::point_to_u32(node.end_position());
                     
-                    return Some(GraphNode {
-                        id: NodeId(0), // Will be set by graph
-                        kind: NodeKind::Class,
-                        name: name.to_string(),
-                        qualified_name: format!("{}::{}", path.display(), name),
-                        file_path: path.clone(),
-                        line_start: Some(start_pos),
-                        line_end: Some(end_pos),
-                        language: Some(Language::Python),
-                        is_container: true,
-                        child_count: 0,
-                        loc: Some(((end_pos - start_pos) as usize) as u32),
-                        metadata: std::collections::HashMap::new(),
-                    });
-                }
-            }
+            return Some(GraphNode {
+                id: NodeId(0), // Will be set by graph
+                kind: NodeKind::Class,
+                name: name.to_string(),
+                qualified_name: qualify(module, name).into(),
+                file_path: path.into(),
+                line_start: Some(start_pos),
+                line_end: Some(end_pos),
+                language: Some(Language::Python),
+                is_container: true,
+                child_count: 0,
+                loc: Some(code_lines(node, source)),
+                metadata: node_metadata(node, source, Language::Python, name),
+            });
         }
         None
     }
     
-    fn extract_method(&self, node: Node, source: &[u8], path: &PathBuf, class_name: Option<&str>) -> Option<GraphNode> {
-        if node.kind() == "function_definition" {
-            if let Some(name_node) = node.child_by_field_name("name") {
-                if let Ok(name) = name_node.utf8_text(source) {
-                    let start_pos = Self::point_to_u32(node.start_position());
-                    let end_pos = Self::point_to_u32(node.end_position());
+    fn extract_method(node: Node, source: &[u8], path: &Path, module: &str, class_name: Option<&str>) -> Option<GraphNode> {
+        if node.kind() == "function_definition"
+            && let Some(name_node) = node.child_by_field_name("name")
+            && let Ok(name) = name_node.utf8_text(source) {
+            let start_pos = Self::point_to_u32(node.start_position());
+            let end_pos = Self::point_to_u32(node.end_position());
                     
-                    let qualified_name = if let Some(class) = class_name {
-                        format!("{}::{}::{}", path.display(), class, name)
-                    } else {
-                        format!("{}::{}", path.display(), name)
-                    };
+            let qualified_name = match class_name {
+                Some(class) => qualify(&qualify(module, class), name),
+                None => qualify(module, name),
+            };
                     
-                    return Some(GraphNode {
-                        id: NodeId(0), // Will be set by graph
-                        kind: NodeKind::Method,
-                        name: name.to_string(),
-                        qualified_name,
-                        file_path: path.clone(),
-                        line_start: Some(start_pos),
-                        line_end: Some(end_pos),
-                        language: Some(Language::Python),
-                        is_container: false,
-                        child_count: 0,
-                        loc: Some(((end_pos - start_pos) as usize) as u32),
-                        metadata: std::collections::HashMap::new(),
+            return Some(GraphNode {
+                id: NodeId(0), // Will be set by graph
+                kind: NodeKind::Method,
+                name: name.to_string(),
+                qualified_name: qualified_name.into(),
+                file_path: path.into(),
+                line_start: Some(start_pos),
+                line_end: Some(end_pos),
+                language: Some(Language::Python),
+                is_container: false,
+                child_count: 0,
+                loc: Some(code_lines(node, source)),
+                metadata: node_metadata(node, source, Language::Python, name),
+            });
+        }
+        None
+    }
+    
+    /// A string literal's value, without prefix and quotes.
+    fn string_value<'s>(node: Node, source: &'s [u8]) -> Option<&'s str> {
+        if node.kind() != "string" {
+            return None;
+        }
+        let text = node.utf8_text(source).ok()?;
+        Some(text.trim_start_matches(|c: char| c.is_ascii_alphabetic()).trim_matches(['"', '\'']))
+    }
+    
+    /// Routes registered by a function's Flask or FastAPI decorators, as
+    /// `METHOD /path`.
+    fn decorator_routes(node: Node, source: &[u8]) -> Vec<String> {
+        let mut routes = Vec::new();
+        let Some(decorated) = node.parent().filter(|p| p.kind() == "decorated_definition") else {
+            return routes;
+        };
+        let mut cursor = decorated.walk();
+        for decorator in decorated.children(&mut cursor).filter(|c| c.kind() == "decorator") {
+            let Some(call) = decorator.named_child(0).filter(|c| c.kind() == "call") else {
+                continue;
+            };
+            let Some(&(_, method)) = call
+                .child_by_field_name("function")
+                .filter(|f| f.kind() == "attribute")
+                .and_then(|f| f.child_by_field_name("attribute"))
+                .and_then(|a| a.utf8_text(source).ok())
+                .and_then(|name| ROUTE_DECORATORS.iter().find(|(decorator, _)| *decorator == name))
+            else {
+                continue;
+            };
+            let Some(arguments) = call.child_by_field_name("arguments") else {
+                continue;
+            };
+            let mut argument_cursor = arguments.walk();
+            let arguments: Vec<Node> = arguments.named_children(&mut argument_cursor).collect();
+            let Some(path) = arguments.first().and_then(|a| Self::string_value(*a, source)) else {
+                continue;
+            };
+            let methods: Vec<String> = if method.is_empty() {
+                let listed = arguments
+                    .iter()
+                    .filter(|a| a.kind() == "keyword_argument")
+                    .find(|a| a.child_by_field_name("name").and_then(|n| n.utf8_text(source).ok()) == Some("methods"))
+                    .and_then(|a| a.child_by_field_name("value"));
+                let mut methods = Vec::new();
+                if let Some(listed) = listed {
+                    let mut list_cursor = listed.walk();
+                    methods.extend(
+                        listed
+                            .named_children(&mut list_cursor)
+                            .filter_map(|m| Self::string_value(m, source))
+                            .map(str::to_uppercase),
+                    );
+                }
+                if methods.is_empty() { vec!["GET".to_string()] } else { methods }
+            } else {
+                vec![method.to_string()]
+            };
+            routes.extend(methods.into_iter().map(|method| format!("{} {}", method, path)));
+        }
+        routes
+    }
+    
+    /// Record the routes a function's decorators register.
+    fn tag_routes(node: Node, source: &[u8], function: &mut GraphNode) {
+        let routes = Self::decorator_routes(node, source);
+        if !routes.is_empty() {
+            function.metadata.extra.insert(ROUTE_KEY.to_string(), routes.join("\n"));
+        }
+    }
+    
+    /// Route nodes for the `path()`, `re_path()` and `url()` entries of a
+    /// Django `urls.py`, with references to their views. Routes are
+    /// registered for any method, and `include()`d prefixes are not
+    /// followed.
+    fn extract_django_urls(&self, root: Node, source: &[u8], path: &Path, nodes: &mut Vec<GraphNode>) -> Vec<Reference> {
+        let mut references = Vec::new();
+        let mut stack = vec![root];
+        while let Some(node) = stack.pop() {
+            let mut cursor = node.walk();
+            stack.extend(node.children(&mut cursor));
+            let is_url = node.kind() == "call"
+                && node
+                    .child_by_field_name("function")
+                    .and_then(|f| f.utf8_text(source).ok())
+                    .is_some_and(|f| DJANGO_URL_FUNCTIONS.contains(&f));
+            let Some(arguments) = node.child_by_field_name("arguments").filter(|_| is_url) else {
+                continue;
+            };
+            let mut argument_cursor = arguments.walk();
+            let mut positional = arguments.named_children(&mut argument_cursor).filter(|a| a.kind() != "keyword_argument");
+            let (Some(pattern), Some(view)) = (positional.next(), positional.next()) else {
+                continue;
+            };
+            let Some(pattern) = Self::string_value(pattern, source) else {
+                continue;
+            };
+            let Ok(view) = view.utf8_text(source) else {
+                continue;
+            };
+            // Class-based views are routed through `View.as_view()`
+            let view = view.split(".as_view(").next().unwrap_or(view).trim();
+            if view.starts_with("include(") {
+                continue;
+            }
+            let route = format!("ANY /{}", pattern.trim_start_matches('^').trim_start_matches('/').trim_end_matches('$'));
+            let line = Self::point_to_u32(node.start_position());
+            references.push(Reference {
+                source: Some(nodes.len()),
+                kind: EdgeKind::RouteHandler,
+                target: view.to_string(),
+                label: format!("handled by {}", view.rsplit('.').next().unwrap_or(view)),
+                line: Some(line),
+                local_first: false,
+            });
+            nodes.push(routes::route_node(&route, path, Some(line), Some(Language::Python)));
+        }
+        references
+    }
+    
+    /// Project types named in an annotation: `Optional[list[User]]`,
+    /// `"User" | None` and `models.User` all give `User`.
+    fn annotation_types(annotation: &str) -> Vec<&str> {
+        let mut types: Vec<&str> = annotation
+            .split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+            .filter_map(|name| name.rsplit('.').next())
+            .filter(|name| name.starts_with(|c: char| c.is_alphabetic() || c == '_'))
+            .filter(|name| !BUILTIN_TYPES.contains(name))
+            .collect();
+        let mut seen = Vec::new();
+        types.retain(|name| {
+            let first = !seen.contains(name);
+            seen.push(*name);
+            first
+        });
+        types
+    }
+    
+    /// References from functions and methods to the types in their
+    /// parameter and return annotations.
+    fn extract_type_hints(&self, root: Node, source: &[u8], nodes: &[GraphNode]) -> Vec<Reference> {
+        let mut references: Vec<Reference> = Vec::new();
+        let mut stack = vec![root];
+        while let Some(node) = stack.pop() {
+            let mut cursor = node.walk();
+            stack.extend(node.children(&mut cursor));
+            if node.kind() != "function_definition" {
+                continue;
+            }
+            let line = Self::point_to_u32(node.start_position());
+            let Some(function) = nodes
+                .iter()
+                .position(|n| matches!(n.kind, NodeKind::Function | NodeKind::Method) && n.line_start == Some(line))
+            else {
+                continue;
+            };
+            
+            let mut annotations = Vec::new();
+            if let Some(parameters) = node.child_by_field_name("parameters") {
+                let mut parameter_cursor = parameters.walk();
+                annotations.extend(
+                    parameters
+                        .named_children(&mut parameter_cursor)
+                        .filter_map(|p| p.child_by_field_name("type"))
+                        .map(|t| ("takes", t)),
+                );
+            }
+            annotations.extend(node.child_by_field_name("return_type").map(|t| ("returns", t)));
+            
+            for (verb, annotation) in annotations {
+                let Ok(text) = annotation.utf8_text(source) else {
+                    continue;
+                };
+                for name in Self::annotation_types(text) {
+                    if references.iter().any(|r| r.source == Some(function) && r.target == name) {
+                        continue;
+                    }
+                    references.push(Reference {
+                        source: Some(function),
+                        kind: EdgeKind::TypeReference,
+                        target: name.to_string(),
+                        label: format!("{} {}", verb, name),
+                        line: Some(Self::point_to_u32(annotation.start_position())),
+                        local_first: false,
                     });
                 }
             }
         }
-        None
+        references
     }
     
-    fn extract_imports(&self, node: Node, source: &[u8]) -> Vec<String> {
+    fn extract_imports(node: Node, source: &[u8]) -> Vec<String> {
         let mut imports = Vec::new();
         
         if node.kind() == "import_statement" {
             // Extract module name from import statement
             let mut cursor = node.walk();
             for child in node.children(&mut cursor) {
-                if child.kind() == "dotted_name" || child.kind() == "aliased_import" {
-                    if let Ok(module) = child.utf8_text(source) {
-                        imports.push(module.split_whitespace().next().unwrap_or("").to_string());
-                    }
+                if (child.kind() == "dotted_name" || child.kind() == "aliased_import")
+                    && let Ok(module) = child.utf8_text(source) {
+                    imports.push(module.split_whitespace().next().unwrap_or("").to_string());
                 }
             }
         } else if node.kind() == "import_from_statement" {
             // Extract module name from "from module import" statement
-            if let Some(module_node) = node.child_by_field_name("module_name") {
-                if let Ok(module) = module_node.utf8_text(source) {
+            if let Some(module_node) = node.child_by_field_name("module_name")
+                && let Ok(module) = module_node.utf8_text(source) {
+                if module.chars().all(|c| c == '.') {
+                    // `from . import views` imports the sibling module itself
+                    let mut cursor = node.walk();
+                    for name in node.children_by_field_name("name", &mut cursor) {
+                        let name = name.child_by_field_name("name").unwrap_or(name);
+                        if let Ok(name) = name.utf8_text(source) {
+                            imports.push(format!("{}{}", module, name));
+                        }
+                    }
+                } else {
                     imports.push(module.to_string());
                 }
             }
@@ -134,21 +388,20 @@ impl PythonExtractor {
 }
 
 impl LanguageExtractor for PythonExtractor {
-    fn extract(&self, path: &PathBuf, content: &[u8]) -> Result<ExtractionResult> {
+    fn extract(&self, path: &Path, content: &[u8]) -> Result<ExtractionResult> {
         let source_code = std::str::from_utf8(content)?;
         
         // Use the parser pool to parse the content
         let request = ParseRequest {
             file_type: FileType::Python,
             content: source_code.to_string(),
-            path: path.clone(),
+            path: path.to_path_buf(),
         };
         
         let parse_result = self.parser_pool.parse_blocking(request)?;
         let tree = parse_result.tree;
         
         let mut nodes = Vec::new();
-        let mut edges = Vec::new();
         let mut import_modules = Vec::new();
         
         // Walk the AST
@@ -157,77 +410,69 @@ impl LanguageExtractor for PythonExtractor {
         fn visit_node(
             node: Node,
             source: &str,
-            path: &PathBuf,
+            path: &Path,
+            module: &str,
             nodes: &mut Vec<GraphNode>,
-            edges: &mut Vec<GraphEdge>,
             imports: &mut Vec<String>,
-            extractor: &PythonExtractor,
             in_class: bool,
-            class_name: Option<&str>,
         ) {
             // Extract functions at module level
-            if !in_class {
-                if let Some(function) = extractor.extract_function(node, source.as_bytes(), path) {
-                    nodes.push(function);
-                }
+            if !in_class
+                && let Some(mut function) = PythonExtractor::extract_function(node, source.as_bytes(), path, module) {
+                PythonExtractor::tag_routes(node, source.as_bytes(), &mut function);
+                nodes.push(function);
             }
             
             // Extract classes
-            if node.kind() == "class_definition" {
-                if let Some(class) = extractor.extract_class(node, source.as_bytes(), path) {
-                    let class_name = class.name.clone();
-                    nodes.push(class);
+            if node.kind() == "class_definition"
+                && let Some(class) = PythonExtractor::extract_class(node, source.as_bytes(), path, module) {
+                let class_name = class.name.clone();
+                nodes.push(class);
                     
-                    // Extract methods within the class
-                    let mut cursor = node.walk();
-                    for child in node.children(&mut cursor) {
-                        if let Some(method) = extractor.extract_method(child, source.as_bytes(), path, Some(&class_name)) {
+                // Extract methods within the class body
+                if let Some(body) = node.child_by_field_name("body") {
+                    let mut cursor = body.walk();
+                    for child in body.children(&mut cursor) {
+                        // Decorated methods are wrapped in a decorated_definition
+                        let definition = match child.kind() {
+                            "decorated_definition" => child.child_by_field_name("definition").unwrap_or(child),
+                            _ => child,
+                        };
+                        if let Some(mut method) = PythonExtractor::extract_method(definition, source.as_bytes(), path, module, Some(&class_name)) {
+                            PythonExtractor::tag_routes(definition, source.as_bytes(), &mut method);
                             nodes.push(method);
                         }
-                        
-                        // Recursively visit class body
-                        if child.kind() == "block" {
-                            visit_node(child, source, path, nodes, edges, imports, extractor, true, Some(&class_name));
-                        }
                     }
+
+                    // Recursively visit class body
+                    visit_node(body, source, path, module, nodes, imports, true);
                 }
             }
             
             // Extract imports
-            imports.extend(extractor.extract_imports(node, source.as_bytes()));
+            imports.extend(PythonExtractor::extract_imports(node, source.as_bytes()));
             
             // Visit children (except in class body which we handled above)
             if node.kind() != "class_definition" {
                 let mut cursor = node.walk();
                 for child in node.children(&mut cursor) {
-                    visit_node(child, source, path, nodes, edges, imports, extractor, in_class, class_name);
+                    visit_node(child, source, path, module, nodes, imports, in_class);
                 }
             }
         }
         
         // Start visiting from root
-        visit_node(root_node, source_code, path, &mut nodes, &mut edges, &mut import_modules, self, false, None);
+        visit_node(root_node, source_code, path, &module_name(path), &mut nodes, &mut import_modules, false);
         
-        // Create edges from imports to nodes
-        for import in &import_modules {
-            for node in &nodes {
-                // Simple heuristic: if node name appears in import or vice versa
-                if import.contains(&node.name) || node.name.contains(import) {
-                    edges.push(GraphEdge {
-                        id: EdgeId(0), // Will be set by graph
-                        source: NodeId(0), // Placeholder - would need proper resolution
-                        target: node.id,
-                        kind: EdgeKind::Imports,
-                        edge_source: EdgeSource::Heuristic,
-                        confidence: 0.7,
-                        label: Some(format!("imports {}", import)),
-                        file_path: Some(path.clone()),
-                        line: node.line_start,
-                    });
-                }
-            }
-        }
+        tag_tests(path, &mut nodes);
         
-        Ok(ExtractionResult { nodes, edges })
+        let (calls, mut references) = local_calls(root_node, source_code.as_bytes(), &nodes, CALL_KINDS);
+        references.extend(import_modules.iter().map(|import| Reference::file_level(EdgeKind::Imports, "imports", import)));
+        references.extend(self.extract_type_hints(root_node, source_code.as_bytes(), &nodes));
+        references.extend(routes::route_nodes(&mut nodes));
+        if path.file_name().is_some_and(|name| name == "urls.py") {
+            references.extend(self.extract_django_urls(root_node, source_code.as_bytes(), path, &mut nodes));
+        }
+        Ok(ExtractionResult { nodes, edges: Vec::new(), contains: Vec::new(), calls, references, errors: syntax_errors(root_node) })
     }
 }
\ No newline at end of file
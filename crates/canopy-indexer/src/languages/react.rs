@@ -0,0 +1,162 @@
+//! React component and hook detection for the TypeScript and JavaScript
+//! extractors
+//!
+//! Components are capitalized functions containing JSX and classes extending
+//! `Component`; hooks are functions named `useX`. Rendering `<Child />` is
+//! recorded as a call from the enclosing function to `Child`, so components
+//! form a tree the same way functions form a call graph.
+
+use crate::calls::{callee_name, enclosing_callable, LocalCall};
+use crate::resolve::Reference;
+use canopy_core::{EdgeKind, GraphNode, NodeKind};
+use tree_sitter::Node;
+
+/// Metadata key marking React code: `component` or `hook`.
+pub const REACT_KEY: &str = "react";
+
+/// Whether `name` follows the hook naming rule: `use` then a capital.
+fn is_hook_name(name: &str) -> bool {
+    name.strip_prefix("use").is_some_and(|rest| rest.starts_with(|c: char| c.is_ascii_uppercase()))
+}
+
+fn contains_jsx(node: Node) -> bool {
+    let mut cursor = node.walk();
+    node.children(&mut cursor).any(|child| child.kind().starts_with("jsx_") || contains_jsx(child))
+}
+
+/// Whether a class declaration extends `Component` or `PureComponent`,
+/// imported or through `React.`.
+fn extends_component(node: Node, source: &[u8]) -> bool {
+    let mut cursor = node.walk();
+    let heritage = node.children(&mut cursor).find(|c| c.kind() == "class_heritage");
+    heritage.and_then(|h| h.utf8_text(source).ok()).is_some_and(|text| {
+        text.split(|c: char| !c.is_alphanumeric() && c != '_').any(|t| t == "Component" || t == "PureComponent")
+    })
+}
+
+/// Tag `extracted`, extracted from `node`, as a component or hook.
+pub fn tag(node: Node, source: &[u8], extracted: &mut GraphNode) {
+    let role = match extracted.kind {
+        NodeKind::Function if is_hook_name(&extracted.name) => "hook",
+        NodeKind::Function if extracted.name.starts_with(|c: char| c.is_ascii_uppercase()) && contains_jsx(node) => "component",
+        NodeKind::Class if extends_component(node, source) => "component",
+        _ => return,
+    };
+    extracted.metadata.extra.insert(REACT_KEY.to_string(), role.to_string());
+}
+
+/// Components rendered within `root`, as calls from the enclosing function
+/// to components defined in the same file and references to the rest.
+/// Lowercase elements are DOM tags and skipped.
+pub fn renders(root: Node, source: &[u8], nodes: &[GraphNode]) -> (Vec<LocalCall>, Vec<Reference>) {
+    let mut resolved: Vec<LocalCall> = Vec::new();
+    let mut unresolved: Vec<Reference> = Vec::new();
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        let mut cursor = node.walk();
+        stack.extend(node.children(&mut cursor));
+        if !matches!(node.kind(), "jsx_opening_element" | "jsx_self_closing_element") {
+            continue;
+        }
+        let Some(name) = node
+            .child_by_field_name("name")
+            .and_then(|n| n.utf8_text(source).ok())
+            .and_then(callee_name)
+            .filter(|name| name.starts_with(|c: char| c.is_ascii_uppercase()))
+        else {
+            continue;
+        };
+        let line = node.start_position().row as u32 + 1;
+        let Some(caller) = enclosing_callable(nodes, line) else {
+            continue;
+        };
+        let mut components = nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| n.name == name && matches!(n.kind, NodeKind::Function | NodeKind::Class));
+        match (components.next(), components.next()) {
+            (Some((callee, _)), None) => {
+                if !resolved.iter().any(|&(c, d, _)| c == caller && d == callee) {
+                    resolved.push((caller, callee, line));
+                }
+            }
+            (Some(_), Some(_)) => {}
+            (None, _) => {
+                if !unresolved.iter().any(|r| r.source == Some(caller) && r.target == name) {
+                    unresolved.push(Reference {
+                        source: Some(caller),
+                        kind: EdgeKind::Calls,
+                        target: name.to_string(),
+                        label: format!("renders {}", name),
+                        line: Some(line),
+                        local_first: false,
+                    });
+                }
+            }
+        }
+    }
+    resolved.sort_by_key(|&(_, _, line)| line);
+    (resolved, unresolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::languages::get_extractor;
+    use std::path::Path;
+
+    #[tokio::test]
+    async fn test_react_components() {
+        let code = r#"
+import { Avatar } from './avatar';
+
+export function useUser(id: string) {
+    return useState(id);
+}
+
+function Badge() {
+    return <span className="badge" />;
+}
+
+export const Profile = ({ id }: { id: string }) => {
+    const user = useUser(id);
+    return (
+        <div>
+            <Avatar user={user} />
+            <Badge />
+        </div>
+    );
+};
+
+class Legacy extends React.Component {
+    render() {
+        return <Profile id="1" />;
+    }
+}
+
+function formatName(name: string) {
+    return name.trim();
+}
+"#;
+        for file in ["profile.tsx", "profile.jsx"] {
+            let path = Path::new(file);
+            // The JavaScript grammar has no type annotations
+            let code = if file.ends_with(".jsx") { code.replace(": string", "").replace(": { id: string }", "") } else { code.to_string() };
+            let result = get_extractor(path).unwrap().extract(path, code.as_bytes()).unwrap();
+            let role = |name: &str| {
+                let node = result.nodes.iter().find(|n| n.name == name).unwrap();
+                node.metadata.extra.get(REACT_KEY).map(String::as_str)
+            };
+            assert_eq!(role("useUser"), Some("hook"), "{}", file);
+            assert_eq!(role("Badge"), Some("component"), "{}", file);
+            assert_eq!(role("Profile"), Some("component"), "{}", file);
+            assert_eq!(role("Legacy"), Some("component"), "{}", file);
+            assert_eq!(role("formatName"), None, "{}", file);
+
+            let name = |i: usize| result.nodes[i].name.as_str();
+            let calls: Vec<_> = result.calls.iter().map(|&(c, d, _)| (name(c), name(d))).collect();
+            assert_eq!(calls, vec![("Profile", "useUser"), ("Profile", "Badge"), ("render", "Profile")], "{}", file);
+            assert!(result.references.iter().any(|r| r.label == "renders Avatar"), "{}", file);
+        }
+    }
+}
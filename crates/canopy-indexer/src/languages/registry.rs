@@ -0,0 +1,341 @@
+//! Which extractor handles which file
+//!
+//! Extractors are registered by name against file patterns. The built-in
+//! languages are registered when the registry is first used; downstream
+//! crates add theirs with [`register_extractor`], and a project's config can
+//! send files matching a glob to any registered extractor with
+//! [`map_pattern`]. A file goes to, in order:
+//!
+//! 1. the extractor of the most recent mapping whose glob matches it,
+//! 2. the most recently registered extractor with a matching file name
+//!    pattern, then with a matching extension,
+//! 3. the generic fallback, which finds no symbols unless the project's
+//!    [`RegexRule`]s added with [`add_regex_rule`] match the file.
+//!
+//! So a plugin registering an extension Canopy already knows replaces the
+//! built-in extractor for it.
+
+use super::generic::{GenericExtractor, RegexRule};
+use crate::extractor::LanguageExtractor;
+use crate::parser_pool::shared_parser_pool;
+use anyhow::{bail, Result};
+use globset::{Glob, GlobMatcher};
+use std::path::Path;
+use std::sync::{Arc, LazyLock, RwLock};
+
+/// Makes an extractor for a file.
+pub type ExtractorFactory = Arc<dyn Fn() -> Box<dyn LanguageExtractor> + Send + Sync>;
+
+/// Files an extractor is registered for.
+#[derive(Clone)]
+pub enum FilePattern {
+    /// Files with this extension, without the dot (`rs`).
+    Extension(String),
+    /// Files whose name matches a glob (`Dockerfile`, `*.proto`).
+    Name(GlobMatcher),
+    /// Files a function picks by their whole path.
+    Path(fn(&Path) -> bool),
+}
+
+impl FilePattern {
+    pub fn extension(extension: &str) -> Self {
+        FilePattern::Extension(extension.trim_start_matches('.').to_string())
+    }
+
+    pub fn name(glob: &str) -> Result<Self> {
+        Ok(FilePattern::Name(Glob::new(glob)?.compile_matcher()))
+    }
+
+    fn matches_name(&self, path: &Path) -> bool {
+        match self {
+            FilePattern::Extension(_) => false,
+            FilePattern::Name(glob) => path.file_name().is_some_and(|name| glob.is_match(name)),
+            FilePattern::Path(matches) => matches(path),
+        }
+    }
+
+    fn matches_extension(&self, path: &Path) -> bool {
+        matches!(self, FilePattern::Extension(extension) if path.extension().is_some_and(|e| e == extension.as_str()))
+    }
+}
+
+/// A glob matched against a file's name, or against its whole path if it
+/// holds a `/`. A relative path glob matches at any depth.
+#[derive(Debug, Clone)]
+pub struct PathGlob(GlobMatcher);
+
+impl PathGlob {
+    pub fn new(glob: &str) -> Result<Self> {
+        let glob = if glob.contains('/') && !glob.starts_with('/') && !glob.starts_with("**") {
+            format!("**/{}", glob)
+        } else {
+            glob.to_string()
+        };
+        Ok(PathGlob(Glob::new(&glob)?.compile_matcher()))
+    }
+
+    pub fn is_match(&self, path: &Path) -> bool {
+        if self.0.glob().glob().contains('/') {
+            self.0.is_match(path)
+        } else {
+            path.file_name().is_some_and(|n| self.0.is_match(n))
+        }
+    }
+}
+
+struct Registration {
+    name: String,
+    patterns: Vec<FilePattern>,
+    factory: ExtractorFactory,
+}
+
+/// Extractors by name and the files each handles.
+pub struct ExtractorRegistry {
+    registrations: Vec<Registration>,
+    mappings: Vec<(PathGlob, String)>,
+    rules: Vec<RegexRule>,
+    fallback: ExtractorFactory,
+}
+
+impl ExtractorRegistry {
+    /// A registry with no extractors, sending every file to the generic
+    /// fallback.
+    pub fn new() -> Self {
+        Self {
+            registrations: Vec::new(),
+            mappings: Vec::new(),
+            rules: Vec::new(),
+            fallback: Arc::new(|| Box::new(GenericExtractor::new(shared_parser_pool()))),
+        }
+    }
+
+    /// A registry with Canopy's own languages and config formats.
+    pub fn with_builtins() -> Self {
+        use super::*;
+        use crate::config;
+
+        let mut registry = Self::new();
+        let extensions = |extensions: &[&str]| extensions.iter().map(|e| FilePattern::extension(e)).collect();
+        registry.register("rust", extensions(&["rs"]), Arc::new(|| Box::new(rust::RustExtractor::new(shared_parser_pool()))));
+        registry.register("typescript", extensions(&["ts", "tsx"]), Arc::new(|| Box::new(typescript::TypeScriptExtractor::new(shared_parser_pool()))));
+        registry.register("javascript", extensions(&["js", "jsx"]), Arc::new(|| Box::new(javascript::JavaScriptExtractor::new(shared_parser_pool()))));
+        registry.register("python", extensions(&["py"]), Arc::new(|| Box::new(python::PythonExtractor::new(shared_parser_pool()))));
+        registry.register("go", extensions(&["go"]), Arc::new(|| Box::new(go::GoExtractor::new(shared_parser_pool()))));
+        registry.register("java", extensions(&["java"]), Arc::new(|| Box::new(java::JavaExtractor::new(shared_parser_pool()))));
+        registry.register("c", extensions(&["c"]), Arc::new(|| Box::new(c::CExtractor::new(shared_parser_pool()))));
+        registry.register("cpp", extensions(&["cpp", "cc", "cxx", "c++"]), Arc::new(|| Box::new(cpp::CppExtractor::new(shared_parser_pool()))));
+        registry.register("php", extensions(&["php"]), Arc::new(|| Box::new(php::PhpExtractor::new(shared_parser_pool()))));
+        registry.register("scala", extensions(&["scala", "sc"]), Arc::new(|| Box::new(scala::ScalaExtractor::new(shared_parser_pool()))));
+        registry.register("graphql", extensions(&["graphql", "gql"]), Arc::new(|| Box::new(graphql::GraphQlExtractor::new())));
+        registry.register("markdown", extensions(&["md", "markdown", "mdx"]), Arc::new(|| Box::new(markdown::MarkdownExtractor::new(shared_parser_pool()))));
+        registry.register("yaml", extensions(&["yml", "yaml"]), Arc::new(|| Box::new(config::yaml::YamlParser::new(shared_parser_pool()))));
+        registry.register("json", extensions(&["json"]), Arc::new(|| Box::new(config::json::JsonParser::new())));
+        registry.register("toml", extensions(&["toml"]), Arc::new(|| Box::new(config::toml_parser::TomlParser::new())));
+        // Formats recognised by name rather than extension; `.env` and
+        // `.env.local` are named by prefix
+        registry.register("docker-compose", vec![FilePattern::Path(config::docker_compose::is_compose_file)], Arc::new(|| Box::new(config::docker_compose::DockerComposeParser::new())));
+        registry.register("github-actions", vec![FilePattern::Path(config::github_actions::is_workflow)], Arc::new(|| Box::new(config::github_actions::GithubActionsParser::new())));
+        registry.register("openapi", vec![FilePattern::Path(config::openapi::is_openapi_path)], Arc::new(|| Box::new(config::openapi::OpenApiParser::new())));
+        registry.register("dotenv", vec![FilePattern::Path(config::dotenv::is_dotenv)], Arc::new(|| Box::new(config::dotenv::DotenvParser::new())));
+        registry
+    }
+
+    /// Register the extractor `name` for files matching any of `patterns`,
+    /// replacing an extractor registered under the same name.
+    pub fn register(&mut self, name: &str, patterns: Vec<FilePattern>, factory: ExtractorFactory) {
+        self.registrations.retain(|r| r.name != name);
+        self.registrations.push(Registration { name: name.to_string(), patterns, factory });
+    }
+
+    /// Send files matching `glob` to the registered extractor `name`. The
+    /// glob is matched against the file name unless it holds a `/`, in
+    /// which case it is matched against the whole path.
+    pub fn map_pattern(&mut self, glob: &str, name: &str) -> Result<()> {
+        if !self.registrations.iter().any(|r| r.name == name) {
+            bail!("No extractor named {} to map {} to", name, glob);
+        }
+        self.mappings.push((PathGlob::new(glob)?, name.to_string()));
+        Ok(())
+    }
+
+    /// Have the generic fallback apply `rule` to the files it matches.
+    pub fn add_regex_rule(&mut self, rule: RegexRule) {
+        self.rules.push(rule);
+    }
+
+    /// Names of the registered extractors, in registration order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.registrations.iter().map(|r| r.name.as_str())
+    }
+
+    /// The extractor handling `path`, if one is registered or mapped for it.
+    fn registration(&self, path: &Path) -> Option<&Registration> {
+        let by_name = |name: &str| self.registrations.iter().find(|r| r.name == name);
+        let mapped = self.mappings.iter().rev().find_map(|(glob, name)| glob.is_match(path).then(|| by_name(name)).flatten());
+        mapped
+            .or_else(|| self.registrations.iter().rev().find(|r| r.patterns.iter().any(|p| p.matches_name(path))))
+            .or_else(|| self.registrations.iter().rev().find(|r| r.patterns.iter().any(|p| p.matches_extension(path))))
+    }
+
+    /// The regex rules the fallback applies to `path`.
+    fn rules_for(&self, path: &Path) -> Vec<RegexRule> {
+        self.rules.iter().filter(|rule| rule.applies_to(path)).cloned().collect()
+    }
+
+    /// Whether an extractor other than the fallback handles `path`, or
+    /// regex rules apply to it.
+    pub fn handles(&self, path: &Path) -> bool {
+        self.registration(path).is_some() || self.rules.iter().any(|rule| rule.applies_to(path))
+    }
+
+    /// The name of the extractor handling `path`, if not the fallback.
+    pub fn extractor_name(&self, path: &Path) -> Option<&str> {
+        self.registration(path).map(|r| r.name.as_str())
+    }
+
+    /// An extractor for `path`, falling back to the generic one with the
+    /// regex rules that apply to it.
+    pub fn extractor_for(&self, path: &Path) -> Box<dyn LanguageExtractor> {
+        if let Some(registration) = self.registration(path) {
+            return (registration.factory)();
+        }
+        match self.rules_for(path) {
+            rules if rules.is_empty() => (self.fallback)(),
+            rules => Box::new(GenericExtractor::new(shared_parser_pool()).with_rules(rules)),
+        }
+    }
+}
+
+impl Default for ExtractorRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+static REGISTRY: LazyLock<RwLock<ExtractorRegistry>> = LazyLock::new(|| RwLock::new(ExtractorRegistry::with_builtins()));
+
+/// The process-wide registry `get_extractor` consults.
+pub fn registry() -> std::sync::RwLockReadGuard<'static, ExtractorRegistry> {
+    REGISTRY.read().unwrap()
+}
+
+/// Register the extractor `name` for files matching any of `patterns` in
+/// the process-wide registry. Registering a built-in's name replaces it.
+pub fn register_extractor(name: &str, patterns: Vec<FilePattern>, factory: ExtractorFactory) {
+    REGISTRY.write().unwrap().register(name, patterns, factory);
+}
+
+/// Send files matching `glob` to the extractor `name` in the process-wide
+/// registry, as a project's config asks.
+pub fn map_pattern(glob: &str, name: &str) -> Result<()> {
+    REGISTRY.write().unwrap().map_pattern(glob, name)
+}
+
+/// Have the process-wide generic fallback apply `rule`, as a project's
+/// config asks.
+pub fn add_regex_rule(rule: RegexRule) {
+    REGISTRY.write().unwrap().add_regex_rule(rule);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extractor::ExtractionResult;
+    use canopy_core::NodeKind;
+
+    /// Finds nothing, but says so with its name.
+    struct Named(&'static str);
+
+    impl LanguageExtractor for Named {
+        fn extract(&self, _path: &Path, _content: &[u8]) -> Result<ExtractionResult> {
+            bail!("{}", self.0)
+        }
+    }
+
+    fn named(name: &'static str) -> ExtractorFactory {
+        Arc::new(move || Box::new(Named(name)))
+    }
+
+    #[test]
+    fn test_registry_precedence() {
+        let mut registry = ExtractorRegistry::with_builtins();
+        let name = |registry: &ExtractorRegistry, path: &str| registry.extractor_name(Path::new(path)).map(str::to_string);
+        assert_eq!(name(&registry, "src/main.rs").as_deref(), Some("rust"));
+        assert_eq!(name(&registry, "api/openapi.yaml").as_deref(), Some("openapi"));
+        assert_eq!(name(&registry, ".env.local").as_deref(), Some("dotenv"));
+        assert_eq!(name(&registry, "schema.proto"), None);
+
+        registry.register("proto", vec![FilePattern::extension("proto")], named("proto"));
+        registry.register("vue", vec![FilePattern::name("*.vue").unwrap()], named("vue"));
+        // A later registration for a known extension replaces the built-in
+        registry.register("my-python", vec![FilePattern::extension("py")], named("my-python"));
+        assert_eq!(name(&registry, "schema.proto").as_deref(), Some("proto"));
+        assert_eq!(name(&registry, "web/App.vue").as_deref(), Some("vue"));
+        assert_eq!(name(&registry, "app.py").as_deref(), Some("my-python"));
+
+        registry.map_pattern("*.pyw", "python").unwrap();
+        registry.map_pattern("gen/*.rs", "proto").unwrap();
+        assert!(registry.map_pattern("*.x", "cobol").is_err());
+        assert_eq!(name(&registry, "tool.pyw").as_deref(), Some("python"));
+        assert_eq!(name(&registry, "/repo/gen/api.rs").as_deref(), Some("proto"));
+        assert_eq!(name(&registry, "/repo/src/api.rs").as_deref(), Some("rust"));
+
+        let error = registry.extractor_for(Path::new("schema.proto")).extract(Path::new("schema.proto"), b"").err().unwrap();
+        assert_eq!(error.to_string(), "proto");
+        assert!(registry.names().any(|n| n == "vue"));
+    }
+
+    #[test]
+    fn test_regex_rules() {
+        #[derive(serde::Deserialize)]
+        struct Config {
+            rule: Vec<RegexRule>,
+        }
+        let config: Config = toml::from_str(
+            r#"
+            [[rule]]
+            files = "*.flow"
+            pattern = '^step\s+(?P<name>\w+)'
+            kind = "Function"
+
+            [[rule]]
+            files = "flows/*.flow"
+            pattern = '^flow (\w+):'
+            kind = "Module"
+            "#,
+        )
+        .unwrap();
+        let mut registry = ExtractorRegistry::with_builtins();
+        let path = Path::new("/repo/flows/billing.flow");
+        assert!(!registry.handles(path));
+        for rule in config.rule {
+            registry.add_regex_rule(rule);
+        }
+        assert!(registry.handles(path));
+        assert!(!registry.handles(Path::new("billing.txt")));
+
+        let source = "flow billing:\n  # charges the card\nstep charge\nstep   refund\n";
+        let result = registry.extractor_for(path).extract(path, source.as_bytes()).unwrap();
+        let nodes: Vec<_> = result.nodes.iter().map(|n| (n.kind, n.name.as_str(), n.line_start)).collect();
+        assert_eq!(
+            nodes,
+            vec![
+                (NodeKind::Function, "charge", Some(3)),
+                (NodeKind::Function, "refund", Some(4)),
+                (NodeKind::Module, "billing", Some(1)),
+            ]
+        );
+        assert_eq!(result.nodes[1].metadata.signature.as_deref(), Some("step   refund"));
+        assert!(toml::from_str::<Config>("[[rule]]\nfiles = \"*.x\"\npattern = \"(\"\nkind = \"Function\"\n").is_err());
+    }
+
+    #[test]
+    fn test_register_extractor() {
+        let path = Path::new("model.canopytest");
+        assert!(!crate::languages::is_indexable(path));
+        register_extractor("canopytest", vec![FilePattern::extension("canopytest")], named("canopytest"));
+        assert!(crate::languages::is_indexable(path));
+        let error = crate::languages::get_extractor(path).unwrap().extract(path, b"").err().unwrap();
+        assert_eq!(error.to_string(), "canopytest");
+    }
+}
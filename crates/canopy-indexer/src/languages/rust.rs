@@ -1,16 +1,47 @@
 //! Rust language extractor using tree-sitter
 
 use super::{ExtractionResult, LanguageExtractor};
-use canopy_core::{GraphNode, GraphEdge, NodeKind, EdgeSource, Language, NodeId, EdgeId};
-use std::path::PathBuf;
+use canopy_core::{GraphNode, NodeKind, EdgeKind, Language, NodeId};
+use std::path::Path;
 use tree_sitter::{Node, Point};
 use anyhow::Result;
+use crate::extractor::syntax_errors;
+use crate::metadata::{code_lines, node_metadata};
 use crate::parser_pool::{ParserPool, ParseRequest, FileType};
+use crate::calls::local_calls;
+use crate::test_cases::tag_tests;
+use crate::resolve::Reference;
+
+/// Call expressions and the field holding their callee.
+const CALL_KINDS: &[(&str, &str)] = &[("call_expression", "function"), ("macro_invocation", "macro")];
+
+/// Named items with the node kind they become and whether they are
+/// containers.
+const ITEM_KINDS: &[(&str, NodeKind, bool)] = &[
+    ("enum_item", NodeKind::Enum, true),
+    ("union_item", NodeKind::Union, true),
+    ("const_item", NodeKind::Constant, false),
+    ("static_item", NodeKind::Variable, false),
+    ("type_item", NodeKind::TypeAlias, false),
+];
 
 pub struct RustExtractor {
     parser_pool: ParserPool,
 }
 
+/// What a walk of one file has collected so far.
+struct Walk<'a> {
+    source: &'a [u8],
+    path: &'a Path,
+    nodes: Vec<GraphNode>,
+    imports: Vec<(String, bool)>,
+    contains: Vec<(usize, usize)>,
+    /// Out-of-line modules: the Module node and the file it is read from.
+    modules: Vec<(usize, String, u32)>,
+    /// Derive macros applied to each item.
+    derives: Vec<(usize, String, u32)>,
+}
+
 impl RustExtractor {
     pub fn new(parser_pool: ParserPool) -> Self {
         Self { parser_pool }
@@ -20,127 +51,279 @@ impl RustExtractor {
         (point.row as u32) + 1
     }
     
-    fn extract_function(&self, node: Node, source: &[u8], path: &PathBuf) -> Option<GraphNode> {
+    /// The type an `impl` or `trait` item belongs to: `Foo` for the items of
+    /// `impl Foo`, `impl<T> Display for path::Foo<T>` and `trait Foo`.
+    fn owner<'s>(node: Node, source: &'s [u8]) -> Option<&'s str> {
+        let block = node.parent().filter(|p| p.kind() == "declaration_list")?.parent()?;
+        let name = match block.kind() {
+            "impl_item" => block.child_by_field_name("type")?,
+            "trait_item" => block.child_by_field_name("name")?,
+            _ => return None,
+        };
+        let name = name.utf8_text(source).ok()?;
+        let name = name.split('<').next().unwrap_or(name);
+        name.rsplit("::").next().map(str::trim)
+    }
+    
+    fn extract_function(&self, node: Node, source: &[u8], path: &Path, module: &str) -> Option<GraphNode> {
         if node.kind() == "function_item" || node.kind() == "method_definition" {
             // Find the identifier node
             let mut cursor = node.walk();
             for child in node.children(&mut cursor) {
-                if child.kind() == "identifier" {
-                    if let Ok(name) = child.utf8_text(source) {
-                        let start_pos = Self::point_to_u32(node.start_position());
-                        let end_pos = Self::point_to_u32(node.end_position());
+                if child.kind() == "identifier"
+                    && let Ok(name) = child.utf8_text(source) {
+                    let start_pos = Self::point_to_u32(node.start_position());
+                    let end_pos = Self::point_to_u32(node.end_position());
+                    let macro_name = proc_macro_name(node, source, name);
+                    let (kind, name) = match macro_name {
+                        Some(macro_name) => (NodeKind::Macro, macro_name),
+                        None => (NodeKind::Function, name),
+                    };
+                    let (kind, qualified_name) = match Self::owner(node, source) {
+                        Some(owner) => (NodeKind::Method, format!("{}::{}::{}", module, owner, name)),
+                        None => (kind, format!("{}::{}", module, name)),
+                    };
                         
-                        return Some(GraphNode {
-                            id: NodeId(0), // Will be set by graph
-                            kind: NodeKind::Function,
-                            name: name.to_string(),
-                            qualified_name: format!("{}::{}", path.display(), name),
-                            file_path: path.clone(),
-                            line_start: Some(start_pos),
-                            line_end: Some(end_pos),
-                            language: Some(Language::Rust),
-                            is_container: false,
-                            child_count: 0,
-                            loc: Some(((end_pos - start_pos) as usize) as u32),
-                            metadata: std::collections::HashMap::new(),
-                        });
-                    }
+                    return Some(GraphNode {
+                        id: NodeId(0), // Will be set by graph
+                        kind,
+                        name: name.to_string(),
+                        qualified_name: qualified_name.into(),
+                        file_path: path.into(),
+                        line_start: Some(start_pos),
+                        line_end: Some(end_pos),
+                        language: Some(Language::Rust),
+                        is_container: false,
+                        child_count: 0,
+                        loc: Some(code_lines(node, source)),
+                        metadata: node_metadata(node, source, Language::Rust, name),
+                    });
                 }
             }
         }
         None
     }
     
-    fn extract_struct(&self, node: Node, source: &[u8], path: &PathBuf) -> Option<GraphNode> {
+    fn extract_struct(&self, node: Node, source: &[u8], path: &Path, module: &str) -> Option<GraphNode> {
         if node.kind() == "struct_item" {
             let mut cursor = node.walk();
             for child in node.children(&mut cursor) {
-                if child.kind() == "type_identifier" || child.kind() == "identifier" {
-                    if let Ok(name) = child.utf8_text(source) {
-                        let start_pos = Self::point_to_u32(node.start_position());
-                        let end_pos = Self::point_to_u32(node.end_position());
+                if (child.kind() == "type_identifier" || child.kind() == "identifier")
+                    && let Ok(name) = child.utf8_text(source) {
+                    let start_pos = Self::point_to_u32(node.start_position());
+                    let end_pos = Self::point_to_u32(node.end_position());
                         
-                        return Some(GraphNode {
-                            id: NodeId(0), // Will be set by graph
-                            kind: NodeKind::Class,
-                            name: name.to_string(),
-                            qualified_name: format!("{}::{}", path.display(), name),
-                            file_path: path.clone(),
-                            line_start: Some(start_pos),
-                            line_end: Some(end_pos),
-                            language: Some(Language::Rust),
-                            is_container: true,
-                            child_count: 0,
-                            loc: Some(((end_pos - start_pos) as usize) as u32),
-                            metadata: std::collections::HashMap::new(),
-                        });
-                    }
+                    return Some(GraphNode {
+                        id: NodeId(0), // Will be set by graph
+                        kind: NodeKind::Struct,
+                        name: name.to_string(),
+                        qualified_name: format!("{}::{}", module, name).into(),
+                        file_path: path.into(),
+                        line_start: Some(start_pos),
+                        line_end: Some(end_pos),
+                        language: Some(Language::Rust),
+                        is_container: true,
+                        child_count: 0,
+                        loc: Some(code_lines(node, source)),
+                        metadata: node_metadata(node, source, Language::Rust, name),
+                    });
                 }
             }
         }
         None
     }
     
-    fn extract_impl_block(&self, node: Node, source: &[u8], path: &PathBuf) -> Vec<GraphNode> {
-        let mut methods = Vec::new();
-        
-        if node.kind() == "impl_item" {
-            let mut cursor = node.walk();
-            for child in node.children(&mut cursor) {
-                if child.kind() == "declaration_list" {
-                    let mut method_cursor = child.walk();
-                    for method in child.children(&mut method_cursor) {
-                        if let Some(method_node) = self.extract_function(method, source, path) {
-                            methods.push(method_node);
-                        }
-                    }
-                }
-            }
+    fn extract_trait(&self, node: Node, source: &[u8], path: &Path, module: &str) -> Option<GraphNode> {
+        if node.kind() == "trait_item"
+            && let Some(name_node) = node.child_by_field_name("name")
+            && let Ok(name) = name_node.utf8_text(source) {
+            let start_pos = Self::point_to_u32(node.start_position());
+            let end_pos = Self::point_to_u32(node.end_position());
+                    
+            return Some(GraphNode {
+                id: NodeId(0), // Will be set by graph
+                kind: NodeKind::Trait,
+                name: name.to_string(),
+                qualified_name: format!("{}::{}", module, name).into(),
+                file_path: path.into(),
+                line_start: Some(start_pos),
+                line_end: Some(end_pos),
+                language: Some(Language::Rust),
+                is_container: true,
+                child_count: 0,
+                loc: Some(code_lines(node, source)),
+                metadata: node_metadata(node, source, Language::Rust, name),
+            });
+        }
+        None
+    }
+    
+    fn extract_field(&self, node: Node, source: &[u8], path: &Path, module: &str) -> Option<GraphNode> {
+        if node.kind() == "field_declaration"
+            && let Some(name_node) = node.child_by_field_name("name")
+            && let Ok(name) = name_node.utf8_text(source) {
+            let start_pos = Self::point_to_u32(node.start_position());
+            let end_pos = Self::point_to_u32(node.end_position());
+            let owner = node
+                .parent()
+                .and_then(|list| list.parent())
+                .and_then(|item| item.child_by_field_name("name"))
+                .and_then(|n| n.utf8_text(source).ok());
+            let qualified_name = match owner {
+                Some(owner) => format!("{}::{}::{}", module, owner, name),
+                None => format!("{}::{}", module, name),
+            };
+                    
+            return Some(GraphNode {
+                id: NodeId(0), // Will be set by graph
+                kind: NodeKind::Field,
+                name: name.to_string(),
+                qualified_name: qualified_name.into(),
+                file_path: path.into(),
+                line_start: Some(start_pos),
+                line_end: Some(end_pos),
+                language: Some(Language::Rust),
+                is_container: false,
+                child_count: 0,
+                loc: Some(code_lines(node, source)),
+                metadata: node_metadata(node, source, Language::Rust, name),
+            });
         }
+        None
+    }
+    
+    /// Enums, unions, consts, statics and type aliases; those in `impl` and
+    /// `trait` blocks are qualified with their type.
+    fn extract_item(&self, node: Node, source: &[u8], path: &Path, module: &str) -> Option<GraphNode> {
+        let &(_, kind, is_container) = ITEM_KINDS.iter().find(|(k, _, _)| *k == node.kind())?;
+        let name = node.child_by_field_name("name")?.utf8_text(source).ok()?;
+        let start_pos = Self::point_to_u32(node.start_position());
+        let end_pos = Self::point_to_u32(node.end_position());
+        let qualified_name = match Self::owner(node, source) {
+            Some(owner) => format!("{}::{}::{}", module, owner, name),
+            None => format!("{}::{}", module, name),
+        };
         
-        methods
+        Some(GraphNode {
+            id: NodeId(0), // Will be set by graph
+            kind,
+            name: name.to_string(),
+            qualified_name: qualified_name.into(),
+            file_path: path.into(),
+            line_start: Some(start_pos),
+            line_end: Some(end_pos),
+            language: Some(Language::Rust),
+            is_container,
+            child_count: 0,
+            loc: Some(code_lines(node, source)),
+            metadata: node_metadata(node, source, Language::Rust, name),
+        })
+    }
+    
+    /// A `macro_rules!` definition. Exported macros live at the crate root.
+    fn extract_macro(&self, node: Node, source: &[u8], path: &Path, module: &str) -> Option<GraphNode> {
+        if node.kind() == "macro_definition"
+            && let Some(name_node) = node.child_by_field_name("name")
+            && let Ok(name) = name_node.utf8_text(source) {
+            let start_pos = Self::point_to_u32(node.start_position());
+            let end_pos = Self::point_to_u32(node.end_position());
+            let exported = attributes(node, source).iter().any(|a| a.trim() == "macro_export");
+            let module = if exported { "crate" } else { module };
+                    
+            return Some(GraphNode {
+                id: NodeId(0), // Will be set by graph
+                kind: NodeKind::Macro,
+                name: name.to_string(),
+                qualified_name: format!("{}::{}", module, name).into(),
+                file_path: path.into(),
+                line_start: Some(start_pos),
+                line_end: Some(end_pos),
+                language: Some(Language::Rust),
+                is_container: false,
+                child_count: 0,
+                loc: Some(code_lines(node, source)),
+                metadata: node_metadata(node, source, Language::Rust, name),
+            });
+        }
+        None
     }
     
-    fn extract_use_statement(&self, node: Node, source: &[u8]) -> Vec<String> {
+    /// A `mod` item, inline or declared for a file of its own.
+    fn extract_module(&self, node: Node, source: &[u8], path: &Path, module: &str) -> Option<GraphNode> {
+        if node.kind() == "mod_item"
+            && let Some(name_node) = node.child_by_field_name("name")
+            && let Ok(name) = name_node.utf8_text(source) {
+            let start_pos = Self::point_to_u32(node.start_position());
+            let end_pos = Self::point_to_u32(node.end_position());
+                    
+            return Some(GraphNode {
+                id: NodeId(0), // Will be set by graph
+                kind: NodeKind::Module,
+                name: name.to_string(),
+                qualified_name: format!("{}::{}", module, name).into(),
+                file_path: path.into(),
+                line_start: Some(start_pos),
+                line_end: Some(end_pos),
+                language: Some(Language::Rust),
+                is_container: true,
+                child_count: 0,
+                loc: Some(code_lines(node, source)),
+                metadata: node_metadata(node, source, Language::Rust, name),
+            });
+        }
+        None
+    }
+    
+    /// Paths brought in by a `use` declaration, with whether they are
+    /// re-exported (`pub use`).
+    fn extract_use_statement(&self, node: Node, source: &[u8]) -> Vec<(String, bool)> {
         let mut imports = Vec::new();
         
         if node.kind() == "use_declaration" {
-            // Extract the path from use statement
+            let mut cursor = node.walk();
+            let is_pub = node.children(&mut cursor).any(|c| c.kind() == "visibility_modifier");
             if let Some(path_node) = node.child_by_field_name("argument") {
-                if let Some(path) = self.extract_use_path(path_node, source) {
-                    imports.push(path);
-                }
+                let mut paths = Vec::new();
+                Self::extract_use_paths(path_node, source, "", &mut paths);
+                imports.extend(paths.into_iter().map(|path| (path, is_pub)));
             }
         }
         
         imports
     }
     
-    fn extract_use_path(&self, node: Node, source: &[u8]) -> Option<String> {
+    /// Expand a use tree into one path per imported item, so
+    /// `crate::model::{Node, edge::Edge}` gives `crate::model::Node` and
+    /// `crate::model::edge::Edge`.
+    fn extract_use_paths(node: Node, source: &[u8], prefix: &str, paths: &mut Vec<String>) {
+        let join = |path: &str| if prefix.is_empty() { path.to_string() } else { format!("{}::{}", prefix, path) };
         match node.kind() {
-            "scoped_identifier" | "identifier" => {
-                node.utf8_text(source).ok().map(|s| s.to_string())
+            "use_list" => {
+                let mut cursor = node.walk();
+                for child in node.named_children(&mut cursor) {
+                    Self::extract_use_paths(child, source, prefix, paths);
+                }
             }
-            "use_wildcard" => {
-                if let Some(prefix) = node.child_by_field_name("prefix") {
-                    prefix.utf8_text(source).ok().map(|s| format!("{}::*", s))
-                } else {
-                    None
+            "scoped_use_list" => {
+                let scope = node
+                    .child_by_field_name("path")
+                    .and_then(|p| p.utf8_text(source).ok())
+                    .map(join)
+                    .unwrap_or_else(|| prefix.to_string());
+                if let Some(list) = node.child_by_field_name("list") {
+                    Self::extract_use_paths(list, source, &scope, paths);
                 }
             }
-            _ => {
-                // Recursively build the path
-                let mut path_parts = Vec::new();
-                let mut cursor = node.walk();
-                for child in node.children(&mut cursor) {
-                    if let Some(part) = self.extract_use_path(child, source) {
-                        path_parts.push(part);
-                    }
+            "use_as_clause" => {
+                if let Some(path) = node.child_by_field_name("path") {
+                    Self::extract_use_paths(path, source, prefix, paths);
                 }
-                if path_parts.is_empty() {
-                    None
-                } else {
-                    Some(path_parts.join("::"))
+            }
+            // `self` inside a list imports the list's own prefix
+            "self" if !prefix.is_empty() => paths.push(prefix.to_string()),
+            _ => {
+                if let Ok(path) = node.utf8_text(source) {
+                    paths.push(join(path));
                 }
             }
         }
@@ -148,7 +331,7 @@ impl RustExtractor {
 }
 
 impl LanguageExtractor for RustExtractor {
-    fn extract(&self, path: &PathBuf, content: &[u8]) -> Result<ExtractionResult> {
+    fn extract(&self, path: &Path, content: &[u8]) -> Result<ExtractionResult> {
         let source_code = std::str::from_utf8(content)?;
         
         // Use the parser pool to parse the content
@@ -156,72 +339,209 @@ impl LanguageExtractor for RustExtractor {
         let request = ParseRequest {
             file_type: FileType::Rust,
             content: source_code.to_string(),
-            path: path.clone(),
+            path: path.to_path_buf(),
         };
         
         let parse_result = self.parser_pool.parse_blocking(request)?;
         let tree = parse_result.tree;
         
-        let mut nodes = Vec::new();
-        let mut edges = Vec::new();
-        let mut imports = Vec::new();
+        let mut walk = Walk {
+            source: source_code.as_bytes(),
+            path,
+            nodes: Vec::new(),
+            imports: Vec::new(),
+            contains: Vec::new(),
+            modules: Vec::new(),
+            derives: Vec::new(),
+        };
         
         // Walk the AST
         let root_node = tree.root_node();
         
-        fn visit_node(
-            node: Node,
-            source: &str,
-            path: &PathBuf,
-            nodes: &mut Vec<GraphNode>,
-            edges: &mut Vec<GraphEdge>,
-            imports: &mut Vec<String>,
-            extractor: &RustExtractor,
-        ) {
-            // Extract functions
-            if let Some(function) = extractor.extract_function(node, source.as_bytes(), path) {
-                nodes.push(function);
+        fn visit_node(node: Node, module: &str, parent: Option<usize>, walk: &mut Walk, extractor: &RustExtractor) {
+            let source = walk.source;
+            let path = walk.path;
+            
+            // Modules: inline ones are walked with their own path, out-of-line
+            // ones link to the file holding them once it is indexed
+            if let Some(module_node) = extractor.extract_module(node, source, path, module) {
+                let index = walk.nodes.len();
+                let inner = module_node.qualified_name.to_string();
+                let name = module_node.name.clone();
+                walk.nodes.push(module_node);
+                if let Some(parent) = parent {
+                    walk.contains.push((parent, index));
+                }
+                match node.child_by_field_name("body") {
+                    Some(body) => {
+                        let mut cursor = body.walk();
+                        for child in body.children(&mut cursor) {
+                            visit_node(child, &inner, Some(index), walk, extractor);
+                        }
+                    }
+                    None => {
+                        let file = module_file(node, source, path, &name);
+                        let line = RustExtractor::point_to_u32(node.start_position());
+                        walk.modules.push((index, file, line));
+                    }
+                }
+                return;
             }
             
-            // Extract structs
-            if let Some(struct_node) = extractor.extract_struct(node, source.as_bytes(), path) {
-                nodes.push(struct_node);
+            let item = extractor
+                .extract_function(node, source, path, module)
+                .or_else(|| extractor.extract_struct(node, source, path, module))
+                .or_else(|| extractor.extract_trait(node, source, path, module))
+                .or_else(|| extractor.extract_item(node, source, path, module))
+                .or_else(|| extractor.extract_macro(node, source, path, module));
+            if let Some(item) = item {
+                let index = walk.nodes.len();
+                if let Some(parent) = parent {
+                    walk.contains.push((parent, index));
+                }
+                let line = RustExtractor::point_to_u32(node.start_position());
+                walk.derives.extend(derives(node, source).into_iter().map(|name| (index, name, line)));
+                walk.nodes.push(item);
             }
             
-            // Extract impl methods
-            if node.kind() == "impl_item" {
-                let methods = extractor.extract_impl_block(node, source.as_bytes(), path);
-                nodes.extend(methods);
+            // Extract struct fields
+            if let Some(field) = extractor.extract_field(node, source, path, module) {
+                walk.nodes.push(field);
             }
             
             // Extract imports
-            imports.extend(extractor.extract_use_statement(node, source.as_bytes()));
+            walk.imports.extend(extractor.extract_use_statement(node, source));
             
             // Visit children
             let mut cursor = node.walk();
             for child in node.children(&mut cursor) {
-                visit_node(child, source, path, nodes, edges, imports, extractor);
+                visit_node(child, module, parent, walk, extractor);
             }
         }
         
-        visit_node(root_node, source_code, path, &mut nodes, &mut edges, &mut imports, self);
+        visit_node(root_node, &module_path(path), None, &mut walk, self);
         
-        // Create edges for imports
-        for import in imports {
-            edges.push(GraphEdge {
-                id: EdgeId(0), // Will be set by graph
-                source: NodeId(0), // Will be set when added to graph
-                target: NodeId(0), // Will be set when added to graph
-                kind: canopy_core::EdgeKind::Imports,
-                edge_source: EdgeSource::Heuristic,
-                confidence: 1.0,
-                label: Some(format!("uses {}", import)),
-                file_path: Some(path.clone()),
-                line: None,
+        let Walk { mut nodes, imports, contains, modules, derives, .. } = walk;
+        tag_tests(path, &mut nodes);
+        let (calls, mut references) = local_calls(root_node, source_code.as_bytes(), &nodes, CALL_KINDS);
+        for (import, is_pub) in imports {
+            references.push(if is_pub {
+                Reference::file_level(EdgeKind::Reexports, "re-exports", &import)
+            } else {
+                Reference::file_level(EdgeKind::Imports, "uses", &import)
             });
         }
-        
-        Ok(ExtractionResult { nodes, edges })
+        for (index, file, line) in modules {
+            references.push(Reference {
+                source: Some(index),
+                kind: EdgeKind::Contains,
+                label: format!("contains {}", file.trim_start_matches("./")),
+                target: file,
+                line: Some(line),
+                local_first: false,
+            });
+        }
+        for (index, name, line) in derives {
+            references.push(Reference {
+                source: Some(index),
+                kind: EdgeKind::Calls,
+                label: format!("derives {}", name),
+                target: name,
+                line: Some(line),
+                local_first: false,
+            });
+        }
+        Ok(ExtractionResult { nodes, edges: Vec::new(), contains, calls, references, errors: syntax_errors(root_node) })
+    }
+}
+
+/// The module path of a file in its crate: `crate` for `src/lib.rs`,
+/// `src/main.rs` and binaries under `src/bin`, `crate::foo::bar` for
+/// `src/foo/bar.rs` and `src/foo/bar/mod.rs`.
+pub fn module_path(path: &Path) -> String {
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+    let Some(src) = path.ancestors().skip(1).find(|a| a.ends_with("src")) else {
+        return match stem {
+            "lib" | "main" | "mod" | "" => "crate".to_string(),
+            stem => format!("crate::{}", stem),
+        };
+    };
+    let relative = path.strip_prefix(src).unwrap_or(path);
+    let mut segments: Vec<String> = relative
+        .parent()
+        .into_iter()
+        .flat_map(|p| p.components())
+        .filter_map(|c| c.as_os_str().to_str().map(str::to_string))
+        .collect();
+    if segments.first().is_some_and(|s| s == "bin") {
+        return "crate".to_string();
+    }
+    let is_root = segments.is_empty() && matches!(stem, "lib" | "main");
+    if !is_root && stem != "mod" {
+        segments.push(stem.to_string());
+    }
+    std::iter::once("crate".to_string()).chain(segments).collect::<Vec<_>>().join("::")
+}
+
+/// The outer attributes of an item, without their `#[` `]`, nearest first.
+fn attributes<'s>(node: Node, source: &'s [u8]) -> Vec<&'s str> {
+    let mut attributes = Vec::new();
+    let mut sibling = node.prev_named_sibling();
+    while let Some(previous) = sibling.filter(|s| matches!(s.kind(), "attribute_item" | "line_comment" | "block_comment")) {
+        if previous.kind() == "attribute_item"
+            && let Ok(text) = previous.utf8_text(source) {
+            attributes.push(text.trim_start_matches("#[").trim_end_matches(']').trim());
+        }
+        sibling = previous.prev_named_sibling();
+    }
+    attributes
+}
+
+/// The arguments of an attribute, `Serialize` and `Debug` for
+/// `derive(serde::Serialize, Debug)`, if it is `attribute`.
+fn attribute_arguments<'s>(text: &'s str, attribute: &str) -> Option<Vec<&'s str>> {
+    let arguments = text.strip_prefix(attribute)?.trim_start().strip_prefix('(')?;
+    let arguments = arguments.rsplit_once(')').map_or(arguments, |(a, _)| a);
+    Some(arguments.split(',').filter_map(|a| a.rsplit("::").next()).map(str::trim).filter(|a| !a.is_empty()).collect())
+}
+
+/// The derive macros applied to an item.
+fn derives(node: Node, source: &[u8]) -> Vec<String> {
+    attributes(node, source)
+        .into_iter()
+        .filter_map(|a| attribute_arguments(a, "derive"))
+        .flatten()
+        .map(str::to_string)
+        .collect()
+}
+
+/// The name a proc-macro function is invoked by: its own for
+/// `#[proc_macro]` and `#[proc_macro_attribute]`, the derive's for
+/// `#[proc_macro_derive(Name)]`. `None` for other functions.
+fn proc_macro_name<'s>(node: Node, source: &'s [u8], name: &'s str) -> Option<&'s str> {
+    attributes(node, source).into_iter().find_map(|a| match a {
+        "proc_macro" | "proc_macro_attribute" => Some(name),
+        _ => attribute_arguments(a, "proc_macro_derive")?.first().copied(),
+    })
+}
+
+/// The file an out-of-line `mod name;` in `path` is read from, relative to
+/// `path`'s directory: its `#[path]` if given, else `foo/mod.rs` when that
+/// exists on disk, else `foo.rs`.
+fn module_file(node: Node, source: &[u8], path: &Path, name: &str) -> String {
+    let dir = match path.file_stem().and_then(|s| s.to_str()) {
+        Some("mod" | "lib" | "main") | None => String::new(),
+        Some(stem) => format!("{}/", stem),
+    };
+    if let Some(value) = attributes(node, source).iter().find_map(|a| a.strip_prefix("path")?.split('"').nth(1)) {
+        // `#[path]` is relative to the declaring file's own directory
+        return format!("./{}", value);
+    }
+    let nested = format!("{}{}/mod.rs", dir, name);
+    if path.parent().is_some_and(|p| p.join(&nested).is_file()) {
+        format!("./{}", nested)
+    } else {
+        format!("./{}{}.rs", dir, name)
     }
 }
 
@@ -257,11 +577,139 @@ pub fn create_user(id: u64, name: String) -> User {
 }
 "#;
         
-        let path = PathBuf::from("test.rs");
-        let result = extractor.extract(&path, code.as_bytes()).unwrap();
+        let path = Path::new("test.rs");
+        let result = extractor.extract(path, code.as_bytes()).unwrap();
         
-        // Should extract 1 struct, 2 methods, 2 functions, 1 impl block
+        // Should extract 1 struct, 2 fields, 2 methods and 1 function
         assert_eq!(result.nodes.len(), 6);
-        assert_eq!(result.edges.len(), 2); // 2 imports
+        assert_eq!(result.nodes.iter().filter(|n| n.kind == NodeKind::Field).count(), 2);
+        assert_eq!(result.nodes.iter().filter(|n| n.kind == NodeKind::Method).count(), 2);
+        assert_eq!(result.references.iter().filter(|r| r.kind == EdgeKind::Imports).count(), 3); // 3 imported items
     }
-}
\ No newline at end of file
+    
+    #[tokio::test]
+    async fn test_extract_rust_modules() {
+        let parser_pool = crate::parser_pool::create_parser_pool();
+        let extractor = RustExtractor::new(parser_pool);
+        let code = r#"
+mod graph;
+
+pub mod model {
+    pub struct Node;
+
+    impl Node {
+        pub fn id(&self) -> u32 { 0 }
+    }
+
+    mod edge {
+        pub fn weight() {}
+    }
+}
+"#;
+        
+        let path = Path::new("/repo/src/lib.rs");
+        let result = extractor.extract(path, code.as_bytes()).unwrap();
+        let qualified = |name: &str| {
+            result.nodes.iter().find(|n| n.name == name).map(|n| n.qualified_name.to_string()).unwrap()
+        };
+        assert_eq!(qualified("graph"), "crate::graph");
+        assert_eq!(qualified("Node"), "crate::model::Node");
+        assert_eq!(qualified("id"), "crate::model::Node::id");
+        assert_eq!(qualified("weight"), "crate::model::edge::weight");
+        
+        // model contains Node, its method and edge; edge contains weight
+        let index = |name: &str| result.nodes.iter().position(|n| n.name == name).unwrap();
+        let mut contains = result.contains.clone();
+        contains.sort();
+        let mut expected = vec![
+            (index("model"), index("Node")),
+            (index("model"), index("id")),
+            (index("model"), index("edge")),
+            (index("edge"), index("weight")),
+        ];
+        expected.sort();
+        assert_eq!(contains, expected);
+        
+        let module = result.references.iter().find(|r| r.kind == EdgeKind::Contains).unwrap();
+        assert_eq!(module.source, Some(index("graph")));
+        assert_eq!(module.target, "./graph.rs");
+    }
+    
+    #[test]
+    fn test_module_path() {
+        assert_eq!(module_path(Path::new("/repo/src/lib.rs")), "crate");
+        assert_eq!(module_path(Path::new("/repo/src/bin/tool.rs")), "crate");
+        assert_eq!(module_path(Path::new("/repo/src/graph.rs")), "crate::graph");
+        assert_eq!(module_path(Path::new("/repo/src/graph/mod.rs")), "crate::graph");
+        assert_eq!(module_path(Path::new("/repo/src/graph/edge.rs")), "crate::graph::edge");
+        assert_eq!(module_path(Path::new("build.rs")), "crate::build");
+    }
+    
+    #[tokio::test]
+    async fn test_extract_rust_items() {
+        let parser_pool = crate::parser_pool::create_parser_pool();
+        let extractor = RustExtractor::new(parser_pool);
+        let code = r#"
+pub enum Shape { Circle, Square }
+pub trait Area { fn area(&self) -> f64; }
+pub const MAX: usize = 10;
+static COUNTER: u32 = 0;
+pub type Shapes = Vec<Shape>;
+union Bits { i: u32, f: f32 }
+
+impl Shape {
+    const SIDES: u32 = 4;
+}
+"#;
+        
+        let path = Path::new("/repo/src/shape.rs");
+        let result = extractor.extract(path, code.as_bytes()).unwrap();
+        let kind = |name: &str| result.nodes.iter().find(|n| n.name == name).map(|n| n.kind);
+        assert_eq!(kind("Shape"), Some(NodeKind::Enum));
+        assert_eq!(kind("Area"), Some(NodeKind::Trait));
+        assert_eq!(kind("MAX"), Some(NodeKind::Constant));
+        assert_eq!(kind("COUNTER"), Some(NodeKind::Variable));
+        assert_eq!(kind("Shapes"), Some(NodeKind::TypeAlias));
+        assert_eq!(kind("Bits"), Some(NodeKind::Union));
+        let sides = result.nodes.iter().find(|n| n.name == "SIDES").unwrap();
+        assert_eq!(sides.qualified_name.to_string(), "crate::shape::Shape::SIDES");
+    }
+    
+    #[tokio::test]
+    async fn test_extract_rust_macros() {
+        let parser_pool = crate::parser_pool::create_parser_pool();
+        let extractor = RustExtractor::new(parser_pool);
+        let code = r#"
+#[macro_export]
+macro_rules! square {
+    ($x:expr) => { $x * $x };
+}
+
+#[proc_macro_derive(Builder, attributes(builder))]
+pub fn derive_builder(input: TokenStream) -> TokenStream {
+    input
+}
+
+#[derive(Debug, serde::Serialize)]
+struct Point { x: i32 }
+
+fn area(side: i32) -> i32 {
+    println!("side {}", side);
+    square!(side)
+}
+"#;
+        
+        let path = Path::new("/repo/src/shapes/area.rs");
+        let result = extractor.extract(path, code.as_bytes()).unwrap();
+        let index = |name: &str| result.nodes.iter().position(|n| n.name == name).unwrap();
+        assert_eq!(result.nodes[index("square")].kind, NodeKind::Macro);
+        assert_eq!(result.nodes[index("square")].qualified_name.to_string(), "crate::square");
+        assert_eq!(result.nodes[index("Builder")].kind, NodeKind::Macro);
+        
+        // Local invocations resolve; the rest are left for cross-file resolution
+        assert_eq!(result.calls, vec![(index("area"), index("square"), 17)]);
+        let mut references: Vec<_> = result.references.iter().map(|r| r.label.as_str()).collect();
+        references.sort();
+        assert_eq!(references, vec!["calls println", "derives Debug", "derives Serialize"]);
+    }
+}
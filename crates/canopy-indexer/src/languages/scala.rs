@@ -0,0 +1,290 @@
+//! Scala language extractor using tree-sitter
+
+use super::{ExtractionResult, LanguageExtractor};
+use canopy_core::{GraphNode, NodeKind, EdgeKind, Language, NodeId};
+use std::path::Path;
+use tree_sitter::{Node, Point};
+use anyhow::Result;
+use crate::extractor::syntax_errors;
+use crate::metadata::{code_lines, node_metadata};
+use crate::parser_pool::{ParserPool, ParseRequest, FileType};
+use crate::calls::local_calls;
+use crate::resolve::Reference;
+
+/// Call expressions and the field holding their callee.
+const CALL_KINDS: &[(&str, &str)] = &[("call_expression", "function")];
+
+/// `name` within the package or type `scope`.
+fn qualify(scope: &str, name: &str) -> String {
+    if scope.is_empty() { name.to_string() } else { format!("{}.{}", scope, name) }
+}
+
+pub struct ScalaExtractor {
+    parser_pool: ParserPool,
+}
+
+impl ScalaExtractor {
+    pub fn new(parser_pool: ParserPool) -> Self {
+        Self { parser_pool }
+    }
+
+    fn point_to_u32(point: Point) -> u32 {
+        (point.row as u32) + 1
+    }
+
+    /// Objects, classes, traits, enums, type aliases, defs and packages.
+    /// A def inside a class, object or trait body is a method.
+    fn extract_declaration(&self, node: Node, source: &[u8], path: &Path, scope: &str) -> Option<GraphNode> {
+        let kind = match node.kind() {
+            "object_definition" | "class_definition" | "package_object" => NodeKind::Class,
+            "trait_definition" => NodeKind::Trait,
+            "enum_definition" => NodeKind::Enum,
+            "type_definition" => NodeKind::TypeAlias,
+            "function_definition" | "function_declaration" if node.parent().is_some_and(|p| p.kind() == "template_body") => NodeKind::Method,
+            "function_definition" | "function_declaration" => NodeKind::Function,
+            _ => return None,
+        };
+        let name = node.child_by_field_name("name")?.utf8_text(source).ok()?;
+
+        Some(GraphNode {
+            id: NodeId(0), // Will be set by graph
+            kind,
+            name: name.to_string(),
+            qualified_name: qualify(scope, name).into(),
+            file_path: path.into(),
+            line_start: Some(Self::point_to_u32(node.start_position())),
+            line_end: Some(Self::point_to_u32(node.end_position())),
+            language: Some(Language::Scala),
+            is_container: !matches!(kind, NodeKind::Function | NodeKind::Method | NodeKind::TypeAlias),
+            child_count: 0,
+            loc: Some(code_lines(node, source)),
+            metadata: node_metadata(node, source, Language::Scala, name),
+        })
+    }
+
+    /// The package a `package a.b` clause names.
+    fn extract_package(&self, node: Node, source: &[u8], path: &Path) -> Option<GraphNode> {
+        if node.kind() != "package_clause" {
+            return None;
+        }
+        let name = node.child_by_field_name("name")?.utf8_text(source).ok()?;
+        let line = Self::point_to_u32(node.start_position());
+        Some(GraphNode {
+            id: NodeId(0), // Will be set by graph
+            kind: NodeKind::Package,
+            name: name.to_string(),
+            qualified_name: name.into(),
+            file_path: path.into(),
+            line_start: Some(line),
+            line_end: Some(Self::point_to_u32(node.end_position())),
+            language: Some(Language::Scala),
+            is_container: true,
+            child_count: 0,
+            loc: None,
+            metadata: Default::default(),
+        })
+    }
+
+    /// Fully qualified paths of an import: `import a.b.{C, D => E}` gives
+    /// `a.b.C` and `a.b.D`, and `import a.b._` gives `a.b._`.
+    fn extract_imports(&self, node: Node, source: &[u8]) -> Vec<String> {
+        let mut imports = Vec::new();
+
+        if node.kind() == "import_declaration" {
+            // Several clauses (`import a.B, c.D`) share one declaration, each
+            // a run of `path` segments followed by an optional selector
+            let mut segments: Vec<&str> = Vec::new();
+            let mut cursor = node.walk();
+            for child in node.children(&mut cursor) {
+                let Ok(text) = child.utf8_text(source) else {
+                    continue;
+                };
+                match child.kind() {
+                    "identifier" | "operator_identifier" => segments.push(text),
+                    "namespace_wildcard" => {
+                        imports.push(format!("{}._", segments.join(".")));
+                        segments.clear();
+                    }
+                    "as_renamed_identifier" => {
+                        if let Some(name) = child.child_by_field_name("name").and_then(|n| n.utf8_text(source).ok()) {
+                            segments.push(name);
+                        }
+                    }
+                    "namespace_selectors" => {
+                        let mut selectors = child.walk();
+                        for selector in child.named_children(&mut selectors) {
+                            let name = match selector.kind() {
+                                "arrow_renamed_identifier" | "as_renamed_identifier" => selector.child_by_field_name("name"),
+                                "identifier" | "operator_identifier" => Some(selector),
+                                _ => None,
+                            };
+                            if let Some(name) = name.and_then(|n| n.utf8_text(source).ok()) {
+                                imports.push(format!("{}.{}", segments.join("."), name));
+                            }
+                        }
+                        segments.clear();
+                    }
+                    "," if !segments.is_empty() => {
+                        imports.push(segments.join("."));
+                        segments.clear();
+                    }
+                    _ => {}
+                }
+            }
+            if !segments.is_empty() {
+                imports.push(segments.join("."));
+            }
+        }
+
+        imports
+    }
+}
+
+impl LanguageExtractor for ScalaExtractor {
+    fn extract(&self, path: &Path, content: &[u8]) -> Result<ExtractionResult> {
+        let source_code = std::str::from_utf8(content)?;
+
+        // Use the parser pool to parse the content
+        let request = ParseRequest {
+            file_type: FileType::Scala,
+            content: source_code.to_string(),
+            path: path.to_path_buf(),
+        };
+
+        let parse_result = self.parser_pool.parse_blocking(request)?;
+        let tree = parse_result.tree;
+
+        let mut nodes = Vec::new();
+        let mut import_modules = Vec::new();
+
+        // Walk the AST
+        let root_node = tree.root_node();
+
+        fn visit_node(
+            node: Node,
+            source: &str,
+            path: &Path,
+            scope: &str,
+            nodes: &mut Vec<GraphNode>,
+            imports: &mut Vec<String>,
+            extractor: &ScalaExtractor,
+        ) {
+            let mut inner_scope = None;
+
+            // Symbols are qualified by their package, and members by their
+            // enclosing types
+            if let Some(package) = extractor.extract_package(node, source.as_bytes(), path) {
+                inner_scope = Some(qualify(scope, &package.name));
+                nodes.push(package);
+            }
+
+            // Extract declarations
+            if let Some(declaration) = extractor.extract_declaration(node, source.as_bytes(), path, scope) {
+                if declaration.is_container {
+                    inner_scope = Some(declaration.qualified_name.to_string());
+                }
+                nodes.push(declaration);
+            }
+
+            // Extract imports
+            imports.extend(extractor.extract_imports(node, source.as_bytes()));
+
+            // Visit children
+            let scope = inner_scope.as_deref().unwrap_or(scope);
+            let mut cursor = node.walk();
+            for child in node.children(&mut cursor) {
+                visit_node(child, source, path, scope, nodes, imports, extractor);
+            }
+        }
+
+        // Start visiting from root. A `package a.b` clause without a body
+        // scopes everything after it, so later siblings take its name
+        let mut scope = String::new();
+        let mut cursor = root_node.walk();
+        for child in root_node.children(&mut cursor) {
+            let before = nodes.len();
+            visit_node(child, source_code, path, &scope, &mut nodes, &mut import_modules, self);
+            if child.kind() == "package_clause" && child.child_by_field_name("body").is_none() {
+                scope = qualify(&scope, &nodes[before].name);
+            }
+        }
+
+        let (calls, mut references) = local_calls(root_node, source_code.as_bytes(), &nodes, CALL_KINDS);
+        references.extend(import_modules.iter().map(|import| Reference::file_level(EdgeKind::Imports, "uses", import)));
+        Ok(ExtractionResult { nodes, edges: Vec::new(), contains: Vec::new(), calls, references, errors: syntax_errors(root_node) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_scala() {
+        let parser_pool = crate::parser_pool::create_parser_pool();
+        let extractor = ScalaExtractor::new(parser_pool);
+        let code = r#"package com.example.app
+
+import scala.collection.mutable
+import com.example.model.{User, Account => Acct}
+import com.example.util._
+
+/** Greets people. */
+trait Greeter {
+  def greet(name: String): String
+}
+
+object Main extends Greeter {
+  private val prefix = "Hello {"
+
+  def greet(name: String): String = {
+    helper()
+    s"$prefix $name"
+  }
+
+  private def helper(): Unit = println("}")
+}
+
+case class Point(x: Int, y: Int)
+
+def topLevel(): Int = 42
+"#;
+
+        let path = Path::new("Main.scala");
+        let result = extractor.extract(path, code.as_bytes()).unwrap();
+        assert!(result.errors.is_empty());
+
+        let find = |name: &str, kind: NodeKind| {
+            result.nodes.iter().find(|n| n.name == name && n.kind == kind).unwrap()
+        };
+        assert_eq!(find("com.example.app", NodeKind::Package).line_start, Some(1));
+        let greeter = find("Greeter", NodeKind::Trait);
+        assert_eq!((greeter.line_start, greeter.line_end), (Some(8), Some(10)));
+        assert_eq!(greeter.metadata.doc_summary.as_deref(), Some("Greets people."));
+        let main = find("Main", NodeKind::Class);
+        assert_eq!(main.qualified_name.as_ref(), "com.example.app.Main");
+        assert_eq!((main.line_start, main.line_end), (Some(12), Some(21)));
+        let greet = result.nodes.iter().find(|n| n.name == "greet" && n.line_start == Some(15)).unwrap();
+        assert_eq!(greet.kind, NodeKind::Method);
+        assert_eq!(greet.line_end, Some(18));
+        assert_eq!(greet.qualified_name.as_ref(), "com.example.app.Main.greet");
+        assert_eq!(find("helper", NodeKind::Method).metadata.visibility, Some(canopy_core::Visibility::Private));
+        assert_eq!(find("Point", NodeKind::Class).line_end, Some(23));
+        assert_eq!(find("topLevel", NodeKind::Function).line_start, Some(25));
+
+        let helper = result.nodes.iter().position(|n| n.name == "helper").unwrap();
+        let greet = result.nodes.iter().position(|n| n.name == "greet" && n.line_start == Some(15)).unwrap();
+        assert!(result.calls.contains(&(greet, helper, 16)));
+
+        let labels: Vec<_> = result.references.iter().filter(|r| r.kind == EdgeKind::Imports).map(|r| r.label.as_str()).collect();
+        assert_eq!(
+            labels,
+            vec![
+                "uses scala.collection.mutable",
+                "uses com.example.model.User",
+                "uses com.example.model.Account",
+                "uses com.example.util._",
+            ]
+        );
+    }
+}
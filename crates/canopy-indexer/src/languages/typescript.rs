@@ -1,11 +1,33 @@
 //! TypeScript language extractor using tree-sitter
 
-use super::{ExtractionResult, LanguageExtractor};
-use canopy_core::{GraphNode, GraphEdge, NodeKind, EdgeSource, Language, NodeId, EdgeId};
-use std::path::PathBuf;
+use super::{decorators, react, ExtractionResult, LanguageExtractor};
+use super::javascript::module_specifier;
+use canopy_core::{GraphNode, NodeKind, EdgeKind, Language, NodeId, Visibility};
+use std::path::Path;
 use tree_sitter::{Node, Point};
 use anyhow::Result;
+use crate::extractor::syntax_errors;
+use crate::metadata::{code_lines, node_metadata};
 use crate::parser_pool::{ParserPool, ParseRequest, FileType};
+use crate::calls::local_calls;
+use crate::test_cases::test_blocks;
+use crate::resolve::Reference;
+use crate::heuristics::routes;
+
+/// Call expressions and the field holding their callee.
+const CALL_KINDS: &[(&str, &str)] = &[("call_expression", "function")];
+
+/// Metadata key holding the name a symbol is exported under (`default` for
+/// default exports).
+pub const EXPORTED_KEY: &str = "exported";
+
+/// Type declarations with the node kind they become and whether they are
+/// containers.
+const DECLARATION_KINDS: &[(&str, NodeKind, bool)] = &[
+    ("interface_declaration", NodeKind::Interface, true),
+    ("enum_declaration", NodeKind::Enum, true),
+    ("type_alias_declaration", NodeKind::TypeAlias, false),
+];
 
 pub struct TypeScriptExtractor {
     parser_pool: ParserPool,
@@ -20,58 +42,142 @@ impl TypeScriptExtractor {
         (point.row as u32) + 1
     }
     
-    fn extract_function(&self, node: Node, source: &[u8], path: &PathBuf) -> Option<GraphNode> {
-        if node.kind() == "function_declaration" || node.kind() == "method_definition" {
-            if let Some(name_node) = node.child_by_field_name("name") {
-                if let Ok(name) = name_node.utf8_text(source) {
-                    let start_pos = Self::point_to_u32(node.start_position());
-                    let end_pos = Self::point_to_u32(node.end_position());
+    fn extract_function(&self, node: Node, source: &[u8], path: &Path, module: &str) -> Option<GraphNode> {
+        if (node.kind() == "function_declaration" || node.kind() == "method_definition")
+            && let Some(name_node) = node.child_by_field_name("name")
+            && let Ok(name) = name_node.utf8_text(source) {
+            let start_pos = Self::point_to_u32(node.start_position());
+            let end_pos = Self::point_to_u32(node.end_position());
                     
-                    return Some(GraphNode {
-                        id: NodeId(0), // Will be set by graph
-                        kind: NodeKind::Function,
-                        name: name.to_string(),
-                        qualified_name: format!("{}::{}", path.display(), name),
-                        file_path: path.clone(),
-                        line_start: Some(start_pos),
-                        line_end: Some(end_pos),
-                        language: Some(Language::TypeScript),
-                        is_container: false,
-                        child_count: 0,
-                        loc: Some(((end_pos - start_pos) as usize) as u32),
-                        metadata: std::collections::HashMap::new(),
-                    });
-                }
-            }
+            return Some(GraphNode {
+                id: NodeId(0), // Will be set by graph
+                kind: NodeKind::Function,
+                name: name.to_string(),
+                qualified_name: format!("{}::{}", module, name).into(),
+                file_path: path.into(),
+                line_start: Some(start_pos),
+                line_end: Some(end_pos),
+                language: Some(Language::TypeScript),
+                is_container: false,
+                child_count: 0,
+                loc: Some(code_lines(node, source)),
+                metadata: node_metadata(node, source, Language::TypeScript, name),
+            });
         }
         None
     }
     
-    fn extract_class(&self, node: Node, source: &[u8], path: &PathBuf) -> Option<GraphNode> {
-        if node.kind() == "class_declaration" {
-            if let Some(name_node) = node.child_by_field_name("name") {
-                if let Ok(name) = name_node.utf8_text(source) {
-                    let start_pos = Self::point_to_u32(node.start_position());
-                    let end_pos = Self::point_to_u32(node.end_position());
+    fn extract_class(&self, node: Node, source: &[u8], path: &Path, module: &str) -> Option<GraphNode> {
+        if node.kind() == "class_declaration"
+            && let Some(name_node) = node.child_by_field_name("name")
+            && let Ok(name) = name_node.utf8_text(source) {
+            let start_pos = Self::point_to_u32(node.start_position());
+            let end_pos = Self::point_to_u32(node.end_position());
                     
-                    return Some(GraphNode {
-                        id: NodeId(0), // Will be set by graph
-                        kind: NodeKind::Class,
-                        name: name.to_string(),
-                        qualified_name: format!("{}::{}", path.display(), name),
-                        file_path: path.clone(),
-                        line_start: Some(start_pos),
-                        line_end: Some(end_pos),
-                        language: Some(Language::TypeScript),
-                        is_container: true,
-                        child_count: 0,
-                        loc: Some(((end_pos - start_pos) as usize) as u32),
-                        metadata: std::collections::HashMap::new(),
-                    });
+            return Some(GraphNode {
+                id: NodeId(0), // Will be set by graph
+                kind: NodeKind::Class,
+                name: name.to_string(),
+                qualified_name: format!("{}::{}", module, name).into(),
+                file_path: path.into(),
+                line_start: Some(start_pos),
+                line_end: Some(end_pos),
+                language: Some(Language::TypeScript),
+                is_container: true,
+                child_count: 0,
+                loc: Some(code_lines(node, source)),
+                metadata: node_metadata(node, source, Language::TypeScript, name),
+            });
+        }
+        None
+    }
+    
+    fn extract_declaration(&self, node: Node, source: &[u8], path: &Path, module: &str) -> Option<GraphNode> {
+        let &(_, kind, is_container) = DECLARATION_KINDS.iter().find(|(k, _, _)| *k == node.kind())?;
+        let name = node.child_by_field_name("name")?.utf8_text(source).ok()?;
+        let start_pos = Self::point_to_u32(node.start_position());
+        let end_pos = Self::point_to_u32(node.end_position());
+        
+        Some(GraphNode {
+            id: NodeId(0), // Will be set by graph
+            kind,
+            name: name.to_string(),
+            qualified_name: format!("{}::{}", module, name).into(),
+            file_path: path.into(),
+            line_start: Some(start_pos),
+            line_end: Some(end_pos),
+            language: Some(Language::TypeScript),
+            is_container,
+            child_count: 0,
+            loc: Some(code_lines(node, source)),
+            metadata: node_metadata(node, source, Language::TypeScript, name),
+        })
+    }
+    
+    /// A function bound to a variable: `const handler = (req) => { ... }`.
+    fn extract_arrow_function(&self, node: Node, source: &[u8], path: &Path, module: &str) -> Option<GraphNode> {
+        if node.kind() != "variable_declarator" {
+            return None;
+        }
+        let value = node.child_by_field_name("value").filter(|v| matches!(v.kind(), "arrow_function" | "function_expression"))?;
+        let name = node.child_by_field_name("name")?.utf8_text(source).ok()?;
+        // Docs and `export` belong to the whole `const` statement
+        let declaration = node.parent().filter(|p| p.kind() == "lexical_declaration").unwrap_or(node);
+        let start_pos = Self::point_to_u32(declaration.start_position());
+        let end_pos = Self::point_to_u32(node.end_position());
+        let mut metadata = node_metadata(declaration, source, Language::TypeScript, name);
+        let body = value.child_by_field_name("body").map_or(value.end_byte(), |b| b.start_byte());
+        let header = declaration.parent().filter(|p| p.kind() == "export_statement").unwrap_or(declaration);
+        metadata.signature = std::str::from_utf8(&source[header.start_byte()..body])
+            .ok()
+            .map(|header| header.split_whitespace().collect::<Vec<_>>().join(" "));
+        
+        Some(GraphNode {
+            id: NodeId(0), // Will be set by graph
+            kind: NodeKind::Function,
+            name: name.to_string(),
+            qualified_name: format!("{}::{}", module, name).into(),
+            file_path: path.into(),
+            line_start: Some(start_pos),
+            line_end: Some(end_pos),
+            language: Some(Language::TypeScript),
+            is_container: false,
+            child_count: 0,
+            loc: Some(code_lines(node, source)),
+            metadata,
+        })
+    }
+    
+    /// The name `node` is exported under if it is declared in an `export`
+    /// statement.
+    fn export_name(node: Node, name: &str) -> Option<String> {
+        let declaration = match node.kind() {
+            "variable_declarator" => node.parent()?,
+            _ => node,
+        };
+        let export = declaration.parent().filter(|p| p.kind() == "export_statement")?;
+        let mut cursor = export.walk();
+        let is_default = export.children(&mut cursor).any(|c| c.kind() == "default");
+        Some(if is_default { "default".to_string() } else { name.to_string() })
+    }
+    
+    /// Local names exported by `export { a, b as c }`, with their exported
+    /// names. Re-exports from another module are imports, not these.
+    fn extract_export_clause(&self, node: Node, source: &[u8]) -> Vec<(String, String)> {
+        let mut exports = Vec::new();
+        if node.kind() == "export_statement" && node.child_by_field_name("source").is_none() {
+            let mut cursor = node.walk();
+            for clause in node.children(&mut cursor).filter(|c| c.kind() == "export_clause") {
+                let mut clause_cursor = clause.walk();
+                for specifier in clause.named_children(&mut clause_cursor) {
+                    let text = |field| specifier.child_by_field_name(field).and_then(|n| n.utf8_text(source).ok());
+                    if let Some(name) = text("name") {
+                        exports.push((name.to_string(), text("alias").unwrap_or(name).to_string()));
+                    }
                 }
             }
         }
-        None
+        exports
     }
     
     fn extract_imports(&self, node: Node, source: &[u8]) -> Vec<String> {
@@ -81,10 +187,9 @@ impl TypeScriptExtractor {
             // Walk through the import statement to find module names
             let mut cursor = node.walk();
             for child in node.children(&mut cursor) {
-                if child.kind() == "string" {
-                    if let Ok(module) = child.utf8_text(source) {
-                        imports.push(module.trim_matches('"').trim_matches('\'').to_string());
-                    }
+                if child.kind() == "string"
+                    && let Ok(module) = child.utf8_text(source) {
+                    imports.push(module.trim_matches('"').trim_matches('\'').to_string());
                 }
             }
         }
@@ -94,22 +199,21 @@ impl TypeScriptExtractor {
 }
 
 impl LanguageExtractor for TypeScriptExtractor {
-    fn extract(&self, path: &PathBuf, content: &[u8]) -> Result<ExtractionResult> {
+    fn extract(&self, path: &Path, content: &[u8]) -> Result<ExtractionResult> {
         let source_code = std::str::from_utf8(content)?;
         
         // Use the parser pool to parse the content
         // Since LanguageExtractor is not async, we use block_in_place to handle the async call
         let request = ParseRequest {
-            file_type: FileType::TypeScript,
+            file_type: if path.extension().is_some_and(|e| e == "tsx") { FileType::Tsx } else { FileType::TypeScript },
             content: source_code.to_string(),
-            path: path.clone(),
+            path: path.to_path_buf(),
         };
         
         let parse_result = self.parser_pool.parse_blocking(request)?;
         let tree = parse_result.tree;
         
         let mut nodes = Vec::new();
-        let mut edges = Vec::new();
         let mut import_modules = Vec::new();
         
         // Walk the AST
@@ -118,50 +222,70 @@ impl LanguageExtractor for TypeScriptExtractor {
         fn visit_node(
             node: Node,
             source: &str,
-            path: &PathBuf,
+            path: &Path,
+            module: &str,
             nodes: &mut Vec<GraphNode>,
-            edges: &mut Vec<GraphEdge>,
             imports: &mut Vec<String>,
             extractor: &TypeScriptExtractor,
         ) {
-            // Extract functions
-            if let Some(function) = extractor.extract_function(node, source.as_bytes(), path) {
-                nodes.push(function);
-            }
-            
-            // Extract classes
-            if let Some(class) = extractor.extract_class(node, source.as_bytes(), path) {
-                nodes.push(class);
+            let source_bytes = source.as_bytes();
+            let symbol = extractor
+                .extract_function(node, source_bytes, path, module)
+                .or_else(|| extractor.extract_class(node, source_bytes, path, module))
+                .or_else(|| extractor.extract_declaration(node, source_bytes, path, module))
+                .or_else(|| extractor.extract_arrow_function(node, source_bytes, path, module));
+            if let Some(mut symbol) = symbol {
+                react::tag(node, source_bytes, &mut symbol);
+                decorators::tag(node, source_bytes, &mut symbol);
+                if let Some(exported) = TypeScriptExtractor::export_name(node, &symbol.name) {
+                    symbol.metadata.extra.insert(EXPORTED_KEY.to_string(), exported);
+                }
+                nodes.push(symbol);
             }
             
             // Extract imports
-            imports.extend(extractor.extract_imports(node, source.as_bytes()));
+            imports.extend(extractor.extract_imports(node, source_bytes));
             
             // Visit children
             let mut cursor = node.walk();
             for child in node.children(&mut cursor) {
-                visit_node(child, source, path, nodes, edges, imports, extractor);
+                visit_node(child, source, path, module, nodes, imports, extractor);
             }
         }
         
-        visit_node(root_node, source_code, path, &mut nodes, &mut edges, &mut import_modules, self);
-        
-        // Create edges for imports
-        for import in import_modules {
-            edges.push(GraphEdge {
-                id: EdgeId(0), // Will be set by graph
-                source: NodeId(0), // Will be set when added to graph
-                target: NodeId(0), // Will be set when added to graph
-                kind: canopy_core::EdgeKind::Imports,
-                edge_source: EdgeSource::Heuristic,
-                confidence: 1.0,
-                label: Some(format!("imports {}", import)),
-                file_path: Some(path.clone()),
-                line: None,
-            });
+        let module = module_specifier(path);
+        visit_node(root_node, source_code, path, &module, &mut nodes, &mut import_modules, self);
+        
+        // `describe`/`it` blocks of test files
+        let mut contains = Vec::new();
+        test_blocks(root_node, source_code.as_bytes(), path, &module, Language::TypeScript, &mut nodes, &mut contains);
+        
+        // `export { ... }` lists are top-level statements
+        let mut cursor = root_node.walk();
+        let export_clauses: Vec<(String, String)> = root_node
+            .children(&mut cursor)
+            .flat_map(|child| self.extract_export_clause(child, source_code.as_bytes()))
+            .collect();
+        
+        // Only unexported top-level declarations are `Internal`; class
+        // members are never listed
+        for (name, exported) in export_clauses {
+            let listed = |n: &GraphNode| n.name == name && n.metadata.visibility == Some(Visibility::Internal);
+            for node in nodes.iter_mut().filter(|n| listed(n)) {
+                node.metadata.visibility = Some(Visibility::Public);
+                node.metadata.extra.insert(EXPORTED_KEY.to_string(), exported.clone());
+            }
         }
         
-        Ok(ExtractionResult { nodes, edges })
+        let (mut calls, mut references) = local_calls(root_node, source_code.as_bytes(), &nodes, CALL_KINDS);
+        let (renders, rendered) = react::renders(root_node, source_code.as_bytes(), &nodes);
+        calls.extend(renders);
+        references.extend(rendered);
+        references.extend(import_modules.iter().map(|import| Reference::file_level(EdgeKind::Imports, "imports", import)));
+        references.extend(nodes.iter().filter(|n| n.metadata.extra.contains_key(EXPORTED_KEY)).map(Reference::export));
+        references.extend(decorators::injections(root_node, source_code.as_bytes(), &nodes));
+        references.extend(routes::route_nodes(&mut nodes));
+        Ok(ExtractionResult { nodes, edges: Vec::new(), contains, calls, references, errors: syntax_errors(root_node) })
     }
 }
 
@@ -194,11 +318,47 @@ export function createController(service: UserService): UserController {
 }
 "#;
         
-        let path = PathBuf::from("test.ts");
-        let result = extractor.extract(&path, code.as_bytes()).unwrap();
+        let path = Path::new("test.ts");
+        let result = extractor.extract(path, code.as_bytes()).unwrap();
         
         // Should extract 1 class, 2 methods, and 1 function
         assert_eq!(result.nodes.len(), 4);
-        assert_eq!(result.edges.len(), 2); // 2 imports
+        assert_eq!(result.references.iter().filter(|r| r.kind == EdgeKind::Imports).count(), 2); // 2 imports
     }
-}
\ No newline at end of file
+    
+    #[tokio::test]
+    async fn test_extract_typescript_declarations() {
+        let parser_pool = crate::parser_pool::create_parser_pool();
+        let extractor = TypeScriptExtractor::new(parser_pool);
+        let code = r#"
+export interface User { id: string; }
+export enum Role { Admin, Member }
+type UserId = string;
+export const loadUser = async (id: UserId): Promise<User> => {
+    return fetchUser(id);
+};
+const fetchUser = function (id: UserId) { return { id }; };
+export default class Store {}
+export { fetchUser as fetch };
+"#;
+        
+        let path = Path::new("users.ts");
+        let result = extractor.extract(path, code.as_bytes()).unwrap();
+        let node = |name: &str| result.nodes.iter().find(|n| n.name == name).unwrap();
+        assert_eq!(node("User").kind, NodeKind::Interface);
+        assert_eq!(node("Role").kind, NodeKind::Enum);
+        assert_eq!(node("UserId").kind, NodeKind::TypeAlias);
+        assert_eq!(node("loadUser").kind, NodeKind::Function);
+        assert_eq!(node("loadUser").metadata.signature.as_deref(), Some("export const loadUser = async (id: UserId): Promise<User> =>"));
+        assert_eq!(node("fetchUser").kind, NodeKind::Function);
+        
+        let exported = |name: &str| node(name).metadata.extra.get(EXPORTED_KEY).map(String::as_str);
+        assert_eq!(exported("User"), Some("User"));
+        assert_eq!(exported("UserId"), None);
+        assert_eq!(exported("Store"), Some("default"));
+        assert_eq!(exported("fetchUser"), Some("fetch"));
+        assert_eq!(node("fetchUser").metadata.visibility, Some(Visibility::Public));
+        assert_eq!(result.references.iter().filter(|r| r.kind == EdgeKind::Exports).count(), 5);
+        assert_eq!(result.calls.len(), 1); // loadUser -> fetchUser
+    }
+}
@@ -1,15 +1,23 @@
 //! File parsing and symbol extraction
 
+pub mod calls;
 pub mod coordinator;
 pub mod tree_cache;
 pub mod extractor;
 pub mod languages;
 pub mod config;
 pub mod heuristics;
+pub mod metadata;
 pub mod parser_pool;
+pub mod project;
+pub mod resolve;
+pub mod test_cases;
+pub mod walk;
 
 #[cfg(test)]
 pub mod tests;
 
 pub use parser_pool::{ParserPool, ParseResult, ParseRequest, FileType, FileParseResult};
 pub use extractor::{ExtractionResult, LanguageExtractor};
+pub use languages::generic::RegexRule;
+pub use languages::registry::{add_regex_rule, register_extractor, ExtractorFactory, FilePattern};
@@ -0,0 +1,412 @@
+//! Typed node metadata derived from syntax trees
+
+use canopy_core::{Language, NodeMetadata, Visibility};
+use tree_sitter::Node;
+
+/// Build metadata (visibility, doc summary, test flag, deprecation,
+/// signature and, for functions, complexity and parameter count) for a
+/// declaration node.
+pub fn node_metadata(node: Node, source: &[u8], language: Language, name: &str) -> NodeMetadata {
+    let doc = doc_comment(node, source, language);
+    let attributes = attributes(node, source, language);
+
+    let deprecated = attributes
+        .iter()
+        .find(|a| a.to_lowercase().contains("deprecated"))
+        .map(|a| deprecation_note(a))
+        .or_else(|| {
+            doc.as_deref()
+                .and_then(|d| d.split("@deprecated").nth(1))
+                .map(|note| note.lines().next().unwrap_or("").trim().to_string())
+        });
+
+    NodeMetadata {
+        visibility: visibility(node, source, language, name),
+        doc_summary: doc.as_deref().and_then(summary),
+        is_test: is_test(language, name, &attributes),
+        deprecated,
+        signature: signature(node, source),
+        complexity: parameter_list(node).map(|_| complexity(node, source)),
+        parameter_count: parameter_list(node).map(|list| parameter_count(list, source, language)),
+        extra: Default::default(),
+    }
+}
+
+/// Lines of a node holding code: blank lines and lines with only comments
+/// are not counted.
+pub fn code_lines(node: Node, source: &[u8]) -> u32 {
+    fn visit(node: Node, source: &[u8], rows: &mut Vec<usize>) {
+        if node.kind().contains("comment") {
+            return;
+        }
+        if node.child_count() == 0 {
+            // Multi-line tokens such as strings cover every row they span
+            if !text(node, source).trim().is_empty() {
+                rows.extend(node.start_position().row..=node.end_position().row);
+            }
+            return;
+        }
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            visit(child, source, rows);
+        }
+    }
+    let mut rows = Vec::new();
+    visit(node, source, &mut rows);
+    rows.sort_unstable();
+    rows.dedup();
+    rows.len() as u32
+}
+
+/// Definitions nested in a function, whose branches are their own.
+const NESTED_DEFINITIONS: &[&str] = &[
+    "function_item", "function_definition", "function_declaration", "method_definition", "method_declaration",
+    "class_definition", "class_declaration", "impl_item",
+];
+
+/// Nodes that add a path through a function.
+const BRANCH_KINDS: &[&str] = &[
+    "if_statement", "if_expression", "elif_clause", "else_if_clause", "conditional_expression", "ternary_expression",
+    "for_statement", "for_in_statement", "for_expression", "enhanced_for_statement", "for_range_loop",
+    "foreach_statement", "while_statement", "while_expression", "do_statement", "catch_clause", "except_clause",
+    "switch_case", "expression_case", "type_case", "communication_case", "for_in_clause", "if_clause",
+];
+
+/// Short-circuit operators, each a branch of its own.
+const SHORT_CIRCUIT: &[&str] = &["&&", "||", "??", "and", "or"];
+
+/// The parameter list of a function, looking through declarators (C, C++)
+/// and variable declarations holding a function (JavaScript, TypeScript).
+fn parameter_list(node: Node) -> Option<Node> {
+    let mut current = node;
+    for _ in 0..4 {
+        if let Some(list) = current.child_by_field_name("parameters").or_else(|| current.child_by_field_name("parameter")) {
+            return Some(list);
+        }
+        current = match current.kind() {
+            "lexical_declaration" | "variable_declaration" => current.named_child(0)?,
+            _ => current.child_by_field_name("declarator").or_else(|| current.child_by_field_name("value"))?,
+        };
+    }
+    None
+}
+
+fn parameter_count(list: Node, source: &[u8], language: Language) -> u32 {
+    // An arrow function's lone unparenthesized parameter
+    if list.kind() == "identifier" {
+        return 1;
+    }
+    let mut cursor = list.walk();
+    let mut count = 0;
+    for (i, param) in list.named_children(&mut cursor).enumerate() {
+        let param_text = text(param, source);
+        count += match param.kind() {
+            kind if kind.contains("comment") => 0,
+            "self_parameter" => 0,
+            _ if language == Language::Python && i == 0 && matches!(param_text, "self" | "cls") => 0,
+            _ if matches!(language, Language::C | Language::Cpp) && param_text == "void" => 0,
+            // `a, b int` declares two
+            "parameter_declaration" if language == Language::Go => {
+                let mut names = param.walk();
+                param.children_by_field_name("name", &mut names).count().max(1) as u32
+            }
+            _ => 1,
+        };
+    }
+    count
+}
+
+/// Cyclomatic complexity of a function: one path, plus one for each
+/// condition, loop, case, handler and short-circuit operator in its body.
+fn complexity(function: Node, source: &[u8]) -> u32 {
+    fn branches(node: Node, source: &[u8]) -> u32 {
+        let own = match node.kind() {
+            kind if BRANCH_KINDS.contains(&kind) => true,
+            // Default cases add no path
+            "switch_label" => text(node, source).starts_with("case"),
+            "case_statement" => node.child_by_field_name("value").is_some(),
+            "match_arm" => node.child_by_field_name("pattern").is_some_and(|p| text(p, source) != "_"),
+            "case_clause" => node.named_child(0).is_some_and(|p| text(p, source) != "_"),
+            "binary_expression" | "boolean_operator" => {
+                node.child_by_field_name("operator").is_some_and(|op| SHORT_CIRCUIT.contains(&op.kind()))
+            }
+            _ => false,
+        };
+        let mut cursor = node.walk();
+        let nested: u32 = node
+            .children(&mut cursor)
+            .filter(|child| !NESTED_DEFINITIONS.contains(&child.kind()))
+            .map(|child| branches(child, source))
+            .sum();
+        u32::from(own) + nested
+    }
+    1 + branches(function, source)
+}
+
+fn text<'a>(node: Node, source: &'a [u8]) -> &'a str {
+    node.utf8_text(source).unwrap_or("")
+}
+
+/// The node that owns leading comments and attributes. Declarations wrapped
+/// in `export` (JS/TS), decorators (Python) or `template<>` (C++) carry
+/// those on the wrapper.
+fn outer(node: Node) -> Node {
+    match node.parent() {
+        Some(parent) if matches!(parent.kind(), "export_statement" | "decorated_definition" | "template_declaration") => parent,
+        _ => node,
+    }
+}
+
+/// Contiguous siblings immediately before the declaration that satisfy `pred`.
+fn leading_siblings<'t>(node: Node<'t>, pred: impl Fn(Node) -> bool) -> Vec<Node<'t>> {
+    let mut result = Vec::new();
+    let mut current = outer(node).prev_sibling();
+    while let Some(sibling) = current {
+        if !pred(sibling) {
+            break;
+        }
+        result.push(sibling);
+        current = sibling.prev_sibling();
+    }
+    result.reverse();
+    result
+}
+
+fn doc_comment(node: Node, source: &[u8], language: Language) -> Option<String> {
+    // Python docstrings are the first statement of the body
+    if language == Language::Python {
+        let body = node.child_by_field_name("body")?;
+        let first = body.named_child(0)?;
+        let string = first.named_child(0).filter(|_| first.kind() == "expression_statement")?;
+        if string.kind() != "string" {
+            return None;
+        }
+        return Some(text(string, source).trim_matches(|c| c == '"' || c == '\'').to_string());
+    }
+
+    let comments = leading_siblings(node, |n| {
+        n.kind().contains("comment") || n.kind() == "attribute_item" || n.kind() == "marker_annotation" || n.kind() == "annotation"
+    });
+    let lines: Vec<String> = comments
+        .into_iter()
+        .filter(|n| n.kind().contains("comment"))
+        .flat_map(|n| {
+            text(n, source)
+                .lines()
+                .map(strip_comment_markers)
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+    let doc = lines.join("\n").trim().to_string();
+    (!doc.is_empty()).then_some(doc)
+}
+
+fn strip_comment_markers(line: &str) -> String {
+    let line = line.trim();
+    let line = line
+        .trim_start_matches("/**")
+        .trim_start_matches("/*!")
+        .trim_start_matches("/*")
+        .trim_start_matches("///")
+        .trim_start_matches("//!")
+        .trim_start_matches("//")
+        .trim_end_matches("*/");
+    line.trim_start_matches('*').trim().to_string()
+}
+
+/// First sentence (or line) of a doc comment, skipping tag lines.
+fn summary(doc: &str) -> Option<String> {
+    let paragraph: Vec<&str> = doc
+        .lines()
+        .map(str::trim)
+        .skip_while(|l| l.is_empty())
+        .take_while(|l| !l.is_empty() && !l.starts_with('@'))
+        .collect();
+    let paragraph = paragraph.join(" ");
+    let sentence = match paragraph.find(". ") {
+        Some(end) => &paragraph[..=end],
+        None => paragraph.as_str(),
+    };
+    let sentence = sentence.trim();
+    (!sentence.is_empty()).then(|| sentence.to_string())
+}
+
+/// Attributes, annotations and decorators attached to the declaration.
+fn attributes(node: Node, source: &[u8], language: Language) -> Vec<String> {
+    let mut found = Vec::new();
+    match language {
+        Language::Rust => {
+            found.extend(
+                leading_siblings(node, |n| n.kind() == "attribute_item" || n.kind().contains("comment"))
+                    .into_iter()
+                    .filter(|n| n.kind() == "attribute_item")
+                    .map(|n| text(n, source).to_string()),
+            );
+        }
+        Language::Java => {
+            if let Some(modifiers) = first_child_of_kind(node, "modifiers") {
+                let mut cursor = modifiers.walk();
+                found.extend(
+                    modifiers
+                        .children(&mut cursor)
+                        .filter(|c| c.kind().ends_with("annotation"))
+                        .map(|c| text(c, source).to_string()),
+                );
+            }
+        }
+        Language::Python => {
+            if let Some(parent) = node.parent().filter(|p| p.kind() == "decorated_definition") {
+                let mut cursor = parent.walk();
+                found.extend(
+                    parent
+                        .children(&mut cursor)
+                        .filter(|c| c.kind() == "decorator")
+                        .map(|c| text(c, source).to_string()),
+                );
+            }
+        }
+        Language::Scala => {
+            let mut cursor = node.walk();
+            found.extend(
+                node.children(&mut cursor)
+                    .filter(|c| c.kind() == "annotation")
+                    .map(|c| text(c, source).to_string()),
+            );
+        }
+        Language::TypeScript | Language::JavaScript => {
+            let mut cursor = node.walk();
+            found.extend(
+                node.children(&mut cursor)
+                    .filter(|c| c.kind() == "decorator")
+                    .map(|c| text(c, source).to_string()),
+            );
+        }
+        _ => {}
+    }
+    found
+}
+
+fn deprecation_note(attribute: &str) -> String {
+    // #[deprecated(note = "use x")] / #[deprecated = "use x"] / @deprecated("use x")
+    attribute
+        .split('"')
+        .nth(1)
+        .unwrap_or("")
+        .to_string()
+}
+
+fn is_test(language: Language, name: &str, attributes: &[String]) -> bool {
+    let annotated = attributes.iter().any(|a| {
+        let a = a.trim_start_matches(['#', '[', '@']);
+        a == "test]" || a.starts_with("test]") || a.ends_with("::test]") || a.starts_with("Test") || a.contains("::test")
+            // A `#[cfg(test)]` module holds tests rather than being one
+            || a == "cfg(test)]"
+    });
+    annotated
+        || match language {
+            Language::Python => name.starts_with("test_") || name.starts_with("Test"),
+            Language::Go => ["Test", "Benchmark", "Fuzz", "Example"].iter().any(|p| name.starts_with(p)),
+            _ => false,
+        }
+}
+
+fn visibility(node: Node, source: &[u8], language: Language, name: &str) -> Option<Visibility> {
+    match language {
+        Language::Rust => Some(match first_child_of_kind(node, "visibility_modifier") {
+            Some(modifier) if text(modifier, source) == "pub" => Visibility::Public,
+            Some(_) => Visibility::Internal,
+            None => Visibility::Private,
+        }),
+        Language::Php => Some(match first_child_of_kind(node, "visibility_modifier").map(|m| text(m, source)) {
+            Some("private") => Visibility::Private,
+            Some("protected") => Visibility::Protected,
+            _ => Visibility::Public,
+        }),
+        Language::Java => {
+            let modifiers = first_child_of_kind(node, "modifiers").map(|m| text(m, source)).unwrap_or("");
+            Some(if modifiers.contains("public") {
+                Visibility::Public
+            } else if modifiers.contains("protected") {
+                Visibility::Protected
+            } else if modifiers.contains("private") {
+                Visibility::Private
+            } else {
+                Visibility::Internal
+            })
+        }
+        Language::Scala => {
+            // `private[pkg]` is visible throughout the package
+            let modifier = first_child_of_kind(node, "modifiers")
+                .and_then(|m| first_child_of_kind(m, "access_modifier"))
+                .or_else(|| first_child_of_kind(node, "access_modifier"));
+            Some(match modifier.map(|m| text(m, source)) {
+                Some(m) if m.contains('[') => Visibility::Internal,
+                Some(m) if m.starts_with("private") => Visibility::Private,
+                Some(m) if m.starts_with("protected") => Visibility::Protected,
+                _ => Visibility::Public,
+            })
+        }
+        Language::TypeScript | Language::JavaScript => {
+            if let Some(modifier) = first_child_of_kind(node, "accessibility_modifier") {
+                return Some(match text(modifier, source) {
+                    "private" => Visibility::Private,
+                    "protected" => Visibility::Protected,
+                    _ => Visibility::Public,
+                });
+            }
+            if name.starts_with('#') {
+                return Some(Visibility::Private);
+            }
+            // Class members default to public; top-level items need `export`
+            let in_class = node.parent().is_some_and(|p| p.kind() == "class_body");
+            let exported = outer(node).kind() == "export_statement";
+            Some(if in_class || exported { Visibility::Public } else { Visibility::Internal })
+        }
+        Language::Python => Some(if name.starts_with("__") && name.ends_with("__") {
+            Visibility::Public
+        } else if name.starts_with('_') {
+            Visibility::Private
+        } else {
+            Visibility::Public
+        }),
+        Language::Go => Some(if name.starts_with(|c: char| c.is_uppercase()) {
+            Visibility::Public
+        } else {
+            Visibility::Internal
+        }),
+        Language::C | Language::Cpp => {
+            let mut cursor = node.walk();
+            let is_static = node
+                .children(&mut cursor)
+                .any(|c| c.kind() == "storage_class_specifier" && text(c, source) == "static");
+            Some(if is_static { Visibility::Private } else { Visibility::Public })
+        }
+        _ => None,
+    }
+}
+
+/// Declaration header: the node's text up to its body, on one line.
+fn signature(node: Node, source: &[u8]) -> Option<String> {
+    let start = node.start_byte();
+    let end = node
+        .child_by_field_name("body")
+        .map(|b| b.start_byte())
+        .unwrap_or_else(|| node.end_byte());
+    let header = std::str::from_utf8(source.get(start..end)?).ok()?;
+    let header = header.split_whitespace().collect::<Vec<_>>().join(" ");
+    let header = header.trim_end_matches([':', '{', '=']).trim_end();
+    if header.is_empty() {
+        return None;
+    }
+    // Bodiless declarations (e.g. arrow functions) can be long; cap them
+    Some(match header.char_indices().nth(200) {
+        Some((cut, _)) => format!("{}…", &header[..cut]),
+        None => header.to_string(),
+    })
+}
+
+fn first_child_of_kind<'t>(node: Node<'t>, kind: &str) -> Option<Node<'t>> {
+    let mut cursor = node.walk();
+    node.children(&mut cursor).find(|c| c.kind() == kind)
+}
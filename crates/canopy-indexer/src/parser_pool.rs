@@ -3,33 +3,50 @@
 //! This module provides a thread-safe way to use tree-sitter parsers in async contexts.
 //! Tree-sitter parsers are not Send + Sync, so we use a channel-based approach with
 //! dedicated parser threads to work around this limitation.
+//!
+//! Each worker keeps one parser per file type, created on first use, so a
+//! request never pays for `set_language` resetting a parser another
+//! language left behind. Requests wait in a bounded queue, and the pool
+//! reports its depth, in-flight parses and per-language latencies through
+//! [`ParserPool::metrics`].
 
-use std::path::PathBuf;
+use std::collections::{hash_map::Entry, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use anyhow::Result;
 use tree_sitter::{Parser, Language};
 
 /// Supported file types for parsing
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum FileType {
     Rust,
     TypeScript,
+    /// TypeScript with JSX.
+    Tsx,
     JavaScript,
     Python,
     Go,
     Java,
     C,
     Cpp,
+    Php,
+    Scala,
+    Markdown,
+    Yaml,
     Generic,
 }
 
 impl FileType {
     /// Determine file type from file extension
-    pub fn from_path(path: &PathBuf) -> Option<Self> {
+    pub fn from_path(path: &Path) -> Option<Self> {
         let ext = path.extension()?.to_str()?;
         match ext {
             "rs" => Some(FileType::Rust),
             "ts" => Some(FileType::TypeScript),
-            "tsx" => Some(FileType::TypeScript),
+            "tsx" => Some(FileType::Tsx),
             "js" => Some(FileType::JavaScript),
             "jsx" => Some(FileType::JavaScript),
             "py" => Some(FileType::Python),
@@ -38,6 +55,10 @@ impl FileType {
             "c" => Some(FileType::C),
             "cpp" | "cc" | "cxx" => Some(FileType::Cpp),
             "h" | "hpp" => Some(FileType::Cpp),
+            "php" => Some(FileType::Php),
+            "scala" | "sc" => Some(FileType::Scala),
+            "md" | "markdown" | "mdx" => Some(FileType::Markdown),
+            "yml" | "yaml" => Some(FileType::Yaml),
             _ => Some(FileType::Generic),
         }
     }
@@ -47,12 +68,17 @@ impl FileType {
         match self {
             FileType::Rust => tree_sitter_rust::LANGUAGE.into(),
             FileType::TypeScript => tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+            FileType::Tsx => tree_sitter_typescript::LANGUAGE_TSX.into(),
             FileType::JavaScript => tree_sitter_javascript::LANGUAGE.into(),
             FileType::Python => tree_sitter_python::LANGUAGE.into(),
             FileType::Go => tree_sitter_go::LANGUAGE.into(),
             FileType::Java => tree_sitter_java::LANGUAGE.into(),
             FileType::C => tree_sitter_c::LANGUAGE.into(),
             FileType::Cpp => tree_sitter_cpp::LANGUAGE.into(),
+            FileType::Php => tree_sitter_php::LANGUAGE_PHP.into(),
+            FileType::Scala => tree_sitter_scala::LANGUAGE.into(),
+            FileType::Markdown => tree_sitter_md::LANGUAGE.into(),
+            FileType::Yaml => tree_sitter_yaml::LANGUAGE.into(),
             FileType::Generic => tree_sitter_rust::LANGUAGE.into(), // Fallback
         }
     }
@@ -82,6 +108,87 @@ pub struct FileParseResult {
     pub path: PathBuf,
 }
 
+/// Requests that may wait for a worker before senders block.
+pub const DEFAULT_QUEUE_CAPACITY: usize = 1024;
+
+/// Upper bounds of the parse latency histogram buckets, in milliseconds.
+/// A final bucket counts slower parses.
+pub const LATENCY_BUCKETS_MS: &[u64] = &[1, 2, 5, 10, 25, 50, 100, 250, 500, 1000];
+
+/// Parse latencies of one file type, bucketed by [`LATENCY_BUCKETS_MS`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LatencyHistogram {
+    /// Parses per bucket, with one more bucket than there are bounds.
+    pub counts: Vec<u64>,
+    pub count: u64,
+    pub total: Duration,
+}
+
+impl LatencyHistogram {
+    fn record(&mut self, latency: Duration) {
+        if self.counts.is_empty() {
+            self.counts = vec![0; LATENCY_BUCKETS_MS.len() + 1];
+        }
+        let millis = latency.as_millis();
+        let bucket = LATENCY_BUCKETS_MS.iter().position(|&b| millis <= b as u128).unwrap_or(LATENCY_BUCKETS_MS.len());
+        self.counts[bucket] += 1;
+        self.count += 1;
+        self.total += latency;
+    }
+
+    /// Mean latency, if anything was parsed.
+    pub fn mean(&self) -> Option<Duration> {
+        (self.count > 0).then(|| self.total / self.count as u32)
+    }
+
+    /// Upper bound of the bucket holding the `quantile` (0.0–1.0) latency;
+    /// `None` when nothing was parsed or it falls in the unbounded bucket.
+    pub fn quantile(&self, quantile: f64) -> Option<Duration> {
+        let rank = (quantile.clamp(0.0, 1.0) * self.count as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (bucket, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return LATENCY_BUCKETS_MS.get(bucket).map(|&ms| Duration::from_millis(ms));
+            }
+        }
+        None
+    }
+}
+
+/// A snapshot of a parser pool's load.
+#[derive(Debug, Clone, Default)]
+pub struct ParserPoolMetrics {
+    /// Requests queued for a worker.
+    pub queue_depth: usize,
+    pub queue_capacity: usize,
+    /// Requests being parsed.
+    pub in_flight: usize,
+    /// Requests turned away by [`ParserPool::try_parse_blocking`].
+    pub rejected: u64,
+    pub latency: HashMap<FileType, LatencyHistogram>,
+}
+
+/// Counters shared by a pool's handles and workers.
+#[derive(Default)]
+struct PoolStats {
+    /// Requests queued and taken off the queue; their difference is the
+    /// queue depth.
+    sent: AtomicUsize,
+    received: AtomicUsize,
+    in_flight: AtomicUsize,
+    rejected: AtomicU64,
+    latency: Mutex<HashMap<FileType, LatencyHistogram>>,
+}
+
+impl PoolStats {
+    fn queue_depth(&self) -> usize {
+        // A request is taken off the queue before its sender counts it
+        let received = self.received.load(Ordering::Relaxed);
+        self.sent.load(Ordering::Relaxed).saturating_sub(received)
+    }
+}
+
 /// Internal message for the parser worker
 #[derive(Debug)]
 struct WorkerRequest {
@@ -90,34 +197,57 @@ struct WorkerRequest {
 }
 
 /// Thread-safe parser pool
+///
+/// Requests queue for the workers in a bounded channel: once
+/// `queue_capacity` requests are waiting, [`ParserPool::parse_blocking`]
+/// blocks and [`ParserPool::try_parse_blocking`] declines, so bulk indexing
+/// cannot outrun the parsers.
 pub struct ParserPool {
-    sender: std::sync::mpsc::Sender<WorkerRequest>,
+    sender: SyncSender<WorkerRequest>,
+    stats: Arc<PoolStats>,
+    queue_capacity: usize,
 }
 
 impl ParserPool {
     /// Create a new parser pool with the specified number of worker threads
     pub fn new(num_workers: usize) -> Self {
-        let (sender, receiver) = std::sync::mpsc::channel::<WorkerRequest>();
-        let receiver = std::sync::Arc::new(std::sync::Mutex::new(receiver));
+        Self::with_queue_capacity(num_workers, DEFAULT_QUEUE_CAPACITY)
+    }
+
+    /// Create a parser pool whose queue holds at most `queue_capacity`
+    /// waiting requests.
+    pub fn with_queue_capacity(num_workers: usize, queue_capacity: usize) -> Self {
+        let (sender, receiver) = std::sync::mpsc::sync_channel::<WorkerRequest>(queue_capacity);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let stats = Arc::new(PoolStats::default());
 
         for i in 0..num_workers {
             let receiver = receiver.clone();
+            let stats = stats.clone();
             std::thread::spawn(move || {
-                Self::worker_thread(i, receiver);
+                Self::worker_thread(i, receiver, stats);
             });
         }
 
-        Self { sender }
+        Self { sender, stats, queue_capacity }
+    }
+
+    /// Current queue depth, in-flight parses and parse latencies.
+    pub fn metrics(&self) -> ParserPoolMetrics {
+        ParserPoolMetrics {
+            queue_depth: self.stats.queue_depth(),
+            queue_capacity: self.queue_capacity,
+            in_flight: self.stats.in_flight.load(Ordering::Relaxed),
+            rejected: self.stats.rejected.load(Ordering::Relaxed),
+            latency: self.stats.latency.lock().unwrap().clone(),
+        }
     }
 
     /// Worker thread function that processes parsing requests
-    fn worker_thread(
-        worker_id: usize,
-        receiver: std::sync::Arc<std::sync::Mutex<std::sync::mpsc::Receiver<WorkerRequest>>>,
-    ) {
+    fn worker_thread(worker_id: usize, receiver: Arc<Mutex<Receiver<WorkerRequest>>>, stats: Arc<PoolStats>) {
         tracing::debug!("Parser worker {} started", worker_id);
         
-        let mut parser = Parser::new();
+        let mut parsers: HashMap<FileType, Parser> = HashMap::new();
         
         loop {
             let request = match receiver.lock().unwrap().recv() {
@@ -129,23 +259,13 @@ impl ParserPool {
             };
 
             let WorkerRequest { request, response_sender } = request;
-            
-            // Set the language for this parser
-            let language = request.file_type.get_language();
-            if let Err(e) = parser.set_language(&language) {
-                let _ = response_sender.send(Err(anyhow::anyhow!("Failed to set language: {}", e)));
-                continue;
-            }
-
-            // Parse the content
-            let result = match parser.parse(&request.content, None) {
-                Some(tree) => Ok(ParseResult {
-                    tree,
-                    path: request.path,
-                    content: request.content,
-                }),
-                None => Err(anyhow::anyhow!("Failed to parse content")),
-            };
+            stats.received.fetch_add(1, Ordering::Relaxed);
+            stats.in_flight.fetch_add(1, Ordering::Relaxed);
+            let started = Instant::now();
+            let file_type = request.file_type.clone();
+            let result = Self::parse_with(&mut parsers, request);
+            stats.latency.lock().unwrap().entry(file_type).or_default().record(started.elapsed());
+            stats.in_flight.fetch_sub(1, Ordering::Relaxed);
 
             // Send the result back
             if response_sender.send(result).is_err() {
@@ -154,8 +274,37 @@ impl ParserPool {
         }
     }
 
+    /// Parse with the worker's parser for the language, setting one up on
+    /// first use.
+    fn parse_with(parsers: &mut HashMap<FileType, Parser>, request: ParseRequest) -> Result<ParseResult> {
+        let parser = match parsers.entry(request.file_type.clone()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let mut parser = Parser::new();
+                parser
+                    .set_language(&request.file_type.get_language())
+                    .map_err(|e| anyhow::anyhow!("Failed to set language: {}", e))?;
+                entry.insert(parser)
+            }
+        };
+
+        match parser.parse(&request.content, None) {
+            Some(tree) => Ok(ParseResult {
+                tree,
+                path: request.path,
+                content: request.content,
+            }),
+            None => {
+                // A halted parse would otherwise resume on the next request
+                parser.reset();
+                Err(anyhow::anyhow!("Failed to parse content"))
+            }
+        }
+    }
+
     /// Parse content synchronously using the parser pool
-    /// Note: This blocks the current thread until parsing is complete
+    /// Note: This blocks the current thread until parsing is complete, and
+    /// until the queue has room
     pub fn parse_blocking(&self, request: ParseRequest) -> Result<ParseResult> {
         let (response_sender, response_receiver) = std::sync::mpsc::channel();
         
@@ -167,43 +316,57 @@ impl ParserPool {
         // Send the request to the worker pool
         self.sender.send(worker_request)
             .map_err(|_| anyhow::anyhow!("Parser pool is shut down"))?;
+        self.stats.sent.fetch_add(1, Ordering::Relaxed);
 
         // Wait for the result
         response_receiver.recv()
             .map_err(|_| anyhow::anyhow!("Parser worker died"))?
     }
 
+    /// Parse content synchronously if the queue has room, returning `None`
+    /// without parsing when it is full so callers can shed load.
+    pub fn try_parse_blocking(&self, request: ParseRequest) -> Result<Option<ParseResult>> {
+        let (response_sender, response_receiver) = std::sync::mpsc::channel();
+        
+        let worker_request = WorkerRequest {
+            request,
+            response_sender,
+        };
+
+        match self.sender.try_send(worker_request) {
+            Ok(()) => {
+                self.stats.sent.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(TrySendError::Full(_)) => {
+                self.stats.rejected.fetch_add(1, Ordering::Relaxed);
+                return Ok(None);
+            }
+            Err(TrySendError::Disconnected(_)) => anyhow::bail!("Parser pool is shut down"),
+        }
+
+        response_receiver.recv()
+            .map_err(|_| anyhow::anyhow!("Parser worker died"))?
+            .map(Some)
+    }
+
     /// Parse content asynchronously using the parser pool
     pub async fn parse(&self, request: ParseRequest) -> Result<ParseResult> {
         // Use spawn_blocking to run the synchronous parse in a blocking context
-        let sender = self.sender.clone();
-        tokio::task::spawn_blocking(move || {
-            let (response_sender, response_receiver) = std::sync::mpsc::channel();
-            
-            let worker_request = WorkerRequest {
-                request,
-                response_sender,
-            };
-
-            // Send the request to the worker pool
-            sender.send(worker_request)
-                .map_err(|_| anyhow::anyhow!("Parser pool is shut down"))?;
-
-            // Wait for the result
-            response_receiver.recv()
-                .map_err(|_| anyhow::anyhow!("Parser worker died"))?
-        }).await.map_err(|e| anyhow::anyhow!("Task join error: {}", e))?
+        let pool = self.clone();
+        tokio::task::spawn_blocking(move || pool.parse_blocking(request))
+            .await
+            .map_err(|e| anyhow::anyhow!("Task join error: {}", e))?
     }
 
     /// Parse a file and return a simplified result with language and AST JSON
-    pub async fn parse_file(&self, path: &PathBuf, content: &str) -> Result<FileParseResult> {
+    pub async fn parse_file(&self, path: &Path, content: &str) -> Result<FileParseResult> {
         let file_type = FileType::from_path(path)
             .ok_or_else(|| anyhow::anyhow!("Cannot determine file type for: {:?}", path))?;
         
         let request = ParseRequest {
             file_type: file_type.clone(),
             content: content.to_string(),
-            path: path.clone(),
+            path: path.to_path_buf(),
         };
         
         let parse_result = self.parse(request).await?;
@@ -213,20 +376,24 @@ impl ParserPool {
         
         let language = match file_type {
             FileType::Rust => "rust",
-            FileType::TypeScript => "typescript",
+            FileType::TypeScript | FileType::Tsx => "typescript",
             FileType::JavaScript => "javascript",
             FileType::Python => "python",
             FileType::Go => "go",
             FileType::Java => "java",
             FileType::C => "c",
             FileType::Cpp => "cpp",
+            FileType::Php => "php",
+            FileType::Scala => "scala",
+            FileType::Markdown => "markdown",
+            FileType::Yaml => "yaml",
             FileType::Generic => "generic",
         };
         
         Ok(FileParseResult {
             language: language.to_string(),
             ast_json,
-            path: path.clone(),
+            path: path.to_path_buf(),
         })
     }
 }
@@ -241,11 +408,10 @@ fn tree_to_json(node: &tree_sitter::Node, source: &str) -> String {
         let _ = write!(writer, "{{\"type\":\"{}\",", node.kind());
         
         // Add text content for leaf nodes
-        if node.child_count() == 0 {
-            if let Ok(text) = node.utf8_text(source.as_bytes()) {
-                let escaped = text.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
-                let _ = write!(writer, "\"text\":\"{}\",", escaped);
-            }
+        if node.child_count() == 0
+            && let Ok(text) = node.utf8_text(source.as_bytes()) {
+            let escaped = text.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+            let _ = write!(writer, "\"text\":\"{}\",", escaped);
         }
         
         let _ = write!(writer, "\"start\":{},\"end\":{},", node.start_byte(), node.end_byte());
@@ -259,7 +425,7 @@ fn tree_to_json(node: &tree_sitter::Node, source: &str) -> String {
                     let _ = write!(writer, ",");
                 }
                 first = false;
-                let _ = write!(writer, "\n");
+                let _ = writeln!(writer);
                 write_node(writer, child, source, depth + 1);
             }
             let _ = write!(writer, "\n{}]", indent);
@@ -277,10 +443,18 @@ impl Clone for ParserPool {
     fn clone(&self) -> Self {
         Self {
             sender: self.sender.clone(),
+            stats: self.stats.clone(),
+            queue_capacity: self.queue_capacity,
         }
     }
 }
 
+/// The process-wide parser pool extractors share, created on first use.
+pub fn shared_parser_pool() -> ParserPool {
+    static POOL: std::sync::OnceLock<ParserPool> = std::sync::OnceLock::new();
+    POOL.get_or_init(create_parser_pool).clone()
+}
+
 /// Convenience function to create a parser pool with default settings
 pub fn create_parser_pool() -> ParserPool {
     // Use number of CPU cores as default worker count, but at least 2
@@ -334,4 +508,80 @@ class MyClass {
         let result = pool.parse(request).await.unwrap();
         assert_eq!(result.tree.root_node().kind(), "program");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_single_worker_switches_languages() {
+        // One worker serves every language from its own parser for each
+        let pool = ParserPool::new(1);
+        for (file_type, content, root) in [
+            (FileType::Rust, "fn main() {}", "source_file"),
+            (FileType::Python, "def main(): pass", "module"),
+            (FileType::Rust, "struct A;", "source_file"),
+            (FileType::Go, "package main", "source_file"),
+            (FileType::Python, "x = 1", "module"),
+        ] {
+            let request = ParseRequest { file_type, content: content.to_string(), path: PathBuf::from("test") };
+            let result = pool.parse_blocking(request).unwrap();
+            assert_eq!(result.tree.root_node().kind(), root);
+            assert!(!result.tree.root_node().has_error(), "{}", content);
+        }
+    }
+
+    #[test]
+    fn test_pool_metrics() {
+        let pool = ParserPool::new(1);
+        for content in ["fn a() {}", "fn b() {}"] {
+            let request = ParseRequest { file_type: FileType::Rust, content: content.to_string(), path: PathBuf::from("test.rs") };
+            pool.parse_blocking(request).unwrap();
+        }
+        let metrics = pool.metrics();
+        assert_eq!((metrics.queue_depth, metrics.in_flight, metrics.queue_capacity), (0, 0, DEFAULT_QUEUE_CAPACITY));
+        let rust = &metrics.latency[&FileType::Rust];
+        assert_eq!(rust.count, 2);
+        assert_eq!(rust.counts.iter().sum::<u64>(), 2);
+        assert!(rust.mean().is_some());
+
+    }
+
+    #[test]
+    fn test_pool_backpressure() {
+        let request = || ParseRequest { file_type: FileType::Rust, content: "fn a() {}".to_string(), path: PathBuf::from("test.rs") };
+        let pool = ParserPool::with_queue_capacity(1, 1);
+
+        // Holding the latency lock stalls the worker once it has parsed,
+        // so one request is in flight and the next fills the queue
+        let latency = pool.stats.latency.lock().unwrap();
+        let senders: Vec<_> = (0..2)
+            .map(|_| {
+                let pool = pool.clone();
+                std::thread::spawn(move || pool.parse_blocking(request()).map(|_| ()))
+            })
+            .collect();
+        while (pool.stats.queue_depth(), pool.stats.in_flight.load(Ordering::Relaxed)) != (1, 1) {
+            std::thread::yield_now();
+        }
+        assert!(pool.try_parse_blocking(request()).unwrap().is_none());
+        assert_eq!(pool.stats.rejected.load(Ordering::Relaxed), 1);
+
+        drop(latency);
+        for sender in senders {
+            sender.join().unwrap().unwrap();
+        }
+        let metrics = pool.metrics();
+        assert_eq!((metrics.queue_depth, metrics.in_flight, metrics.rejected), (0, 0, 1));
+        assert_eq!(metrics.latency[&FileType::Rust].count, 2);
+    }
+
+    #[test]
+    fn test_latency_quantiles() {
+        let mut histogram = LatencyHistogram::default();
+        assert_eq!(histogram.quantile(0.5), None);
+        for millis in [1, 3, 3, 40, 2003] {
+            histogram.record(Duration::from_millis(millis));
+        }
+        assert_eq!(histogram.quantile(0.5), Some(Duration::from_millis(5)));
+        assert_eq!(histogram.quantile(0.8), Some(Duration::from_millis(50)));
+        assert_eq!(histogram.quantile(1.0), None);
+        assert_eq!(histogram.mean(), Some(Duration::from_millis(410)));
+    }
+}
@@ -0,0 +1,184 @@
+//! Project settings from `.canopy.toml`
+//!
+//! The indexer reads these sections of the file at the repository root;
+//! others, such as `[server]`, belong to other parts of Canopy.
+//!
+//! ```toml
+//! # What to leave out of the graph, for every language
+//! [extract]
+//! skip_kinds = ["Constant", "Variable"]
+//! skip_private = false
+//! skip_tests = false
+//!
+//! # Per language: flags replace the defaults above, kinds add to them
+//! [extract.python]
+//! skip_private = true
+//!
+//! # Which files are parsed at all
+//! [index]
+//! max_file_size = 1048576
+//! max_line_length = 500
+//!
+//! # Macros C and C++ conditionals are evaluated against; without this,
+//! # every branch is indexed
+//! [preprocessor]
+//! defines = ["DEBUG", "VERSION=2"]
+//!
+//! # Files sent to an extractor other than their extension's
+//! [extractors]
+//! "*.pyw" = "python"
+//!
+//! # Symbols of formats no extractor handles
+//! [[rules]]
+//! files = "*.flow"
+//! pattern = '^step\s+(?P<name>\w+)'
+//! kind = "Function"
+//! ```
+
+use crate::extractor::ExtractionResult;
+use crate::languages::generic::RegexRule;
+use crate::languages::preprocessor::{self, Defines};
+use crate::languages::registry;
+use crate::test_cases::is_test_file;
+use crate::walk::SkipRules;
+use anyhow::{Context, Result};
+use canopy_core::{GraphNode, Language, NodeKind, Visibility};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// The project settings file, at the repository root.
+pub const CONFIG_FILE: &str = ".canopy.toml";
+
+/// The indexer's sections of `.canopy.toml`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ProjectConfig {
+    pub extract: ExtractionConfig,
+    pub index: IndexLimits,
+    pub preprocessor: PreprocessorConfig,
+    /// Extractor names by the glob of the files they take.
+    pub extractors: BTreeMap<String, String>,
+    pub rules: Vec<RegexRule>,
+}
+
+/// Overrides of the [`SkipRules`] limits.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct IndexLimits {
+    pub max_file_size: Option<u64>,
+    pub max_line_length: Option<usize>,
+}
+
+/// The define set C and C++ conditionals are evaluated against.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PreprocessorConfig {
+    /// `NAME` or `NAME=VALUE` entries, as given with `-D`.
+    pub defines: Option<Vec<String>>,
+}
+
+/// Which extracted symbols make it into the graph.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ExtractionConfig {
+    pub skip_kinds: Vec<NodeKind>,
+    /// Leave out symbols with private visibility.
+    pub skip_private: bool,
+    /// Leave out test files, and test cases in other files.
+    pub skip_tests: bool,
+    /// Overrides by lower-case language name (`python`, `typescript`).
+    #[serde(flatten)]
+    pub languages: HashMap<String, LanguageExtraction>,
+}
+
+/// One language's overrides of the [`ExtractionConfig`] defaults.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct LanguageExtraction {
+    /// Kinds left out in addition to the defaults.
+    pub skip_kinds: Vec<NodeKind>,
+    pub skip_private: Option<bool>,
+    pub skip_tests: Option<bool>,
+}
+
+/// The settings for one language, defaults and overrides combined.
+struct Filter {
+    skip_kinds: Vec<NodeKind>,
+    skip_private: bool,
+    skip_tests: bool,
+}
+
+impl Filter {
+    fn keeps(&self, node: &GraphNode) -> bool {
+        !(self.skip_kinds.contains(&node.kind)
+            || (self.skip_private && node.metadata.visibility == Some(Visibility::Private))
+            || (self.skip_tests && node.kind == NodeKind::TestCase))
+    }
+}
+
+impl ExtractionConfig {
+    fn filter(&self, language: Language) -> Filter {
+        let overrides = self.languages.get(&format!("{:?}", language).to_lowercase());
+        Filter {
+            skip_kinds: self.skip_kinds.iter().chain(overrides.iter().flat_map(|o| &o.skip_kinds)).copied().collect(),
+            skip_private: overrides.and_then(|o| o.skip_private).unwrap_or(self.skip_private),
+            skip_tests: overrides.and_then(|o| o.skip_tests).unwrap_or(self.skip_tests),
+        }
+    }
+
+    /// Whether `path` is left out entirely, as a test file when tests are
+    /// skipped.
+    pub fn skips_file(&self, path: &Path) -> bool {
+        self.filter(Language::from_path(path)).skip_tests && is_test_file(path)
+    }
+
+    /// Drop the symbols of a file's extraction that are left out.
+    pub fn apply(&self, path: &Path, result: &mut ExtractionResult) {
+        let filter = self.filter(Language::from_path(path));
+        result.retain_nodes(|node| filter.keeps(node));
+    }
+}
+
+impl ProjectConfig {
+    pub fn parse(text: &str) -> Result<Self> {
+        Ok(toml::from_str(text)?)
+    }
+
+    /// The settings of the project at `root`, or the defaults if it has no
+    /// settings file.
+    pub fn load(root: &Path) -> Result<Self> {
+        let path = root.join(CONFIG_FILE);
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        let text = std::fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("parsing {}", path.display()))
+    }
+
+    /// The default skip rules with this project's limits.
+    pub fn skip_rules(&self) -> SkipRules {
+        let mut rules = SkipRules::default();
+        if let Some(bytes) = self.index.max_file_size {
+            rules = rules.with_max_file_size(bytes);
+        }
+        if let Some(length) = self.index.max_line_length {
+            rules = rules.with_max_line_length(length);
+        }
+        rules
+    }
+
+    /// Set up the process-wide extractors as this project asks: its
+    /// extractor mappings and regex rules, and its preprocessor defines.
+    pub fn register_extractors(&self) -> Result<()> {
+        let defines = self.preprocessor.defines.as_ref().map(|d| Defines::new(d.iter().map(String::as_str)));
+        preprocessor::set_defines(defines);
+        for (glob, name) in &self.extractors {
+            registry::map_pattern(glob, name)?;
+        }
+        for rule in &self.rules {
+            registry::add_regex_rule(rule.clone());
+        }
+        Ok(())
+    }
+}
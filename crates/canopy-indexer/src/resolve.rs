@@ -0,0 +1,381 @@
+//! Cross-file reference resolution
+//!
+//! An extractor only sees one file, so imports, calls and type references to
+//! symbols defined elsewhere are emitted as [`Reference`]s. Once every file's
+//! symbols are in the [`SymbolTable`], [`crate::coordinator::Coordinator`]
+//! resolves them here by matching the referenced path against where each
+//! candidate is defined: `crate::graph::Graph` is the `Graph` in
+//! `src/graph.rs`, `./utils` the `utils.ts` next to the importing file.
+
+use canopy_core::{EdgeKind, Graph, GraphNode, NodeId, NodeKind, SymbolTable};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+/// Extensions that make a referenced name a file (`util.h`) rather than a
+/// dotted module path (`os.path`).
+const FILE_EXTENSIONS: &[&str] = &[
+    "h", "hh", "hpp", "hxx", "c", "cc", "cpp", "cxx", "js", "jsx", "mjs", "cjs", "ts", "tsx", "json", "py", "rs", "go", "php",
+];
+/// File stems that stand for their directory's module.
+const MODULE_FILES: &[&str] = &["mod", "index", "__init__"];
+
+/// A reference from an extracted file to a symbol that may be defined in
+/// another file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Reference {
+    /// Index into `nodes` of the referring node, or `None` for the file itself.
+    pub source: Option<usize>,
+    pub kind: EdgeKind,
+    /// The referenced path as written (`crate::graph::Graph`, `./utils`,
+    /// `os.path`), or the bare name of a called function.
+    pub target: String,
+    /// Label of the resolved edge.
+    pub label: String,
+    pub line: Option<u32>,
+    /// Whether `target` names a file looked for next to the referring file
+    /// before anywhere else, as a quoted `#include "x.h"` does.
+    #[serde(default)]
+    pub local_first: bool,
+}
+
+impl Reference {
+    /// A reference from the file itself, labelled `"{verb} {target}"`.
+    pub fn file_level(kind: EdgeKind, verb: &str, target: &str) -> Self {
+        Reference {
+            source: None,
+            kind,
+            target: target.to_string(),
+            label: format!("{} {}", verb, target),
+            line: None,
+            local_first: false,
+        }
+    }
+
+    /// A call from `nodes[caller]` to a function not defined in the same file.
+    pub fn call(caller: usize, name: &str, line: u32) -> Self {
+        Reference {
+            source: Some(caller),
+            kind: EdgeKind::Calls,
+            target: name.to_string(),
+            label: format!("calls {}", name),
+            line: Some(line),
+            local_first: false,
+        }
+    }
+
+    /// The file exporting one of its own symbols, found by qualified name.
+    pub fn export(node: &GraphNode) -> Self {
+        Reference {
+            source: None,
+            kind: EdgeKind::Exports,
+            target: node.qualified_name.to_string(),
+            label: format!("exports {}", node.name),
+            line: node.line_start,
+            local_first: false,
+        }
+    }
+}
+
+/// A referenced path split into segments, with the directory it is relative
+/// to for `./x`, `.x`, `crate::x`, `self::x` and `super::x` references.
+struct ReferencePath {
+    anchor: Option<PathBuf>,
+    segments: Vec<String>,
+    /// Whether the last segment names a file, extension included.
+    is_file: bool,
+}
+
+/// Rust's `-` in package names is `_` in paths.
+fn normalize_segment(segment: &str) -> String {
+    segment.replace('-', "_")
+}
+
+/// The directory holding a Rust file's submodules.
+fn rust_module_dir(file: &Path) -> PathBuf {
+    let dir = file.parent().unwrap_or(Path::new("")).to_path_buf();
+    match file.file_stem().and_then(|s| s.to_str()) {
+        Some("mod" | "lib" | "main") | None => dir,
+        Some(stem) => dir.join(stem),
+    }
+}
+
+fn parse_reference(target: &str, from: &Path) -> ReferencePath {
+    let mut rest = target.trim().trim_end_matches("::*").trim_end_matches(".*").trim_end_matches("/*");
+    let is_file = Path::new(rest)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| FILE_EXTENSIONS.contains(&e));
+    let dir = from.parent().unwrap_or(Path::new(""));
+
+    let mut anchor = None;
+    if rest.starts_with("./") || rest.starts_with("../") {
+        let mut base = dir.to_path_buf();
+        loop {
+            if let Some(r) = rest.strip_prefix("./") {
+                rest = r;
+            } else if let Some(r) = rest.strip_prefix("../") {
+                base.pop();
+                rest = r;
+            } else {
+                break;
+            }
+        }
+        anchor = Some(base);
+    } else if rest.starts_with('.') && !is_file {
+        // Python's `from ..pkg import x`: one dot per package level
+        let dots = rest.len() - rest.trim_start_matches('.').len();
+        let mut base = dir.to_path_buf();
+        for _ in 1..dots {
+            base.pop();
+        }
+        rest = &rest[dots..];
+        anchor = Some(base);
+    }
+
+    let mut path = rest.replace("::", "/").replace('\\', "/");
+    if !is_file && !rest.contains(['/', ':', '\\']) {
+        path = path.replace('.', "/");
+    }
+    let mut segments: Vec<String> = path
+        .split('/')
+        .filter(|s| !s.is_empty() && !matches!(*s, "@" | "~"))
+        .map(normalize_segment)
+        .collect();
+
+    match segments.first().map(String::as_str) {
+        Some("crate") => {
+            segments.remove(0);
+            anchor = from.ancestors().find(|a| a.ends_with("src")).or(Some(dir)).map(Path::to_path_buf);
+        }
+        Some("self" | "super") => {
+            let mut base = rust_module_dir(from);
+            if segments[0] == "self" {
+                segments.remove(0);
+            }
+            while segments.first().is_some_and(|s| s == "super") {
+                base.pop();
+                segments.remove(0);
+            }
+            anchor = Some(base);
+        }
+        _ => {}
+    }
+
+    ReferencePath { anchor, segments, is_file }
+}
+
+/// The module path of `file`: its components relative to `anchor`, the last
+/// without its extension when `strip_extension`, and `mod`/`index`/`__init__`
+/// files standing for their directory. `None` if `file` is outside `anchor`.
+fn module_components(file: &Path, anchor: Option<&Path>, strip_extension: bool) -> Option<Vec<String>> {
+    let relative = match anchor {
+        Some(anchor) => file.strip_prefix(anchor).ok()?,
+        None => file,
+    };
+    let mut components: Vec<String> = relative
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(s) => s.to_str().map(normalize_segment),
+            _ => None,
+        })
+        .collect();
+    if strip_extension && let Some(last) = components.pop() {
+        let stem = Path::new(&last).file_stem().and_then(|s| s.to_str()).unwrap_or(&last).to_string();
+        if !MODULE_FILES.contains(&stem.as_str()) {
+            components.push(stem);
+        }
+    }
+    Some(components)
+}
+
+/// `None` unless every segment appears, in order, among `components`; else
+/// how many trailing segments match the trailing components.
+fn path_score(segments: &[String], components: &[String]) -> Option<usize> {
+    let mut remaining = components.iter();
+    if !segments.iter().all(|s| remaining.any(|c| c == s)) {
+        return None;
+    }
+    Some(segments.iter().rev().zip(components.iter().rev()).take_while(|(s, c)| s == c).count())
+}
+
+/// The best-scoring candidate, unless several share the best score.
+fn best_match(candidates: impl IntoIterator<Item = (NodeId, Option<usize>)>) -> Option<NodeId> {
+    let scored: Vec<(NodeId, usize)> = candidates.into_iter().filter_map(|(id, s)| Some((id, s?))).collect();
+    let best = scored.iter().map(|&(_, s)| s).max()?;
+    let mut top = scored.iter().filter(|&&(_, s)| s == best);
+    match (top.next(), top.next()) {
+        (Some(&(id, _)), None) => Some(id),
+        _ => None,
+    }
+}
+
+/// File and directory nodes by module name, for resolving module imports.
+#[derive(Default)]
+pub struct ModuleIndex {
+    modules: HashMap<String, Vec<(NodeId, PathBuf, bool)>>,
+    files: HashMap<PathBuf, NodeId>,
+}
+
+impl ModuleIndex {
+    pub fn new(graph: &Graph) -> Self {
+        let mut index = ModuleIndex::default();
+        for node in graph.all_nodes() {
+            let is_dir = match node.kind {
+                NodeKind::File => false,
+                NodeKind::Directory => true,
+                _ => continue,
+            };
+            let path = node.file_path.to_path_buf();
+            let mut keys = vec![node.name.clone()];
+            if !is_dir {
+                index.files.insert(path.clone(), node.id);
+                keys.extend(module_components(&path, None, true).and_then(|mut c| c.pop()));
+            }
+            for key in keys {
+                let entry = index.modules.entry(normalize_segment(&key)).or_default();
+                if !entry.iter().any(|(id, _, _)| *id == node.id) {
+                    entry.push((node.id, path.clone(), is_dir));
+                }
+            }
+        }
+        index
+    }
+
+    /// The File node for `path`.
+    pub fn file(&self, path: &Path) -> Option<NodeId> {
+        self.files.get(path).copied()
+    }
+
+    /// The File node at `target`, a relative path, from the directory of
+    /// the file `from`.
+    pub fn beside(&self, from: &Path, target: &str) -> Option<NodeId> {
+        let mut path = from.parent()?.to_path_buf();
+        for component in Path::new(target).components() {
+            match component {
+                Component::ParentDir => {
+                    path.pop();
+                }
+                Component::Normal(part) => path.push(part),
+                _ => {}
+            }
+        }
+        self.file(&path)
+    }
+
+    fn resolve(&self, reference: &ReferencePath, from: &Path) -> Option<NodeId> {
+        let last = reference.segments.last()?;
+        let candidates = self.modules.get(last)?.iter().filter(|(_, path, _)| path != from).map(|(id, path, is_dir)| {
+            let components = module_components(path, reference.anchor.as_deref(), !is_dir && !reference.is_file);
+            (*id, components.and_then(|c| path_score(&reference.segments, &c)))
+        });
+        best_match(candidates)
+    }
+}
+
+/// Resolve an import of `target` by `from` to the symbol it names or,
+/// failing that, the file or directory it names.
+pub fn resolve_import(
+    graph: &Graph,
+    symbols: &SymbolTable,
+    modules: &ModuleIndex,
+    from: &Path,
+    target: &str,
+) -> Option<NodeId> {
+    let reference = parse_reference(target, from);
+    let (name, prefix) = reference.segments.split_last()?;
+    if !reference.is_file {
+        // Java's `com.example.User` and Python's `app.models.User` are
+        // qualified names
+        let mut named = symbols
+            .lookup_name(name)
+            .into_iter()
+            .filter_map(|id| graph.node(id))
+            .filter(|node| reference.anchor.is_none() && *node.file_path != *from && node.qualified_name.as_str() == target);
+        if let (Some(node), None) = (named.next(), named.next()) {
+            return Some(node.id);
+        }
+
+        let candidates = symbols
+            .lookup_name(name)
+            .into_iter()
+            .filter_map(|id| graph.node(id))
+            .filter(|node| *node.file_path != *from)
+            .map(|node| {
+                let components = module_components(&node.file_path, reference.anchor.as_deref(), true);
+                (node.id, components.and_then(|c| path_score(prefix, &c)))
+            });
+        if let Some(id) = best_match(candidates) {
+            return Some(id);
+        }
+    }
+    modules.resolve(&reference, from)
+}
+
+/// Resolve a call of `name` to the one function, method or macro of that name
+/// defined in `imported`, the files and directories the caller's file
+/// imports.
+pub fn resolve_call(graph: &Graph, symbols: &SymbolTable, name: &str, imported: &[PathBuf]) -> Option<NodeId> {
+    let mut candidates = symbols
+        .lookup_name(name)
+        .into_iter()
+        .filter_map(|id| graph.node(id))
+        .filter(|node| matches!(node.kind, NodeKind::Function | NodeKind::Method | NodeKind::Macro))
+        .filter(|node| imported.iter().any(|p| node.file_path.starts_with(p)));
+    match (candidates.next(), candidates.next()) {
+        (Some(node), None) => Some(node.id),
+        _ => None,
+    }
+}
+
+/// Resolve a reference to the type `name`: the one type of that name in
+/// `scope`, the referring file and the files it imports, else the one type
+/// of that name anywhere.
+pub fn resolve_type(graph: &Graph, symbols: &SymbolTable, name: &str, scope: &[PathBuf]) -> Option<NodeId> {
+    let types: Vec<&GraphNode> = symbols.lookup_name(name).into_iter().filter_map(|id| graph.node(id)).filter(|node| {
+        matches!(
+            node.kind,
+            NodeKind::Class | NodeKind::Struct | NodeKind::Interface | NodeKind::Trait | NodeKind::Enum | NodeKind::Union | NodeKind::TypeAlias
+        )
+    }).collect();
+    let in_scope: Vec<&GraphNode> = types.iter().copied().filter(|node| scope.iter().any(|p| node.file_path.starts_with(p))).collect();
+    let candidates = if in_scope.is_empty() { types } else { in_scope };
+    match candidates.as_slice() {
+        [node] => Some(node.id),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_reference() {
+        let from = Path::new("/repo/crates/core/src/graph/query.rs");
+        let parsed = |target: &str| {
+            let reference = parse_reference(target, from);
+            (reference.anchor, reference.segments.join("/"), reference.is_file)
+        };
+
+        assert_eq!(parsed("canopy-core::Graph"), (None, "canopy_core/Graph".into(), false));
+        assert_eq!(parsed("crate::model::*"), (Some("/repo/crates/core/src".into()), "model".into(), false));
+        assert_eq!(parsed("super::Node"), (Some("/repo/crates/core/src/graph".into()), "Node".into(), false));
+        assert_eq!(parsed("../utils"), (Some("/repo/crates/core/src".into()), "utils".into(), false));
+        assert_eq!(parsed("..models.User"), (Some("/repo/crates/core/src".into()), "models/User".into(), false));
+        assert_eq!(parsed("os.path"), (None, "os/path".into(), false));
+        assert_eq!(parsed("include/util.h"), (None, "include/util.h".into(), true));
+        assert_eq!(parsed("App\\Models\\User"), (None, "App/Models/User".into(), false));
+    }
+
+    #[test]
+    fn test_path_score() {
+        let components = |path: &str| module_components(Path::new(path), None, true).unwrap();
+        let segments = |s: &[&str]| s.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(path_score(&segments(&["graph"]), &components("src/graph.rs")), Some(1));
+        assert_eq!(path_score(&segments(&["canopy_core"]), &components("crates/canopy-core/src/graph.rs")), Some(0));
+        assert_eq!(path_score(&segments(&["utils"]), &components("web/utils/index.ts")), Some(1));
+        assert_eq!(path_score(&segments(&["std", "fmt"]), &components("src/fmt.rs")), None);
+    }
+}
@@ -0,0 +1,147 @@
+//! Test detection
+//!
+//! Test functions are recognised by their attributes and names when their
+//! metadata is extracted (`#[test]`, `@Test`, `test_*`, Go's `TestX`); here
+//! they become [`NodeKind::TestCase`] nodes. The `describe`/`it` blocks of
+//! JavaScript and TypeScript test files have no function of their own and
+//! are extracted as test cases directly.
+
+use canopy_core::{GraphNode, Language, NodeId, NodeKind, NodeMetadata};
+use std::path::Path;
+use tree_sitter::Node;
+
+/// Calls whose callback is a test or a suite of tests, with their `.only`,
+/// `.skip` and `.each` variants.
+const TEST_BLOCKS: &[&str] = &["describe", "context", "suite", "it", "test", "specify"];
+
+/// Whether `path` is a test file by its name: `_test.go`, `*.spec.ts`,
+/// `*.test.js`, `test_*.py`, `*_test.py` and `*Test.java`, or any file
+/// under a `tests` or `__tests__` directory.
+pub fn is_test_file(path: &Path) -> bool {
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    let stem = name.split('.').next().unwrap_or_default();
+    let in_test_dir = path
+        .parent()
+        .is_some_and(|dir| dir.components().any(|c| matches!(c.as_os_str().to_str(), Some("tests" | "__tests__"))));
+    in_test_dir
+        || name.ends_with("_test.go")
+        || name.contains(".spec.")
+        || name.contains(".test.")
+        || (name.ends_with(".py") && (stem.starts_with("test_") || stem.ends_with("_test")))
+        || name.ends_with("Test.java")
+}
+
+/// Turn the functions and methods of `nodes` flagged as tests into
+/// `TestCase` nodes. Go's naming convention only holds in `_test.go` files.
+pub fn tag_tests(path: &Path, nodes: &mut [GraphNode]) {
+    for node in nodes.iter_mut().filter(|n| matches!(n.kind, NodeKind::Function | NodeKind::Method)) {
+        if node.language == Some(Language::Go) && !is_test_file(path) {
+            node.metadata.is_test = false;
+        }
+        if node.metadata.is_test {
+            node.kind = NodeKind::TestCase;
+        }
+    }
+}
+
+/// What a search for test blocks has collected so far.
+struct Blocks<'a> {
+    source: &'a [u8],
+    path: &'a Path,
+    language: Language,
+    nodes: &'a mut Vec<GraphNode>,
+    contains: &'a mut Vec<(usize, usize)>,
+}
+
+/// Add the `describe`/`it` blocks within `root` as `TestCase` nodes named
+/// by their description and qualified by `module` and their suites, with
+/// Contains from each suite to its tests. Only test files are searched.
+pub fn test_blocks(
+    root: Node,
+    source: &[u8],
+    path: &Path,
+    module: &str,
+    language: Language,
+    nodes: &mut Vec<GraphNode>,
+    contains: &mut Vec<(usize, usize)>,
+) {
+    if is_test_file(path) {
+        let mut blocks = Blocks { source, path, language, nodes, contains };
+        visit(root, module, None, &mut blocks);
+    }
+}
+
+/// The test block a call opens, as its description.
+fn block_name<'s>(node: Node, source: &'s [u8]) -> Option<&'s str> {
+    if node.kind() != "call_expression" {
+        return None;
+    }
+    let callee = node.child_by_field_name("function")?.utf8_text(source).ok()?;
+    let base = callee.split('.').next().unwrap_or(callee);
+    if !TEST_BLOCKS.contains(&base) {
+        return None;
+    }
+    let description = node.child_by_field_name("arguments")?.named_child(0)?;
+    if !matches!(description.kind(), "string" | "template_string") {
+        return None;
+    }
+    description.utf8_text(source).ok().map(|d| d.trim_matches(['"', '\'', '`']))
+}
+
+fn visit(node: Node, scope: &str, parent: Option<usize>, blocks: &mut Blocks) {
+    let (scope, parent) = match block_name(node, blocks.source) {
+        Some(name) => {
+            let index = blocks.nodes.len();
+            let start = node.start_position().row as u32 + 1;
+            let end = node.end_position().row as u32 + 1;
+            let qualified_name = format!("{}::{}", scope, name);
+            blocks.nodes.push(GraphNode {
+                id: NodeId(0), // Will be set by graph
+                kind: NodeKind::TestCase,
+                name: name.to_string(),
+                qualified_name: qualified_name.clone().into(),
+                file_path: blocks.path.into(),
+                line_start: Some(start),
+                line_end: Some(end),
+                language: Some(blocks.language),
+                is_container: false,
+                child_count: 0,
+                loc: Some(end - start),
+                metadata: NodeMetadata { is_test: true, ..Default::default() },
+            });
+            if let Some(parent) = parent {
+                blocks.contains.push((parent, index));
+            }
+            (qualified_name, Some(index))
+        }
+        None => (scope.to_string(), parent),
+    };
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        visit(child, &scope, parent, blocks);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_test_file() {
+        for (path, expected) in [
+            ("server_test.go", true),
+            ("src/cart.spec.ts", true),
+            ("src/cart.test.jsx", true),
+            ("tests/test_api.py", true),
+            ("api_test.py", true),
+            ("src/__tests__/cart.js", true),
+            ("src/test/java/UserServiceTest.java", true),
+            ("crates/core/tests/graph.rs", true),
+            ("src/contest.py", false),
+            ("src/testing.go", false),
+            ("src/cart.ts", false),
+        ] {
+            assert_eq!(is_test_file(Path::new(path)), expected, "{}", path);
+        }
+    }
+}
@@ -1,7 +1,7 @@
 //! Unit tests for canopy-indexer module
 
-use canopy_indexer::languages::get_extractor;
-use canopy_core::{GraphNode, NodeKind, Language};
+use crate::languages::get_extractor;
+use canopy_core::{GraphNode, NodeId, NodeKind};
 use std::path::PathBuf;
 
 #[test]
@@ -15,6 +15,17 @@ fn test_extractor_detection() {
         ("Main.java", "java"),
         ("main.c", "c"),
         ("main.cpp", "cpp"),
+        ("index.php", "php"),
+        ("Main.scala", "scala"),
+        ("schema.graphql", "graphql"),
+        ("README.md", "markdown"),
+        ("config.yaml", "yaml"),
+        ("Cargo.toml", "toml"),
+        ("package.json", "json"),
+        (".env", "dotenv"),
+        ("docker-compose.yml", "docker-compose"),
+        (".github/workflows/ci.yml", "github-actions"),
+        ("openapi.yaml", "openapi"),
         ("unknown.xyz", "generic"),
     ];
     
@@ -23,6 +34,8 @@ fn test_extractor_detection() {
         let extractor = get_extractor(&path);
         
         assert!(extractor.is_some(), "Should have extractor for {}", filename);
+        let name = crate::languages::registry::registry().extractor_name(&path).map(str::to_string);
+        assert_eq!(name.as_deref().unwrap_or("generic"), expected_type, "Wrong extractor for {}", filename);
         
         // Test that we can extract empty content without error
         let result = extractor.unwrap().extract(&path, b"");
@@ -32,7 +45,7 @@ fn test_extractor_detection() {
 
 #[test]
 fn test_rust_extraction() {
-    use canopy_indexer::languages::get_extractor;
+    use crate::languages::get_extractor;
     
     let rust_code = r#"
 fn main() {
@@ -75,13 +88,13 @@ impl User {
         .filter(|n| n.kind == NodeKind::Struct)
         .collect();
     
-    assert!(structs.len() >= 1, "Should extract at least 1 struct");
+    assert!(!structs.is_empty(), "Should extract at least 1 struct");
     assert!(structs.iter().any(|s| s.name == "User"));
 }
 
 #[test]
 fn test_javascript_extraction() {
-    use canopy_indexer::languages::get_extractor;
+    use crate::languages::get_extractor;
     
     let js_code = r#"
 function greet(name) {
@@ -112,20 +125,20 @@ const arrowFunc = () => {
         .filter(|n| n.kind == NodeKind::Function)
         .collect();
     
-    assert!(functions.len() >= 1, "Should extract at least 1 function");
+    assert!(!functions.is_empty(), "Should extract at least 1 function");
     assert!(functions.iter().any(|f| f.name == "greet"));
     
     let classes: Vec<_> = result.nodes.iter()
         .filter(|n| n.kind == NodeKind::Class)
         .collect();
     
-    assert!(classes.len() >= 1, "Should extract at least 1 class");
+    assert!(!classes.is_empty(), "Should extract at least 1 class");
     assert!(classes.iter().any(|c| c.name == "Person"));
 }
 
 #[test]
 fn test_python_extraction() {
-    use canopy_indexer::languages::get_extractor;
+    use crate::languages::get_extractor;
     
     let python_code = r#"
 def greet(name):
@@ -143,9 +156,9 @@ class Person:
     let extractor = get_extractor(&path).unwrap();
     let result = extractor.extract(&path, python_code.as_bytes()).unwrap();
     
-    // Should extract function and class
+    // Should extract functions, methods and class
     let functions: Vec<_> = result.nodes.iter()
-        .filter(|n| n.kind == NodeKind::Function)
+        .filter(|n| matches!(n.kind, NodeKind::Function | NodeKind::Method))
         .collect();
     
     assert!(functions.len() >= 2, "Should extract at least 2 functions");
@@ -154,13 +167,13 @@ class Person:
         .filter(|n| n.kind == NodeKind::Class)
         .collect();
     
-    assert!(classes.len() >= 1, "Should extract at least 1 class");
+    assert!(!classes.is_empty(), "Should extract at least 1 class");
     assert!(classes.iter().any(|c| c.name == "Person"));
 }
 
 #[test]
 fn test_edge_creation() {
-    use canopy_indexer::languages::get_extractor;
+    use crate::languages::get_extractor;
     
     let code = r#"
 import os
@@ -174,21 +187,18 @@ def process_file(path: Path):
     let extractor = get_extractor(&path).unwrap();
     let result = extractor.extract(&path, code.as_bytes()).unwrap();
     
-    // Should extract import relationships
-    assert!(result.edges.len() > 0, "Should extract some edges");
-    
-    // Check for import edges
-    let imports: Vec<_> = result.edges.iter()
-        .filter(|e| e.kind == canopy_core::EdgeKind::Imports)
+    // Check for import references
+    let imports: Vec<_> = result.references.iter()
+        .filter(|r| r.kind == canopy_core::EdgeKind::Imports)
         .collect();
     
-    // Should have edges for the imports
+    // Should have references for the imports
     assert!(!imports.is_empty(), "Should extract import relationships");
 }
 
 #[test]
 fn test_empty_extraction() {
-    use canopy_indexer::languages::get_extractor;
+    use crate::languages::get_extractor;
     
     let path = PathBuf::from("empty.rs");
     let extractor = get_extractor(&path).unwrap();
@@ -201,7 +211,7 @@ fn test_empty_extraction() {
 
 #[test]
 fn test_invalid_utf8_handling() {
-    use canopy_indexer::languages::get_extractor;
+    use crate::languages::get_extractor;
     
     let path = PathBuf::from("binary.rs");
     let extractor = get_extractor(&path).unwrap();
@@ -212,4 +222,1307 @@ fn test_invalid_utf8_handling() {
     
     // Should handle invalid UTF-8 gracefully
     assert!(result.is_err() || result.unwrap().nodes.is_empty());
-}
\ No newline at end of file
+}
+#[test]
+fn test_node_metadata() {
+    use canopy_core::Visibility;
+
+    let rust_code = r#"
+/// Adds two numbers. Returns the sum.
+pub fn add(a: i32, b: i32) -> i32 {
+    a + b
+}
+
+#[deprecated(note = "use add")]
+fn old_add(a: i32) -> i32 {
+    a
+}
+
+#[test]
+fn adds() {
+    assert_eq!(add(1, 2), 3);
+}
+"#;
+
+    let path = PathBuf::from("math.rs");
+    let extractor = get_extractor(&path).unwrap();
+    let result = extractor.extract(&path, rust_code.as_bytes()).unwrap();
+    let node = |name: &str| result.nodes.iter().find(|n| n.name == name).unwrap();
+
+    let add = &node("add").metadata;
+    assert_eq!(add.visibility, Some(Visibility::Public));
+    assert_eq!(add.doc_summary.as_deref(), Some("Adds two numbers."));
+    assert_eq!(add.signature.as_deref(), Some("pub fn add(a: i32, b: i32) -> i32"));
+    assert!(!add.is_test);
+
+    let old_add = &node("old_add").metadata;
+    assert_eq!(old_add.visibility, Some(Visibility::Private));
+    assert_eq!(old_add.deprecated.as_deref(), Some("use add"));
+
+    assert!(node("adds").metadata.is_test);
+
+    let python_code = r#"
+def _helper():
+    """Internal helper."""
+    pass
+
+def test_helper():
+    _helper()
+"#;
+
+    let path = PathBuf::from("helpers.py");
+    let extractor = get_extractor(&path).unwrap();
+    let result = extractor.extract(&path, python_code.as_bytes()).unwrap();
+    let node = |name: &str| result.nodes.iter().find(|n| n.name == name).unwrap();
+
+    let helper = &node("_helper").metadata;
+    assert_eq!(helper.visibility, Some(Visibility::Private));
+    assert_eq!(helper.doc_summary.as_deref(), Some("Internal helper."));
+    assert!(node("test_helper").metadata.is_test);
+}
+
+#[test]
+fn test_function_metrics() {
+    let cases: &[(&str, &str, &str, u32, u32, u32)] = &[
+        (
+            "grade.rs",
+            r#"
+impl Grader {
+    /// Letter grade of a score.
+    fn grade(&self, score: u32, curve: bool) -> char {
+        // Curved scores round up
+
+        let score = if curve && score < 100 { score + 5 } else { score };
+        match score {
+            90.. => 'A',
+            80..=89 => 'B',
+            _ => 'F',
+        }
+    }
+}
+"#,
+            "grade",
+            8,
+            5,
+            2,
+        ),
+        (
+            "grade.py",
+            r#"
+class Grader:
+    def grade(self, score, curve):
+        # Curved scores round up
+        if curve or score > 100:
+            score += 5
+        for bonus in self.bonuses:
+            score += bonus
+
+        try:
+            return self.letter(score)
+        except KeyError:
+            return None
+"#,
+            "grade",
+            9,
+            5,
+            2,
+        ),
+        (
+            "grade.js",
+            r#"
+function grade(score, curve = false) {
+  /* Curved scores round up */
+  switch (true) {
+    case score > 90: return 'A';
+    case score > 80: return curve ? 'A' : 'B';
+    default: return 'F';
+  }
+}
+"#,
+            "grade",
+            7,
+            4,
+            2,
+        ),
+        (
+            "grade.go",
+            r#"
+package grades
+
+func Grade(score, bonus int, curve bool) string {
+	for i := 0; i < bonus; i++ {
+		score++
+	}
+	return "A"
+}
+"#,
+            "Grade",
+            6,
+            2,
+            3,
+        ),
+    ];
+
+    for &(file, code, name, loc, complexity, parameters) in cases {
+        let path = PathBuf::from(file);
+        let result = get_extractor(&path).unwrap().extract(&path, code.as_bytes()).unwrap();
+        let node = result.nodes.iter().find(|n| n.name == name).unwrap();
+        assert_eq!(node.loc, Some(loc), "{}", file);
+        assert_eq!(node.metadata.complexity, Some(complexity), "{}", file);
+        assert_eq!(node.metadata.parameter_count, Some(parameters), "{}", file);
+    }
+}
+
+#[test]
+fn test_extended_node_kinds() {
+    let rust_code = r#"
+pub trait Shape {
+    fn area(&self) -> f64;
+}
+"#;
+    let path = PathBuf::from("shape.rs");
+    let result = get_extractor(&path).unwrap().extract(&path, rust_code.as_bytes()).unwrap();
+    assert!(result.nodes.iter().any(|n| n.kind == NodeKind::Trait && n.name == "Shape"));
+
+    let c_code = r#"
+union Value {
+    int i;
+    float *f;
+};
+
+struct Point {
+    int x;
+    int y;
+};
+"#;
+    let path = PathBuf::from("value.c");
+    let result = get_extractor(&path).unwrap().extract(&path, c_code.as_bytes()).unwrap();
+    assert!(result.nodes.iter().any(|n| n.kind == NodeKind::Union && n.name == "Value"));
+    let mut fields: Vec<_> = result.nodes.iter()
+        .filter(|n| n.kind == NodeKind::Field)
+        .map(|n| n.name.as_str())
+        .collect();
+    fields.sort();
+    assert_eq!(fields, vec!["f", "i", "x", "y"]);
+
+    let go_code = r#"
+package main
+
+var Version = "1.0"
+
+func main() {
+    var local = 1
+    _ = local
+}
+"#;
+    let path = PathBuf::from("main.go");
+    let result = get_extractor(&path).unwrap().extract(&path, go_code.as_bytes()).unwrap();
+    let variables: Vec<_> = result.nodes.iter().filter(|n| n.kind == NodeKind::Variable).collect();
+    assert_eq!(variables.len(), 1);
+    assert_eq!(variables[0].name, "Version");
+}
+
+#[test]
+fn test_rust_reexports() {
+    use canopy_core::EdgeKind;
+
+    let code = r#"
+use std::fmt;
+pub use crate::model::Node;
+"#;
+    let path = PathBuf::from("lib.rs");
+    let result = get_extractor(&path).unwrap().extract(&path, code.as_bytes()).unwrap();
+    let kinds: Vec<_> = result.references.iter().map(|r| r.kind).collect();
+    assert_eq!(kinds, vec![EdgeKind::Imports, EdgeKind::Reexports]);
+    assert_eq!(result.references[1].label, "re-exports crate::model::Node");
+}
+
+#[test]
+fn test_graphql_resolver_linking() {
+    use canopy_core::{EdgeKind, Graph};
+
+    let mut graph = Graph::new();
+    let schema = "type User { id: ID! }\n\ntype Query {\n  user(id: ID!): User\n}\n";
+    let path = PathBuf::from("schema.graphql");
+    let code = "export function resolveUser(id) {}\nexport class UserResolver {}\nfunction unrelated() {}\n";
+    let js_path = PathBuf::from("resolvers.js");
+    for (path, content) in [(&path, schema), (&js_path, code)] {
+        for node in get_extractor(path).unwrap().extract(path, content.as_bytes()).unwrap().nodes {
+            graph.add_node(node);
+        }
+    }
+
+    assert_eq!(crate::heuristics::graphql::link_resolvers(&mut graph).len(), 2);
+    let name = |id| graph.node(id).unwrap().name.clone();
+    let mut links: Vec<_> = graph.all_edges()
+        .filter(|e| e.kind == EdgeKind::RouteHandler)
+        .map(|e| (name(e.source), name(e.target)))
+        .collect();
+    links.sort();
+    assert_eq!(links, vec![
+        ("Query.user".to_string(), "resolveUser".to_string()),
+        ("User".to_string(), "UserResolver".to_string()),
+    ]);
+
+    // Linking again adds nothing
+    assert!(crate::heuristics::graphql::link_resolvers(&mut graph).is_empty());
+}
+
+#[test]
+fn test_markdown_doc_linking() {
+    use canopy_core::{EdgeKind, Graph, GraphNode, NodeMetadata};
+
+    let mut graph = Graph::new();
+    let file = GraphNode {
+        id: canopy_core::NodeId(0),
+        kind: NodeKind::File,
+        name: "graph.rs".to_string(),
+        qualified_name: "graph.rs".into(),
+        file_path: PathBuf::from("/repo/src/graph.rs").into(),
+        line_start: None,
+        line_end: None,
+        language: None,
+        is_container: true,
+        child_count: 0,
+        loc: None,
+        metadata: NodeMetadata::default(),
+    };
+    let file_id = graph.add_node(file);
+
+    let rust_path = PathBuf::from("/repo/src/graph.rs");
+    let rust_code = "pub struct Graph;\n\npub fn add_node(graph: &mut Graph) {}\n";
+    let doc_path = PathBuf::from("/repo/docs/adr-001.md");
+    let doc = "# ADR 1\n\nNodes are added with `Graph::add_node` in [graph](../src/graph.rs).\nThe `id` field is opaque.\n";
+    for (path, content) in [(&rust_path, rust_code), (&doc_path, doc)] {
+        for node in get_extractor(path).unwrap().extract(path, content.as_bytes()).unwrap().nodes {
+            graph.add_node(node);
+        }
+    }
+
+    let added = crate::heuristics::link_graph(&mut graph);
+    let section = graph.all_nodes().find(|n| n.kind == NodeKind::Section).unwrap().id;
+    let targets: Vec<_> = added.iter()
+        .map(|&id| graph.edge(id).unwrap())
+        .inspect(|e| assert_eq!((e.source, e.kind), (section, EdgeKind::SemanticReference)))
+        .map(|e| e.target)
+        .collect();
+    let add_node = graph.find_node_by_name("add_node").unwrap();
+    assert_eq!(targets.len(), 2);
+    assert!(targets.contains(&file_id));
+    assert!(targets.contains(&add_node));
+
+    assert!(crate::heuristics::link_graph(&mut graph).is_empty());
+}
+
+#[test]
+fn test_yaml_config_linking() {
+    use canopy_core::{EdgeKind, Graph};
+
+    let mut graph = Graph::new();
+    let yaml = "database:\n  maxConnections: 10\n  port: 5432\n";
+    let yaml_path = PathBuf::from("config.yaml");
+    let rust_code = "pub struct DatabaseConfig {\n    pub max_connections: u32,\n    pub port: u16,\n}\n";
+    let rust_path = PathBuf::from("config.rs");
+    for (path, content) in [(&yaml_path, yaml), (&rust_path, rust_code)] {
+        let result = get_extractor(path).unwrap().extract(path, content.as_bytes()).unwrap();
+        let ids: Vec<_> = result.nodes.into_iter().map(|n| graph.add_node(n)).collect();
+        for (parent, child) in result.contains {
+            graph.add_edge(canopy_core::GraphEdge {
+                id: canopy_core::EdgeId(0),
+                source: ids[parent],
+                target: ids[child],
+                kind: EdgeKind::Contains,
+                edge_source: canopy_core::EdgeSource::Structural,
+                confidence: 1.0,
+                label: None,
+                file_path: None,
+                line: None,
+            });
+        }
+    }
+
+    let database = graph.find_node_by_qualified("config.yaml::database").unwrap();
+    let max_connections = graph.find_node_by_qualified("config.yaml::database.maxConnections").unwrap();
+    assert!(graph.has_edge_between(database, max_connections, EdgeKind::Contains));
+
+    crate::heuristics::link_graph(&mut graph);
+    let name = |id| graph.node(id).unwrap().name.clone();
+    let mut links: Vec<_> = graph.all_edges()
+        .filter(|e| e.kind == EdgeKind::ConfiguresArgument)
+        .map(|e| (name(e.source), name(e.target)))
+        .collect();
+    links.sort();
+    // `port` is too short to link
+    assert_eq!(links, vec![
+        ("database".to_string(), "DatabaseConfig".to_string()),
+        ("maxConnections".to_string(), "max_connections".to_string()),
+    ]);
+}
+
+#[test]
+fn test_cargo_package_linking() {
+    use canopy_core::{EdgeKind, Graph};
+
+    let mut graph = Graph::new();
+    let core = "[package]\nname = \"canopy-core\"\n\n[dependencies]\nserde = \"1\"\n";
+    let server = "[package]\nname = \"canopy-server\"\n\n[dependencies]\ncanopy-core = { path = \"../canopy-core\" }\naxum = \"0.7\"\n\n[features]\ndefault = []\n";
+    for (path, content) in [("crates/canopy-core/Cargo.toml", core), ("crates/canopy-server/Cargo.toml", server)] {
+        let path = PathBuf::from(path);
+        let result = get_extractor(&path).unwrap().extract(&path, content.as_bytes()).unwrap();
+        for node in result.nodes {
+            graph.add_node(node);
+        }
+    }
+    assert!(graph.find_node_by_qualified("crates/canopy-server/Cargo.toml::features.default").is_some());
+
+    crate::heuristics::link_graph(&mut graph);
+    let name = |id| graph.node(id).unwrap().name.clone();
+    let links: Vec<_> = graph.all_edges()
+        .filter(|e| e.kind == EdgeKind::Imports)
+        .map(|e| (name(e.source), name(e.target), e.label.clone().unwrap_or_default()))
+        .collect();
+    // External crates are not in the graph and stay unlinked
+    assert_eq!(links, vec![(
+        "canopy-server".to_string(),
+        "canopy-core".to_string(),
+        "depends on canopy-core".to_string(),
+    )]);
+}
+
+#[test]
+fn test_package_script_linking() {
+    use canopy_core::{EdgeKind, Graph, GraphNode, NodeMetadata};
+
+    let mut graph = Graph::new();
+    graph.add_node(GraphNode {
+        id: canopy_core::NodeId(0),
+        kind: NodeKind::File,
+        name: "bundle.mjs".to_string(),
+        qualified_name: "bundle.mjs".into(),
+        file_path: PathBuf::from("/repo/ui/scripts/bundle.mjs").into(),
+        line_start: None,
+        line_end: None,
+        language: None,
+        is_container: true,
+        child_count: 0,
+        loc: None,
+        metadata: NodeMetadata::default(),
+    });
+
+    let ui = r#"{
+  "name": "ui",
+  "scripts": {
+    "build": "codegen && node ./scripts/bundle.mjs",
+    "release": "npm run build && npm publish"
+  },
+  "devDependencies": { "codegen": "workspace:*", "esbuild": "^0.20" }
+}"#;
+    let codegen = r#"{ "name": "codegen", "bin": { "codegen": "cli.js" } }"#;
+    for (path, content) in [("/repo/ui/package.json", ui), ("/repo/codegen/package.json", codegen)] {
+        let path = PathBuf::from(path);
+        let result = get_extractor(&path).unwrap().extract(&path, content.as_bytes()).unwrap();
+        for node in result.nodes {
+            graph.add_node(node);
+        }
+    }
+
+    crate::heuristics::link_graph(&mut graph);
+    let name = |id| graph.node(id).unwrap().name.clone();
+    let mut links: Vec<_> = graph.all_edges()
+        .filter(|e| matches!(e.kind, EdgeKind::Calls | EdgeKind::Imports))
+        .map(|e| (name(e.source), e.label.clone().unwrap_or_default(), name(e.target)))
+        .collect();
+    links.sort();
+    assert_eq!(links, vec![
+        ("build".to_string(), "runs ./scripts/bundle.mjs".to_string(), "bundle.mjs".to_string()),
+        ("build".to_string(), "runs codegen".to_string(), "codegen".to_string()),
+        ("release".to_string(), "runs build".to_string(), "build".to_string()),
+        ("ui".to_string(), "depends on codegen".to_string(), "codegen".to_string()),
+    ]);
+}
+
+#[test]
+fn test_env_variable_linking() {
+    use canopy_core::{EdgeKind, Graph};
+
+    let mut graph = Graph::new();
+    let files = [
+        (".env", "DATABASE_URL=postgres://localhost/canopy\nPORT=8080\nUNUSED=1\n"),
+        ("server.ts", "export function listen() {\n  return Number(process.env.PORT);\n}\n"),
+        ("db.py", "import os\n\ndef connect():\n    return os.environ[\"DATABASE_URL\"]\n"),
+        ("main.rs", "fn database_url() -> String {\n    std::env::var(\"DATABASE_URL\").unwrap()\n}\n"),
+    ];
+    for (path, content) in files {
+        let path = PathBuf::from(path);
+        let mut result = get_extractor(&path).unwrap().extract(&path, content.as_bytes()).unwrap();
+        crate::heuristics::env_vars::annotate_env_reads(&mut result, content);
+        for node in result.nodes {
+            graph.add_node(node);
+        }
+    }
+
+    crate::heuristics::link_graph(&mut graph);
+    let name = |id| graph.node(id).unwrap().name.clone();
+    let mut links: Vec<_> = graph.all_edges()
+        .filter(|e| e.kind == EdgeKind::EnvironmentBinding)
+        .map(|e| (name(e.source), name(e.target)))
+        .collect();
+    links.sort();
+    assert_eq!(links, vec![
+        ("DATABASE_URL".to_string(), "connect".to_string()),
+        ("DATABASE_URL".to_string(), "database_url".to_string()),
+        ("PORT".to_string(), "listen".to_string()),
+    ]);
+}
+
+#[test]
+fn test_compose_service_linking() {
+    use canopy_core::{EdgeKind, Graph, GraphNode, NodeMetadata};
+
+    let mut graph = Graph::new();
+    graph.add_node(GraphNode {
+        id: canopy_core::NodeId(0),
+        kind: NodeKind::Directory,
+        name: "src".to_string(),
+        qualified_name: "src".into(),
+        file_path: PathBuf::from("/repo/api/src").into(),
+        line_start: None,
+        line_end: None,
+        language: None,
+        is_container: true,
+        child_count: 0,
+        loc: None,
+        metadata: NodeMetadata::default(),
+    });
+
+    let compose = "services:\n  api:\n    volumes:\n      - ./api/src:/app/src\n    environment:\n      - DATABASE_URL\n    depends_on: [db]\n  db:\n    image: postgres\n";
+    for (path, content) in [("/repo/docker-compose.yml", compose), ("/repo/.env", "DATABASE_URL=postgres://db\n")] {
+        let path = PathBuf::from(path);
+        let result = get_extractor(&path).unwrap().extract(&path, content.as_bytes()).unwrap();
+        for node in result.nodes {
+            graph.add_node(node);
+        }
+    }
+
+    crate::heuristics::link_graph(&mut graph);
+    let name = |id| graph.node(id).unwrap().name.clone();
+    let mut links: Vec<_> = graph.all_edges()
+        .map(|e| (e.kind, name(e.source), name(e.target)))
+        .collect();
+    links.sort_by(|a, b| (a.1.as_str(), a.2.as_str()).cmp(&(b.1.as_str(), b.2.as_str())));
+    assert_eq!(links, vec![
+        (EdgeKind::EnvironmentBinding, "DATABASE_URL".to_string(), "api".to_string()),
+        (EdgeKind::Imports, "api".to_string(), "db".to_string()),
+        (EdgeKind::DockerMount, "api".to_string(), "src".to_string()),
+    ]);
+}
+
+#[test]
+fn test_ci_job_linking() {
+    use canopy_core::{EdgeKind, Graph, GraphNode, NodeMetadata};
+
+    let mut graph = Graph::new();
+    for (kind, path) in [(NodeKind::Directory, "/repo/crates"), (NodeKind::File, "/repo/ui/scripts/deploy.sh")] {
+        graph.add_node(GraphNode {
+            id: canopy_core::NodeId(0),
+            kind,
+            name: path.rsplit('/').next().unwrap().to_string(),
+            qualified_name: path.into(),
+            file_path: PathBuf::from(path).into(),
+            line_start: None,
+            line_end: None,
+            language: None,
+            is_container: true,
+            child_count: 0,
+            loc: None,
+            metadata: NodeMetadata::default(),
+        });
+    }
+
+    let workflow = "on:\n  push:\n    paths: [\"crates/**\", \"!docs/**\"]\njobs:\n  test:\n    steps:\n      - run: cargo test -p canopy-core\n  ui:\n    defaults:\n      run:\n        working-directory: ui\n    steps:\n      - run: npm run build && ./scripts/deploy.sh\n";
+    let files = [
+        ("/repo/.github/workflows/ci.yml", workflow),
+        ("/repo/crates/canopy-core/Cargo.toml", "[package]\nname = \"canopy-core\"\n"),
+        ("/repo/ui/package.json", "{ \"name\": \"ui\", \"scripts\": { \"build\": \"vite build\" } }"),
+    ];
+    for (path, content) in files {
+        let path = PathBuf::from(path);
+        let result = get_extractor(&path).unwrap().extract(&path, content.as_bytes()).unwrap();
+        for node in result.nodes {
+            graph.add_node(node);
+        }
+    }
+
+    crate::heuristics::link_graph(&mut graph);
+    let name = |id| graph.node(id).unwrap().name.clone();
+    let mut links: Vec<_> = graph.all_edges()
+        .map(|e| (e.kind, name(e.source), e.label.clone().unwrap_or_default(), name(e.target)))
+        .collect();
+    links.sort_by(|a, b| (a.1.as_str(), a.2.as_str()).cmp(&(b.1.as_str(), b.2.as_str())));
+    assert_eq!(links, vec![
+        (EdgeKind::Calls, "test".to_string(), "runs canopy-core".to_string(), "canopy-core".to_string()),
+        (EdgeKind::CITrigger, "test".to_string(), "triggered by crates/**".to_string(), "crates".to_string()),
+        (EdgeKind::Calls, "ui".to_string(), "runs ./scripts/deploy.sh".to_string(), "deploy.sh".to_string()),
+        (EdgeKind::Calls, "ui".to_string(), "runs build".to_string(), "build".to_string()),
+        (EdgeKind::CITrigger, "ui".to_string(), "triggered by crates/**".to_string(), "crates".to_string()),
+    ]);
+}
+
+#[test]
+fn test_openapi_route_linking() {
+    use canopy_core::{EdgeKind, Graph};
+
+    let mut graph = Graph::new();
+    let spec = "openapi: 3.0.0\npaths:\n  /users/{id}:\n    get:\n      operationId: getUserById\n  /health:\n    get:\n      operationId: health\n";
+    let handlers = "def get_user_by_id(user_id):\n    pass\n\ndef status():\n    pass\n";
+    for (path, content) in [("api.yaml", spec), ("handlers.py", handlers)] {
+        let path = PathBuf::from(path);
+        let result = get_extractor(&path).unwrap().extract(&path, content.as_bytes()).unwrap();
+        for node in result.nodes {
+            graph.add_node(node);
+        }
+    }
+    // A framework extractor records what a handler registers
+    let status = graph.find_node_by_name("status").unwrap();
+    graph.node_mut(status).unwrap().metadata.extra
+        .insert(crate::heuristics::routes::ROUTE_KEY.to_string(), "get /health/".to_string());
+
+    crate::heuristics::link_graph(&mut graph);
+    let name = |id| graph.node(id).unwrap().name.clone();
+    let mut links: Vec<_> = graph.all_edges()
+        .filter(|e| e.kind == EdgeKind::RouteHandler)
+        .map(|e| (name(e.source), name(e.target), e.confidence))
+        .collect();
+    links.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(links, vec![
+        ("GET /health".to_string(), "status".to_string(), 0.95),
+        ("GET /users/{id}".to_string(), "get_user_by_id".to_string(), 0.8),
+    ]);
+}
+
+#[test]
+fn test_local_call_extraction() {
+    let cases = [
+        ("lib.rs", "fn helper() -> u32 { 1 }\n\nfn run() -> u32 {\n    helper() + helper()\n}\n", 4),
+        ("app.ts", "function helper(): number { return 1; }\n\nexport function run() {\n  return helper();\n}\n", 4),
+        ("app.js", "function helper() { return 1; }\n\nfunction run() {\n  return helper();\n}\n", 4),
+        ("app.py", "def helper():\n    return 1\n\ndef run():\n    return helper()\n", 5),
+        ("main.go", "package main\n\nfunc helper() int { return 1 }\n\nfunc run() int {\n\treturn helper()\n}\n", 6),
+        ("App.java", "class App {\n  int helper() { return 1; }\n\n  int run() {\n    return this.helper();\n  }\n}\n", 5),
+        ("main.c", "int helper(void) { return 1; }\n\nint run(void) {\n    return helper();\n}\n", 4),
+        ("main.cpp", "int helper() { return 1; }\n\nint run() {\n    return helper();\n}\n", 4),
+        ("app.php", "<?php\nfunction helper() { return 1; }\n\nfunction run() {\n    return helper();\n}\n", 5),
+    ];
+    for (file, code, line) in cases {
+        let path = PathBuf::from(file);
+        let result = get_extractor(&path).unwrap().extract(&path, code.as_bytes()).unwrap();
+        let calls: Vec<_> = result.calls.iter()
+            .map(|&(caller, callee, line)| (result.nodes[caller].name.as_str(), result.nodes[callee].name.as_str(), line))
+            .collect();
+        assert_eq!(calls, vec![("run", "helper", line)], "calls in {}", file);
+    }
+}
+
+/// Add the File node the filesystem walk would create for `file`.
+fn add_file_node(graph: &mut canopy_core::Graph, file: &str) {
+    graph.add_node(GraphNode {
+        id: NodeId(0),
+        kind: NodeKind::File,
+        name: file.rsplit('/').next().unwrap().to_string(),
+        qualified_name: file.into(),
+        file_path: std::path::Path::new(file).into(),
+        line_start: None,
+        line_end: None,
+        language: None,
+        is_container: true,
+        child_count: 0,
+        loc: None,
+        metadata: canopy_core::NodeMetadata::default(),
+    });
+}
+
+/// Extract `code` as `file`, adding its nodes to the graph and registering
+/// its references, as the watcher does.
+fn index_source(graph: &mut canopy_core::Graph, coordinator: &mut crate::coordinator::Coordinator, file: &str, code: &str) {
+    let path = std::path::Path::new(file);
+    let result = get_extractor(path).unwrap().extract(path, code.as_bytes()).unwrap();
+    let nodes: Vec<GraphNode> = result.nodes.into_iter()
+        .map(|mut node| {
+            node.id = graph.add_node(node.clone());
+            node
+        })
+        .collect();
+    coordinator.register_file(path, &nodes, result.references);
+}
+
+#[test]
+fn test_cross_file_resolution() {
+    use crate::coordinator::Coordinator;
+    use canopy_core::{EdgeKind, Graph};
+    use std::path::Path;
+
+    let files = [
+        ("/repo/src/graph.rs", "pub struct Graph;\n\npub fn build_graph() -> Graph {\n    Graph\n}\n"),
+        ("/repo/src/main.rs", "mod graph;\nuse crate::graph::{Graph, build_graph};\n\nfn main() {\n    let _g: Graph = build_graph();\n}\n"),
+        ("/repo/web/app.ts", "import { format } from './utils';\n"),
+        ("/repo/web/utils.ts", "export function format() {}\n"),
+    ];
+    let mut graph = Graph::new();
+    let mut coordinator = Coordinator::new();
+    for (file, code) in files {
+        add_file_node(&mut graph, file);
+        index_source(&mut graph, &mut coordinator, file, code);
+    }
+    coordinator.resolve_references(&mut graph);
+
+    let links = |graph: &Graph| {
+        let name = |id| graph.node(id).unwrap().name.clone();
+        let mut links: Vec<_> = graph.all_edges()
+            .map(|e| (e.kind, name(e.source), e.label.clone().unwrap_or_default(), name(e.target)))
+            .collect();
+        links.sort_by(|a, b| (a.1.as_str(), a.2.as_str()).cmp(&(b.1.as_str(), b.2.as_str())));
+        links
+    };
+    let expected = vec![
+        (EdgeKind::Imports, "app.ts".to_string(), "imports ./utils".to_string(), "utils.ts".to_string()),
+        (EdgeKind::Contains, "graph".to_string(), "contains graph.rs".to_string(), "graph.rs".to_string()),
+        (EdgeKind::Calls, "main".to_string(), "calls build_graph".to_string(), "build_graph".to_string()),
+        (EdgeKind::Imports, "main.rs".to_string(), "uses crate::graph::Graph".to_string(), "Graph".to_string()),
+        (EdgeKind::Imports, "main.rs".to_string(), "uses crate::graph::build_graph".to_string(), "build_graph".to_string()),
+        (EdgeKind::Exports, "utils.ts".to_string(), "exports format".to_string(), "format".to_string()),
+    ];
+    assert_eq!(links(&graph), expected);
+
+    // Re-indexing the target file drops the edges into it; the next pass
+    // resolves them against the new nodes
+    let old: Vec<_> = graph.all_nodes().filter(|n| *n.file_path == *Path::new(files[0].0) && n.kind != NodeKind::File)
+        .map(|n| n.id)
+        .collect();
+    for id in old {
+        graph.remove_node(id);
+    }
+    index_source(&mut graph, &mut coordinator, files[0].0, files[0].1);
+    assert_eq!(links(&graph).len(), 3);
+    assert_eq!(coordinator.resolve_references(&mut graph).len(), 3);
+    assert_eq!(links(&graph), expected);
+}
+
+#[test]
+fn test_python_type_hints() {
+    use crate::coordinator::Coordinator;
+    use canopy_core::{EdgeKind, Graph};
+
+    let files = [
+        ("/repo/app/models.py", "class User:\n    pass\n\nclass Order:\n    pass\n"),
+        ("/repo/legacy/models.py", "class User:\n    pass\n"),
+        (
+            "/repo/app/service.py",
+            "from app.models import User, Order\n\ndef load(user_id: int) -> Optional[User]:\n    pass\n\ndef save(user: \"User\", orders: list[Order] = None) -> None:\n    pass\n",
+        ),
+    ];
+    let mut graph = Graph::new();
+    let mut coordinator = Coordinator::new();
+    for (file, code) in files {
+        add_file_node(&mut graph, file);
+        index_source(&mut graph, &mut coordinator, file, code);
+    }
+    coordinator.resolve_references(&mut graph);
+
+    // `User` is defined twice; the import picks the one in app/models.py
+    let mut hints: Vec<_> = graph.all_edges()
+        .filter(|e| e.kind == EdgeKind::TypeReference)
+        .map(|e| {
+            let target = graph.node(e.target).unwrap();
+            (graph.node(e.source).unwrap().name.clone(), e.label.clone().unwrap(), target.file_path.display().to_string())
+        })
+        .collect();
+    hints.sort();
+    let models = "/repo/app/models.py".to_string();
+    assert_eq!(hints, vec![
+        ("load".to_string(), "returns User".to_string(), models.clone()),
+        ("save".to_string(), "takes Order".to_string(), models.clone()),
+        ("save".to_string(), "takes User".to_string(), models),
+    ]);
+}
+
+#[test]
+fn test_python_routes() {
+    use crate::coordinator::Coordinator;
+    use crate::heuristics::routes::ROUTE_KEY;
+    use canopy_core::{EdgeKind, Graph};
+
+    let files = [
+        (
+            "/repo/api/app.py",
+            r#"
+@app.route("/users", methods=["GET", "POST"])
+def users():
+    pass
+
+@router.get("/items/{item_id}")
+async def read_item(item_id: int):
+    pass
+
+class Admin:
+    @bp.delete("/admin/cache")
+    def clear(self):
+        pass
+"#,
+        ),
+        ("/repo/shop/views.py", "def order_detail(request, pk):\n    pass\n\nclass CartView(View):\n    pass\n"),
+        (
+            "/repo/shop/urls.py",
+            r#"
+from django.urls import path, include
+from . import views
+
+urlpatterns = [
+    path("orders/<int:pk>/", views.order_detail, name="order"),
+    path("cart/", views.CartView.as_view()),
+    path("api/", include("api.urls")),
+]
+"#,
+        ),
+    ];
+    let mut graph = Graph::new();
+    let mut coordinator = Coordinator::new();
+    for (file, code) in files {
+        add_file_node(&mut graph, file);
+        index_source(&mut graph, &mut coordinator, file, code);
+    }
+    coordinator.resolve_references(&mut graph);
+
+    let users = graph.all_nodes().find(|n| n.name == "users").unwrap();
+    assert_eq!(users.metadata.extra.get(ROUTE_KEY).map(String::as_str), Some("GET /users\nPOST /users"));
+
+    let mut handlers: Vec<_> = graph.all_edges()
+        .filter(|e| e.kind == EdgeKind::RouteHandler)
+        .map(|e| (graph.node(e.source).unwrap().name.clone(), graph.node(e.target).unwrap().name.clone()))
+        .collect();
+    handlers.sort();
+    let expected: Vec<(String, String)> = [
+        ("ANY /cart/", "CartView"),
+        ("ANY /orders/<int:pk>/", "order_detail"),
+        ("DELETE /admin/cache", "clear"),
+        ("GET /items/{item_id}", "read_item"),
+        ("GET /users", "users"),
+        ("POST /users", "users"),
+    ]
+    .iter()
+    .map(|(route, handler)| (route.to_string(), handler.to_string()))
+    .collect();
+    assert_eq!(handlers, expected);
+}
+
+#[test]
+fn test_go_receivers() {
+    use crate::heuristics::link_graph;
+    use canopy_core::{EdgeKind, Graph};
+
+    let files = [
+        (
+            "/repo/server/server.go",
+            "package server\n\n// Server serves requests.\ntype Server struct {\n\taddr string\n}\n\ntype Handler interface {\n\tServe()\n\tClose() error\n}\n\ntype Any interface{}\n",
+        ),
+        ("/repo/server/methods.go", "package server\n\nfunc (s *Server) Serve() {}\n\nfunc (s Server) Close() error { return nil }\n"),
+        ("/repo/client/client.go", "package client\n\ntype Server struct{}\n\nfunc (s *Server) Serve() {}\n"),
+    ];
+    let mut graph = Graph::new();
+    for (file, code) in files {
+        let path = PathBuf::from(file);
+        let result = get_extractor(&path).unwrap().extract(&path, code.as_bytes()).unwrap();
+        for node in result.nodes {
+            graph.add_node(node);
+        }
+    }
+    let server = graph.all_nodes().find(|n| n.name == "Server" && n.file_path.starts_with("/repo/server")).unwrap();
+    assert_eq!(server.kind, NodeKind::Struct);
+    assert_eq!(server.metadata.doc_summary.as_deref(), Some("Server serves requests."));
+    link_graph(&mut graph);
+
+    let name = |id| {
+        let node = graph.node(id).unwrap();
+        format!("{}:{}", node.file_path.parent().unwrap().display(), node.name)
+    };
+    let mut links: Vec<_> = graph.all_edges().map(|e| (e.kind, name(e.source), name(e.target))).collect();
+    links.sort_by(|a, b| (a.1.as_str(), a.2.as_str()).cmp(&(b.1.as_str(), b.2.as_str())));
+    assert_eq!(links, vec![
+        (EdgeKind::Contains, "/repo/client:Server".to_string(), "/repo/client:Serve".to_string()),
+        (EdgeKind::Contains, "/repo/server:Server".to_string(), "/repo/server:Close".to_string()),
+        (EdgeKind::Implements, "/repo/server:Server".to_string(), "/repo/server:Handler".to_string()),
+        (EdgeKind::Contains, "/repo/server:Server".to_string(), "/repo/server:Serve".to_string()),
+    ]);
+}
+
+#[test]
+fn test_c_include_resolution() {
+    use crate::coordinator::Coordinator;
+    use canopy_core::{EdgeKind, Graph};
+
+    // Quoted includes are looked up next to the including file first,
+    // among the files of the graph
+    let root = "/repo";
+    let file = |relative: &str| format!("{}/{}", root, relative);
+    let files = [
+        (file("include/parser.h"), "int parse(const char *s);\n"),
+        (file("src/util/strings.h"), "int length(const char *s);\n"),
+        (file("vendor/util/strings.h"), "int length(const char *s);\n"),
+        (file("src/parser.c"), "#include \"parser.h\"\n#include \"util/strings.h\"\n#include <stdio.h>\n\nint parse(const char *s) { return length(s); }\n"),
+        (file("src/net/socket.c"), "#include \"../util/strings.h\"\n"),
+    ];
+    let mut graph = Graph::new();
+    let mut coordinator = Coordinator::new();
+    for (file, code) in &files {
+        add_file_node(&mut graph, file);
+        index_source(&mut graph, &mut coordinator, file, code);
+    }
+    coordinator.resolve_references(&mut graph);
+
+    let mut links: Vec<_> = graph.all_edges()
+        .filter(|e| matches!(e.kind, EdgeKind::Imports | EdgeKind::Implements))
+        .map(|e| {
+            let target = graph.node(e.target).unwrap().file_path.strip_prefix(root).unwrap().display().to_string();
+            (e.label.clone().unwrap(), target)
+        })
+        .collect();
+    links.sort();
+    assert_eq!(links, vec![
+        ("implements parser.h".to_string(), "include/parser.h".to_string()),
+        ("includes ../util/strings.h".to_string(), "src/util/strings.h".to_string()),
+        ("includes parser.h".to_string(), "include/parser.h".to_string()),
+        ("includes util/strings.h".to_string(), "src/util/strings.h".to_string()),
+    ]);
+}
+
+#[test]
+fn test_cpp_scopes() {
+    let code = r#"
+namespace geo {
+
+/// A shape with an area.
+template <typename T>
+class Shape {
+public:
+    T area() const;
+    T scale(T factor) { return area() * factor; }
+private:
+    T width;
+};
+
+struct Point { int x; };
+
+template <typename T>
+T Shape<T>::area() const { return width * width; }
+
+}
+
+int main() { return 0; }
+"#;
+    let path = PathBuf::from("shapes.cpp");
+    let result = get_extractor(&path).unwrap().extract(&path, code.as_bytes()).unwrap();
+    let node = |name: &str| result.nodes.iter().position(|n| n.name == name).unwrap();
+    let mut nodes: Vec<_> = result.nodes.iter()
+        .map(|n| (n.qualified_name.to_string(), n.kind, n.line_start.unwrap()))
+        .collect();
+    nodes.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(nodes, vec![
+        ("shapes.cpp::geo".to_string(), NodeKind::Module, 2),
+        ("shapes.cpp::geo::Point".to_string(), NodeKind::Struct, 14),
+        ("shapes.cpp::geo::Point::x".to_string(), NodeKind::Field, 14),
+        ("shapes.cpp::geo::Shape".to_string(), NodeKind::Class, 6),
+        // The out-of-line definition replaces the declaration
+        ("shapes.cpp::geo::Shape::area".to_string(), NodeKind::Method, 17),
+        ("shapes.cpp::geo::Shape::scale".to_string(), NodeKind::Method, 9),
+        ("shapes.cpp::geo::Shape::width".to_string(), NodeKind::Field, 11),
+        ("shapes.cpp::main".to_string(), NodeKind::Function, 21),
+    ]);
+
+    let mut contains = result.contains.clone();
+    contains.sort();
+    let mut expected = vec![
+        (node("geo"), node("Shape")),
+        (node("geo"), node("Point")),
+        (node("Shape"), node("area")),
+        (node("Shape"), node("scale")),
+        (node("Shape"), node("width")),
+        (node("Point"), node("x")),
+    ];
+    expected.sort();
+    assert_eq!(contains, expected);
+
+    let shape = &result.nodes[node("Shape")].metadata;
+    assert_eq!(shape.signature.as_deref(), Some("template<typename T> class Shape"));
+    assert_eq!(shape.doc_summary.as_deref(), Some("A shape with an area."));
+    assert_eq!(result.nodes[node("scale")].metadata.visibility, Some(canopy_core::Visibility::Public));
+    assert_eq!(result.nodes[node("width")].metadata.visibility, Some(canopy_core::Visibility::Private));
+    assert_eq!(result.nodes[node("x")].metadata.visibility, Some(canopy_core::Visibility::Public));
+    assert_eq!(result.calls, vec![(node("scale"), node("area"), 9)]);
+}
+
+#[test]
+fn test_tested_by_links() {
+    use crate::coordinator::Coordinator;
+    use canopy_core::{EdgeKind, Graph};
+
+    let files = [
+        ("/repo/web/cart.ts", "export function total(items: number[]) { return 0; }\n"),
+        ("/repo/web/cart.spec.ts", "import { total } from './cart';\n\ndescribe('cart', () => {\n  it('sums items', () => {\n    expect(total([])).toBe(0);\n  });\n});\n"),
+        ("/repo/pricing.py", "def discount(price):\n    return price - 1\n"),
+        ("/repo/tests/test_pricing.py", "from pricing import discount\n\ndef test_discount():\n    assert discount(10) == 9\n"),
+        ("/repo/server.go", "package main\n\nfunc TestConnection() bool { return true }\n"),
+    ];
+    let mut graph = Graph::new();
+    let mut coordinator = Coordinator::new();
+    for (file, code) in files {
+        add_file_node(&mut graph, file);
+        index_source(&mut graph, &mut coordinator, file, code);
+    }
+    coordinator.resolve_references(&mut graph);
+    crate::heuristics::link_graph(&mut graph);
+
+    let mut tests: Vec<_> = graph.all_nodes()
+        .filter(|n| n.kind == NodeKind::TestCase)
+        .map(|n| n.qualified_name.to_string())
+        .collect();
+    tests.sort();
+    assert_eq!(tests, vec![
+        "/repo/web/cart.spec::cart",
+        "/repo/web/cart.spec::cart::sums items",
+        "test_pricing.test_discount",
+    ]);
+
+    let name = |id| graph.node(id).unwrap().name.clone();
+    let mut links: Vec<_> = graph.all_edges()
+        .filter(|e| e.kind == EdgeKind::TestedBy)
+        .map(|e| (name(e.source), e.label.clone().unwrap(), name(e.target)))
+        .collect();
+    links.sort();
+    assert_eq!(links, vec![
+        ("cart.ts".to_string(), "tested by cart.spec.ts".to_string(), "cart.spec.ts".to_string()),
+        ("discount".to_string(), "tested by test_discount".to_string(), "test_discount".to_string()),
+        ("pricing.py".to_string(), "tested by test_pricing.py".to_string(), "test_pricing.py".to_string()),
+        ("total".to_string(), "tested by sums items".to_string(), "sums items".to_string()),
+    ]);
+    assert!(crate::heuristics::link_graph(&mut graph).is_empty());
+}
+
+#[test]
+fn test_module_qualified_names() {
+    use crate::coordinator::Coordinator;
+    use canopy_core::{EdgeKind, Graph};
+
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    let files = [
+        ("app/__init__.py", ""),
+        ("app/models/__init__.py", ""),
+        ("app/models/user.py", "class User:\n    def save(self):\n        pass\n"),
+        ("app/views.py", "from app.models.user import User\n\ndef show():\n    return User()\n"),
+        ("java/com/acme/User.java", "package com.acme;\n\npublic class User {\n    private String name;\n    public String getName() { return name; }\n}\n"),
+        ("java/com/acme/web/Api.java", "package com.acme.web;\n\nimport com.acme.User;\n\npublic class Api {}\n"),
+        ("web/package.json", "{\"name\": \"@acme/web\"}"),
+        ("web/src/cart.ts", "export function total() { return 0; }\n"),
+        ("web/src/utils/index.js", "function format() {}\n"),
+    ];
+    let mut graph = Graph::new();
+    for (file, code) in files {
+        let path = root.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, code).unwrap();
+        add_file_node(&mut graph, &path.display().to_string());
+    }
+    let mut coordinator = Coordinator::new();
+    coordinator.run_full_index(&mut graph, 2);
+
+    let qualified = |name| graph.node(graph.find_node_by_name(name).unwrap()).unwrap().qualified_name.to_string();
+    assert_eq!(qualified("save"), "app.models.user.User.save");
+    assert_eq!(qualified("show"), "app.views.show");
+    assert_eq!(qualified("getName"), "com.acme.User.getName");
+    assert_eq!(qualified("name"), "com.acme.User.name");
+    assert_eq!(qualified("Api"), "com.acme.web.Api");
+    assert_eq!(qualified("total"), "@acme/web/src/cart::total");
+    assert_eq!(qualified("format"), "@acme/web/src/utils::format");
+
+    // Java imports name the class itself
+    let api_file = graph.find_node_by_name("Api.java").unwrap();
+    let user_class = graph.all_nodes()
+        .find(|n| n.kind == NodeKind::Class && n.qualified_name.as_str() == "com.acme.User")
+        .unwrap()
+        .id;
+    assert!(graph.has_edge_between(api_file, user_class, EdgeKind::Imports));
+    assert!(coordinator.symbols().lookup("app.models.user.User").is_some());
+}
+
+#[test]
+fn test_naming_and_layout_links() {
+    use canopy_core::{EdgeKind, EdgeSource, Graph};
+
+    let mut graph = Graph::new();
+    let mut coordinator = crate::coordinator::Coordinator::new();
+    let files = [
+        ("/repo/src/UserController.java", "public class UserController {}\n"),
+        ("/repo/src/UserService.java", "public interface UserService {}\n"),
+        ("/repo/src/UserRepository.java", "public class UserRepository {}\n"),
+        ("/repo/src/OrderController.java", "public class OrderController {}\n"),
+        ("/repo/src/OrderRepository.java", "public class OrderRepository {}\n"),
+        ("/repo/server/cart.go", "package server\n"),
+        ("/repo/server/cart_test.go", "package server\n"),
+        ("/repo/api/routes/users.ts", "export const users = 1;\n"),
+        ("/repo/api/services/user.service.ts", "export const user = 1;\n"),
+        ("/repo/app/db.py", "import os\n\ndef connect():\n    return os.environ[\"DATABASE_MAX_CONNECTIONS\"]\n"),
+        ("/repo/config.yaml", "database:\n  maxConnections: 10\n"),
+    ];
+    for (file, code) in files {
+        add_file_node(&mut graph, file);
+        let path = std::path::Path::new(file);
+        let result = crate::languages::extract_file(path, code.as_bytes()).unwrap();
+        let nodes: Vec<GraphNode> = result.nodes.into_iter()
+            .map(|mut node| {
+                node.id = graph.add_node(node.clone());
+                node
+            })
+            .collect();
+        coordinator.register_file(path, &nodes, result.references);
+    }
+    coordinator.resolve_references(&mut graph);
+    crate::heuristics::link_graph(&mut graph);
+
+    let name = |id| graph.node(id).unwrap().name.clone();
+    let mut links: Vec<_> = graph.all_edges()
+        .filter(|e| e.edge_source == EdgeSource::Heuristic)
+        .filter(|e| matches!(e.kind, EdgeKind::DependsOn | EdgeKind::TestedBy | EdgeKind::EnvironmentBinding))
+        .map(|e| (name(e.source), e.label.clone().unwrap(), name(e.target), e.confidence))
+        .collect();
+    links.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(links, vec![
+        ("OrderController".to_string(), "depends on OrderRepository".to_string(), "OrderRepository".to_string(), 0.5),
+        ("UserController".to_string(), "depends on UserService".to_string(), "UserService".to_string(), 0.7),
+        ("UserService".to_string(), "depends on UserRepository".to_string(), "UserRepository".to_string(), 0.7),
+        ("cart.go".to_string(), "tested by cart_test.go".to_string(), "cart_test.go".to_string(), 0.9),
+        ("maxConnections".to_string(), "read as DATABASE_MAX_CONNECTIONS by connect".to_string(), "connect".to_string(), 0.7),
+        ("users.ts".to_string(), "depends on user.service.ts".to_string(), "user.service.ts".to_string(), 0.6),
+    ]);
+    assert!(crate::heuristics::link_graph(&mut graph).is_empty());
+}
+
+#[test]
+fn test_run_full_index() {
+    use crate::coordinator::Coordinator;
+    use canopy_core::{EdgeKind, Graph};
+
+    let dir = tempfile::tempdir().unwrap();
+    let files = [
+        ("src/graph.rs", "pub struct Graph;\n\npub fn build_graph() -> Graph {\n    Graph\n}\n"),
+        ("src/main.rs", "mod graph;\nuse crate::graph::build_graph;\n\nfn main() {\n    build_graph();\n}\n"),
+        ("app.py", "def helper():\n    return 1\n\ndef run():\n    return helper()\n"),
+        ("notes.bin", "not indexed"),
+    ];
+    let mut graph = Graph::new();
+    for (file, code) in files {
+        let path = dir.path().join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, code).unwrap();
+        add_file_node(&mut graph, &path.display().to_string());
+    }
+    // A file that vanished after the walk is reported, not fatal
+    add_file_node(&mut graph, &dir.path().join("gone.rs").display().to_string());
+
+    let mut coordinator = Coordinator::new();
+    let report = coordinator.run_full_index(&mut graph, 4);
+
+    assert_eq!(report.file_nodes.len(), 3);
+    assert_eq!(report.failed.len(), 1);
+    assert!(report.failed[0].0.ends_with("gone.rs"));
+    assert_eq!(report.file_nodes[&dir.path().join("app.py")].len(), 2);
+    for name in ["Graph", "build_graph", "main", "helper", "run"] {
+        assert!(graph.find_node_by_name(name).is_some(), "{}", name);
+    }
+    // Each File node counts the file's own lines
+    let loc = |file: &str| graph.all_nodes().find(|n| n.file_path == dir.path().join(file)).unwrap().loc;
+    assert_eq!(loc("src/graph.rs"), Some(5));
+    assert_eq!(loc("app.py"), Some(5));
+    assert_eq!(loc("notes.bin"), None);
+
+    let name = |id| graph.node(id).unwrap().name.clone();
+    let mut calls: Vec<_> = graph.all_edges()
+        .filter(|e| e.kind == EdgeKind::Calls)
+        .map(|e| (name(e.source), name(e.target)))
+        .collect();
+    calls.sort();
+    assert_eq!(calls, vec![
+        ("main".to_string(), "build_graph".to_string()),
+        ("run".to_string(), "helper".to_string()),
+    ]);
+    assert!(coordinator.symbols().lookup("crate::graph::build_graph").is_some());
+}
+
+#[test]
+fn test_full_index_skips_files() {
+    use crate::coordinator::Coordinator;
+    use crate::walk::{SkipRules, SKIPPED_KEY};
+    use canopy_core::Graph;
+
+    let dir = tempfile::tempdir().unwrap();
+    let minified = format!("function a(){{{}}}\n", "b();".repeat(150));
+    let files = [
+        ("app.js", "function run() {\n    return 1;\n}\n".to_string()),
+        ("vendor.min.js", "function a(){return 1}".to_string()),
+        ("bundle.js", minified),
+        ("blob.rs", "fn a() {}\n\0\0\0".to_string()),
+        ("api_pb.rs", "// @generated\npub struct Api;\n".to_string()),
+        ("big.py", "x = 1\n".repeat(200)),
+    ];
+    let mut graph = Graph::new();
+    for (file, code) in &files {
+        let path = dir.path().join(file);
+        std::fs::write(&path, code).unwrap();
+        add_file_node(&mut graph, &path.display().to_string());
+    }
+
+    let rules = SkipRules::default().with_max_file_size(1024).with_max_line_length(500);
+    let mut coordinator = Coordinator::new().with_skip_rules(rules);
+    let report = coordinator.run_full_index(&mut graph, 2);
+
+    assert_eq!(report.file_nodes.len(), 1);
+    assert!(report.failed.is_empty());
+    let mut skipped: Vec<_> = report.skipped.iter()
+        .map(|(path, reason)| (path.file_name().unwrap().to_str().unwrap(), reason.as_str()))
+        .collect();
+    skipped.sort();
+    assert_eq!(skipped, vec![
+        ("api_pb.rs", "generated"),
+        ("big.py", "larger than 1024 bytes"),
+        ("blob.rs", "binary"),
+        ("bundle.js", "minified"),
+        ("vendor.min.js", "generated (min.js)"),
+    ]);
+
+    let skipped_key = |name| graph.node(graph.find_node_by_name(name).unwrap()).unwrap().metadata.extra.get(SKIPPED_KEY).cloned();
+    assert_eq!(skipped_key("blob.rs").as_deref(), Some("binary"));
+    assert_eq!(skipped_key("app.js"), None);
+    assert!(graph.find_node_by_name("run").is_some());
+    assert!(graph.find_node_by_name("Api").is_none());
+}
+
+#[test]
+fn test_partial_extraction() {
+    use crate::coordinator::{Coordinator, PARSE_ERRORS_KEY};
+    use canopy_core::Graph;
+
+    let dir = tempfile::tempdir().unwrap();
+    let files = [
+        ("ok.py", "def fine():\n    return 1\n"),
+        ("broken.py", "def before():\n    return 1\n\ndef broken(:\n    pass\n\ndef after():\n    return 2\n"),
+    ];
+    let mut graph = Graph::new();
+    for (file, code) in files {
+        let path = dir.path().join(file);
+        std::fs::write(&path, code).unwrap();
+        add_file_node(&mut graph, &path.display().to_string());
+    }
+
+    let report = Coordinator::new().run_full_index(&mut graph, 1);
+    assert_eq!(report.partial.len(), 1);
+    let (path, errors) = &report.partial[0];
+    assert!(path.ends_with("broken.py"));
+    assert!(errors.iter().all(|e| e.line_start == 4), "{:?}", errors);
+
+    // What parsed around the error is still indexed
+    assert!(graph.find_node_by_name("before").is_some());
+    assert!(graph.find_node_by_name("after").is_some());
+    let parse_errors = |name| graph.node(graph.find_node_by_name(name).unwrap()).unwrap().metadata.extra.get(PARSE_ERRORS_KEY).cloned();
+    assert_eq!(parse_errors("broken.py").as_deref(), Some("4 (missing `)`)"));
+    assert_eq!(parse_errors("ok.py"), None);
+}
+
+#[test]
+fn test_extraction_config() {
+    use crate::coordinator::Coordinator;
+    use crate::project::ProjectConfig;
+    use crate::walk::SKIPPED_KEY;
+    use canopy_core::{EdgeKind, Graph};
+
+    let config = ProjectConfig::parse(
+        r#"
+[server]
+port = 7890
+
+[extract]
+skip_kinds = ["Constant"]
+
+[extract.python]
+skip_private = true
+skip_tests = true
+
+[index]
+max_line_length = 200
+"#,
+    )
+    .unwrap();
+    assert_eq!(config.skip_rules().max_line_length, 200);
+
+    let dir = tempfile::tempdir().unwrap();
+    let files = [
+        ("lib.rs", "const LIMIT: u32 = 3;\n\nfn helper() {}\n\npub fn run() {\n    helper();\n}\n"),
+        ("app.py", "class _Cache:\n    def get(self):\n        return load()\n\ndef load():\n    return 1\n"),
+        ("test_app.py", "def test_load():\n    assert load() == 1\n"),
+    ];
+    let mut graph = Graph::new();
+    for (file, code) in files {
+        let path = dir.path().join(file);
+        std::fs::write(&path, code).unwrap();
+        add_file_node(&mut graph, &path.display().to_string());
+    }
+
+    let mut coordinator = Coordinator::new().with_extraction(config.extract);
+    let report = coordinator.run_full_index(&mut graph, 1);
+    assert_eq!(report.skipped.len(), 1);
+    let test_file = graph.node(graph.find_node_by_name("test_app.py").unwrap()).unwrap();
+    assert_eq!(test_file.metadata.extra.get(SKIPPED_KEY).map(String::as_str), Some("test file"));
+
+    // Constants are skipped everywhere, private symbols only in Python
+    assert!(graph.find_node_by_name("LIMIT").is_none());
+    assert!(graph.find_node_by_name("helper").is_some());
+    assert!(graph.find_node_by_name("_Cache").is_none());
+    assert!(graph.find_node_by_name("test_load").is_none());
+
+    // The method of the skipped class keeps its call
+    let get = graph.find_node_by_name("get").unwrap();
+    let load = graph.find_node_by_name("load").unwrap();
+    assert!(graph.has_edge_between(get, load, EdgeKind::Calls));
+}
+
+#[test]
+fn test_tree_cache() {
+    use crate::coordinator::Coordinator;
+    use crate::tree_cache::ParseTreeCache;
+    use canopy_core::Graph;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("lib.rs");
+    let code = "fn run() {}\n";
+    std::fs::write(&path, code).unwrap();
+    let index = |cache: ParseTreeCache| {
+        let mut graph = Graph::new();
+        add_file_node(&mut graph, &path.display().to_string());
+        Coordinator::new().with_tree_cache(cache).run_full_index(&mut graph, 1);
+        graph.all_nodes().filter(|n| n.kind == NodeKind::Function).map(|n| n.name.clone()).collect::<Vec<_>>()
+    };
+
+    let cache = ParseTreeCache::open(dir.path());
+    assert!(cache.get(&path, code.as_bytes()).is_none());
+    assert_eq!(index(cache.clone()), vec!["run"]);
+    let mut cached = cache.get(&path, code.as_bytes()).expect("the extraction is cached");
+    assert!(cache.get(&path, b"fn walk() {}\n").is_none());
+
+    // A restart takes unchanged files from the cache rather than parsing them
+    for node in cached.nodes.iter_mut().filter(|n| n.name == "run") {
+        node.name = "from_cache".to_string();
+    }
+    cache.put(&path, code.as_bytes(), &cached).unwrap();
+    assert_eq!(index(ParseTreeCache::open(dir.path())), vec!["from_cache"]);
+
+    // Other settings may extract differently, so they miss
+    std::fs::write(dir.path().join(".canopy.toml"), "[preprocessor]\ndefines = [\"DEBUG\"]\n").unwrap();
+    assert!(ParseTreeCache::open(dir.path()).get(&path, code.as_bytes()).is_none());
+}
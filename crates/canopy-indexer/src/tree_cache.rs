@@ -1,9 +1,86 @@
-//! Parse tree cache for incremental tree-sitter parsing
+//! On-disk cache of extraction results
+//!
+//! Parsing and extracting every file is most of the time a full index
+//! takes. [`ParseTreeCache`] keeps each file's [`ExtractionResult`] under
+//! `.canopy/tree-cache/`, keyed by its path and checked against a hash of
+//! its content, so a restart only reparses the files that changed.
+//!
+//! The hash also covers the indexer's version and the project's
+//! `.canopy.toml`, whose extractor mappings, regex rules and defines shape
+//! what is extracted: entries made by another version or under other
+//! settings are missed and replaced.
 
-pub struct ParseTreeCache;
+use crate::extractor::ExtractionResult;
+use crate::project::CONFIG_FILE;
+use anyhow::Result;
+use canopy_core::cache::cache_dir;
+use serde::{Deserialize, Serialize};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+/// Directory of the cache, within [`canopy_core::cache::CACHE_DIR`].
+pub const TREE_CACHE_DIR: &str = "tree-cache";
+
+/// A cached result with the hash of the content it was extracted from.
+#[derive(Serialize, Deserialize)]
+struct Entry<R> {
+    hash: u64,
+    result: R,
+}
+
+/// The extraction results of a project's files, stored one file each.
+#[derive(Debug, Clone)]
+pub struct ParseTreeCache {
+    dir: PathBuf,
+    /// Hash of what besides content decides a file's result.
+    seed: u64,
+}
 
 impl ParseTreeCache {
-    pub fn new() -> Self {
-        ParseTreeCache
+    /// The cache of the project at `root`. Nothing is written until a
+    /// result is [`put`](Self::put).
+    pub fn open(root: &Path) -> Self {
+        let settings = std::fs::read(root.join(CONFIG_FILE)).unwrap_or_default();
+        let mut hasher = DefaultHasher::new();
+        env!("CARGO_PKG_VERSION").hash(&mut hasher);
+        settings.hash(&mut hasher);
+        ParseTreeCache {
+            dir: cache_dir(root).join(TREE_CACHE_DIR),
+            seed: hasher.finish(),
+        }
+    }
+
+    fn entry_path(&self, path: &Path) -> PathBuf {
+        let mut hasher = DefaultHasher::new();
+        path.hash(&mut hasher);
+        self.dir.join(format!("{:016x}.json", hasher.finish()))
+    }
+
+    fn content_hash(&self, content: &[u8]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.seed.hash(&mut hasher);
+        content.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// The result cached for `path`, if it was extracted from `content`.
+    /// Unreadable entries count as missing.
+    pub fn get(&self, path: &Path, content: &[u8]) -> Option<ExtractionResult> {
+        let text = std::fs::read(self.entry_path(path)).ok()?;
+        let entry: Entry<ExtractionResult> = serde_json::from_slice(&text).ok()?;
+        (entry.hash == self.content_hash(content)).then_some(entry.result)
+    }
+
+    /// Cache `result` as extracted from `content`, replacing what was
+    /// cached for `path` before.
+    pub fn put(&self, path: &Path, content: &[u8], result: &ExtractionResult) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let entry = Entry { hash: self.content_hash(content), result };
+        // Written aside and renamed, so a reader never sees half an entry
+        let target = self.entry_path(path);
+        let partial = target.with_extension(format!("{}.tmp", std::process::id()));
+        std::fs::write(&partial, serde_json::to_vec(&entry)?)?;
+        std::fs::rename(&partial, &target)?;
+        Ok(())
     }
 }
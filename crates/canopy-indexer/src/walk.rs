@@ -0,0 +1,257 @@
+//! Which files of a repository are indexed
+//!
+//! Files ignored by git — through `.gitignore` files, `.git/info/exclude`
+//! and the global excludes file — are skipped, as are those matched by a
+//! `.canopyignore`, which uses the same syntax and takes precedence over
+//! `.gitignore` in its directory. The full index walks with [`walker`];
+//! the watcher checks each changed path with [`IgnoreRules`].
+//!
+//! Files that are walked may still not be worth parsing: [`SkipRules`]
+//! singles out binaries, minified bundles, generated code and files over a
+//! size limit, which stay plain File nodes marked with [`SKIPPED_KEY`].
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::{Match, WalkBuilder};
+use std::path::{Path, PathBuf};
+
+/// Project-level ignore file for paths git tracks but Canopy should not.
+pub const CANOPY_IGNORE: &str = ".canopyignore";
+
+/// Directories skipped whether or not they are ignored, Canopy's own cache
+/// among them.
+const ALWAYS_IGNORED: &[&str] = &[".git", "node_modules", "target", canopy_core::cache::CACHE_DIR];
+
+fn always_ignored(path: &Path) -> bool {
+    path.components().any(|c| c.as_os_str().to_str().is_some_and(|c| ALWAYS_IGNORED.contains(&c)))
+}
+
+/// A walker over the files and directories of `root` that are not ignored.
+/// Ignore files apply outside git repositories too.
+pub fn walker(root: &Path) -> WalkBuilder {
+    let mut builder = WalkBuilder::new(root);
+    builder
+        .hidden(false)
+        .require_git(false)
+        .add_custom_ignore_filename(CANOPY_IGNORE)
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .filter_entry(|entry| !always_ignored(Path::new(entry.file_name())));
+    builder
+}
+
+/// Ignore rules for checking paths one at a time, as the watcher sees them.
+/// Ignore files are read on every check, so edits to them apply at once.
+pub struct IgnoreRules {
+    root: PathBuf,
+    global: Gitignore,
+}
+
+impl IgnoreRules {
+    pub fn new(root: &Path) -> Self {
+        let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+        let (global, _) = Gitignore::global();
+        Self { root, global }
+    }
+
+    /// Whether `path` is ignored. The closest directory's ignore files
+    /// decide, as with git, then the repository's and the global excludes;
+    /// paths outside the root are only checked against the global ones.
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+        if always_ignored(relative) {
+            return true;
+        }
+        let is_dir = path.is_dir();
+        if path.starts_with(&self.root) {
+            for dir in path.ancestors().skip(1).take_while(|dir| dir.starts_with(&self.root)) {
+                match Self::dir_rules(dir).matched_path_or_any_parents(path, is_dir) {
+                    Match::Ignore(_) => return true,
+                    Match::Whitelist(_) => return false,
+                    Match::None => {}
+                }
+            }
+            match self.exclude_rules().matched_path_or_any_parents(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+        self.global.matched_path_or_any_parents(path, is_dir).is_ignore()
+    }
+
+    /// The repository's own excludes in `.git/info/exclude`, which git
+    /// checks after every `.gitignore` and before the global excludes.
+    fn exclude_rules(&self) -> Gitignore {
+        let mut builder = GitignoreBuilder::new(&self.root);
+        let path = self.root.join(".git/info/exclude");
+        if path.is_file() {
+            builder.add(path);
+        }
+        builder.build().unwrap_or_else(|_| Gitignore::empty())
+    }
+
+    /// The rules of a directory's own ignore files, `.canopyignore` last so
+    /// its patterns win.
+    fn dir_rules(dir: &Path) -> Gitignore {
+        let mut builder = GitignoreBuilder::new(dir);
+        for file in [".gitignore", CANOPY_IGNORE] {
+            let path = dir.join(file);
+            if path.is_file() {
+                builder.add(path);
+            }
+        }
+        builder.build().unwrap_or_else(|_| Gitignore::empty())
+    }
+}
+
+/// Metadata key on File nodes that were not parsed, holding why.
+pub const SKIPPED_KEY: &str = "skipped";
+
+/// Largest file parsed by default, in bytes.
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 2 * 1024 * 1024;
+
+/// Longest line of a readable source file by default; longer lines mark
+/// minified code.
+pub const DEFAULT_MAX_LINE_LENGTH: usize = 1000;
+
+/// Bytes read from the start of a file when sniffing its content.
+const SNIFF_LEN: usize = 8 * 1024;
+
+/// File name suffixes of minified and generated files.
+const GENERATED_SUFFIXES: &[&str] = &[
+    ".min.js", ".min.css", ".bundle.js", ".map", "_pb2.py", ".pb.go", ".g.dart", ".designer.cs",
+    "package-lock.json", "yarn.lock", "pnpm-lock.yaml", "Cargo.lock", "poetry.lock", "go.sum",
+];
+
+/// Markers generators put in a file's header.
+const GENERATED_MARKERS: &[&str] = &["@generated", "DO NOT EDIT", "auto-generated", "autogenerated"];
+
+/// Limits deciding which files are skipped rather than parsed.
+#[derive(Debug, Clone)]
+pub struct SkipRules {
+    pub max_file_size: u64,
+    pub max_line_length: usize,
+}
+
+impl Default for SkipRules {
+    fn default() -> Self {
+        Self { max_file_size: DEFAULT_MAX_FILE_SIZE, max_line_length: DEFAULT_MAX_LINE_LENGTH }
+    }
+}
+
+impl SkipRules {
+    pub fn with_max_file_size(mut self, bytes: u64) -> Self {
+        self.max_file_size = bytes;
+        self
+    }
+
+    pub fn with_max_line_length(mut self, length: usize) -> Self {
+        self.max_line_length = length;
+        self
+    }
+
+    /// Why a file of `size` bytes is skipped before reading it, by its name
+    /// and size alone.
+    pub fn skip_by_name(&self, path: &Path, size: u64) -> Option<String> {
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+        if size > self.max_file_size {
+            return Some(format!("larger than {} bytes", self.max_file_size));
+        }
+        if let Some(suffix) = GENERATED_SUFFIXES.iter().find(|s| name.ends_with(*s)) {
+            return Some(format!("generated ({})", suffix.trim_start_matches(['.', '_'])));
+        }
+        None
+    }
+
+    /// Why a file is skipped, by its name, size and the start of its
+    /// content: binary data, minified lines or a generated-code header.
+    pub fn skip_reason(&self, path: &Path, content: &[u8]) -> Option<String> {
+        if let Some(reason) = self.skip_by_name(path, content.len() as u64) {
+            return Some(reason);
+        }
+        let head = &content[..content.len().min(SNIFF_LEN)];
+        // A multi-byte character may be cut at the end of the sniffed bytes
+        let text = match std::str::from_utf8(head) {
+            Ok(text) => text,
+            Err(e) if e.error_len().is_none() => std::str::from_utf8(&head[..e.valid_up_to()]).unwrap_or_default(),
+            Err(_) => return Some("binary".to_string()),
+        };
+        if text.contains('\0') {
+            return Some("binary".to_string());
+        }
+        if text.lines().take(5).any(|line| GENERATED_MARKERS.iter().any(|m| line.contains(m))) {
+            return Some("generated".to_string());
+        }
+        // A line running past the sniffed bytes is at least as long as its
+        // sniffed part
+        if text.lines().any(|line| line.len() > self.max_line_length) {
+            return Some("minified".to_string());
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ignore_rules() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let write = |file: &str, content: &str| {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        };
+        write(".gitignore", "dist/\n*.log\n");
+        write(".canopyignore", "vendor/\n!keep.log\n");
+        write("web/.gitignore", "generated.ts\n");
+        write(".git/info/exclude", "scratch/\n");
+        for file in ["scratch/notes.rs", "src/main.rs", "dist/app.js", "vendor/lib.rs", "debug.log", "keep.log", "web/generated.ts", "web/app.ts", "node_modules/pkg/index.js"] {
+            write(file, "");
+        }
+
+        let mut walked: Vec<_> = walker(&root)
+            .build()
+            .filter_map(Result::ok)
+            .filter(|e| e.file_type().is_some_and(|t| t.is_file()))
+            .map(|e| e.path().strip_prefix(&root).unwrap().display().to_string())
+            .collect();
+        walked.sort();
+        assert_eq!(walked, vec![".canopyignore", ".gitignore", "keep.log", "src/main.rs", "web/.gitignore", "web/app.ts"]);
+
+        let rules = IgnoreRules::new(&root);
+        for (file, ignored) in [
+            ("src/main.rs", false),
+            ("dist/app.js", true),
+            ("vendor/lib.rs", true),
+            ("debug.log", true),
+            ("keep.log", false),
+            ("web/generated.ts", true),
+            ("web/app.ts", false),
+            ("scratch/notes.rs", true),
+            ("node_modules/pkg/index.js", true),
+            (".git/HEAD", true),
+        ] {
+            assert_eq!(rules.is_ignored(&root.join(file)), ignored, "{}", file);
+        }
+    }
+
+    #[test]
+    fn test_skip_rules() {
+        let rules = SkipRules::default().with_max_file_size(64 * 1024);
+        let minified = format!("!function(){{{}}}();", "a=1;".repeat(400));
+        let long_file = "fn a() {}\n".repeat(10_000);
+        for (file, content, expected) in [
+            ("src/main.rs", "fn main() {}\n".as_bytes(), None),
+            ("logo.png", b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".as_slice(), Some("binary")),
+            ("data.rs", b"fn a() {}\n\0\0".as_slice(), Some("binary")),
+            ("app.min.js", b"var a=1;".as_slice(), Some("generated (min.js)")),
+            ("bundle.js", minified.as_bytes(), Some("minified")),
+            ("api.rs", b"// @generated by protoc\npub struct A;\n".as_slice(), Some("generated")),
+            ("big.rs", long_file.as_bytes(), Some("larger than 65536 bytes")),
+        ] {
+            assert_eq!(rules.skip_reason(Path::new(file), content).as_deref(), expected, "{}", file);
+        }
+    }
+}
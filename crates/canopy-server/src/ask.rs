@@ -0,0 +1,531 @@
+//! Questions about the codebase, answered by the AI provider
+//!
+//! A question is answered from the part of the graph a query, translated
+//! from it by the provider, finds. Questions that can't be translated are
+//! answered from the nodes whose names appear in them, and their direct
+//! neighbours.
+//!
+//! Each answer belongs to a session, whose id is sent before it. Asked
+//! with that id, a follow-up is answered knowing the earlier questions and
+//! the nodes they were answered from.
+//!
+//! Answers are streamed from `/api/ask/stream`, or returned whole from
+//! `POST /api/ask` with the nodes they name, for the UI to highlight.
+//!
+//! Translating and answering a question spend from the same token budget
+//! as the watcher's analysis; once it's used up, questions to a paid
+//! provider are refused.
+
+use std::collections::HashSet;
+use std::convert::Infallible;
+use std::path::{Component, Path};
+use std::sync::{Arc, Mutex};
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::sse::{Event, KeepAlive, Sse},
+    response::Json,
+};
+use canopy_ai::prompt::{code_question_prompt, graph_query_prompt};
+use canopy_ai::query::query_question;
+use canopy_ai::{AIProvider, Budget};
+use canopy_core::{Graph, NodeId, Subgraph};
+use futures_util::{stream, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::ServerState;
+
+/// Most nodes given to the provider with a question.
+pub const MAX_RELEVANT_NODES: usize = 50;
+
+/// Query parameters of the ask endpoints
+#[derive(Debug, Deserialize)]
+pub struct AskQuery {
+    /// The question
+    pub q: String,
+    /// The session the question follows up, if any
+    pub session: Option<String>,
+}
+
+/// Body of the ask API: `{"question": "...", "session": "...", "focus": 42}`
+#[derive(Debug, Deserialize)]
+pub struct AskRequest {
+    pub question: String,
+    /// The session the question follows up, if any
+    pub session: Option<String>,
+    /// A node the question is about, answered from with its neighbours
+    pub focus: Option<u64>,
+    /// A file or directory the question is about, relative to the
+    /// project's root, answered from with the nodes in it
+    pub path: Option<String>,
+}
+
+/// An answer, and the nodes it names
+#[derive(Debug, Serialize)]
+pub struct AskResponse {
+    pub answer: String,
+    /// The conversation's id, to ask a follow-up with
+    pub session: String,
+    /// The nodes answered from whose names the answer mentions
+    pub referenced: Vec<u64>,
+}
+
+/// The nodes `question` names, matched by name ignoring case, with their
+/// neighbours and the edges between them.
+pub fn relevant_subgraph(graph: &Graph, question: &str) -> Subgraph {
+    let words: HashSet<String> = question
+        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|word| word.len() >= 3)
+        .map(str::to_lowercase)
+        .collect();
+    let mut named: Vec<_> = graph.all_nodes().filter(|n| words.contains(&n.name.to_lowercase())).collect();
+    named.sort_by_key(|n| (n.is_container, n.id.0));
+
+    let mut subgraph = Subgraph::default();
+    let mut seen = HashSet::new();
+    for node in named {
+        let around = graph.subgraph_around(node.id, 1, &[]);
+        if !subgraph.nodes.is_empty() && subgraph.nodes.len() + around.nodes.len() > MAX_RELEVANT_NODES {
+            break;
+        }
+        subgraph.nodes.extend(around.nodes.into_iter().filter(|n| seen.insert(n.id)));
+        subgraph.edges.extend(around.edges);
+    }
+    let mut edge_ids = HashSet::new();
+    subgraph.edges.retain(|e| edge_ids.insert(e.id));
+    subgraph
+}
+
+/// The node `focus` with its neighbours, then the nodes of the files
+/// under `path`, with the edges between them.
+pub fn focused_subgraph(graph: &Graph, focus: Option<NodeId>, path: Option<&Path>) -> Subgraph {
+    let mut subgraph = focus.map(|id| graph.subgraph_around(id, 1, &[])).unwrap_or_default();
+    if let Some(path) = path {
+        let mut under: Vec<_> = graph.all_nodes().filter(|n| n.file_path.starts_with(path)).collect();
+        under.sort_by_key(|n| (n.is_container, n.id.0));
+        let seen: HashSet<NodeId> = subgraph.nodes.iter().map(|n| n.id).collect();
+        let added: Vec<_> = under.into_iter().filter(|n| !seen.contains(&n.id)).take(MAX_RELEVANT_NODES).collect();
+        let kept: HashSet<NodeId> = seen.into_iter().chain(added.iter().map(|n| n.id)).collect();
+        let mut edge_ids: HashSet<_> = subgraph.edges.iter().map(|e| e.id).collect();
+        for node in &added {
+            let edges = graph.edges_from(node.id).chain(graph.edges_to(node.id));
+            subgraph.edges.extend(edges.filter(|e| kept.contains(&e.source) && kept.contains(&e.target) && edge_ids.insert(e.id)).cloned());
+        }
+        subgraph.nodes.extend(added.into_iter().cloned());
+    }
+    subgraph
+}
+
+/// The nodes of `context` that `answer` mentions by name or qualified
+/// name.
+pub fn referenced_nodes(context: &Subgraph, answer: &str) -> Vec<NodeId> {
+    let words: HashSet<&str> = answer.split(|c: char| !(c.is_alphanumeric() || c == '_')).filter(|w| !w.is_empty()).collect();
+    context
+        .nodes
+        .iter()
+        .filter(|n| words.contains(n.name.as_str()) || (n.qualified_name.contains("::") && answer.contains(n.qualified_name.as_str())))
+        .map(|n| n.id)
+        .collect()
+}
+
+/// Refuse a request of about `estimate` tokens to a paid provider once
+/// the AI budget, shared with the watcher's analysis, can't cover it.
+async fn admit(state: &ServerState, provider: &dyn AIProvider, estimate: u32) -> Result<(), (StatusCode, String)> {
+    if provider.is_billed() && !state.ai_budget.write().await.admit(estimate) {
+        return Err((StatusCode::TOO_MANY_REQUESTS, "The AI token budget is exhausted".to_string()));
+    }
+    Ok(())
+}
+
+/// Count `estimate` tokens of a request reporting no usage as spent, if
+/// the provider bills for them.
+async fn charge(state: &ServerState, provider: &dyn AIProvider, estimate: u32) {
+    if provider.is_billed() {
+        state.ai_budget.write().await.use_tokens(estimate);
+    }
+}
+
+/// [`grounded_subgraph`], within the AI budget: the translation of the
+/// question is a request of its own.
+async fn budgeted_subgraph(state: &ServerState, provider: &dyn AIProvider, question: &str) -> Result<Subgraph, (StatusCode, String)> {
+    let estimate = Budget::estimate_tokens(&graph_query_prompt(question));
+    admit(state, provider, estimate).await?;
+    let found = grounded_subgraph(provider, &state.graph, question).await;
+    charge(state, provider, estimate).await;
+    Ok(found)
+}
+
+/// The part of `graph` to answer `question` from: what the graph query
+/// it translates into finds, or else the nodes it names.
+pub async fn grounded_subgraph(provider: &dyn AIProvider, graph: &RwLock<Graph>, question: &str) -> Subgraph {
+    match query_question(provider, question, graph).await {
+        Some((_, result)) => result,
+        None => relevant_subgraph(&*graph.read().await, question),
+    }
+}
+
+/// Stream the answer to `?q=`, in the conversation `&session=` if given,
+/// as server-sent events: a `session` event with the conversation's id,
+/// a message per token, then a `done` event, or an `error` event if the
+/// provider fails part way.
+pub async fn ask_stream(
+    State(state): State<Arc<ServerState>>,
+    Query(query): Query<AskQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, (StatusCode, String)> {
+    let Some(provider) = state.ai_provider.read().await.clone() else {
+        return Err((StatusCode::SERVICE_UNAVAILABLE, "No AI provider is configured".to_string()));
+    };
+    let mut session = state.ask_sessions.write().await.open(query.session.as_deref());
+    let found = budgeted_subgraph(&state, provider.as_ref(), &query.q).await?;
+    let relevant = session.context(found);
+    let prompt = session.prompt(&query.q);
+    let estimate = Budget::estimate_tokens(&code_question_prompt(&prompt, &relevant.nodes, &relevant.edges));
+    admit(&state, provider.as_ref(), estimate).await?;
+    let tokens = provider
+        .stream_code_question(&prompt, &relevant.nodes, &relevant.edges)
+        .await
+        .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?;
+    charge(&state, provider.as_ref(), estimate).await;
+
+    let opened = Event::default().event("session").data(session.id.clone());
+    let answer = Arc::new(Mutex::new(Some(String::new())));
+    let streamed = Arc::clone(&answer);
+    // Without a closing event, browsers would reconnect and ask again
+    let events = stream::once(async { opened })
+        .chain(tokens.map(move |token| {
+            let mut answer = streamed.lock().unwrap();
+            match token {
+                Ok(token) => {
+                    if let Some(answer) = answer.as_mut() {
+                        answer.push_str(&token);
+                    }
+                    Event::default().data(token.replace('\r', ""))
+                }
+                Err(e) => {
+                    // A broken answer isn't worth following up
+                    *answer = None;
+                    Event::default().event("error").data(e.to_string().replace('\r', ""))
+                }
+            }
+        }))
+        .chain(stream::once(async move {
+            let answered = answer.lock().unwrap().take();
+            if let Some(answer) = answered {
+                session.record(&query.q, &answer, relevant);
+                state.ask_sessions.write().await.save(session);
+            }
+            Event::default().event("done").data("")
+        }))
+        .map(Ok);
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+/// Answer a question about the code from the nodes it's about and those
+/// it names, in the conversation `session` if given
+pub async fn ask(
+    State(state): State<Arc<ServerState>>,
+    Json(request): Json<AskRequest>,
+) -> Result<Json<AskResponse>, (StatusCode, String)> {
+    let Some(provider) = state.ai_provider.read().await.clone() else {
+        return Err((StatusCode::SERVICE_UNAVAILABLE, "No AI provider is configured".to_string()));
+    };
+    let path = match &request.path {
+        Some(path) if Path::new(path).components().any(|c| c == Component::ParentDir) => {
+            return Err((StatusCode::BAD_REQUEST, format!("{} leaves its directory", path)));
+        }
+        Some(path) => Some(state.root.read().await.as_deref().unwrap_or(Path::new("")).join(path)),
+        None => None,
+    };
+    let focused = {
+        let graph = state.graph.read().await;
+        if let Some(id) = request.focus
+            && graph.node(NodeId(id)).is_none()
+        {
+            return Err((StatusCode::NOT_FOUND, format!("No node {}", id)));
+        }
+        focused_subgraph(&graph, request.focus.map(NodeId), path.as_deref())
+    };
+
+    // What the question is about comes first, should there be too much
+    let mut session = state.ask_sessions.write().await.open(request.session.as_deref());
+    let found = budgeted_subgraph(&state, provider.as_ref(), &request.question).await?;
+    let relevant = session.context(Subgraph {
+        nodes: focused.nodes.into_iter().chain(found.nodes).collect(),
+        edges: focused.edges.into_iter().chain(found.edges).collect(),
+    });
+    let prompt = session.prompt(&request.question);
+    let estimate = Budget::estimate_tokens(&code_question_prompt(&prompt, &relevant.nodes, &relevant.edges));
+    admit(&state, provider.as_ref(), estimate).await?;
+    let answer = provider
+        .answer_code_question(&prompt, &relevant.nodes, &relevant.edges)
+        .await
+        .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?;
+    charge(&state, provider.as_ref(), estimate).await;
+
+    let referenced = referenced_nodes(&relevant, &answer).into_iter().map(|id| id.0).collect();
+    session.record(&request.question, &answer, relevant);
+    let id = session.id.clone();
+    state.ask_sessions.write().await.save(session);
+    Ok(Json(AskResponse { answer, session: id, referenced }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use canopy_core::{EdgeId, EdgeKind, EdgeSource, GraphEdge, GraphNode, NodeId, NodeKind};
+
+    fn add(graph: &mut Graph, name: &str) -> NodeId {
+        graph.add_node(GraphNode::for_test(NodeKind::Function, name).in_file("src/lib.rs"))
+    }
+
+    #[test]
+    fn test_relevant_subgraph() {
+        let mut graph = Graph::new();
+        let save = add(&mut graph, "save_user");
+        let caller = add(&mut graph, "register");
+        add(&mut graph, "unrelated");
+        graph.add_edge(GraphEdge {
+            id: EdgeId(0),
+            source: caller,
+            target: save,
+            kind: EdgeKind::Calls,
+            edge_source: EdgeSource::Structural,
+            confidence: 1.0,
+            label: None,
+            file_path: None,
+            line: None,
+        });
+
+        let relevant = relevant_subgraph(&graph, "Who calls save_user?");
+        let mut names: Vec<_> = relevant.nodes.iter().map(|n| n.name.as_str()).collect();
+        names.sort();
+        assert_eq!(names, ["register", "save_user"]);
+        assert_eq!(relevant.edges.len(), 1);
+        assert!(relevant_subgraph(&graph, "What is this?").nodes.is_empty());
+    }
+
+    struct Translator;
+
+    #[async_trait::async_trait]
+    impl AIProvider for Translator {
+        async fn analyze_semantic_relationships(
+            &self,
+            _request: canopy_ai::SemanticAnalysisRequest,
+        ) -> anyhow::Result<canopy_ai::SemanticAnalysisResult> {
+            anyhow::bail!("not used in this test")
+        }
+
+        async fn generate_node_summary(&self, _node: &GraphNode, _context: &canopy_ai::AnalysisContext) -> anyhow::Result<String> {
+            anyhow::bail!("not used in this test")
+        }
+
+        async fn translate_question(&self, question: &str) -> anyhow::Result<canopy_ai::GraphQuery> {
+            match question {
+                "Which functions call save_user?" => canopy_ai::GraphQuery::parse(
+                    r#"{"nodes": {"kinds": ["Function"]}, "related": {"edges": ["Calls"], "to": {"names": ["save_user"]}}}"#,
+                ),
+                _ => anyhow::bail!("not about the graph"),
+            }
+        }
+
+        async fn answer_code_question(&self, _question: &str, _nodes: &[GraphNode], _edges: &[GraphEdge]) -> anyhow::Result<String> {
+            anyhow::bail!("not used in this test")
+        }
+
+        fn name(&self) -> &str {
+            "translator"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_grounded_subgraph() {
+        let mut graph = Graph::new();
+        let save = add(&mut graph, "save_user");
+        let register = add(&mut graph, "register");
+        let import = add(&mut graph, "import_users");
+        for source in [register, import] {
+            graph.add_edge(GraphEdge {
+                id: EdgeId(0),
+                source,
+                target: save,
+                kind: EdgeKind::Calls,
+                edge_source: EdgeSource::Structural,
+                confidence: 1.0,
+                label: None,
+                file_path: None,
+                line: None,
+            });
+        }
+        let graph = RwLock::new(graph);
+
+        // The translated query finds both callers
+        let grounded = grounded_subgraph(&Translator, &graph, "Which functions call save_user?").await;
+        assert_eq!(grounded.nodes.len(), 3);
+        assert_eq!(grounded.edges.len(), 2);
+
+        // Untranslated questions fall back to the nodes they name
+        let named = grounded_subgraph(&Translator, &graph, "Tell me about register").await;
+        let names: Vec<_> = named.nodes.iter().map(|n| n.name.as_str()).collect();
+        assert_eq!(names.len(), 2);
+        assert!(names.contains(&"register"));
+    }
+
+    /// Answers with the nodes it was given and the question it was asked.
+    struct Echo;
+
+    #[async_trait::async_trait]
+    impl AIProvider for Echo {
+        async fn analyze_semantic_relationships(
+            &self,
+            _request: canopy_ai::SemanticAnalysisRequest,
+        ) -> anyhow::Result<canopy_ai::SemanticAnalysisResult> {
+            anyhow::bail!("not used in this test")
+        }
+
+        async fn generate_node_summary(&self, _node: &GraphNode, _context: &canopy_ai::AnalysisContext) -> anyhow::Result<String> {
+            anyhow::bail!("not used in this test")
+        }
+
+        async fn answer_code_question(&self, question: &str, nodes: &[GraphNode], _edges: &[GraphEdge]) -> anyhow::Result<String> {
+            let names: Vec<_> = nodes.iter().map(|n| n.name.as_str()).collect();
+            Ok(format!("[{}] {}", names.join(","), question))
+        }
+
+        fn name(&self) -> &str {
+            "echo"
+        }
+    }
+
+    /// The events of asking `question`, as (event, data) pairs.
+    async fn ask(state: &Arc<ServerState>, question: &str, session: Option<String>) -> Vec<(String, String)> {
+        use axum::response::IntoResponse;
+
+        let query = AskQuery { q: question.to_string(), session };
+        let response = ask_stream(State(Arc::clone(state)), Query(query)).await.unwrap().into_response();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8(body.to_vec())
+            .unwrap()
+            .split("\n\n")
+            .filter(|event| !event.is_empty())
+            .map(|event| {
+                let field = |name: &str| {
+                    event.lines().filter_map(|line| line.strip_prefix(name)).map(|v| v.strip_prefix(' ').unwrap_or(v)).collect::<Vec<_>>().join("\n")
+                };
+                let kind = field("event:");
+                (if kind.is_empty() { "message".to_string() } else { kind }, field("data:"))
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_ask_within_budget() {
+        let state = Arc::new(ServerState::new(Graph::new()));
+        state.set_ai_provider(Arc::new(Echo)).await;
+        let request = || Json(AskRequest { question: "What is save_user?".to_string(), session: None, focus: None, path: None });
+
+        let Json(answer) = super::ask(State(Arc::clone(&state)), request()).await.unwrap();
+        assert!(answer.answer.ends_with("What is save_user?"));
+        let used = state.ai_budget.read().await.tokens_used;
+        assert!(used > 0);
+
+        // Once the budget is spent, questions are refused before the
+        // provider is asked
+        state.ai_budget.write().await.total_tokens = used;
+        let (status, _) = super::ask(State(Arc::clone(&state)), request()).await.unwrap_err();
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(state.ai_budget.read().await.tokens_used, used);
+        assert_eq!(state.ai_budget.read().await.requests_skipped, 1);
+    }
+
+    #[tokio::test]
+    async fn test_ask_session() {
+        let mut graph = Graph::new();
+        add(&mut graph, "save_user");
+        let state = Arc::new(ServerState::new(graph));
+        state.set_ai_provider(Arc::new(Echo)).await;
+
+        let events = ask(&state, "What does save_user do?", None).await;
+        assert_eq!(events[0].0, "session");
+        assert_eq!(events[1], ("message".to_string(), "[save_user] What does save_user do?".to_string()));
+        assert_eq!(events[2].0, "done");
+
+        // The follow-up names nothing, but is answered from the node of
+        // the first question, and with it in the prompt
+        let id = events[0].1.clone();
+        let events = ask(&state, "Who calls that?", Some(id.clone())).await;
+        assert_eq!(events[0].1, id);
+        assert!(events[1].1.starts_with("[save_user] Earlier in this conversation:"));
+        assert!(events[1].1.contains("Q: What does save_user do?"));
+        assert!(events[1].1.ends_with("Who calls that?"));
+
+        // Without the id, it starts over
+        let events = ask(&state, "Who calls that?", None).await;
+        assert_ne!(events[0].1, id);
+        assert_eq!(events[1].1, "[] Who calls that?");
+    }
+
+    #[tokio::test]
+    async fn test_ask() {
+        let mut graph = Graph::new();
+        let save = add(&mut graph, "save_user");
+        let register = add(&mut graph, "register");
+        add(&mut graph, "unrelated");
+        let trim = add(&mut graph, "trim_name");
+        graph.node_mut(trim).unwrap().file_path = std::path::Path::new("src/util.rs").into();
+        graph.add_edge(GraphEdge {
+            id: EdgeId(0),
+            source: register,
+            target: save,
+            kind: EdgeKind::Calls,
+            edge_source: EdgeSource::Structural,
+            confidence: 1.0,
+            label: None,
+            file_path: None,
+            line: None,
+        });
+        let state = Arc::new(ServerState::new(graph));
+        let request = |focus: Option<NodeId>, path: Option<&str>, session: Option<String>| AskRequest {
+            question: "What does this do?".to_string(),
+            session,
+            focus: focus.map(|id| id.0),
+            path: path.map(str::to_string),
+        };
+        let error = super::ask(State(Arc::clone(&state)), Json(request(None, None, None))).await.unwrap_err();
+        assert_eq!(error.0, StatusCode::SERVICE_UNAVAILABLE);
+        state.set_ai_provider(Arc::new(Echo)).await;
+
+        // Answered from the focus and its neighbours, all of them named
+        let Json(answered) = super::ask(State(Arc::clone(&state)), Json(request(Some(register), None, None))).await.unwrap();
+        assert!(answered.answer.ends_with("] What does this do?"));
+        let mut referenced = answered.referenced.clone();
+        referenced.sort();
+        assert_eq!(referenced, [save.0, register.0]);
+
+        // A follow-up about a file adds its nodes to the conversation's
+        let session = Some(answered.session.clone());
+        let Json(followed) = super::ask(State(Arc::clone(&state)), Json(request(None, Some("src/util.rs"), session))).await.unwrap();
+        assert_eq!(followed.session, answered.session);
+        assert!(followed.answer.starts_with("[trim_name,"), "{}", followed.answer);
+        assert!(!followed.answer.contains("unrelated"), "{}", followed.answer);
+        assert_eq!(followed.referenced.len(), 3);
+
+        let error = super::ask(State(Arc::clone(&state)), Json(request(Some(NodeId(99)), None, None))).await.unwrap_err();
+        assert_eq!(error.0, StatusCode::NOT_FOUND);
+        let error = super::ask(State(state), Json(request(None, Some("../secrets"), None))).await.unwrap_err();
+        assert_eq!(error.0, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_referenced_nodes() {
+        let mut graph = Graph::new();
+        let save = add(&mut graph, "save_user");
+        add(&mut graph, "user");
+        let context = Subgraph { nodes: graph.all_nodes().cloned().collect(), edges: Vec::new() };
+
+        // Whole names count, not words of them
+        assert_eq!(referenced_nodes(&context, "It calls `save_user()` once."), [save]);
+        assert!(referenced_nodes(&context, "Nothing here saves.").is_empty());
+    }
+}
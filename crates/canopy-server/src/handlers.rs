@@ -3,19 +3,51 @@
 use std::sync::Arc;
 
 use axum::{
-    extract::State,
-    http::StatusCode,
+    body::Body,
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
     response::{IntoResponse, Json},
 };
-use serde::Serialize;
+use canopy_ai::budget::ModelSpend;
+use canopy_ai::Budget;
+use canopy_core::{
+    export, AggregatedEdge, EdgeId, EdgeKind, EdgeSource, GraphDiff, GraphEdge, GraphNode, GraphStats, Language, NodeId, NodeKind, SearchFilter,
+    ViewState,
+};
+use futures_util::{stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Mutex;
 
 use crate::ServerState;
 
 /// Response structure for the graph API
 #[derive(Debug, Serialize)]
 pub struct GraphResponse {
-    pub nodes: Vec<NodeResponse>,
+    /// Each with the fields asked for, or all of them
+    pub nodes: Vec<serde_json::Value>,
+    /// The edges from the nodes
     pub edges: Vec<EdgeResponse>,
+    /// Where the next page starts, if there is one
+    pub next_cursor: Option<u64>,
+}
+
+/// Most nodes in a page of the graph, whatever the query's `limit`
+pub const MAX_GRAPH_LIMIT: usize = 10_000;
+
+/// Query of the graph API: `?scope=src/db&fields=name,kind&cursor=17&limit=1000`
+#[derive(Debug, Deserialize)]
+pub struct GraphQuery {
+    /// Only the nodes of this file or directory, as the graph's nodes give
+    /// it or relative to the project's root, and the edges between them
+    pub scope: Option<String>,
+    /// Comma-separated fields of [`NodeResponse`] to send of each node;
+    /// its `id` is always sent
+    pub fields: Option<String>,
+    /// The `next_cursor` of the page before; the first page if absent
+    pub cursor: Option<u64>,
+    /// Nodes in a page; all of them unless given
+    pub limit: Option<usize>,
 }
 
 /// Simplified node representation for the API
@@ -32,6 +64,107 @@ pub struct NodeResponse {
     pub is_container: bool,
     pub child_count: u32,
     pub loc: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub complexity: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parameter_count: Option<u32>,
+    /// Where a file failed to parse, if it was only partly indexed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parse_errors: Option<String>,
+    /// What the node does, or for a file or directory what it's for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ai_summary: Option<String>,
+}
+
+impl NodeResponse {
+    /// The fields a node is sent with, some only when they have a value
+    pub const FIELDS: &[&str] = &[
+        "id",
+        "kind",
+        "name",
+        "qualified_name",
+        "file_path",
+        "line_start",
+        "line_end",
+        "language",
+        "is_container",
+        "child_count",
+        "loc",
+        "complexity",
+        "parameter_count",
+        "parse_errors",
+        "ai_summary",
+    ];
+}
+
+impl From<&GraphNode> for NodeResponse {
+    fn from(node: &GraphNode) -> Self {
+        Self {
+            id: node.id.0,
+            kind: format!("{:?}", node.kind),
+            name: node.name.clone(),
+            qualified_name: node.qualified_name.to_string(),
+            file_path: node.file_path.to_string_lossy().to_string(),
+            line_start: node.line_start,
+            line_end: node.line_end,
+            language: node.language.map(|l| format!("{:?}", l)),
+            is_container: node.is_container,
+            child_count: node.child_count,
+            loc: node.loc,
+            complexity: node.metadata.complexity,
+            parameter_count: node.metadata.parameter_count,
+            // The indexer's `PARSE_ERRORS_KEY`
+            parse_errors: node.metadata.extra.get("parse_errors").cloned(),
+            ai_summary: node.metadata.extra.get("ai_summary").cloned(),
+        }
+    }
+}
+
+/// A node in full, for the side panel: the node as in the graph, the rest
+/// of its metadata, and its edges
+#[derive(Debug, Serialize)]
+pub struct NodeDetailResponse {
+    #[serde(flatten)]
+    pub node: NodeResponse,
+    pub visibility: Option<String>,
+    pub signature: Option<String>,
+    pub doc_summary: Option<String>,
+    pub deprecated: Option<String>,
+    pub is_test: bool,
+    /// Metadata of extractors and AI analysis, by key
+    pub extra: BTreeMap<String, String>,
+    /// Edges into the node, from their sources
+    pub incoming: Vec<EdgeSummary>,
+    /// Edges out of the node, to their targets
+    pub outgoing: Vec<EdgeSummary>,
+}
+
+/// An edge of a node, with the node at its other end
+#[derive(Debug, Serialize)]
+pub struct EdgeSummary {
+    pub id: u64,
+    pub kind: String,
+    pub edge_source: String,
+    pub confidence: f32,
+    pub label: Option<String>,
+    pub node: u64,
+    pub node_name: String,
+    pub node_kind: String,
+}
+
+impl EdgeSummary {
+    fn new(edge: &GraphEdge, other: &GraphNode) -> Self {
+        Self {
+            id: edge.id.0,
+            kind: format!("{:?}", edge.kind),
+            edge_source: format!("{:?}", edge.edge_source),
+            confidence: edge.confidence,
+            label: edge.label.clone(),
+            node: other.id.0,
+            node_name: other.name.clone(),
+            node_kind: format!("{:?}", other.kind),
+        }
+    }
 }
 
 /// Simplified edge representation for the API
@@ -46,6 +179,239 @@ pub struct EdgeResponse {
     pub label: Option<String>,
 }
 
+/// Most matches a search returns, whatever its `limit`
+pub const MAX_SEARCH_LIMIT: usize = 100;
+
+/// Query of the search API: `?q=user&kind=Function&lang=Rust&limit=20`
+#[derive(Debug, Deserialize)]
+pub struct SearchQuery {
+    pub q: String,
+    pub kind: Option<NodeKind>,
+    pub lang: Option<Language>,
+    pub limit: Option<usize>,
+}
+
+/// A node matching a search, with where it is
+#[derive(Debug, Serialize)]
+pub struct SearchResult {
+    pub id: u64,
+    pub name: String,
+    pub qualified_name: String,
+    pub kind: String,
+    pub file_path: String,
+    pub line_start: Option<u32>,
+    pub line_end: Option<u32>,
+    /// Higher is better; ranks the results, no more
+    pub score: u32,
+}
+
+impl From<&GraphEdge> for EdgeResponse {
+    fn from(edge: &GraphEdge) -> Self {
+        Self {
+            id: edge.id.0,
+            source: edge.source.0,
+            target: edge.target.0,
+            kind: format!("{:?}", edge.kind),
+            edge_source: format!("{:?}", edge.edge_source),
+            confidence: edge.confidence,
+            label: edge.label.clone(),
+        }
+    }
+}
+
+/// Paths found unless the query asks for fewer or more
+pub const DEFAULT_PATH_LIMIT: usize = 5;
+
+/// Most paths returned, whatever the query's `limit`
+pub const MAX_PATH_LIMIT: usize = 50;
+
+/// Query of the path API: `?from=3&to=42&kinds=Calls,Imports&directed=true`
+#[derive(Debug, Deserialize)]
+pub struct PathQuery {
+    pub from: u64,
+    pub to: u64,
+    /// Comma-separated edge kinds to follow; every kind but `Contains` if
+    /// absent
+    pub kinds: Option<String>,
+    /// Follow edges only from source to target
+    #[serde(default)]
+    pub directed: bool,
+    pub limit: Option<usize>,
+}
+
+/// A path between two nodes
+#[derive(Debug, Serialize)]
+pub struct PathResponse {
+    /// From the first node to the last
+    pub nodes: Vec<NodeResponse>,
+    /// The edge between each two nodes, which may point either way unless
+    /// the path is directed
+    pub edges: Vec<EdgeResponse>,
+}
+
+/// Formats the graph is exported in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Dot,
+    Graphml,
+    Mermaid,
+    Json,
+}
+
+impl ExportFormat {
+    fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Dot => "text/vnd.graphviz",
+            ExportFormat::Graphml => "application/graphml+xml",
+            ExportFormat::Mermaid => "text/plain; charset=utf-8",
+            ExportFormat::Json => "application/json",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Dot => "dot",
+            ExportFormat::Graphml => "graphml",
+            ExportFormat::Mermaid => "mmd",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+/// Query of the export API: `?format=dot&scope=src/db`
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    pub format: ExportFormat,
+    /// Only the nodes of this file or directory, as the graph's nodes give
+    /// it or relative to the project's root, and the edges between them
+    pub scope: Option<String>,
+}
+
+/// Levels of the hierarchy shown unless the query asks for more or fewer
+pub const DEFAULT_AGGREGATED_DEPTH: usize = 1;
+
+/// Query of the aggregated graph API: `?expanded=4,9&depth=1`
+#[derive(Debug, Deserialize)]
+pub struct AggregatedQuery {
+    /// Comma-separated ids of containers to expand
+    pub expanded: Option<String>,
+    /// Containers fewer than this many levels below a root are expanded
+    /// too
+    pub depth: Option<usize>,
+}
+
+/// The graph as a view shows it: the visible nodes, and the edges of the
+/// hidden ones lifted onto their nearest visible container
+#[derive(Debug, Serialize)]
+pub struct AggregatedResponse {
+    pub nodes: Vec<NodeResponse>,
+    /// The visible containers that are not expanded
+    pub collapsed: Vec<u64>,
+    pub edges: Vec<AggregatedEdgeResponse>,
+}
+
+/// The edges between two visible nodes, with those of the nodes they hide
+#[derive(Debug, Serialize)]
+pub struct AggregatedEdgeResponse {
+    pub source: u64,
+    pub target: u64,
+    pub count: u32,
+    pub kind_counts: BTreeMap<String, u32>,
+    /// Lowest confidence of the AI edges among them, if any
+    pub min_confidence: Option<f32>,
+}
+
+impl From<&AggregatedEdge> for AggregatedEdgeResponse {
+    fn from(edge: &AggregatedEdge) -> Self {
+        Self {
+            source: edge.source.0,
+            target: edge.target.0,
+            count: edge.count,
+            kind_counts: edge.kind_counts.iter().map(|(kind, count)| (format!("{:?}", kind), *count)).collect(),
+            min_confidence: edge.min_confidence,
+        }
+    }
+}
+
+/// Edges in a page unless the query asks for fewer or more
+pub const DEFAULT_EDGE_LIMIT: usize = 100;
+
+/// Most edges in a page, whatever the query's `limit`
+pub const MAX_EDGE_LIMIT: usize = 1000;
+
+/// Query of the edges API:
+/// `?kind=Calls&edge_source=AI&min_confidence=0.5&source=3&target=42&cursor=17&limit=100`
+#[derive(Debug, Deserialize)]
+pub struct EdgesQuery {
+    pub kind: Option<EdgeKind>,
+    pub edge_source: Option<EdgeSource>,
+    pub min_confidence: Option<f32>,
+    /// Only edges from this node
+    pub source: Option<u64>,
+    /// Only edges to this node
+    pub target: Option<u64>,
+    /// The `next_cursor` of the page before; the first page if absent
+    pub cursor: Option<u64>,
+    pub limit: Option<usize>,
+}
+
+/// A page of edges, in order of id
+#[derive(Debug, Serialize)]
+pub struct EdgesResponse {
+    pub edges: Vec<EdgeResponse>,
+    /// Where the next page starts, if there is one
+    pub next_cursor: Option<u64>,
+}
+
+/// Query of the diffs API: `?since=42`
+#[derive(Debug, Deserialize)]
+pub struct DiffsQuery {
+    /// The last sequence number seen; only the current one is returned
+    /// without it
+    pub since: Option<u64>,
+}
+
+/// The diffs after the sequence number asked for, oldest first
+#[derive(Debug, Serialize)]
+pub struct DiffsResponse {
+    /// The last diff's sequence number, to pass as `since` next time
+    pub sequence: u64,
+    pub diffs: Vec<GraphDiff>,
+}
+
+/// Largest file the file API returns
+pub const MAX_FILE_BYTES: u64 = 2 * 1024 * 1024;
+
+/// Query of the file API: `?path=src/lib.rs`
+#[derive(Debug, Deserialize)]
+pub struct FileQuery {
+    /// As the graph's nodes give it, or relative to the project's root
+    pub path: String,
+}
+
+/// A file's content, and where its symbols are in it
+#[derive(Debug, Serialize)]
+pub struct FileResponse {
+    /// As the graph's nodes give it
+    pub path: String,
+    pub language: Option<String>,
+    pub total_lines: usize,
+    pub content: String,
+    /// The nodes with lines in the file, in the order they start
+    pub symbols: Vec<SymbolSpan>,
+}
+
+/// Where a node is in its file
+#[derive(Debug, Serialize)]
+pub struct SymbolSpan {
+    pub id: u64,
+    pub name: String,
+    pub kind: String,
+    pub line_start: u32,
+    pub line_end: u32,
+}
+
 /// Health check response
 #[derive(Debug, Serialize)]
 pub struct HealthResponse {
@@ -53,53 +419,451 @@ pub struct HealthResponse {
     pub version: String,
 }
 
-/// Get the current graph as JSON
+/// AI spend response
+#[derive(Debug, Serialize)]
+pub struct SpendResponse {
+    pub tokens_used: u32,
+    pub total_tokens: u32,
+    pub cost_usd: f64,
+    pub models: BTreeMap<String, ModelSpend>,
+    /// AI requests not sent because the token budget ran out
+    pub requests_skipped: u32,
+}
+
+impl From<&Budget> for SpendResponse {
+    fn from(budget: &Budget) -> Self {
+        Self {
+            tokens_used: budget.tokens_used,
+            total_tokens: budget.total_tokens,
+            cost_usd: budget.spend.cost_usd,
+            models: budget.spend.models.clone(),
+            requests_skipped: budget.requests_skipped,
+        }
+    }
+}
+
+/// Query of the stats API: `?top=10`
+#[derive(Debug, Deserialize)]
+pub struct StatsQuery {
+    /// Entries in each ranking, [`canopy_core::stats::DEFAULT_TOP_N`]
+    /// unless given
+    pub top: Option<usize>,
+}
+
+/// Stats of the graph, and of AI analysis, for the dashboard
+#[derive(Debug, Serialize)]
+pub struct StatsResponse {
+    #[serde(flatten)]
+    pub graph: GraphStats,
+    pub ai: SpendResponse,
+}
+
+/// A file or directory asked for, as the graph's nodes give it or relative
+/// to the project's root
+struct Scope {
+    path: std::path::PathBuf,
+    under_root: Option<std::path::PathBuf>,
+}
+
+impl Scope {
+    /// `scope`, or 400 if it climbs out of its directory
+    async fn resolve(state: &ServerState, scope: &str) -> Result<Self, (StatusCode, String)> {
+        let path = std::path::PathBuf::from(scope);
+        if path.components().any(|c| c == std::path::Component::ParentDir) {
+            return Err((StatusCode::BAD_REQUEST, format!("{} leaves its directory", scope)));
+        }
+        let under_root = state.root.read().await.as_ref().map(|root| root.join(&path));
+        Ok(Self { path, under_root })
+    }
+
+    fn contains(&self, node: &GraphNode) -> bool {
+        node.file_path.starts_with(&self.path) || self.under_root.as_ref().is_some_and(|root| node.file_path.starts_with(root))
+    }
+}
+
+/// Get the graph as JSON, in order of node id: all of it, or a page at a
+/// time with the edges from that page's nodes, of the whole graph or of a
+/// scope, with every field of the nodes or those asked for
 pub async fn get_graph(
     State(state): State<Arc<ServerState>>,
-) -> Result<impl IntoResponse, StatusCode> {
+    Query(query): Query<GraphQuery>,
+) -> Result<Json<GraphResponse>, (StatusCode, String)> {
+    let fields: Option<Vec<&str>> = query.fields.as_deref().map(|f| f.split(',').map(str::trim).filter(|f| !f.is_empty()).collect());
+    if let Some(unknown) = fields.iter().flatten().find(|f| !NodeResponse::FIELDS.contains(f)) {
+        return Err((StatusCode::BAD_REQUEST, format!("Unknown node field {}", unknown)));
+    }
+    let scope = match &query.scope {
+        Some(scope) => Some(Scope::resolve(&state, scope).await?),
+        None => None,
+    };
+    let in_scope = |node: &GraphNode| scope.as_ref().is_none_or(|scope| scope.contains(node));
+
+    let graph = state.graph.read().await;
+    let mut nodes: Vec<&GraphNode> = graph
+        .all_nodes()
+        .filter(|n| query.cursor.is_none_or(|cursor| n.id.0 > cursor))
+        .filter(|n| in_scope(n))
+        .collect();
+    nodes.sort_by_key(|n| n.id.0);
+    let limit = query.limit.map_or(usize::MAX, |limit| limit.clamp(1, MAX_GRAPH_LIMIT));
+    let next_cursor = if nodes.len() > limit { Some(nodes[limit - 1].id.0) } else { None };
+    nodes.truncate(limit);
+
+    let edges = nodes
+        .iter()
+        .flat_map(|n| graph.edges_from(n.id))
+        .filter(|e| scope.is_none() || graph.node(e.target).is_some_and(in_scope))
+        .map(EdgeResponse::from)
+        .collect();
+    let nodes = nodes
+        .into_iter()
+        .map(|node| {
+            let mut value = serde_json::to_value(NodeResponse::from(node)).unwrap_or_default();
+            if let (Some(fields), Some(object)) = (&fields, value.as_object_mut()) {
+                object.retain(|key, _| key == "id" || fields.contains(&key.as_str()));
+            }
+            value
+        })
+        .collect();
+    Ok(Json(GraphResponse { nodes, edges, next_cursor }))
+}
+
+/// The visible nodes and aggregated edges of the view with the containers
+/// in `expanded`, and those above `depth`, expanded
+pub async fn get_aggregated_graph(
+    State(state): State<Arc<ServerState>>,
+    Query(query): Query<AggregatedQuery>,
+) -> Result<Json<AggregatedResponse>, (StatusCode, String)> {
+    let expanded: Vec<NodeId> = query
+        .expanded
+        .iter()
+        .flat_map(|ids| ids.split(','))
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(|id| id.parse().map(NodeId).map_err(|_| (StatusCode::BAD_REQUEST, format!("Bad node id {}", id))))
+        .collect::<Result<_, _>>()?;
+
+    let graph = state.graph.read().await;
+    let mut view = ViewState::expanded_to_depth(&graph, query.depth.unwrap_or(DEFAULT_AGGREGATED_DEPTH));
+    for id in expanded {
+        view.expand(&graph, id);
+    }
+    let mut nodes: Vec<&GraphNode> = view.visible_nodes(&graph).into_iter().filter_map(|id| graph.node(id)).collect();
+    nodes.sort_by_key(|n| n.id.0);
+    let mut collapsed: Vec<u64> = view.collapsed_nodes(&graph).into_iter().map(|id| id.0).collect();
+    collapsed.sort_unstable();
+    let mut edges: Vec<AggregatedEdgeResponse> = view.aggregated_edges(&graph).iter().map(AggregatedEdgeResponse::from).collect();
+    edges.sort_by_key(|e| (e.source, e.target));
+    Ok(Json(AggregatedResponse {
+        nodes: nodes.into_iter().map(NodeResponse::from).collect(),
+        collapsed,
+        edges,
+    }))
+}
+
+/// Nodes or edges exported for each hold of the graph's lock, so a slow
+/// download doesn't keep the watcher from updating it
+const EXPORT_BATCH: usize = 500;
+
+/// What a streamed export has written so far
+#[derive(Default)]
+struct Exported {
+    nodes: HashSet<NodeId>,
+    edges: usize,
+}
+
+/// The graph, or the part of it in scope, as a file to download in the
+/// format asked for, streamed a batch of nodes and edges at a time. Nodes
+/// removed while it streams are left out, with their edges. Mermaid draws
+/// the contents of the topmost container
+pub async fn export_graph(
+    State(state): State<Arc<ServerState>>,
+    Query(query): Query<ExportQuery>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let scope = match &query.scope {
+        Some(scope) => Some(Scope::resolve(&state, scope).await?),
+        None => None,
+    };
+    let in_scope = |node: &GraphNode| scope.as_ref().is_none_or(|scope| scope.contains(node));
+    let name = query
+        .scope
+        .as_deref()
+        .and_then(|scope| std::path::Path::new(scope).file_name())
+        .map_or("canopy".to_string(), |name| format!("canopy-{}", name.to_string_lossy()));
+    let disposition = format!("attachment; filename=\"{}.{}\"", name.replace('"', ""), query.format.extension());
+    let headers = [(header::CONTENT_TYPE, query.format.content_type().to_string()), (header::CONTENT_DISPOSITION, disposition)];
+
+    let graph = state.graph.read().await;
+    let mut nodes: Vec<NodeId> = graph.all_nodes().filter(|n| in_scope(n)).map(|n| n.id).collect();
+    if nodes.is_empty() {
+        return Err((StatusCode::NOT_FOUND, "Nothing to export".to_string()));
+    }
+    let format = match query.format {
+        ExportFormat::Dot => export::StreamFormat::Dot,
+        ExportFormat::Graphml => export::StreamFormat::Graphml,
+        ExportFormat::Json => export::StreamFormat::Json,
+        // A flowchart is drawn from a few dozen nodes at most
+        ExportFormat::Mermaid => {
+            let graph = graph.filter_nodes(in_scope);
+            let contains_children = |id| graph.edges_from(id).any(|e| e.kind == EdgeKind::Contains);
+            let is_root = |id| !graph.edges_to(id).any(|e| e.kind == EdgeKind::Contains);
+            let root = graph
+                .all_nodes()
+                .map(|n| n.id)
+                .filter(|&id| is_root(id) && contains_children(id))
+                .min_by_key(|id| id.0)
+                .ok_or((StatusCode::NOT_FOUND, "No container to draw".to_string()))?;
+            let body = export::to_mermaid(&graph, &export::MermaidScope::new(root));
+            return Ok((headers, Body::from(body)));
+        }
+    };
+    let mut edges: Vec<EdgeId> = graph.all_edges().map(|e| e.id).collect();
+    drop(graph);
+    nodes.sort_unstable_by_key(|id| id.0);
+    edges.sort_unstable_by_key(|id| id.0);
+
+    let exported = Arc::new(Mutex::new(Exported::default()));
+    let node_batches = stream::iter(nodes.chunks(EXPORT_BATCH).map(<[NodeId]>::to_vec).collect::<Vec<_>>()).then({
+        let (graph, exported) = (Arc::clone(&state.graph), Arc::clone(&exported));
+        move |batch| {
+            let (graph, exported) = (Arc::clone(&graph), Arc::clone(&exported));
+            async move {
+                let graph = graph.read().await;
+                let mut exported = exported.lock().unwrap();
+                let mut chunk = String::new();
+                for node in batch.iter().filter_map(|&id| graph.node(id)) {
+                    chunk.push_str(&format.node(node, exported.nodes.len())?);
+                    exported.nodes.insert(node.id);
+                }
+                Ok(chunk)
+            }
+        }
+    });
+    let edge_batches = stream::iter(edges.chunks(EXPORT_BATCH).map(<[EdgeId]>::to_vec).collect::<Vec<_>>()).then({
+        let graph = Arc::clone(&state.graph);
+        move |batch| {
+            let (graph, exported) = (Arc::clone(&graph), Arc::clone(&exported));
+            async move {
+                let graph = graph.read().await;
+                let mut exported = exported.lock().unwrap();
+                let mut chunk = String::new();
+                for edge in batch.iter().filter_map(|&id| graph.edge(id)) {
+                    if exported.nodes.contains(&edge.source) && exported.nodes.contains(&edge.target) {
+                        chunk.push_str(&format.edge(edge, exported.edges)?);
+                        exported.edges += 1;
+                    }
+                }
+                Ok(chunk)
+            }
+        }
+    });
+    let body = stream::once(async move { Ok::<_, serde_json::Error>(format.header()) })
+        .chain(node_batches)
+        .chain(stream::once(async move { Ok(format.between().to_string()) }))
+        .chain(edge_batches)
+        .chain(stream::once(async move { Ok(format.footer().to_string()) }));
+    Ok((headers, Body::from_stream(body)))
+}
+
+/// Get one node with its metadata and edges, or 404 if there's no such
+/// node
+pub async fn get_node(
+    State(state): State<Arc<ServerState>>,
+    Path(id): Path<u64>,
+) -> Result<Json<NodeDetailResponse>, (StatusCode, String)> {
     let graph = state.graph.read().await;
-    
-    // Collect all nodes
-    let mut nodes = Vec::new();
-    // We need to iterate through the graph to get all nodes
-    // Since Graph doesn't expose a direct nodes iterator, we'll use a workaround
-    // by checking node indices from 0 to node_count
-    for i in 0..graph.node_count() {
-        let node_id = canopy_core::NodeId(i as u64);
-        if let Some(node) = graph.node(node_id) {
-            nodes.push(NodeResponse {
+    let node = graph.node(NodeId(id)).ok_or((StatusCode::NOT_FOUND, format!("No node {}", id)))?;
+    let summaries = |edges: Vec<(&GraphEdge, NodeId)>| -> Vec<EdgeSummary> {
+        edges
+            .into_iter()
+            .filter_map(|(edge, other)| Some(EdgeSummary::new(edge, graph.node(other)?)))
+            .collect()
+    };
+    let metadata = &node.metadata;
+    Ok(Json(NodeDetailResponse {
+        node: NodeResponse::from(node),
+        visibility: metadata.visibility.map(|v| format!("{:?}", v)),
+        signature: metadata.signature.clone(),
+        doc_summary: metadata.doc_summary.clone(),
+        deprecated: metadata.deprecated.clone(),
+        is_test: metadata.is_test,
+        extra: metadata.extra.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+        incoming: summaries(graph.edges_to(node.id).map(|e| (e, e.source)).collect()),
+        outgoing: summaries(graph.edges_from(node.id).map(|e| (e, e.target)).collect()),
+    }))
+}
+
+/// Search nodes by name, fuzzily, best matches first
+pub async fn search(
+    State(state): State<Arc<ServerState>>,
+    Query(query): Query<SearchQuery>,
+) -> Json<Vec<SearchResult>> {
+    let filter = SearchFilter {
+        kind: query.kind,
+        language: query.lang,
+        limit: query.limit.unwrap_or(canopy_core::search::DEFAULT_SEARCH_LIMIT).min(MAX_SEARCH_LIMIT),
+    };
+    let graph = state.graph.read().await;
+    let results = graph
+        .search(&query.q, &filter)
+        .into_iter()
+        .filter_map(|m| {
+            let node = graph.node(m.id)?;
+            Some(SearchResult {
                 id: node.id.0,
-                kind: format!("{:?}", node.kind),
                 name: node.name.clone(),
-                qualified_name: node.qualified_name.clone(),
+                qualified_name: node.qualified_name.to_string(),
+                kind: format!("{:?}", node.kind),
                 file_path: node.file_path.to_string_lossy().to_string(),
                 line_start: node.line_start,
                 line_end: node.line_end,
-                language: node.language.map(|l| format!("{:?}", l)),
-                is_container: node.is_container,
-                child_count: node.child_count,
-                loc: node.loc,
-            });
+                score: m.score,
+            })
+        })
+        .collect();
+    Json(results)
+}
+
+/// The shortest paths between two nodes, along the edge kinds asked for
+pub async fn find_paths(
+    State(state): State<Arc<ServerState>>,
+    Query(query): Query<PathQuery>,
+) -> Result<Json<Vec<PathResponse>>, (StatusCode, String)> {
+    let kinds: Vec<EdgeKind> = match &query.kinds {
+        Some(kinds) => kinds
+            .split(',')
+            .map(str::trim)
+            .filter(|kind| !kind.is_empty())
+            .map(|kind| {
+                serde_json::from_value(serde_json::Value::String(kind.to_string()))
+                    .map_err(|_| (StatusCode::BAD_REQUEST, format!("Unknown edge kind {}", kind)))
+            })
+            .collect::<Result<_, _>>()?,
+        None => EdgeKind::ALL.iter().copied().filter(|&kind| kind != EdgeKind::Contains).collect(),
+    };
+    let graph = state.graph.read().await;
+    for id in [query.from, query.to] {
+        if graph.node(NodeId(id)).is_none() {
+            return Err((StatusCode::NOT_FOUND, format!("No node {}", id)));
         }
     }
+    let limit = query.limit.unwrap_or(DEFAULT_PATH_LIMIT).min(MAX_PATH_LIMIT);
+    let paths = graph
+        .shortest_paths(NodeId(query.from), NodeId(query.to), &kinds, query.directed, limit)
+        .into_iter()
+        .map(|path| PathResponse {
+            nodes: path.nodes.iter().filter_map(|&id| graph.node(id)).map(NodeResponse::from).collect(),
+            edges: path.edges.iter().filter_map(|&id| graph.edge(id)).map(EdgeResponse::from).collect(),
+        })
+        .collect();
+    Ok(Json(paths))
+}
 
-    // Collect all edges
-    let mut edges = Vec::new();
-    // We need to iterate through all possible edge indices
-    for edge_ref in graph.all_edges() {
-        edges.push(EdgeResponse {
-            id: edge_ref.id.0,
-            source: edge_ref.source.0,
-            target: edge_ref.target.0,
-            kind: format!("{:?}", edge_ref.kind),
-            edge_source: format!("{:?}", edge_ref.edge_source),
-            confidence: edge_ref.confidence,
-            label: edge_ref.label.clone(),
-        });
+/// The edges matching the query's filters, a page at a time
+pub async fn list_edges(
+    State(state): State<Arc<ServerState>>,
+    Query(query): Query<EdgesQuery>,
+) -> Json<EdgesResponse> {
+    let graph = state.graph.read().await;
+    // From or to a node, only its edges need looking at
+    let candidates: Vec<&GraphEdge> = match (query.source, query.target) {
+        (Some(source), _) => graph.edges_from(NodeId(source)).collect(),
+        (None, Some(target)) => graph.edges_to(NodeId(target)).collect(),
+        (None, None) => graph.all_edges().collect(),
+    };
+    let mut edges: Vec<&GraphEdge> = candidates
+        .into_iter()
+        .filter(|e| query.target.is_none_or(|target| e.target.0 == target))
+        .filter(|e| query.kind.is_none_or(|kind| e.kind == kind))
+        .filter(|e| query.edge_source.is_none_or(|source| e.edge_source == source))
+        .filter(|e| query.min_confidence.is_none_or(|min| e.confidence >= min))
+        .filter(|e| query.cursor.is_none_or(|cursor| e.id.0 > cursor))
+        .collect();
+    edges.sort_by_key(|e| e.id.0);
+
+    let limit = query.limit.unwrap_or(DEFAULT_EDGE_LIMIT).clamp(1, MAX_EDGE_LIMIT);
+    let next_cursor = if edges.len() > limit { Some(edges[limit - 1].id.0) } else { None };
+    Json(EdgesResponse {
+        edges: edges.into_iter().take(limit).map(EdgeResponse::from).collect(),
+        next_cursor,
+    })
+}
+
+/// The diffs made to the graph since a sequence number, for clients that
+/// poll rather than hold a WebSocket open. Returns 410 if some of them are
+/// no longer kept, or the sequence number is from an earlier run: the
+/// client then fetches the graph again
+pub async fn get_diffs(
+    State(state): State<Arc<ServerState>>,
+    Query(query): Query<DiffsQuery>,
+) -> Result<Json<DiffsResponse>, (StatusCode, String)> {
+    let journal = state.journal.read().await;
+    let sequence = journal.sequence();
+    let diffs = match query.since {
+        Some(since) => journal.since(since).ok_or((
+            StatusCode::GONE,
+            format!("Diffs since {} are no longer kept; the current sequence is {}", since, sequence),
+        ))?,
+        None => Vec::new(),
+    };
+    Ok(Json(DiffsResponse { sequence, diffs }))
+}
+
+/// A file's content with the spans of the symbols in it, for the code
+/// viewer. Only files in the graph are served: 404 for any other, 400 for a
+/// path climbing out of its directory, 403 for a sensitive file such as a
+/// key or credentials, and 413 past [`MAX_FILE_BYTES`]
+pub async fn get_file(
+    State(state): State<Arc<ServerState>>,
+    Query(query): Query<FileQuery>,
+) -> Result<Json<FileResponse>, (StatusCode, String)> {
+    let asked = std::path::Path::new(&query.path);
+    if asked.components().any(|c| c == std::path::Component::ParentDir) {
+        return Err((StatusCode::BAD_REQUEST, format!("{} leaves its directory", query.path)));
     }
+    let under_root = state.root.read().await.as_ref().map(|root| root.join(asked));
+    let (path, language, mut symbols) = {
+        let graph = state.graph.read().await;
+        let file = graph
+            .all_nodes()
+            .filter(|n| n.kind == NodeKind::File)
+            .find(|n| &*n.file_path == asked || under_root.as_deref() == Some(&*n.file_path))
+            .ok_or((StatusCode::NOT_FOUND, format!("No file {}", query.path)))?;
+        if state.sensitive_files.read().await.is_sensitive(&file.file_path) {
+            return Err((StatusCode::FORBIDDEN, format!("{} is not served", query.path)));
+        }
+        let symbols: Vec<SymbolSpan> = graph
+            .all_nodes()
+            .filter(|n| n.id != file.id && n.file_path == file.file_path)
+            .filter_map(|n| {
+                let line_start = n.line_start?;
+                Some(SymbolSpan {
+                    id: n.id.0,
+                    name: n.name.clone(),
+                    kind: format!("{:?}", n.kind),
+                    line_start,
+                    line_end: n.line_end.unwrap_or(line_start),
+                })
+            })
+            .collect();
+        (file.file_path.to_path_buf(), file.language.map(|l| format!("{:?}", l)), symbols)
+    };
+    symbols.sort_by_key(|s| (s.line_start, std::cmp::Reverse(s.line_end), s.id));
 
-    let response = GraphResponse { nodes, edges };
-    Ok(Json(response))
+    let unreadable = |e: std::io::Error| (StatusCode::NOT_FOUND, format!("Cannot read {}: {}", path.display(), e));
+    let size = tokio::fs::metadata(&path).await.map_err(unreadable)?.len();
+    if size > MAX_FILE_BYTES {
+        return Err((StatusCode::PAYLOAD_TOO_LARGE, format!("{} is {} bytes", path.display(), size)));
+    }
+    let bytes = tokio::fs::read(&path).await.map_err(unreadable)?;
+    let content = String::from_utf8_lossy(&bytes).into_owned();
+    Ok(Json(FileResponse {
+        path: path.to_string_lossy().to_string(),
+        language,
+        total_lines: content.lines().count(),
+        content,
+        symbols,
+    }))
 }
 
 /// Health check endpoint
@@ -111,6 +875,19 @@ pub async fn health_check() -> impl IntoResponse {
     Json(health)
 }
 
+/// What AI analysis has used and cost so far
+pub async fn get_ai_spend(State(state): State<Arc<ServerState>>) -> Json<SpendResponse> {
+    Json(SpendResponse::from(&*state.ai_budget.read().await))
+}
+
+/// Counts of the graph's nodes and edges by kind, source and language, the
+/// nodes most depended on and depending, and what AI analysis has spent
+pub async fn get_stats(State(state): State<Arc<ServerState>>, Query(query): Query<StatsQuery>) -> Json<StatsResponse> {
+    let graph = state.graph.read().await.stats_with_limit(query.top.unwrap_or(canopy_core::stats::DEFAULT_TOP_N));
+    let ai = SpendResponse::from(&*state.ai_budget.read().await);
+    Json(StatsResponse { graph, ai })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -120,4 +897,440 @@ mod tests {
         let _response = health_check().await;
         // Should succeed
     }
+
+    #[tokio::test]
+    async fn test_get_graph() {
+        use canopy_core::{EdgeId, EdgeSource, Graph};
+
+        let mut graph = Graph::new();
+        let [load, parse, main] = [("load", "src/config.rs"), ("parse", "src/config.rs"), ("main", "bin/main.rs")].map(|(name, file)| {
+            graph.add_node(GraphNode::for_test(NodeKind::Function, name).in_file(file).with_lines(1, 2).with_language(Language::Rust))
+        });
+        for (source, target) in [(load, parse), (main, load)] {
+            graph.add_edge(GraphEdge {
+                id: EdgeId(0),
+                source,
+                target,
+                kind: EdgeKind::Calls,
+                edge_source: EdgeSource::Structural,
+                confidence: 1.0,
+                label: None,
+                file_path: None,
+                line: None,
+            });
+        }
+        let state = Arc::new(ServerState::new(graph));
+        let query = |scope: Option<&str>, fields: Option<&str>, cursor: Option<u64>, limit: Option<usize>| {
+            let query = GraphQuery { scope: scope.map(str::to_string), fields: fields.map(str::to_string), cursor, limit };
+            get_graph(State(Arc::clone(&state)), Query(query))
+        };
+        let names = |graph: &GraphResponse| -> Vec<String> { graph.nodes.iter().map(|n| n["name"].as_str().unwrap().to_string()).collect() };
+
+        // Everything at once, unless asked for a page at a time
+        let Json(all) = query(None, None, None, None).await.unwrap();
+        assert_eq!((all.nodes.len(), all.edges.len(), all.next_cursor), (3, 2, None));
+        let Json(first) = query(None, None, None, Some(2)).await.unwrap();
+        assert_eq!((names(&first), first.edges.len()), (vec!["load".to_string(), "parse".to_string()], 1));
+        let Json(second) = query(None, None, first.next_cursor, Some(2)).await.unwrap();
+        assert_eq!((names(&second), second.edges.len(), second.next_cursor), (vec!["main".to_string()], 1, None));
+
+        // Only the fields asked for, and the nodes in scope with the edges
+        // between them
+        let Json(slim) = query(Some("src"), Some("name,line_start"), None, None).await.unwrap();
+        assert_eq!(names(&slim), ["load", "parse"]);
+        let keys: Vec<&String> = slim.nodes[0].as_object().unwrap().keys().collect();
+        assert_eq!(keys.len(), 3);
+        assert_eq!(slim.nodes[0]["line_start"], 1);
+        assert_eq!((slim.edges.len(), slim.edges[0].target), (1, parse.0));
+
+        let error = query(None, Some("name,colour"), None, None).await.unwrap_err();
+        assert_eq!(error, (StatusCode::BAD_REQUEST, "Unknown node field colour".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_get_aggregated_graph() {
+        use canopy_core::{EdgeId, Graph};
+
+        let mut graph = Graph::new();
+        let [root, db, api, pool, handler] = [
+            ("root", NodeKind::Directory),
+            ("db", NodeKind::Directory),
+            ("api", NodeKind::Directory),
+            ("pool.rs", NodeKind::File),
+            ("handler.rs", NodeKind::File),
+        ]
+        .map(|(name, kind)| {
+            graph.add_node(GraphNode::for_test(kind, name))
+        });
+        let mut edge = |source, target, kind, edge_source, confidence| {
+            graph.add_edge(GraphEdge {
+                id: EdgeId(0),
+                source,
+                target,
+                kind,
+                edge_source,
+                confidence,
+                label: None,
+                file_path: None,
+                line: None,
+            });
+        };
+        for (parent, child) in [(root, db), (root, api), (db, pool), (api, handler)] {
+            edge(parent, child, EdgeKind::Contains, EdgeSource::Structural, 1.0);
+        }
+        edge(handler, pool, EdgeKind::Imports, EdgeSource::Structural, 1.0);
+        edge(handler, pool, EdgeKind::Calls, EdgeSource::AI, 0.6);
+        let state = Arc::new(ServerState::new(graph));
+        let view = |expanded: Option<&str>, depth| {
+            let query = AggregatedQuery { expanded: expanded.map(str::to_string), depth };
+            get_aggregated_graph(State(Arc::clone(&state)), Query(query))
+        };
+
+        // By default the roots' children show, with their files' edges
+        let Json(top) = view(None, None).await.unwrap();
+        let ids: Vec<u64> = top.nodes.iter().map(|n| n.id).collect();
+        assert_eq!((ids, top.collapsed), (vec![root.0, db.0, api.0], vec![db.0, api.0]));
+        assert_eq!(top.edges.len(), 1);
+        let edge = &top.edges[0];
+        assert_eq!((edge.source, edge.target, edge.count), (api.0, db.0, 2));
+        assert_eq!((edge.kind_counts["Calls"], edge.min_confidence), (1, Some(0.6)));
+
+        // Expanding a container lifts its edges no further than its children
+        let Json(opened) = view(Some(&api.0.to_string()), None).await.unwrap();
+        assert_eq!(opened.collapsed, [db.0]);
+        assert_eq!((opened.edges[0].source, opened.edges[0].target), (handler.0, db.0));
+        let Json(root_only) = view(None, Some(0)).await.unwrap();
+        assert_eq!((root_only.nodes.len(), root_only.edges.len()), (1, 0));
+
+        let error = view(Some("3,x"), None).await.unwrap_err();
+        assert_eq!(error.0, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_export_graph() {
+        use canopy_core::{EdgeId, Graph};
+
+        let mut graph = Graph::new();
+        let [src, lib, load, bin] =
+            [("src", NodeKind::Directory, "src"), ("lib.rs", NodeKind::File, "src/lib.rs"), ("load", NodeKind::Function, "src/lib.rs"), ("bin", NodeKind::Directory, "bin")]
+                .map(|(name, kind, file)| graph.add_node(GraphNode::for_test(kind, name).in_file(file)));
+        for (source, target, kind) in [(src, lib, EdgeKind::Contains), (lib, load, EdgeKind::Contains), (bin, load, EdgeKind::DependsOn)] {
+            graph.add_edge(GraphEdge {
+                id: EdgeId(0),
+                source,
+                target,
+                kind,
+                edge_source: EdgeSource::Structural,
+                confidence: 1.0,
+                label: None,
+                file_path: None,
+                line: None,
+            });
+        }
+        let state = Arc::new(ServerState::new(graph));
+        let export = async |format, scope: Option<&str>| {
+            let query = ExportQuery { format, scope: scope.map(str::to_string) };
+            let response = export_graph(State(Arc::clone(&state)), Query(query)).await?.into_response();
+            let header = |name| response.headers()[name].to_str().unwrap().to_string();
+            let (content_type, disposition) = (header(header::CONTENT_TYPE), header(header::CONTENT_DISPOSITION));
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            Ok::<_, (StatusCode, String)>((content_type, disposition, String::from_utf8(body.to_vec()).unwrap()))
+        };
+
+        let (content_type, disposition, dot) = export(ExportFormat::Dot, None).await.unwrap();
+        assert_eq!((content_type.as_str(), disposition.as_str()), ("text/vnd.graphviz", "attachment; filename=\"canopy.dot\""));
+        assert!(dot.contains(&format!("n{} -> n{}", bin.0, load.0)));
+
+        // A scope keeps its nodes, their ids, and the edges between them
+        let (_, disposition, graphml) = export(ExportFormat::Graphml, Some("src")).await.unwrap();
+        assert_eq!(disposition, "attachment; filename=\"canopy-src.graphml\"");
+        assert!(graphml.contains(&format!("<node id=\"n{}\">", load.0)));
+        assert!(!graphml.contains(&format!("<node id=\"n{}\">", bin.0)) && !graphml.contains("DependsOn"));
+        let (_, _, json) = export(ExportFormat::Json, Some("src")).await.unwrap();
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!((json["nodes"].as_array().unwrap().len(), json["edges"].as_array().unwrap().len()), (3, 2));
+
+        // Mermaid draws the contents of the scope's topmost container
+        let (_, _, mermaid) = export(ExportFormat::Mermaid, Some("src")).await.unwrap();
+        assert!(mermaid.starts_with("flowchart LR\n"));
+        assert!(mermaid.contains(&format!("n{}(\"load\")", load.0)));
+
+        let error = export(ExportFormat::Dot, Some("docs")).await.unwrap_err();
+        assert_eq!(error.0, StatusCode::NOT_FOUND);
+
+        // Nodes removed while the export streams are left out, with their edges
+        let query = ExportQuery { format: ExportFormat::Json, scope: Some("src".to_string()) };
+        let response = export_graph(State(Arc::clone(&state)), Query(query)).await.unwrap().into_response();
+        state.graph.write().await.remove_node(load);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!((json["nodes"].as_array().unwrap().len(), json["edges"].as_array().unwrap().len()), (2, 1));
+    }
+
+    #[tokio::test]
+    async fn test_get_node() {
+        use canopy_core::{EdgeId, EdgeKind, EdgeSource, Graph, NodeKind, NodeMetadata};
+
+        let node = |name: &str, kind| GraphNode {
+            metadata: NodeMetadata::with_extra([("ai_summary", "Loads the config".to_string())]),
+            ..GraphNode::for_test(kind, name).in_file("src/lib.rs").with_lines(3, 9)
+        };
+        let edge = |source, target, kind| GraphEdge {
+            id: EdgeId(0),
+            source,
+            target,
+            kind,
+            edge_source: EdgeSource::Structural,
+            confidence: 1.0,
+            label: None,
+            file_path: None,
+            line: None,
+        };
+        let mut graph = Graph::new();
+        let file = graph.add_node(node("lib.rs", NodeKind::File));
+        let load = graph.add_node(node("load", NodeKind::Function));
+        let parse = graph.add_node(node("parse", NodeKind::Function));
+        graph.add_edge(edge(file, load, EdgeKind::Contains));
+        graph.add_edge(edge(load, parse, EdgeKind::Calls));
+        let state = Arc::new(ServerState::new(graph));
+
+        let Json(detail) = get_node(State(Arc::clone(&state)), Path(load.0)).await.unwrap();
+        assert_eq!((detail.node.name.as_str(), detail.node.line_start), ("load", Some(3)));
+        assert_eq!(detail.node.ai_summary.as_deref(), Some("Loads the config"));
+        assert_eq!(detail.extra["ai_summary"], "Loads the config");
+        let incoming: Vec<(&str, &str)> = detail.incoming.iter().map(|e| (e.kind.as_str(), e.node_name.as_str())).collect();
+        assert_eq!(incoming, [("Contains", "lib.rs")]);
+        let outgoing: Vec<(&str, &str)> = detail.outgoing.iter().map(|e| (e.kind.as_str(), e.node_name.as_str())).collect();
+        assert_eq!(outgoing, [("Calls", "parse")]);
+
+        let error = get_node(State(state), Path(99)).await.unwrap_err();
+        assert_eq!(error.0, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_search() {
+        use canopy_core::Graph;
+
+        let mut graph = Graph::new();
+        for (name, kind) in [("User", NodeKind::Struct), ("get_user", NodeKind::Function), ("parse", NodeKind::Function)] {
+            graph.add_node(GraphNode::for_test(kind, name).in_file("src/user.rs").with_lines(4, 8).with_language(Language::Rust));
+        }
+        let state = Arc::new(ServerState::new(graph));
+        let query = |q: &str, kind| SearchQuery { q: q.to_string(), kind, lang: Some(Language::Rust), limit: None };
+
+        let Json(results) = search(State(Arc::clone(&state)), Query(query("user", None))).await;
+        let names: Vec<&str> = results.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["User", "get_user"]);
+        assert_eq!((results[1].file_path.as_str(), results[1].line_start), ("src/user.rs", Some(4)));
+
+        let Json(results) = search(State(state), Query(query("user", Some(NodeKind::Function)))).await;
+        assert_eq!(results.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_find_paths() {
+        use canopy_core::{EdgeId, EdgeSource, Graph};
+
+        let mut graph = Graph::new();
+        let [dir, http, service, db] = ["src", "http", "service", "db"].map(|name| {
+            graph.add_node(GraphNode::for_test(NodeKind::Function, name).in_file("src/lib.rs"))
+        });
+        let mut edge = |source, target, kind| {
+            graph.add_edge(GraphEdge {
+                id: EdgeId(0),
+                source,
+                target,
+                kind,
+                edge_source: EdgeSource::Structural,
+                confidence: 1.0,
+                label: None,
+                file_path: None,
+                line: None,
+            })
+        };
+        edge(dir, http, EdgeKind::Contains);
+        edge(dir, db, EdgeKind::Contains);
+        edge(http, service, EdgeKind::Calls);
+        edge(service, db, EdgeKind::Imports);
+        let state = Arc::new(ServerState::new(graph));
+        let query = |kinds: Option<&str>| PathQuery { from: http.0, to: db.0, kinds: kinds.map(str::to_string), directed: false, limit: None };
+
+        // Containment is left out unless asked for
+        let Json(paths) = find_paths(State(Arc::clone(&state)), Query(query(None))).await.unwrap();
+        let names: Vec<&str> = paths[0].nodes.iter().map(|n| n.name.as_str()).collect();
+        assert_eq!(names, ["http", "service", "db"]);
+        assert_eq!(paths[0].edges.len(), 2);
+        let Json(paths) = find_paths(State(Arc::clone(&state)), Query(query(Some("Contains")))).await.unwrap();
+        assert_eq!(paths[0].nodes.len(), 3);
+        assert_eq!(paths[0].edges[0].kind, "Contains");
+        let Json(paths) = find_paths(State(Arc::clone(&state)), Query(query(Some("Calls")))).await.unwrap();
+        assert!(paths.is_empty());
+
+        let error = find_paths(State(Arc::clone(&state)), Query(query(Some("Calls,Likes")))).await.unwrap_err();
+        assert_eq!(error.0, StatusCode::BAD_REQUEST);
+        let error = find_paths(State(state), Query(PathQuery { to: 99, ..query(None) })).await.unwrap_err();
+        assert_eq!(error.0, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_get_diffs() {
+        use canopy_core::DiffJournal;
+
+        let state = Arc::new(ServerState::new(canopy_core::Graph::new()));
+        *state.journal.write().await = DiffJournal::with_capacity(2);
+        let diffs = |since| get_diffs(State(Arc::clone(&state)), Query(DiffsQuery { since }));
+        let Json(response) = diffs(None).await.unwrap();
+        assert_eq!((response.sequence, response.diffs.len()), (0, 0));
+
+        for sequence in 1..=3 {
+            state.journal.write().await.record(GraphDiff::new(sequence));
+        }
+        let Json(response) = diffs(Some(1)).await.unwrap();
+        let sequences: Vec<u64> = response.diffs.iter().map(|d| d.sequence).collect();
+        assert_eq!((response.sequence, sequences), (3, vec![2, 3]));
+        assert!(diffs(Some(3)).await.unwrap().0.diffs.is_empty());
+        assert!(diffs(None).await.unwrap().0.diffs.is_empty());
+
+        // The first diff is no longer kept
+        assert_eq!(diffs(Some(0)).await.unwrap_err().0, StatusCode::GONE);
+    }
+
+    #[tokio::test]
+    async fn test_list_edges() {
+        use canopy_core::{EdgeId, Graph};
+
+        let mut graph = Graph::new();
+        let [main, load, parse, save] = ["main", "load", "parse", "save"].map(|name| {
+            graph.add_node(GraphNode::for_test(NodeKind::Function, name).in_file("src/lib.rs"))
+        });
+        for (source, target, kind, edge_source, confidence) in [
+            (main, load, EdgeKind::Calls, EdgeSource::Structural, 1.0),
+            (load, parse, EdgeKind::Calls, EdgeSource::AI, 0.9),
+            (main, save, EdgeKind::Calls, EdgeSource::AI, 0.4),
+            (main, parse, EdgeKind::DependsOn, EdgeSource::AI, 0.7),
+            (save, parse, EdgeKind::Calls, EdgeSource::AI, 0.8),
+        ] {
+            graph.add_edge(GraphEdge {
+                id: EdgeId(0),
+                source,
+                target,
+                kind,
+                edge_source,
+                confidence,
+                label: None,
+                file_path: None,
+                line: None,
+            });
+        }
+        let state = Arc::new(ServerState::new(graph));
+        let list = |query: EdgesQuery| list_edges(State(Arc::clone(&state)), Query(query));
+        let all = || EdgesQuery {
+            kind: None,
+            edge_source: None,
+            min_confidence: None,
+            source: None,
+            target: None,
+            cursor: None,
+            limit: None,
+        };
+        let ends = |page: &EdgesResponse| -> Vec<(u64, u64)> { page.edges.iter().map(|e| (e.source, e.target)).collect() };
+
+        let Json(page) = list(all()).await;
+        assert_eq!((page.edges.len(), page.next_cursor), (5, None));
+
+        // Filters combine
+        let ai_calls = || EdgesQuery { kind: Some(EdgeKind::Calls), edge_source: Some(EdgeSource::AI), min_confidence: Some(0.5), ..all() };
+        let Json(page) = list(ai_calls()).await;
+        assert_eq!(ends(&page), [(load.0, parse.0), (save.0, parse.0)]);
+        let Json(page) = list(EdgesQuery { target: Some(parse.0), source: Some(main.0), ..all() }).await;
+        assert_eq!(ends(&page), [(main.0, parse.0)]);
+
+        // Each page picks up where the last ended
+        let Json(first) = list(EdgesQuery { edge_source: Some(EdgeSource::AI), limit: Some(3), ..all() }).await;
+        assert_eq!(ends(&first), [(load.0, parse.0), (main.0, save.0), (main.0, parse.0)]);
+        let Json(second) = list(EdgesQuery { edge_source: Some(EdgeSource::AI), limit: Some(3), cursor: first.next_cursor, ..all() }).await;
+        assert_eq!((ends(&second), second.next_cursor), (vec![(save.0, parse.0)], None));
+    }
+
+    #[tokio::test]
+    async fn test_get_file() {
+        use canopy_core::Graph;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("src/lib.rs");
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(&path, "struct Config;\n\nfn load() {\n    parse();\n}\n").unwrap();
+        let mut graph = Graph::new();
+        for (name, kind, lines) in [
+            ("load", NodeKind::Function, Some((3, 5))),
+            ("lib.rs", NodeKind::File, None),
+            ("Config", NodeKind::Struct, Some((1, 1))),
+        ] {
+            let node = GraphNode::for_test(kind, name).in_file(&path).with_language(Language::Rust);
+            graph.add_node(match lines {
+                Some((start, end)) => node.with_lines(start, end),
+                None => node,
+            });
+        }
+        let state = Arc::new(ServerState::new(graph));
+        state.set_root(dir.path().to_path_buf()).await;
+        let query = |path: &str| Query(FileQuery { path: path.to_string() });
+
+        // Relative to the root, or as the graph has it
+        let Json(file) = get_file(State(Arc::clone(&state)), query("src/lib.rs")).await.unwrap();
+        assert_eq!((file.language.as_deref(), file.total_lines), (Some("Rust"), 5));
+        assert!(file.content.starts_with("struct Config;"));
+        let spans: Vec<(&str, u32, u32)> = file.symbols.iter().map(|s| (s.name.as_str(), s.line_start, s.line_end)).collect();
+        assert_eq!(spans, [("Config", 1, 1), ("load", 3, 5)]);
+        let Json(same) = get_file(State(Arc::clone(&state)), query(&path.to_string_lossy())).await.unwrap();
+        assert_eq!(same.path, file.path);
+
+        // Files outside the graph are not served, even when they exist
+        std::fs::write(dir.path().join("secret.txt"), "hunter2").unwrap();
+        let error = get_file(State(Arc::clone(&state)), query("secret.txt")).await.unwrap_err();
+        assert_eq!(error.0, StatusCode::NOT_FOUND);
+        let error = get_file(State(Arc::clone(&state)), query("src/../secret.txt")).await.unwrap_err();
+        assert_eq!(error.0, StatusCode::BAD_REQUEST);
+
+        // Nor are keys and credentials, though the walk made nodes of them
+        let npmrc = dir.path().join(".npmrc");
+        std::fs::write(&npmrc, "//registry.npmjs.org/:_authToken=npm_abc\n").unwrap();
+        let mut node = state.graph.read().await.all_nodes().find(|n| n.kind == NodeKind::File).unwrap().clone();
+        node.file_path = npmrc.as_path().into();
+        state.graph.write().await.add_node(node);
+        let error = get_file(State(state), query(".npmrc")).await.unwrap_err();
+        assert_eq!(error.0, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_get_ai_spend() {
+        let state = Arc::new(ServerState::new(canopy_core::Graph::new()));
+        state.ai_budget.write().await.record_usage(&canopy_ai::TokenUsage {
+            model: "gpt-4o-mini".to_string(),
+            input_tokens: 2_000_000,
+            output_tokens: 0,
+        });
+        let Json(spend) = get_ai_spend(State(state)).await;
+        assert_eq!(spend.tokens_used, 2_000_000);
+        assert!((spend.cost_usd - 0.3).abs() < 1e-9);
+        assert_eq!(spend.models["gpt-4o-mini"].input_tokens, 2_000_000);
+    }
+
+    #[tokio::test]
+    async fn test_get_stats() {
+        use canopy_core::Graph;
+
+        let mut graph = Graph::new();
+        graph.add_node(GraphNode::for_test(NodeKind::File, "lib.rs").in_file("src/lib.rs").with_lines(1, 40).with_language(Language::Rust));
+        let state = Arc::new(ServerState::new(graph));
+        state.ai_budget.write().await.use_tokens(1200);
+
+        let Json(stats) = get_stats(State(state), Query(StatsQuery { top: Some(3) })).await;
+        assert_eq!(stats.graph.node_count, 1);
+        assert_eq!(stats.ai.tokens_used, 1200);
+        let json = serde_json::to_value(&stats).unwrap();
+        assert_eq!(json["nodes_by_kind"]["File"], 1);
+        assert_eq!(json["languages"]["Rust"], 1);
+        assert_eq!(json["largest_files"][0]["loc"], 40);
+    }
 }
@@ -1,15 +1,22 @@
 //! HTTP + WebSocket server for Canopy
 
+pub mod ask;
 pub mod assets;
 pub mod handlers;
+pub mod reindex;
+pub mod reviews;
 pub mod router;
 pub mod websocket;
 
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
 
 use anyhow::Result;
-use canopy_core::Graph;
+use canopy_ai::redact::SensitiveFiles;
+use canopy_ai::{AIProvider, Budget, ReviewQueue, Sessions};
+use canopy_core::{DiffJournal, Graph};
+use canopy_watcher::ReindexJobs;
 use tokio::net::TcpListener;
 use tokio::sync::{broadcast, RwLock};
 use tracing::info;
@@ -40,6 +47,24 @@ pub struct ServerState {
     pub graph: Arc<RwLock<Graph>>,
     /// Broadcast channel for graph diffs to WebSocket clients
     pub diff_tx: broadcast::Sender<String>,
+    /// The latest diffs, shared with the watcher that makes them
+    pub journal: Arc<RwLock<DiffJournal>>,
+    /// Provider answering questions about the code, once one is set up
+    pub ai_provider: RwLock<Option<Arc<dyn AIProvider>>>,
+    /// Tokens and dollars spent on AI, shared with the watcher
+    pub ai_budget: Arc<RwLock<Budget>>,
+    /// AI edges waiting for review, shared with the watcher
+    pub reviews: Arc<RwLock<ReviewQueue>>,
+    /// Conversations of questions asked, for their follow-ups
+    pub ask_sessions: RwLock<Sessions>,
+    /// The project's root, which relative paths asked for are under
+    pub root: RwLock<Option<PathBuf>>,
+    /// Files whose content is never served, such as keys and credentials
+    pub sensitive_files: RwLock<SensitiveFiles>,
+    /// Re-indexes asked for, shared with the watcher that runs them
+    pub reindex: Arc<RwLock<ReindexJobs>>,
+    /// Token that requests changing the index must carry
+    pub api_token: RwLock<Option<String>>,
 }
 
 impl std::fmt::Debug for ServerState {
@@ -47,6 +72,15 @@ impl std::fmt::Debug for ServerState {
         f.debug_struct("ServerState")
             .field("graph", &"<Graph>")
             .field("diff_tx", &self.diff_tx)
+            .field("journal", &"<DiffJournal>")
+            .field("ai_provider", &"<AIProvider>")
+            .field("ai_budget", &self.ai_budget)
+            .field("reviews", &self.reviews)
+            .field("ask_sessions", &self.ask_sessions)
+            .field("root", &self.root)
+            .field("sensitive_files", &self.sensitive_files)
+            .field("reindex", &self.reindex)
+            .field("api_token", &"<token>")
             .finish()
     }
 }
@@ -57,9 +91,38 @@ impl ServerState {
         Self {
             graph: Arc::new(RwLock::new(graph)),
             diff_tx,
+            journal: Arc::new(RwLock::new(DiffJournal::new())),
+            ai_provider: RwLock::new(None),
+            ai_budget: Arc::new(RwLock::new(Budget::default())),
+            reviews: Arc::new(RwLock::new(ReviewQueue::new())),
+            ask_sessions: RwLock::new(Sessions::new()),
+            root: RwLock::new(None),
+            sensitive_files: RwLock::new(SensitiveFiles::default()),
+            reindex: Arc::new(RwLock::new(ReindexJobs::new())),
+            api_token: RwLock::new(None),
         }
     }
 
+    /// Answer questions with `provider`.
+    pub async fn set_ai_provider(&self, provider: Arc<dyn AIProvider>) {
+        *self.ai_provider.write().await = Some(provider);
+    }
+
+    /// Resolve relative paths asked for against `root`.
+    pub async fn set_root(&self, root: PathBuf) {
+        *self.root.write().await = Some(root);
+    }
+
+    /// Never serve the content of `files`.
+    pub async fn set_sensitive_files(&self, files: SensitiveFiles) {
+        *self.sensitive_files.write().await = files;
+    }
+
+    /// Allow requests carrying `token` to change the index.
+    pub async fn set_api_token(&self, token: String) {
+        *self.api_token.write().await = Some(token);
+    }
+
     /// Update the graph and broadcast the diff to all connected WebSocket clients
     pub async fn update_graph(&self, new_graph: Graph) -> Result<()> {
         let mut graph = self.graph.write().await;
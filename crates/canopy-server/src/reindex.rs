@@ -0,0 +1,129 @@
+//! Re-indexing on request
+//!
+//! A re-index is queued in the state's [`ReindexJobs`] for the watcher to
+//! run in the background; the request returns the job straight away, and
+//! its progress is served by id and sent to WebSocket clients as `reindex`
+//! messages. Asking for one takes the API token, sent as
+//! `Authorization: Bearer <token>`; without a token set, none is allowed.
+
+use std::path::Component;
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::Json,
+};
+use canopy_watcher::{JobId, ReindexJob};
+use serde::Deserialize;
+use subtle::ConstantTimeEq;
+
+use crate::ServerState;
+
+/// Body of a re-index request: `{"paths": ["src/db", "src/main.rs"]}`
+#[derive(Debug, Default, Deserialize)]
+pub struct ReindexRequest {
+    /// Files and directories, relative to the project's root; everything
+    /// if empty or absent
+    #[serde(default)]
+    pub paths: Vec<String>,
+}
+
+/// Queue a re-index, returning the job with its id
+pub async fn start_reindex(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    request: Option<Json<ReindexRequest>>,
+) -> Result<(StatusCode, Json<ReindexJob>), (StatusCode, String)> {
+    authorize(&state, &headers).await?;
+    let Json(request) = request.unwrap_or_default();
+    let root = state.root.read().await.clone().unwrap_or_default();
+    let mut paths = Vec::new();
+    for path in &request.paths {
+        let path = std::path::Path::new(path);
+        if path.is_absolute() || path.components().any(|c| c == Component::ParentDir) {
+            return Err((StatusCode::BAD_REQUEST, format!("{} is not under the project's root", path.display())));
+        }
+        paths.push(root.join(path));
+    }
+    let job = state
+        .reindex
+        .write()
+        .await
+        .submit(paths)
+        .ok_or((StatusCode::SERVICE_UNAVAILABLE, "The watcher is not running".to_string()))?;
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+/// Where a re-index job is, or 404 if there's no such job
+pub async fn get_reindex(
+    State(state): State<Arc<ServerState>>,
+    Path(id): Path<JobId>,
+) -> Result<Json<ReindexJob>, (StatusCode, String)> {
+    let jobs = state.reindex.read().await;
+    let job = jobs.get(id).ok_or((StatusCode::NOT_FOUND, format!("No re-index job {}", id)))?;
+    Ok(Json(job.clone()))
+}
+
+/// Check the request carries the API token, as every request changing the
+/// index or the graph must
+pub(crate) async fn authorize(state: &ServerState, headers: &HeaderMap) -> Result<(), (StatusCode, String)> {
+    let token = state.api_token.read().await;
+    let Some(token) = token.as_deref() else {
+        return Err((StatusCode::FORBIDDEN, "Set CANOPY_API_TOKEN to allow this".to_string()));
+    };
+    let sent = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    // Compared in constant time, so timing doesn't reveal how much of a
+    // guess was right
+    let matches = sent.is_some_and(|sent| bool::from(sent.as_bytes().ct_eq(token.as_bytes())));
+    if !matches {
+        return Err((StatusCode::UNAUTHORIZED, "Missing or wrong API token".to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use canopy_core::Graph;
+    use canopy_watcher::JobState;
+    use std::path::PathBuf;
+
+    #[tokio::test]
+    async fn test_reindex() {
+        let state = Arc::new(ServerState::new(Graph::new()));
+        state.set_root(PathBuf::from("/repo")).await;
+        let request = |token: Option<&str>, paths: &[&str]| {
+            let mut headers = HeaderMap::new();
+            if let Some(token) = token {
+                headers.insert(header::AUTHORIZATION, format!("Bearer {}", token).parse().unwrap());
+            }
+            let body = ReindexRequest { paths: paths.iter().map(|p| p.to_string()).collect() };
+            start_reindex(State(Arc::clone(&state)), headers, Some(Json(body)))
+        };
+
+        // Refused until a token is set, then without it
+        assert_eq!(request(Some("s3cret"), &[]).await.unwrap_err().0, StatusCode::FORBIDDEN);
+        state.set_api_token("s3cret".to_string()).await;
+        assert_eq!(request(None, &[]).await.unwrap_err().0, StatusCode::UNAUTHORIZED);
+        assert_eq!(request(Some("guess"), &[]).await.unwrap_err().0, StatusCode::UNAUTHORIZED);
+
+        // Queued once something runs the jobs
+        let error = request(Some("s3cret"), &["src"]).await.unwrap_err();
+        assert_eq!(error.0, StatusCode::SERVICE_UNAVAILABLE);
+        let mut queue = state.reindex.write().await.listen();
+        let (status, Json(job)) = request(Some("s3cret"), &["src"]).await.unwrap();
+        assert_eq!((status, job.state), (StatusCode::ACCEPTED, JobState::Queued));
+        assert_eq!(job.paths, [PathBuf::from("/repo/src")]);
+        assert_eq!(queue.try_recv().unwrap(), job.id);
+        let error = request(Some("s3cret"), &["../elsewhere"]).await.unwrap_err();
+        assert_eq!(error.0, StatusCode::BAD_REQUEST);
+
+        let Json(status) = get_reindex(State(Arc::clone(&state)), Path(job.id)).await.unwrap();
+        assert_eq!(status, job);
+        assert_eq!(get_reindex(State(state), Path(99)).await.unwrap_err().0, StatusCode::NOT_FOUND);
+    }
+}
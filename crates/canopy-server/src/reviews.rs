@@ -0,0 +1,160 @@
+//! Review of AI-inferred edges
+//!
+//! AI edges wait in the state's [`ReviewQueue`] until accepted, which adds
+//! them to the graph, or rejected. Proposal IDs are 64-bit hashes, sent as
+//! hex strings since JavaScript numbers can't hold them.
+//!
+//! Deciding takes the API token, as re-indexing does.
+//!
+//! The decisions also show how far each provider's confidence can be
+//! trusted, served as its [`CalibrationStats`].
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::Json,
+};
+use canopy_ai::calibration::CalibrationStats;
+use canopy_ai::review::{Proposal, Verdict};
+use serde::Serialize;
+
+use crate::reindex::authorize;
+use crate::ServerState;
+
+/// An AI edge waiting for review
+#[derive(Debug, Serialize)]
+pub struct ProposalResponse {
+    pub id: String,
+    pub source: String,
+    pub target: String,
+    pub kind: String,
+    pub confidence: f32,
+    pub explanation: Option<String>,
+    pub file_path: Option<String>,
+    pub line: Option<u32>,
+}
+
+impl From<&Proposal> for ProposalResponse {
+    fn from(proposal: &Proposal) -> Self {
+        Self {
+            id: format!("{:016x}", proposal.id),
+            source: proposal.source.qualified_name.clone(),
+            target: proposal.target.qualified_name.clone(),
+            kind: format!("{:?}", proposal.edge.kind),
+            confidence: proposal.edge.confidence,
+            explanation: proposal.edge.label.clone(),
+            file_path: proposal.edge.file_path.as_ref().map(|p| p.to_string_lossy().to_string()),
+            line: proposal.edge.line,
+        }
+    }
+}
+
+/// Outcome of a review decision
+#[derive(Debug, Serialize)]
+pub struct DecisionResponse {
+    /// Whether an edge was added to the graph
+    pub added: bool,
+}
+
+/// List the AI edges waiting for review
+pub async fn list_reviews(State(state): State<Arc<ServerState>>) -> Json<Vec<ProposalResponse>> {
+    let reviews = state.reviews.read().await;
+    Json(reviews.proposed().map(ProposalResponse::from).collect())
+}
+
+/// How each provider's confidence held up in review, by relationship
+pub async fn get_calibration(State(state): State<Arc<ServerState>>) -> Json<Vec<CalibrationStats>> {
+    Json(state.reviews.read().await.calibration().stats())
+}
+
+/// Accept a proposed edge, adding it to the graph
+pub async fn accept_review(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<Json<DecisionResponse>, (StatusCode, String)> {
+    authorize(&state, &headers).await?;
+    decide(&state, &id, Verdict::Accepted).await
+}
+
+/// Reject a proposed edge
+pub async fn reject_review(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<Json<DecisionResponse>, (StatusCode, String)> {
+    authorize(&state, &headers).await?;
+    decide(&state, &id, Verdict::Rejected).await
+}
+
+async fn decide(state: &ServerState, id: &str, verdict: Verdict) -> Result<Json<DecisionResponse>, (StatusCode, String)> {
+    let id = u64::from_str_radix(id, 16).map_err(|_| (StatusCode::BAD_REQUEST, format!("Invalid review ID: {}", id)))?;
+    let mut graph = state.graph.write().await;
+    let mut reviews = state.reviews.write().await;
+    if !reviews.proposed().any(|p| p.id == id) {
+        return Err((StatusCode::NOT_FOUND, format!("No proposed edge {:016x}", id)));
+    }
+    let edge = reviews
+        .decide(id, verdict, &graph)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let added = edge.is_some();
+    if let Some(edge) = edge {
+        graph.add_edge(edge);
+    }
+    Ok(Json(DecisionResponse { added }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use canopy_ai::review::Inference;
+    use canopy_core::{EdgeId, EdgeKind, EdgeSource, Graph, GraphEdge, GraphNode, NodeKind};
+
+    #[tokio::test]
+    async fn test_accept_review() {
+        let mut graph = Graph::new();
+        let load = graph.add_node(GraphNode::for_test(NodeKind::Function, "load"));
+        let parse = graph.add_node(GraphNode::for_test(NodeKind::Function, "parse"));
+        let state = Arc::new(ServerState::new(graph));
+        {
+            let graph = state.graph.read().await;
+            let edge = GraphEdge {
+                id: EdgeId(0),
+                source: load,
+                target: parse,
+                kind: EdgeKind::Calls,
+                edge_source: EdgeSource::AI,
+                confidence: 0.8,
+                label: None,
+                file_path: None,
+                line: None,
+            };
+            let inference = Inference { provider: "test".to_string(), confidence: 0.8 };
+            state.reviews.write().await.propose(edge, inference, &graph);
+        }
+
+        let Json(proposed) = list_reviews(State(Arc::clone(&state))).await;
+        assert_eq!(proposed.len(), 1);
+        assert_eq!((proposed[0].source.as_str(), proposed[0].target.as_str()), ("load", "parse"));
+
+        // Deciding takes the API token
+        let id = proposed[0].id.clone();
+        state.set_api_token("s3cret".to_string()).await;
+        let error = accept_review(State(Arc::clone(&state)), HeaderMap::new(), Path(id.clone())).await.unwrap_err();
+        assert_eq!(error.0, StatusCode::UNAUTHORIZED);
+        let mut headers = HeaderMap::new();
+        headers.insert(axum::http::header::AUTHORIZATION, "Bearer s3cret".parse().unwrap());
+        let Json(decision) = accept_review(State(Arc::clone(&state)), headers.clone(), Path(id.clone())).await.unwrap();
+        assert!(decision.added);
+        assert_eq!(state.graph.read().await.edge_count(), 1);
+        let Json(calibration) = get_calibration(State(Arc::clone(&state))).await;
+        assert_eq!(calibration.len(), 1);
+        assert_eq!((calibration[0].provider.as_str(), calibration[0].decisions, calibration[0].accepted), ("test", 1, 1));
+        let error = reject_review(State(Arc::clone(&state)), headers.clone(), Path(id)).await.unwrap_err();
+        assert_eq!(error.0, StatusCode::NOT_FOUND);
+        let error = reject_review(State(state), headers, Path("not-hex".to_string())).await.unwrap_err();
+        assert_eq!(error.0, StatusCode::BAD_REQUEST);
+    }
+}
@@ -2,12 +2,18 @@
 
 use std::sync::Arc;
 
-use axum::{routing::get, Router};
+use axum::{routing::{get, post}, Router};
 use tower_http::cors::CorsLayer;
 
 use crate::{
+    ask::{ask, ask_stream},
     assets::static_handler,
-    handlers::{get_graph, health_check},
+    handlers::{
+        export_graph, find_paths, get_aggregated_graph, get_ai_spend, get_diffs, get_file, get_graph, get_node,
+        get_stats, health_check, list_edges, search,
+    },
+    reindex::{get_reindex, start_reindex},
+    reviews::{accept_review, get_calibration, list_reviews, reject_review},
     websocket::ws_handler,
     ServerState,
 };
@@ -19,12 +25,33 @@ pub fn create_router(state: Arc<ServerState>) -> Router {
         .route("/ws", get(ws_handler))
         // REST API endpoints
         .route("/api/graph", get(get_graph))
+        .route("/api/graph/aggregated", get(get_aggregated_graph))
+        .route("/api/export", get(export_graph))
+        .route("/api/nodes/:id", get(get_node))
+        .route("/api/edges", get(list_edges))
+        .route("/api/diffs", get(get_diffs))
+        .route("/api/search", get(search))
+        .route("/api/path", get(find_paths))
+        .route("/api/stats", get(get_stats))
         .route("/api/health", get(health_check))
+        .route("/api/reindex/:id", get(get_reindex))
+        .route("/api/ai/spend", get(get_ai_spend))
+        .route("/api/ai/calibration", get(get_calibration))
+        .route("/api/reviews", get(list_reviews))
         // Static file serving
         .route("/", get(static_handler))
         .route("/*path", get(static_handler))
         // Add CORS support
         .layer(CorsLayer::permissive())
+        // Source files, and requests that change the graph or spend on
+        // AI, only to the web interface itself, never to another site's
+        // page in the same browser
+        .route("/api/file", get(get_file))
+        .route("/api/reindex", post(start_reindex))
+        .route("/api/reviews/:id/accept", post(accept_review))
+        .route("/api/reviews/:id/reject", post(reject_review))
+        .route("/api/ask", post(ask))
+        .route("/api/ask/stream", get(ask_stream))
         // Add state
         .with_state(state)
 }
@@ -38,8 +65,25 @@ mod tests {
     fn test_router_creation() {
         let graph = Graph::new();
         let state = Arc::new(ServerState::new(graph));
-        let _router = create_router(state);
         // Router creation should succeed
-        assert!(true);
+        let _router = create_router(state);
+    }
+
+    #[tokio::test]
+    async fn test_private_routes_have_no_cors() {
+        use axum::body::Body;
+        use axum::http::{header, Request};
+        use tower::ServiceExt;
+
+        let router = create_router(Arc::new(ServerState::new(Graph::new())));
+        let request = |uri: &str| {
+            Request::get(uri).header(header::ORIGIN, "https://example.com").body(Body::empty()).unwrap()
+        };
+        let response = router.clone().oneshot(request("/api/stats")).await.unwrap();
+        assert!(response.headers().contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+        for uri in ["/api/file?path=src/lib.rs", "/api/ask/stream?q=why"] {
+            let response = router.clone().oneshot(request(uri)).await.unwrap();
+            assert!(!response.headers().contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN), "{}", uri);
+        }
     }
 }
@@ -1,6 +1,8 @@
 //! WebSocket handling for real-time graph updates
 
-use std::sync::Arc;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 
 use futures_util::{SinkExt, StreamExt};
 use axum::{
@@ -10,8 +12,9 @@ use axum::{
     },
     response::IntoResponse,
 };
+use canopy_core::{EdgeId, EdgeKind, GraphDiff, GraphEdge, GraphNode, Language, NodeId, NodeKind};
 use serde::{Deserialize, Serialize};
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, mpsc};
 use tracing::{debug, info, warn};
 
 use crate::ServerState;
@@ -26,16 +29,25 @@ pub enum WsMessage {
     /// Server sends the full graph
     #[serde(rename = "full_graph")]
     FullGraph { graph: GraphData },
-    /// Server broadcasts a graph diff
+    /// Server broadcasts a graph diff, with what the change did if the AI
+    /// provider explained it
     #[serde(rename = "graph_diff")]
-    GraphDiff { diff: DiffData },
+    GraphDiff {
+        diff: GraphDiff,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        explanation: Option<String>,
+    },
     /// Client acknowledges a diff
     #[serde(rename = "diff_ack")]
     DiffAck { sequence: u64 },
-    /// Client subscribes to updates
+    /// Client subscribes to the updates of the part of the graph its
+    /// filter keeps
     #[serde(rename = "subscribe")]
-    Subscribe,
-    /// Client unsubscribes from updates
+    Subscribe {
+        #[serde(default)]
+        filter: SubscriptionFilter,
+    },
+    /// Client drops its filter, to be sent everything again
     #[serde(rename = "unsubscribe")]
     Unsubscribe,
     /// Ping/pong for keepalive
@@ -56,31 +68,203 @@ pub struct GraphData {
     pub sequence: u64,
 }
 
-/// Diff data structure for incremental updates
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct DiffData {
-    pub sequence: u64,
-    pub modified_nodes: Vec<canopy_core::model::NodeId>,
-    pub changes: serde_json::Value,
+/// The part of the graph a client wants updates of. Each filter given
+/// narrows it; none keeps everything
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SubscriptionFilter {
+    /// Only nodes of files under this directory, relative to the
+    /// project's root
+    pub path: Option<String>,
+    /// Only nodes in these languages, and those in none, such as
+    /// directories
+    pub languages: Vec<Language>,
+    pub node_kinds: Vec<NodeKind>,
+    pub edge_kinds: Vec<EdgeKind>,
+    /// Only edges at least this confident
+    pub min_confidence: Option<f32>,
+}
+
+/// A filter a client subscribed with, and what it has been sent through it
+#[derive(Debug, Default)]
+struct Subscription {
+    filter: SubscriptionFilter,
+    /// The filter's path under the project's root
+    under_root: Option<PathBuf>,
+    /// Nodes and edges the client has, whose removal it needs to hear of
+    nodes: HashSet<NodeId>,
+    edges: HashSet<EdgeId>,
+}
+
+impl Subscription {
+    fn new(filter: SubscriptionFilter, root: Option<PathBuf>) -> Self {
+        let under_root = root.zip(filter.path.as_ref()).map(|(root, path)| root.join(path));
+        Self { filter, under_root, ..Self::default() }
+    }
+
+    fn keeps_node(&self, node: &GraphNode) -> bool {
+        let filter = &self.filter;
+        let under = |path: &std::path::Path| node.file_path.starts_with(path);
+        let in_path = filter.path.as_ref().is_none_or(|path| {
+            under(std::path::Path::new(path)) || self.under_root.as_deref().is_some_and(under)
+        });
+        in_path
+            && node.language.is_none_or(|l| filter.languages.is_empty() || filter.languages.contains(&l))
+            && (filter.node_kinds.is_empty() || filter.node_kinds.contains(&node.kind))
+    }
+
+    /// Whether to send `edge`, between nodes the client has
+    fn keeps_edge(&self, edge: &GraphEdge) -> bool {
+        let filter = &self.filter;
+        self.nodes.contains(&edge.source)
+            && self.nodes.contains(&edge.target)
+            && (filter.edge_kinds.is_empty() || filter.edge_kinds.contains(&edge.kind))
+            && filter.min_confidence.is_none_or(|min| edge.confidence >= min)
+    }
+
+    /// Keep the part of a full graph the filter does, which the client
+    /// then has and nothing else
+    fn retain_graph(&mut self, graph: &mut GraphData) {
+        graph.nodes.retain(|n| self.keeps_node(n));
+        self.nodes = graph.nodes.iter().map(|n| n.id).collect();
+        graph.edges.retain(|e| self.keeps_edge(e));
+        self.edges = graph.edges.iter().map(|e| e.id).collect();
+    }
+
+    /// Keep the part of `diff` about nodes and edges the filter keeps or
+    /// the client has
+    fn retain_diff(&mut self, diff: &mut GraphDiff) {
+        diff.removed_edges.retain(|id| self.edges.remove(id));
+        diff.removed_nodes.retain(|id| self.nodes.remove(id));
+        diff.modified_nodes.retain(|id| self.nodes.contains(id));
+        diff.added_nodes.retain(|n| self.keeps_node(n));
+        self.nodes.extend(diff.added_nodes.iter().map(|n| n.id));
+        diff.added_edges.retain(|e| self.keeps_edge(e));
+        self.edges.extend(diff.added_edges.iter().map(|e| e.id));
+    }
 }
 
-/// Convert the current graph to GraphData format expected by frontend
+/// Where a connection is in the stream of diffs, and which of them it
+/// wants
+#[derive(Debug, Default)]
+struct ClientProgress {
+    /// The last diff the client acknowledged, or the sequence of the full
+    /// graph it was last sent
+    acked: u64,
+    /// The last diff sent to the client
+    sent: u64,
+    /// The filter subscribed with; everything is sent without one
+    subscription: Option<Subscription>,
+}
+
+impl ClientProgress {
+    /// The message sending `diff`, as much of it as the client subscribed
+    /// to; `None` if that's nothing
+    fn diff_message(&mut self, mut diff: GraphDiff, explanation: Option<String>) -> Option<String> {
+        self.sent = self.sent.max(diff.sequence);
+        if let Some(subscription) = &mut self.subscription {
+            subscription.retain_diff(&mut diff);
+            if diff.is_empty() {
+                return None;
+            }
+        }
+        serde_json::to_string(&WsMessage::GraphDiff { diff, explanation }).ok()
+    }
+
+    /// The broadcast diff `message` at `sequence` to send on, unless it was
+    /// already sent, in a full graph or catching up, or the client
+    /// subscribed to none of it
+    fn forward(&mut self, sequence: u64, message: String) -> Option<String> {
+        if sequence <= self.sent {
+            return None;
+        }
+        if self.subscription.is_none() {
+            self.sent = sequence;
+            return Some(message);
+        }
+        match serde_json::from_str(&message) {
+            Ok(WsMessage::GraphDiff { diff, explanation }) => self.diff_message(diff, explanation),
+            _ => None,
+        }
+    }
+}
+
+/// Enough of a broadcast message to tell a graph diff's sequence
+#[derive(Deserialize)]
+struct Broadcast {
+    #[serde(rename = "type")]
+    kind: String,
+    diff: Option<BroadcastSequence>,
+}
+
+#[derive(Deserialize)]
+struct BroadcastSequence {
+    sequence: u64,
+}
+
+/// Convert the current graph to GraphData format expected by frontend,
+/// with the sequence of the last diff it includes
 async fn graph_to_graph_data(state: &Arc<ServerState>) -> GraphData {
+    // The watcher journals each diff under the graph's write lock, so the
+    // sequence read under its read lock matches the graph
     let graph = state.graph.read().await;
-    
+    let sequence = state.journal.read().await.sequence();
+
     // Collect all nodes
-    let nodes = graph.all_nodes().map(|n| n.clone()).collect();
+    let nodes = graph.all_nodes().cloned().collect();
 
     // Collect all edges
-    let edges = graph.all_edges().map(|e| e.clone()).collect();
+    let edges = graph.all_edges().cloned().collect();
 
     GraphData {
         nodes,
         edges,
-        sequence: 0,
+        sequence,
     }
 }
 
+/// The full graph message for a client, which has everything up to its
+/// sequence once sent
+async fn full_graph_message(state: &Arc<ServerState>, progress: &Mutex<ClientProgress>) -> Option<String> {
+    let mut graph = graph_to_graph_data(state).await;
+    let sequence = graph.sequence;
+    let mut progress = progress.lock().unwrap();
+    if let Some(subscription) = &mut progress.subscription {
+        subscription.retain_graph(&mut graph);
+    }
+    match serde_json::to_string(&WsMessage::FullGraph { graph }) {
+        Ok(message) => {
+            (progress.acked, progress.sent) = (sequence, sequence);
+            Some(message)
+        }
+        Err(e) => {
+            warn!("Failed to serialize full graph message: {}", e);
+            None
+        }
+    }
+}
+
+/// The messages bringing a client that lagged up to date: the diffs it
+/// hasn't acknowledged, or the full graph if some are no longer kept
+async fn catch_up(state: &Arc<ServerState>, progress: &Mutex<ClientProgress>) -> Vec<String> {
+    let acked = progress.lock().unwrap().acked;
+    let missed = state.journal.read().await.since(acked);
+    let Some(diffs) = missed else {
+        return full_graph_message(state, progress).await.into_iter().collect();
+    };
+    let mut progress = progress.lock().unwrap();
+    diffs.into_iter().filter_map(|diff| progress.diff_message(diff, None)).collect()
+}
+
+/// The sequence of a broadcast message if it's a graph diff
+fn diff_sequence(message: &str) -> Option<u64> {
+    let broadcast: Broadcast = serde_json::from_str(message).ok()?;
+    if broadcast.kind != "graph_diff" {
+        return None;
+    }
+    broadcast.diff.map(|diff| diff.sequence)
+}
+
 /// Handle WebSocket upgrade requests
 pub async fn ws_handler(
     ws: WebSocketUpgrade,
@@ -95,23 +279,22 @@ async fn handle_socket(socket: WebSocket, state: Arc<ServerState>) {
 
     let (mut sender, mut receiver) = socket.split();
     let mut rx = state.diff_tx.subscribe();
+    let progress = Arc::new(Mutex::new(ClientProgress::default()));
 
     // Send full graph immediately after connection
-    let full_graph_data = graph_to_graph_data(&state).await;
-    let full_graph_msg = WsMessage::FullGraph { graph: full_graph_data };
-    
-    if let Ok(json_msg) = serde_json::to_string(&full_graph_msg) {
-        if sender.send(Message::Text(json_msg)).await.is_err() {
+    if let Some(full_graph_msg) = full_graph_message(&state, &progress).await {
+        if sender.send(Message::Text(full_graph_msg)).await.is_err() {
             warn!("Failed to send initial full graph to WebSocket client");
             return;
         }
         info!("Sent full graph to WebSocket client");
-    } else {
-        warn!("Failed to serialize full graph message");
     }
 
-    // Spawn a task to handle incoming messages from the client
+    // Spawn a task to handle incoming messages from the client, passing
+    // its replies to the task sending
+    let (reply_tx, mut reply_rx) = mpsc::unbounded_channel::<String>();
     let state_clone = Arc::clone(&state);
+    let progress_clone = Arc::clone(&progress);
     let mut recv_task = tokio::spawn(async move {
         while let Some(Ok(msg)) = receiver.next().await {
             if let Message::Text(text) = msg {
@@ -119,7 +302,9 @@ async fn handle_socket(socket: WebSocket, state: Arc<ServerState>) {
                 
                 match serde_json::from_str::<WsMessage>(&text) {
                     Ok(ws_msg) => {
-                        handle_client_message(ws_msg, &state_clone).await;
+                        if let Some(reply) = handle_client_message(ws_msg, &state_clone, &progress_clone).await {
+                            let _ = reply_tx.send(reply);
+                        }
                     }
                     Err(e) => {
                         warn!("Failed to parse WebSocket message: {}", e);
@@ -132,22 +317,35 @@ async fn handle_socket(socket: WebSocket, state: Arc<ServerState>) {
         }
     });
 
-    // Spawn a task to broadcast diffs to the client
+    // Spawn a task to broadcast diffs and replies to the client
     let mut send_task = tokio::spawn(async move {
         loop {
-            match rx.recv().await {
-                Ok(msg) => {
-                    if sender.send(Message::Text(msg)).await.is_err() {
-                        debug!("Failed to send message to WebSocket client");
+            let messages = tokio::select! {
+                reply = reply_rx.recv() => match reply {
+                    Some(reply) => vec![reply],
+                    None => break,
+                },
+                msg = rx.recv() => match msg {
+                    Ok(msg) => match diff_sequence(&msg) {
+                        Some(sequence) => match progress.lock().unwrap().forward(sequence, msg) {
+                            Some(msg) => vec![msg],
+                            None => continue,
+                        },
+                        None => vec![msg],
+                    },
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("WebSocket client lagged behind by {} messages, resending its missed diffs", skipped);
+                        catch_up(&state, &progress).await
+                    }
+                    Err(broadcast::error::RecvError::Closed) => {
                         break;
                     }
-                }
-                Err(broadcast::error::RecvError::Lagged(_)) => {
-                    warn!("WebSocket client lagged behind");
-                    continue;
-                }
-                Err(broadcast::error::RecvError::Closed) => {
-                    break;
+                },
+            };
+            for msg in messages {
+                if sender.send(Message::Text(msg)).await.is_err() {
+                    debug!("Failed to send message to WebSocket client");
+                    return;
                 }
             }
         }
@@ -166,27 +364,44 @@ async fn handle_socket(socket: WebSocket, state: Arc<ServerState>) {
     info!("WebSocket connection closed");
 }
 
-/// Handle messages received from the WebSocket client
-async fn handle_client_message(msg: WsMessage, _state: &ServerState) {
+/// Handle messages received from the WebSocket client, returning the
+/// reply to send it, if any
+async fn handle_client_message(
+    msg: WsMessage,
+    state: &Arc<ServerState>,
+    progress: &Mutex<ClientProgress>,
+) -> Option<String> {
     match msg {
         WsMessage::RequestFullGraph => {
             debug!("Client requested full graph");
-            // The full graph is sent automatically on connection and can be re-requested
+            full_graph_message(state, progress).await
         }
-        WsMessage::Subscribe => {
-            debug!("Client subscribed to updates");
+        WsMessage::Subscribe { filter } => {
+            debug!("Client subscribed to updates: {:?}", filter);
+            // The client is sent the part of the graph it now follows
+            let root = state.root.read().await.clone();
+            progress.lock().unwrap().subscription = Some(Subscription::new(filter, root));
+            full_graph_message(state, progress).await
         }
         WsMessage::Unsubscribe => {
             debug!("Client unsubscribed from updates");
+            let unfiltered = progress.lock().unwrap().subscription.take().is_some();
+            if unfiltered { full_graph_message(state, progress).await } else { None }
         }
         WsMessage::DiffAck { sequence } => {
             debug!("Client acknowledged diff with sequence: {}", sequence);
+            // Nothing past what was sent can have been received
+            let mut progress = progress.lock().unwrap();
+            progress.acked = progress.acked.max(sequence.min(progress.sent));
+            None
         }
         WsMessage::Ping => {
             debug!("Received ping");
+            None
         }
         _ => {
             debug!("Received message: {:?}", msg);
+            None
         }
     }
 }
@@ -216,4 +431,113 @@ mod tests {
         let result = state.broadcast(msg);
         assert!(result.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_full_graph_and_acks() {
+        use canopy_core::{DiffJournal, GraphDiff};
+
+        let state = Arc::new(ServerState::new(Graph::new()));
+        *state.journal.write().await = DiffJournal::with_capacity(3);
+        for sequence in 1..=2 {
+            state.journal.write().await.record(GraphDiff::new(sequence));
+        }
+        let progress = Mutex::new(ClientProgress::default());
+
+        // The full graph carries the sequence it's up to
+        let reply = handle_client_message(WsMessage::RequestFullGraph, &state, &progress).await.unwrap();
+        let reply: serde_json::Value = serde_json::from_str(&reply).unwrap();
+        assert_eq!((reply["type"].as_str(), reply["graph"]["sequence"].as_u64()), (Some("full_graph"), Some(2)));
+        let sent = progress.lock().unwrap().sent;
+        assert_eq!((progress.lock().unwrap().acked, sent), (2, 2));
+
+        // Diffs sent but not acknowledged are resent after a lag
+        for sequence in 3..=4 {
+            state.journal.write().await.record(GraphDiff::new(sequence));
+            let message = serde_json::json!({ "type": "graph_diff", "diff": GraphDiff::new(sequence) }).to_string();
+            assert_eq!(diff_sequence(&message), Some(sequence));
+            progress.lock().unwrap().sent = sequence;
+        }
+        assert!(handle_client_message(WsMessage::DiffAck { sequence: 3 }, &state, &progress).await.is_none());
+        let resent: Vec<Option<u64>> = catch_up(&state, &progress).await.iter().map(|m| diff_sequence(m)).collect();
+        assert_eq!(resent, [Some(4)]);
+
+        // Acknowledging more than was sent acknowledges what was
+        handle_client_message(WsMessage::DiffAck { sequence: 9 }, &state, &progress).await;
+        assert_eq!(progress.lock().unwrap().acked, 4);
+
+        // The full graph again once the journal has dropped what's missed
+        progress.lock().unwrap().acked = 1;
+        state.journal.write().await.record(GraphDiff::new(5));
+        let resent = catch_up(&state, &progress).await;
+        assert_eq!(resent.len(), 1);
+        assert!(resent[0].contains(r#""type":"full_graph""#));
+        assert_eq!(progress.lock().unwrap().acked, 5);
+        assert_eq!(diff_sequence(&resent[0]), None);
+    }
+
+    fn call(id: u64, source: NodeId, target: NodeId, confidence: f32) -> GraphEdge {
+        GraphEdge {
+            id: EdgeId(id),
+            source,
+            target,
+            kind: EdgeKind::Calls,
+            edge_source: canopy_core::EdgeSource::AI,
+            confidence,
+            label: None,
+            file_path: None,
+            line: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_subscription_filter() {
+        let mut graph = Graph::new();
+        let charge = graph.add_node(GraphNode::for_test(NodeKind::Function, "/repo/services/payments/charge.rs").with_language(Language::Rust));
+        let refund = graph.add_node(GraphNode::for_test(NodeKind::Function, "/repo/services/payments/refund.py").with_language(Language::Python));
+        let signup = graph.add_node(GraphNode::for_test(NodeKind::Function, "/repo/services/users/signup.rs").with_language(Language::Rust));
+        let sure = graph.add_edge(call(0, refund, charge, 0.9));
+        graph.add_edge(call(0, charge, refund, 0.4));
+        graph.add_edge(call(0, signup, charge, 0.9));
+        let state = Arc::new(ServerState::new(graph));
+        state.set_root(PathBuf::from("/repo")).await;
+        let progress = Mutex::new(ClientProgress::default());
+
+        // The full graph sent on subscribing is the part the filter keeps
+        let filter: SubscriptionFilter =
+            serde_json::from_str(r#"{"path": "services/payments", "min_confidence": 0.5}"#).unwrap();
+        let reply = handle_client_message(WsMessage::Subscribe { filter }, &state, &progress).await.unwrap();
+        let Ok(WsMessage::FullGraph { graph }) = serde_json::from_str(&reply) else { panic!("{}", reply) };
+        let nodes: HashSet<NodeId> = graph.nodes.iter().map(|n| n.id).collect();
+        assert_eq!(nodes, HashSet::from([charge, refund]));
+        assert_eq!(graph.edges.iter().map(|e| e.id).collect::<Vec<_>>(), [sure]);
+
+        // Diffs are cut down to it, and dropped when nothing is left
+        let mut diff = GraphDiff::new(1);
+        diff.added_nodes = vec![
+            GraphNode::for_test(NodeKind::Function, "/repo/services/payments/void.rs").with_id(100).with_language(Language::Rust),
+            GraphNode::for_test(NodeKind::Function, "/repo/services/users/login.rs").with_id(101).with_language(Language::Rust),
+        ];
+        diff.added_edges = vec![call(200, NodeId(100), charge, 0.9), call(201, NodeId(101), charge, 0.9)];
+        diff.removed_nodes = vec![refund, signup];
+        diff.removed_edges = vec![sure];
+        let message = serde_json::to_string(&WsMessage::GraphDiff { diff, explanation: None }).unwrap();
+        let sent = progress.lock().unwrap().forward(1, message).unwrap();
+        let Ok(WsMessage::GraphDiff { diff, .. }) = serde_json::from_str(&sent) else { panic!("{}", sent) };
+        assert_eq!(diff.added_nodes.iter().map(|n| n.id).collect::<Vec<_>>(), [NodeId(100)]);
+        assert_eq!(diff.added_edges.iter().map(|e| e.id).collect::<Vec<_>>(), [EdgeId(200)]);
+        assert_eq!((diff.removed_nodes, diff.removed_edges), (vec![refund], vec![sure]));
+        let mut elsewhere = GraphDiff::new(2);
+        elsewhere.removed_nodes = vec![signup];
+        let message = serde_json::to_string(&WsMessage::GraphDiff { diff: elsewhere, explanation: None }).unwrap();
+        assert!(progress.lock().unwrap().forward(2, message).is_none());
+
+        // Languages narrow it too, and unsubscribing widens it again
+        let filter = SubscriptionFilter { languages: vec![Language::Python], ..Default::default() };
+        let reply = handle_client_message(WsMessage::Subscribe { filter }, &state, &progress).await.unwrap();
+        let Ok(WsMessage::FullGraph { graph }) = serde_json::from_str(&reply) else { panic!("{}", reply) };
+        assert_eq!(graph.nodes.iter().map(|n| n.id).collect::<Vec<_>>(), [refund]);
+        let reply = handle_client_message(WsMessage::Unsubscribe, &state, &progress).await.unwrap();
+        let Ok(WsMessage::FullGraph { graph }) = serde_json::from_str(&reply) else { panic!("{}", reply) };
+        assert_eq!((graph.nodes.len(), graph.edges.len()), (3, 3));
+    }
 }
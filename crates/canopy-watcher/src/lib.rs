@@ -1,5 +1,7 @@
 //! Filesystem monitoring
 
+pub mod reindex;
 pub mod watcher;
 
+pub use reindex::{JobId, JobState, ReindexJob, ReindexJobs};
 pub use watcher::{FileWatcher, WatchEvent, WatcherService};
\ No newline at end of file
@@ -0,0 +1,97 @@
+//! Re-indexing on request
+//!
+//! After a large git operation the watcher may have missed changes, or
+//! seen so many that its view is stale. A [`ReindexJobs`] queue, shared
+//! between whoever asks and the [`WatcherService`](crate::WatcherService),
+//! takes requests to re-index everything or some paths. The watcher runs
+//! them one at a time between file events, and reports each job's progress
+//! in its [`ReindexJob`].
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use tokio::sync::mpsc;
+
+/// Identifies a re-index job; the first is 1.
+pub type JobId = u64;
+
+/// Finished jobs kept for their status to be asked after.
+const MAX_FINISHED_JOBS: usize = 100;
+
+/// Where a re-index job is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Queued,
+    Running,
+    Done,
+    Failed,
+}
+
+impl JobState {
+    pub fn is_finished(self) -> bool {
+        matches!(self, JobState::Done | JobState::Failed)
+    }
+}
+
+/// A request to re-index, and how far it has got.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReindexJob {
+    pub id: JobId,
+    /// Files and directories to re-index; everything if empty
+    pub paths: Vec<PathBuf>,
+    pub state: JobState,
+    /// Files to re-index, once they are known
+    pub files_total: usize,
+    pub files_done: usize,
+    /// Why the job failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Re-index jobs, queued for the watcher and kept after they finish.
+#[derive(Debug, Default)]
+pub struct ReindexJobs {
+    jobs: BTreeMap<JobId, ReindexJob>,
+    last_id: JobId,
+    queue: Option<mpsc::UnboundedSender<JobId>>,
+}
+
+impl ReindexJobs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Receive the ids of the jobs submitted from now on. Only the last
+    /// listener receives them.
+    pub fn listen(&mut self) -> mpsc::UnboundedReceiver<JobId> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.queue = Some(tx);
+        rx
+    }
+
+    /// Queue a re-index of `paths`, or of everything if there are none.
+    /// Returns `None` if nothing is listening to run it.
+    pub fn submit(&mut self, paths: Vec<PathBuf>) -> Option<ReindexJob> {
+        let id = self.last_id + 1;
+        self.queue.as_ref()?.send(id).ok()?;
+        self.last_id = id;
+        let job = ReindexJob { id, paths, state: JobState::Queued, files_total: 0, files_done: 0, error: None };
+        self.jobs.insert(id, job.clone());
+
+        // The oldest finished jobs are forgotten
+        let finished: Vec<JobId> = self.jobs.values().filter(|j| j.state.is_finished()).map(|j| j.id).collect();
+        for id in finished.iter().take(finished.len().saturating_sub(MAX_FINISHED_JOBS)) {
+            self.jobs.remove(id);
+        }
+        Some(job)
+    }
+
+    pub fn get(&self, id: JobId) -> Option<&ReindexJob> {
+        self.jobs.get(&id)
+    }
+
+    pub(crate) fn get_mut(&mut self, id: JobId) -> Option<&mut ReindexJob> {
+        self.jobs.get_mut(&id)
+    }
+}
@@ -1,17 +1,44 @@
 //! Filesystem watcher implementation
 
 use anyhow::Result;
-use canopy_core::{Graph, GraphDiff, NodeId, EdgeId, GraphNode, GraphEdge, EdgeSource};
+use canopy_core::{DiffJournal, Graph, GraphDiff, NodeId, EdgeId, GraphNode, GraphEdge, EdgeSource, NodeKind};
 use canopy_core::diff::DiffEngine;
 use canopy_indexer::ExtractionResult;
-use canopy_ai::bridge::{AIProvider, SemanticAnalysisRequest, AnalysisContext, SemanticRelationship};
+use canopy_indexer::coordinator::{add_extraction, count_lines, mark_parse_errors, Coordinator};
+use canopy_indexer::languages::is_indexable;
+use canopy_indexer::walk::{walker, IgnoreRules};
+use canopy_ai::bridge::{AIProvider, SemanticBatchRequest, AnalysisContext, SemanticConfig, InferredRelationship};
+use canopy_ai::cache::compute_content_hash;
+use canopy_ai::change::{ChangedEdge, FileSnapshot, GraphChange};
+use canopy_ai::hierarchy::{self, is_architectural};
+use canopy_ai::prompt::{change_explanation_prompt, container_summary_prompt, node_summary_prompt};
+use canopy_ai::redact::SensitiveFiles;
+use canopy_ai::review::{Inference, MAX_EXAMPLES};
+use canopy_ai::source::{collect_sources, node_source, MAX_SOURCE_LINES};
+use canopy_ai::{AnalysisCache, Budget, EmbeddingIndex, ReviewQueue};
 use notify::{RecommendedWatcher, RecursiveMode, Watcher};
 use std::collections::{HashSet, HashMap};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use tokio::sync::{mpsc, RwLock};
 use tracing::{debug, error, info, warn};
 
+use crate::reindex::{JobId, JobState, ReindexJob, ReindexJobs};
+
+/// Candidates offered for each node in an AI analysis.
+const CANDIDATES_PER_NODE: usize = 10;
+
+/// Files re-indexed between reports of a job's progress.
+const REINDEX_PROGRESS_EVERY: usize = 25;
+
+/// How long analyses are reused unless a cache is given.
+const ANALYSIS_TTL: std::time::Duration = std::time::Duration::from_secs(24 * 60 * 60);
+
+/// File updates between purges of the interned names and paths, each of
+/// which scans every interned value.
+const PURGE_EVERY: usize = 64;
+
 /// Events emitted by the file watcher
 #[derive(Debug, Clone)]
 pub enum WatchEvent {
@@ -40,11 +67,13 @@ impl FileWatcher {
         let (event_tx, event_rx) = mpsc::unbounded_channel();
         
         let event_tx_clone = event_tx.clone();
+        // Paths ignored by git or `.canopyignore` never become events
+        let ignore = IgnoreRules::new(&root_path);
         let watcher = notify::recommended_watcher(move |res: Result<notify::Event, notify::Error>| {
             match res {
                 Ok(event) => {
                     debug!("File system event: {:?}", event);
-                    Self::handle_notify_event(event, &event_tx_clone);
+                    Self::handle_notify_event(event, &event_tx_clone, &ignore);
                 }
                 Err(e) => {
                     error!("File system watch error: {}", e);
@@ -61,11 +90,11 @@ impl FileWatcher {
     }
 
     /// Handle a notify event and convert to our watch events
-    fn handle_notify_event(event: notify::Event, event_tx: &mpsc::UnboundedSender<WatchEvent>) {
+    fn handle_notify_event(event: notify::Event, event_tx: &mpsc::UnboundedSender<WatchEvent>, ignore: &IgnoreRules) {
         match event.kind {
             notify::EventKind::Create(_) => {
                 for path in event.paths {
-                    if should_ignore_path(&path) {
+                    if ignore.is_ignored(&path) {
                         continue;
                     }
                     if let Err(e) = event_tx.send(WatchEvent::Created(path)) {
@@ -75,7 +104,7 @@ impl FileWatcher {
             }
             notify::EventKind::Modify(_) => {
                 for path in event.paths {
-                    if should_ignore_path(&path) {
+                    if ignore.is_ignored(&path) {
                         continue;
                     }
                     if let Err(e) = event_tx.send(WatchEvent::Modified(path)) {
@@ -85,7 +114,7 @@ impl FileWatcher {
             }
             notify::EventKind::Remove(_) => {
                 for path in event.paths {
-                    if should_ignore_path(&path) {
+                    if ignore.is_ignored(&path) {
                         continue;
                     }
                     if let Err(e) = event_tx.send(WatchEvent::Removed(path)) {
@@ -152,8 +181,31 @@ pub struct WatcherService {
     /// Track which nodes belong to which file for incremental updates
     file_to_nodes: Arc<RwLock<HashMap<PathBuf, Vec<NodeId>>>>,
     file_to_edges: Arc<RwLock<HashMap<PathBuf, Vec<EdgeId>>>>,
+    /// Symbols and cross-file references of the indexed files
+    coordinator: Arc<RwLock<Coordinator>>,
     /// AI provider for semantic analysis
     ai_provider: Option<Arc<dyn AIProvider>>,
+    /// Limits on the AI requests made
+    ai_budget: Arc<RwLock<Budget>>,
+    /// Embeddings of the graph's nodes, for choosing analysis candidates
+    embeddings: Arc<RwLock<EmbeddingIndex>>,
+    /// Analyses already made, by node and file content
+    analysis_cache: Arc<RwLock<AnalysisCache>>,
+    /// AI edges waiting for review, and the decisions made on them
+    reviews: Arc<RwLock<ReviewQueue>>,
+    /// Files never shown to the AI provider
+    sensitive_files: SensitiveFiles,
+    /// The source of each file as last changed, to explain its next change
+    file_sources: Arc<RwLock<HashMap<PathBuf, String>>>,
+    /// Relationships to look for, and how confident to be of them
+    semantic: SemanticConfig,
+    /// Re-indexes asked for, run between file events
+    reindex: Arc<RwLock<ReindexJobs>>,
+    /// The diffs broadcast, for clients catching up
+    journal: Arc<RwLock<DiffJournal>>,
+    /// File updates and removals made, to purge the interner every
+    /// [`PURGE_EVERY`]
+    updates: AtomicUsize,
 }
 
 impl WatcherService {
@@ -168,7 +220,18 @@ impl WatcherService {
             diff_engine,
             file_to_nodes: Arc::new(RwLock::new(HashMap::new())),
             file_to_edges: Arc::new(RwLock::new(HashMap::new())),
+            coordinator: Arc::new(RwLock::new(Coordinator::new())),
             ai_provider: None,
+            ai_budget: Arc::new(RwLock::new(Budget::default())),
+            embeddings: Arc::new(RwLock::new(EmbeddingIndex::default())),
+            analysis_cache: Arc::new(RwLock::new(AnalysisCache::new(ANALYSIS_TTL))),
+            reviews: Arc::new(RwLock::new(ReviewQueue::new())),
+            sensitive_files: SensitiveFiles::default(),
+            file_sources: Arc::new(RwLock::new(HashMap::new())),
+            semantic: SemanticConfig::default(),
+            reindex: Arc::new(RwLock::new(ReindexJobs::new())),
+            journal: Arc::new(RwLock::new(DiffJournal::new())),
+            updates: AtomicUsize::new(0),
         })
     }
 
@@ -187,16 +250,87 @@ impl WatcherService {
             diff_engine,
             file_to_nodes: Arc::new(RwLock::new(HashMap::new())),
             file_to_edges: Arc::new(RwLock::new(HashMap::new())),
+            coordinator: Arc::new(RwLock::new(Coordinator::new())),
             ai_provider: None,
+            ai_budget: Arc::new(RwLock::new(Budget::default())),
+            embeddings: Arc::new(RwLock::new(EmbeddingIndex::default())),
+            analysis_cache: Arc::new(RwLock::new(AnalysisCache::new(ANALYSIS_TTL))),
+            reviews: Arc::new(RwLock::new(ReviewQueue::new())),
+            sensitive_files: SensitiveFiles::default(),
+            file_sources: Arc::new(RwLock::new(HashMap::new())),
+            semantic: SemanticConfig::default(),
+            reindex: Arc::new(RwLock::new(ReindexJobs::new())),
+            journal: Arc::new(RwLock::new(DiffJournal::new())),
+            updates: AtomicUsize::new(0),
         })
     }
 
+    /// Continue from a full index of the graph: its coordinator, and the
+    /// nodes it extracted from each file so changes replace them.
+    pub fn with_index(mut self, coordinator: Coordinator, file_nodes: HashMap<PathBuf, Vec<NodeId>>) -> Self {
+        self.coordinator = Arc::new(RwLock::new(coordinator));
+        self.file_to_nodes = Arc::new(RwLock::new(file_nodes));
+        self
+    }
+
     /// Set the AI provider for semantic analysis
     pub fn with_ai_provider(mut self, provider: Arc<dyn AIProvider>) -> Self {
         self.ai_provider = Some(provider);
         self
     }
 
+    /// Limit AI requests by `budget`, and record their spend in it.
+    pub fn with_ai_budget(mut self, budget: Arc<RwLock<Budget>>) -> Self {
+        self.ai_budget = budget;
+        self
+    }
+
+    /// Choose the candidates of AI analysis with `index`.
+    pub fn with_embeddings(mut self, index: EmbeddingIndex) -> Self {
+        self.embeddings = Arc::new(RwLock::new(index));
+        self
+    }
+
+    /// Reuse the analyses in `cache` for nodes of unchanged files.
+    pub fn with_analysis_cache(mut self, cache: AnalysisCache) -> Self {
+        self.analysis_cache = Arc::new(RwLock::new(cache));
+        self
+    }
+
+    /// Propose AI edges to `reviews`, shared with whoever decides on them,
+    /// rather than adding them to the graph.
+    pub fn with_reviews(mut self, reviews: Arc<RwLock<ReviewQueue>>) -> Self {
+        self.reviews = reviews;
+        self
+    }
+
+    /// Look for the relationships `config` names, keeping those as
+    /// confident as it asks.
+    pub fn with_semantic_config(mut self, config: SemanticConfig) -> Self {
+        self.semantic = config;
+        self
+    }
+
+    /// Keep the files matching `files` from the AI provider: they are
+    /// neither analyzed, summarized nor offered as candidates.
+    pub fn with_sensitive_files(mut self, files: SensitiveFiles) -> Self {
+        self.sensitive_files = files;
+        self
+    }
+
+    /// Run the re-index jobs submitted to `jobs` once processing events.
+    pub fn with_reindex(mut self, jobs: Arc<RwLock<ReindexJobs>>) -> Self {
+        self.reindex = jobs;
+        self
+    }
+
+    /// Record the diffs broadcast in `journal`, such as the server's, for
+    /// clients to replay.
+    pub fn with_journal(mut self, journal: Arc<RwLock<DiffJournal>>) -> Self {
+        self.journal = journal;
+        self
+    }
+
     /// Start watching the project directory
     pub async fn start_watching(&self) -> Result<()> {
         let mut watcher = self.watcher.write().await;
@@ -209,47 +343,149 @@ impl WatcherService {
         Ok(())
     }
 
-    /// Process file system events and update the graph
+    /// Process file system events and update the graph, and run the
+    /// re-index jobs submitted meanwhile
     pub async fn process_events(&self) -> Result<()> {
+        let mut jobs = self.reindex.write().await.listen();
         let mut watcher = self.watcher.write().await;
+        let root_path = watcher.root_path.clone();
         let event_rx = watcher.event_receiver();
-        
-        while let Some(event) = event_rx.recv().await {
-            debug!("Processing watch event: {:?}", event);
-            
-            match event {
-                WatchEvent::Created(path) => {
-                    info!("File created: {:?}", path);
-                    self.handle_file_change(&path).await?;
-                }
-                WatchEvent::Modified(path) => {
-                    info!("File modified: {:?}", path);
-                    self.handle_file_change(&path).await?;
-                }
-                WatchEvent::Removed(path) => {
-                    info!("File removed: {:?}", path);
-                    self.handle_file_removal(&path).await?;
-                }
-                WatchEvent::ChangesFlushed => {
-                    info!("Batch of changes completed");
-                }
+
+        loop {
+            tokio::select! {
+                event = event_rx.recv() => match event {
+                    Some(event) => self.handle_event(event).await?,
+                    None => break,
+                },
+                Some(job) = jobs.recv() => self.run_reindex(&root_path, job).await,
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn handle_event(&self, event: WatchEvent) -> Result<()> {
+        debug!("Processing watch event: {:?}", event);
+
+        match event {
+            WatchEvent::Created(path) => {
+                info!("File created: {:?}", path);
+                self.handle_file_change(&path).await?;
+            }
+            WatchEvent::Modified(path) => {
+                info!("File modified: {:?}", path);
+                self.handle_file_change(&path).await?;
+            }
+            WatchEvent::Removed(path) => {
+                info!("File removed: {:?}", path);
+                self.handle_file_removal(&path).await?;
+            }
+            WatchEvent::ChangesFlushed => {
+                info!("Batch of changes completed");
             }
         }
-        
         Ok(())
     }
 
+    /// Re-index the files of job `id` under `root` as if each had changed,
+    /// and drop those indexed before that are gone, reporting progress to
+    /// WebSocket clients as it goes
+    async fn run_reindex(&self, root: &Path, id: JobId) {
+        let Some(paths) = self.update_job(id, |job| job.state = JobState::Running).await else {
+            return;
+        };
+        let asked = |path: &Path| paths.is_empty() || paths.iter().any(|p| path.starts_with(p));
+        let mut files: Vec<PathBuf> = walker(root)
+            .build()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+            .map(|entry| entry.into_path())
+            .filter(|path| is_indexable(path) && asked(path))
+            .collect();
+        let gone: Vec<PathBuf> = {
+            let file_to_nodes = self.file_to_nodes.read().await;
+            file_to_nodes.keys().filter(|path| asked(path) && !path.exists()).cloned().collect()
+        };
+        let total = files.len() + gone.len();
+        info!("Re-indexing {} files for job {}", total, id);
+        self.update_job(id, |job| job.files_total = total).await;
+
+        files.extend(gone);
+        for (done, path) in files.iter().enumerate() {
+            let result = if path.exists() {
+                self.handle_file_change(path).await
+            } else {
+                self.handle_file_removal(path).await
+            };
+            if let Err(e) = result {
+                error!("Re-index job {} failed on {}: {}", id, path.display(), e);
+                self.update_job(id, |job| {
+                    job.state = JobState::Failed;
+                    job.error = Some(format!("{}: {}", path.display(), e));
+                })
+                .await;
+                return;
+            }
+            if (done + 1) % REINDEX_PROGRESS_EVERY == 0 {
+                self.update_job(id, |job| job.files_done = done + 1).await;
+            }
+        }
+        self.update_job(id, |job| {
+            job.files_done = total;
+            job.state = JobState::Done;
+        })
+        .await;
+        info!("Re-index job {} done", id);
+    }
+
+    /// Apply `update` to job `id` and tell WebSocket clients, returning the
+    /// job's paths, or `None` if it's unknown
+    async fn update_job(&self, id: JobId, update: impl FnOnce(&mut ReindexJob)) -> Option<Vec<PathBuf>> {
+        let mut jobs = self.reindex.write().await;
+        let job = jobs.get_mut(id)?;
+        update(job);
+        if let Some(ref diff_tx) = self.diff_tx {
+            match serde_json::to_string(&*job) {
+                Ok(json) => {
+                    let _ = diff_tx.send(format!(r#"{{"type":"reindex","job":{}}}"#, json));
+                }
+                Err(e) => error!("Failed to serialize re-index job: {}", e),
+            }
+        }
+        Some(job.paths.clone())
+    }
+
     /// Handle a file change event
     async fn handle_file_change(&self, path: &Path) -> Result<()> {
-        // Only process code files
-        if !is_code_file(path) {
+        // Only process files in a language or format Canopy indexes
+        if !is_indexable(path) {
             return Ok(());
         }
 
         info!("Processing code file change: {:?}", path);
 
         // Read file content
-        let content = match tokio::fs::read_to_string(path).await {
+        let content = match tokio::fs::read(path).await {
+            Ok(content) => content,
+            Err(e) => {
+                error!("Failed to read file {}: {}", path.display(), e);
+                return Ok(());
+            }
+        };
+
+        // Binaries, minified and generated files are not parsed; symbols
+        // from before the file became one are dropped
+        let skip_reason = {
+            let coordinator = self.coordinator.read().await;
+            let skipped_test = coordinator.extraction().skips_file(path).then(|| "test file".to_string());
+            skipped_test.or_else(|| coordinator.skip_rules().skip_reason(path, &content))
+        };
+        if let Some(reason) = skip_reason {
+            debug!("Skipping {}: {}", path.display(), reason);
+            return self.handle_file_removal(path).await;
+        }
+
+        let content = match String::from_utf8(content) {
             Ok(content) => content,
             Err(e) => {
                 error!("Failed to read file {}: {}", path.display(), e);
@@ -276,19 +512,35 @@ impl WatcherService {
             file_to_edges.get(path).cloned().unwrap_or_default()
         };
 
+        let before = self.snapshot(path, &old_nodes, &old_edges).await;
+
         // Update the graph incrementally
-        let mut graph_diff = self.update_graph_incrementally(path, extraction_result.clone(), old_nodes, old_edges).await?;
-
-        if let Some(summary_updates) = self.generate_node_summaries(path, &graph_diff.added_nodes).await? {
-            if !summary_updates.modified_ids.is_empty() {
-                graph_diff.modified_nodes.extend(summary_updates.modified_ids.clone());
-                // Update added nodes in the diff payload with the summaries
-                for node in &mut graph_diff.added_nodes {
-                    if let Some(summary) = summary_updates.summaries.get(&node.id) {
-                        node.metadata.insert("ai_summary".to_string(), summary.clone());
-                    }
-                }
+        let lines = count_lines(content.as_bytes());
+        let mut graph_diff = self.update_graph_incrementally(path, extraction_result.clone(), lines, old_nodes, old_edges).await?;
+
+        let after = {
+            let graph = self.graph.read().await;
+            FileSnapshot {
+                nodes: graph_diff.added_nodes.clone(),
+                edges: graph_diff.added_edges.iter().filter_map(|e| changed_edge(&graph, e)).collect(),
+                source: Some(content.clone()),
             }
+        };
+        let explanation = self.explain_change(path, &before, &after).await;
+
+        if let Some(summary_updates) = self.generate_node_summaries(path, &content, &graph_diff.added_nodes).await? {
+            summary_updates.apply(&mut graph_diff);
+        }
+
+        // The file, and the directories holding it, are described anew
+        // from their children
+        let changed: Vec<NodeId> = {
+            let graph = self.graph.read().await;
+            let file_node = graph.all_nodes().find(|n| n.kind == NodeKind::File && n.file_path == path).map(|n| n.id);
+            graph_diff.added_nodes.iter().map(|n| n.id).chain(file_node).collect()
+        };
+        if let Some(summary_updates) = self.summarize_containers(changed).await {
+            summary_updates.apply(&mut graph_diff);
         }
 
         // Perform AI semantic analysis on newly added nodes
@@ -296,14 +548,22 @@ impl WatcherService {
             match self.perform_ai_analysis(path, &content, &graph_diff.added_nodes).await {
                 Ok(ai_edges) => {
                     if !ai_edges.is_empty() {
-                        // Add AI-inferred edges to the graph
+                        // AI-inferred edges wait for review, unless accepted
+                        // before or confident enough to skip it
                         let mut graph = self.graph.write().await;
+                        let mut reviews = self.reviews.write().await;
                         let mut new_edge_ids = Vec::new();
-                        for mut edge in ai_edges {
-                            let edge_id = graph.add_edge(edge.clone());
-                            edge.id = edge_id;
-                            new_edge_ids.push(edge_id);
+                        for (edge, inference) in ai_edges {
+                            let accepted = if self.semantic.auto_accepts(edge.confidence) {
+                                reviews.auto_accept(edge, &graph)
+                            } else {
+                                reviews.propose(edge, inference, &graph)
+                            };
+                            if let Some(edge) = accepted {
+                                new_edge_ids.push(graph.add_edge(edge));
+                            }
                         }
+                        drop(reviews);
                         drop(graph);
 
                         // Update file_to_edges tracking
@@ -314,7 +574,7 @@ impl WatcherService {
                             }
                         }
 
-                        info!("Added {} AI-inferred edges for {:?}", new_edge_ids.len(), path);
+                        info!("Added {} accepted AI-inferred edges for {:?}", new_edge_ids.len(), path);
                     }
                 }
                 Err(e) => {
@@ -323,29 +583,99 @@ impl WatcherService {
             }
         }
 
-        // Broadcast the graph diff to WebSocket clients
+        self.publish_diff(graph_diff, explanation).await;
+        Ok(())
+    }
+
+    /// Record `diff` in the journal and broadcast it to WebSocket clients,
+    /// with the explanation of the change that made it.
+    async fn publish_diff(&self, diff: GraphDiff, explanation: Option<String>) {
+        let diff_json = match serde_json::to_string(&diff) {
+            Ok(json) => json,
+            Err(e) => {
+                error!("Failed to serialize graph diff: {}", e);
+                return;
+            }
+        };
+        // Journaled under the graph's lock, so a full graph sent to a
+        // client never goes out with a sequence its contents don't match
+        {
+            let _graph = self.graph.write().await;
+            self.journal.write().await.record(diff);
+        }
         if let Some(ref diff_tx) = self.diff_tx {
-            let diff_json = match serde_json::to_string(&graph_diff) {
-                Ok(json) => json,
-                Err(e) => {
-                    error!("Failed to serialize graph diff: {}", e);
-                    return Ok(());
-                }
-            };
+            // The change log panel shows the explanation with the diff
+            let explanation = explanation
+                .map(|explanation| format!(r#","explanation":{}"#, serde_json::Value::from(explanation)))
+                .unwrap_or_default();
             let message = format!(
-                r#"{{"type":"graph_diff","diff":{}}}"#,
-                diff_json
+                r#"{{"type":"graph_diff","diff":{}{}}}"#,
+                diff_json,
+                explanation
             );
             // It's okay if there are no receivers - just means no WebSocket clients connected
             let _ = diff_tx.send(message);
         }
+    }
 
-        Ok(())
+    /// The part of the graph of the file at `path`, made of `nodes` and
+    /// `edges`, with the source it had when last changed.
+    async fn snapshot(&self, path: &Path, nodes: &[NodeId], edges: &[EdgeId]) -> FileSnapshot {
+        if self.ai_provider.is_none() {
+            return FileSnapshot::default();
+        }
+        let graph = self.graph.read().await;
+        FileSnapshot {
+            nodes: nodes.iter().filter_map(|&id| graph.node(id).cloned()).collect(),
+            edges: edges.iter().filter_map(|&id| graph.edge(id)).filter_map(|e| changed_edge(&graph, e)).collect(),
+            source: self.file_sources.read().await.get(path).cloned(),
+        }
+    }
+
+    /// Explain the change to the file at `path` from `before` to `after`
+    /// for the change log, if it changed the graph.
+    async fn explain_change(&self, path: &Path, before: &FileSnapshot, after: &FileSnapshot) -> Option<String> {
+        let ai_provider = self.ai_provider.as_ref()?;
+        if self.sensitive_files.is_sensitive(path) {
+            return None;
+        }
+        if let Some(source) = &after.source {
+            self.file_sources.write().await.insert(path.to_path_buf(), source.clone());
+        }
+        let change = GraphChange::between(path, before, after);
+        if change.is_empty() {
+            return None;
+        }
+
+        // Explanations report no usage, so their estimate is what's spent
+        let estimate = Budget::estimate_tokens(&change_explanation_prompt(&change));
+        if !self.admit(estimate).await {
+            info!("AI budget exhausted, skipping explanation of {}", path.display());
+            return None;
+        }
+        match ai_provider.explain_change(&change).await {
+            Ok(explanation) => {
+                self.charge(estimate).await;
+                Some(explanation)
+            }
+            Err(err) => {
+                warn!("AI change explanation failed for {}: {}", path.display(), err);
+                None
+            }
+        }
+    }
+
+    /// Drop the interned values no node holds any more, once every
+    /// [`PURGE_EVERY`] updates rather than on each save.
+    fn purge_interned(&self) {
+        if self.updates.fetch_add(1, Ordering::Relaxed) % PURGE_EVERY == PURGE_EVERY - 1 {
+            canopy_core::intern::purge_unused();
+        }
     }
 
     /// Handle a file removal event
     async fn handle_file_removal(&self, path: &Path) -> Result<()> {
-        if !is_code_file(path) {
+        if !is_indexable(path) {
             return Ok(());
         }
 
@@ -369,7 +699,17 @@ impl WatcherService {
         for node_id in &nodes_to_remove {
             graph.remove_node(*node_id);
         }
+        // The file's lines no longer count towards its directories, as a
+        // skipped file's don't at startup
+        let file_node = graph.all_nodes().find(|n| n.kind == NodeKind::File && n.file_path == path).map(|n| n.id);
+        if let Some(file) = file_node.and_then(|id| graph.node_mut(id)) {
+            file.loc = None;
+        }
+        let refreshed = graph.refresh_rollups(&Vec::from_iter(file_node));
         drop(graph);
+        self.coordinator.write().await.remove_file(path);
+        // Names and paths only the file's symbols held are freed in time
+        self.purge_interned();
 
         // Update tracking maps
         {
@@ -385,48 +725,22 @@ impl WatcherService {
         let mut diff = GraphDiff::new(0);
         diff.removed_nodes = nodes_to_remove;
         diff.removed_edges = edges_to_remove;
+        diff.modified_nodes = refreshed;
 
         // Increment sequence and update
         let mut diff_engine = self.diff_engine.write().await;
+        diff.sequence = diff_engine.sequence() + 1;
         diff_engine.compute_diff(&Graph::new(), &Graph::new()); // Just to increment sequence
         drop(diff_engine);
 
-        // Broadcast the graph diff to WebSocket clients
-        if let Some(ref diff_tx) = self.diff_tx {
-            let diff_json = match serde_json::to_string(&diff) {
-                Ok(json) => json,
-                Err(e) => {
-                    error!("Failed to serialize graph diff: {}", e);
-                    return Ok(());
-                }
-            };
-            let message = format!(
-                r#"{{"type":"graph_diff","diff":{}}}"#,
-                diff_json
-            );
-            let _ = diff_tx.send(message);
-        }
-
+        self.publish_diff(diff, None).await;
         Ok(())
     }
 
-    /// Extract nodes and edges from a file using language-specific extractors
+    /// Extract nodes and edges from a file using language-specific
+    /// extractors, leaving out those the extraction config skips
     async fn extract_from_file(&self, path: &Path, content: &str) -> Result<ExtractionResult> {
-        let path_buf = path.to_path_buf();
-
-        // Get the appropriate extractor based on file extension
-        let extractor = canopy_indexer::languages::get_extractor(&path_buf);
-
-        if let Some(extractor) = extractor {
-            // Use the extractor to get nodes and edges
-            extractor.extract(&path_buf, content.as_bytes())
-        } else {
-            // No extractor available, return empty result
-            Ok(ExtractionResult {
-                nodes: Vec::new(),
-                edges: Vec::new(),
-            })
-        }
+        self.coordinator.read().await.extract(path, content.as_bytes())
     }
 
     /// Update the graph incrementally with new nodes and edges
@@ -434,6 +748,7 @@ impl WatcherService {
         &self,
         path: &Path,
         extraction_result: ExtractionResult,
+        lines: u32,
         old_nodes: Vec<NodeId>,
         old_edges: Vec<EdgeId>,
     ) -> Result<GraphDiff> {
@@ -447,30 +762,41 @@ impl WatcherService {
             graph.remove_node(*node_id);
         }
 
-        // Add new nodes and collect their IDs
-        let mut new_node_ids = Vec::new();
-        let mut added_nodes = Vec::new();
-        for mut node in extraction_result.nodes {
-            let node_id = graph.add_node(node.clone());
-            node.id = node_id;
-            new_node_ids.push(node_id);
-            added_nodes.push(node);
+        // Add new nodes and the edges between them
+        let added = add_extraction(&mut graph, path, extraction_result);
+        // The File node counts the file's lines and flags a partial parse,
+        // or stops flagging a fixed one
+        let file_node = graph.all_nodes().find(|n| n.kind == NodeKind::File && n.file_path == path).map(|n| n.id);
+        if let Some(file) = file_node.and_then(|id| graph.node_mut(id)) {
+            file.loc = Some(lines);
+            mark_parse_errors(file, &added.errors);
         }
-
-        // Add new edges and collect their IDs
-        let mut new_edge_ids = Vec::new();
-        let mut added_edges = Vec::new();
-        for mut edge in extraction_result.edges {
-            // Update edge source/target to point to actual node IDs if needed
-            // For now, edges reference nodes by their position in the extraction result
-            // This is a simplified approach - in production, you'd need proper node resolution
-            let edge_id = graph.add_edge(edge.clone());
-            edge.id = edge_id;
-            new_edge_ids.push(edge_id);
-            added_edges.push(edge);
+        let new_node_ids: Vec<NodeId> = added.nodes.iter().map(|n| n.id).collect();
+        let mut new_edge_ids = added.edges;
+        let mut added_edges: Vec<GraphEdge> = new_edge_ids.iter().filter_map(|&id| graph.edge(id).cloned()).collect();
+        let added_nodes = added.nodes;
+
+        // Resolve this file's references, and those of other files that
+        // point into it
+        let mut coordinator = self.coordinator.write().await;
+        coordinator.register_file(path, &added_nodes, added.references);
+        let mut linked = coordinator.resolve_references(&mut graph);
+        drop(coordinator);
+
+        // Docs, schemas and the code they link to may arrive in any order
+        linked.extend(canopy_indexer::heuristics::link_graph(&mut graph));
+        for edge_id in linked {
+            if let Some(edge) = graph.edge(edge_id) {
+                added_edges.push(edge.clone());
+                new_edge_ids.push(edge_id);
+            }
         }
 
+        // The file and the directories holding it are re-counted
+        let refreshed = graph.refresh_rollups(&Vec::from_iter(file_node));
         drop(graph);
+        // Names and paths only the replaced nodes held are freed in time
+        self.purge_interned();
 
         // Update tracking maps
         {
@@ -488,6 +814,7 @@ impl WatcherService {
         diff.removed_nodes = old_nodes;
         diff.added_edges = added_edges;
         diff.removed_edges = old_edges;
+        diff.modified_nodes = refreshed;
 
         // Update sequence number
         let mut diff_engine = self.diff_engine.write().await;
@@ -504,18 +831,19 @@ impl WatcherService {
         diff_engine.sequence()
     }
 
-    /// Perform AI semantic analysis on newly added nodes
+    /// Perform AI semantic analysis on newly added nodes, returning each
+    /// edge inferred with the provider's own confidence in it
     async fn perform_ai_analysis(
         &self,
         path: &Path,
-        _content: &str,
+        content: &str,
         added_nodes: &[GraphNode],
-    ) -> Result<Vec<GraphEdge>> {
+    ) -> Result<Vec<(GraphEdge, Inference)>> {
         let Some(ai_provider) = &self.ai_provider else {
             return Ok(Vec::new());
         };
 
-        if added_nodes.is_empty() {
+        if added_nodes.is_empty() || self.sensitive_files.is_sensitive(path) {
             return Ok(Vec::new());
         }
 
@@ -523,61 +851,136 @@ impl WatcherService {
 
         let mut ai_edges = Vec::new();
 
-        // Get all nodes in the graph as candidates for relationships
-        let candidate_nodes = {
+        // Analyze the file's functions and methods together, in as few
+        // requests as the budget's per-request limit allows
+        let mut source_nodes: Vec<GraphNode> = added_nodes
+            .iter()
+            .filter(|n| matches!(n.kind, canopy_core::NodeKind::Function | canopy_core::NodeKind::Method))
+            .cloned()
+            .collect();
+        if source_nodes.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // Nodes analyzed before in the same file content take their cached
+        // relationships instead of a request
+        let content_hash = compute_content_hash(content);
+        let mut results = Vec::new();
+        let nodes: Vec<GraphNode> = {
             let graph = self.graph.read().await;
-            graph.all_nodes().cloned().collect::<Vec<_>>()
+            let mut cache = self.analysis_cache.write().await;
+            source_nodes.retain(|node| match cache.get(node, content_hash, &graph) {
+                Some(result) => {
+                    results.push(result);
+                    false
+                }
+                None => true,
+            });
+            // Sensitive files' nodes are neither embedded nor offered
+            graph.all_nodes().filter(|n| !self.sensitive_files.is_sensitive(&n.file_path)).cloned().collect()
         };
+        if !results.is_empty() {
+            info!("Reusing cached AI analysis of {} nodes from {:?}", results.len(), path);
+        }
 
-        // Analyze each function/method node
-        for source_node in added_nodes.iter().filter(|n| {
-            matches!(n.kind, canopy_core::NodeKind::Function | canopy_core::NodeKind::Method)
-        }) {
-            // Build context for the analysis
-            let context = AnalysisContext {
-                file_path: path.to_path_buf(),
-                language: format!("{:?}", source_node.language.unwrap_or(canopy_core::Language::Other)),
-                enclosing_context: Vec::new(),
-                imports: Vec::new(),
-                project_context: HashMap::new(),
+        if !source_nodes.is_empty() {
+            // Offer the nodes most like the file's as candidates, rather
+            // than the whole graph
+            let chosen = {
+                let mut embeddings = self.embeddings.write().await;
+                // Candidates come from whatever the index holds, so a failed
+                // sync only makes them staler
+                if let Err(e) = embeddings.sync(&nodes).await {
+                    warn!("Failed to update the embedding index: {:#}", e);
+                }
+                embeddings.candidates(&source_nodes, CANDIDATES_PER_NODE).await?
             };
-
-            // Create analysis request
-            let request = SemanticAnalysisRequest {
-                source_node: source_node.clone(),
-                candidate_nodes: candidate_nodes.clone(),
-                context,
-                relationship_types: vec![
-                    SemanticRelationship::Calls,
-                    SemanticRelationship::DependsOn,
-                    SemanticRelationship::Uses,
-                ],
+            let mut nodes: HashMap<NodeId, GraphNode> = nodes.into_iter().map(|n| (n.id, n)).collect();
+            let candidate_nodes: Vec<GraphNode> = chosen
+                .into_iter()
+                .filter_map(|id| nodes.remove(&id))
+                .collect();
+            let sources = collect_sources(&source_nodes, content, &candidate_nodes);
+            let batch = SemanticBatchRequest {
+                source_nodes,
+                candidate_nodes,
+                context: AnalysisContext {
+                    file_path: path.to_path_buf(),
+                    language: format!("{:?}", canopy_core::Language::from_path(path)),
+                    enclosing_context: Vec::new(),
+                    imports: Vec::new(),
+                    project_context: HashMap::new(),
+                    examples: self.reviews.read().await.examples(MAX_EXAMPLES),
+                    sources,
+                },
+                relationship_types: self.semantic.relationship_types.clone(),
             };
 
-            // Call AI provider
-            match ai_provider.analyze_semantic_relationships(request).await {
-                Ok(result) => {
-                    info!("AI analysis found {} relationships for {}", result.relationships.len(), source_node.name);
-                    
-                    for rel in result.relationships {
-                        // Only accept high-confidence relationships
-                        if rel.confidence >= 0.7 {
-                            ai_edges.push(GraphEdge {
-                                id: EdgeId(0), // Will be set by graph
-                                source: rel.source_id,
-                                target: rel.target_id,
-                                kind: rel.relationship.into(),
-                                edge_source: EdgeSource::AI,
-                                confidence: rel.confidence,
-                                label: Some(rel.explanation),
-                                file_path: Some(path.to_path_buf()),
-                                line: rel.line_reference,
-                            });
+            // The batches the budget admits, all together, are sent at
+            // once, or as many of them as the provider's work queue allows
+            let mut admitted = Vec::new();
+            let batches = self.ai_budget.read().await.plan_batches(batch);
+            let mut planned: u32 = 0;
+            for batch in batches {
+                let estimate = Budget::estimate_batch(&batch);
+                if self.admit(planned.saturating_add(estimate)).await {
+                    planned = planned.saturating_add(estimate);
+                    admitted.push(batch);
+                } else {
+                    info!("AI budget exhausted, skipping analysis of {} nodes of {:?}", batch.source_nodes.len(), path);
+                }
+            }
+            let analyses = admitted.into_iter().map(|batch| async move {
+                let sources = batch.source_nodes.clone();
+                (sources, ai_provider.analyze_batch(batch).await)
+            });
+            for (sources, analysis) in futures_util::future::join_all(analyses).await {
+                let count = sources.len();
+                match analysis {
+                    Ok(result) => {
+                        info!("AI analysis found {} relationships for {} nodes", result.relationships.len(), count);
+                        self.ai_budget.write().await.record_result(&result);
+                        let graph = self.graph.read().await;
+                        let mut cache = self.analysis_cache.write().await;
+                        for source in &sources {
+                            let relationships: Vec<&InferredRelationship> = result
+                                .relationships
+                                .iter()
+                                .filter(|rel| rel.source_id == source.id)
+                                .collect();
+                            cache.insert(source, content_hash, &relationships, &result.provider, &graph);
                         }
+                        results.push(result);
+                    }
+                    Err(e) => {
+                        warn!("AI analysis failed for {} nodes of {:?}: {}", count, path, e);
                     }
                 }
-                Err(e) => {
-                    warn!("AI analysis failed for {}: {}", source_node.name, e);
+            }
+        }
+
+        // Label each edge with the provider behind it, which may be a
+        // fallback, and calibrate its confidence by how the provider's
+        // edges fared in review
+        let calibration = self.reviews.read().await.calibration();
+        for result in results {
+            for rel in result.relationships {
+                let kind = rel.relationship.into();
+                let confidence = calibration.adjust(&result.provider, kind, rel.confidence);
+                // Only accept relationships as confident as the project asks
+                if confidence >= self.semantic.confidence_threshold {
+                    let edge = GraphEdge {
+                        id: EdgeId(0), // Will be set by graph
+                        source: rel.source_id,
+                        target: rel.target_id,
+                        kind,
+                        edge_source: EdgeSource::AI,
+                        confidence,
+                        label: Some(format!("{} [{}]", rel.explanation, result.provider)),
+                        file_path: Some(path.into()),
+                        line: rel.line_reference,
+                    };
+                    ai_edges.push((edge, Inference { provider: result.provider.clone(), confidence: rel.confidence }));
                 }
             }
         }
@@ -586,34 +989,91 @@ impl WatcherService {
         Ok(ai_edges)
     }
 
+    /// Whether the provider's requests are paid for, and so budgeted.
+    fn is_billed(&self) -> bool {
+        self.ai_provider.as_ref().is_some_and(|p| p.is_billed())
+    }
+
+    /// Count `tokens` of a request reporting no usage as spent, if the
+    /// provider bills for them.
+    async fn charge(&self, tokens: u32) {
+        if self.is_billed() {
+            self.ai_budget.write().await.use_tokens(tokens);
+        }
+    }
+
+    /// Whether the budget allows a request of about `estimated_tokens`,
+    /// as it always does of a free provider. The first request refused
+    /// logs a warning.
+    async fn admit(&self, estimated_tokens: u32) -> bool {
+        if !self.is_billed() {
+            return true;
+        }
+        let mut budget = self.ai_budget.write().await;
+        if budget.admit(estimated_tokens) {
+            return true;
+        }
+        if budget.requests_skipped == 1 {
+            warn!("AI budget of {} tokens exhausted: AI analysis is paused", budget.total_tokens);
+        }
+        false
+    }
+
+    /// Summarize the functions, methods and containers among `added_nodes`
+    /// of the file with `content`, reusing summaries of unchanged source.
     async fn generate_node_summaries(
         &self,
         path: &Path,
+        content: &str,
         added_nodes: &[GraphNode],
     ) -> Result<Option<SummaryUpdates>> {
         let Some(ai_provider) = &self.ai_provider else {
             return Ok(None);
         };
 
-        if added_nodes.is_empty() {
+        if added_nodes.is_empty() || self.sensitive_files.is_sensitive(path) {
             return Ok(None);
         }
 
         let mut summaries = HashMap::new();
         let mut modified_ids = Vec::new();
 
-        for node in added_nodes {
+        // Files, modules and directories are summarized from their children
+        // by `summarize_containers`
+        let summarized = added_nodes
+            .iter()
+            .filter(|n| n.is_container || matches!(n.kind, NodeKind::Function | NodeKind::Method))
+            .filter(|n| !is_architectural(n.kind));
+        for node in summarized {
+            // A summary holds as long as the node's own source is unchanged
+            let source_hash = compute_content_hash(node_source(content, node, usize::MAX).as_deref().unwrap_or(content));
+            if let Some(summary) = self.analysis_cache.write().await.get_summary(node, source_hash) {
+                summaries.insert(node.id, summary);
+                modified_ids.push(node.id);
+                continue;
+            }
+
             let context = AnalysisContext {
                 file_path: path.to_path_buf(),
                 language: format!("{:?}", node.language.unwrap_or(canopy_core::Language::Other)),
                 enclosing_context: Vec::new(),
                 imports: Vec::new(),
                 project_context: HashMap::new(),
+                examples: Vec::new(),
+                sources: node_source(content, node, MAX_SOURCE_LINES).map(|source| (node.id, source)).into_iter().collect(),
             };
 
+            // Summaries report no usage, so their estimate is what's spent
+            let estimate = Budget::estimate_tokens(&node_summary_prompt(node, &context));
+            if !self.admit(estimate).await {
+                info!("AI budget exhausted, skipping summary of {}", node.name);
+                continue;
+            }
             match ai_provider.generate_node_summary(node, &context).await {
                 Ok(summary) => {
-                    summaries.insert(node.id, summary.clone());
+                    self.charge(estimate).await;
+                    self.analysis_cache.write().await.insert_summary(node, source_hash, &summary);
+                    summaries.insert(node.id, summary);
                     modified_ids.push(node.id);
                 }
                 Err(err) => {
@@ -630,7 +1090,7 @@ impl WatcherService {
             let mut graph = self.graph.write().await;
             for (node_id, summary) in summaries.iter() {
                 if let Some(node) = graph.node_mut(*node_id) {
-                    node.metadata.insert("ai_summary".to_string(), summary.clone());
+                    node.metadata.extra.insert("ai_summary".to_string(), summary.clone());
                 }
             }
         }
@@ -640,6 +1100,85 @@ impl WatcherService {
             modified_ids,
         }))
     }
+
+    /// Summarize every file, module and directory of the project, deepest
+    /// first. Summaries of unchanged containers come from the cache.
+    pub async fn summarize_architecture(&self) -> usize {
+        let containers = hierarchy::all_architecture(&*self.graph.read().await);
+        self.summarize_containers(containers).await.map_or(0, |updates| updates.modified_ids.len())
+    }
+
+    /// Summarize the files, modules and directories among `ids` and those
+    /// containing them, bottom-up: each is described from its children,
+    /// including the summaries just given to them.
+    async fn summarize_containers(&self, ids: Vec<NodeId>) -> Option<SummaryUpdates> {
+        let ai_provider = self.ai_provider.as_ref()?;
+        let containers = hierarchy::bottom_up(&*self.graph.read().await, ids);
+
+        let mut summaries = HashMap::new();
+        let mut modified_ids = Vec::new();
+        for id in containers {
+            let (node, children) = {
+                let graph = self.graph.read().await;
+                let Some(node) = graph.node(id).cloned() else {
+                    continue;
+                };
+                if self.sensitive_files.is_sensitive(&node.file_path) {
+                    continue;
+                }
+                (node, hierarchy::children(&graph, id))
+            };
+            if children.is_empty() {
+                continue;
+            }
+
+            // A summary holds as long as the children and their summaries do
+            let prompt = container_summary_prompt(&node, &children);
+            let prompt_hash = compute_content_hash(&prompt);
+            let cached = self.analysis_cache.write().await.get_summary(&node, prompt_hash);
+            let summary = match cached {
+                Some(summary) => summary,
+                None => {
+                    let estimate = Budget::estimate_tokens(&prompt);
+                    if !self.admit(estimate).await {
+                        info!("AI budget exhausted, skipping summary of {}", node.name);
+                        continue;
+                    }
+                    match ai_provider.summarize_container(&node, &children).await {
+                        Ok(summary) => {
+                            self.charge(estimate).await;
+                            self.analysis_cache.write().await.insert_summary(&node, prompt_hash, &summary);
+                            summary
+                        }
+                        Err(err) => {
+                            warn!("AI summary failed for {}: {}", node.name, err);
+                            continue;
+                        }
+                    }
+                }
+            };
+            if node.metadata.extra.get("ai_summary") == Some(&summary) {
+                continue;
+            }
+
+            if let Some(node) = self.graph.write().await.node_mut(id) {
+                node.metadata.extra.insert("ai_summary".to_string(), summary.clone());
+            }
+            summaries.insert(id, summary);
+            modified_ids.push(id);
+        }
+
+        (!summaries.is_empty()).then_some(SummaryUpdates { summaries, modified_ids })
+    }
+}
+
+/// `edge`, by the qualified names of the nodes it joins.
+fn changed_edge(graph: &Graph, edge: &GraphEdge) -> Option<ChangedEdge> {
+    Some(ChangedEdge {
+        source: graph.node(edge.source)?.qualified_name.to_string(),
+        kind: edge.kind,
+        target: graph.node(edge.target)?.qualified_name.to_string(),
+    })
 }
 
 struct SummaryUpdates {
@@ -647,25 +1186,17 @@ struct SummaryUpdates {
     modified_ids: Vec<NodeId>,
 }
 
-/// Check if a path is a code file we should process
-fn is_code_file(path: &Path) -> bool {
-    match path.extension().and_then(|s| s.to_str()) {
-        Some("rs") | Some("ts") | Some("js") | Some("jsx") | Some("tsx") | Some("py") | Some("go") | Some("java") | Some("cpp") | Some("c") | Some("h") => true,
-        _ => false,
-    }
-}
-
-/// Check if a path should be ignored (e.g., target/, .git/, etc.)
-fn should_ignore_path(path: &Path) -> bool {
-    // Check if any component of the path is a directory we should ignore
-    for component in path.components() {
-        if let Some(name) = component.as_os_str().to_str() {
-            if name == "target" || name == ".git" || name == "node_modules" || name == ".openclaw" {
-                return true;
+impl SummaryUpdates {
+    /// Mark the summarized nodes modified in `diff`, and carry their
+    /// summaries in the nodes it adds.
+    fn apply(self, diff: &mut GraphDiff) {
+        for node in &mut diff.added_nodes {
+            if let Some(summary) = self.summaries.get(&node.id) {
+                node.metadata.extra.insert("ai_summary".to_string(), summary.clone());
             }
         }
+        diff.modified_nodes.extend(self.modified_ids);
     }
-    false
 }
 
 #[cfg(test)]
@@ -708,12 +1239,467 @@ mod tests {
         }
     }
 
-    #[test]
-    fn test_is_code_file() {
-        assert!(is_code_file(Path::new("test.rs")));
-        assert!(is_code_file(Path::new("main.ts")));
-        assert!(is_code_file(Path::new("app.js")));
-        assert!(!is_code_file(Path::new("readme.md")));
-        assert!(!is_code_file(Path::new("image.png")));
+    #[tokio::test]
+    async fn test_ai_budget_enforced() {
+        let temp_dir = TempDir::new().unwrap();
+        let graph = Arc::new(RwLock::new(Graph::new()));
+        let node = GraphNode::for_test(NodeKind::Function, "load")
+            .with_qualified_name("src/lib.rs::load")
+            .in_file("src/lib.rs")
+            .with_lines(1, 3)
+            .with_language(canopy_core::Language::Rust);
+        let id = graph.write().await.add_node(node);
+        let node = graph.read().await.node(id).unwrap().clone();
+
+        let budget = Arc::new(RwLock::new(Budget::new(100)));
+        let service = WatcherService::new(temp_dir.path(), Arc::clone(&graph))
+            .unwrap()
+            .with_ai_provider(Arc::new(Billed::local()))
+            .with_ai_budget(Arc::clone(&budget));
+
+        // Neither analysis nor summaries fit in 100 tokens
+        let edges = service.perform_ai_analysis(Path::new("src/lib.rs"), "fn load() {}", std::slice::from_ref(&node)).await.unwrap();
+        assert!(edges.is_empty());
+        assert!(service.generate_node_summaries(Path::new("src/lib.rs"), "fn load() {}", std::slice::from_ref(&node)).await.unwrap().is_none());
+        assert_eq!((budget.read().await.requests_skipped, budget.read().await.tokens_used), (2, 0));
+
+        // A free provider is neither limited nor charged
+        let free = canopy_ai::providers::create_provider("local", None).unwrap();
+        let service = service.with_ai_provider(Arc::from(free));
+        assert!(service.generate_node_summaries(Path::new("src/lib.rs"), "fn load() {}", &[node]).await.unwrap().is_some());
+        assert_eq!((budget.read().await.requests_skipped, budget.read().await.tokens_used), (2, 0));
+    }
+
+    /// The local provider, charged for as a paid one would be.
+    struct Billed(Box<dyn AIProvider>);
+
+    impl Billed {
+        fn local() -> Self {
+            Billed(canopy_ai::providers::create_provider("local", None).unwrap())
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl AIProvider for Billed {
+        async fn analyze_semantic_relationships(
+            &self,
+            request: canopy_ai::SemanticAnalysisRequest,
+        ) -> Result<canopy_ai::SemanticAnalysisResult> {
+            self.0.analyze_semantic_relationships(request).await
+        }
+
+        async fn generate_node_summary(&self, node: &GraphNode, context: &AnalysisContext) -> Result<String> {
+            self.0.generate_node_summary(node, context).await
+        }
+
+        async fn summarize_container(&self, node: &GraphNode, children: &[GraphNode]) -> Result<String> {
+            self.0.summarize_container(node, children).await
+        }
+
+        async fn answer_code_question(&self, question: &str, nodes: &[GraphNode], edges: &[GraphEdge]) -> Result<String> {
+            self.0.answer_code_question(question, nodes, edges).await
+        }
+
+        fn name(&self) -> &str {
+            self.0.name()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sensitive_files_kept_from_ai() {
+        let temp_dir = TempDir::new().unwrap();
+        let graph = Arc::new(RwLock::new(Graph::new()));
+        let node = GraphNode::for_test(NodeKind::Function, "connect")
+            .with_qualified_name("vault/keys.rs::connect")
+            .in_file("vault/keys.rs")
+            .with_lines(1, 1)
+            .with_language(canopy_core::Language::Rust);
+        let id = graph.write().await.add_node(node);
+        let node = graph.read().await.node(id).unwrap().clone();
+
+        let provider = canopy_ai::providers::create_provider("local", None).unwrap();
+        let budget = Arc::new(RwLock::new(Budget::default()));
+        let service = WatcherService::new(temp_dir.path(), Arc::clone(&graph))
+            .unwrap()
+            .with_ai_provider(Arc::from(provider))
+            .with_ai_budget(Arc::clone(&budget))
+            .with_sensitive_files(SensitiveFiles::new(&["**/vault/**"]));
+
+        // Nothing of the file is sent, so nothing is spent
+        let path = Path::new("vault/keys.rs");
+        assert!(service.perform_ai_analysis(path, "fn connect() {}", std::slice::from_ref(&node)).await.unwrap().is_empty());
+        assert!(service.generate_node_summaries(path, "fn connect() {}", &[node]).await.unwrap().is_none());
+        assert_eq!(service.summarize_containers(vec![id]).await.map(|u| u.modified_ids), None);
+        assert_eq!(budget.read().await.tokens_used, 0);
+    }
+
+    /// Records the texts it is asked to embed.
+    struct RecordingEmbedder(Arc<std::sync::Mutex<Vec<String>>>);
+
+    #[async_trait::async_trait]
+    impl canopy_ai::embedding::Embedder for RecordingEmbedder {
+        async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+            self.0.lock().unwrap().extend_from_slice(texts);
+            Ok(texts.iter().map(|_| vec![1.0]).collect())
+        }
+
+        fn name(&self) -> &str {
+            "recording"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sensitive_files_not_embedded() {
+        let temp_dir = TempDir::new().unwrap();
+        let graph = Arc::new(RwLock::new(Graph::new()));
+        let (secret, load) = {
+            let mut graph = graph.write().await;
+            let secret = graph.add_node(GraphNode::for_test(NodeKind::Function, "connect").in_file("vault/keys.rs").with_lines(1, 1));
+            let load = graph.add_node(GraphNode::for_test(NodeKind::Function, "load").in_file("src/lib.rs").with_lines(1, 1));
+            (graph.node(secret).unwrap().clone(), graph.node(load).unwrap().clone())
+        };
+
+        let embedded = Arc::default();
+        let provider = canopy_ai::providers::create_provider("local", None).unwrap();
+        let service = WatcherService::new(temp_dir.path(), Arc::clone(&graph))
+            .unwrap()
+            .with_ai_provider(Arc::from(provider))
+            .with_embeddings(EmbeddingIndex::new(Box::new(RecordingEmbedder(Arc::clone(&embedded)))))
+            .with_sensitive_files(SensitiveFiles::new(&["**/vault/**"]));
+
+        service.perform_ai_analysis(Path::new("src/lib.rs"), "fn load() {}", &[load]).await.unwrap();
+        let embedded = embedded.lock().unwrap();
+        assert!(!embedded.is_empty());
+        assert!(embedded.iter().all(|text| !text.contains(&secret.name)), "{:?}", embedded);
+    }
+
+    #[tokio::test]
+    async fn test_summaries_cached() {
+        let temp_dir = TempDir::new().unwrap();
+        let graph = Arc::new(RwLock::new(Graph::new()));
+        let node = |name: &str, kind, lines: (u32, u32)| GraphNode::for_test(kind, name)
+            .with_qualified_name(&format!("src/lib.rs::{}", name))
+            .in_file("src/lib.rs")
+            .with_lines(lines.0, lines.1)
+            .with_language(canopy_core::Language::Rust)
+            .with_container(false);
+        let nodes: Vec<GraphNode> = {
+            let mut graph = graph.write().await;
+            let ids = [
+                graph.add_node(node("load", NodeKind::Function, (1, 1))),
+                graph.add_node(node("LIMIT", NodeKind::Constant, (2, 2))),
+            ];
+            ids.iter().map(|&id| graph.node(id).unwrap().clone()).collect()
+        };
+
+        let budget = Arc::new(RwLock::new(Budget::default()));
+        let service = WatcherService::new(temp_dir.path(), Arc::clone(&graph))
+            .unwrap()
+            .with_ai_provider(Arc::new(Billed::local()))
+            .with_ai_budget(Arc::clone(&budget));
+        let path = Path::new("src/lib.rs");
+
+        // Functions are summarized, into their metadata; constants aren't
+        let updates = service.generate_node_summaries(path, "fn load() {}\nconst LIMIT: u32 = 1;", &nodes).await.unwrap().unwrap();
+        assert_eq!(updates.modified_ids, [nodes[0].id]);
+        assert!(graph.read().await.node(nodes[0].id).unwrap().metadata.extra.contains_key("ai_summary"));
+        let spent = budget.read().await.tokens_used;
+        assert!(spent > 0);
+
+        // The same source is summarized from the cache, whatever else changed
+        let updates = service.generate_node_summaries(path, "fn load() {}\nconst LIMIT: u32 = 2;", &nodes).await.unwrap().unwrap();
+        assert_eq!(updates.modified_ids, [nodes[0].id]);
+        assert_eq!(budget.read().await.tokens_used, spent);
+        service.generate_node_summaries(path, "fn load() { run() }", &nodes).await.unwrap();
+        assert!(budget.read().await.tokens_used > spent);
+    }
+
+    /// Relates each function to each other it's offered, surely if the
+    /// other is `parse` and doubtfully otherwise.
+    struct Scored;
+
+    #[async_trait::async_trait]
+    impl AIProvider for Scored {
+        async fn analyze_semantic_relationships(
+            &self,
+            _request: canopy_ai::SemanticAnalysisRequest,
+        ) -> Result<canopy_ai::SemanticAnalysisResult> {
+            anyhow::bail!("not used in this test")
+        }
+
+        async fn analyze_batch(&self, batch: SemanticBatchRequest) -> Result<canopy_ai::SemanticAnalysisResult> {
+            assert_eq!(batch.relationship_types, [canopy_ai::SemanticRelationship::TestedBy]);
+            let mut relationships = Vec::new();
+            for source in &batch.source_nodes {
+                for candidate in batch.candidate_nodes.iter().filter(|c| c.id != source.id && c.kind == NodeKind::Function) {
+                    relationships.push(InferredRelationship {
+                        source_id: source.id,
+                        target_id: candidate.id,
+                        relationship: canopy_ai::SemanticRelationship::TestedBy,
+                        confidence: if candidate.name == "parse" { 0.95 } else { 0.6 },
+                        explanation: String::new(),
+                        line_reference: None,
+                    });
+                }
+            }
+            Ok(canopy_ai::SemanticAnalysisResult {
+                relationships,
+                explanation: String::new(),
+                tokens_used: 0,
+                provider: "scored".to_string(),
+                usage: Vec::new(),
+            })
+        }
+
+        async fn generate_node_summary(&self, _node: &GraphNode, _context: &AnalysisContext) -> Result<String> {
+            Ok(String::new())
+        }
+
+        async fn answer_code_question(&self, _question: &str, _nodes: &[GraphNode], _edges: &[GraphEdge]) -> Result<String> {
+            anyhow::bail!("not used in this test")
+        }
+
+        fn name(&self) -> &str {
+            "scored"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_semantic_config_applied() {
+        let temp_dir = TempDir::new().unwrap();
+        let graph = Arc::new(RwLock::new(Graph::new()));
+        let reviews = Arc::new(RwLock::new(ReviewQueue::new()));
+        let config = SemanticConfig::parse(
+            "[ai]\nrelationship_types = [\"TestedBy\"]\nconfidence_threshold = 0.5\nauto_accept_threshold = 0.9\n",
+        )
+        .unwrap();
+        let service = WatcherService::new(temp_dir.path(), Arc::clone(&graph))
+            .unwrap()
+            .with_ai_provider(Arc::new(Scored))
+            .with_reviews(Arc::clone(&reviews))
+            .with_semantic_config(config);
+        for (file, source) in [("util.rs", "fn parse() {}\nfn check() {}\n"), ("lib.rs", "fn load() {}\n")] {
+            let path = temp_dir.path().join(file);
+            std::fs::write(&path, source).unwrap();
+            service.handle_file_change(&path).await.unwrap();
+        }
+
+        // The sure edge joins the graph, the doubtful one waits for review
+        let graph = graph.read().await;
+        let ai_edges: Vec<_> = graph.all_edges().filter(|e| e.edge_source == EdgeSource::AI).collect();
+        assert_eq!(ai_edges.len(), 1);
+        assert_eq!(graph.node(ai_edges[0].target).unwrap().name, "parse");
+        let proposed: Vec<_> = reviews.read().await.proposed().map(|p| p.edge.confidence).collect();
+        assert_eq!(proposed, [0.6]);
+    }
+
+    #[tokio::test]
+    async fn test_change_explained() {
+        let temp_dir = TempDir::new().unwrap();
+        let graph = Arc::new(RwLock::new(Graph::new()));
+        let (diff_tx, mut diff_rx) = tokio::sync::broadcast::channel(16);
+        let provider = canopy_ai::providers::create_provider("local", None).unwrap();
+        let service = WatcherService::with_broadcast(temp_dir.path(), graph, diff_tx)
+            .unwrap()
+            .with_ai_provider(Arc::from(provider));
+        let path = temp_dir.path().join("lib.rs");
+        let mut explanation = async |source: &str| {
+            std::fs::write(&path, source).unwrap();
+            service.handle_file_change(&path).await.unwrap();
+            let message: serde_json::Value = serde_json::from_str(&diff_rx.recv().await.unwrap()).unwrap();
+            assert_eq!(message["type"], "graph_diff");
+            message["explanation"].as_str().map(str::to_string)
+        };
+
+        // The explanation is broadcast with the diff, of what changed since
+        // the file was last seen
+        let first = explanation("fn load() {}\n").await.unwrap();
+        assert!(first.contains("added load"), "{}", first);
+        let second = explanation("fn load() {}\nfn parse() {}\n").await.unwrap();
+        assert!(second.contains("added parse"), "{}", second);
+        assert!(!second.contains("load"), "{}", second);
+        let third = explanation("fn load() { parse() }\nfn parse() {}\n").await.unwrap();
+        assert!(third.contains("changed load"), "{}", third);
+
+        // Saving the same source changes nothing worth explaining
+        assert_eq!(explanation("fn load() { parse() }\nfn parse() {}\n").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_containers_summarized_bottom_up() {
+        let temp_dir = TempDir::new().unwrap();
+        let graph = Arc::new(RwLock::new(Graph::new()));
+        let node = |name: &str, kind| GraphNode::for_test(kind, name)
+            .in_file("src/lib.rs")
+            .with_container(kind != NodeKind::Function);
+        let (src, lib, load) = {
+            let mut graph = graph.write().await;
+            let src = graph.add_node(node("src", NodeKind::Directory));
+            let lib = graph.add_node(node("lib.rs", NodeKind::File));
+            let load = graph.add_node(node("load", NodeKind::Function));
+            for (source, target) in [(src, lib), (lib, load)] {
+                graph.add_edge(GraphEdge {
+                    id: EdgeId(0),
+                    source,
+                    target,
+                    kind: canopy_core::EdgeKind::Contains,
+                    edge_source: EdgeSource::Structural,
+                    confidence: 1.0,
+                    label: None,
+                    file_path: None,
+                    line: None,
+                });
+            }
+            (src, lib, load)
+        };
+
+        let budget = Arc::new(RwLock::new(Budget::default()));
+        let service = WatcherService::new(temp_dir.path(), Arc::clone(&graph))
+            .unwrap()
+            .with_ai_provider(Arc::new(Billed::local()))
+            .with_ai_budget(Arc::clone(&budget));
+
+        assert_eq!(service.summarize_architecture().await, 2);
+        let summary = |id| graph.try_read().unwrap().node(id).unwrap().metadata.extra.get("ai_summary").cloned();
+        assert!(summary(lib).unwrap().contains("load"));
+        assert!(summary(src).unwrap().contains("lib.rs"));
+        let spent = budget.read().await.tokens_used;
+
+        // Unchanged containers aren't asked about again, but a new summary
+        // of a child changes its parent's prompt
+        assert_eq!(service.summarize_architecture().await, 0);
+        assert_eq!(budget.read().await.tokens_used, spent);
+        graph.write().await.node_mut(load).unwrap().metadata.extra.insert("ai_summary".to_string(), "Loads config".to_string());
+        service.summarize_architecture().await;
+        assert!(budget.read().await.tokens_used > spent);
+    }
+
+    #[tokio::test]
+    async fn test_rollups_follow_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("lib.rs");
+        let graph = Arc::new(RwLock::new(Graph::new()));
+        let node = |name: &str, kind| GraphNode::for_test(kind, name)
+            .in_file(path.as_path())
+            .with_container(true);
+        let (src, lib) = {
+            let mut graph = graph.write().await;
+            let src = graph.add_node(node("src", NodeKind::Directory));
+            let lib = graph.add_node(node("lib.rs", NodeKind::File));
+            graph.add_edge(GraphEdge {
+                id: EdgeId(0),
+                source: src,
+                target: lib,
+                kind: canopy_core::EdgeKind::Contains,
+                edge_source: EdgeSource::Structural,
+                confidence: 1.0,
+                label: None,
+                file_path: None,
+                line: None,
+            });
+            (src, lib)
+        };
+        let journal = Arc::new(RwLock::new(DiffJournal::new()));
+        let service = WatcherService::new(temp_dir.path(), Arc::clone(&graph))
+            .unwrap()
+            .with_journal(Arc::clone(&journal));
+        let loc = async |id| graph.read().await.node(id).unwrap().loc;
+
+        // The file keeps its own line count, blank lines and all, and its
+        // directory is re-counted with it
+        std::fs::write(&path, "fn load() {}\n\n// Saves\nfn save() {}\n").unwrap();
+        service.handle_file_change(&path).await.unwrap();
+        assert_eq!(loc(lib).await, Some(4));
+        assert_eq!(loc(src).await, Some(4));
+        std::fs::write(&path, "fn load() {}\n").unwrap();
+        service.handle_file_change(&path).await.unwrap();
+        assert_eq!(loc(src).await, Some(1));
+        let modified = journal.read().await.since(1).unwrap()[0].modified_nodes.clone();
+        assert_eq!(modified, vec![lib, src]);
+
+        std::fs::remove_file(&path).unwrap();
+        service.handle_file_removal(&path).await.unwrap();
+        assert_eq!(loc(lib).await, None);
+        assert_eq!(loc(src).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_reindex_job() {
+        let temp_dir = TempDir::new().unwrap();
+        let src = temp_dir.path().join("src");
+        std::fs::create_dir(&src).unwrap();
+        std::fs::write(src.join("lib.rs"), "fn load() {}\nfn parse() {}\n").unwrap();
+        std::fs::write(temp_dir.path().join("build.rs"), "fn main() {}\n").unwrap();
+        std::fs::write(temp_dir.path().join("Cargo.toml"), "[package]\nname = \"demo\"\n").unwrap();
+        let graph = Arc::new(RwLock::new(Graph::new()));
+        let (diff_tx, mut diff_rx) = tokio::sync::broadcast::channel(16);
+        let jobs = Arc::new(RwLock::new(ReindexJobs::new()));
+        let journal = Arc::new(RwLock::new(DiffJournal::new()));
+        let service = WatcherService::with_broadcast(temp_dir.path(), Arc::clone(&graph), diff_tx)
+            .unwrap()
+            .with_reindex(Arc::clone(&jobs))
+            .with_journal(Arc::clone(&journal));
+        let _queue = jobs.write().await.listen();
+
+        // Only the files under the paths asked for are indexed
+        let job = jobs.write().await.submit(vec![src.clone()]).unwrap();
+        service.run_reindex(temp_dir.path(), job.id).await;
+        let finished = jobs.read().await.get(job.id).unwrap().clone();
+        assert_eq!((finished.state, finished.files_total, finished.files_done), (JobState::Done, 1, 1));
+        let names: HashSet<String> = graph.read().await.all_nodes().map(|n| n.name.clone()).collect();
+        assert!(names.contains("load") && names.contains("parse") && !names.contains("main"));
+
+        // Clients hear the job start and finish around the file's diff
+        let mut kinds = Vec::new();
+        while let Ok(message) = diff_rx.try_recv() {
+            let message: serde_json::Value = serde_json::from_str(&message).unwrap();
+            kinds.push(format!("{} {}", message["type"].as_str().unwrap(), message["job"]["state"].as_str().unwrap_or("")));
+        }
+        assert_eq!(kinds, ["reindex running", "reindex running", "graph_diff ", "reindex done"]);
+
+        // A file gone since is dropped by a full re-index
+        std::fs::remove_file(src.join("lib.rs")).unwrap();
+        let job = jobs.write().await.submit(Vec::new()).unwrap();
+        service.run_reindex(temp_dir.path(), job.id).await;
+        // Config files are re-indexed with the code
+        assert_eq!(jobs.read().await.get(job.id).unwrap().files_total, 3);
+        let names: HashSet<String> = graph.read().await.all_nodes().map(|n| n.name.clone()).collect();
+        assert!(names.contains("main") && !names.contains("load"));
+
+        // Every diff broadcast is journaled, the removal's too
+        let journal = journal.read().await;
+        let sequences: Vec<u64> = journal.since(0).unwrap().iter().map(|d| d.sequence).collect();
+        assert_eq!(sequences, [1, 2, 3, 4]);
+    }
+
+    #[tokio::test]
+    async fn test_registered_extensions_watched() {
+        let temp_dir = TempDir::new().unwrap();
+        let graph = Arc::new(RwLock::new(Graph::new()));
+        let service = WatcherService::new(temp_dir.path(), Arc::clone(&graph)).unwrap();
+        let names = async || graph.read().await.all_nodes().map(|n| n.name.clone()).collect::<HashSet<_>>();
+
+        // A project's own mapping is followed on every change, as it was
+        // by the startup index
+        canopy_indexer::languages::registry::map_pattern("*.rstmpl", "rust").unwrap();
+        let path = temp_dir.path().join("handlers.rstmpl");
+        std::fs::write(&path, "fn route() {}\n").unwrap();
+        service.handle_file_change(&path).await.unwrap();
+        assert!(names().await.contains("route"));
+        std::fs::write(&path, "fn dispatch() {}\n").unwrap();
+        service.handle_file_change(&path).await.unwrap();
+        let after = names().await;
+        assert!(after.contains("dispatch") && !after.contains("route"));
+
+        // And a removal drops them, rather than leaving them behind
+        std::fs::remove_file(&path).unwrap();
+        service.handle_file_removal(&path).await.unwrap();
+        assert!(!names().await.contains("dispatch"));
+
+        // Files in no format Canopy knows are still left alone
+        let image = temp_dir.path().join("logo.png");
+        std::fs::write(&image, [0x89, b'P', b'N', b'G']).unwrap();
+        service.handle_file_change(&image).await.unwrap();
+        assert!(graph.read().await.all_nodes().all(|n| n.file_path != image));
     }
 }
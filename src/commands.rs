@@ -1,10 +1,21 @@
 //! CLI command implementations
 
-use canopy_core::{Graph, Language};
-use canopy_ai::providers::create_provider;
+use canopy_core::{Graph, Language, NodeId, add_workspace_nodes, discover_workspace};
+use canopy_ai::{AIProvider, AnalysisCache, ReviewQueue, SemanticConfig};
+use canopy_ai::embedding::{EmbeddingIndex, OpenAIEmbedder};
+use canopy_ai::providers::openrouter::{OpenRouterOptions, ProviderPreferences};
+use canopy_ai::providers::{create_provider_with, default_provider_name, ProviderOptions};
+use canopy_ai::budget::ModelPrice;
+use canopy_ai::providers::limit::RateLimits;
+use canopy_ai::redact::{SensitiveFiles, DEFAULT_SENSITIVE_FILES};
+use canopy_indexer::coordinator::{Coordinator, IndexReport};
+use canopy_indexer::project::ProjectConfig;
+use canopy_indexer::tree_cache::ParseTreeCache;
+use canopy_indexer::walk::walker;
 use canopy_server::{CanopyServer, ServerConfig, ServerState};
 use canopy_watcher::WatcherService;
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 pub async fn serve(root: PathBuf, host: String, port: u16, _open: bool) -> anyhow::Result<()> {
@@ -12,7 +23,31 @@ pub async fn serve(root: PathBuf, host: String, port: u16, _open: bool) -> anyho
     
     // Build initial graph
     let mut graph = Graph::new();
-    walk_filesystem(&root, &mut graph)?;
+    let root_id = walk_filesystem(&root, &mut graph)?;
+    match discover_workspace(&root) {
+        Ok(Some(workspace)) => {
+            tracing::info!("Detected {:?} workspace with {} packages", workspace.kind, workspace.packages.len());
+            add_workspace_nodes(&mut graph, &workspace, Some(root_id));
+        }
+        Ok(None) => {}
+        Err(e) => tracing::warn!("Failed to read workspace manifests: {}", e),
+    }
+    
+    // Extract symbols from every file, one worker per core, as the
+    // project's settings ask; unchanged files come from the tree cache
+    let project = ProjectConfig::load(&root)?;
+    let semantic = SemanticConfig::load(&root)?;
+    project.register_extractors()?;
+    let workers = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(2);
+    let mut coordinator = Coordinator::new()
+        .with_skip_rules(project.skip_rules())
+        .with_extraction(project.extract)
+        .with_tree_cache(ParseTreeCache::open(&root));
+    let report = coordinator.run_full_index(&mut graph, workers);
+    for (path, error) in &report.failed {
+        tracing::debug!("Skipped {}: {}", path.display(), error);
+    }
+    graph.recompute_rollups();
     
     tracing::info!("Indexed {} nodes, {} edges", graph.node_count(), graph.edge_count());
     
@@ -20,12 +55,17 @@ pub async fn serve(root: PathBuf, host: String, port: u16, _open: bool) -> anyho
     let config = ServerConfig { host, port };
     let server = CanopyServer::new(graph, config);
     let state = server.state();
+    state.set_root(root.clone()).await;
+    state.set_sensitive_files(sensitive_files()).await;
+    if let Ok(token) = std::env::var("CANOPY_API_TOKEN") {
+        state.set_api_token(token).await;
+    }
     
     // Start file watcher in background task
     let watcher_root = root.clone();
     let watcher_state = Arc::clone(&state);
     tokio::spawn(async move {
-        if let Err(e) = run_watcher(watcher_root, watcher_state).await {
+        if let Err(e) = run_watcher(watcher_root, watcher_state, coordinator, report, semantic).await {
             tracing::error!("File watcher error: {}", e);
         }
     });
@@ -34,19 +74,141 @@ pub async fn serve(root: PathBuf, host: String, port: u16, _open: bool) -> anyho
     server.start().await
 }
 
+/// The rate limits set in the environment, for each of the named
+/// providers.
+fn ai_rate_limits(provider_names: &str) -> HashMap<String, RateLimits> {
+    let per_minute = |var: &str| std::env::var(var).ok().and_then(|value| value.parse().ok());
+    let limits = RateLimits {
+        requests_per_minute: per_minute("CANOPY_AI_REQUESTS_PER_MINUTE"),
+        tokens_per_minute: per_minute("CANOPY_AI_TOKENS_PER_MINUTE"),
+        ..RateLimits::default()
+    };
+    provider_names.split(',').map(|name| (name.trim().to_string(), limits.clone())).collect()
+}
+
+/// OpenRouter's provider preferences set in the environment:
+/// `CANOPY_AI_OPENROUTER_ORDER=Anthropic,Amazon Bedrock` to try those
+/// first, `CANOPY_AI_OPENROUTER_FALLBACKS=false` to use no others, and
+/// `CANOPY_AI_OPENROUTER_SORT=price` to rank them.
+fn openrouter_options() -> OpenRouterOptions {
+    let order = std::env::var("CANOPY_AI_OPENROUTER_ORDER").unwrap_or_default();
+    OpenRouterOptions {
+        models: Vec::new(),
+        preferences: ProviderPreferences {
+            order: order.split(',').map(str::trim).filter(|p| !p.is_empty()).map(String::from).collect(),
+            allow_fallbacks: std::env::var("CANOPY_AI_OPENROUTER_FALLBACKS").ok().and_then(|f| f.parse().ok()),
+            sort: std::env::var("CANOPY_AI_OPENROUTER_SORT").ok(),
+            ..ProviderPreferences::default()
+        },
+    }
+}
+
+/// Set the token budget, the spend alerts and the price of the chosen
+/// model given in the environment: `CANOPY_AI_TOKEN_BUDGET=500000`,
+/// `CANOPY_AI_SPEND_ALERTS=1,5,20` in dollars, and
+/// `CANOPY_AI_PRICE=0.15,0.60` in dollars per million input and output
+/// tokens.
+async fn configure_ai_spend(state: &ServerState, model: Option<&str>) {
+    let dollars = |value: String| value.split(',').filter_map(|d| d.trim().parse().ok()).collect::<Vec<f64>>();
+    let mut budget = state.ai_budget.write().await;
+    if let Some(tokens) = std::env::var("CANOPY_AI_TOKEN_BUDGET").ok().and_then(|t| t.parse().ok()) {
+        budget.total_tokens = tokens;
+    }
+    if let Ok(alerts) = std::env::var("CANOPY_AI_SPEND_ALERTS") {
+        budget.spend_alerts = dollars(alerts);
+    }
+    if let (Some(model), Ok(price)) = (model, std::env::var("CANOPY_AI_PRICE")) {
+        match dollars(price)[..] {
+            [input, output] => {
+                budget.prices.insert(model.to_string(), ModelPrice { input, output });
+            }
+            _ => tracing::warn!("CANOPY_AI_PRICE should be an input and an output price, e.g. 0.15,0.60"),
+        }
+    }
+}
+
+/// The files kept from AI providers: the defaults, and the globs listed
+/// in `CANOPY_AI_SENSITIVE_FILES`.
+fn sensitive_files() -> SensitiveFiles {
+    let listed = std::env::var("CANOPY_AI_SENSITIVE_FILES").unwrap_or_default();
+    let globs: Vec<&str> = DEFAULT_SENSITIVE_FILES
+        .iter()
+        .copied()
+        .chain(listed.split(',').map(str::trim).filter(|glob| !glob.is_empty()))
+        .collect();
+    SensitiveFiles::new(&globs)
+}
+
+/// How long AI analyses persisted under `.canopy/ai-cache/` are reused.
+const AI_CACHE_TTL: std::time::Duration = std::time::Duration::from_secs(7 * 24 * 60 * 60);
+
 /// Run the file watcher and broadcast changes to WebSocket clients
-async fn run_watcher(root: PathBuf, state: Arc<ServerState>) -> anyhow::Result<()> {
+async fn run_watcher(
+    root: PathBuf,
+    state: Arc<ServerState>,
+    coordinator: Coordinator,
+    report: IndexReport,
+    semantic: SemanticConfig,
+) -> anyhow::Result<()> {
     tracing::info!("Starting file watcher for: {}", root.display());
     
-    // Create watcher service with shared graph and broadcast channel
+    // Create watcher service with shared graph and broadcast channel,
+    // carrying on from the startup index
     let graph = Arc::clone(&state.graph);
-    let mut watcher = WatcherService::with_broadcast(&root, graph, state.diff_tx.clone())?;
+    let mut watcher = WatcherService::with_broadcast(&root, graph, state.diff_tx.clone())?
+        .with_index(coordinator, report.file_nodes)
+        .with_reindex(Arc::clone(&state.reindex))
+        .with_journal(Arc::clone(&state.journal));
 
-    let provider_name = std::env::var("CANOPY_AI_PROVIDER").unwrap_or_else(|_| "local".to_string());
+    // Without a key, nodes named and described alike are related locally
     let api_key = std::env::var("CANOPY_AI_API_KEY").ok();
-    match create_provider(&provider_name, api_key) {
+    let provider_name = std::env::var("CANOPY_AI_PROVIDER")
+        .unwrap_or_else(|_| default_provider_name(api_key.as_deref()).to_string());
+    let options = ProviderOptions {
+        api_key,
+        base_url: std::env::var("CANOPY_AI_BASE_URL").ok(),
+        model: std::env::var("CANOPY_AI_MODEL").ok(),
+        rate_limits: ai_rate_limits(&provider_name),
+        openrouter: openrouter_options(),
+        model_dir: Some(root.join(".canopy").join("models")),
+        max_concurrent: std::env::var("CANOPY_AI_MAX_CONCURRENT").ok().and_then(|n| n.parse().ok()),
+    };
+    // OpenRouter's fallback models are priced as they answer
+    let model = options.model.as_deref().and_then(|models| models.split(',').next());
+    configure_ai_spend(&state, model).await;
+    *state.reviews.write().await = ReviewQueue::open(&root);
+    match create_provider_with(&provider_name, &options) {
         Ok(provider) => {
-            watcher = watcher.with_ai_provider(Arc::from(provider));
+            let provider: Arc<dyn AIProvider> = Arc::from(provider);
+            // A bad key or model shows up now rather than on the first save
+            match provider.health_check().await {
+                Ok(capabilities) => tracing::info!(
+                    "AI provider {} is ready (model: {}, context: {}, tools: {}, streaming: {})",
+                    provider.name(),
+                    capabilities.model.as_deref().unwrap_or("default"),
+                    capabilities.max_context_tokens.map_or_else(|| "unknown".to_string(), |n| format!("{} tokens", n)),
+                    capabilities.supports_tools,
+                    capabilities.supports_streaming,
+                ),
+                Err(err) => tracing::warn!("AI provider {} failed its health check: {}", provider.name(), err),
+            }
+            state.set_ai_provider(Arc::clone(&provider)).await;
+            watcher = watcher
+                .with_ai_provider(provider)
+                .with_ai_budget(Arc::clone(&state.ai_budget))
+                .with_reviews(Arc::clone(&state.reviews))
+                .with_sensitive_files(sensitive_files())
+                .with_semantic_config(semantic)
+                .with_analysis_cache(AnalysisCache::persistent(&root, AI_CACHE_TTL));
+            // Candidates are chosen by local hashing unless an embedding
+            // model is named
+            if let Ok(model) = std::env::var("CANOPY_AI_EMBEDDING_MODEL") {
+                let mut embedder = OpenAIEmbedder::new(options.api_key.clone()).with_model(model);
+                if let Some(base_url) = &options.base_url {
+                    embedder = embedder.with_base_url(base_url);
+                }
+                watcher = watcher.with_embeddings(EmbeddingIndex::new(Box::new(embedder)));
+            }
             tracing::info!("AI provider enabled: {}", provider_name);
         }
         Err(err) => {
@@ -56,6 +218,12 @@ async fn run_watcher(root: PathBuf, state: Arc<ServerState>) -> anyhow::Result<(
     
     // Start watching
     watcher.start_watching().await?;
+
+    // Describe the project's files and directories, bottom-up
+    let summarized = watcher.summarize_architecture().await;
+    if summarized > 0 {
+        tracing::info!("Summarized {} files and directories", summarized);
+    }
     
     // Process events (this runs indefinitely)
     watcher.process_events().await?;
@@ -63,12 +231,11 @@ async fn run_watcher(root: PathBuf, state: Arc<ServerState>) -> anyhow::Result<(
     Ok(())
 }
 
-/// Walk filesystem and build basic directory/file structure
-fn walk_filesystem(root: &PathBuf, graph: &mut Graph) -> anyhow::Result<()> {
-    use std::fs;
-    use std::collections::VecDeque;
-    
-    let mut queue = VecDeque::new();
+/// Walk filesystem and build basic directory/file structure, skipping
+/// files ignored by git or `.canopyignore`.
+/// Returns the ID of the root directory node.
+fn walk_filesystem(root: &Path, graph: &mut Graph) -> anyhow::Result<NodeId> {
+    use std::collections::HashMap;
     
     // Add root directory node
     let root_node = canopy_core::GraphNode {
@@ -78,119 +245,79 @@ fn walk_filesystem(root: &PathBuf, graph: &mut Graph) -> anyhow::Result<()> {
             .and_then(|n| n.to_str())
             .unwrap_or("root")
             .to_string(),
-        qualified_name: String::new(),
-        file_path: root.clone(),
+        qualified_name: canopy_core::IStr::default(),
+        file_path: root.to_path_buf().into(),
         line_start: None,
         line_end: None,
         language: None,
         is_container: true,
         child_count: 0,
         loc: None,
-        metadata: std::collections::HashMap::new(),
+        metadata: canopy_core::NodeMetadata::default(),
     };
     let root_id = graph.add_node(root_node);
-    queue.push_back((root.clone(), root_id));
     
-    while let Some((current_path, parent_id)) = queue.pop_front() {
-        tracing::debug!("Processing directory: {}", current_path.display());
-        
-        let entries = match fs::read_dir(&current_path) {
-            Ok(entries) => entries,
+    // Directories come before their contents, so each parent is known
+    let mut directories = HashMap::from([(root.to_path_buf(), root_id)]);
+    for entry in walker(root).build().skip(1) {
+        let entry = match entry {
+            Ok(entry) => entry,
             Err(e) => {
-                tracing::warn!("Cannot read directory {}: {}", current_path.display(), e);
+                tracing::warn!("Cannot read entry: {}", e);
                 continue;
             }
         };
         
-        for entry in entries {
-            let entry = match entry {
-                Ok(entry) => entry,
-                Err(e) => {
-                    tracing::warn!("Cannot read entry: {}", e);
-                    continue;
-                }
-            };
-            
-            let path = entry.path();
-            let file_name = entry.file_name();
-            
-            // Skip hidden files and .git
-            if file_name.to_string_lossy().starts_with('.') {
-                continue;
-            }
-            
-            if path.is_dir() {
-                // Add directory node
-                let dir_name_str = file_name.to_string_lossy().to_string();
-                let dir_node = canopy_core::GraphNode {
-                    id: canopy_core::NodeId(0),
-                    kind: canopy_core::NodeKind::Directory,
-                    name: dir_name_str.clone(),
-                    qualified_name: dir_name_str.clone(),
-                    file_path: path.clone(),
-                    line_start: None,
-                    line_end: None,
-                    language: None,
-                    is_container: true,
-                    child_count: 0,
-                    loc: None,
-                    metadata: std::collections::HashMap::new(),
-                };
-                let child_id = graph.add_node(dir_node);
-                
-                // Add containment edge
-                let label = format!("contains {}", dir_name_str);
-                let edge = canopy_core::GraphEdge {
-                    id: canopy_core::EdgeId(0), // Will be assigned by graph
-                    source: parent_id,
-                    target: child_id,
-                    kind: canopy_core::EdgeKind::Contains,
-                    edge_source: canopy_core::EdgeSource::Structural,
-                    confidence: 1.0,
-                    label: Some(label),
-                    file_path: None,
-                    line: None,
-                };
-                graph.add_edge(edge);
-                
-                queue.push_back((path, child_id));
-            } else if path.is_file() {
-                // Add file node
-                let language = Language::from_path(&path);
-                let file_name_str = file_name.to_string_lossy().to_string();
-                let file_node = canopy_core::GraphNode {
-                    id: canopy_core::NodeId(0),
-                    kind: canopy_core::NodeKind::File,
-                    name: file_name_str.clone(),
-                    qualified_name: file_name_str.clone(),
-                    file_path: path.clone(),
-                    line_start: None,
-                    line_end: None,
-                    language: Some(language),
-                    is_container: true,
-                    child_count: 0,
-                    loc: None,
-                    metadata: std::collections::HashMap::new(),
-                };
-                let child_id = graph.add_node(file_node);
-                
-                // Add containment edge
-                let label = format!("contains {}", file_name_str);
-                let edge = canopy_core::GraphEdge {
-                    id: canopy_core::EdgeId(0),
-                    source: parent_id,
-                    target: child_id,
-                    kind: canopy_core::EdgeKind::Contains,
-                    edge_source: canopy_core::EdgeSource::Structural,
-                    confidence: 1.0,
-                    label: Some(label),
-                    file_path: None,
-                    line: None,
-                };
-                graph.add_edge(edge);
-            }
+        let path = entry.path();
+        let Some(&parent_id) = path.parent().and_then(|p| directories.get(p)) else {
+            continue;
+        };
+        let file_name_str = entry.file_name().to_string_lossy().to_string();
+        let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+        if !is_dir && !entry.file_type().is_some_and(|t| t.is_file()) {
+            continue;
+        }
+        
+        let (kind, language) = if is_dir {
+            tracing::debug!("Processing directory: {}", path.display());
+            (canopy_core::NodeKind::Directory, None)
+        } else {
+            (canopy_core::NodeKind::File, Some(Language::from_path(path)))
+        };
+        let node = canopy_core::GraphNode {
+            id: canopy_core::NodeId(0),
+            kind,
+            name: file_name_str.clone(),
+            qualified_name: file_name_str.as_str().into(),
+            file_path: path.into(),
+            line_start: None,
+            line_end: None,
+            language,
+            is_container: true,
+            child_count: 0,
+            loc: None,
+            metadata: canopy_core::NodeMetadata::default(),
+        };
+        let child_id = graph.add_node(node);
+        if is_dir {
+            directories.insert(path.to_path_buf(), child_id);
         }
+        
+        // Add containment edge
+        let label = format!("contains {}", file_name_str);
+        let edge = canopy_core::GraphEdge {
+            id: canopy_core::EdgeId(0), // Will be assigned by graph
+            source: parent_id,
+            target: child_id,
+            kind: canopy_core::EdgeKind::Contains,
+            edge_source: canopy_core::EdgeSource::Structural,
+            confidence: 1.0,
+            label: Some(label),
+            file_path: None,
+            line: None,
+        };
+        graph.add_edge(edge);
     }
     
-    Ok(())
+    Ok(root_id)
 }